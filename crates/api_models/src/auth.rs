@@ -0,0 +1,295 @@
+//! Users, roles and access tokens.
+//!
+//! Access control is off until it is enabled in `AuthSettings` and an admin
+//! account exists. From then on every request needs a session (a cookie
+//! from `POST /api/auth/login`) or an API token, and what it may do follows
+//! the user's [`Role`]: [`UserCommand::required_role`] decides for the
+//! WebSocket commands, the server's routes for everything else. [`User`] and
+//! [`AccessToken`] are what `rsplayer_metadata` stores; clients only ever see
+//! [`UserInfo`] and [`ApiTokenInfo`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumIter, EnumString, IntoStaticStr};
+
+use crate::common::{
+    MetadataCommand, MultiroomCommand, PlayerCommand, PlaylistCommand, QueueCommand, SchedulerCommand, SystemRequest, UserCommand,
+};
+
+/// What a user may do; each role includes everything the lower ones may.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Default, EnumIter, EnumString, IntoStaticStr,
+)]
+pub enum Role {
+    /// Browses the library and sees what plays.
+    #[default]
+    Guest,
+    /// Plays music: queue, playlists, likes, volume, alarms, `/music`.
+    Listener,
+    /// Settings, storage, DSP, library edits, power and user management.
+    Admin,
+}
+
+/// A stored account. The password is kept only as a salted PBKDF2 hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+    pub password_hash: String,
+}
+
+impl User {
+    pub fn from_bytes(value: &[u8]) -> Option<Self> {
+        serde_json::from_slice(value).ok()
+    }
+    pub fn to_json_string_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("User serialization failed!")
+    }
+    pub fn info(&self) -> UserInfo {
+        UserInfo {
+            name: self.name.clone(),
+            role: self.role,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenKind {
+    /// Issued at login, sent back as a cookie; expires.
+    Session,
+    /// Created by the user for scripts and apps; valid until revoked.
+    Api,
+}
+
+/// A session or API token. Only the SHA-256 of the token is stored, so the
+/// token itself is shown once, when it is created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub id: String,
+    pub token_hash: String,
+    pub user: String,
+    pub kind: TokenKind,
+    /// What the user called an API token; empty for sessions.
+    #[serde(default)]
+    pub name: String,
+    pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl AccessToken {
+    pub fn from_bytes(value: &[u8]) -> Option<Self> {
+        serde_json::from_slice(value).ok()
+    }
+    pub fn to_json_string_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("AccessToken serialization failed!")
+    }
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub user: String,
+    pub created: DateTime<Utc>,
+}
+
+/// `GET /api/auth/status`: `user` is `None` when nobody is logged in. While
+/// access control is off everyone is an admin, logged in or not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct AuthStatus {
+    pub enabled: bool,
+    pub user: Option<UserInfo>,
+    /// Whether an admin account exists; access control needs one.
+    pub has_admin: bool,
+}
+
+impl AuthStatus {
+    /// The role the UI should offer controls for; `None` when it has to
+    /// ask for a login first.
+    pub fn role(&self) -> Option<Role> {
+        if self.enabled {
+            self.user.as_ref().map(|user| user.role)
+        } else {
+            Some(Role::Admin)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub name: String,
+    pub password: String,
+}
+
+/// Creates a user, or updates the one with the name; the password is kept
+/// when `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveUserRequest {
+    pub name: String,
+    pub role: Role,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+}
+
+/// The new token, in plain text this one time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub info: ApiTokenInfo,
+    pub token: String,
+}
+
+impl UserCommand {
+    /// The lowest role allowed to send the command. Queries are open to
+    /// guests; anything that changes playback or a user's own collection
+    /// needs a listener; the rest of the system needs an admin.
+    pub const fn required_role(&self) -> Role {
+        match self {
+            Self::Player(cmd) => match cmd {
+                PlayerCommand::QueryCurrentPlayerInfo | PlayerCommand::QueryBookmarks | PlayerCommand::QuerySleepTimer => Role::Guest,
+                _ => Role::Listener,
+            },
+            Self::Queue(cmd) => match cmd {
+                QueueCommand::QueryCurrentSong | QueueCommand::QueryCurrentQueue(_) | QueueCommand::QuerySnapshots => Role::Guest,
+                _ => Role::Listener,
+            },
+            Self::Playlist(cmd) => match cmd {
+                PlaylistCommand::QueryPlaylistItems(..)
+                | PlaylistCommand::QueryAlbumItems(..)
+                | PlaylistCommand::QueryPlaylist
+                | PlaylistCommand::QueryAlbumsByGenre(_)
+                | PlaylistCommand::QueryAlbumsByDecade(_) => Role::Guest,
+                _ => Role::Listener,
+            },
+            Self::Metadata(cmd) => match cmd {
+                MetadataCommand::RescanMetadata(..)
+                | MetadataCommand::UpdateTags(..)
+                | MetadataCommand::UpdateAlbumTags(..)
+                | MetadataCommand::SetAlbumImage(..) => Role::Admin,
+                MetadataCommand::LikeMediaItem(_)
+                | MetadataCommand::DislikeMediaItem(_)
+                | MetadataCommand::SaveRadioStation(_)
+                | MetadataCommand::DeleteRadioStation(_)
+                | MetadataCommand::MoveRadioStation(..)
+                | MetadataCommand::ImportFavoriteRadioStations
+                | MetadataCommand::FindDuplicates(_) => Role::Listener,
+                MetadataCommand::QueryLocalFiles(..)
+                | MetadataCommand::SearchLocalFiles(..)
+                | MetadataCommand::QueryArtists
+                | MetadataCommand::SearchArtists(_)
+                | MetadataCommand::QueryAlbumsByArtist(_)
+                | MetadataCommand::QuerySongsByAlbum(_)
+                | MetadataCommand::QueryFavoriteRadioStations
+                | MetadataCommand::QueryLibraryStats
+                | MetadataCommand::QueryComposers
+                | MetadataCommand::QueryWorksByComposer(_)
+                | MetadataCommand::QueryRecordingsByWork(_)
+                | MetadataCommand::QuerySongsByRecording(_)
+                | MetadataCommand::QueryRadioStations => Role::Guest,
+            },
+            Self::UpdateDsp(_) | Self::Storage(_) => Role::Admin,
+            Self::System(cmd) => match cmd {
+                SystemRequest::QueryCurrentVolume => Role::Guest,
+                SystemRequest::VolUp | SystemRequest::VolDown | SystemRequest::SetVol(_) | SystemRequest::ToggleMute => Role::Listener,
+                SystemRequest::PowerOff
+                | SystemRequest::RestartSystem
+                | SystemRequest::RestartRSPlayer
+                | SystemRequest::SetFirmwarePower(_) => Role::Admin,
+            },
+            Self::Multiroom(cmd) => match cmd {
+                MultiroomCommand::QueryState => Role::Guest,
+                MultiroomCommand::AddToGroup(_) | MultiroomCommand::RemoveFromGroup(_) | MultiroomCommand::LeaveGroup => Role::Listener,
+                MultiroomCommand::AddManualPeer(_) => Role::Admin,
+            },
+            Self::Scheduler(cmd) => match cmd {
+                SchedulerCommand::QuerySchedules => Role::Guest,
+                _ => Role::Listener,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::StorageCommand;
+    use crate::settings::{NetworkMountConfig, NetworkMountType};
+
+    #[test]
+    fn roles_are_ordered() {
+        assert!(Role::Admin > Role::Listener && Role::Listener > Role::Guest);
+    }
+
+    #[test]
+    fn system_and_storage_commands_need_an_admin() {
+        assert_eq!(UserCommand::System(SystemRequest::PowerOff).required_role(), Role::Admin);
+        assert_eq!(UserCommand::System(SystemRequest::SetVol(10)).required_role(), Role::Listener);
+        assert_eq!(UserCommand::System(SystemRequest::QueryCurrentVolume).required_role(), Role::Guest);
+        let mount = NetworkMountConfig {
+            name: "nas".to_owned(),
+            mount_type: NetworkMountType::Smb,
+            server: "nas".to_owned(),
+            share: "music".to_owned(),
+            username: None,
+            password: Some("secret".to_owned()),
+            domain: None,
+            mount_point: None,
+        };
+        assert_eq!(UserCommand::Storage(StorageCommand::Mount(mount)).required_role(), Role::Admin);
+    }
+
+    #[test]
+    fn guests_only_query() {
+        assert_eq!(UserCommand::Queue(QueueCommand::ClearQueue).required_role(), Role::Listener);
+        assert_eq!(
+            UserCommand::Player(PlayerCommand::QueryCurrentPlayerInfo).required_role(),
+            Role::Guest
+        );
+        assert_eq!(
+            UserCommand::Metadata(MetadataCommand::SearchArtists("a".to_owned())).required_role(),
+            Role::Guest
+        );
+        assert_eq!(
+            UserCommand::Metadata(MetadataCommand::LikeMediaItem("a".to_owned())).required_role(),
+            Role::Listener
+        );
+        assert_eq!(
+            UserCommand::Metadata(MetadataCommand::FindDuplicates(true)).required_role(),
+            Role::Listener
+        );
+        assert_eq!(
+            UserCommand::Metadata(MetadataCommand::RescanMetadata(String::new(), true)).required_role(),
+            Role::Admin
+        );
+    }
+
+    #[test]
+    fn status_asks_for_a_login_only_when_enabled() {
+        assert_eq!(AuthStatus::default().role(), Some(Role::Admin));
+        let enabled = AuthStatus {
+            enabled: true,
+            ..AuthStatus::default()
+        };
+        assert_eq!(enabled.role(), None);
+    }
+}
@@ -10,8 +10,8 @@
 use std::time::Duration;
 
 use crate::{
-    player::Song,
-    settings::{DspSettings, NetworkMountConfig},
+    player::{RadioStation, Song, TagFields},
+    settings::{DspSettings, NetworkMountConfig, Schedule},
     state::CurrentQueueQuery,
 };
 use chrono::{DateTime, Utc};
@@ -36,6 +36,23 @@ pub enum MetadataLibraryItem {
         id: String,
         year: Option<DateTime<Utc>>,
     },
+    Composer {
+        name: String,
+    },
+    /// Header above the songs of one disc of a multi-disc album.
+    Disc {
+        name: String,
+    },
+    /// A classical work of a composer; `id` is the work id.
+    Work {
+        name: String,
+        id: String,
+    },
+    /// One recording of a work, labelled by its performers and album.
+    Recording {
+        name: String,
+        id: String,
+    },
     Empty,
 }
 
@@ -43,7 +60,12 @@ impl MetadataLibraryItem {
     pub fn get_title(&self) -> String {
         match self {
             MetadataLibraryItem::SongItem(song) => song.get_title(),
-            MetadataLibraryItem::Directory { name } | MetadataLibraryItem::Artist { name } => name.clone(),
+            MetadataLibraryItem::Directory { name }
+            | MetadataLibraryItem::Artist { name }
+            | MetadataLibraryItem::Composer { name }
+            | MetadataLibraryItem::Disc { name }
+            | MetadataLibraryItem::Work { name, .. }
+            | MetadataLibraryItem::Recording { name, .. } => name.clone(),
             MetadataLibraryItem::Album { name, year, .. } => year
                 .as_ref()
                 .map_or_else(|| name.clone(), |year| format!("{name} ({})", year.format("%Y"))),
@@ -53,7 +75,10 @@ impl MetadataLibraryItem {
     pub fn get_id(&self) -> String {
         match self {
             MetadataLibraryItem::Directory { name } => format!("{name}/"),
-            MetadataLibraryItem::Artist { name } => name.to_owned(),
+            MetadataLibraryItem::Artist { name } | MetadataLibraryItem::Composer { name } | MetadataLibraryItem::Disc { name } => {
+                name.to_owned()
+            }
+            MetadataLibraryItem::Work { id, .. } | MetadataLibraryItem::Recording { id, .. } => id.to_owned(),
             MetadataLibraryItem::Album { id, name, .. } => {
                 if id.is_empty() {
                     name.to_owned()
@@ -132,6 +157,17 @@ pub enum UserCommand {
     Storage(StorageCommand),
     System(SystemRequest),
     Multiroom(MultiroomCommand),
+    Scheduler(SchedulerCommand),
+}
+
+/// Alarms and timed playback starts, kept in `Settings::schedules`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SchedulerCommand {
+    QuerySchedules,
+    /// Add the schedule, or replace the one with its id.
+    SaveSchedule(Schedule),
+    DeleteSchedule(String),
+    SetScheduleEnabled(String, bool),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -185,6 +221,9 @@ pub enum StorageCommand {
     QueryMountStatus,
     QueryMusicDirStatus,
     SaveExternalMount(String),
+    /// Write measured loudness into the files as `REPLAYGAIN_*` tags; `true` only
+    /// reports what would change.
+    WriteLoudnessTags(bool),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, EnumString, EnumIter, IntoStaticStr)]
@@ -201,6 +240,23 @@ pub enum PlayerCommand {
     SeekBackward,
     QueryCurrentPlayerInfo,
     TogglePlay,
+    /// Bookmark the current song at the current position under a name.
+    AddBookmark(String),
+    /// Play a bookmarked song from the bookmarked position (by bookmark id).
+    JumpToBookmark(String),
+    DeleteBookmark(String),
+    QueryBookmarks,
+    /// Fade out and stop after the given number of minutes; 0 cancels.
+    SetSleepTimer(u32),
+    /// Fade out and stop when the current song ends.
+    SleepAtEndOfTrack,
+    /// Fade out and stop when the last queued song of the current album ends.
+    SleepAtEndOfAlbum,
+    CancelSleepTimer,
+    QuerySleepTimer,
+    /// Record the playing radio stream to the recordings directory.
+    StartRecording,
+    StopRecording,
 }
 
 pub use wire::PlaybackMode;
@@ -224,6 +280,32 @@ pub enum MetadataCommand {
     DislikeMediaItem(String),
     QueryFavoriteRadioStations,
     QueryLibraryStats,
+    /// Write tags to the song with the given key and update the library.
+    UpdateTags(String, TagFields),
+    /// Write tags to every song of the album with the given id; `title` and
+    /// `track` are per song and ignored.
+    UpdateAlbumTags(String, TagFields),
+    /// Use the stored artwork with the given id for the album with the given
+    /// id; sent by the artwork upload endpoint.
+    SetAlbumImage(String, String),
+    QueryComposers,
+    QueryWorksByComposer(String),
+    /// Recordings of the work with the given id.
+    QueryRecordingsByWork(String),
+    /// Movements of the recording with the given id, in order.
+    QuerySongsByRecording(String),
+    /// Report songs present more than once; `true` also compares the
+    /// acoustic fingerprints taken by the loudness scan.
+    FindDuplicates(bool),
+    QueryRadioStations,
+    /// Add the station, or update it when its id is set.
+    SaveRadioStation(RadioStation),
+    DeleteRadioStation(String),
+    /// Move the station with the given id to the given position.
+    MoveRadioStation(String, usize),
+    /// Save the radio-browser stations liked before the station library
+    /// existed.
+    ImportFavoriteRadioStations,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -234,6 +316,21 @@ pub enum PlaylistCommand {
     QueryPlaylist,
     QueryAlbumsByGenre(String),
     QueryAlbumsByDecade(String),
+    /// Rename the playlist with the given id.
+    RenamePlaylist(String, String),
+    DeletePlaylist(String),
+    /// Copy the playlist with the given id under a new name.
+    DuplicatePlaylist(String, String),
+    /// Set the description and image of the playlist with the given id.
+    UpdatePlaylistDetails(String, Option<String>, Option<String>),
+    /// Append a song (by id) to the playlist with the given id.
+    AddSongToPlaylist(String, String),
+    AddAlbumToPlaylist(String, String),
+    AddDirectoryToPlaylist(String, String),
+    /// Remove the item at the given position.
+    RemovePlaylistItem(String, usize),
+    /// Move the item at the first position to the second position.
+    MovePlaylistItem(String, usize, usize),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -264,9 +361,23 @@ pub enum QueueCommand {
     AddGenreToQueue(String),
     LoadDecadeInQueue(String),
     AddDecadeToQueue(String),
+    /// Replace the queue with the movements of a work or recording (by id),
+    /// in order; a work plays all of its recordings one after another.
+    LoadWorkInQueue(String),
+    AddWorkToQueue(String),
     /// Move the song with the first id to the position of the song with the second id.
     MoveItem(String, String),
     MoveItemAfterCurrent(String),
+    /// Turn auto-continue (append similar songs when the queue runs out) on or off.
+    SetAutoContinue(bool),
+    /// Revert the last queue change.
+    Undo,
+    Redo,
+    /// Save the queue, position and playback mode under a name.
+    SaveSnapshot(String),
+    RestoreSnapshot(String),
+    DeleteSnapshot(String),
+    QuerySnapshots,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -8,8 +8,10 @@
 //! Layout: [`common`] — commands and small shared value types; [`state`] —
 //! `StateChangeEvent`, the one enum broadcast to every WebSocket client;
 //! [`settings`] — the persisted `Settings` tree edited in the UI; [`player`],
-//! [`playlist`], [`stat`] — songs, albums/playlists and library statistics.
+//! [`playlist`], [`stat`] — songs, albums/playlists and library statistics;
+//! [`auth`] — users, roles and access tokens.
 
+pub mod auth;
 pub mod common;
 pub mod player;
 pub mod playlist;
@@ -59,6 +59,16 @@ pub struct Song {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_id: Option<String>,
 
+    /// Every artist credited on the song — the artist tags split at the
+    /// configured separators, then the album artist — while `artist` keeps
+    /// the display string. Empty for songs scanned before credits existed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artists: Vec<String>,
+
+    /// The genre tags split like [`Song::artists`]; `genre` is the display string.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+
     pub tags: HashMap<String, String>,
 
     pub file: String,
@@ -128,6 +138,37 @@ impl Song {
         parse_r128_gain(&self.tags, "R128_ALBUM_GAIN").or_else(|| parse_replaygain(&self.tags, "REPLAYGAIN_ALBUM_GAIN"))
     }
 
+    /// Disc number from `disc` (`2` or `2/3`).
+    #[must_use]
+    pub fn disc_number(&self) -> Option<u32> {
+        self.disc.as_deref().and_then(leading_number)
+    }
+
+    /// Track number from `track` (`7` or `07/12`).
+    #[must_use]
+    pub fn track_number(&self) -> Option<u32> {
+        self.track.as_deref().and_then(leading_number)
+    }
+
+    /// Artists to browse the song under: the credits, or the display artist.
+    #[must_use]
+    pub fn credited_artists(&self) -> Vec<&str> {
+        if self.artists.is_empty() {
+            self.artist.iter().map(String::as_str).collect()
+        } else {
+            self.artists.iter().map(String::as_str).collect()
+        }
+    }
+
+    #[must_use]
+    pub fn credited_genres(&self) -> Vec<&str> {
+        if self.genres.is_empty() {
+            self.genre.iter().map(String::as_str).collect()
+        } else {
+            self.genres.iter().map(String::as_str).collect()
+        }
+    }
+
     #[must_use]
     pub fn all_text(&self) -> String {
         let mut result = String::new();
@@ -163,11 +204,21 @@ impl Song {
             result.push(' ');
             result.push_str(t.as_str());
         }
+        for t in self.artists.iter().chain(&self.genres) {
+            result.push(' ');
+            result.push_str(t.as_str());
+        }
 
         result
     }
 }
 
+fn leading_number(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let end = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
 /// Parse an EBU R128 gain tag stored as a Q7.8 fixed-point integer string (e.g. `"256"` → 1.0 dB).
 fn parse_r128_gain(tags: &HashMap<String, String>, key: &str) -> Option<f64> {
     let value = tags.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v)?;
@@ -185,6 +236,137 @@ fn parse_replaygain(tags: &HashMap<String, String>, key: &str) -> Option<f64> {
         .ok()
 }
 
+/// Tag edits for a song or a whole album. `None` keeps a field as it is, an
+/// empty string removes it. `track` and `disc` may carry a total (`3/12`).
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub struct TagFields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track: Option<String>,
+    pub disc: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
+}
+
+impl TagFields {
+    /// Every editable field of `song`, missing ones as empty strings.
+    #[must_use]
+    pub fn from_song(song: &Song) -> Self {
+        let value = |field: &Option<String>| Some(field.clone().unwrap_or_default());
+        Self {
+            title: value(&song.title),
+            artist: value(&song.artist),
+            album: value(&song.album),
+            album_artist: value(&song.album_artist),
+            track: value(&song.track),
+            disc: value(&song.disc),
+            date: value(&song.date),
+            genre: value(&song.genre),
+            composer: value(&song.composer),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.artist.is_none()
+            && self.album.is_none()
+            && self.album_artist.is_none()
+            && self.track.is_none()
+            && self.disc.is_none()
+            && self.date.is_none()
+            && self.genre.is_none()
+            && self.composer.is_none()
+    }
+
+    /// Sets the edited fields on `song`; track and disc keep only the number,
+    /// as the scanner does.
+    pub fn apply_to(&self, song: &mut Song) {
+        let edits = [
+            (&self.title, &mut song.title),
+            (&self.artist, &mut song.artist),
+            (&self.album, &mut song.album),
+            (&self.album_artist, &mut song.album_artist),
+            (&self.date, &mut song.date),
+            (&self.genre, &mut song.genre),
+            (&self.composer, &mut song.composer),
+        ];
+        for (edit, field) in edits {
+            if let Some(value) = edit {
+                *field = non_empty(value);
+            }
+        }
+        for (edit, field) in [(&self.track, &mut song.track), (&self.disc, &mut song.disc)] {
+            if let Some(value) = edit {
+                *field = non_empty(value.split('/').next().unwrap_or_default());
+            }
+        }
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+/// A named position in a song, for jumping back into long-form audio.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: String,
+    pub name: String,
+    pub song_file: String,
+    pub song_title: String,
+    pub position_secs: u32,
+    pub created: DateTime<Utc>,
+}
+
+impl Bookmark {
+    pub fn from_bytes(value: &[u8]) -> Option<Self> {
+        serde_json::from_slice(value).ok()
+    }
+    pub fn to_json_string_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Bookmark serialization failed!")
+    }
+}
+
+/// An internet radio station saved in the library, playable without the
+/// directory it was found in.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub struct RadioStation {
+    /// Assigned by the server; empty for a station not saved yet.
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitrate_kbps: Option<u32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Place in the station list.
+    #[serde(default)]
+    pub position: usize,
+    /// Station uuid on radio-browser.info, for stations added from there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radio_browser_uuid: Option<String>,
+}
+
+impl RadioStation {
+    pub fn from_bytes(value: &[u8]) -> Option<Self> {
+        serde_json::from_slice(value).ok()
+    }
+    pub fn to_json_string_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("RadioStation serialization failed!")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,4 +492,27 @@ mod tests {
         assert!((track - 3.14).abs() < 1e-9);
         assert!((album - (-1.50)).abs() < 1e-9);
     }
+
+    #[test]
+    fn tag_fields_edit_only_what_is_set() {
+        let mut song = Song {
+            title: Some("Old".to_owned()),
+            artist: Some("Artsit".to_owned()),
+            genre: Some("Rock".to_owned()),
+            ..Default::default()
+        };
+        let fields = TagFields {
+            artist: Some(" Artist ".to_owned()),
+            genre: Some(String::new()),
+            track: Some("3/12".to_owned()),
+            ..Default::default()
+        };
+        fields.apply_to(&mut song);
+        assert_eq!(song.title.as_deref(), Some("Old"));
+        assert_eq!(song.artist.as_deref(), Some("Artist"));
+        assert_eq!(song.genre, None);
+        assert_eq!(song.track.as_deref(), Some("3"));
+        assert!(!fields.is_empty());
+        assert!(TagFields::default().is_empty());
+    }
 }
@@ -1,13 +1,16 @@
-//! Albums, playlists and the composite home-page listing.
+//! Albums, work recordings, playlists and the composite home-page listing.
 //!
 //! [`PlaylistType`] models one row source on the UI's home page: saved
 //! playlists, dynamic ones (most played, liked), album carousels (latest,
 //! recently added) and lazy `GenreHeader`/`DecadeHeader` entries that carry
 //! only a count until the user expands the section.
 
+use std::{collections::BTreeMap, time::Duration};
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::common::{PlaybackMode, dur_to_string};
 use crate::player::Song;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
@@ -26,6 +29,49 @@ pub struct Album {
     pub released: Option<DateTime<Utc>>,
     pub added: DateTime<Utc>,
     pub song_keys: Vec<String>,
+    /// Every artist credited on the album's songs (see `Song::artists`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artists: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    /// `MusicBrainz` id of the album artist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist_id: Option<String>,
+    /// Disc and track of each song in playing order; `song_keys` follows it.
+    /// Empty for albums stored before tracks were recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<AlbumTrack>,
+    /// Disc subtitles by disc number.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub disc_subtitles: BTreeMap<u32, String>,
+}
+
+/// Position and length of one song of an album.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
+pub struct AlbumTrack {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disc: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<Duration>,
+}
+
+impl AlbumTrack {
+    pub fn of_song(song: &Song) -> Self {
+        Self {
+            key: song.file.clone(),
+            disc: song.disc_number(),
+            track: song.track_number(),
+            time: song.time,
+        }
+    }
+
+    /// Songs without a disc number count as disc 1.
+    pub fn disc_number(&self) -> u32 {
+        self.disc.unwrap_or(1)
+    }
 }
 
 impl Album {
@@ -35,6 +81,95 @@ impl Album {
     pub fn to_json_string_bytes(&self) -> Vec<u8> {
         serde_json::to_vec(self).expect("Album serialization failed!")
     }
+
+    /// Adds or updates a song and puts `song_keys` in disc, then track
+    /// order. Keys stored before tracks were recorded follow the tracks.
+    pub fn add_track(&mut self, track: AlbumTrack) {
+        self.tracks.retain(|t| t.key != track.key);
+        self.tracks.push(track);
+        self.tracks.sort_by(|a, b| {
+            (a.disc_number(), a.track.unwrap_or(u32::MAX), &a.key).cmp(&(b.disc_number(), b.track.unwrap_or(u32::MAX), &b.key))
+        });
+        let untracked: Vec<String> = self
+            .song_keys
+            .drain(..)
+            .filter(|key| !self.tracks.iter().any(|t| &t.key == key))
+            .collect();
+        self.song_keys = self.tracks.iter().map(|t| t.key.clone()).chain(untracked).collect();
+    }
+
+    /// Keeps the songs whose key passes `keep`.
+    pub fn retain_songs(&mut self, keep: impl Fn(&str) -> bool) {
+        self.song_keys.retain(|key| keep(key));
+        self.tracks.retain(|t| keep(&t.key));
+        self.disc_subtitles
+            .retain(|disc, _| self.tracks.iter().any(|t| t.disc_number() == *disc));
+    }
+
+    pub fn disc_count(&self) -> usize {
+        let mut discs: Vec<u32> = self.tracks.iter().map(AlbumTrack::disc_number).collect();
+        discs.dedup();
+        discs.len()
+    }
+
+    /// Summed length of the songs whose length is known.
+    pub fn total_time(&self) -> Duration {
+        self.tracks.iter().filter_map(|t| t.time).sum()
+    }
+
+    /// `Disc 2: Live · 00:41:07` — number, subtitle and length of a disc.
+    pub fn disc_label(&self, disc: u32) -> String {
+        let time: Duration = self.tracks.iter().filter(|t| t.disc_number() == disc).filter_map(|t| t.time).sum();
+        let title = self
+            .disc_subtitles
+            .get(&disc)
+            .map_or_else(|| format!("Disc {disc}"), |subtitle| format!("Disc {disc}: {subtitle}"));
+        format!("{title} · {}", dur_to_string(&time))
+    }
+}
+
+/// One recording of a classical work: its movements on one album, with
+/// the conductor, orchestra or soloists that perform them.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
+pub struct WorkRecording {
+    pub id: String,
+    pub composer: String,
+    pub work: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub performers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    pub song_keys: Vec<String>,
+}
+
+impl WorkRecording {
+    pub fn from_bytes(value: &[u8]) -> Option<Self> {
+        serde_json::from_slice(value).ok()
+    }
+    pub fn to_json_string_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("WorkRecording serialization failed!")
+    }
+
+    /// `Performers — Album (year)`, leaving out what is unknown; the work
+    /// title when neither performers nor album are known.
+    pub fn label(&self) -> String {
+        let mut label = self.performers.join(", ");
+        if let Some(album) = &self.album {
+            if !label.is_empty() {
+                label.push_str(" — ");
+            }
+            label.push_str(album);
+        }
+        if label.is_empty() {
+            label.clone_from(&self.work);
+        }
+        match self.date.as_deref().and_then(|d| d.get(..4)) {
+            Some(year) => format!("{label} ({year})"),
+            None => label,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
@@ -75,6 +210,28 @@ pub struct PlaylistPage {
     pub items: Vec<Song>,
 }
 
+/// A named copy of the playback queue, kept apart from saved playlists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub name: String,
+    /// Empty in snapshot listings; see `song_count`.
+    pub songs: Vec<Song>,
+    #[serde(default)]
+    pub song_count: usize,
+    pub current_index: Option<usize>,
+    pub playback_mode: PlaybackMode,
+    pub created: DateTime<Utc>,
+}
+
+impl QueueSnapshot {
+    pub fn from_bytes(value: &[u8]) -> Option<Self> {
+        serde_json::from_slice(value).ok()
+    }
+    pub fn to_json_string_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("QueueSnapshot serialization failed!")
+    }
+}
+
 impl PlaylistPage {
     pub fn remove_item(&mut self, song_id: &str) {
         self.items.retain(|s| s.file != song_id);
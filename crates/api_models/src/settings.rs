@@ -7,11 +7,14 @@
 //! `alsa_*` names predate cross-platform support and now configure the cpal
 //! output on every OS).
 
+pub use chrono::Weekday;
+use chrono::{Datelike, Days, NaiveDateTime, NaiveTime, Timelike};
 use serde::{Deserialize, Serialize};
 use strum_macros::{EnumIter, EnumString, IntoStaticStr};
 use validator::Validate;
 
 use crate::common::{AudioCard, CardMixer, PcmOutputDevice, VolumeCrtlType};
+use crate::player::Song;
 
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
@@ -55,6 +58,24 @@ pub struct Settings {
     pub multiroom_settings: MultiroomSettings,
     #[serde(default)]
     pub install_method: InstallMethod,
+    #[serde(default)]
+    #[validate(nested)]
+    pub resume_settings: ResumeSettings,
+    #[serde(default)]
+    #[validate(nested)]
+    pub sleep_timer_settings: SleepTimerSettings,
+    /// Alarms and timed playback starts.
+    #[serde(default)]
+    #[validate(nested)]
+    pub schedules: Vec<Schedule>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub stream_output_settings: StreamOutputSettings,
+    #[serde(default)]
+    #[validate(nested)]
+    pub transcode_settings: TranscodeSettings,
+    #[serde(default)]
+    pub auth_settings: AuthSettings,
 }
 
 /// How this rsplayer instance was installed — detected by the server at runtime
@@ -70,6 +91,289 @@ pub enum InstallMethod {
     Unknown,
 }
 
+/// Per-song resume points for long-form audio (audiobooks, DJ mixes, long
+/// classical works): the player remembers where a matching song stopped and
+/// starts it from there next time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct ResumeSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Songs at least this long get resume points.
+    #[serde(default = "ResumeSettings::default_min_duration_minutes")]
+    #[validate(range(min = 1, max = 1440))]
+    pub min_duration_minutes: u32,
+    /// Genres (case-insensitive) whose songs get resume points regardless of length.
+    #[serde(default)]
+    pub genres: Vec<String>,
+    /// Library directories whose songs get resume points regardless of length.
+    #[serde(default)]
+    pub directories: Vec<String>,
+}
+
+impl ResumeSettings {
+    const fn default_min_duration_minutes() -> u32 {
+        30
+    }
+
+    /// Whether `song` should remember and resume its playback position.
+    pub fn applies_to(&self, song: &Song) -> bool {
+        if !self.enabled || song.file.starts_with("http") {
+            return false;
+        }
+        let long_enough = song.time.is_some_and(|t| t.as_secs() >= u64::from(self.min_duration_minutes) * 60);
+        let in_genre = song.genre.as_deref().is_some_and(|genre| {
+            genre
+                .split([';', '/', ','])
+                .any(|g| self.genres.iter().any(|wanted| wanted.trim().eq_ignore_ascii_case(g.trim())))
+        });
+        let in_directory = self.directories.iter().any(|dir| {
+            let dir = dir.trim_end_matches('/');
+            !dir.is_empty() && song.file.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+        });
+        long_enough || in_genre || in_directory
+    }
+}
+
+impl Default for ResumeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_duration_minutes: Self::default_min_duration_minutes(),
+            genres: Vec::new(),
+            directories: Vec::new(),
+        }
+    }
+}
+
+/// How an armed sleep timer ends playback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct SleepTimerSettings {
+    /// Length of the volume fade before playback stops; 0 stops abruptly.
+    #[serde(default = "SleepTimerSettings::default_fade_seconds")]
+    #[validate(range(max = 600))]
+    pub fade_seconds: u32,
+    /// Bring the volume back once playback has stopped. When off the volume
+    /// is set to 0, so playback stays silent until the volume is raised.
+    #[serde(default = "SleepTimerSettings::default_restore_volume")]
+    pub restore_volume: bool,
+}
+
+impl SleepTimerSettings {
+    const fn default_fade_seconds() -> u32 {
+        30
+    }
+
+    const fn default_restore_volume() -> bool {
+        true
+    }
+}
+
+impl Default for SleepTimerSettings {
+    fn default() -> Self {
+        Self {
+            fade_seconds: Self::default_fade_seconds(),
+            restore_volume: Self::default_restore_volume(),
+        }
+    }
+}
+
+/// Formats the server encodes audio to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default, EnumIter, EnumString, IntoStaticStr)]
+pub enum AudioEncoding {
+    #[default]
+    Mp3,
+    Opus,
+    Flac,
+}
+
+impl AudioEncoding {
+    #[must_use]
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/ogg",
+            Self::Flac => "audio/flac",
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+            Self::Flac => "flac",
+        }
+    }
+
+    /// The encoding a `format` query value or a MIME type names.
+    #[must_use]
+    pub fn parse_loose(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mp3" | "audio/mpeg" | "audio/mp3" => Some(Self::Mp3),
+            "opus" | "ogg" | "audio/ogg" | "audio/opus" => Some(Self::Opus),
+            "flac" | "audio/flac" | "audio/x-flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+
+    /// Whether `bitrate_kbps` settings apply to it.
+    #[must_use]
+    pub const fn is_lossy(self) -> bool {
+        !matches!(self, Self::Flac)
+    }
+}
+
+/// Serving what the player plays as an Icecast-style web radio at `/stream`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct StreamOutputSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub encoding: AudioEncoding,
+    /// Bitrate of the MP3 and Opus streams.
+    #[serde(default = "StreamOutputSettings::default_bitrate_kbps")]
+    #[validate(range(min = 32, max = 320))]
+    pub bitrate_kbps: u32,
+    /// Listeners served at once; each one is encoded separately, so this
+    /// bounds the CPU the web radio takes.
+    #[serde(default = "StreamOutputSettings::default_max_listeners")]
+    #[validate(range(min = 1, max = 64))]
+    pub max_listeners: u32,
+}
+
+impl StreamOutputSettings {
+    const fn default_bitrate_kbps() -> u32 {
+        192
+    }
+
+    const fn default_max_listeners() -> u32 {
+        8
+    }
+}
+
+impl Default for StreamOutputSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            encoding: AudioEncoding::default(),
+            bitrate_kbps: Self::default_bitrate_kbps(),
+            max_listeners: Self::default_max_listeners(),
+        }
+    }
+}
+
+/// Transcoding of library tracks for browser playback: `/music` re-encodes a
+/// track when asked for a format, or when no browser can play the original.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct TranscodeSettings {
+    /// Used when the request names no format the server encodes.
+    #[serde(default = "TranscodeSettings::default_encoding")]
+    pub encoding: AudioEncoding,
+    /// Bitrate of MP3 and Opus transcodes.
+    #[serde(default = "TranscodeSettings::default_bitrate_kbps")]
+    #[validate(range(min = 32, max = 320))]
+    pub bitrate_kbps: u32,
+    /// Highest bitrate sent to clients outside the local network, 0 for no
+    /// limit. Above it tracks are transcoded to MP3 or Opus at this rate.
+    #[serde(default)]
+    #[validate(range(max = 320))]
+    pub remote_max_bitrate_kbps: u32,
+}
+
+impl TranscodeSettings {
+    const fn default_encoding() -> AudioEncoding {
+        AudioEncoding::Flac
+    }
+
+    const fn default_bitrate_kbps() -> u32 {
+        192
+    }
+}
+
+impl Default for TranscodeSettings {
+    fn default() -> Self {
+        Self {
+            encoding: Self::default_encoding(),
+            bitrate_kbps: Self::default_bitrate_kbps(),
+            remote_max_bitrate_kbps: 0,
+        }
+    }
+}
+
+/// Access control for the web UI and the API, see [`crate::auth`]. Takes
+/// effect only once an admin account exists, so enabling it cannot lock
+/// everyone out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct AuthSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Origins besides the server's own, like `https://music.example.com`,
+    /// whose pages may call the API and open the WebSocket.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+/// Starts playback at a time of day on the chosen days of the week: a
+/// wake-up alarm, or a timed radio or playlist start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct Schedule {
+    /// Assigned by the server when the schedule is first saved.
+    #[serde(default)]
+    pub id: String,
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+    pub enabled: bool,
+    /// Days it fires on; empty means every day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[validate(range(max = 23))]
+    pub hour: u8,
+    #[validate(range(max = 59))]
+    pub minute: u8,
+    pub action: ScheduledAction,
+    /// Volume playback starts at, in the output's volume units as the volume
+    /// slider; `None` keeps the last saved volume.
+    #[serde(default)]
+    pub volume: Option<u8>,
+    /// Seconds over which the volume rises from zero; 0 starts at full volume.
+    #[serde(default)]
+    #[validate(range(max = 3600))]
+    pub fade_in_secs: u32,
+    /// The user profile that saved it, which its playback runs for.
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduledAction {
+    /// Play the stream with this URL, e.g. a saved radio station's.
+    Radio(String),
+    /// Replace the queue with the playlist with this id and play it.
+    Playlist(String),
+}
+
+impl Schedule {
+    pub fn fires_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Whether the schedule fires in the minute of `now` (local time),
+    /// enabled or not.
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        self.fires_on(now.weekday()) && now.hour() == u32::from(self.hour) && now.minute() == u32::from(self.minute)
+    }
+
+    /// The first time after `now` (local time) the schedule fires, enabled
+    /// or not.
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let time = NaiveTime::from_hms_opt(self.hour.into(), self.minute.into(), 0)?;
+        (0..=7)
+            .filter_map(|days| now.date().checked_add_days(Days::new(days)))
+            .map(|date| date.and_time(time))
+            .find(|at| *at > now && self.fires_on(at.weekday()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
 pub struct MultiroomSettings {
     #[serde(default)]
@@ -147,6 +451,7 @@ impl Default for UiPreferences {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum NormalizationSource {
     /// Prefer file tags (track gain); fall back to `RSPlayer` EBU R128 calculated loudness.
+    /// While an album plays in sequence, album gain (tags, then calculated) takes precedence.
     #[default]
     Auto,
     /// Use `REPLAYGAIN_TRACK_GAIN` or `R128_TRACK_GAIN` from the file, applied as-is.
@@ -155,8 +460,11 @@ pub enum NormalizationSource {
     FileTagsAlbum,
     /// Use `RSPlayer`'s own EBU R128 integrated loudness measurement (original behavior).
     Calculated,
+    /// Use `RSPlayer`'s own EBU R128 loudness of the whole album, gated across all of its tracks.
+    CalculatedAlbum,
 }
 
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
 pub struct RsPlayerSettings {
     pub enabled: bool,
@@ -172,6 +480,11 @@ pub struct RsPlayerSettings {
     #[serde(default = "thread_priority_default_value")]
     #[validate(range(min = 1, max = 99))]
     pub player_threads_priority: u8,
+    /// Seconds of a radio stream read ahead before playback starts (and
+    /// again after the buffer ran dry), covering reconnects.
+    #[serde(default = "radio_prebuffer_secs_default_value")]
+    #[validate(range(max = 30))]
+    pub radio_prebuffer_secs: u32,
     pub alsa_buffer_size: Option<u32>,
     #[serde(default)]
     pub fixed_output_sample_rate: Option<u32>,
@@ -186,6 +499,15 @@ pub struct RsPlayerSettings {
     pub loudness_normalization_target_lufs: f64,
     #[serde(default)]
     pub loudness_normalization_source: NormalizationSource,
+    /// Normalization gain is capped so the measured true peak stays at or
+    /// below this level (dBTP). Not applied while the limiter is on.
+    #[serde(default = "default_true_peak_ceiling")]
+    #[validate(range(min = -6.0, max = 0.0))]
+    pub loudness_normalization_true_peak_ceiling: f64,
+    /// Run a lookahead limiter at the ceiling as the last DSP stage instead
+    /// of capping the gain, for targets louder than the music allows.
+    #[serde(default)]
+    pub loudness_normalization_limiter: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
@@ -329,6 +651,9 @@ const fn ring_buffer_size_default_value() -> usize {
 const fn input_stream_buffer_size_default_value() -> usize {
     10
 }
+const fn radio_prebuffer_secs_default_value() -> u32 {
+    3
+}
 const fn default_dsp_enabled() -> bool {
     false
 }
@@ -338,6 +663,9 @@ const fn default_vu_meter_enabled() -> bool {
 const fn default_normalization_target_lufs() -> f64 {
     -18.0
 }
+const fn default_true_peak_ceiling() -> f64 {
+    -1.0
+}
 
 impl Default for RsPlayerSettings {
     fn default() -> Self {
@@ -346,6 +674,7 @@ impl Default for RsPlayerSettings {
             input_stream_buffer_size_mb: 10,
             ring_buffer_size_ms: 1000,
             player_threads_priority: 40,
+            radio_prebuffer_secs: radio_prebuffer_secs_default_value(),
             alsa_buffer_size: None,
             fixed_output_sample_rate: None,
             dsp_settings: DspSettings::default(),
@@ -353,6 +682,8 @@ impl Default for RsPlayerSettings {
             loudness_normalization_enabled: false,
             loudness_normalization_target_lufs: default_normalization_target_lufs(),
             loudness_normalization_source: NormalizationSource::default(),
+            loudness_normalization_true_peak_ceiling: default_true_peak_ceiling(),
+            loudness_normalization_limiter: false,
         }
     }
 }
@@ -382,6 +713,22 @@ pub struct MetadataStoreSettings {
     #[serde(default, skip_serializing)]
     pub supported_extensions: Vec<String>,
     pub db_path: String,
+    /// Image file names (without extension) looked for next to the audio
+    /// files when a song has no embedded picture, in order of preference.
+    #[serde(default = "MetadataStoreSettings::default_artwork_file_names")]
+    pub artwork_file_names: Vec<String>,
+    /// Separators splitting artist and genre tags into several credits.
+    /// Surrounding spaces are ignored; a separator starting with a letter
+    /// (`feat.`) only matches as a separate word.
+    #[serde(default = "MetadataStoreSettings::default_credit_separators")]
+    pub credit_separators: Vec<String>,
+    /// Radio recordings are written here, one directory per station, and
+    /// scanned with the music directories. Empty disables recording.
+    #[serde(default)]
+    pub recordings_directory: String,
+    /// Start a new recording file at every title change of the station.
+    #[serde(default)]
+    pub split_recordings_on_title: bool,
 }
 
 impl MetadataStoreSettings {
@@ -398,11 +745,35 @@ impl MetadataStoreSettings {
             self.music_directories.clone()
         }
     }
+
+    /// The music directories plus the recordings directory, unless it lies
+    /// inside one of them: every directory the scanner indexes.
+    pub fn library_directories(&self) -> Vec<String> {
+        let mut dirs = self.effective_directories();
+        let recordings = self.recordings_directory.trim();
+        if !recordings.is_empty() && !dirs.iter().any(|dir| std::path::Path::new(recordings).starts_with(dir)) {
+            dirs.push(recordings.to_owned());
+        }
+        dirs
+    }
+
+    fn default_artwork_file_names() -> Vec<String> {
+        ["cover", "folder", "front", "album", "albumart"].map(str::to_owned).to_vec()
+    }
+
+    /// `/` is left out because it is part of names such as `AC/DC`.
+    fn default_credit_separators() -> Vec<String> {
+        [";", " feat. "].map(str::to_owned).to_vec()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
 pub struct PlaybackQueueSetting {
     pub db_path: String,
+    /// When the queue runs out, append similar songs from the library
+    /// instead of stopping.
+    #[serde(default)]
+    pub auto_continue: bool,
 }
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
 pub struct PlaylistSetting {
@@ -450,7 +821,7 @@ impl Default for MetadataStoreSettings {
             supported_extensions: vec![
                 // Lossless
                 "flac", "wav", "aiff", "aif", "ape", // Lossy
-                "mp3", "mp2", "mp1", "m4a", "ogg", "oga", // Lossless containers
+                "mp3", "mp2", "mp1", "aac", "m4a", "ogg", "oga", // Lossless containers
                 "caf", // Matroska / WebM (audio-only containers)
                 "mka", "weba", // DSD
                 "dsf", "dff", // SACD disc image
@@ -460,6 +831,10 @@ impl Default for MetadataStoreSettings {
             .map(std::borrow::ToOwned::to_owned)
             .collect(),
             db_path: "ignored_files.db".to_string(),
+            artwork_file_names: Self::default_artwork_file_names(),
+            credit_separators: Self::default_credit_separators(),
+            recordings_directory: String::new(),
+            split_recordings_on_title: false,
         }
     }
 }
@@ -467,6 +842,7 @@ impl Default for PlaybackQueueSetting {
     fn default() -> Self {
         Self {
             db_path: "queue.db".to_string(),
+            auto_continue: false,
         }
     }
 }
@@ -515,6 +891,81 @@ impl Default for Settings {
             ui_preferences: UiPreferences::default(),
             multiroom_settings: MultiroomSettings::default(),
             install_method: InstallMethod::default(),
+            resume_settings: ResumeSettings::default(),
+            sleep_timer_settings: SleepTimerSettings::default(),
+            schedules: Vec::new(),
+            stream_output_settings: StreamOutputSettings::default(),
+            transcode_settings: TranscodeSettings::default(),
+            auth_settings: AuthSettings::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn song(file: &str, minutes: u64, genre: Option<&str>) -> Song {
+        Song {
+            file: file.to_owned(),
+            time: Some(Duration::from_secs(minutes * 60)),
+            genre: genre.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    fn enabled() -> ResumeSettings {
+        ResumeSettings {
+            enabled: true,
+            genres: vec!["Audiobook".to_owned()],
+            directories: vec!["mixes/".to_owned()],
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn resume_applies_to_long_songs_only_when_enabled() {
+        assert!(!ResumeSettings::default().applies_to(&song("a.flac", 90, None)));
+        assert!(enabled().applies_to(&song("a.flac", 30, None)));
+        assert!(!enabled().applies_to(&song("a.flac", 29, None)));
+        assert!(!enabled().applies_to(&song("http://radio/stream", 90, None)));
+    }
+
+    #[test]
+    fn resume_applies_to_configured_genres_and_directories() {
+        assert!(enabled().applies_to(&song("a.mp3", 5, Some("Spoken; audiobook"))));
+        assert!(enabled().applies_to(&song("mixes/set.mp3", 5, None)));
+        assert!(!enabled().applies_to(&song("mixes2/set.mp3", 5, None)));
+        assert!(!enabled().applies_to(&song("a.mp3", 5, Some("Rock"))));
+    }
+
+    #[test]
+    fn schedule_fires_on_its_days_only() {
+        let alarm = Schedule {
+            id: String::new(),
+            name: "Wake up".to_owned(),
+            enabled: true,
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            hour: 7,
+            minute: 0,
+            action: ScheduledAction::Radio("http://radio/stream".to_owned()),
+            volume: Some(30),
+            fade_in_secs: 60,
+            profile: None,
+        };
+        let at = |day: u32, hour: u32, minute: u32| {
+            chrono::NaiveDate::from_ymd_opt(2026, 10, day)
+                .and_then(|date| date.and_hms_opt(hour, minute, 0))
+                .expect("date")
+        };
+        // 2026-10-16 is a Friday.
+        assert!(alarm.is_due(at(16, 7, 0)));
+        assert!(!alarm.is_due(at(17, 7, 0)));
+        assert_eq!(alarm.next_after(at(16, 6, 59)), Some(at(16, 7, 0)));
+        assert_eq!(alarm.next_after(at(16, 7, 0)), Some(at(19, 7, 0)));
+        let daily = Schedule { days: vec![], ..alarm };
+        assert_eq!(daily.next_after(at(17, 8, 0)), Some(at(18, 7, 0)));
+    }
 }
@@ -12,13 +12,15 @@ use core::option::Option;
 
 use core::time::Duration;
 
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 use crate::common::MetadataLibraryItem;
 use crate::{
     common::{PlaybackMode, Volume},
-    player::Song,
-    playlist::{Album, PlaylistPage, Playlists},
+    player::{Bookmark, RadioStation, Song},
+    playlist::{Album, PlaylistPage, Playlists, QueueSnapshot},
+    settings::Schedule,
     stat::LibraryStats,
 };
 
@@ -78,17 +80,74 @@ pub enum StateChangeEvent {
     NotificationSuccess(String),
     NotificationError(String),
     FavoriteRadioStations(Vec<String>),
+    /// The saved radio stations, in list order.
+    RadioStationsEvent(Vec<RadioStation>),
     PlaybackStateEvent(PlayerState),
     PlaybackModeChangedEvent(PlaybackMode),
+    AutoContinueChangedEvent(bool),
+    /// Saved queue snapshots, without their songs.
+    QueueSnapshotsEvent(Vec<QueueSnapshot>),
+    BookmarksEvent(Vec<Bookmark>),
+    /// The armed sleep timer, or `None` once it is cancelled or has fired.
+    SleepTimerEvent(Option<SleepTimerState>),
+    /// The file the playing radio stream is recorded to, or `None` when
+    /// nothing is recorded.
+    RecordingEvent(Option<String>),
+    /// All schedules, and the enabled one firing next.
+    SchedulesEvent(Vec<Schedule>, Option<NextAlarm>),
     VUEvent(u8, u8),
     VuMeterEnabledEvent(bool),
     RSPlayerFirmwarePowerEvent(bool),
     LibraryStatsEvent(LibraryStats),
     MountStatusEvent(Vec<MountStatus>),
     MusicDirStatusEvent(Vec<MusicDirStatus>),
+    LoudnessTagReportEvent(LoudnessTagReport),
+    DuplicateReportEvent(DuplicateReport),
     ExternalMountsEvent(Vec<ExternalMount>),
     MultiroomPeersEvent(Vec<MultiroomPeer>),
     MultiroomGroupEvent(MultiroomGroupState),
+    /// An event meant only for the sessions of one user profile (`None`
+    /// is the shared profile). The server unwraps it before sending.
+    ProfileEvent(Option<String>, Box<StateChangeEvent>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SleepTimerMode {
+    /// Stop after a fixed number of minutes.
+    Timer,
+    EndOfTrack,
+    EndOfAlbum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SleepTimerState {
+    pub mode: SleepTimerMode,
+    /// Seconds until playback stops; `None` while the song length is unknown.
+    pub remaining_secs: Option<u32>,
+}
+
+/// The enabled schedule firing next.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NextAlarm {
+    pub schedule_id: String,
+    pub name: String,
+    /// Local time it fires at.
+    pub at: NaiveDateTime,
+}
+
+impl NextAlarm {
+    pub fn of(schedules: &[Schedule], now: NaiveDateTime) -> Option<Self> {
+        schedules
+            .iter()
+            .filter(|schedule| schedule.enabled)
+            .filter_map(|schedule| schedule.next_after(now).map(|at| (schedule, at)))
+            .min_by_key(|(_, at)| *at)
+            .map(|(schedule, at)| Self {
+                schedule_id: schedule.id.clone(),
+                name: schedule.name.clone(),
+                at,
+            })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -135,6 +194,75 @@ pub struct MusicDirStatus {
     pub writable: bool,
 }
 
+/// Gain tag values written to (or, in a dry run, planned for) one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct LoudnessTagChange {
+    pub file: String,
+    pub track_gain: String,
+    pub track_peak: String,
+    pub album_gain: Option<String>,
+    pub album_peak: Option<String>,
+}
+
+/// Outcome of a [`crate::common::StorageCommand::WriteLoudnessTags`] run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct LoudnessTagReport {
+    pub dry_run: bool,
+    /// Files written, or that would be written.
+    pub changed: usize,
+    /// Files whose tags already hold the measured values.
+    pub unchanged: usize,
+    pub not_analysed: usize,
+    /// Formats other than FLAC, MP3 and M4A.
+    pub unsupported: usize,
+    /// Files under a music directory that is not writable.
+    pub read_only: usize,
+    /// Files that could not be found or written, with the reason.
+    pub failed: Vec<(String, String)>,
+    /// The first changes, for review; `changed` is the full count.
+    pub changes: Vec<LoudnessTagChange>,
+}
+
+/// One copy of a song in a [`DuplicateGroup`]; properties the file header or
+/// file system could not give are `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct DuplicateFile {
+    pub file: String,
+    /// Codec (`FLAC`, `MP3`…), or the file extension when it cannot be read.
+    pub format: String,
+    /// Average bitrate from the file size and length.
+    pub bitrate_kbps: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub bits_per_sample: Option<u32>,
+    pub size_bytes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+/// Songs found to be copies of one recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct DuplicateGroup {
+    pub artist: String,
+    pub title: String,
+    /// Whether a copy was matched by its fingerprint rather than its tags.
+    pub by_fingerprint: bool,
+    /// Highest bitrate first.
+    pub files: Vec<DuplicateFile>,
+}
+
+/// Outcome of a [`crate::common::MetadataCommand::FindDuplicates`] run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct DuplicateReport {
+    pub use_fingerprints: bool,
+    pub songs_checked: usize,
+    /// Songs with a fingerprint from the loudness scan.
+    pub fingerprinted: usize,
+    /// All groups found; `groups` lists the first of them.
+    pub group_count: usize,
+    /// Copies beyond one per group, across all groups.
+    pub redundant_files: usize,
+    pub groups: Vec<DuplicateGroup>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExternalMount {
     pub source: String,
@@ -5,12 +5,14 @@
 //! reads clone the cache, writes update cache and disk together. One-time
 //! schema migrations (legacy `alsa_mixer` object, single `music_directory`)
 //! run in [`Configuration::new`] when the stored JSON predates the current
-//! model. On first launch (or unreadable stored settings) the caller-provided
-//! platform-aware defaults are persisted instead of `Settings::default()`.
+//! model; the supported extensions, which are not stored, are set to the
+//! current list there. On first launch (or unreadable stored settings) the
+//! caller-provided platform-aware defaults are persisted instead of
+//! `Settings::default()`.
 
 use std::sync::{Arc, RwLock};
 
-use api_models::settings::Settings;
+use api_models::settings::{MetadataStoreSettings, Settings};
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
 
 const SETTINGS_KEY: &str = "settings";
@@ -50,6 +52,9 @@ impl Configuration {
                             settings.metadata_settings.music_directory
                         );
                     }
+                    // Not stored, so settings saved before a format was
+                    // supported (such as "aac") get the current list.
+                    settings.metadata_settings.supported_extensions = MetadataStoreSettings::default().supported_extensions;
                     settings
                 }
                 Err(e) => {
@@ -108,9 +113,12 @@ mod tests {
     }
 
     /// What a settings value looks like after a storage round trip —
-    /// `skip_serializing` fields (e.g. `supported_extensions`) are dropped.
+    /// `skip_serializing` fields are dropped, and `supported_extensions` is
+    /// the current list again.
     fn persisted(settings: &Settings) -> Settings {
-        serde_json::from_slice(&serde_json::to_vec(settings).expect("serialize")).expect("deserialize")
+        let mut persisted: Settings = serde_json::from_slice(&serde_json::to_vec(settings).expect("serialize")).expect("deserialize");
+        persisted.metadata_settings.supported_extensions = MetadataStoreSettings::default().supported_extensions;
+        persisted
     }
 
     #[test]
@@ -143,6 +151,21 @@ mod tests {
         assert_eq!(config.get_settings(), persisted(&stored));
     }
 
+    #[test]
+    fn saved_settings_pick_up_new_extensions() {
+        let tmp = tempfile::TempDir::new().expect("temp dir");
+        let db = open_db(tmp.path());
+        let mut stored = Settings::default();
+        stored.metadata_settings.supported_extensions = vec!["flac".to_owned()];
+        Configuration::new(&db, Settings::default()).save_settings(&stored);
+        drop(db);
+
+        let db = open_db(tmp.path());
+        let config = Configuration::new(&db, first_launch_settings());
+        let extensions = config.get_settings().metadata_settings.supported_extensions;
+        assert!(extensions.iter().any(|ext| ext == "aac"));
+    }
+
     #[test]
     fn unreadable_stored_settings_fall_back_to_persisted_first_launch_settings() {
         let tmp = tempfile::TempDir::new().expect("temp dir");
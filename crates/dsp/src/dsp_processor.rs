@@ -158,6 +158,8 @@ pub struct DspHandle {
     /// Per-song loudness normalization gain in dB.  Set by the playback loop
     /// before each track starts; applied on top of user EQ filters in `rebuild`.
     pub normalization_gain_db: Arc<Mutex<Option<f64>>>,
+    /// Ceiling (dBTP) of the final-stage limiter; `None` when it is off.
+    limiter_ceiling_db: Option<f64>,
 }
 
 impl DspHandle {
@@ -174,6 +176,9 @@ impl DspHandle {
             {
                 warn!("Failed to apply normalization gain: {e}");
             }
+            if let Some(ceiling_db) = self.limiter_ceiling_db {
+                eq.set_limiter(rate, ceiling_db);
+            }
             let active = eq.has_filters();
             if let Ok(mut slot) = self.pending.lock() {
                 *slot = Some(eq);
@@ -198,7 +203,8 @@ pub struct DspProcessor {
 }
 
 impl DspProcessor {
-    pub fn new(dsp_settings: DspSettings) -> Self {
+    /// `limiter_ceiling_db` enables the final-stage limiter at that level.
+    pub fn new(dsp_settings: DspSettings, limiter_ceiling_db: Option<f64>) -> Self {
         Self {
             channels: 0,
             rate: 0,
@@ -207,6 +213,7 @@ impl DspProcessor {
                 has_filters: Arc::new(AtomicBool::new(false)),
                 settings: Arc::new(Mutex::new(dsp_settings)),
                 normalization_gain_db: Arc::new(Mutex::new(None)),
+                limiter_ceiling_db,
             },
         }
     }
@@ -219,6 +226,7 @@ impl DspProcessor {
             has_filters: self.handle.has_filters.clone(),
             settings: self.handle.settings.clone(),
             normalization_gain_db: self.handle.normalization_gain_db.clone(),
+            limiter_ceiling_db: self.handle.limiter_ceiling_db,
         }
     }
 
@@ -238,6 +246,9 @@ impl DspProcessor {
             {
                 warn!("Failed to apply normalization gain: {e}");
             }
+            if let Some(ceiling_db) = self.handle.limiter_ceiling_db {
+                eq.set_limiter(self.rate, ceiling_db);
+            }
             let active = eq.has_filters();
             if let Ok(mut slot) = self.handle.pending.lock() {
                 *slot = Some(eq);
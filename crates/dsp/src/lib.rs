@@ -6,17 +6,20 @@
 //! builds a fresh `Equalizer` whenever settings or per-track normalization
 //! gain change, and parks it in [`DspHandle::pending`]; the playback thread
 //! swaps it in between writes — the audio hot path never waits on a lock
-//! (see `dsp_processor.rs` for the threading contract).
+//! (see `dsp_processor.rs` for the threading contract). An optional
+//! lookahead limiter (`limiter.rs`) runs after all channel filters.
 
 use anyhow::Result;
 use log::error;
 
 mod filters;
+mod limiter;
 pub mod config {
     pub use crate::filters::config::*;
 }
 pub use filters::BiquadParameters;
 use filters::{Biquad, BiquadCoefficients, Filter as CamillaDspFilter, Gain};
+use limiter::Limiter;
 
 use symphonia::core::audio::conv::{FromSample, IntoSample};
 use symphonia::core::audio::sample::Sample;
@@ -31,6 +34,7 @@ pub struct Equalizer {
     filters: Vec<Vec<Box<dyn CamillaDspFilter + Send>>>,
     scratch_buffers: Vec<Vec<f32>>,
     conversion_scratch: Vec<f32>,
+    limiter: Option<Limiter>,
 }
 
 impl Equalizer {
@@ -46,6 +50,7 @@ impl Equalizer {
             filters,
             scratch_buffers,
             conversion_scratch: Vec::new(),
+            limiter: None,
         }
     }
 
@@ -84,15 +89,29 @@ impl Equalizer {
         Ok(())
     }
 
+    /// Limits the output to `ceiling_db` after all other filters.
+    pub fn set_limiter(&mut self, samplerate: usize, ceiling_db: f64) {
+        self.limiter = Some(Limiter::new(self.channels, samplerate, ceiling_db));
+    }
+
+    /// Carries the limiter state of the equalizer this one replaces, so the
+    /// swap between tracks stays gapless (the lookahead is not refilled).
+    pub fn continue_from(&mut self, previous: &mut Self) {
+        if let (Some(limiter), Some(old)) = (&mut self.limiter, previous.limiter.take()) {
+            limiter.continue_from(old);
+        }
+    }
+
     pub fn clear(&mut self) {
         for ch in 0..self.channels {
             self.filters[ch].clear();
         }
+        self.limiter = None;
     }
 
-    /// Returns `true` if any channel has at least one filter configured.
+    /// Returns `true` if any channel has at least one filter configured, or the limiter is on.
     pub fn has_filters(&self) -> bool {
-        self.filters.iter().any(|ch_filters| !ch_filters.is_empty())
+        self.limiter.is_some() || self.filters.iter().any(|ch_filters| !ch_filters.is_empty())
     }
 
     pub fn process_samples<T>(&mut self, samples: &mut [T])
@@ -172,5 +191,31 @@ impl Equalizer {
                 }
             }
         }
+
+        if let Some(limiter) = &mut self.limiter {
+            limiter.process(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuild_mid_stream_keeps_limiter_lookahead() {
+        let mut eq = Equalizer::new(2);
+        eq.set_limiter(48_000, -1.0);
+        let mut first = vec![0.5_f32; 2_000];
+        eq.process(&mut first);
+
+        let mut rebuilt = Equalizer::new(2);
+        rebuilt.add_global_gain_filter(-3.0).unwrap();
+        rebuilt.set_limiter(48_000, -1.0);
+        rebuilt.continue_from(&mut eq);
+        let mut second = vec![0.5_f32; 2_000];
+        rebuilt.process(&mut second);
+
+        assert!(second.iter().all(|s| s.abs() > 0.1), "silent samples after the rebuild");
     }
 }
@@ -0,0 +1,167 @@
+//! Lookahead soft limiter, the optional last stage of an [`crate::Equalizer`].
+//!
+//! Gain reduction follows the loudest channel of each frame, so the stereo
+//! image does not shift, with a soft knee below the ceiling. The required
+//! gain is minimum-filtered over the lookahead window, released
+//! exponentially, then averaged over the same window while the audio is
+//! delayed to match: the gain has already ramped down when a peak arrives,
+//! and no sample leaves above the ceiling. Sample peaks are limited, so
+//! inter-sample peaks may still exceed it slightly.
+
+use std::collections::VecDeque;
+
+const LOOKAHEAD_SECS: f64 = 0.005;
+const RELEASE_SECS: f64 = 0.1;
+/// Width of the soft knee, centred on the ceiling.
+const KNEE_DB: f32 = 2.0;
+
+pub struct Limiter {
+    channels: usize,
+    ceiling_db: f32,
+    knee_start: f32,
+    lookahead: usize,
+    release_coef: f32,
+    /// Interleaved frames, `lookahead` of them; the frame written
+    /// `lookahead - 1` frames ago is read back out.
+    delay: Vec<f32>,
+    /// Required gain of the frames in the window, increasing from front to
+    /// back, keyed by frame number: the front is the window minimum.
+    window_min: VecDeque<(u64, f32)>,
+    released: f32,
+    averaged: Vec<f32>,
+    averaged_sum: f64,
+    pos: usize,
+    frame: u64,
+}
+
+impl Limiter {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    pub fn new(channels: usize, samplerate: usize, ceiling_db: f64) -> Self {
+        let rate = samplerate as f64;
+        let lookahead = ((rate * LOOKAHEAD_SECS) as usize).max(1);
+        let ceiling_db = ceiling_db as f32;
+        Self {
+            channels,
+            ceiling_db,
+            knee_start: db_to_linear(ceiling_db - KNEE_DB / 2.0),
+            lookahead,
+            release_coef: (1.0 - (-1.0 / (rate * RELEASE_SECS)).exp()) as f32,
+            delay: vec![0.0; lookahead * channels],
+            window_min: VecDeque::with_capacity(lookahead + 1),
+            released: 1.0,
+            averaged: vec![1.0; lookahead],
+            averaged_sum: lookahead as f64,
+            pos: 0,
+            frame: 0,
+        }
+    }
+
+    /// Takes over the delay line and gain envelope of `previous` so a rebuilt
+    /// equalizer continues the stream instead of restarting from silence.
+    /// Ignored when the channel count or sample rate changed.
+    pub fn continue_from(&mut self, previous: Self) {
+        if previous.channels != self.channels || previous.lookahead != self.lookahead {
+            return;
+        }
+        self.delay = previous.delay;
+        self.window_min = previous.window_min;
+        self.released = previous.released;
+        self.averaged = previous.averaged;
+        self.averaged_sum = previous.averaged_sum;
+        self.pos = previous.pos;
+        self.frame = previous.frame;
+    }
+
+    /// Limits interleaved `buffer` in place; output lags input by the lookahead.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn process(&mut self, buffer: &mut [f32]) {
+        if self.channels == 0 {
+            return;
+        }
+        for frame in buffer.chunks_exact_mut(self.channels) {
+            let peak = frame.iter().fold(0.0_f32, |max, s| max.max(s.abs()));
+            let required = self.required_gain(peak);
+
+            while self.window_min.back().is_some_and(|&(_, gain)| gain >= required) {
+                self.window_min.pop_back();
+            }
+            self.window_min.push_back((self.frame, required));
+            while self
+                .window_min
+                .front()
+                .is_some_and(|&(n, _)| n + self.lookahead as u64 <= self.frame)
+            {
+                self.window_min.pop_front();
+            }
+            let target = self.window_min.front().map_or(1.0, |&(_, gain)| gain);
+
+            self.released = if target < self.released {
+                target
+            } else {
+                (target - self.released).mul_add(self.release_coef, self.released)
+            };
+            self.averaged_sum += f64::from(self.released) - f64::from(self.averaged[self.pos]);
+            self.averaged[self.pos] = self.released;
+            let gain = (self.averaged_sum / self.lookahead as f64) as f32;
+
+            let write = self.pos * self.channels;
+            self.delay[write..write + self.channels].copy_from_slice(frame);
+            self.pos = (self.pos + 1) % self.lookahead;
+            let read = self.pos * self.channels;
+            for (out, delayed) in frame.iter_mut().zip(&self.delay[read..read + self.channels]) {
+                *out = delayed * gain;
+            }
+            self.frame += 1;
+        }
+    }
+
+    /// Gain that brings `peak` under the ceiling, easing in over the knee.
+    fn required_gain(&self, peak: f32) -> f32 {
+        if peak <= self.knee_start {
+            return 1.0;
+        }
+        let over = 20.0_f32.mul_add(peak.log10(), KNEE_DB / 2.0 - self.ceiling_db);
+        let reduction_db = if over < KNEE_DB {
+            over * over / (2.0 * KNEE_DB)
+        } else {
+            over - KNEE_DB / 2.0
+        };
+        db_to_linear(-reduction_db)
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_stays_under_ceiling() {
+        let mut limiter = Limiter::new(2, 48_000, -1.0);
+        let ceiling = db_to_linear(-1.0);
+        let mut buffer: Vec<f32> = (0..48_000)
+            .flat_map(|i| {
+                let s = (i as f32 * 0.05).sin() * if i % 4_800 < 200 { 2.5 } else { 0.5 };
+                [s, -s * 0.7]
+            })
+            .collect();
+        limiter.process(&mut buffer);
+        let peak = buffer.iter().fold(0.0_f32, |max, s| max.max(s.abs()));
+        assert!(peak <= ceiling * 1.0001, "peak {peak} above ceiling {ceiling}");
+    }
+
+    #[test]
+    fn quiet_signal_passes_delayed_and_unchanged() {
+        let mut limiter = Limiter::new(1, 48_000, -1.0);
+        let input: Vec<f32> = (0..1_000).map(|i| (i as f32 * 0.01).sin() * 0.3).collect();
+        let mut buffer = input.clone();
+        limiter.process(&mut buffer);
+        let lag = limiter.lookahead - 1;
+        for (out, expected) in buffer[lag..].iter().zip(&input) {
+            assert!((out - expected).abs() < 1e-6);
+        }
+    }
+}
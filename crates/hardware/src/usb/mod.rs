@@ -1,9 +1,9 @@
 //! USB serial link to the `RSPlayer` front-panel firmware.
 //!
 //! Speaks the `wire` crate protocol (postcard + COBS frames): a sender
-//! thread mirrors `StateChangeEvent`s (track, progress, VU, mode) to the
-//! panel display, a receiver thread turns panel input (`FwToHost`) into
-//! player/system commands. Reconnects when the device disappears.
+//! thread mirrors `StateChangeEvent`s (track, progress, VU, mode, sleep
+//! timer) to the panel display, a receiver thread turns panel input
+//! (`FwToHost`) into player/system commands. Reconnects when the device disappears.
 
 use anyhow::Result;
 use api_models::{
@@ -351,6 +351,9 @@ fn dispatch_host_to_fw_msg(service: &UsbService, event: StateChangeEvent) {
             *service.last_playback_mode_cache.lock().expect("lock poisoned") = Some(mode);
             let _ = service.send(&HostToFw::PlaybackMode(mode));
         }
+        StateChangeEvent::SleepTimerEvent(timer) => {
+            let _ = service.send(&HostToFw::SleepTimer(timer.map(|t| t.remaining_secs.unwrap_or(0))));
+        }
         _ => {}
     }
 }
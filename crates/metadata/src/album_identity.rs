@@ -0,0 +1,99 @@
+//! Album ids derived from a song's tags.
+//!
+//! A song is filed under its `MUSICBRAINZ_ALBUMID` (`mb:<id>`), else under
+//! the `MusicBrainz` id of its album artist and the album name, else under
+//! [`album_db_key`] — normalized `artist|album`. Songs without an album tag
+//! join the singleton collection of each of their artists instead. These are
+//! plain functions of the song, so services that only need to know which
+//! album a song belongs to do not depend on the album storage.
+
+use api_models::player::Song;
+
+use crate::genre_utils::normalize_name;
+
+/// Key prefix of the per-artist collections of songs without an album tag.
+const SINGLETONS_PREFIX: &str = "__singletons__";
+/// Prefix of album keys made from `MusicBrainz` ids.
+const MUSICBRAINZ_PREFIX: &str = "mb:";
+/// `MusicBrainz` tag names with spaces and underscores removed, so
+/// `MUSICBRAINZ_ALBUMID` and `TXXX:MusicBrainz Album Id` both match.
+const MB_ALBUM_ID: &str = "musicbrainzalbumid";
+const MB_ALBUM_ARTIST_ID: &str = "musicbrainzalbumartistid";
+const MB_ARTIST_ID: &str = "musicbrainzartistid";
+
+pub fn album_db_key(artist: &str, album: &str) -> String {
+    let na = normalize_name(artist);
+    let nb = normalize_name(album);
+    if na.is_empty() { nb } else { format!("{na}|{nb}") }
+}
+
+/// Id of the album `song` is filed under; `None` for songs without an
+/// album tag (those only join their artist's singleton collection).
+pub fn album_id_for_song(song: &Song) -> Option<String> {
+    let album = song.album.as_deref().map(str::trim).filter(|a| !a.is_empty())?;
+    if let Some(album_id) = musicbrainz_id(song, MB_ALBUM_ID) {
+        return Some(format!("{MUSICBRAINZ_PREFIX}{album_id}"));
+    }
+    if let Some(artist_id) = album_artist_id(song) {
+        return Some(format!("{MUSICBRAINZ_PREFIX}{artist_id}|{}", normalize_name(album)));
+    }
+    name_id_for_song(song)
+}
+
+/// Id the album of `song` had before `MusicBrainz` ids were used.
+pub fn name_id_for_song(song: &Song) -> Option<String> {
+    let album = song.album.as_deref().map(str::trim).filter(|a| !a.is_empty())?;
+    let artist = song.album_artist.as_deref().or(song.artist.as_deref()).unwrap_or("");
+    let key = album_db_key(artist, album);
+    (!key.is_empty()).then_some(key)
+}
+
+/// Whether `album_id` is a per-artist collection of songs without an album.
+pub fn is_singletons_id(album_id: &str) -> bool {
+    album_id.starts_with(SINGLETONS_PREFIX)
+}
+
+pub(crate) fn singletons_key(artist: &str) -> String {
+    normalize_name(&format!("{SINGLETONS_PREFIX}{artist}"))
+}
+
+/// Whether a raw tag key holds a `MusicBrainz` id, so the extractor keeps it
+/// in `Song.tags`.
+pub fn is_musicbrainz_tag(raw_key: &str) -> bool {
+    musicbrainz_tag_name(raw_key).starts_with("musicbrainz")
+}
+
+fn musicbrainz_tag_name(raw_key: &str) -> String {
+    raw_key
+        .rsplit(':')
+        .next()
+        .unwrap_or(raw_key)
+        .chars()
+        .filter(|c| *c != ' ' && *c != '_')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// First id of a `MusicBrainz` tag; multi-artist ids come joined by `/` or `;`.
+fn musicbrainz_id(song: &Song, name: &str) -> Option<String> {
+    song.tags
+        .iter()
+        .filter(|(key, _)| musicbrainz_tag_name(key) == name)
+        .find_map(|(_, value)| {
+            let id = value.split(['/', ';', ',']).next()?.trim().to_lowercase();
+            (!id.is_empty()).then_some(id)
+        })
+}
+
+/// `MusicBrainz` id of the album artist; the artist id counts when the song
+/// has no separate album artist.
+pub(crate) fn album_artist_id(song: &Song) -> Option<String> {
+    musicbrainz_id(song, MB_ALBUM_ARTIST_ID).or_else(|| {
+        let has_album_artist = song.album_artist.as_deref().is_some_and(|a| !a.trim().is_empty());
+        if has_album_artist {
+            None
+        } else {
+            musicbrainz_id(song, MB_ARTIST_ID)
+        }
+    })
+}
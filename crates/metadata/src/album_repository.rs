@@ -1,8 +1,17 @@
 //! Fjall-backed [`AlbumRepository`].
 //!
-//! Albums are keyed by [`FjallAlbumRepository::album_db_key`] — normalized
-//! `artist|album` (see `genre_utils::normalize_name`) so tag-case and
-//! diacritic variants of the same album merge into one entry.
+//! Albums are keyed by their `MUSICBRAINZ_ALBUMID` (`mb:<id>`), else by the
+//! `MusicBrainz` id of their album artist and the album name, else by
+//! [`album_db_key`](crate::album_identity::album_db_key) — normalized `artist|album` (see
+//! `genre_utils::normalize_name`) so tag-case and diacritic variants of the
+//! same album merge into one entry. The artist id is also kept on the album,
+//! so artist browsing merges spelling variants of the same artist. Each album
+//! also collects the artist and genre credits of its songs, so artist and
+//! genre browsing lists it under every credited name (recollected from the
+//! stored songs when one leaves the album); songs without an album
+//! join the singleton collection of each of their artists. Songs are kept in
+//! disc, then track order (`Album.tracks`), with disc subtitles from the
+//! `DISCSUBTITLE`/`TSST` tags.
 
 use std::cmp::Reverse;
 
@@ -10,14 +19,23 @@ use chrono::DateTime;
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
 use log::error;
 
-use api_models::{player::Song, playlist::Album};
+use api_models::{
+    player::Song,
+    playlist::{Album, AlbumTrack},
+};
 
+use crate::album_identity::{album_artist_id, album_id_for_song, singletons_key};
 use crate::error::{RepoError, RepoResult};
 use crate::genre_utils::{is_junk_genre, normalize_genre_key, normalize_name, resolve_id3v1_genre, title_case_genre};
 pub use crate::ports::album_repository::{AlbumRepository, ArcAlbumRepository};
 
+/// Disc subtitle tag names (Vorbis/APE, `ID3v2`, MP4 freeform).
+const DISC_SUBTITLE_TAGS: [&str; 3] = ["discsubtitle", "tsst", "setsubtitle"];
+
 pub struct FjallAlbumRepository {
     pub(crate) albums_db: Keyspace,
+    /// The song repository's keyspace, read to recollect album credits.
+    songs_db: Keyspace,
 }
 
 impl FjallAlbumRepository {
@@ -26,6 +44,9 @@ impl FjallAlbumRepository {
             albums_db: db
                 .keyspace("albums", KeyspaceCreateOptions::default)
                 .expect("Failed to open albums keyspace"),
+            songs_db: db
+                .keyspace("songs", KeyspaceCreateOptions::default)
+                .expect("Failed to open songs keyspace"),
         }
     }
 
@@ -36,13 +57,137 @@ impl FjallAlbumRepository {
             albums_db: db
                 .keyspace("albums", KeyspaceCreateOptions::default)
                 .expect("Failed to open albums keyspace"),
+            songs_db: db
+                .keyspace("songs", KeyspaceCreateOptions::default)
+                .expect("Failed to open songs keyspace"),
+        }
+    }
+
+    /// Collects the artist and genre credits of the album's stored songs
+    /// again, dropping those only a removed song had.
+    fn recollect_credits(&self, album: &mut Album) {
+        album.artists.clear();
+        album.genres.clear();
+        for key in &album.song_keys {
+            let Some(song) = self.songs_db.get(key).ok().flatten().and_then(|bytes| Song::bytes_to_song(&bytes)) else {
+                continue;
+            };
+            let known_artist = known_artist(album, &song);
+            add_credits(&mut album.artists, respelled_credits(&song, known_artist.as_deref()));
+            add_credits(&mut album.genres, song.credited_genres());
+        }
+    }
+
+    /// Sets the subtitle of `disc` from the album's tracks on that disc:
+    /// `song`'s own subtitle if it has one, else the first stored song's.
+    fn refresh_disc_subtitle(&self, album: &mut Album, disc: u32, song: Option<&Song>) {
+        let subtitle = song.and_then(disc_subtitle).map(str::to_owned).or_else(|| {
+            album
+                .tracks
+                .iter()
+                .filter(|track| track.disc_number() == disc && song.is_none_or(|song| track.key != song.file))
+                .filter_map(|track| self.songs_db.get(&track.key).ok().flatten())
+                .find_map(|bytes| Song::bytes_to_song(&bytes).as_ref().and_then(disc_subtitle).map(str::to_owned))
+        });
+        match subtitle {
+            Some(subtitle) => album.disc_subtitles.insert(disc, subtitle),
+            None => album.disc_subtitles.remove(&disc),
+        };
+    }
+}
+
+/// Artists whose singleton collection a song without an album joins.
+fn singleton_artists(song: &Song) -> Vec<String> {
+    if song.artists.is_empty() {
+        let effective_artist = song.album_artist.as_deref().or(song.artist.as_deref()).map(str::trim);
+        effective_artist.filter(|s| !s.is_empty()).map(str::to_owned).into_iter().collect()
+    } else {
+        song.artists.clone()
+    }
+}
+
+/// Whether a raw tag key holds a disc subtitle, so the extractor keeps it
+/// in `Song.tags`.
+pub fn is_disc_subtitle_tag(raw_key: &str) -> bool {
+    let name = raw_key.rsplit(':').next().unwrap_or(raw_key).to_lowercase();
+    DISC_SUBTITLE_TAGS.contains(&name.as_str())
+}
+
+fn disc_subtitle(song: &Song) -> Option<&str> {
+    song.tags
+        .iter()
+        .find(|(key, value)| is_disc_subtitle_tag(key) && !value.trim().is_empty())
+        .map(|(_, value)| value.trim())
+}
+
+fn add_track(album: &mut Album, song: &Song) {
+    let track = AlbumTrack::of_song(song);
+    if let Some(subtitle) = disc_subtitle(song) {
+        album.disc_subtitles.insert(track.disc_number(), subtitle.to_owned());
+    }
+    album.add_track(track);
+}
+
+/// The album artist, else the artist, of `song`.
+fn effective_artist(song: &Song) -> Option<&str> {
+    song.album_artist
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .or_else(|| song.artist.as_deref().map(str::trim).filter(|s| !s.is_empty()))
+}
+
+/// The album's artist when `song` credits the same `MusicBrainz` artist:
+/// another spelling of it keeps the one the album is already filed under.
+fn known_artist(album: &Album, song: &Song) -> Option<String> {
+    let artist_id = album_artist_id(song);
+    album.artist.clone().filter(|_| artist_id.is_some() && album.artist_id == artist_id)
+}
+
+fn respelled_credits<'a>(song: &'a Song, known_artist: Option<&'a str>) -> Vec<&'a str> {
+    let mut credits = song.credited_artists();
+    if let (Some(known), Some(effective)) = (known_artist, effective_artist(song)) {
+        for credit in &mut credits {
+            if normalize_name(credit) == normalize_name(effective) {
+                *credit = known;
+            }
+        }
+    }
+    credits
+}
+
+fn add_credits(credits: &mut Vec<String>, names: Vec<&str>) {
+    for name in names {
+        let key = normalize_name(name);
+        if !key.is_empty() && !credits.iter().any(|c| normalize_name(c) == key) {
+            credits.push(name.trim().to_owned());
         }
     }
+}
+
+/// Names an album is listed under in artist browsing; albums stored before
+/// credits existed fall back to their display artist.
+fn credited_artists(album: Album) -> Vec<String> {
+    if album.artists.is_empty() {
+        album.artist.into_iter().collect()
+    } else {
+        album.artists
+    }
+}
+
+/// `MusicBrainz` artist id of each normalized album artist name that has one.
+fn artist_ids_by_name(albums: &[Album]) -> std::collections::HashMap<String, String> {
+    albums
+        .iter()
+        .filter_map(|album| Some((normalize_name(album.artist.as_deref()?), album.artist_id.clone()?)))
+        .collect()
+}
 
-    pub fn album_db_key(artist: &str, album: &str) -> String {
-        let na = normalize_name(artist);
-        let nb = normalize_name(album);
-        if na.is_empty() { nb } else { format!("{na}|{nb}") }
+fn credited_genres(album: &Album) -> Vec<String> {
+    if album.genres.is_empty() {
+        album.genre.iter().cloned().collect()
+    } else {
+        album.genres.clone()
     }
 }
 
@@ -52,11 +197,12 @@ impl AlbumRepository for FjallAlbumRepository {
     }
 
     fn find_all_album_artists(&self) -> Vec<String> {
-        let mut pairs: Vec<(String, String)> = self
-            .find_all()
+        let albums = self.find_all();
+        let ids = artist_ids_by_name(&albums);
+        let mut pairs: Vec<(String, String)> = albums
             .into_iter()
-            .filter_map(|a| {
-                let display = a.artist?;
+            .flat_map(credited_artists)
+            .filter_map(|display| {
                 let key = normalize_name(&display);
                 if key.is_empty() {
                     return None;
@@ -66,6 +212,9 @@ impl AlbumRepository for FjallAlbumRepository {
             .collect();
         pairs.sort_by(|a, b| a.0.cmp(&b.0));
         pairs.dedup_by(|a, b| a.0 == b.0);
+        // Spellings of one MusicBrainz artist are listed once, under the first.
+        let mut seen_ids = std::collections::HashSet::new();
+        pairs.retain(|(key, _)| ids.get(key).is_none_or(|id| seen_ids.insert(id.clone())));
         pairs.into_iter().map(|(_, display)| display).collect()
     }
     fn find_all(&self) -> Vec<Album> {
@@ -76,6 +225,7 @@ impl AlbumRepository for FjallAlbumRepository {
                 let mut album = Album::from_bytes(&value)?;
                 album.id = String::from_utf8(key.to_vec()).ok()?;
                 album.song_keys.clear();
+                album.tracks.clear();
                 Some(album)
             })
             .collect()
@@ -124,17 +274,19 @@ impl AlbumRepository for FjallAlbumRepository {
         let albums = self.find_all();
         let mut genre_map: std::collections::HashMap<String, (String, Vec<Album>)> = std::collections::HashMap::new();
         for album in albums {
-            if let Some(ref raw_genre) = album.genre {
-                let genre_str = resolve_id3v1_genre(raw_genre).map_or_else(|| raw_genre.clone(), String::from);
+            let mut album_keys = Vec::new();
+            for raw_genre in credited_genres(&album) {
+                let genre_str = resolve_id3v1_genre(&raw_genre).map_or(raw_genre, String::from);
                 if genre_str.is_empty() {
                     continue;
                 }
                 let key = normalize_genre_key(&genre_str);
-                if is_junk_genre(&key) {
+                if is_junk_genre(&key) || album_keys.contains(&key) {
                     continue;
                 }
+                album_keys.push(key.clone());
                 let entry = genre_map.entry(key).or_insert_with(|| (title_case_genre(&genre_str), Vec::new()));
-                entry.1.push(album);
+                entry.1.push(album.clone());
             }
         }
         let mut result: Vec<(String, Vec<Album>)> = genre_map
@@ -179,7 +331,8 @@ impl AlbumRepository for FjallAlbumRepository {
 
     fn find_by_artist(&self, artist: &str) -> Vec<Album> {
         let normalized_query = normalize_name(artist);
-        self.albums_db
+        let albums: Vec<Album> = self
+            .albums_db
             .iter()
             .filter_map(|guard| {
                 let (key, value) = guard.into_inner().ok()?;
@@ -187,7 +340,15 @@ impl AlbumRepository for FjallAlbumRepository {
                 album.id = String::from_utf8(key.to_vec()).ok()?;
                 Some(album)
             })
-            .filter(|a| a.artist.as_ref().is_some_and(|a| normalize_name(a) == normalized_query))
+            .collect();
+        let artist_id = artist_ids_by_name(&albums).remove(&normalized_query);
+        albums
+            .into_iter()
+            .filter(|a| {
+                a.artist.as_ref().is_some_and(|a| normalize_name(a) == normalized_query)
+                    || a.artists.iter().any(|a| normalize_name(a) == normalized_query)
+                    || (artist_id.is_some() && a.artist_id == artist_id)
+            })
             .collect()
     }
 
@@ -195,67 +356,59 @@ impl AlbumRepository for FjallAlbumRepository {
         let raw_album = match song.album.as_ref() {
             Some(a) if !a.trim().is_empty() => a.trim().to_owned(),
             _ => {
-                let effective_artist = song
-                    .album_artist
-                    .as_deref()
-                    .or(song.artist.as_deref())
-                    .map(str::trim)
-                    .filter(|s| !s.is_empty());
-                if let Some(artist) = effective_artist {
-                    let key = normalize_name(&format!("__singletons__{artist}"));
+                for artist in singleton_artists(&song) {
+                    let key = singletons_key(&artist);
                     if key.is_empty() {
-                        return Ok(());
+                        continue;
                     }
                     let existing_album = self
                         .albums_db
                         .get(key.as_bytes())
                         .map_err(|e| RepoError::Storage(format!("read singleton '{artist}': {e}")))?;
                     let mut album = existing_album.and_then(|bytes| Album::from_bytes(&bytes)).unwrap_or_default();
-                    if !album.song_keys.contains(&song.file) {
-                        album.song_keys.push(song.file);
-                    }
+                    add_track(&mut album, &song);
                     if song.image_id.is_some() {
-                        album.image_id = song.image_id;
+                        album.image_id.clone_from(&song.image_id);
                     }
-                    album.artist = Some(artist.to_owned());
                     if album.title.is_empty() {
                         album.title = format!("[{artist}]");
                     }
+                    album.artist = Some(artist);
                     album.added = song.file_date;
-                    return self
-                        .albums_db
+                    self.albums_db
                         .insert(key.as_bytes(), album.to_json_string_bytes())
-                        .map_err(|e| RepoError::Storage(format!("write album '{}': {e}", album.title)));
+                        .map_err(|e| RepoError::Storage(format!("write album '{}': {e}", album.title)))?;
                 }
                 return Ok(());
             }
         };
-        let artist_for_key = song.album_artist.as_deref().or(song.artist.as_deref()).unwrap_or("");
-        let key = Self::album_db_key(artist_for_key, &raw_album);
-        if key.is_empty() {
+        let Some(key) = album_id_for_song(&song) else {
             return Ok(());
-        }
+        };
         let existing_album = self
             .albums_db
             .get(key.as_bytes())
             .map_err(|e| RepoError::Storage(format!("read album '{raw_album}': {e}")))?;
         let mut album = existing_album.and_then(|bytes| Album::from_bytes(&bytes)).unwrap_or_default();
 
-        if !album.song_keys.contains(&song.file) {
-            album.song_keys.push(song.file);
-        }
+        add_track(&mut album, &song);
+        self.refresh_disc_subtitle(&mut album, AlbumTrack::of_song(&song).disc_number(), Some(&song));
         if song.image_id.is_some() {
-            album.image_id = song.image_id;
+            album.image_id.clone_from(&song.image_id);
         }
-        let effective_artist = song
-            .album_artist
-            .as_deref()
-            .map(str::trim)
-            .filter(|s| !s.is_empty())
-            .or_else(|| song.artist.as_deref().map(str::trim).filter(|s| !s.is_empty()));
-        if let Some(artist) = effective_artist {
+        let effective_artist = effective_artist(&song);
+        let artist_id = album_artist_id(&song);
+        let known_artist = known_artist(&album, &song);
+        add_credits(&mut album.artists, respelled_credits(&song, known_artist.as_deref()));
+        add_credits(&mut album.genres, song.credited_genres());
+        if known_artist.is_none()
+            && let Some(artist) = effective_artist
+        {
             album.artist = Some(artist.to_owned());
         }
+        if artist_id.is_some() {
+            album.artist_id = artist_id;
+        }
         if let Some(date) = song.date {
             if date.len() == 4
                 && let Ok(dt) = DateTime::parse_from_rfc3339(&format!("{date}-01-01T00:00:00Z"))
@@ -289,16 +442,10 @@ impl AlbumRepository for FjallAlbumRepository {
         let raw_album = match song.album.as_ref() {
             Some(a) if !a.trim().is_empty() => a.trim().to_owned(),
             _ => {
-                let effective_artist = song
-                    .album_artist
-                    .as_deref()
-                    .or(song.artist.as_deref())
-                    .map(str::trim)
-                    .filter(|s| !s.is_empty());
-                if let Some(artist) = effective_artist {
-                    let key = normalize_name(&format!("__singletons__{artist}"));
+                for artist in singleton_artists(song) {
+                    let key = singletons_key(&artist);
                     if key.is_empty() {
-                        return Ok(());
+                        continue;
                     }
                     let existing = self
                         .albums_db
@@ -306,7 +453,7 @@ impl AlbumRepository for FjallAlbumRepository {
                         .map_err(|e| RepoError::Storage(format!("read singleton '{artist}': {e}")))?;
                     if let Some(bytes) = existing {
                         let mut album = Album::from_bytes(&bytes).unwrap_or_default();
-                        album.song_keys.retain(|k| k != &song.file);
+                        album.retain_songs(|k| k != song.file);
                         if album.song_keys.is_empty() {
                             self.albums_db
                                 .remove(key.as_bytes())
@@ -322,11 +469,9 @@ impl AlbumRepository for FjallAlbumRepository {
             }
         };
 
-        let artist_for_key = song.album_artist.as_deref().or(song.artist.as_deref()).unwrap_or("");
-        let key = Self::album_db_key(artist_for_key, &raw_album);
-        if key.is_empty() {
+        let Some(key) = album_id_for_song(song) else {
             return Ok(());
-        }
+        };
 
         let existing = self
             .albums_db
@@ -335,12 +480,14 @@ impl AlbumRepository for FjallAlbumRepository {
 
         if let Some(bytes) = existing {
             let mut album = Album::from_bytes(&bytes).unwrap_or_default();
-            album.song_keys.retain(|k| k != &song.file);
+            album.retain_songs(|k| k != song.file);
             if album.song_keys.is_empty() {
                 self.albums_db
                     .remove(key.as_bytes())
                     .map_err(|e| RepoError::Storage(format!("delete empty album '{key}': {e}")))?;
             } else {
+                self.recollect_credits(&mut album);
+                self.refresh_disc_subtitle(&mut album, AlbumTrack::of_song(song).disc_number(), None);
                 self.albums_db
                     .insert(key.as_bytes(), album.to_json_string_bytes())
                     .map_err(|e| RepoError::Storage(format!("write album '{}': {e}", album.title)))?;
@@ -358,7 +505,7 @@ impl AlbumRepository for FjallAlbumRepository {
                 let (key, value) = guard.into_inner().ok()?;
                 let mut album = Album::from_bytes(&value)?;
                 let original_len = album.song_keys.len();
-                album.song_keys.retain(|k| valid_song_keys.contains(k));
+                album.retain_songs(|k| valid_song_keys.contains(k));
                 if album.song_keys.len() < original_len {
                     Some((key.to_vec(), album))
                 } else {
@@ -384,6 +531,7 @@ impl AlbumRepository for FjallAlbumRepository {
 
 #[cfg(test)]
 mod test {
+    use crate::album_identity::{album_db_key, album_id_for_song, is_singletons_id};
     use crate::album_repository::{AlbumRepository, FjallAlbumRepository};
     use crate::test::test_shared;
     use api_models::playlist::Album;
@@ -539,7 +687,7 @@ mod test {
         let all = repo.find_all();
         assert_eq!(all.len(), 1);
         assert_eq!(all[0].title, "Dark Side of the Moon");
-        let key = FjallAlbumRepository::album_db_key("Pink Floyd", "Dark Side of the Moon");
+        let key = album_db_key("Pink Floyd", "Dark Side of the Moon");
         let full = repo.find_by_id(&key).expect("album not found");
         assert_eq!(full.song_keys.len(), 3);
     }
@@ -647,6 +795,174 @@ mod test {
         assert_eq!(title_case_genre("progressive rock"), "Progressive Rock");
     }
 
+    #[test]
+    fn credits_are_split_on_separators() {
+        use crate::genre_utils::split_credits;
+        let separators = [";", " feat. ", "/"].map(str::to_owned);
+        let values = [
+            "Artist A feat. Artist B".to_owned(),
+            "Artist C; artist b".to_owned(),
+            "Defeat.".to_owned(),
+        ];
+        assert_eq!(split_credits(&values, &separators), ["Artist A", "Artist B", "Artist C", "Defeat."]);
+        assert_eq!(split_credits(&["Rock/Pop".to_owned()], &separators), ["Rock", "Pop"]);
+        assert_eq!(
+            split_credits(&["AC/DC\0Artist A".to_owned()], &[";".to_owned()]),
+            ["AC/DC", "Artist A"]
+        );
+    }
+
+    #[test]
+    fn albums_are_listed_under_every_credit() {
+        use api_models::player::Song;
+        let repo = create_album_repo();
+        repo.update_from_song(Song {
+            file: "a/1.flac".to_owned(),
+            album: Some("Duets".to_owned()),
+            artist: Some("Artist A feat. Artist B".to_owned()),
+            artists: vec!["Artist A".to_owned(), "Artist B".to_owned()],
+            genre: Some("Rock".to_owned()),
+            genres: vec!["Rock".to_owned(), "Pop".to_owned()],
+            file_date: Utc::now(),
+            ..Default::default()
+        })
+        .expect("update_from_song failed");
+        assert_eq!(repo.find_all_album_artists(), ["Artist A", "Artist B"]);
+        assert_eq!(repo.find_by_artist("artist b").len(), 1);
+        let genres = repo.find_all_by_genre(20);
+        assert_eq!(genres.len(), 2);
+        assert!(genres.iter().all(|(_, albums)| albums.len() == 1));
+    }
+
+    #[test]
+    fn removed_song_takes_its_credits_along() {
+        use api_models::player::Song;
+        let repo = create_album_repo();
+        let songs = [("a/1.flac", "Artist A", "Rock"), ("a/2.flac", "Artist B", "Jazz")].map(|(file, artist, genre)| Song {
+            file: file.to_owned(),
+            album: Some("Split".to_owned()),
+            album_artist: Some("Various".to_owned()),
+            artist: Some(artist.to_owned()),
+            genre: Some(genre.to_owned()),
+            file_date: Utc::now(),
+            ..Default::default()
+        });
+        for song in &songs {
+            repo.songs_db.insert(&song.file, song.to_json_string_bytes()).expect("save song");
+            repo.update_from_song(song.clone()).expect("update_from_song failed");
+        }
+        repo.remove_from_song(&songs[1]).expect("remove_from_song failed");
+        let album = &repo.find_all()[0];
+        assert_eq!(album.artists, ["Artist A"]);
+        assert_eq!(album.genres, ["Rock"]);
+    }
+
+    #[test]
+    fn disc_subtitles_follow_the_songs() {
+        use api_models::player::Song;
+        let repo = create_album_repo();
+        let song = |file: &str, subtitle: Option<&str>| {
+            let mut song = Song {
+                file: file.to_owned(),
+                album: Some("Box".to_owned()),
+                artist: Some("Artist".to_owned()),
+                disc: Some("2".to_owned()),
+                file_date: Utc::now(),
+                ..Default::default()
+            };
+            if let Some(subtitle) = subtitle {
+                song.tags.insert("DISCSUBTITLE".to_owned(), subtitle.to_owned());
+            }
+            song
+        };
+        let subtitle = || repo.find_all()[0].disc_subtitles.get(&2).cloned();
+        for song in [song("cd2/01.flac", None), song("cd2/02.flac", Some("Live"))] {
+            repo.songs_db.insert(&song.file, song.to_json_string_bytes()).expect("save song");
+            repo.update_from_song(song).expect("update_from_song failed");
+        }
+        assert_eq!(subtitle().as_deref(), Some("Live"));
+
+        let retagged = song("cd2/02.flac", None);
+        repo.songs_db
+            .insert(&retagged.file, retagged.to_json_string_bytes())
+            .expect("save song");
+        repo.update_from_song(retagged).expect("update_from_song failed");
+        assert_eq!(subtitle(), None);
+
+        let tagged = song("cd2/01.flac", Some("Encore"));
+        repo.songs_db
+            .insert(&tagged.file, tagged.to_json_string_bytes())
+            .expect("save song");
+        repo.update_from_song(tagged.clone()).expect("update_from_song failed");
+        assert_eq!(subtitle().as_deref(), Some("Encore"));
+        repo.remove_from_song(&tagged).expect("remove_from_song failed");
+        assert_eq!(subtitle(), None);
+    }
+
+    #[test]
+    fn musicbrainz_ids_take_precedence_over_names() {
+        use api_models::player::Song;
+        let repo = create_album_repo();
+        let song = |file: &str, album: &str, artist: &str, tag: &str, id: &str| {
+            let mut song = Song {
+                file: file.to_owned(),
+                album: Some(album.to_owned()),
+                artist: Some(artist.to_owned()),
+                file_date: Utc::now(),
+                ..Default::default()
+            };
+            song.tags.insert(tag.to_owned(), id.to_owned());
+            song
+        };
+        for song in [
+            song("a/1.flac", "Greatest Hits", "Queen", "MUSICBRAINZ_ALBUMID", "id-1"),
+            song("b/1.flac", "Greatest Hits", "Queen", "MUSICBRAINZ_ALBUMID", "id-2"),
+            song("c/1.mp3", "Abbey Road", "The Beatles", "TXXX:MusicBrainz Album Artist Id", "B10"),
+            song("c/2.flac", "Abbey Road", "Beatles", "MUSICBRAINZ_ALBUMARTISTID", "b10"),
+        ] {
+            repo.update_from_song(song).expect("update_from_song failed");
+        }
+        assert_eq!(repo.find_all().len(), 3);
+        assert_eq!(repo.find_by_id("mb:id-2").expect("album").song_keys, ["b/1.flac"]);
+        assert_eq!(repo.find_all_album_artists(), ["Queen", "The Beatles"]);
+        let albums = repo.find_by_artist("The Beatles");
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].song_keys.len(), 2);
+    }
+
+    #[test]
+    fn songs_are_ordered_by_disc_then_track() {
+        use api_models::player::Song;
+        use std::time::Duration;
+        let repo = create_album_repo();
+        for (file, disc, track) in [
+            ("cd2/01.flac", "2/2", "1"),
+            ("cd1/02.flac", "1/2", "02/10"),
+            ("cd2/02.flac", "2", "2"),
+            ("cd1/01.flac", "1", "1"),
+        ] {
+            let mut song = Song {
+                file: file.to_owned(),
+                album: Some("Box".to_owned()),
+                artist: Some("Artist".to_owned()),
+                disc: Some(disc.to_owned()),
+                track: Some(track.to_owned()),
+                time: Some(Duration::from_secs(60)),
+                file_date: Utc::now(),
+                ..Default::default()
+            };
+            if disc.starts_with('2') {
+                song.tags.insert("TSST".to_owned(), "Live".to_owned());
+            }
+            repo.update_from_song(song).expect("update_from_song failed");
+        }
+        let album = repo.find_by_id(&album_db_key("Artist", "Box")).expect("album");
+        assert_eq!(album.song_keys, ["cd1/01.flac", "cd1/02.flac", "cd2/01.flac", "cd2/02.flac"]);
+        assert_eq!(album.disc_count(), 2);
+        assert_eq!(album.disc_subtitles.get(&2).map(String::as_str), Some("Live"));
+        assert_eq!(album.total_time(), Duration::from_secs(240));
+    }
+
     #[test]
     fn find_all_by_genre_merges_case_variants() {
         let repo = create_album_repo();
@@ -758,7 +1074,7 @@ mod test {
         })
         .expect("update_from_song failed");
 
-        let key = FjallAlbumRepository::album_db_key("Test Artist", "Test Album");
+        let key = album_db_key("Test Artist", "Test Album");
         let album = repo.find_by_id(&key).expect("album should exist");
         assert_eq!(album.song_keys.len(), 2);
 
@@ -789,7 +1105,7 @@ mod test {
         })
         .expect("update_from_song failed");
 
-        let key = FjallAlbumRepository::album_db_key("Test Artist", "Lone Album");
+        let key = album_db_key("Test Artist", "Lone Album");
         assert!(repo.find_by_id(&key).is_some());
 
         repo.remove_from_song(&Song {
@@ -817,7 +1133,7 @@ mod test {
         })
         .expect("update_from_song failed");
 
-        let key = FjallAlbumRepository::album_db_key("Artist", "Album");
+        let key = album_db_key("Artist", "Album");
         let album = repo.find_by_id(&key).expect("album should exist");
         assert_eq!(album.song_keys.len(), 1);
 
@@ -1025,6 +1341,41 @@ mod test {
         assert_eq!(albums[0].song_keys.len(), 2);
     }
 
+    #[test]
+    fn album_id_for_song_matches_stored_album() {
+        use api_models::player::Song;
+        use chrono::Utc;
+        let repo = create_album_repo();
+        let in_album = Song {
+            file: "a/1.flac".to_string(),
+            album: Some(" Wish You Were Here ".to_string()),
+            artist: Some("Pink Floyd".to_string()),
+            file_date: Utc::now(),
+            ..Default::default()
+        };
+        let single = Song {
+            file: "singles/1.flac".to_string(),
+            album: None,
+            artist: Some("Solo Artist".to_string()),
+            file_date: Utc::now(),
+            ..Default::default()
+        };
+        repo.update_from_song(in_album.clone()).expect("update_from_song failed");
+        repo.update_from_song(single.clone()).expect("update_from_song failed");
+
+        let id = album_id_for_song(&in_album).expect("album id");
+        assert_eq!(repo.find_by_id(&id).expect("album").song_keys, vec!["a/1.flac".to_string()]);
+        assert!(album_id_for_song(&single).is_none());
+
+        let singleton_ids: Vec<String> = repo
+            .find_all()
+            .into_iter()
+            .map(|a| a.id)
+            .filter(|id| is_singletons_id(id))
+            .collect();
+        assert_eq!(singleton_ids.len(), 1);
+    }
+
     #[test]
     fn cleanup_orphaned_albums_removes_stale_keys() {
         use api_models::player::Song;
@@ -1048,7 +1399,7 @@ mod test {
         })
         .expect("update_from_song failed");
 
-        let key = FjallAlbumRepository::album_db_key("Artist", "Album");
+        let key = album_db_key("Artist", "Album");
         let album = repo.find_by_id(&key).expect("album should exist");
         assert_eq!(album.song_keys.len(), 2);
 
@@ -1075,7 +1426,7 @@ mod test {
         })
         .expect("update_from_song failed");
 
-        let key = FjallAlbumRepository::album_db_key("Artist", "Ghost");
+        let key = album_db_key("Artist", "Ghost");
         assert!(repo.find_by_id(&key).is_some());
 
         let valid = HashSet::<String>::new();
@@ -1099,7 +1450,7 @@ mod test {
         })
         .expect("update_from_song failed");
 
-        let key = FjallAlbumRepository::album_db_key("Artist", "Valid Album");
+        let key = album_db_key("Artist", "Valid Album");
         assert!(repo.find_by_id(&key).is_some());
 
         let valid = HashSet::from_iter(["track1.flac".to_string()]);
@@ -0,0 +1,247 @@
+//! Artwork files referenced by `Song.image_id` and `Album.image_id`.
+//!
+//! Each picture is stored twice under its id: re-encoded as JPEG at most
+//! [`FULL_SIZE`] pixels on the long side in `artwork/`, and at
+//! [`THUMBNAIL_SIZE`] in `artwork/thumbs/` for grids and lists. Data the
+//! decoder does not understand is kept as it is in both places, so the
+//! browser can still try. Pictures come from embedded tags, from a folder
+//! image next to the audio files ([`find_folder_image`]) or from an upload.
+//! The id is derived from the picture's bytes, so the same picture embedded
+//! in every track of an album, or found again by a rescan, is stored once.
+//! [`ArtworkStore::collect_garbage`] removes the ones nothing refers to,
+//! sparing pictures saved in the last [`GARBAGE_GRACE`] — an upload is stored
+//! before the command that makes it the album's artwork arrives.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use image::{DynamicImage, codecs::jpeg::JpegEncoder, imageops::FilterType};
+use log::{debug, warn};
+use ring::digest::{SHA256, digest};
+use uuid::Uuid;
+
+pub const ARTWORK_DIR: &str = "artwork";
+pub const THUMBS_DIR: &str = "thumbs";
+const FULL_SIZE: u32 = 1200;
+const THUMBNAIL_SIZE: u32 = 300;
+const JPEG_QUALITY: u8 = 85;
+/// Unreferenced artwork younger than this is kept by garbage collection.
+pub const GARBAGE_GRACE: Duration = Duration::from_hours(1);
+/// Folder images considered by [`find_folder_image`].
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+pub struct ArtworkStore {
+    dir: PathBuf,
+}
+
+impl ArtworkStore {
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Stores a picture under the id of its content and returns the id. A
+    /// picture stored before is not written again, only marked as fresh.
+    pub fn save(&self, data: &[u8]) -> Result<String> {
+        fs::create_dir_all(self.thumbs_dir())?;
+        let id = content_id(data);
+        if self.dir.join(&id).is_file() && self.thumbs_dir().join(&id).is_file() {
+            File::options()
+                .write(true)
+                .open(self.dir.join(&id))?
+                .set_modified(SystemTime::now())?;
+            return Ok(id);
+        }
+        match image::load_from_memory(data) {
+            Ok(image) => {
+                fs::write(self.dir.join(&id), encode(&image, FULL_SIZE)?)?;
+                fs::write(self.thumbs_dir().join(&id), encode(&image, THUMBNAIL_SIZE)?)?;
+            }
+            Err(e) => {
+                debug!("Storing artwork {id} as is, it cannot be decoded: {e}");
+                fs::write(self.dir.join(&id), data)?;
+                fs::write(self.thumbs_dir().join(&id), data)?;
+            }
+        }
+        Ok(id)
+    }
+
+    #[must_use]
+    pub fn exists(&self, id: &str) -> bool {
+        is_artwork_id(id) && self.dir.join(id).is_file()
+    }
+
+    /// Creates the missing thumbnail of artwork stored before thumbnails existed.
+    pub fn ensure_thumbnail(&self, id: &str) -> Result<()> {
+        let thumb = self.thumbs_dir().join(id);
+        if thumb.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(self.thumbs_dir())?;
+        let data = fs::read(self.dir.join(id))?;
+        let thumbnail = match image::load_from_memory(&data) {
+            Ok(image) => encode(&image, THUMBNAIL_SIZE)?,
+            Err(_) => data,
+        };
+        fs::write(thumb, thumbnail)?;
+        Ok(())
+    }
+
+    /// Deletes every picture and thumbnail whose id is not in `referenced`,
+    /// except pictures saved within [`GARBAGE_GRACE`]. Returns the number of
+    /// pictures removed.
+    pub fn collect_garbage(&self, referenced: &HashSet<String>) -> usize {
+        let mut removed = 0;
+        for dir in [self.dir.clone(), self.thumbs_dir()] {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let Some(id) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !is_artwork_id(id) || referenced.contains(id) || !path.is_file() || self.is_fresh(id) {
+                    continue;
+                }
+                match fs::remove_file(&path) {
+                    Ok(()) if dir == self.dir => removed += 1,
+                    Ok(()) => {}
+                    Err(e) => warn!("Failed to remove artwork {}: {e}", path.display()),
+                }
+            }
+        }
+        removed
+    }
+
+    /// Whether the picture `id` was saved within [`GARBAGE_GRACE`].
+    fn is_fresh(&self, id: &str) -> bool {
+        fs::metadata(self.dir.join(id))
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < GARBAGE_GRACE)
+    }
+
+    fn thumbs_dir(&self) -> PathBuf {
+        self.dir.join(THUMBS_DIR)
+    }
+}
+
+/// The image in `dir` named after the first entry of `names` that has one
+/// (`Cover.JPG` matches `cover`), or `None`.
+#[must_use]
+pub fn find_folder_image(dir: &Path, names: &[String]) -> Option<PathBuf> {
+    let mut images: Vec<(String, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+            if !IMAGE_EXTENSIONS.contains(&extension.as_str()) || !path.is_file() {
+                return None;
+            }
+            Some((path.file_stem()?.to_str()?.to_lowercase(), path))
+        })
+        .collect();
+    images.sort();
+    names.iter().find_map(|name| {
+        let name = name.trim().to_lowercase();
+        images.iter().find(|(stem, _)| *stem == name).map(|(_, path)| path.clone())
+    })
+}
+
+/// The first 128 bits of the SHA-256 of `data`, formatted as a UUID so ids
+/// of pictures stored before content ids look the same.
+fn content_id(data: &[u8]) -> String {
+    let hash = digest(&SHA256, data);
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash.as_ref()[..16]);
+    Uuid::from_bytes(bytes).to_string()
+}
+
+/// Artwork files are named by UUID; anything else in the directory is left alone.
+fn is_artwork_id(id: &str) -> bool {
+    Uuid::parse_str(id).is_ok()
+}
+
+fn encode(image: &DynamicImage, max_size: u32) -> Result<Vec<u8>> {
+    let rgb = if image.width() > max_size || image.height() > max_size {
+        image.resize(max_size, max_size, FilterType::Lanczos3).to_rgb8()
+    } else {
+        image.to_rgb8()
+    };
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).encode_image(&rgb)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbImage};
+
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsptest_artwork_{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    #[test]
+    fn folder_image_follows_name_priority() {
+        let dir = temp_dir();
+        for name in ["Folder.PNG", "cover.jpg", "back.jpg", "cover.txt"] {
+            fs::write(dir.join(name), b"x").expect("write");
+        }
+        let names = ["front", "folder", "cover"].map(str::to_owned);
+        assert_eq!(find_folder_image(&dir, &names), Some(dir.join("Folder.PNG")));
+        assert_eq!(find_folder_image(&dir, &names[2..]), Some(dir.join("cover.jpg")));
+        assert_eq!(find_folder_image(&dir, &names[..1]), None);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn saved_artwork_is_resized_and_unreferenced_removed() {
+        let dir = temp_dir();
+        let store = ArtworkStore::new(&dir);
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(2000, 1000))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .expect("encode png");
+        let kept = store.save(&png).expect("save");
+        assert_eq!(store.save(&png).expect("save again"), kept);
+        let dropped = store.save(b"not an image").expect("save raw");
+        let fresh = store.save(b"just uploaded").expect("save upload");
+        fs::write(dir.join("README"), b"keep").expect("write");
+
+        let full = image::load_from_memory(&fs::read(dir.join(&kept)).expect("read")).expect("full size");
+        assert_eq!((full.width(), full.height()), (FULL_SIZE, FULL_SIZE / 2));
+        let thumb = image::load_from_memory(&fs::read(dir.join(THUMBS_DIR).join(&kept)).expect("read")).expect("thumbnail");
+        assert_eq!(thumb.width(), THUMBNAIL_SIZE);
+        assert_eq!(fs::read(dir.join(&dropped)).expect("raw"), b"not an image");
+
+        let old = SystemTime::now() - GARBAGE_GRACE * 2;
+        for path in [dir.join(&kept), dir.join(&dropped)] {
+            File::options()
+                .write(true)
+                .open(path)
+                .expect("open")
+                .set_modified(old)
+                .expect("age");
+        }
+        assert_eq!(store.collect_garbage(&HashSet::from([kept.clone()])), 1);
+        assert!(store.exists(&kept));
+        assert!(!store.exists(&dropped));
+        assert!(store.exists(&fresh));
+        assert!(!dir.join(THUMBS_DIR).join(&dropped).exists());
+        assert!(dir.join("README").exists());
+        fs::remove_dir_all(dir).ok();
+    }
+}
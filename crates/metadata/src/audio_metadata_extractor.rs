@@ -1,6 +1,10 @@
 //! Tag → [`Song`] mapping: pulls Symphonia's standard tags, duration and
 //! embedded artwork out of a probed format reader. First occurrence of a
-//! tag wins; non-standard tags land in `Song.tags`.
+//! tag wins, except that every artist and genre value is also collected in
+//! `Song.artists`/`Song.genres` (split into credits by the scanner);
+//! non-standard tags land in `Song.tags`, as do disc subtitles, MusicBrainz
+//! ids and the work and movement tags of classical music (see
+//! `work_repository`).
 
 use std::time::Duration;
 
@@ -10,6 +14,8 @@ use symphonia::core::{
     meta::StandardTag,
 };
 
+use crate::{album_identity::is_musicbrainz_tag, album_repository::is_disc_subtitle_tag, work_repository::is_work_tag};
+
 pub struct AudioMetadataExtractor;
 
 impl AudioMetadataExtractor {
@@ -48,8 +54,12 @@ impl AudioMetadataExtractor {
                     StandardTag::AlbumArtist(_) if song.album_artist.is_none() => {
                         song.album_artist = Some(Self::tag_value_to_option(tag));
                     }
-                    StandardTag::Artist(_) if song.artist.is_none() => {
-                        song.artist = Some(Self::tag_value_to_option(tag));
+                    StandardTag::Artist(_) => {
+                        let value = Self::tag_value_to_option(tag);
+                        if song.artist.is_none() {
+                            song.artist = Some(value.clone());
+                        }
+                        song.artists.push(value);
                     }
                     StandardTag::Composer(_) if song.composer.is_none() => {
                         song.composer = Some(Self::tag_value_to_option(tag));
@@ -65,8 +75,12 @@ impl AudioMetadataExtractor {
                     StandardTag::DiscNumber(_) if song.disc.is_none() => {
                         song.disc = Some(Self::tag_value_to_option(tag));
                     }
-                    StandardTag::Genre(_) if song.genre.is_none() => {
-                        song.genre = Some(Self::tag_value_to_option(tag));
+                    StandardTag::Genre(_) => {
+                        let value = Self::tag_value_to_option(tag);
+                        if song.genre.is_none() {
+                            song.genre = Some(value.clone());
+                        }
+                        song.genres.push(value);
                     }
                     StandardTag::Label(_) if song.label.is_none() => {
                         song.label = Some(Self::tag_value_to_option(tag));
@@ -89,6 +103,14 @@ impl AudioMetadataExtractor {
                             .entry(tag.raw.key.clone())
                             .or_insert_with(|| Self::tag_value_to_option(tag));
                     }
+                    // Work, movement, conductor and orchestra are kept raw for the works
+                    // index, disc subtitles for the album's disc headers and MusicBrainz
+                    // ids for album and artist identity.
+                    _ if is_work_tag(&tag.raw.key) || is_disc_subtitle_tag(&tag.raw.key) || is_musicbrainz_tag(&tag.raw.key) => {
+                        song.tags
+                            .entry(tag.raw.key.clone())
+                            .or_insert_with(|| Self::tag_value_to_option(tag));
+                    }
                     _ => {}
                 }
             }
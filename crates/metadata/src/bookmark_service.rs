@@ -0,0 +1,80 @@
+//! Resume points and named bookmarks for long-form audio.
+//!
+//! `resume_points` maps a song key to the second playback stopped at (kept
+//! only for songs matching [`api_models::settings::ResumeSettings`]; the
+//! player decides). `bookmarks` holds user-named positions keyed by a uuid,
+//! independent of the resume point and of the queue.
+
+use std::sync::Arc;
+
+use fjall::{Database, Keyspace, KeyspaceCreateOptions};
+use uuid::Uuid;
+
+use api_models::player::{Bookmark, Song};
+
+pub struct BookmarkService {
+    resume_db: Keyspace,
+    bookmark_db: Keyspace,
+}
+
+impl BookmarkService {
+    #[must_use]
+    pub fn new(db: &Database) -> Arc<Self> {
+        let resume_db = db
+            .keyspace("resume_points", KeyspaceCreateOptions::default)
+            .expect("Failed to open resume_points keyspace");
+        let bookmark_db = db
+            .keyspace("bookmarks", KeyspaceCreateOptions::default)
+            .expect("Failed to open bookmarks keyspace");
+        Arc::new(Self { resume_db, bookmark_db })
+    }
+
+    pub fn get_resume_point(&self, song_file: &str) -> Option<u32> {
+        let value = self.resume_db.get(song_file).ok()??;
+        let arr: [u8; 4] = value.as_ref().try_into().ok()?;
+        Some(u32::from_be_bytes(arr))
+    }
+
+    pub fn save_resume_point(&self, song_file: &str, position_secs: u32) {
+        _ = self.resume_db.insert(song_file, position_secs.to_be_bytes());
+    }
+
+    pub fn clear_resume_point(&self, song_file: &str) {
+        _ = self.resume_db.remove(song_file);
+    }
+
+    pub fn add_bookmark(&self, song: &Song, name: &str, position_secs: u32) -> Bookmark {
+        let bookmark = Bookmark {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            song_file: song.file.clone(),
+            song_title: song.get_title(),
+            position_secs,
+            created: chrono::Utc::now(),
+        };
+        _ = self.bookmark_db.insert(bookmark.id.as_str(), bookmark.to_json_string_bytes());
+        bookmark
+    }
+
+    pub fn get_bookmark(&self, id: &str) -> Option<Bookmark> {
+        Bookmark::from_bytes(&self.bookmark_db.get(id).ok()??)
+    }
+
+    pub fn delete_bookmark(&self, id: &str) -> bool {
+        if self.get_bookmark(id).is_none() {
+            return false;
+        }
+        self.bookmark_db.remove(id).is_ok()
+    }
+
+    /// All bookmarks, grouped by song and ordered by position within it.
+    pub fn get_bookmarks(&self) -> Vec<Bookmark> {
+        let mut bookmarks: Vec<Bookmark> = self
+            .bookmark_db
+            .iter()
+            .filter_map(|guard| Bookmark::from_bytes(&guard.value().ok()?))
+            .collect();
+        bookmarks.sort_by(|a, b| a.song_file.cmp(&b.song_file).then(a.position_secs.cmp(&b.position_secs)));
+        bookmarks
+    }
+}
@@ -0,0 +1,270 @@
+//! Duplicate songs, for libraries merged from several shares.
+//!
+//! Songs match when their normalized artist and title are equal and their
+//! lengths differ by at most [`DURATION_TOLERANCE`]. With fingerprints,
+//! songs of about the same length whose fingerprints agree (see
+//! [`crate::fingerprint`]) match too, whatever their tags. Matches join into
+//! groups transitively. Each copy is listed with its codec, bitrate, sample
+//! rate and bit depth read from the file, so the user can decide which one
+//! to keep; nothing is deleted.
+
+use std::{collections::HashMap, fs::File, path::Path, time::Duration};
+
+use log::info;
+use symphonia::core::{
+    codecs::CodecParameters,
+    formats::{FormatOptions, TrackType, probe::Hint},
+    io::{MediaSourceStream, MediaSourceStreamOptions},
+    meta::MetadataOptions,
+};
+
+use api_models::{
+    player::Song,
+    state::{DuplicateFile, DuplicateGroup, DuplicateReport},
+};
+
+use crate::genre_utils::normalize_name;
+use crate::ports::{loudness_repository::ArcLoudnessRepository, song_repository::ArcSongRepository};
+use crate::{build_codec_registry, build_probe, fingerprint};
+
+const DURATION_TOLERANCE: Duration = Duration::from_secs(3);
+/// Share of equal fingerprint bits from which two songs count as copies.
+const FINGERPRINT_THRESHOLD: f64 = 0.85;
+/// Groups listed in a report; the counts cover everything.
+const REPORT_LIMIT: usize = 500;
+
+pub struct DuplicateFinder {
+    song_repository: ArcSongRepository,
+    loudness_repository: ArcLoudnessRepository,
+}
+
+impl DuplicateFinder {
+    #[must_use]
+    pub fn new(song_repository: ArcSongRepository, loudness_repository: ArcLoudnessRepository) -> Self {
+        Self {
+            song_repository,
+            loudness_repository,
+        }
+    }
+
+    /// Groups the copies of each song. `music_dirs` are searched in order
+    /// for the files, whose headers give the reported properties.
+    pub fn run(&self, music_dirs: &[String], use_fingerprints: bool) -> DuplicateReport {
+        let songs = self.song_repository.find_all();
+        let fingerprints: Vec<Option<Vec<u32>>> = if use_fingerprints {
+            songs.iter().map(|s| self.loudness_repository.get_fingerprint(&s.file)).collect()
+        } else {
+            vec![None; songs.len()]
+        };
+        let sets = find_duplicates(&songs, &fingerprints);
+
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for index in 0..songs.len() {
+            members.entry(sets.root(index)).or_default().push(index);
+        }
+        let mut groups: Vec<(usize, Vec<usize>)> = members.into_iter().filter(|(_, m)| m.len() > 1).collect();
+        groups.sort_by_cached_key(|(_, m)| name_key(&songs[m[0]]));
+
+        let report = DuplicateReport {
+            use_fingerprints,
+            songs_checked: songs.len(),
+            fingerprinted: fingerprints.iter().flatten().count(),
+            group_count: groups.len(),
+            redundant_files: groups.iter().map(|(_, m)| m.len() - 1).sum(),
+            groups: groups
+                .into_iter()
+                .take(REPORT_LIMIT)
+                .map(|(root, m)| group(&songs, &m, sets.by_fingerprint[root], music_dirs))
+                .collect(),
+        };
+        info!(
+            "Duplicate report: {} groups, {} redundant files among {} songs ({} fingerprinted)",
+            report.group_count, report.redundant_files, report.songs_checked, report.fingerprinted
+        );
+        report
+    }
+}
+
+/// Union-find over song indexes; a root remembers whether a fingerprint
+/// joined two of its sets.
+struct DisjointSets {
+    parent: Vec<usize>,
+    by_fingerprint: Vec<bool>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            by_fingerprint: vec![false; len],
+        }
+    }
+
+    fn root(&self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            index = self.parent[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize, by_fingerprint: bool) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            self.parent[b] = a;
+            self.by_fingerprint[a] |= self.by_fingerprint[b] || by_fingerprint;
+        }
+    }
+}
+
+fn find_duplicates(songs: &[Song], fingerprints: &[Option<Vec<u32>>]) -> DisjointSets {
+    let mut sets = DisjointSets::new(songs.len());
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (index, song) in songs.iter().enumerate() {
+        let key = name_key(song);
+        if !key.0.is_empty() && !key.1.is_empty() {
+            by_name.entry(key).or_default().push(index);
+        }
+    }
+    for indexes in by_name.into_values() {
+        for_close_lengths(songs, indexes, |a, b| sets.join(a, b, false));
+    }
+
+    let fingerprinted: Vec<usize> = (0..songs.len()).filter(|i| fingerprints[*i].is_some()).collect();
+    for_close_lengths(songs, fingerprinted, |a, b| {
+        if sets.root(a) == sets.root(b) {
+            return;
+        }
+        let (Some(fa), Some(fb)) = (&fingerprints[a], &fingerprints[b]) else {
+            return;
+        };
+        if fingerprint::similarity(fa, fb).is_some_and(|s| s >= FINGERPRINT_THRESHOLD) {
+            sets.join(a, b, true);
+        }
+    });
+    sets
+}
+
+/// Calls `visit` for each pair of `indexes` whose songs' lengths are within
+/// [`DURATION_TOLERANCE`]; songs of unknown length are left out.
+fn for_close_lengths(songs: &[Song], mut indexes: Vec<usize>, mut visit: impl FnMut(usize, usize)) {
+    indexes.retain(|i| songs[*i].time.is_some());
+    indexes.sort_by_key(|i| songs[*i].time);
+    for (position, &a) in indexes.iter().enumerate() {
+        let time_a = songs[a].time.unwrap_or_default();
+        for &b in &indexes[position + 1..] {
+            if songs[b].time.unwrap_or_default().saturating_sub(time_a) > DURATION_TOLERANCE {
+                break;
+            }
+            visit(a, b);
+        }
+    }
+}
+
+fn name_key(song: &Song) -> (String, String) {
+    let artist = song.artist.as_deref().or(song.album_artist.as_deref()).unwrap_or_default();
+    (normalize_name(artist), normalize_name(song.title.as_deref().unwrap_or_default()))
+}
+
+fn group(songs: &[Song], members: &[usize], by_fingerprint: bool, music_dirs: &[String]) -> DuplicateGroup {
+    let first = &songs[members[0]];
+    let mut files: Vec<DuplicateFile> = members.iter().map(|i| file_details(&songs[*i], music_dirs)).collect();
+    files.sort_by_key(|f| std::cmp::Reverse(f.bitrate_kbps));
+    DuplicateGroup {
+        artist: first.artist.clone().or_else(|| first.album_artist.clone()).unwrap_or_default(),
+        title: first.get_title(),
+        by_fingerprint,
+        files,
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+fn file_details(song: &Song, music_dirs: &[String]) -> DuplicateFile {
+    let path = music_dirs.iter().map(|dir| Path::new(dir).join(&song.file)).find(|p| p.exists());
+    let size_bytes = path.as_ref().and_then(|p| p.metadata().ok()).map(|m| m.len());
+    let header = path.as_deref().and_then(read_header);
+    let extension = Path::new(&song.file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_uppercase();
+    DuplicateFile {
+        file: song.file.clone(),
+        format: header.as_ref().and_then(|h| h.codec.clone()).unwrap_or(extension),
+        bitrate_kbps: size_bytes
+            .zip(song.time.filter(|t| !t.is_zero()))
+            .map(|(size, time)| (size as f64 * 8.0 / time.as_secs_f64() / 1000.0).round() as u32),
+        sample_rate: header.as_ref().and_then(|h| h.sample_rate),
+        bits_per_sample: header.as_ref().and_then(|h| h.bits_per_sample),
+        size_bytes,
+        time: song.time,
+    }
+}
+
+struct AudioHeader {
+    codec: Option<String>,
+    sample_rate: Option<u32>,
+    bits_per_sample: Option<u32>,
+}
+
+fn read_header(path: &Path) -> Option<AudioHeader> {
+    let mss = MediaSourceStream::new(Box::new(File::open(path).ok()?), MediaSourceStreamOptions::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let format = build_probe()
+        .probe(&hint, mss, FormatOptions::default(), MetadataOptions::default())
+        .ok()?;
+    let track = format.default_track(TrackType::Audio)?;
+    let CodecParameters::Audio(params) = track.codec_params.as_ref()? else {
+        return None;
+    };
+    Some(AudioHeader {
+        codec: build_codec_registry()
+            .get_audio_decoder(params.codec)
+            .map(|d| d.codec.info.short_name.to_uppercase()),
+        sample_rate: params.sample_rate,
+        bits_per_sample: params.bits_per_sample,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn song(file: &str, artist: &str, title: &str, secs: u64) -> Song {
+        Song {
+            file: file.to_owned(),
+            artist: Some(artist.to_owned()),
+            title: Some(title.to_owned()),
+            time: Some(Duration::from_secs(secs)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn songs_match_by_tags_and_length_or_fingerprint() {
+        let songs = [
+            song("a/01.flac", "Queen", "Bohemian Rhapsody", 355),
+            song("b/01.mp3", "queen", "Bohemian  Rhapsody", 357),
+            song("c/01.mp3", "Queen", "Bohemian Rhapsody", 370),
+            song("d/track01.m4a", "Unknown", "Track 1", 356),
+            song("e/01.flac", "Queen", "Radio Ga Ga", 356),
+        ];
+        let contour = vec![0x5a5a_5a5a, 0x1234_5678, 0x0f0f_0f0f, 0xdead_beef];
+        let mut fingerprints = vec![None; songs.len()];
+        fingerprints[0] = Some(contour.clone());
+        fingerprints[3] = Some(contour);
+        fingerprints[4] = Some(vec![!0x5a5a_5a5a, !0x1234_5678, !0x0f0f_0f0f, !0xdead_beef]);
+
+        let sets = find_duplicates(&songs, &vec![None; songs.len()]);
+        assert_eq!(sets.root(0), sets.root(1));
+        assert_ne!(sets.root(0), sets.root(2));
+        assert_ne!(sets.root(0), sets.root(3));
+
+        let sets = find_duplicates(&songs, &fingerprints);
+        assert_eq!(sets.root(0), sets.root(3));
+        assert_ne!(sets.root(0), sets.root(4));
+        assert!(sets.by_fingerprint[sets.root(0)]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Compact acoustic fingerprint, taken while the loudness pass decodes a
+//! file and used to find duplicates whose tags differ.
+//!
+//! The mono downmix is cut into windows of [`WINDOW_SECS`]; each bit records
+//! whether a window carries more energy than the one before, 32 windows to a
+//! word. Leading silence is skipped so copies with different padding line
+//! up. Lossy and lossless encodings of one recording keep the same energy
+//! contour, different recordings do not. Only the first [`MAX_WORDS`] words
+//! (two minutes) are kept.
+
+const WINDOW_SECS: f64 = 0.25;
+const MAX_WORDS: usize = 15;
+/// Fingerprints shorter than this (32 s) are too short to compare.
+const MIN_WORDS: usize = 4;
+/// Windows quieter than -60 dBFS count as silence.
+const SILENCE: f64 = 1e-6;
+/// Windows by which copies may be offset against each other.
+const MAX_SHIFT: usize = 4;
+
+pub struct FingerprintBuilder {
+    channels: usize,
+    window_frames: usize,
+    energy: f64,
+    frames: usize,
+    previous: Option<f64>,
+    bits: Vec<bool>,
+}
+
+impl FingerprintBuilder {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn new(channels: u32, sample_rate: u32) -> Self {
+        Self {
+            channels: channels.max(1) as usize,
+            window_frames: ((f64::from(sample_rate) * WINDOW_SECS) as usize).max(1),
+            energy: 0.0,
+            frames: 0,
+            previous: None,
+            bits: Vec::with_capacity(MAX_WORDS * 32),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn add_interleaved(&mut self, samples: &[f32]) {
+        if self.bits.len() >= MAX_WORDS * 32 {
+            return;
+        }
+        for frame in samples.chunks_exact(self.channels) {
+            let mono = frame.iter().map(|s| f64::from(*s)).sum::<f64>() / frame.len() as f64;
+            self.energy += mono * mono;
+            self.frames += 1;
+            if self.frames == self.window_frames {
+                self.close_window();
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<u32> {
+        self.bits
+            .chunks_exact(32)
+            .map(|word| word.iter().fold(0u32, |acc, bit| (acc << 1) | u32::from(*bit)))
+            .collect()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn close_window(&mut self) {
+        let energy = self.energy / self.frames as f64;
+        self.energy = 0.0;
+        self.frames = 0;
+        match self.previous {
+            None if energy < SILENCE => {}
+            None => self.previous = Some(energy),
+            Some(previous) => {
+                self.bits.push(energy > previous);
+                self.previous = Some(energy);
+            }
+        }
+    }
+}
+
+/// Share of matching bits of two fingerprints at the best offset of up to
+/// [`MAX_SHIFT`] windows, or `None` when either is too short to compare.
+#[allow(clippy::cast_precision_loss)]
+pub fn similarity(a: &[u32], b: &[u32]) -> Option<f64> {
+    if a.len() < MIN_WORDS || b.len() < MIN_WORDS {
+        return None;
+    }
+    let best = (0..=MAX_SHIFT)
+        .flat_map(|shift| [(shift, 0), (0, shift)])
+        .filter_map(|(skip_a, skip_b)| {
+            let total = (a.len() * 32 - skip_a).min(b.len() * 32 - skip_b);
+            let differing: usize = (0..total)
+                .step_by(32)
+                .map(|bit| {
+                    let left = total - bit;
+                    let mask = if left >= 32 { u32::MAX } else { !(u32::MAX >> left) };
+                    ((word_at(a, skip_a + bit) ^ word_at(b, skip_b + bit)) & mask).count_ones() as usize
+                })
+                .sum();
+            (total > 0).then(|| (total - differing) as f64 / total as f64)
+        })
+        .fold(0.0, f64::max);
+    Some(best)
+}
+
+/// The 32 bits of `words` from bit `bit` on, first bit highest, zero past
+/// the end.
+fn word_at(words: &[u32], bit: usize) -> u32 {
+    let (index, offset) = (bit / 32, bit % 32);
+    let word = words.get(index).copied().unwrap_or(0);
+    if offset == 0 {
+        word
+    } else {
+        word << offset | words.get(index + 1).copied().unwrap_or(0) >> (32 - offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tone whose level follows `levels`, a quarter second each.
+    #[allow(clippy::cast_precision_loss)]
+    fn render(levels: &[f32], lead_silence: usize, sample_rate: u32) -> Vec<u32> {
+        let mut builder = FingerprintBuilder::new(2, sample_rate);
+        let window = (sample_rate / 4) as usize;
+        builder.add_interleaved(&vec![0.0; lead_silence * 2]);
+        for level in levels {
+            let samples: Vec<f32> = (0..window).flat_map(|i| [level * ((i % 7) as f32 - 3.0) / 3.0; 2]).collect();
+            builder.add_interleaved(&samples);
+        }
+        builder.finish()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn levels(seed: u32, count: usize) -> Vec<f32> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                0.05 + (state >> 16 & 0xff) as f32 / 400.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn copies_match_and_other_recordings_do_not() {
+        let song = levels(7, 200);
+        let original = render(&song, 0, 8000);
+        let padded = render(&song, 8000 / 3, 11025);
+        assert_eq!(original.len(), 6);
+        assert!(similarity(&original, &padded).expect("comparable") > 0.95);
+        let other = render(&levels(99, 200), 0, 8000);
+        assert!(similarity(&original, &other).expect("comparable") < 0.75);
+        assert_eq!(similarity(&original[..2], &padded), None);
+    }
+}
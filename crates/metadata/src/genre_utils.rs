@@ -1,4 +1,5 @@
-//! Genre and name normalization for stable database keys.
+//! Genre and name normalization for stable database keys, and splitting of
+//! multi-valued artist/genre tags into credits ([`split_credits`]).
 //!
 //! Tags are messy: numeric `ID3v1` genre indices (`(17)`), junk values, case
 //! and diacritic variants. These helpers resolve indices against
@@ -252,3 +253,43 @@ pub fn title_case_genre(s: &str) -> String {
         .collect::<Vec<_>>()
         .join(" ")
 }
+
+/// Splits multi-valued artist or genre tags (`A feat. B`, `Rock; Pop`) at
+/// `separators` into single names, in order and without duplicates.
+/// Separators match case-insensitively and without their surrounding
+/// spaces; one starting with a letter only matches after a space, so
+/// `feat.` does not split `Defeat.`. The NUL between `ID3v2.4` values
+/// always splits.
+pub fn split_credits(values: &[String], separators: &[String]) -> Vec<String> {
+    let mut parts: Vec<&str> = values.iter().flat_map(|value| value.split('\0')).collect();
+    for separator in separators.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        parts = parts.into_iter().flat_map(|part| split_at_separator(part, separator)).collect();
+    }
+    let mut credits: Vec<String> = Vec::new();
+    for part in parts.into_iter().map(str::trim).filter(|p| !p.is_empty()) {
+        let key = normalize_name(part);
+        if !credits.iter().any(|c| normalize_name(c) == key) {
+            credits.push(part.to_owned());
+        }
+    }
+    credits
+}
+
+fn split_at_separator<'a>(value: &'a str, separator: &str) -> Vec<&'a str> {
+    // ASCII lowercasing keeps byte offsets, so matches index `value` too.
+    let haystack = value.to_ascii_lowercase();
+    let needle = separator.to_ascii_lowercase();
+    let word = needle.starts_with(char::is_alphanumeric);
+    let mut pieces = Vec::new();
+    let (mut start, mut from) = (0, 0);
+    while let Some(found) = haystack[from..].find(&needle).map(|i| i + from) {
+        let end = found + needle.len();
+        if !word || found == 0 || haystack[..found].ends_with(char::is_whitespace) {
+            pieces.push(&value[start..found]);
+            start = end;
+        }
+        from = end;
+    }
+    pieces.push(&value[start..]);
+    pieces
+}
@@ -0,0 +1,646 @@
+//! HTTP Live Streaming (HLS) radio.
+//!
+//! [`HlsReader`] turns an HLS stream into one continuous byte stream for the
+//! decoder. It picks the master playlist's variant with the highest
+//! bandwidth (audio-only variants first) and follows the media playlist as
+//! the server appends segments. Each segment is unwrapped: MPEG-TS is
+//! demuxed to its AAC or MP3 stream ([`crate::mpeg_ts`]), packed audio
+//! loses its ID3 header, and fMP4 segments follow their init section. Titles
+//! in ID3 timed metadata are published as `CurrentSongEvent`s, like ICY
+//! titles.
+
+use std::collections::VecDeque;
+use std::io::{Error as IoError, Read, Result as IoResult};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail, format_err};
+use api_models::state::StateChangeEvent;
+use log::{info, warn};
+use tokio::sync::broadcast::Sender;
+
+use crate::mpeg_ts::{SYNC_BYTE, TsDemuxer};
+use crate::radio_meta::RadioMeta;
+use crate::stream_playlist::resolve_url;
+
+/// Segments before the live edge where playback starts; the HLS spec asks
+/// for at least three target durations.
+const LIVE_EDGE_SEGMENTS: usize = 3;
+/// Failed fetches in a row after which the stream is given up.
+const MAX_FAILURES: u32 = 3;
+const DEFAULT_TARGET_DURATION: Duration = Duration::from_secs(6);
+/// Longest a playlist or segment download may take, body included. The
+/// radio agent sets no body timeout, as plain streams never end, so a
+/// server stalling mid-segment would otherwise hang playback.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+const VIDEO_CODECS: [&str; 6] = ["avc1", "avc3", "hvc1", "hev1", "av01", "vp09"];
+const MP4_BOXES: [&[u8]; 4] = [b"ftyp", b"styp", b"moof", b"sidx"];
+
+struct MediaPlaylist {
+    target_duration: Duration,
+    media_sequence: u64,
+    segments: Vec<String>,
+    init_section: Option<String>,
+    ended: bool,
+}
+
+enum Container {
+    Ts(TsDemuxer),
+    PackedAudio,
+    Fmp4,
+}
+
+pub struct HlsReader {
+    agent: ureq::Agent,
+    playlist_url: String,
+    pending: VecDeque<String>,
+    next_sequence: u64,
+    target_duration: Duration,
+    ended: bool,
+    next_reload: Instant,
+    container: Container,
+    buffer: Vec<u8>,
+    position: usize,
+    failures: u32,
+    changes_tx: Sender<StateChangeEvent>,
+    radio_meta: Option<RadioMeta>,
+    last_title: String,
+}
+
+impl HlsReader {
+    /// Opens the stream whose playlist `body` was fetched from `url` and
+    /// reads its first segment. Also returns the file extension of the
+    /// unwrapped stream (`aac`, `mp3` or `mp4`) as a probe hint.
+    pub fn open(
+        agent: ureq::Agent,
+        url: &str,
+        body: &str,
+        changes_tx: Sender<StateChangeEvent>,
+        radio_meta: Option<RadioMeta>,
+    ) -> Result<(Self, Option<&'static str>)> {
+        let (playlist_url, body) = match select_variant(body, url) {
+            Some(variant) => {
+                info!("HLS variant {variant}");
+                let body = fetch(&agent, &variant)?.read_to_string()?;
+                (variant, body)
+            }
+            None => (url.to_owned(), body.to_owned()),
+        };
+        let playlist = parse_media_playlist(&body, &playlist_url)?;
+        let start = if playlist.ended {
+            0
+        } else {
+            playlist.segments.len().saturating_sub(LIVE_EDGE_SEGMENTS)
+        };
+        let mut reader = Self {
+            agent,
+            playlist_url,
+            pending: VecDeque::new(),
+            next_sequence: playlist.media_sequence + start as u64,
+            target_duration: playlist.target_duration,
+            ended: playlist.ended,
+            next_reload: Instant::now() + playlist.target_duration,
+            container: Container::PackedAudio,
+            buffer: Vec::new(),
+            position: 0,
+            failures: 0,
+            changes_tx,
+            radio_meta,
+            last_title: String::new(),
+        };
+        reader.queue(&playlist);
+
+        let first = reader
+            .pending
+            .pop_front()
+            .ok_or_else(|| format_err!("HLS playlist {} has no segments", reader.playlist_url))?;
+        let data = fetch(&reader.agent, &first)?.read_to_vec()?;
+        reader.container = if data.first() == Some(&SYNC_BYTE) {
+            Container::Ts(TsDemuxer::default())
+        } else if playlist.init_section.is_some() || data.get(4..8).is_some_and(|b| MP4_BOXES.contains(&b)) {
+            Container::Fmp4
+        } else {
+            Container::PackedAudio
+        };
+        if let (Container::Fmp4, Some(init_section)) = (&reader.container, &playlist.init_section) {
+            reader.buffer = fetch(&reader.agent, init_section)?.read_to_vec()?;
+        }
+        reader.unwrap_segment(&data);
+        let extension = match &reader.container {
+            Container::Ts(demuxer) => demuxer.codec().map(|codec| codec.extension()),
+            Container::PackedAudio => elementary_stream_extension(&reader.buffer),
+            Container::Fmp4 => Some("mp4"),
+        };
+        Ok((reader, extension))
+    }
+
+    /// Queues the segments of `playlist` not queued before.
+    fn queue(&mut self, playlist: &MediaPlaylist) {
+        for (sequence, url) in (playlist.media_sequence..).zip(&playlist.segments) {
+            if sequence >= self.next_sequence {
+                self.pending.push_back(url.clone());
+                self.next_sequence = sequence + 1;
+            }
+        }
+        self.target_duration = playlist.target_duration;
+        self.ended = playlist.ended;
+    }
+
+    /// Fills the buffer with the next segment, waiting for the live
+    /// playlist to grow when needed; `false` at the end of the stream.
+    fn next_segment(&mut self) -> IoResult<bool> {
+        loop {
+            if let Some(url) = self.pending.pop_front() {
+                match fetch(&self.agent, &url).and_then(|mut body| Ok(body.read_to_vec()?)) {
+                    Ok(data) => {
+                        self.failures = 0;
+                        self.unwrap_segment(&data);
+                        return Ok(true);
+                    }
+                    Err(e) => {
+                        warn!("HLS segment {url} failed: {e}");
+                        self.count_failure()?;
+                    }
+                }
+            } else if self.ended {
+                return Ok(false);
+            } else {
+                self.reload()?;
+            }
+        }
+    }
+
+    /// Re-reads the live playlist, after a target duration, or half of one
+    /// when the last reload brought nothing new.
+    fn reload(&mut self) -> IoResult<()> {
+        thread::sleep(self.next_reload.saturating_duration_since(Instant::now()));
+        let queued = self.next_sequence;
+        let playlist = fetch(&self.agent, &self.playlist_url)
+            .and_then(|mut body| Ok(body.read_to_string()?))
+            .and_then(|body| parse_media_playlist(&body, &self.playlist_url));
+        match playlist {
+            Ok(playlist) => {
+                self.failures = 0;
+                self.queue(&playlist);
+            }
+            Err(e) => {
+                warn!("HLS playlist {} failed: {e}", self.playlist_url);
+                self.count_failure()?;
+            }
+        }
+        let interval = if self.next_sequence == queued {
+            self.target_duration / 2
+        } else {
+            self.target_duration
+        };
+        self.next_reload = Instant::now() + interval;
+        Ok(())
+    }
+
+    fn count_failure(&mut self) -> IoResult<()> {
+        self.failures += 1;
+        if self.failures >= MAX_FAILURES {
+            return Err(IoError::other(format!("HLS stream {} is unavailable", self.playlist_url)));
+        }
+        Ok(())
+    }
+
+    fn unwrap_segment(&mut self, data: &[u8]) {
+        let tags = match &mut self.container {
+            Container::Ts(demuxer) => demuxer.push(data, &mut self.buffer),
+            Container::PackedAudio => {
+                let mut tags = Vec::new();
+                let mut rest = data;
+                while let Some(len) = id3_tag_len(rest) {
+                    let (tag, tail) = rest.split_at(len.min(rest.len()));
+                    tags.push(tag.to_vec());
+                    rest = tail;
+                }
+                self.buffer.extend_from_slice(rest);
+                tags
+            }
+            Container::Fmp4 => {
+                self.buffer.extend_from_slice(data);
+                Vec::new()
+            }
+        };
+        for tag in tags {
+            self.publish_title(&tag);
+        }
+    }
+
+    fn publish_title(&mut self, tag: &[u8]) {
+        let Some(radio_meta) = &self.radio_meta else {
+            return;
+        };
+        let Some((artist, title)) = id3_title(tag) else {
+            return;
+        };
+        let shown = artist
+            .as_ref()
+            .map_or_else(|| title.clone(), |artist| format!("{artist} - {title}"));
+        if shown != self.last_title {
+            info!("HLS title: {shown}");
+            self.last_title = shown;
+            let song = radio_meta.now_playing(artist, Some(title));
+            self.changes_tx.send(StateChangeEvent::CurrentSongEvent(song)).ok();
+        }
+    }
+}
+
+impl Read for HlsReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        while self.position >= self.buffer.len() {
+            self.buffer.clear();
+            self.position = 0;
+            if !self.next_segment()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.buffer.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+fn fetch(agent: &ureq::Agent, url: &str) -> Result<ureq::Body> {
+    let resp = agent
+        .get(url)
+        .config()
+        .timeout_global(Some(FETCH_TIMEOUT))
+        .build()
+        .call()
+        .map_err(|e| format_err!("Failed to get url {url}: {e}"))?;
+    Ok(resp.into_body())
+}
+
+/// Media playlist URL to follow when `body` is a master playlist.
+fn select_variant(body: &str, base_url: &str) -> Option<String> {
+    let mut variants: Vec<(bool, u64, &str)> = Vec::new();
+    let mut audio_rendition: Option<(bool, &str)> = None;
+    let mut lines = body.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        if let Some(list) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            let attributes = attributes(list);
+            let bandwidth = attribute(&attributes, "BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0);
+            let audio_only = attribute(&attributes, "RESOLUTION").is_none()
+                && attribute(&attributes, "CODECS").is_none_or(|codecs| !VIDEO_CODECS.iter().any(|v| codecs.contains(v)));
+            if let Some(uri) = lines.find(|l| !l.is_empty() && !l.starts_with('#')) {
+                variants.push((audio_only, bandwidth, uri));
+            }
+        } else if let Some(list) = line.strip_prefix("#EXT-X-MEDIA:") {
+            let attributes = attributes(list);
+            let is_default = attribute(&attributes, "DEFAULT") == Some("YES");
+            if attribute(&attributes, "TYPE") == Some("AUDIO")
+                && let Some(uri) = attribute(&attributes, "URI")
+                && audio_rendition.is_none_or(|(was_default, _)| is_default && !was_default)
+            {
+                audio_rendition = Some((is_default, uri));
+            }
+        }
+    }
+    let best = |audio_only: bool| {
+        variants
+            .iter()
+            .filter(|(only, ..)| *only || !audio_only)
+            .max_by_key(|(_, bandwidth, _)| *bandwidth)
+            .map(|(.., uri)| *uri)
+    };
+    best(true)
+        .or_else(|| audio_rendition.map(|(_, uri)| uri))
+        .or_else(|| best(false))
+        .map(|uri| resolve_url(base_url, uri))
+}
+
+fn parse_media_playlist(body: &str, base_url: &str) -> Result<MediaPlaylist> {
+    let mut playlist = MediaPlaylist {
+        target_duration: DEFAULT_TARGET_DURATION,
+        media_sequence: 0,
+        segments: Vec::new(),
+        init_section: None,
+        ended: false,
+    };
+    for line in body.lines().map(str::trim) {
+        if let Some(secs) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                playlist.target_duration = Duration::from_secs(secs.max(1));
+            }
+        } else if let Some(sequence) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            playlist.media_sequence = sequence.parse().unwrap_or(0);
+        } else if let Some(list) = line.strip_prefix("#EXT-X-MAP:") {
+            playlist.init_section = attribute(&attributes(list), "URI").map(|uri| resolve_url(base_url, uri));
+        } else if let Some(list) = line.strip_prefix("#EXT-X-KEY:") {
+            if attribute(&attributes(list), "METHOD").is_some_and(|method| method != "NONE") {
+                bail!("Encrypted HLS stream {base_url} is not supported");
+            }
+        } else if line == "#EXT-X-ENDLIST" {
+            playlist.ended = true;
+        } else if !line.is_empty() && !line.starts_with('#') {
+            playlist.segments.push(resolve_url(base_url, line));
+        }
+    }
+    Ok(playlist)
+}
+
+/// `NAME=value` pairs of a tag's attribute list, quotes removed.
+fn attributes(list: &str) -> Vec<(&str, &str)> {
+    let mut pairs = Vec::new();
+    let mut rest = list;
+    while let Some((name, tail)) = rest.split_once('=') {
+        let (value, tail) = tail.strip_prefix('"').map_or_else(
+            || tail.split_once(',').unwrap_or((tail, "")),
+            |quoted| quoted.split_once('"').unwrap_or((quoted, "")),
+        );
+        pairs.push((name.trim(), value));
+        rest = tail.trim_start_matches(',');
+    }
+    pairs
+}
+
+fn attribute<'a>(attributes: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    attributes.iter().find(|(n, _)| *n == name).map(|(_, value)| *value)
+}
+
+/// Extension for ADTS AAC or MPEG audio frames at the start of `data`.
+fn elementary_stream_extension(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xff, second, ..] if second & 0xf6 == 0xf0 => Some("aac"),
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => Some("mp3"),
+        _ => None,
+    }
+}
+
+/// Length of the ID3 tag at the start of `data`, footer included.
+fn id3_tag_len(data: &[u8]) -> Option<usize> {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return None;
+    }
+    let footer = if data[5] & 0x10 == 0 { 0 } else { 10 };
+    Some(10 + syncsafe(&data[6..10]) + footer)
+}
+
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |acc, b| (acc << 7) | usize::from(b & 0x7f))
+}
+
+/// Artist (`TPE1`) and title (`TIT2`) of an ID3 tag, version 2.3 or 2.4.
+fn id3_title(tag: &[u8]) -> Option<(Option<String>, String)> {
+    let version = *tag.get(3)?;
+    if !(3..=4).contains(&version) {
+        return None;
+    }
+    let end = (10 + syncsafe(tag.get(6..10)?)).min(tag.len());
+    let mut position = 10;
+    if tag[5] & 0x40 != 0 {
+        let size = tag.get(10..14)?;
+        position += if version == 4 {
+            syncsafe(size)
+        } else {
+            4 + u32::from_be_bytes(size.try_into().ok()?) as usize
+        };
+    }
+    let (mut artist, mut title) = (None, None);
+    while let Some(header) = tag.get(position..position + 10).filter(|_| position + 10 <= end) {
+        if header[0] == 0 {
+            break;
+        }
+        let size = if version == 4 {
+            syncsafe(&header[4..8])
+        } else {
+            u32::from_be_bytes(header[4..8].try_into().ok()?) as usize
+        };
+        let Some(body) = tag.get(position + 10..position + 10 + size) else {
+            break;
+        };
+        match &header[..4] {
+            b"TIT2" => title = id3_text(body),
+            b"TPE1" => artist = id3_text(body),
+            _ => {}
+        }
+        position += 10 + size;
+    }
+    Some((artist, title?))
+}
+
+/// First value of a text frame, in whichever of the four ID3 encodings.
+fn id3_text(body: &[u8]) -> Option<String> {
+    let (&encoding, text) = body.split_first()?;
+    let decoded = match encoding {
+        0 => text.iter().map(|b| char::from(*b)).collect(),
+        1 | 2 => {
+            let mut units: Vec<u16> = text.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            match units.first() {
+                Some(0xfeff) => {
+                    units.remove(0);
+                }
+                Some(0xfffe) => {
+                    units.remove(0);
+                    for unit in &mut units {
+                        *unit = unit.swap_bytes();
+                    }
+                }
+                _ => {}
+            }
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    };
+    let value = decoded.split('\0').next().unwrap_or_default().trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// Serves `files` (path, content type, body) on a local port until the
+    /// test ends; other paths get a 404.
+    fn serve(files: Vec<(&'static str, &'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let base = format!("http://{}", listener.local_addr().expect("test server address"));
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut request = BufReader::new(&stream);
+                let mut request_line = String::new();
+                request.read_line(&mut request_line).ok();
+                let mut header = String::new();
+                while request.read_line(&mut header).is_ok_and(|len| len > 2) {
+                    header.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let response = files.iter().find(|(p, ..)| *p == path).map_or_else(
+                    || b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    |(_, content_type, body)| {
+                        let head = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        );
+                        [head.as_bytes(), body].concat()
+                    },
+                );
+                (&stream).write_all(&response).ok();
+            }
+        });
+        base
+    }
+
+    /// One transport stream packet, padded to size with an adaptation field.
+    #[allow(clippy::cast_possible_truncation)]
+    fn packet(pid: u16, unit_start: bool, payload: &[u8]) -> Vec<u8> {
+        let stuffing = 184 - payload.len();
+        let mut packet = vec![SYNC_BYTE, (u8::from(unit_start) << 6) | (pid >> 8) as u8, pid as u8];
+        if stuffing == 0 {
+            packet.push(0x10);
+        } else {
+            packet.extend([0x30, (stuffing - 1) as u8]);
+        }
+        if stuffing > 1 {
+            packet.push(0);
+            packet.extend(std::iter::repeat_n(0xff, stuffing - 2));
+        }
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn pes(stream_id: u8, data: &[u8]) -> Vec<u8> {
+        [&[0, 0, 1, stream_id, 0, 0, 0x80, 0x80, 5, 0x21, 0, 1, 0, 1][..], data].concat()
+    }
+
+    fn segment(audio: &[u8], tag: Option<&[u8]>) -> Vec<u8> {
+        let pat = [0, 0x00, 0xb0, 13, 0, 1, 0xc1, 0, 0, 0, 1, 0xf0, 0x00, 0, 0, 0, 0];
+        let pmt = [
+            0, 0x02, 0xb0, 23, 0, 1, 0xc1, 0, 0, 0xe1, 0x00, 0xf0, 0, 0x0f, 0xe1, 0x00, 0xf0, 0, 0x15, 0xe1, 0x02, 0xf0, 0, 0, 0, 0, 0,
+        ];
+        let mut ts = [
+            packet(0, true, &pat),
+            packet(0x1000, true, &pmt),
+            packet(0x100, true, &pes(0xc0, audio)),
+        ]
+        .concat();
+        if let Some(tag) = tag {
+            ts.extend(packet(0x102, true, &pes(0xbd, tag)));
+        }
+        ts
+    }
+
+    fn id3(frames: &[(&[u8; 4], &str)]) -> Vec<u8> {
+        let body: Vec<u8> = frames
+            .iter()
+            .flat_map(|(id, text)| {
+                let len = u8::try_from(text.len() + 1).expect("short text");
+                [&id[..], &[0, 0, 0, len, 0, 0, 3], text.as_bytes()].concat()
+            })
+            .collect();
+        let len = u8::try_from(body.len()).expect("short tag");
+        [&b"ID3\x04\x00\x00\x00\x00\x00"[..], &[len], &body].concat()
+    }
+
+    #[test]
+    fn plays_the_best_audio_variant_and_publishes_titles() {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=48000,CODECS=\"mp4a.40.5\"\nlow/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=900000,CODECS=\"avc1.4d401f,mp4a.40.2\",RESOLUTION=640x360\nvideo/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp4a.40.2\"\nhigh/index.m3u8\n";
+        let media =
+            "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:7\n#EXTINF:1.0,\nseg7.ts\n#EXTINF:1.0,\nseg8.ts\n#EXT-X-ENDLIST\n";
+        let first: Vec<u8> = [0xff, 0xf1, 0x50, 0x80].into_iter().chain(1..=60).collect();
+        let second: Vec<u8> = (61..=120).collect();
+        let tag = id3(&[(b"TPE1", "Band"), (b"TIT2", "Song")]);
+        let base = serve(vec![
+            ("/live/high/index.m3u8", "application/vnd.apple.mpegurl", media.as_bytes().to_vec()),
+            ("/live/high/seg7.ts", "video/mp2t", segment(&first, Some(&tag))),
+            ("/live/high/seg8.ts", "video/mp2t", segment(&second, None)),
+        ]);
+        let (changes_tx, mut changes_rx) = tokio::sync::broadcast::channel(8);
+        let url = format!("{base}/live/master.m3u8");
+
+        let (mut reader, extension) =
+            HlsReader::open(ureq::Agent::new_with_defaults(), &url, master, changes_tx, Some(radio_meta(&url))).expect("open HLS stream");
+        let mut audio = Vec::new();
+        reader.read_to_end(&mut audio).expect("read HLS stream");
+
+        assert_eq!(extension, Some("aac"));
+        assert_eq!(audio, [first, second].concat());
+        let Ok(StateChangeEvent::CurrentSongEvent(song)) = changes_rx.try_recv() else {
+            panic!("no title published");
+        };
+        assert_eq!(song.artist.as_deref(), Some("Band"));
+        assert_eq!(song.title.as_deref(), Some("Song"));
+        assert_eq!(song.album.as_deref(), Some("Test FM"));
+    }
+
+    fn radio_meta(url: &str) -> RadioMeta {
+        RadioMeta {
+            name: Some("Test FM".to_owned()),
+            description: None,
+            url: url.to_owned(),
+            genre: None,
+            image_url: None,
+            samplerate: None,
+            channels: None,
+            bitrate: None,
+        }
+    }
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(8 + payload.len()).expect("small box");
+        [&len.to_be_bytes()[..], kind, payload].concat()
+    }
+
+    #[test]
+    fn packed_audio_loses_its_id3_tags() {
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXTINF:1.0,\nseg1.aac\n#EXTINF:1.0,\nseg2.aac\n#EXT-X-ENDLIST\n";
+        let first: Vec<u8> = [0xff, 0xf1, 0x50, 0x80].into_iter().chain(1..=60).collect();
+        let second: Vec<u8> = [0xff, 0xf1, 0x50, 0x80].into_iter().chain(61..=120).collect();
+        let first_tag = id3(&[(b"TPE1", "Band"), (b"TIT2", "Song")]);
+        let second_tag = id3(&[(b"TPE1", "Band"), (b"TIT2", "Next")]);
+        let base = serve(vec![
+            ("/packed/seg1.aac", "audio/aac", [first_tag, first.clone()].concat()),
+            ("/packed/seg2.aac", "audio/aac", [second_tag, second.clone()].concat()),
+        ]);
+        let (changes_tx, mut changes_rx) = tokio::sync::broadcast::channel(8);
+        let url = format!("{base}/packed/index.m3u8");
+
+        let (mut reader, extension) =
+            HlsReader::open(ureq::Agent::new_with_defaults(), &url, media, changes_tx, Some(radio_meta(&url))).expect("open HLS stream");
+        let mut audio = Vec::new();
+        reader.read_to_end(&mut audio).expect("read HLS stream");
+
+        assert_eq!(extension, Some("aac"));
+        assert_eq!(audio, [first, second].concat());
+        let titles: Vec<String> = std::iter::from_fn(|| match changes_rx.try_recv() {
+            Ok(StateChangeEvent::CurrentSongEvent(song)) => song.title,
+            _ => None,
+        })
+        .collect();
+        assert_eq!(titles, ["Song", "Next"]);
+    }
+
+    #[test]
+    fn fmp4_segments_follow_their_init_section() {
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MAP:URI=\"init.mp4\"\n\
+            #EXTINF:1.0,\nseg1.m4s\n#EXTINF:1.0,\nseg2.m4s\n#EXT-X-ENDLIST\n";
+        let init = [mp4_box(b"ftyp", b"iso6"), mp4_box(b"moov", &[1; 16])].concat();
+        let first = [mp4_box(b"moof", &[2; 16]), mp4_box(b"mdat", &[3; 32])].concat();
+        let second = [mp4_box(b"moof", &[4; 16]), mp4_box(b"mdat", &[5; 32])].concat();
+        let base = serve(vec![
+            ("/fmp4/init.mp4", "video/mp4", init.clone()),
+            ("/fmp4/seg1.m4s", "video/iso.segment", first.clone()),
+            ("/fmp4/seg2.m4s", "video/iso.segment", second.clone()),
+        ]);
+        let (changes_tx, _changes_rx) = tokio::sync::broadcast::channel(8);
+        let url = format!("{base}/fmp4/index.m3u8");
+
+        let (mut reader, extension) =
+            HlsReader::open(ureq::Agent::new_with_defaults(), &url, media, changes_tx, None).expect("open HLS stream");
+        let mut stream = Vec::new();
+        reader.read_to_end(&mut stream).expect("read HLS stream");
+
+        assert_eq!(extension, Some("mp4"));
+        assert_eq!(stream, [init, first, second].concat());
+    }
+}
@@ -3,15 +3,18 @@
 //! Wraps the HTTP body `Read` and strips the in-band metadata blocks that
 //! appear every `metaint` bytes, publishing `StreamTitle` changes as
 //! `CurrentSongEvent`s while handing the pure audio bytes to the decoder.
+//! Title changes also reach the [`StreamRecorder`], which may start a new
+//! recording file with them.
 
 use std::io::{Read, Result as IoResult};
+use std::sync::Arc;
 
-use api_models::player::Song;
 use api_models::state::StateChangeEvent;
 use log::info;
 use tokio::sync::broadcast::Sender;
 
 use crate::radio_meta::RadioMeta;
+use crate::stream_recorder::StreamRecorder;
 
 pub struct IcyMetadataReader<R: Read> {
     inner: R,
@@ -20,10 +23,17 @@ pub struct IcyMetadataReader<R: Read> {
     changes_tx: Sender<StateChangeEvent>,
     last_title: String,
     radio_meta: RadioMeta,
+    recorder: Arc<StreamRecorder>,
 }
 
 impl<R: Read> IcyMetadataReader<R> {
-    pub const fn new(inner: R, metaint: usize, changes_tx: Sender<StateChangeEvent>, radio_meta: RadioMeta) -> Self {
+    pub const fn new(
+        inner: R,
+        metaint: usize,
+        changes_tx: Sender<StateChangeEvent>,
+        radio_meta: RadioMeta,
+        recorder: Arc<StreamRecorder>,
+    ) -> Self {
         Self {
             inner,
             metaint,
@@ -31,6 +41,7 @@ impl<R: Read> IcyMetadataReader<R> {
             changes_tx,
             last_title: String::new(),
             radio_meta,
+            recorder,
         }
     }
 
@@ -51,28 +62,14 @@ impl<R: Read> IcyMetadataReader<R> {
                     && title != self.last_title
                 {
                     self.last_title = title.to_string();
+                    self.recorder.title_changed(title);
                     let parts: Vec<&str> = title.splitn(2, " - ").collect();
                     let (artist, song_title) = if parts.len() == 2 {
                         (Some(parts[0].to_string()), Some(parts[1].to_string()))
                     } else {
                         (None, Some(title.to_string()))
                     };
-                    let mut album = self.radio_meta.description.clone().unwrap_or_default();
-                    if album.is_empty() {
-                        album = self.radio_meta.name.clone().unwrap_or_default();
-                    }
-                    if album.is_empty() {
-                        album.clone_from(&self.radio_meta.url);
-                    }
-                    let song = Song {
-                        title: song_title,
-                        artist,
-                        album: Some(album),
-                        genre: self.radio_meta.genre.clone(),
-                        file: self.radio_meta.url.clone(),
-                        image_url: self.radio_meta.image_url.clone(),
-                        ..Default::default()
-                    };
+                    let song = self.radio_meta.now_playing(artist, song_title);
                     self.changes_tx.send(StateChangeEvent::CurrentSongEvent(song)).ok();
                 }
             }
@@ -2,39 +2,69 @@
 //!
 //! Persistence is fjall (LSM key-value store), one `Database` shared by the
 //! whole process with one keyspace per concern (`songs`, `albums`, `queue`,
-//! `playlist`, `play_statistics`, `loudness`…). Songs are keyed by
-//! library-relative file path; albums by normalized `artist|album`.
+//! `playlist`, `play_statistics`, `loudness`, `radio_stations`, `users`…).
+//! Songs are keyed by library-relative file path; albums by `MusicBrainz` id
+//! or normalized `artist|album`.
 //!
-//! Layout: `metadata_service` — scanner and library queries;
-//! `queue_service` — the playback queue; `playlist_service` — saved
-//! playlists; `*_repository` — fjall implementations of the `ports` traits
-//! (kept behind traits so tests can use `ports::fakes`); `loudness_*` —
-//! EBU R128 analysis for volume normalization; `icy_reader`/`radio_*` —
-//! internet-radio metadata; `*_bundle` — custom Symphonia format/codec
-//! plugins (APE, DSF/DSD, SACD ISO) registered via [`build_probe`] and
+//! Layout: `metadata_service` — scanner and library queries
+//! (`tag_editor` writes edited tags back to the files, `artwork` stores
+//! album pictures and thumbnails, `work_repository` indexes classical works
+//! by composer); `queue_service` —
+//! the playback queue (`similarity` picks its auto-continue songs);
+//! `playlist_service` — saved playlists;
+//! `bookmark_service` — resume points and bookmarks for long-form audio;
+//! `*_repository` — fjall implementations of the `ports` traits
+//! (kept behind traits so tests can use `ports::fakes`; `album_identity`
+//! derives album ids from song tags without them); `loudness_*` —
+//! EBU R128 analysis for volume normalization (`loudness_tag_writer` writes
+//! it back as `REPLAYGAIN_*` tags, `fingerprint` is taken in the same pass
+//! and `duplicate_finder` reports duplicate songs with it); `icy_reader`/`radio_*` —
+//! internet-radio metadata, `radio_station_service` — the saved radio
+//! stations, `stream_recorder` — radio recordings to files,
+//! `user_service` — accounts, sessions and API tokens for access control;
+//! `stream_playlist`/`hls_reader`/`mpeg_ts` — playlist-wrapped and HLS
+//! radio streams; `*_bundle` — custom Symphonia format/codec plugins (APE,
+//! DSF/DSD, SACD ISO) registered via [`build_probe`] and
 //! [`build_codec_registry`], which the playback crate also uses.
 
+pub mod album_identity;
 pub mod album_repository;
 pub mod ape_bundle;
+pub mod artwork;
 pub mod audio_metadata_extractor;
+pub mod bookmark_service;
 pub mod dsd_bundle;
+pub mod duplicate_finder;
 pub mod error;
+pub mod fingerprint;
 pub mod genre_utils;
+pub mod hls_reader;
 pub mod icy_reader;
 pub mod loudness_analyzer;
 pub mod loudness_repository;
 pub mod loudness_service;
+pub mod loudness_tag_writer;
 pub mod metadata_service;
+pub mod mpeg_ts;
 pub mod play_statistic_repository;
 pub mod playlist_service;
 pub mod ports;
 pub mod queue_service;
 pub mod radio_meta;
 pub mod radio_providers;
+pub mod radio_station_repository;
+pub mod radio_station_service;
 pub mod sacd_bundle;
+pub mod similarity;
 pub mod song_repository;
+pub mod stream_playlist;
+pub mod stream_recorder;
+pub mod tag_editor;
 #[cfg(test)]
 mod test;
+pub mod user_repository;
+pub mod user_service;
+pub mod work_repository;
 use crate::ape_bundle::{ApeDecoder, ApeReader};
 use crate::dsd_bundle::{DsdDecoder, DsfReader};
 use symphonia::core::codecs::registry::CodecRegistry;
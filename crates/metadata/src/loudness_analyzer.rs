@@ -1,8 +1,14 @@
 //! Integrated-loudness measurement (EBU R128) of one file.
 //!
 //! Decodes the whole track with Symphonia and feeds it to the `ebur128`
-//! crate; returns integrated LUFS. DSD is skipped — it cannot go through
-//! the PCM analysis chain.
+//! crate; returns integrated LUFS, sample and true peak (the crate
+//! oversamples 4x below 96 kHz, 2x below 192 kHz) and loudness range. Silent
+//! tracks count as unmeasurable. The meter is handed back
+//! with the result so an album can be gated across all of its tracks
+//! ([`LoudnessAnalyzer::album_loudness`]) without decoding them twice. The
+//! same decode also yields the acoustic fingerprint used to find duplicates
+//! (see [`crate::fingerprint`]). DSD is skipped — it cannot go through the
+//! PCM analysis chain.
 
 use std::{fs::File, path::Path};
 
@@ -15,13 +21,27 @@ use symphonia::core::{
     meta::MetadataOptions,
 };
 
+use ebur128::{EbuR128, Mode};
+
 use crate::dsd_bundle::{CODEC_TYPE_DSD_LSBF, CODEC_TYPE_DSD_MSBF};
+use crate::fingerprint::FingerprintBuilder;
 use crate::{build_codec_registry, build_probe};
 
+pub struct TrackAnalysis {
+    pub integrated_lufs: f64,
+    /// Highest sample peak over all channels in dBFS.
+    pub sample_peak_dbfs: f64,
+    /// Highest true peak over all channels in dBTP.
+    pub true_peak_dbtp: f64,
+    pub loudness_range_lu: f64,
+    pub fingerprint: Vec<u32>,
+    meter: EbuR128,
+}
+
 pub struct LoudnessAnalyzer;
 
 impl LoudnessAnalyzer {
-    pub fn measure_file(file_path: &Path) -> Option<f64> {
+    pub fn measure_file(file_path: &Path) -> Option<TrackAnalysis> {
         let file = Box::new(File::open(file_path).ok()?);
         let mss = MediaSourceStream::new(file, symphonia::core::io::MediaSourceStreamOptions::default());
 
@@ -36,7 +56,19 @@ impl LoudnessAnalyzer {
         Self::measure_from_format(&mut *format)
     }
 
-    fn measure_from_format(format: &mut dyn FormatReader) -> Option<f64> {
+    /// Integrated loudness and true peak of `tracks` played back to back:
+    /// the relative gate is computed over the blocks of all tracks together,
+    /// as album gain requires. Returns `(LUFS, dBTP)`.
+    pub fn album_loudness(tracks: &[TrackAnalysis]) -> Option<(f64, f64)> {
+        let loudness = EbuR128::loudness_global_multiple(tracks.iter().map(|t| &t.meter)).ok()?;
+        if !loudness.is_finite() {
+            return None;
+        }
+        let true_peak = tracks.iter().map(|t| t.true_peak_dbtp).reduce(f64::max)?;
+        Some((loudness, true_peak))
+    }
+
+    fn measure_from_format(format: &mut dyn FormatReader) -> Option<TrackAnalysis> {
         let track = format.default_track(TrackType::Audio)?;
         let track_id = track.id;
 
@@ -57,7 +89,8 @@ impl LoudnessAnalyzer {
             .make_audio_decoder(audio_params, &AudioDecoderOptions::default())
             .ok()?;
 
-        let mut meter = ebur128::EbuR128::new(channels, sample_rate, ebur128::Mode::I).ok()?;
+        let mut meter = EbuR128::new(channels, sample_rate, Mode::I | Mode::LRA | Mode::SAMPLE_PEAK | Mode::TRUE_PEAK).ok()?;
+        let mut fingerprint = FingerprintBuilder::new(channels, sample_rate);
         let mut sample_vec: Vec<f32> = Vec::new();
 
         loop {
@@ -79,8 +112,23 @@ impl LoudnessAnalyzer {
             };
             audio_buf.copy_to_vec_interleaved(&mut sample_vec);
             let _ = meter.add_frames_f32(&sample_vec);
+            fingerprint.add_interleaved(&sample_vec);
         }
 
-        meter.loudness_global().ok()
+        let integrated_lufs = meter.loudness_global().ok().filter(|lufs| lufs.is_finite())?;
+        let sample_peak = (0..channels).filter_map(|ch| meter.sample_peak(ch).ok()).fold(0.0, f64::max);
+        let true_peak = (0..channels).filter_map(|ch| meter.true_peak(ch).ok()).fold(0.0, f64::max);
+        Some(TrackAnalysis {
+            integrated_lufs,
+            sample_peak_dbfs: to_db(sample_peak),
+            true_peak_dbtp: to_db(true_peak),
+            loudness_range_lu: meter.loudness_range().ok()?,
+            fingerprint: fingerprint.finish(),
+            meter,
+        })
     }
 }
+
+fn to_db(linear: f64) -> f64 {
+    20.0 * linear.max(f64::MIN_POSITIVE).log10()
+}
@@ -1,13 +1,27 @@
-//! Fjall-backed [`LoudnessRepository`]: measured integrated loudness (LUFS)
-//! per song key, consumed by playback for volume normalization.
+//! Fjall-backed [`LoudnessRepository`]: measured integrated loudness (LUFS),
+//! peaks and loudness range per song key, consumed by playback for volume
+//! normalization. Values written before peaks were recorded hold only the
+//! loudness; they still serve [`LoudnessRepository::get`] but do not count
+//! as analysed, so the background scan measures those files again.
+//!
+//! Album measurements live in a separate `album_loudness` keyspace keyed by
+//! album id, and acoustic fingerprints in `fingerprints` keyed by song key,
+//! so counting analysed songs stays a plain keyspace length.
 
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
 
 use crate::error::{RepoError, RepoResult};
-pub use crate::ports::loudness_repository::{ArcLoudnessRepository, LoudnessRepository};
+pub use crate::ports::loudness_repository::{AlbumLoudness, ArcLoudnessRepository, LoudnessRepository, TrackLoudness};
+
+/// Leading byte of a track value.
+const UNAVAILABLE: u8 = 0x00;
+const LOUDNESS_ONLY: u8 = 0x01;
+const MEASURED: u8 = 0x02;
 
 pub struct FjallLoudnessRepository {
     db: Keyspace,
+    album_db: Keyspace,
+    fingerprint_db: Keyspace,
 }
 
 impl FjallLoudnessRepository {
@@ -16,17 +30,19 @@ impl FjallLoudnessRepository {
             db: db
                 .keyspace("loudness", KeyspaceCreateOptions::default)
                 .expect("Failed to open loudness keyspace"),
+            album_db: db
+                .keyspace("album_loudness", KeyspaceCreateOptions::default)
+                .expect("Failed to open album_loudness keyspace"),
+            fingerprint_db: db
+                .keyspace("fingerprints", KeyspaceCreateOptions::default)
+                .expect("Failed to open fingerprints keyspace"),
         }
     }
 
     /// Standalone constructor for tests — opens its own fjall Database.
     pub fn new_standalone(db_path: &str) -> Self {
         let db = Database::builder(db_path).open().expect("Failed to open loudness db");
-        Self {
-            db: db
-                .keyspace("loudness", KeyspaceCreateOptions::default)
-                .expect("Failed to open loudness keyspace"),
-        }
+        Self::new(&db)
     }
 }
 
@@ -34,30 +50,96 @@ impl LoudnessRepository for FjallLoudnessRepository {
     fn get(&self, file_key: &str) -> Option<i32> {
         let bytes = self.db.get(file_key).ok()??;
         match bytes.as_ref() {
-            [0x01, a, b, c, d] => Some(i32::from_le_bytes([*a, *b, *c, *d])),
+            [LOUDNESS_ONLY, a, b, c, d] => Some(i32::from_le_bytes([*a, *b, *c, *d])),
+            [MEASURED, rest @ ..] => decode_track(rest).map(|t| t.loudness),
+            _ => None,
+        }
+    }
+
+    fn get_track(&self, file_key: &str) -> Option<TrackLoudness> {
+        let bytes = self.db.get(file_key).ok()??;
+        match bytes.as_ref() {
+            [MEASURED, rest @ ..] => decode_track(rest),
             _ => None,
         }
     }
 
     fn contains(&self, file_key: &str) -> bool {
-        self.db.contains_key(file_key).unwrap_or(false)
+        self.db
+            .get(file_key)
+            .ok()
+            .flatten()
+            .is_some_and(|bytes| bytes.first() != Some(&LOUDNESS_ONLY))
     }
 
-    fn save_loudness(&self, file_key: &str, loudness: i32) -> RepoResult<()> {
-        let mut bytes = [0u8; 5];
-        bytes[0] = 0x01;
-        bytes[1..5].copy_from_slice(&loudness.to_le_bytes());
+    fn save_loudness(&self, file_key: &str, loudness: TrackLoudness) -> RepoResult<()> {
+        let mut bytes = Vec::with_capacity(17);
+        bytes.push(MEASURED);
+        for value in [loudness.loudness, loudness.sample_peak, loudness.true_peak, loudness.lra] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
         self.db
-            .insert(file_key, bytes.as_ref())
+            .insert(file_key, bytes)
             .map_err(|e| RepoError::Storage(format!("save loudness for '{file_key}': {e}")))
     }
 
     fn save_unavailable(&self, file_key: &str) -> RepoResult<()> {
         self.db
-            .insert(file_key, &[0x00u8][..])
+            .insert(file_key, &[UNAVAILABLE][..])
             .map_err(|e| RepoError::Storage(format!("save loudness sentinel for '{file_key}': {e}")))
     }
 
+    fn get_album(&self, album_id: &str) -> Option<AlbumLoudness> {
+        let bytes = self.album_db.get(album_id).ok()??;
+        match bytes.as_ref() {
+            [0x01, l0, l1, l2, l3, p0, p1, p2, p3] => Some(AlbumLoudness {
+                loudness: i32::from_le_bytes([*l0, *l1, *l2, *l3]),
+                true_peak: i32::from_le_bytes([*p0, *p1, *p2, *p3]),
+            }),
+            _ => None,
+        }
+    }
+
+    fn contains_album(&self, album_id: &str) -> bool {
+        self.album_db.contains_key(album_id).unwrap_or(false)
+    }
+
+    fn save_album_loudness(&self, album_id: &str, loudness: AlbumLoudness) -> RepoResult<()> {
+        let mut bytes = [0u8; 9];
+        bytes[0] = 0x01;
+        bytes[1..5].copy_from_slice(&loudness.loudness.to_le_bytes());
+        bytes[5..9].copy_from_slice(&loudness.true_peak.to_le_bytes());
+        self.album_db
+            .insert(album_id, bytes.as_ref())
+            .map_err(|e| RepoError::Storage(format!("save album loudness for '{album_id}': {e}")))
+    }
+
+    fn save_album_unavailable(&self, album_id: &str) -> RepoResult<()> {
+        self.album_db
+            .insert(album_id, &[0x00u8][..])
+            .map_err(|e| RepoError::Storage(format!("save album loudness sentinel for '{album_id}': {e}")))
+    }
+
+    fn get_fingerprint(&self, file_key: &str) -> Option<Vec<u32>> {
+        let bytes = self.fingerprint_db.get(file_key).ok()??;
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        (!words.is_empty()).then_some(words)
+    }
+
+    fn contains_fingerprint(&self, file_key: &str) -> bool {
+        self.fingerprint_db.contains_key(file_key).unwrap_or(false)
+    }
+
+    fn save_fingerprint(&self, file_key: &str, fingerprint: &[u32]) -> RepoResult<()> {
+        let bytes: Vec<u8> = fingerprint.iter().flat_map(|word| word.to_le_bytes()).collect();
+        self.fingerprint_db
+            .insert(file_key, bytes)
+            .map_err(|e| RepoError::Storage(format!("save fingerprint for '{file_key}': {e}")))
+    }
+
     fn count_analysed(&self) -> usize {
         self.db.approximate_len()
     }
@@ -71,5 +153,68 @@ impl LoudnessRepository for FjallLoudnessRepository {
         for key in keys {
             _ = self.db.remove(key);
         }
+        _ = self.album_db.clear();
+        _ = self.fingerprint_db.clear();
+    }
+}
+
+fn decode_track(bytes: &[u8]) -> Option<TrackLoudness> {
+    if bytes.len() != 16 {
+        return None;
+    }
+    let mut values = bytes.chunks_exact(4).map(|c| i32::from_le_bytes([c[0], c[1], c[2], c[3]]));
+    Some(TrackLoudness {
+        loudness: values.next()?,
+        sample_peak: values.next()?,
+        true_peak: values.next()?,
+        lra: values.next()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::test_shared;
+
+    fn create_repo() -> FjallLoudnessRepository {
+        let ctx = test_shared::Context::default();
+        FjallLoudnessRepository::new_standalone(&ctx.db_dir)
+    }
+
+    #[test]
+    fn measurement_round_trip() {
+        let repo = create_repo();
+        let measured = TrackLoudness {
+            loudness: -1432,
+            sample_peak: -15,
+            true_peak: 42,
+            lra: 780,
+        };
+        repo.save_loudness("a.flac", measured).expect("save loudness");
+        assert!(repo.contains("a.flac"));
+        assert_eq!(repo.get("a.flac"), Some(-1432));
+        assert_eq!(repo.get_track("a.flac"), Some(measured));
+
+        repo.save_unavailable("b.dsf").expect("save unavailable");
+        assert!(repo.contains("b.dsf"));
+        assert_eq!(repo.get("b.dsf"), None);
+
+        assert!(!repo.contains_fingerprint("a.flac"));
+        repo.save_fingerprint("a.flac", &[7, u32::MAX]).expect("save fingerprint");
+        assert_eq!(repo.get_fingerprint("a.flac"), Some(vec![7, u32::MAX]));
+        repo.save_fingerprint("b.dsf", &[]).expect("save empty fingerprint");
+        assert_eq!(repo.get_fingerprint("b.dsf"), None);
+        assert!(repo.contains_fingerprint("b.dsf"));
+    }
+
+    #[test]
+    fn loudness_only_values_are_analysed_again() {
+        let repo = create_repo();
+        let mut legacy = vec![LOUDNESS_ONLY];
+        legacy.extend_from_slice(&(-1800i32).to_le_bytes());
+        repo.db.insert("old.flac", legacy).expect("insert");
+        assert_eq!(repo.get("old.flac"), Some(-1800));
+        assert_eq!(repo.get_track("old.flac"), None);
+        assert!(!repo.contains("old.flac"));
     }
 }
@@ -1,18 +1,25 @@
 //! Background loudness scan of the whole library.
 //!
 //! Runs on its own thread pool (half the cores), analysing songs that have
-//! no stored loudness yet and persisting results via the loudness
-//! repository. Pauses itself while playback is active (`is_playing`) so the
-//! analysis never competes with the audio thread for CPU or disk.
+//! no stored measurement yet (loudness, peaks, loudness range) and
+//! persisting results via the loudness repository. Albums are analysed as one unit so their integrated loudness
+//! is gated across all tracks (album gain); an album is re-measured when it
+//! gains an unanalysed song. The same decode yields each track's acoustic
+//! fingerprint, stored for the duplicate report; songs measured before
+//! fingerprints were recorded are decoded once more for theirs. Pauses itself while playback is active
+//! (`is_playing`) so the analysis never competes with the audio thread for
+//! CPU or disk. Songs and albums whose files are missing are remembered and
+//! skipped until one of their files changes.
 
 use std::{
-    path::Path,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
     thread,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use log::{debug, info, warn};
@@ -20,25 +27,42 @@ use rayon::prelude::*;
 use std::sync::atomic::AtomicU32;
 use std::thread::available_parallelism;
 
-use crate::loudness_analyzer::LoudnessAnalyzer;
-use crate::ports::{loudness_repository::ArcLoudnessRepository, song_repository::ArcSongRepository};
+use api_models::player::Song;
+
+use crate::album_identity::{album_id_for_song, is_singletons_id};
+use crate::loudness_analyzer::{LoudnessAnalyzer, TrackAnalysis};
+use crate::ports::{
+    album_repository::ArcAlbumRepository,
+    loudness_repository::{AlbumLoudness, ArcLoudnessRepository, TrackLoudness},
+    song_repository::ArcSongRepository,
+};
 
 pub struct LoudnessService {
     repository: ArcLoudnessRepository,
     song_repository: ArcSongRepository,
+    album_repository: ArcAlbumRepository,
     music_dirs: Vec<String>,
     pub is_playing: Arc<AtomicBool>,
     stop: Arc<AtomicBool>,
+    /// Units that could not be read, with the file state they failed on.
+    failed: Mutex<HashMap<String, Vec<Option<SystemTime>>>>,
 }
 
 impl LoudnessService {
-    pub fn new(repository: ArcLoudnessRepository, song_repository: ArcSongRepository, music_dirs: Vec<String>) -> Arc<Self> {
+    pub fn new(
+        repository: ArcLoudnessRepository,
+        song_repository: ArcSongRepository,
+        album_repository: ArcAlbumRepository,
+        music_dirs: Vec<String>,
+    ) -> Arc<Self> {
         Arc::new(Self {
             repository,
             song_repository,
+            album_repository,
             music_dirs,
             is_playing: Arc::new(AtomicBool::new(false)),
             stop: Arc::new(AtomicBool::new(false)),
+            failed: Mutex::new(HashMap::new()),
         })
     }
 
@@ -69,6 +93,14 @@ impl LoudnessService {
         self.repository.get(file_key)
     }
 
+    pub fn get_track_loudness(&self, file_key: &str) -> Option<TrackLoudness> {
+        self.repository.get_track(file_key)
+    }
+
+    pub fn get_album_loudness(&self, song: &Song) -> Option<AlbumLoudness> {
+        self.repository.get_album(&album_id_for_song(song)?)
+    }
+
     fn scan_loop(&self, thread_pool: &rayon::ThreadPool) {
         info!("Loudness scan thread started");
         loop {
@@ -77,16 +109,11 @@ impl LoudnessService {
                 break;
             }
 
-            let pending: Vec<String> = self
-                .song_repository
-                .find_all()
-                .into_iter()
-                .filter(|s| !self.repository.contains(&s.file))
-                .map(|s| s.file)
-                .collect();
+            let mut pending = self.pending_units();
+            pending.retain(|unit| !self.failed_before(unit));
 
             if pending.is_empty() {
-                debug!("Loudness scan: all songs analysed, sleeping 60s");
+                debug!("Loudness scan: all songs and albums analysed, sleeping 60s");
                 thread::sleep(Duration::from_mins(1));
                 continue;
             }
@@ -102,58 +129,262 @@ impl LoudnessService {
                 info!("Loudness scan: playback stopped, starting batch");
             }
 
-            info!("Loudness scan: {} songs pending, analysing in parallel", pending.len());
-            let scanned = AtomicU32::new(0);
+            info!("Loudness scan: {} songs/albums pending, analysing in parallel", pending.len());
+            let progress = Progress::default();
 
             thread_pool.install(|| {
-                pending.par_iter().for_each(|file_key| {
+                pending.par_iter().for_each(|unit| {
                     if self.stop.load(Ordering::Relaxed) {
                         return;
                     }
-                    if self.is_playing.load(Ordering::Relaxed) {
-                        debug!("Loudness scan: skipping {file_key} (playback started)");
-                        return;
-                    }
-
-                    let full_path = self
-                        .music_dirs
-                        .iter()
-                        .map(|dir| format!("{dir}/{file_key}"))
-                        .find(|p| Path::new(p).exists());
-                    let Some(full_path) = full_path else {
-                        warn!("Loudness scan: file not found in any music directory: {file_key}");
-                        return;
-                    };
-                    debug!("Loudness scan: analysing {file_key}");
-                    if let Some(lufs) = LoudnessAnalyzer::measure_file(Path::new(&full_path)) {
-                        #[allow(clippy::cast_possible_truncation)]
-                        let stored = (lufs * 100.0).round() as i32;
-                        debug!("Loudness scan: {file_key} => {lufs:.2} LUFS");
-                        if let Err(e) = self.repository.save_loudness(file_key, stored) {
-                            warn!("Failed to persist loudness for '{file_key}': {e}");
-                        }
-                    } else {
-                        warn!("Loudness scan: no loudness for {file_key} (DSD or unsupported)");
-                        if let Err(e) = self.repository.save_unavailable(file_key) {
-                            warn!("Failed to persist loudness sentinel for '{file_key}': {e}");
-                        }
-                    }
-
-                    let c = scanned.fetch_add(1, Ordering::Relaxed) + 1;
-                    if c.is_multiple_of(50) {
-                        info!("Loudness scan: {c} songs done so far");
-                        self.repository.flush();
+                    match unit {
+                        ScanUnit::Song(file_key) => self.analyse_song(file_key, &progress),
+                        ScanUnit::Album { id, song_keys } => self.analyse_album(id, song_keys, &progress),
+                        ScanUnit::Fingerprint(file_key) => self.fingerprint_song(file_key, &progress),
                     }
                 });
             });
 
             self.repository.flush();
-            let total = scanned.load(Ordering::Relaxed);
-            info!("Loudness scan: pass complete, {total} songs analysed");
-            if total == 0 {
-                warn!("Loudness scan: no files could be read (files unavailable?), sleeping 60s before retry");
-                thread::sleep(Duration::from_mins(1));
+            let analysed = progress.analysed.load(Ordering::Relaxed);
+            let already = progress.already_analysed.load(Ordering::Relaxed);
+            let fingerprinted = progress.fingerprinted.load(Ordering::Relaxed);
+            let unreadable = progress.unreadable.load(Ordering::Relaxed);
+            info!(
+                "Loudness scan: pass complete, {analysed} songs analysed, {already} already analysed, \
+                 {fingerprinted} fingerprinted, {unreadable} songs/albums unreadable"
+            );
+            if analysed == 0 && already == 0 && fingerprinted == 0 && unreadable > 0 {
+                warn!("Loudness scan: no files could be read (files unavailable?), skipping them until they change");
+            }
+        }
+    }
+
+    /// Albums without album loudness or with unanalysed songs are measured as
+    /// a whole; unanalysed songs outside any album one by one. Measured songs
+    /// without a fingerprint are only fingerprinted.
+    fn pending_units(&self) -> Vec<ScanUnit> {
+        let (pending, analysed): (Vec<Song>, Vec<Song>) = self
+            .song_repository
+            .find_all()
+            .into_iter()
+            .partition(|s| !self.repository.contains(&s.file));
+
+        let mut album_ids: HashSet<String> = self
+            .album_repository
+            .find_all()
+            .into_iter()
+            .map(|album| album.id)
+            .filter(|id| !is_singletons_id(id) && !self.repository.contains_album(id))
+            .collect();
+        album_ids.extend(pending.iter().filter_map(album_id_for_song));
+
+        let mut in_album: HashSet<String> = HashSet::new();
+        let mut units: Vec<ScanUnit> = album_ids
+            .into_iter()
+            .filter_map(|id| {
+                let album = self.album_repository.find_by_id(&id)?;
+                if album.song_keys.is_empty() {
+                    return None;
+                }
+                in_album.extend(album.song_keys.iter().cloned());
+                Some(ScanUnit::Album {
+                    id,
+                    song_keys: album.song_keys,
+                })
+            })
+            .collect();
+        units.extend(
+            pending
+                .into_iter()
+                .filter(|s| !in_album.contains(&s.file))
+                .map(|s| ScanUnit::Song(s.file)),
+        );
+        units.extend(
+            analysed
+                .into_iter()
+                .filter(|s| !in_album.contains(&s.file) && self.repository.get_track(&s.file).is_some())
+                .filter(|s| !self.repository.contains_fingerprint(&s.file))
+                .map(|s| ScanUnit::Fingerprint(s.file)),
+        );
+        units
+    }
+
+    fn analyse_song(&self, file_key: &str, progress: &Progress) {
+        if self.is_playing.load(Ordering::Relaxed) {
+            debug!("Loudness scan: skipping {file_key} (playback started)");
+            return;
+        }
+        let Some(full_path) = self.resolve_path(file_key) else {
+            warn!("Loudness scan: file not found in any music directory: {file_key}");
+            self.record_failure(file_key, std::slice::from_ref(&file_key.to_string()), progress);
+            return;
+        };
+        debug!("Loudness scan: analysing {file_key}");
+        let analysis = LoudnessAnalyzer::measure_file(&full_path);
+        self.save_track(file_key, analysis.as_ref(), progress);
+    }
+
+    /// Takes the fingerprint of a song measured before fingerprints were
+    /// recorded. An empty one is stored when none comes out, so the song is
+    /// not decoded again.
+    fn fingerprint_song(&self, file_key: &str, progress: &Progress) {
+        if self.is_playing.load(Ordering::Relaxed) {
+            debug!("Loudness scan: skipping fingerprint of {file_key} (playback started)");
+            return;
+        }
+        let Some(full_path) = self.resolve_path(file_key) else {
+            warn!("Loudness scan: file not found in any music directory: {file_key}");
+            self.record_failure(file_key, std::slice::from_ref(&file_key.to_string()), progress);
+            return;
+        };
+        debug!("Loudness scan: fingerprinting {file_key}");
+        let analysis = LoudnessAnalyzer::measure_file(&full_path);
+        let fingerprint = analysis.as_ref().map_or(&[][..], |a| a.fingerprint.as_slice());
+        if let Err(e) = self.repository.save_fingerprint(file_key, fingerprint) {
+            warn!("Failed to persist fingerprint for '{file_key}': {e}");
+        }
+        progress.fingerprinted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decodes every track of the album (also those already analysed — the
+    /// album gate needs all of their meters) and stores the album loudness.
+    /// Skipped without decoding anything when a track file is missing.
+    fn analyse_album(&self, album_id: &str, song_keys: &[String], progress: &Progress) {
+        let Some(paths) = song_keys.iter().map(|key| self.resolve_path(key)).collect::<Option<Vec<_>>>() else {
+            warn!("Loudness scan: album {album_id} has tracks missing from the music directories, skipping");
+            self.record_failure(album_id, song_keys, progress);
+            return;
+        };
+        debug!("Loudness scan: analysing album {album_id} ({} tracks)", song_keys.len());
+        let mut tracks = Vec::with_capacity(paths.len());
+        for (file_key, path) in song_keys.iter().zip(paths) {
+            if self.stop.load(Ordering::Relaxed) {
+                return;
+            }
+            if self.is_playing.load(Ordering::Relaxed) {
+                debug!("Loudness scan: abandoning album {album_id} (playback started)");
+                return;
+            }
+            let analysis = LoudnessAnalyzer::measure_file(&path);
+            if self.repository.contains(file_key) {
+                self.save_fingerprint(file_key, analysis.as_ref());
+                progress.already_analysed.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.save_track(file_key, analysis.as_ref(), progress);
+            }
+            tracks.extend(analysis);
+        }
+
+        let result = if let Some((lufs, true_peak)) = LoudnessAnalyzer::album_loudness(&tracks) {
+            debug!("Loudness scan: album {album_id} => {lufs:.2} LUFS, true peak {true_peak:.2} dBTP");
+            self.repository.save_album_loudness(
+                album_id,
+                AlbumLoudness {
+                    loudness: hundredths(lufs),
+                    true_peak: hundredths(true_peak),
+                },
+            )
+        } else {
+            warn!("Loudness scan: no album loudness for {album_id} (DSD or unsupported)");
+            self.repository.save_album_unavailable(album_id)
+        };
+        if let Err(e) = result {
+            warn!("Failed to persist album loudness for '{album_id}': {e}");
+        }
+    }
+
+    fn save_track(&self, file_key: &str, analysis: Option<&TrackAnalysis>, progress: &Progress) {
+        if let Some(a) = analysis {
+            debug!(
+                "Loudness scan: {file_key} => {:.2} LUFS, peak {:.2} dBFS, true peak {:.2} dBTP, LRA {:.1} LU",
+                a.integrated_lufs, a.sample_peak_dbfs, a.true_peak_dbtp, a.loudness_range_lu
+            );
+            let measured = TrackLoudness {
+                loudness: hundredths(a.integrated_lufs),
+                sample_peak: hundredths(a.sample_peak_dbfs),
+                true_peak: hundredths(a.true_peak_dbtp),
+                lra: hundredths(a.loudness_range_lu),
+            };
+            if let Err(e) = self.repository.save_loudness(file_key, measured) {
+                warn!("Failed to persist loudness for '{file_key}': {e}");
+            }
+            self.save_fingerprint(file_key, analysis);
+        } else {
+            warn!("Loudness scan: no loudness for {file_key} (DSD or unsupported)");
+            if let Err(e) = self.repository.save_unavailable(file_key) {
+                warn!("Failed to persist loudness sentinel for '{file_key}': {e}");
             }
         }
+
+        let c = progress.analysed.fetch_add(1, Ordering::Relaxed) + 1;
+        if c.is_multiple_of(50) {
+            info!("Loudness scan: {c} songs done so far");
+            self.repository.flush();
+        }
+    }
+
+    fn save_fingerprint(&self, file_key: &str, analysis: Option<&TrackAnalysis>) {
+        if let Some(a) = analysis
+            && let Err(e) = self.repository.save_fingerprint(file_key, &a.fingerprint)
+        {
+            warn!("Failed to persist fingerprint for '{file_key}': {e}");
+        }
+    }
+
+    /// Remembers an unreadable song or album so later passes skip it until
+    /// one of its files appears, disappears or is modified.
+    fn record_failure(&self, key: &str, song_keys: &[String], progress: &Progress) {
+        progress.unreadable.fetch_add(1, Ordering::Relaxed);
+        let state = self.file_state(song_keys);
+        self.failed.lock().expect("lock poisoned").insert(key.to_string(), state);
+    }
+
+    fn failed_before(&self, unit: &ScanUnit) -> bool {
+        let (key, song_keys) = match unit {
+            ScanUnit::Song(file_key) | ScanUnit::Fingerprint(file_key) => (file_key, std::slice::from_ref(file_key)),
+            ScanUnit::Album { id, song_keys } => (id, song_keys.as_slice()),
+        };
+        let failed = self.failed.lock().expect("lock poisoned");
+        failed.get(key).is_some_and(|state| *state == self.file_state(song_keys))
+    }
+
+    /// Modification time of each file, `None` for files that cannot be found.
+    fn file_state(&self, song_keys: &[String]) -> Vec<Option<SystemTime>> {
+        song_keys
+            .iter()
+            .map(|key| self.resolve_path(key).and_then(|p| p.metadata().and_then(|m| m.modified()).ok()))
+            .collect()
     }
+
+    fn resolve_path(&self, file_key: &str) -> Option<PathBuf> {
+        self.music_dirs
+            .iter()
+            .map(|dir| PathBuf::from(format!("{dir}/{file_key}")))
+            .find(|p| p.exists())
+    }
+}
+
+/// Per-pass counters: newly analysed songs, album tracks decoded again only
+/// for the album gate, songs decoded again only for their fingerprint, and
+/// songs or albums with missing files.
+#[derive(Default)]
+struct Progress {
+    analysed: AtomicU32,
+    already_analysed: AtomicU32,
+    fingerprinted: AtomicU32,
+    unreadable: AtomicU32,
+}
+
+/// One piece of scan work: a song outside any album, a whole album, or a
+/// measured song that lacks its fingerprint.
+enum ScanUnit {
+    Song(String),
+    Album { id: String, song_keys: Vec<String> },
+    Fingerprint(String),
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn hundredths(value: f64) -> i32 {
+    (value * 100.0).round() as i32
 }
@@ -0,0 +1,246 @@
+//! Writes measured loudness back into the files as `REPLAYGAIN_*` tags.
+//!
+//! Opt-in via `StorageCommand::WriteLoudnessTags`. Gains are relative to the
+//! -18 LUFS reference level (RG 2.0) and peaks are the linear true peak;
+//! album values are written when the album has been measured as a whole.
+//! FLAC gets Vorbis comments, MP3 `ID3v2` `TXXX` frames and M4A freeform
+//! atoms (see [`crate::tag_editor`]). Files under a music directory that is
+//! not writable are never opened for writing, and a dry run only reports.
+//! Songs whose tags already hold the measured values are skipped, so running
+//! it again after a scan only touches new songs.
+
+use std::path::Path;
+
+use lofty::tag::ItemKey;
+use log::{info, warn};
+
+use api_models::{
+    player::Song,
+    state::{LoudnessTagChange, LoudnessTagReport},
+};
+
+use crate::album_identity::album_id_for_song;
+use crate::ports::{
+    loudness_repository::{AlbumLoudness, ArcLoudnessRepository, TrackLoudness},
+    song_repository::ArcSongRepository,
+};
+use crate::tag_editor;
+
+/// RG 2.0 reference loudness.
+const REFERENCE_LUFS: f64 = -18.0;
+/// Changes and failures listed in a report; the counts cover everything.
+const REPORT_LIMIT: usize = 500;
+
+const TRACK_GAIN: &str = "REPLAYGAIN_TRACK_GAIN";
+const TRACK_PEAK: &str = "REPLAYGAIN_TRACK_PEAK";
+const ALBUM_GAIN: &str = "REPLAYGAIN_ALBUM_GAIN";
+const ALBUM_PEAK: &str = "REPLAYGAIN_ALBUM_PEAK";
+/// Formats with an established place for these tags.
+const SUPPORTED_EXTENSIONS: [&str; 4] = ["flac", "mp3", "m4a", "mp4"];
+
+pub struct LoudnessTagWriter {
+    song_repository: ArcSongRepository,
+    loudness_repository: ArcLoudnessRepository,
+}
+
+impl LoudnessTagWriter {
+    #[must_use]
+    pub fn new(song_repository: ArcSongRepository, loudness_repository: ArcLoudnessRepository) -> Self {
+        Self {
+            song_repository,
+            loudness_repository,
+        }
+    }
+
+    /// Tags every analysed song. `music_dirs` lists the library roots in
+    /// configuration order, each with whether it may be written to.
+    pub fn run(&self, music_dirs: &[(String, bool)], dry_run: bool) -> LoudnessTagReport {
+        let mut report = LoudnessTagReport {
+            dry_run,
+            ..Default::default()
+        };
+        for mut song in self.song_repository.find_all() {
+            if !is_supported(&song.file) {
+                report.unsupported += 1;
+                continue;
+            }
+            let Some(track) = self.loudness_repository.get_track(&song.file) else {
+                report.not_analysed += 1;
+                continue;
+            };
+            let album = album_id_for_song(&song).and_then(|id| self.loudness_repository.get_album(&id));
+            let change = tag_values(&song.file, track, album);
+            if tag_items(&change)
+                .iter()
+                .all(|(key, value)| existing_tag(&song, key) == Some(*value))
+            {
+                report.unchanged += 1;
+                continue;
+            }
+            let Some((dir, writable)) = music_dirs.iter().find(|(dir, _)| Path::new(dir).join(&song.file).exists()) else {
+                push_failure(&mut report, &song.file, "file not found");
+                continue;
+            };
+            if !writable {
+                report.read_only += 1;
+                continue;
+            }
+            if !dry_run {
+                if let Err(e) = write_tags(&Path::new(dir).join(&song.file), &change) {
+                    warn!("Failed to write ReplayGain tags to '{}': {e}", song.file);
+                    push_failure(&mut report, &song.file, &e.to_string());
+                    continue;
+                }
+                update_song_tags(&mut song, &change);
+                if let Err(e) = self.song_repository.save(&song) {
+                    warn!("Failed to update tags of '{}': {e}", song.file);
+                }
+            }
+            report.changed += 1;
+            if report.changes.len() < REPORT_LIMIT {
+                report.changes.push(change);
+            }
+        }
+        info!(
+            "ReplayGain tags{}: {} changed, {} unchanged, {} not analysed, {} unsupported, {} read-only, {} failed",
+            if dry_run { " (dry run)" } else { "" },
+            report.changed,
+            report.unchanged,
+            report.not_analysed,
+            report.unsupported,
+            report.read_only,
+            report.failed.len()
+        );
+        report
+    }
+}
+
+fn is_supported(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn tag_values(file: &str, track: TrackLoudness, album: Option<AlbumLoudness>) -> LoudnessTagChange {
+    LoudnessTagChange {
+        file: file.to_owned(),
+        track_gain: format_gain(track.loudness),
+        track_peak: format_peak(track.true_peak),
+        album_gain: album.map(|a| format_gain(a.loudness)),
+        album_peak: album.map(|a| format_peak(a.true_peak)),
+    }
+}
+
+/// Gain to the reference from a loudness in hundredths of a LUFS, e.g. `-3.42 dB`.
+fn format_gain(loudness: i32) -> String {
+    format!("{:+.2} dB", REFERENCE_LUFS - f64::from(loudness) / 100.0)
+}
+
+/// Linear peak from a true peak in hundredths of a dBTP.
+fn format_peak(true_peak: i32) -> String {
+    format!("{:.6}", 10f64.powf(f64::from(true_peak) / 2000.0))
+}
+
+fn tag_items(change: &LoudnessTagChange) -> Vec<(&'static str, &str)> {
+    let mut items = vec![(TRACK_GAIN, change.track_gain.as_str()), (TRACK_PEAK, change.track_peak.as_str())];
+    if let (Some(gain), Some(peak)) = (&change.album_gain, &change.album_peak) {
+        items.push((ALBUM_GAIN, gain.as_str()));
+        items.push((ALBUM_PEAK, peak.as_str()));
+    }
+    items
+}
+
+/// Raw tag keys differ by format (`REPLAYGAIN_TRACK_GAIN`, `TXXX:replaygain_track_gain`…).
+fn matches_key(raw: &str, key: &str) -> bool {
+    raw.len()
+        .checked_sub(key.len())
+        .and_then(|start| raw.get(start..))
+        .is_some_and(|tail| tail.eq_ignore_ascii_case(key))
+}
+
+fn existing_tag<'a>(song: &'a Song, key: &str) -> Option<&'a str> {
+    song.tags
+        .iter()
+        .find(|(raw, _)| matches_key(raw, key))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Mirrors a successful write in the stored song, so playback sees the new
+/// tags before the next rescan.
+fn update_song_tags(song: &mut Song, change: &LoudnessTagChange) {
+    for (key, value) in tag_items(change) {
+        song.tags.retain(|raw, _| !matches_key(raw, key));
+        song.tags.insert(key.to_owned(), value.to_owned());
+    }
+}
+
+fn push_failure(report: &mut LoudnessTagReport, file: &str, reason: &str) {
+    if report.failed.len() < REPORT_LIMIT {
+        report.failed.push((file.to_owned(), reason.to_owned()));
+    }
+}
+
+fn write_tags(path: &Path, change: &LoudnessTagChange) -> anyhow::Result<()> {
+    tag_editor::edit_tags(path, |tag| {
+        for (key, value) in tag_items(change) {
+            tag.insert_text(item_key(key), value.to_owned());
+        }
+    })
+}
+
+fn item_key(key: &str) -> ItemKey {
+    match key {
+        TRACK_GAIN => ItemKey::ReplayGainTrackGain,
+        TRACK_PEAK => ItemKey::ReplayGainTrackPeak,
+        ALBUM_GAIN => ItemKey::ReplayGainAlbumGain,
+        _ => ItemKey::ReplayGainAlbumPeak,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_gain_and_peak_like_replaygain() {
+        assert_eq!(format_gain(-1400), "-4.00 dB");
+        assert_eq!(format_gain(-2342), "+5.42 dB");
+        assert_eq!(format_peak(0), "1.000000");
+        assert_eq!(format_peak(-600), "0.501187");
+    }
+
+    #[test]
+    fn album_values_are_written_only_when_measured() {
+        let track = TrackLoudness {
+            loudness: -1800,
+            ..Default::default()
+        };
+        let change = tag_values("a.flac", track, None);
+        assert_eq!(tag_items(&change).len(), 2);
+        let album = AlbumLoudness {
+            loudness: -1900,
+            true_peak: -100,
+        };
+        let change = tag_values("a.flac", track, Some(album));
+        assert_eq!(change.album_gain.as_deref(), Some("+1.00 dB"));
+        assert_eq!(tag_items(&change).len(), 4);
+    }
+
+    #[test]
+    fn existing_tags_match_whatever_the_raw_key() {
+        let mut song = Song::default();
+        song.tags.insert("TXXX:replaygain_track_gain".to_owned(), "+1.00 dB".to_owned());
+        assert_eq!(existing_tag(&song, TRACK_GAIN), Some("+1.00 dB"));
+        let change = LoudnessTagChange {
+            track_gain: "-2.00 dB".to_owned(),
+            track_peak: "0.900000".to_owned(),
+            ..Default::default()
+        };
+        update_song_tags(&mut song, &change);
+        assert_eq!(song.tags.len(), 2);
+        assert_eq!(song.tags.get(TRACK_GAIN).map(String::as_str), Some("-2.00 dB"));
+        assert!(is_supported("x/Song.M4A"));
+        assert!(!is_supported("x/song.ogg"));
+    }
+}
@@ -2,21 +2,32 @@
 //!
 //! [`MetadataService`] walks the configured music directories, probes each
 //! file with Symphonia (plus the custom APE/DSF/SACD readers), extracts tags
-//! and artwork (written to `artwork/<uuid>`, referenced by `Song.image_id`)
-//! and fills the
-//! song/album repositories. Scans run on a background thread guarded by
-//! `scan_running`; progress streams to clients as `MetadataSongScan*` events.
+//! and artwork and fills the song/album repositories (and the works index
+//! for songs with a composer and a work tag). A song's picture
+//! (`Song.image_id`, see [`crate::artwork`]) is the one uploaded for its
+//! album, else the embedded one, else a folder image named in
+//! `artwork_file_names`; uploads are kept in the `album_artwork` keyspace so
+//! they survive rescans, and each scan ends by removing artwork no song or
+//! playlist refers to.
+//! Scans run on a background thread guarded by `scan_running`; progress
+//! streams to clients as `MetadataSongScan*` events. Migrations that need
+//! every song file read again run the same way
+//! ([`MetadataService::run_library_migrations`]).
 //! SACD ISOs expand into one virtual song per track (`#SACD_` marker in the
 //! file key). Also answers browse/search queries and computes
 //! [`api_models::stat::LibraryStats`].
+//!
+//! Play counters are kept for the whole household and, for the profile
+//! currently listening (see [`MetadataService::set_listening_profile`]),
+//! once more for that profile. Likes belong to the profile that gave them.
 
 use std::{
     cmp::Reverse,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
         atomic::{AtomicBool, Ordering},
     },
     time,
@@ -24,7 +35,7 @@ use std::{
 
 use anyhow::{Error, Result};
 use chrono::{DateTime, Utc};
-use fjall::{Database, KeyspaceCreateOptions, PersistMode};
+use fjall::{Database, Keyspace, KeyspaceCreateOptions, PersistMode};
 use log::{debug, info, warn};
 use symphonia::core::{
     formats::{FormatOptions, probe::Hint},
@@ -36,51 +47,88 @@ use walkdir::WalkDir;
 
 use api_models::{
     common::MetadataLibraryItem,
-    player::Song,
+    player::{Song, TagFields},
     settings::MetadataStoreSettings,
     stat::{LibraryStats, PlayItemStatistics},
     state::StateChangeEvent,
 };
 
+use crate::album_identity::{album_id_for_song, name_id_for_song};
+use crate::artwork::{ARTWORK_DIR, ArtworkStore, find_folder_image};
 use crate::audio_metadata_extractor::AudioMetadataExtractor;
+use crate::genre_utils::split_credits;
+use crate::play_statistic_repository::ProfilePlayStatisticsRepository;
+use crate::playlist_service::PlaylistService;
 use crate::ports::{
-    album_repository::ArcAlbumRepository, play_statistics_repository::ArcPlayStatisticsRepository, song_repository::ArcSongRepository,
+    album_repository::ArcAlbumRepository,
+    play_statistics_repository::{ArcPlayStatisticsRepository, PlayStatisticsRepository},
+    song_repository::ArcSongRepository,
+    work_repository::ArcWorkRepository,
 };
 use crate::sacd_bundle::{SACD_TRACK_MARKER, detect_sector_mode, read_areas, read_tracks};
-
-const ARTWORK_DIR: &str = "artwork";
+use crate::tag_editor;
 
 pub struct MetadataService {
     settings: RwLock<MetadataStoreSettings>,
     scan_running: AtomicBool,
     song_repository: ArcSongRepository,
     album_repository: ArcAlbumRepository,
+    work_repository: ArcWorkRepository,
     statistic_repository: ArcPlayStatisticsRepository,
+    /// The counters of every profile, see [`ProfilePlayStatisticsRepository`].
+    profile_statistic_repository: ArcPlayStatisticsRepository,
+    /// Whose plays and skips count, besides the household's.
+    listening_profile: RwLock<Option<String>>,
     db: Arc<Database>,
+    artwork: ArtworkStore,
+    /// Album id → artwork id uploaded for it.
+    album_artwork: Keyspace,
+    /// Folder image found for each directory during the current scan.
+    folder_artwork: Mutex<HashMap<PathBuf, Option<String>>>,
+    /// Playlist images are artwork too and must survive its cleanup.
+    playlist_service: Arc<PlaylistService>,
 }
 
 impl MetadataService {
     const MIGRATION_MARKER: &'static [u8] = b"cleanup_orphaned_albums_v3_5_5";
+    const WORKS_MIGRATION_MARKER: &'static [u8] = b"index_works_v1";
+    const ALBUM_TRACKS_MIGRATION_MARKER: &'static [u8] = b"album_tracks_v1";
+    /// `v1` keyed albums by the stored songs, whose `MusicBrainz` ids older
+    /// scans had dropped.
+    const ALBUM_IDENTITY_MIGRATION_MARKER: &'static [u8] = b"album_identity_v2";
 
     pub fn new(
         db: Arc<Database>,
         settings: &MetadataStoreSettings,
         song_repository: ArcSongRepository,
         album_repository: ArcAlbumRepository,
+        work_repository: ArcWorkRepository,
         statistic_repository: ArcPlayStatisticsRepository,
+        profile_statistic_repository: ArcPlayStatisticsRepository,
+        playlist_service: Arc<PlaylistService>,
     ) -> Result<Arc<Self>> {
         let settings = settings.clone();
 
         Self::run_migration_if_needed(&db, &song_repository, &album_repository);
+        Self::order_album_tracks_if_needed(&db, &song_repository, &album_repository)?;
+        let album_artwork = db.keyspace("album_artwork", KeyspaceCreateOptions::default)?;
 
-        Ok(Arc::new(Self {
+        let service = Self {
             settings: RwLock::new(settings),
             scan_running: AtomicBool::new(false),
             song_repository,
             album_repository,
+            work_repository,
             statistic_repository,
+            profile_statistic_repository,
+            listening_profile: RwLock::new(None),
             db,
-        }))
+            artwork: ArtworkStore::new(ARTWORK_DIR),
+            album_artwork,
+            folder_artwork: Mutex::new(HashMap::new()),
+            playlist_service,
+        };
+        Ok(Arc::new(service))
     }
 
     fn run_migration_if_needed(db: &Database, song_repository: &ArcSongRepository, album_repository: &ArcAlbumRepository) {
@@ -108,16 +156,211 @@ impl MetadataService {
         }
     }
 
+    /// Records disc and track numbers in albums stored before they were
+    /// kept, which also puts their songs in disc order.
+    fn order_album_tracks_if_needed(
+        db: &Database,
+        song_repository: &ArcSongRepository,
+        album_repository: &ArcAlbumRepository,
+    ) -> Result<()> {
+        let migrations = db.keyspace("_migrations", KeyspaceCreateOptions::default)?;
+        if migrations.get(Self::ALBUM_TRACKS_MIGRATION_MARKER)?.is_some() {
+            return Ok(());
+        }
+        info!("Running one-shot migration: order album tracks by disc");
+        for song in song_repository.find_all() {
+            album_repository.update_from_song(song)?;
+        }
+        migrations.insert(Self::ALBUM_TRACKS_MIGRATION_MARKER, b"1")?;
+        Ok(())
+    }
+
+    /// Indexes classical works and files albums under their `MusicBrainz`
+    /// ids for libraries scanned before either existed. Their stored songs
+    /// lack those tags, so every song file is read again first, which takes
+    /// long on a large library; the server runs this on a background thread
+    /// behind the scan guard. Albums and works keep their old keys until all
+    /// files are read. Retried at the next start when no file can be read.
+    pub fn run_library_migrations(&self, state_changes_sender: &Sender<StateChangeEvent>) {
+        let pending = self
+            .migration_done(Self::ALBUM_IDENTITY_MIGRATION_MARKER)
+            .and_then(|albums_done| Ok((!albums_done, !self.migration_done(Self::WORKS_MIGRATION_MARKER)?)));
+        let (rekey_albums, index_works) = match pending {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("Migration: failed to read the migration markers: {e}");
+                return;
+            }
+        };
+        if !rekey_albums && !index_works {
+            return;
+        }
+        if self.scan_running.swap(true, Ordering::Relaxed) {
+            warn!("Migration: a library scan is running, updating the library at the next start");
+            return;
+        }
+        if let Err(e) = state_changes_sender.send(StateChangeEvent::MetadataSongScanStarted) {
+            warn!("Failed to send scan started event: {e}");
+        }
+        if rekey_albums {
+            info!("Running one-shot migration: key albums by MusicBrainz id");
+        }
+        if index_works {
+            info!("Running one-shot migration: index classical works");
+        }
+        let start_time = time::Instant::now();
+        let message = match self.reindex_library(rekey_albums) {
+            Ok(true) => format!("Library updated in {} seconds", start_time.elapsed().as_secs()),
+            Ok(false) => {
+                warn!("Migration: no song file could be read, updating the library at the next start");
+                "No song file could be read, the library is updated at the next start".to_owned()
+            }
+            Err(e) => {
+                warn!("Migration: failed to update the library: {e}");
+                format!("Library update failed: {e}")
+            }
+        };
+        self.scan_running.store(false, Ordering::Relaxed);
+        if let Err(e) = state_changes_sender.send(StateChangeEvent::MetadataSongScanFinished(message)) {
+            warn!("Failed to send scan finished event: {e}");
+        }
+        if let Err(e) = self.db.persist(PersistMode::SyncData) {
+            warn!("Failed to persist database after migration: {e}");
+        }
+    }
+
+    fn migration_done(&self, marker: &[u8]) -> Result<bool> {
+        let migrations = self.db.keyspace("_migrations", KeyspaceCreateOptions::default)?;
+        Ok(migrations.get(marker)?.is_some())
+    }
+
+    /// Rebuilds the works index, and with `rekey_albums` the albums, from
+    /// the songs read again. Uploaded covers and album loudness follow their
+    /// album to its new key. Loudness of albums merged into one is dropped,
+    /// so the next loudness scan measures the merged album. Returns `false`
+    /// when no song file could be read.
+    fn reindex_library(&self, rekey_albums: bool) -> Result<bool> {
+        if !self.read_library_tags() {
+            return Ok(false);
+        }
+        let songs = self.song_repository.find_all();
+        self.work_repository.delete_all();
+        if !rekey_albums {
+            for song in &songs {
+                self.work_repository.update_from_song(song)?;
+            }
+            let migrations = self.db.keyspace("_migrations", KeyspaceCreateOptions::default)?;
+            migrations.insert(Self::WORKS_MIGRATION_MARKER, b"1")?;
+            return Ok(true);
+        }
+        let mut old_ids: HashMap<String, HashSet<String>> = HashMap::new();
+        for song in &songs {
+            if let (Some(old_id), Some(new_id)) = (name_id_for_song(song), album_id_for_song(song))
+                && old_id != new_id
+            {
+                old_ids.entry(new_id).or_default().insert(old_id);
+            }
+        }
+        let album_loudness = self.db.keyspace("album_loudness", KeyspaceCreateOptions::default)?;
+        for (new_id, old_ids) in &old_ids {
+            for old_id in old_ids {
+                if let Some(image_id) = self.album_artwork.get(old_id.as_str())? {
+                    self.album_artwork.insert(new_id.as_str(), image_id)?;
+                    self.album_artwork.remove(old_id.as_str())?;
+                }
+                if let Some(loudness) = album_loudness.get(old_id.as_str())? {
+                    if old_ids.len() == 1 {
+                        album_loudness.insert(new_id.as_str(), loudness)?;
+                    }
+                    album_loudness.remove(old_id.as_str())?;
+                }
+            }
+        }
+        self.album_repository.delete_all();
+        for mut song in songs {
+            // Read again before its cover moved, the song shows its own picture.
+            let uploaded = album_id_for_song(&song)
+                .and_then(|album_id| self.album_artwork.get(album_id).ok().flatten())
+                .map(|id| String::from_utf8_lossy(&id).into_owned())
+                .filter(|id| self.artwork.exists(id));
+            if uploaded.is_some() && uploaded != song.image_id {
+                song.image_id = uploaded;
+                self.song_repository.save(&song)?;
+            }
+            self.work_repository.update_from_song(&song)?;
+            self.album_repository.update_from_song(song)?;
+        }
+        let migrations = self.db.keyspace("_migrations", KeyspaceCreateOptions::default)?;
+        migrations.insert(Self::ALBUM_IDENTITY_MIGRATION_MARKER, b"1")?;
+        migrations.insert(Self::WORKS_MIGRATION_MARKER, b"1")?;
+        Ok(true)
+    }
+
+    /// Reads the tags of every stored song from its file again and stores
+    /// the song, leaving the album and works indexes as they are. Songs whose
+    /// file cannot be read keep their stored tags. Returns `false` when there
+    /// are songs but none was read.
+    fn read_library_tags(&self) -> bool {
+        let settings = self.settings.read().expect("settings lock poisoned").clone();
+        let songs = self.song_repository.find_all();
+        info!("Reading the tags of {} songs again", songs.len());
+        let mut read = 0;
+        for (i, song) in songs.iter().enumerate() {
+            let path = settings
+                .library_directories()
+                .iter()
+                .map(|dir| Path::new(dir).join(&song.file))
+                .find(|p| p.is_file());
+            let stored = path.map(|path| {
+                self.read_file(&path, &settings)?
+                    .iter()
+                    .try_for_each(|song| self.song_repository.save(song).map_err(Error::from))
+            });
+            match stored {
+                Some(Ok(())) => read += 1,
+                Some(Err(e)) => warn!("Failed to read the tags of '{}' again: {e}", song.file),
+                None => debug!("Not reading the tags of '{}' again, the file is missing", song.file),
+            }
+            if (i + 1) % 500 == 0 {
+                self.song_repository.flush();
+                info!("Read the tags of {} of {} songs again", i + 1, songs.len());
+            }
+        }
+        self.song_repository.flush();
+        songs.is_empty() || read > 0
+    }
+
     pub fn update_settings(&self, settings: MetadataStoreSettings) {
         *self.settings.write().expect("settings lock poisoned") = settings;
     }
 
-    pub fn effective_directories(&self) -> Vec<String> {
-        self.settings.read().expect("settings lock poisoned").effective_directories()
+    /// The music directories and the radio recordings directory.
+    pub fn library_directories(&self) -> Vec<String> {
+        self.settings.read().expect("settings lock poisoned").library_directories()
     }
 
-    pub fn get_favorite_radio_stations(&self) -> Vec<String> {
-        self.statistic_repository
+    /// The profile's counters, or the household's for `None`.
+    fn statistics(&self, profile: Option<&str>) -> ArcPlayStatisticsRepository {
+        match profile {
+            Some(profile) => Arc::new(ProfilePlayStatisticsRepository::new(
+                self.profile_statistic_repository.clone(),
+                profile,
+            )),
+            None => self.statistic_repository.clone(),
+        }
+    }
+
+    /// Credits the plays and skips from now on to `profile` as well.
+    pub fn set_listening_profile(&self, profile: Option<String>) {
+        *self.listening_profile.write().expect("profile lock poisoned") = profile;
+    }
+
+    pub fn get_song_statistics(&self, profile: Option<&str>, media_item_id: &str) -> Option<PlayItemStatistics> {
+        self.statistics(profile).find_by_id(media_item_id)
+    }
+
+    pub fn get_favorite_radio_stations(&self, profile: Option<&str>) -> Vec<String> {
+        self.statistics(profile)
             .find_by_key_prefix("radio_uuid_")
             .iter()
             .filter(|stat| stat.liked_count > 0)
@@ -125,8 +368,8 @@ impl MetadataService {
             .collect()
     }
 
-    pub fn get_most_played_songs(&self, limit: usize) -> Vec<Song> {
-        let mut stats = self.statistic_repository.get_all();
+    pub fn get_most_played_songs(&self, profile: Option<&str>, limit: usize) -> Vec<Song> {
+        let mut stats = self.statistics(profile).get_all();
         stats.sort_by_key(|b| Reverse(b.play_count));
         stats
             .into_iter()
@@ -137,8 +380,8 @@ impl MetadataService {
             .collect()
     }
 
-    pub fn get_liked_songs(&self, limit: usize) -> Vec<Song> {
-        let mut stats = self.statistic_repository.get_all();
+    pub fn get_liked_songs(&self, profile: Option<&str>, limit: usize) -> Vec<Song> {
+        let mut stats = self.statistics(profile).get_all();
         stats.sort_by_key(|b| Reverse(b.liked_count));
         stats
             .into_iter()
@@ -149,15 +392,16 @@ impl MetadataService {
             .collect()
     }
 
-    pub fn get_library_stats(&self) -> LibraryStats {
+    /// Library totals; plays and likes are the profile's.
+    pub fn get_library_stats(&self, profile: Option<&str>) -> LibraryStats {
         let all_songs: Vec<Song> = self.song_repository.find_all();
         let total_songs = all_songs.len();
         let total_duration_secs = all_songs.iter().filter_map(|s| s.time).map(|d| d.as_secs()).sum();
 
         let mut genre_map: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
         for song in &all_songs {
-            if let Some(genre) = &song.genre {
-                *genre_map.entry(genre.clone()).or_insert(0) += 1;
+            for genre in song.credited_genres() {
+                *genre_map.entry(genre.to_owned()).or_insert(0) += 1;
             }
         }
         let mut top_genres: Vec<(String, usize)> = genre_map.into_iter().collect();
@@ -174,7 +418,7 @@ impl MetadataService {
             .map(|(decade, albums)| (decade, albums.len()))
             .collect();
 
-        let all_stats = self.statistic_repository.get_all();
+        let all_stats = self.statistics(profile).get_all();
         let total_plays = all_stats.iter().map(|s| s.play_count.max(0).cast_unsigned()).sum();
         let unique_songs_played = all_stats
             .iter()
@@ -199,36 +443,224 @@ impl MetadataService {
         }
     }
 
-    pub fn like_media_item(&self, media_item_id: &str) {
-        self.update_or_create_media_item_stat(media_item_id, |item| item.liked_count += 1);
+    pub fn like_media_item(&self, profile: Option<&str>, media_item_id: &str) {
+        update_or_create_media_item_stat(&*self.statistics(profile), media_item_id, |item| item.liked_count += 1);
     }
 
-    pub fn dislike_media_item(&self, media_item_id: &str) {
-        self.update_or_create_media_item_stat(media_item_id, |item| item.liked_count -= 1);
+    pub fn dislike_media_item(&self, profile: Option<&str>, media_item_id: &str) {
+        update_or_create_media_item_stat(&*self.statistics(profile), media_item_id, |item| item.liked_count -= 1);
     }
 
     pub fn increase_play_count(&self, media_item_id: &str) {
-        self.update_or_create_media_item_stat(media_item_id, |item| item.play_count += 1);
+        self.update_listening_stats(media_item_id, |item| item.play_count += 1);
     }
 
-    fn update_or_create_media_item_stat<J>(&self, media_item_id: &str, mut job: J)
-    where
-        J: FnMut(&mut PlayItemStatistics),
-    {
-        let stat = if let Some(mut stat) = self.statistic_repository.find_by_id(media_item_id) {
-            job(&mut stat);
-            stat
-        } else {
-            let mut stat = PlayItemStatistics {
-                play_item_id: media_item_id.to_string(),
-                ..Default::default()
-            };
-            job(&mut stat);
-            stat
+    pub fn increase_skip_count(&self, media_item_id: &str) {
+        self.update_listening_stats(media_item_id, |item| item.skipped_count += 1);
+    }
+
+    /// Counts for the household and for the listening profile, if any.
+    fn update_listening_stats(&self, media_item_id: &str, job: impl Fn(&mut PlayItemStatistics)) {
+        update_or_create_media_item_stat(&*self.statistic_repository, media_item_id, &job);
+        let profile = self.listening_profile.read().expect("profile lock poisoned").clone();
+        if let Some(profile) = profile {
+            update_or_create_media_item_stat(&*self.statistics(Some(&profile)), media_item_id, &job);
+        }
+    }
+
+    /// Writes `fields` into the song's file, then re-reads the file into the
+    /// stored song, moving it between albums when album or artist changed.
+    pub fn update_song_tags(&self, song_key: &str, fields: &TagFields) -> Result<Song> {
+        if self.scan_running.load(Ordering::Relaxed) {
+            return Err(Error::msg("A library scan is running, try again when it has finished"));
+        }
+        let path = self.tag_editable_path(song_key)?;
+        tag_editor::write_fields(&path, fields)?;
+        let song = self.reread_song(song_key, &path)?;
+        info!("Updated tags of {song_key}");
+        Ok(song)
+    }
+
+    /// Writes `fields` into every song of an album, skipping the per-song
+    /// `title` and `track`. Every file is checked before the first one is
+    /// written, then the files are written one at a time; an error after
+    /// that names the files left unchanged.
+    pub fn update_album_tags(&self, album_id: &str, fields: &TagFields) -> Result<Vec<Song>> {
+        if self.scan_running.load(Ordering::Relaxed) {
+            return Err(Error::msg("A library scan is running, try again when it has finished"));
+        }
+        let album = self
+            .album_repository
+            .find_by_id(album_id)
+            .ok_or_else(|| Error::msg(format!("Album not found: {album_id}")))?;
+        let fields = TagFields {
+            title: None,
+            track: None,
+            ..fields.clone()
         };
-        if let Err(e) = self.statistic_repository.save(&stat) {
-            warn!("Failed to save stats for '{media_item_id}': {e}");
+        let mut pending = Vec::with_capacity(album.song_keys.len());
+        for song_key in &album.song_keys {
+            let path = self
+                .tag_editable_path(song_key)
+                .map_err(|e| Error::msg(format!("No file was changed, {song_key} cannot be written: {e}")))?;
+            pending.push((song_key, path));
+        }
+
+        let mut updated = Vec::with_capacity(pending.len());
+        let mut unchanged = Vec::new();
+        let mut not_reread = Vec::new();
+        for (song_key, path) in pending {
+            if let Err(e) = tag_editor::write_fields(&path, &fields) {
+                warn!("Failed to update tags of '{song_key}': {e}");
+                unchanged.push(song_key.as_str());
+                continue;
+            }
+            match self.reread_song(song_key, &path) {
+                Ok(song) => {
+                    info!("Updated tags of {song_key}");
+                    updated.push(song);
+                }
+                Err(e) => {
+                    warn!("Failed to re-read '{song_key}' after updating its tags: {e}");
+                    not_reread.push(song_key.as_str());
+                }
+            }
+        }
+        if unchanged.is_empty() && not_reread.is_empty() {
+            return Ok(updated);
         }
+        let mut problems = Vec::new();
+        if !unchanged.is_empty() {
+            problems.push(format!("Tags of these files were not updated: {}.", unchanged.join(", ")));
+        }
+        if !not_reread.is_empty() {
+            problems.push(format!(
+                "These files were updated but show the new tags only after the next rescan: {}.",
+                not_reread.join(", ")
+            ));
+        }
+        Err(Error::msg(problems.join(" ")))
+    }
+
+    /// Full path of a song whose tags the editor can write.
+    fn tag_editable_path(&self, song_key: &str) -> Result<PathBuf> {
+        if self.song_repository.find_by_id(song_key).is_none() {
+            return Err(Error::msg(format!("Song not found: {song_key}")));
+        }
+        if song_key.contains(SACD_TRACK_MARKER) || !tag_editor::is_supported(song_key) {
+            return Err(Error::msg(format!("Tags of this format cannot be written: {song_key}")));
+        }
+        self.library_directories()
+            .iter()
+            .map(|dir| Path::new(dir).join(song_key))
+            .find(|p| p.exists())
+            .ok_or_else(|| Error::msg(format!("File not found: {song_key}")))
+    }
+
+    /// Replaces the stored song with a fresh read of its edited file, so
+    /// `Song.tags` and the album and work indexes match what was written.
+    fn reread_song(&self, song_key: &str, path: &Path) -> Result<Song> {
+        if let Some(old) = self.song_repository.find_by_id(song_key) {
+            self.album_repository.remove_from_song(&old)?;
+            self.work_repository.remove_from_song(&old)?;
+        }
+        let settings = self.settings.read().expect("settings lock poisoned").clone();
+        if let Err(e) = self.scan_single_file(path, &settings) {
+            if let Some(old) = self.song_repository.find_by_id(song_key) {
+                self.work_repository.update_from_song(&old)?;
+                self.album_repository.update_from_song(old)?;
+            }
+            return Err(e);
+        }
+        self.song_repository
+            .find_by_id(song_key)
+            .ok_or_else(|| Error::msg(format!("Song not found after re-reading: {song_key}")))
+    }
+
+    /// Uses the stored artwork `image_id` for the album and its songs, also
+    /// after later rescans. The replaced picture is deleted when nothing else
+    /// refers to it.
+    pub fn set_album_image(&self, album_id: &str, image_id: &str) -> Result<()> {
+        if self.scan_running.load(Ordering::Relaxed) {
+            return Err(Error::msg("A library scan is running, try again when it has finished"));
+        }
+        if !self.artwork.exists(image_id) {
+            return Err(Error::msg(format!("Artwork not found: {image_id}")));
+        }
+        let album = self
+            .album_repository
+            .find_by_id(album_id)
+            .ok_or_else(|| Error::msg(format!("Album not found: {album_id}")))?;
+        self.album_artwork.insert(album_id, image_id)?;
+        for song_key in &album.song_keys {
+            let Some(mut song) = self.song_repository.find_by_id(song_key) else {
+                continue;
+            };
+            song.image_id = Some(image_id.to_owned());
+            self.song_repository.save(&song)?;
+            self.album_repository.update_from_song(song)?;
+        }
+        info!("Set artwork {image_id} for album {album_id}");
+        self.collect_artwork_garbage();
+        Ok(())
+    }
+
+    /// Deletes artwork no song or playlist refers to, forgets uploads whose
+    /// picture is gone and creates missing thumbnails.
+    fn collect_artwork_garbage(&self) {
+        let mut referenced: HashSet<String> = self.song_repository.find_all().into_iter().filter_map(|s| s.image_id).collect();
+        referenced.extend(self.playlist_service.image_ids());
+        let removed = self.artwork.collect_garbage(&referenced);
+        if removed > 0 {
+            info!("Removed {removed} unreferenced artwork files");
+        }
+        for guard in self.album_artwork.iter() {
+            let Ok((album_id, image_id)) = guard.into_inner() else {
+                continue;
+            };
+            if !referenced.contains(String::from_utf8_lossy(&image_id).as_ref())
+                && let Err(e) = self.album_artwork.remove(album_id)
+            {
+                warn!("Failed to forget album artwork: {e}");
+            }
+        }
+        for id in &referenced {
+            if let Err(e) = self.artwork.ensure_thumbnail(id) {
+                warn!("Failed to create thumbnail of artwork {id}: {e}");
+            }
+        }
+    }
+
+    /// Picture for a song being scanned: the one uploaded for its album, the
+    /// embedded one, or the folder image of its directory (looked up once
+    /// per directory and scan).
+    fn artwork_for(&self, song: &Song, embedded: Option<&[u8]>, file_path: &Path, settings: &MetadataStoreSettings) -> Option<String> {
+        let uploaded = album_id_for_song(song)
+            .and_then(|album_id| self.album_artwork.get(album_id).ok().flatten())
+            .map(|id| String::from_utf8_lossy(&id).into_owned())
+            .filter(|id| self.artwork.exists(id));
+        if uploaded.is_some() {
+            return uploaded;
+        }
+        if let Some(data) = embedded {
+            match self.artwork.save(data) {
+                Ok(id) => return Some(id),
+                Err(e) => warn!("Error writing image file: {e}"),
+            }
+        }
+        let dir = file_path.parent()?;
+        let mut folders = self.folder_artwork.lock().expect("folder artwork lock poisoned");
+        folders
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let image = find_folder_image(dir, &settings.artwork_file_names)?;
+                std::fs::read(&image)
+                    .map_err(Error::from)
+                    .and_then(|data| self.artwork.save(&data))
+                    .inspect_err(|e| warn!("Failed to store folder image {}: {e}", image.display()))
+                    .ok()
+            })
+            .clone()
     }
 
     pub fn search_local_files_by_dir(&self, dir: &str) -> Vec<MetadataLibraryItem> {
@@ -293,13 +725,12 @@ impl MetadataService {
         if full_scan {
             self.song_repository.delete_all();
             self.album_repository.delete_all();
+            self.work_repository.delete_all();
         }
 
-        if !Path::new(ARTWORK_DIR).exists() {
-            _ = std::fs::create_dir(ARTWORK_DIR);
-        }
+        self.folder_artwork.lock().expect("folder artwork lock poisoned").clear();
         let (new_files, deleted_db_keys) = self.get_diff(&settings);
-        info!("Scanning directories: {:?}", settings.effective_directories());
+        info!("Scanning directories: {:?}", settings.library_directories());
         info!(
             "New files found: {} / Deleted files found: {}",
             new_files.len(),
@@ -310,10 +741,13 @@ impl MetadataService {
         if !full_scan {
             info!("Deleting {} files from database", deleted_db_keys.len());
             for db_key in &deleted_db_keys {
-                if let Some(song) = self.song_repository.find_by_id(db_key)
-                    && let Err(e) = self.album_repository.remove_from_song(&song)
-                {
-                    warn!("Failed to remove song '{db_key}' from album: {e}");
+                if let Some(song) = self.song_repository.find_by_id(db_key) {
+                    if let Err(e) = self.album_repository.remove_from_song(&song) {
+                        warn!("Failed to remove song '{db_key}' from album: {e}");
+                    }
+                    if let Err(e) = self.work_repository.remove_from_song(&song) {
+                        warn!("Failed to remove song '{db_key}' from works: {e}");
+                    }
                 }
                 if let Err(e) = self.song_repository.delete(db_key) {
                     warn!("Failed to delete song '{db_key}' from db: {e}");
@@ -325,6 +759,7 @@ impl MetadataService {
                 }
             }
         }
+        self.collect_artwork_garbage();
         if let Err(e) = state_changes_sender.send(StateChangeEvent::MetadataSongScanFinished(format!(
             "Music directory scan finished: {count} files scanned in {} seconds",
             start_time.elapsed().as_secs()
@@ -349,7 +784,7 @@ impl MetadataService {
     }
 
     fn full_path_to_database_key(settings: &MetadataStoreSettings, input: &str) -> String {
-        for dir in &settings.effective_directories() {
+        for dir in &settings.library_directories() {
             let mut prefix = dir.clone();
             if !prefix.ends_with('/') {
                 prefix.push('/');
@@ -369,7 +804,7 @@ impl MetadataService {
         let supported_exts = MetadataStoreSettings::default().supported_extensions;
         debug!("Supported extensions: {supported_exts:?}");
 
-        for music_dir in &settings.effective_directories() {
+        for music_dir in &settings.library_directories() {
             if !Path::new(music_dir).exists() {
                 warn!("Music directory does not exist, skipping: {music_dir}");
                 continue;
@@ -461,7 +896,12 @@ impl MetadataService {
 
     /// Fast metadata extraction for APE files using the `ape_decoder` crate directly.
     /// Reads only header + seek table + tags from disk, avoiding loading the entire file.
-    fn scan_ape_file_fast(&self, file_path: &Path, settings: &MetadataStoreSettings, file_modification_date: DateTime<Utc>) -> Result<()> {
+    fn read_ape_file_fast(
+        &self,
+        file_path: &Path,
+        settings: &MetadataStoreSettings,
+        file_modification_date: DateTime<Utc>,
+    ) -> Result<Song> {
         let path_str = file_path.to_str().ok_or_else(|| Error::msg("APE file path is not valid UTF-8"))?;
         let file_p = Self::full_path_to_database_key(settings, path_str);
         info!("Scanning APE file (fast path):\t{file_p}");
@@ -531,12 +971,11 @@ impl MetadataService {
             }
         }
 
+        set_credits(&mut song, &settings.credit_separators);
+        song.image_id = self.artwork_for(&song, None, file_path, settings);
         song.file.clone_from(&file_p);
         song.file_date = file_modification_date;
-        debug!("Add/update song in database: {song:?}");
-        self.song_repository.save(&song)?;
-        self.album_repository.update_from_song(song)?;
-        Ok(())
+        Ok(song)
     }
 
     /// Decode an `ID3v2` text frame payload to a string.
@@ -558,8 +997,13 @@ impl MetadataService {
         if text.is_empty() { None } else { Some(text) }
     }
 
-    /// Scan an SACD ISO file and create one `Song` entry per audio track using virtual paths.
-    fn scan_sacd_iso_file(&self, file_path: &Path, settings: &MetadataStoreSettings, file_modification_date: DateTime<Utc>) -> Result<()> {
+    /// Read an SACD ISO file as one `Song` per audio track using virtual paths.
+    fn read_sacd_iso_file(
+        &self,
+        file_path: &Path,
+        settings: &MetadataStoreSettings,
+        file_modification_date: DateTime<Utc>,
+    ) -> Result<Vec<Song>> {
         let path_str = file_path.to_str().ok_or_else(|| Error::msg("SACD ISO path is not valid UTF-8"))?;
         let iso_key = Self::full_path_to_database_key(settings, path_str);
         info!("Scanning SACD ISO:\t{iso_key}");
@@ -582,6 +1026,8 @@ impl MetadataService {
             return Err(Error::msg("SACD ISO contains no tracks"));
         }
 
+        let image_id = self.artwork_for(&Song::default(), None, file_path, settings);
+        let mut songs = Vec::with_capacity(tracks.len());
         for (idx, track) in tracks.iter().enumerate() {
             let virtual_key = format!("{iso_key}{SACD_TRACK_MARKER}{idx:04}");
             let duration_secs = track.duration_secs(area.channel_count, area.frame_format);
@@ -592,30 +1038,42 @@ impl MetadataService {
                 time: Some(std::time::Duration::from_secs_f64(duration_secs)),
                 file: virtual_key.clone(),
                 file_date: file_modification_date,
+                image_id: image_id.clone(),
                 ..Default::default()
             };
 
             log::debug!("SACD track {idx}: {virtual_key} ({duration_secs:.1}s)");
+            songs.push(song);
+        }
+
+        Ok(songs)
+    }
+
+    /// Reads the file and stores its songs, updating the album and works indexes.
+    fn scan_single_file(&self, file_path: &Path, settings: &MetadataStoreSettings) -> Result<()> {
+        for song in self.read_file(file_path, settings)? {
+            debug!("Add/update song in database: {song:?}");
             self.song_repository.save(&song)?;
+            self.work_repository.update_from_song(&song)?;
             self.album_repository.update_from_song(song)?;
         }
-
         Ok(())
     }
 
-    fn scan_single_file(&self, file_path: &Path, settings: &MetadataStoreSettings) -> Result<()> {
+    /// The songs of a file (several for an SACD ISO), read without storing them.
+    fn read_file(&self, file_path: &Path, settings: &MetadataStoreSettings) -> Result<Vec<Song>> {
         info!("Scanning file:\t{}", file_path.display());
 
         // Fast path for APE files: read tags directly without loading entire file.
         if file_path.extension().is_some_and(|e| e.eq_ignore_ascii_case("ape")) {
             let file_modification_date: DateTime<Utc> = file_path.metadata()?.modified()?.into();
-            return self.scan_ape_file_fast(file_path, settings, file_modification_date);
+            return Ok(vec![self.read_ape_file_fast(file_path, settings, file_modification_date)?]);
         }
 
         // SACD ISO: expand to one Song entry per audio track.
         if file_path.extension().is_some_and(|e| e.eq_ignore_ascii_case("iso")) {
             let file_modification_date: DateTime<Utc> = file_path.metadata()?.modified()?.into();
-            return self.scan_sacd_iso_file(file_path, settings, file_modification_date);
+            return self.read_sacd_iso_file(file_path, settings, file_modification_date);
         }
 
         let file = Box::new(File::open(file_path)?);
@@ -643,22 +1101,12 @@ impl MetadataService {
         match probe_result {
             Ok(mut probed) => {
                 let (mut song, image_data) = AudioMetadataExtractor::extract(&mut *probed);
-                if let Some(image_data) = &image_data {
-                    let image_id = uuid::Uuid::new_v4();
-                    if let Err(e) = std::fs::write(Path::new(ARTWORK_DIR).join(image_id.to_string()), &image_data.data) {
-                        warn!("Error writing image file: {e}");
-                    } else {
-                        song.image_id = Some(image_id.to_string());
-                    }
-                }
+                set_credits(&mut song, &settings.credit_separators);
+                song.image_id = self.artwork_for(&song, image_data.as_ref().map(|image| &*image.data), file_path, settings);
 
                 song.file.clone_from(file_p);
                 song.file_date = file_modification_date;
-                log::debug!("Add/update song in database: {song:?}");
-                self.song_repository.save(&song)?;
-                self.album_repository.update_from_song(song)?;
-
-                Ok(())
+                Ok(vec![song])
             }
             Err(err) => {
                 warn!("{err}");
@@ -667,3 +1115,42 @@ impl MetadataService {
         }
     }
 }
+
+/// Fills `artists` and `genres` with the credits of the artist (then album
+/// artist) and genre tags. Values collected by the extractor are used when
+/// present, the display strings otherwise.
+fn set_credits(song: &mut Song, separators: &[String]) {
+    let mut artists = if song.artists.is_empty() {
+        song.artist.iter().cloned().collect()
+    } else {
+        std::mem::take(&mut song.artists)
+    };
+    artists.extend(song.album_artist.iter().cloned());
+    song.artists = split_credits(&artists, separators);
+    let genres = if song.genres.is_empty() {
+        song.genre.iter().cloned().collect()
+    } else {
+        std::mem::take(&mut song.genres)
+    };
+    song.genres = split_credits(&genres, separators);
+}
+
+fn update_or_create_media_item_stat<J>(repository: &dyn PlayStatisticsRepository, media_item_id: &str, mut job: J)
+where
+    J: FnMut(&mut PlayItemStatistics),
+{
+    let stat = if let Some(mut stat) = repository.find_by_id(media_item_id) {
+        job(&mut stat);
+        stat
+    } else {
+        let mut stat = PlayItemStatistics {
+            play_item_id: media_item_id.to_string(),
+            ..Default::default()
+        };
+        job(&mut stat);
+        stat
+    };
+    if let Err(e) = repository.save(&stat) {
+        warn!("Failed to save stats for '{media_item_id}': {e}");
+    }
+}
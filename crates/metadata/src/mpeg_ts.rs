@@ -0,0 +1,146 @@
+//! Minimal MPEG transport stream demuxer for HLS segments.
+//!
+//! Follows the PAT and PMT to the first AAC (ADTS) or MPEG audio stream and
+//! to the ID3 timed-metadata stream, and hands out their PES payloads; every
+//! other PID is dropped. Tables are expected to fit one packet, as HLS
+//! packagers write them.
+
+use std::mem;
+
+const PACKET_LEN: usize = 188;
+pub const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0;
+const TABLE_PAT: u8 = 0x00;
+const TABLE_PMT: u8 = 0x02;
+const STREAM_TYPE_MPEG1_AUDIO: u8 = 0x03;
+const STREAM_TYPE_MPEG2_AUDIO: u8 = 0x04;
+const STREAM_TYPE_ADTS_AAC: u8 = 0x0f;
+const STREAM_TYPE_METADATA: u8 = 0x15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioCodec {
+    Aac,
+    Mp3,
+}
+
+impl AudioCodec {
+    /// File extension that makes the probe pick the elementary stream reader.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Aac => "aac",
+            Self::Mp3 => "mp3",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TsDemuxer {
+    pmt_pid: Option<u16>,
+    audio: Option<(u16, AudioCodec)>,
+    metadata_pid: Option<u16>,
+    metadata: Vec<u8>,
+}
+
+impl TsDemuxer {
+    pub fn codec(&self) -> Option<AudioCodec> {
+        self.audio.map(|(_, codec)| codec)
+    }
+
+    /// Appends the audio carried by the packets of `data` to `audio` and
+    /// returns the metadata payloads (ID3 tags) completed in it. A segment
+    /// ends any metadata payload still open.
+    pub fn push(&mut self, data: &[u8], audio: &mut Vec<u8>) -> Vec<Vec<u8>> {
+        let mut tags = Vec::new();
+        for packet in data.chunks_exact(PACKET_LEN) {
+            if packet[0] != SYNC_BYTE {
+                continue;
+            }
+            let pid = (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2]);
+            let unit_start = packet[1] & 0x40 != 0;
+            let Some(payload) = payload(packet) else {
+                continue;
+            };
+            if pid == PAT_PID {
+                self.read_pat(payload, unit_start);
+            } else if Some(pid) == self.pmt_pid {
+                self.read_pmt(payload, unit_start);
+            } else if Some(pid) == self.audio.map(|(audio_pid, _)| audio_pid) {
+                audio.extend_from_slice(pes_payload(payload, unit_start));
+            } else if Some(pid) == self.metadata_pid {
+                if unit_start && !self.metadata.is_empty() {
+                    tags.push(mem::take(&mut self.metadata));
+                }
+                self.metadata.extend_from_slice(pes_payload(payload, unit_start));
+            }
+        }
+        if !self.metadata.is_empty() {
+            tags.push(mem::take(&mut self.metadata));
+        }
+        tags
+    }
+
+    fn read_pat(&mut self, payload: &[u8], unit_start: bool) {
+        let Some(section) = section(payload, unit_start, TABLE_PAT) else {
+            return;
+        };
+        let mut programs = section.get(8..).unwrap_or_default().chunks_exact(4);
+        if let Some(program) = programs.find(|p| p[0] != 0 || p[1] != 0) {
+            self.pmt_pid = Some((u16::from(program[2] & 0x1f) << 8) | u16::from(program[3]));
+        }
+    }
+
+    fn read_pmt(&mut self, payload: &[u8], unit_start: bool) {
+        let Some(section) = section(payload, unit_start, TABLE_PMT) else {
+            return;
+        };
+        let Some(info) = section.get(10..12) else {
+            return;
+        };
+        let mut position = 12 + ((usize::from(info[0] & 0x0f) << 8) | usize::from(info[1]));
+        while let Some(entry) = section.get(position..position + 5) {
+            let pid = (u16::from(entry[1] & 0x1f) << 8) | u16::from(entry[2]);
+            match entry[0] {
+                STREAM_TYPE_ADTS_AAC if self.audio.is_none() => self.audio = Some((pid, AudioCodec::Aac)),
+                STREAM_TYPE_MPEG1_AUDIO | STREAM_TYPE_MPEG2_AUDIO if self.audio.is_none() => self.audio = Some((pid, AudioCodec::Mp3)),
+                STREAM_TYPE_METADATA => self.metadata_pid = Some(pid),
+                _ => {}
+            }
+            position += 5 + ((usize::from(entry[3] & 0x0f) << 8) | usize::from(entry[4]));
+        }
+    }
+}
+
+fn payload(packet: &[u8]) -> Option<&[u8]> {
+    let control = (packet[3] >> 4) & 0x3;
+    if control & 0x1 == 0 {
+        return None;
+    }
+    let start = if control & 0x2 == 0 { 4 } else { 5 + usize::from(packet[4]) };
+    packet.get(start..)
+}
+
+/// The PSI section starting in `payload`, without its CRC.
+fn section(payload: &[u8], unit_start: bool, table_id: u8) -> Option<&[u8]> {
+    if !unit_start {
+        return None;
+    }
+    let pointer = usize::from(*payload.first()?);
+    let section = payload.get(1 + pointer..)?;
+    if *section.first()? != table_id {
+        return None;
+    }
+    let length = (usize::from(section.get(1)? & 0x0f) << 8) | usize::from(*section.get(2)?);
+    section.get(..(3 + length).checked_sub(4)?)
+}
+
+/// Elementary stream bytes of a PES packet payload; continuation packets
+/// carry nothing but those.
+fn pes_payload(payload: &[u8], unit_start: bool) -> &[u8] {
+    if !unit_start {
+        return payload;
+    }
+    if payload.len() < 9 || payload[..3] != [0, 0, 1] {
+        return &[];
+    }
+    payload.get(9 + usize::from(payload[8])..).unwrap_or_default()
+}
@@ -1,5 +1,9 @@
 //! Fjall-backed [`PlayStatisticsRepository`]: play/skip/like counters per
 //! song key, feeding the "most played" and "liked" dynamic playlists.
+//!
+//! `play_statistics` counts for the whole household. Each user profile
+//! keeps its own counters in `profile_statistics`, seen through a
+//! [`ProfilePlayStatisticsRepository`].
 
 use api_models::stat::PlayItemStatistics;
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
@@ -20,6 +24,16 @@ impl FjallPlayStatisticsRepository {
         }
     }
 
+    /// The counters of all profiles, to be read through
+    /// [`ProfilePlayStatisticsRepository`].
+    pub fn profiles(db: &Database) -> Self {
+        Self {
+            db: db
+                .keyspace("profile_statistics", KeyspaceCreateOptions::default)
+                .expect("Failed to open profile_statistics keyspace"),
+        }
+    }
+
     /// Standalone constructor for tests — opens its own fjall Database.
     pub fn new_standalone(db_path: &str) -> Self {
         let db = Database::builder(db_path).open().expect("Failed to open statistics db");
@@ -70,9 +84,63 @@ impl PlayStatisticsRepository for FjallPlayStatisticsRepository {
     }
 }
 
+/// One profile's counters in a repository shared by all profiles. Keys are
+/// the song key behind the profile name and a NUL, which user names cannot
+/// contain, so one profile's prefix never matches another's.
+pub struct ProfilePlayStatisticsRepository {
+    inner: ArcPlayStatisticsRepository,
+    prefix: String,
+}
+
+impl ProfilePlayStatisticsRepository {
+    pub fn new(inner: ArcPlayStatisticsRepository, profile: &str) -> Self {
+        Self {
+            inner,
+            prefix: format!("{profile}\0"),
+        }
+    }
+
+    fn unscoped(&self, mut stat: PlayItemStatistics) -> Option<PlayItemStatistics> {
+        stat.play_item_id = stat.play_item_id.strip_prefix(&self.prefix)?.to_owned();
+        Some(stat)
+    }
+}
+
+impl PlayStatisticsRepository for ProfilePlayStatisticsRepository {
+    fn find_by_id(&self, play_item_id: &str) -> Option<PlayItemStatistics> {
+        self.inner
+            .find_by_id(&format!("{}{play_item_id}", self.prefix))
+            .and_then(|stat| self.unscoped(stat))
+    }
+
+    fn find_by_key_prefix(&self, prefix: &str) -> Vec<PlayItemStatistics> {
+        self.inner
+            .find_by_key_prefix(&format!("{}{prefix}", self.prefix))
+            .into_iter()
+            .filter_map(|stat| self.unscoped(stat))
+            .collect()
+    }
+
+    fn get_all(&self) -> Vec<PlayItemStatistics> {
+        self.find_by_key_prefix("")
+    }
+
+    fn save(&self, play_item_statistics: &PlayItemStatistics) -> RepoResult<()> {
+        self.inner.save(&PlayItemStatistics {
+            play_item_id: format!("{}{}", self.prefix, play_item_statistics.play_item_id),
+            ..play_item_statistics.clone()
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{FjallPlayStatisticsRepository, PlayStatisticsRepository};
+    use std::sync::Arc;
+
+    use api_models::stat::PlayItemStatistics;
+
+    use super::{FjallPlayStatisticsRepository, PlayStatisticsRepository, ProfilePlayStatisticsRepository};
+    use crate::ports::fakes::InMemoryPlayStatisticsRepository;
 
     #[test]
     fn test() {
@@ -93,4 +161,25 @@ mod test {
         assert_eq!(play_item_statistics.play_count, 1);
         assert_eq!(play_item_statistics.play_item_id, "test");
     }
+
+    #[test]
+    fn profiles_keep_their_own_counters() {
+        let shared = Arc::new(InMemoryPlayStatisticsRepository::default());
+        let anna = ProfilePlayStatisticsRepository::new(shared.clone(), "anna");
+        let ann = ProfilePlayStatisticsRepository::new(shared, "ann");
+        anna.save(&PlayItemStatistics {
+            play_item_id: "a/song.flac".to_owned(),
+            liked_count: 1,
+            ..Default::default()
+        })
+        .expect("save");
+
+        let stat = anna.find_by_id("a/song.flac").expect("anna's counter");
+        assert_eq!(stat.play_item_id, "a/song.flac");
+        assert_eq!(stat.liked_count, 1);
+        assert_eq!(anna.get_all().len(), 1);
+        assert!(ann.find_by_id("a/song.flac").is_none());
+        assert!(ann.get_all().is_empty());
+        assert_eq!(anna.find_by_key_prefix("a/").len(), 1);
+    }
 }
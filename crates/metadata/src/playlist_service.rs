@@ -1,14 +1,22 @@
 //! Saved playlists plus the composite home-page listing.
 //!
-//! `playlist` keyspace holds playlist items keyed `{name}_{index}`; the service
-//! also assembles the dynamic rows (most played, liked, recently added,
-//! by-genre/decade headers) from the other repositories into
-//! [`api_models::playlist::Playlists`].
+//! Every saved playlist gets a stable uuid when it is created. `playlist_list`
+//! maps that id to its [`Playlist`] header; the `playlist` keyspace holds the
+//! items keyed `{id}/` followed by the big-endian position, so a prefix scan
+//! returns them in order and renaming never touches the items. Edits
+//! (append, remove, move) rewrite the playlist's items in one batch.
+//!
+//! Older databases keyed items `{name}_{index}` with the name doubling as the
+//! id; [`PlaylistService::new`] migrates those once on startup.
+//!
+//! A playlist saved from a user profile has that profile as `owner_name` and
+//! is listed for that profile only; playlists without an owner are shared.
 
 use std::sync::Arc;
 
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
-use log::error;
+use log::{error, info};
+use uuid::Uuid;
 
 use api_models::{
     player::Song,
@@ -16,6 +24,7 @@ use api_models::{
 };
 
 pub struct PlaylistService {
+    db: Database,
     main_db: Keyspace,
     pl_tree: Keyspace,
 }
@@ -29,42 +38,141 @@ impl PlaylistService {
         let pl_tree = db
             .keyspace("playlist_list", KeyspaceCreateOptions::default)
             .expect("Failed to open playlist_list keyspace");
-        Arc::new(Self { main_db, pl_tree })
+        let service = Self {
+            db: db.clone(),
+            main_db,
+            pl_tree,
+        };
+        service.migrate_legacy_playlists();
+        Arc::new(service)
     }
 
-    pub fn save_new_playlist(&self, playlist_name: &str, songs: &[Song]) {
+    /// Saves `songs` as playlist `playlist_name`, replacing the items of an
+    /// existing playlist with the same name the profile sees.
+    pub fn save_new_playlist(&self, profile: Option<&str>, playlist_name: &str, songs: &[Song]) {
         if songs.is_empty() {
             return;
         }
-        let keys_to_remove: Vec<Vec<u8>> = self
-            .main_db
-            .prefix(playlist_name)
-            .filter_map(|guard| guard.key().ok().map(|k| k.to_vec()))
-            .collect();
-        for key in keys_to_remove {
-            _ = self.main_db.remove(key);
-        }
-        for (idx, song) in songs.iter().enumerate() {
-            _ = self
-                .main_db
-                .insert(format!("{playlist_name}_{idx}").as_str(), song.to_json_string_bytes());
+        if let Some(existing) = self.find_playlist_by_name(profile, playlist_name) {
+            self.write_items(&existing.id, songs);
+        } else {
+            self.create_playlist(profile, playlist_name, songs);
         }
+    }
+
+    /// Creates a new playlist owned by `profile`, even if one with the same
+    /// name already exists.
+    pub fn create_playlist(&self, profile: Option<&str>, playlist_name: &str, songs: &[Song]) -> Playlist {
         let pl = Playlist {
             name: playlist_name.to_string(),
-            id: playlist_name.to_string(),
+            id: Uuid::new_v4().to_string(),
             description: None,
             image: None,
-            owner_name: None,
+            owner_name: profile.map(ToOwned::to_owned),
         };
-        _ = self
-            .pl_tree
-            .insert(playlist_name, serde_json::to_vec(&pl).expect("failed to serialize"));
+        self.save_header(&pl);
+        self.write_items(&pl.id, songs);
+        pl
+    }
+
+    pub fn get_playlist(&self, playlist_id: &str) -> Option<Playlist> {
+        let value = self.pl_tree.get(playlist_id).ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+
+    /// Whether the playlist is shared or owned by `profile`.
+    pub fn is_visible_to(&self, playlist_id: &str, profile: Option<&str>) -> bool {
+        self.get_playlist(playlist_id).is_some_and(|pl| visible_to(&pl, profile))
+    }
+
+    /// The images of all playlists, which keep their artwork from cleanup.
+    pub fn image_ids(&self) -> Vec<String> {
+        self.saved_playlists().into_iter().filter_map(|pl| pl.image).collect()
+    }
+
+    pub fn find_playlist_by_name(&self, profile: Option<&str>, playlist_name: &str) -> Option<Playlist> {
+        self.saved_playlists()
+            .into_iter()
+            .find(|pl| pl.name == playlist_name && visible_to(pl, profile))
+    }
+
+    pub fn rename_playlist(&self, playlist_id: &str, new_name: &str) -> bool {
+        self.update_header(playlist_id, |pl| pl.name = new_name.to_string())
+    }
+
+    /// Replaces the description and image of a playlist; `None` clears them.
+    pub fn update_playlist_details(&self, playlist_id: &str, description: Option<String>, image: Option<String>) -> bool {
+        self.update_header(playlist_id, |pl| {
+            pl.description = description;
+            pl.image = image;
+        })
+    }
+
+    pub fn delete_playlist(&self, playlist_id: &str) -> bool {
+        if self.get_playlist(playlist_id).is_none() {
+            return false;
+        }
+        let mut batch = self.db.batch();
+        for key in self.item_keys(playlist_id) {
+            batch.remove(&self.main_db, key);
+        }
+        batch.remove(&self.pl_tree, playlist_id);
+        if let Err(e) = batch.commit() {
+            error!("Failed to delete playlist '{playlist_id}': {e}");
+            return false;
+        }
+        true
+    }
+
+    /// Copies a playlist's items and details under a new id, owned by
+    /// `profile`.
+    pub fn duplicate_playlist(&self, profile: Option<&str>, playlist_id: &str, new_name: &str) -> Option<Playlist> {
+        let source = self.get_playlist(playlist_id)?;
+        let mut copy = self.create_playlist(profile, new_name, &self.get_all_items(playlist_id));
+        copy.description = source.description;
+        copy.image = source.image;
+        self.save_header(&copy);
+        Some(copy)
+    }
+
+    pub fn append_songs(&self, playlist_id: &str, songs: &[Song]) -> bool {
+        if self.get_playlist(playlist_id).is_none() {
+            return false;
+        }
+        let mut items = self.get_all_items(playlist_id);
+        items.extend_from_slice(songs);
+        self.write_items(playlist_id, &items);
+        true
     }
 
-    pub fn get_playlist_page_by_name(&self, playlist_name: &str, offset: usize, limit: usize) -> PlaylistPage {
+    pub fn remove_item(&self, playlist_id: &str, position: usize) -> bool {
+        let mut items = self.get_all_items(playlist_id);
+        if position >= items.len() {
+            return false;
+        }
+        items.remove(position);
+        self.write_items(playlist_id, &items);
+        true
+    }
+
+    /// Moves the item at `from` so that it ends up at position `to`.
+    pub fn move_item(&self, playlist_id: &str, from: usize, to: usize) -> bool {
+        let mut items = self.get_all_items(playlist_id);
+        if from >= items.len() || to >= items.len() {
+            return false;
+        }
+        if from != to {
+            let song = items.remove(from);
+            items.insert(to, song);
+            self.write_items(playlist_id, &items);
+        }
+        true
+    }
+
+    pub fn get_playlist_page(&self, playlist_id: &str, offset: usize, limit: usize) -> PlaylistPage {
         let entries: Vec<Vec<u8>> = self
             .main_db
-            .prefix(playlist_name)
+            .prefix(item_prefix(playlist_id))
             .filter_map(|guard| guard.value().ok().map(|v| v.to_vec()))
             .collect();
         let total = entries.len();
@@ -74,7 +182,7 @@ impl PlaylistService {
             .take(limit)
             .filter_map(|value| {
                 Song::bytes_to_song(&value).or_else(|| {
-                    error!("playlist '{playlist_name}': skipping song with malformed bytes");
+                    error!("playlist '{playlist_id}': skipping song with malformed bytes");
                     None
                 })
             })
@@ -87,16 +195,122 @@ impl PlaylistService {
         }
     }
 
-    pub fn get_playlists(&self) -> Playlists {
+    pub fn get_all_items(&self, playlist_id: &str) -> Vec<Song> {
+        self.get_playlist_page(playlist_id, 0, usize::MAX).items
+    }
+
+    /// The shared playlists and those of `profile`.
+    pub fn get_playlists(&self, profile: Option<&str>) -> Playlists {
         Playlists {
             items: self
-                .pl_tree
-                .iter()
+                .saved_playlists()
+                .into_iter()
+                .filter(|pl| visible_to(pl, profile))
+                .map(PlaylistType::Saved)
+                .collect(),
+        }
+    }
+
+    fn saved_playlists(&self) -> Vec<Playlist> {
+        self.pl_tree
+            .iter()
+            .filter_map(|guard| {
+                let value = guard.value().ok()?;
+                serde_json::from_slice(&value).ok()
+            })
+            .collect()
+    }
+
+    fn save_header(&self, pl: &Playlist) {
+        _ = self
+            .pl_tree
+            .insert(pl.id.as_str(), serde_json::to_vec(pl).expect("failed to serialize"));
+    }
+
+    fn update_header(&self, playlist_id: &str, update: impl FnOnce(&mut Playlist)) -> bool {
+        let Some(mut pl) = self.get_playlist(playlist_id) else {
+            return false;
+        };
+        update(&mut pl);
+        self.save_header(&pl);
+        true
+    }
+
+    fn item_keys(&self, playlist_id: &str) -> Vec<Vec<u8>> {
+        self.main_db
+            .prefix(item_prefix(playlist_id))
+            .filter_map(|guard| guard.key().ok().map(|k| k.to_vec()))
+            .collect()
+    }
+
+    fn write_items(&self, playlist_id: &str, songs: &[Song]) {
+        let mut batch = self.db.batch();
+        for key in self.item_keys(playlist_id) {
+            batch.remove(&self.main_db, key);
+        }
+        for (idx, song) in songs.iter().enumerate() {
+            batch.insert(&self.main_db, item_key(playlist_id, idx), song.to_json_string_bytes());
+        }
+        if let Err(e) = batch.commit() {
+            error!("Failed to write items of playlist '{playlist_id}': {e}");
+        }
+    }
+
+    /// Moves playlists saved under the `{name}_{index}` scheme to uuid ids.
+    fn migrate_legacy_playlists(&self) {
+        let legacy: Vec<Playlist> = self
+            .saved_playlists()
+            .into_iter()
+            .filter(|pl| Uuid::parse_str(&pl.id).is_err())
+            .collect();
+        for old in legacy {
+            let legacy_prefix = format!("{}_", old.id);
+            let mut entries: Vec<(usize, Vec<u8>, Vec<u8>)> = self
+                .main_db
+                .prefix(&legacy_prefix)
                 .filter_map(|guard| {
-                    let value = guard.value().ok()?;
-                    Some(PlaylistType::Saved(serde_json::from_slice(&value).ok()?))
+                    let (key, value) = guard.into_inner().ok()?;
+                    // Another playlist's name may share the prefix ("a" vs "a_b").
+                    let idx = std::str::from_utf8(&key[legacy_prefix.len()..]).ok()?.parse().ok()?;
+                    Some((idx, key.to_vec(), value.to_vec()))
                 })
-                .collect(),
+                .collect();
+            // Keys sorted as strings put "_10" before "_2"; restore numeric order.
+            entries.sort_by_key(|(idx, _, _)| *idx);
+
+            let new = Playlist {
+                id: Uuid::new_v4().to_string(),
+                ..old.clone()
+            };
+            let mut batch = self.db.batch();
+            for (pos, (_, old_key, value)) in entries.iter().enumerate() {
+                batch.remove(&self.main_db, old_key.clone());
+                batch.insert(&self.main_db, item_key(&new.id, pos), value.clone());
+            }
+            batch.remove(&self.pl_tree, old.id.as_str());
+            batch.insert(
+                &self.pl_tree,
+                new.id.as_str(),
+                serde_json::to_vec(&new).expect("failed to serialize"),
+            );
+            match batch.commit() {
+                Ok(()) => info!("Migrated playlist '{}' ({} items) to id {}", new.name, entries.len(), new.id),
+                Err(e) => error!("Failed to migrate playlist '{}': {e}", old.name),
+            }
         }
     }
 }
+
+fn visible_to(playlist: &Playlist, profile: Option<&str>) -> bool {
+    playlist.owner_name.is_none() || playlist.owner_name.as_deref() == profile
+}
+
+fn item_prefix(playlist_id: &str) -> String {
+    format!("{playlist_id}/")
+}
+
+fn item_key(playlist_id: &str, position: usize) -> Vec<u8> {
+    let mut key = item_prefix(playlist_id).into_bytes();
+    key.extend_from_slice(&(position as u64).to_be_bytes());
+    key
+}
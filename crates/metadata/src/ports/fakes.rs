@@ -4,12 +4,21 @@
 
 use std::sync::Mutex;
 
-use api_models::{player::Song, playlist::Album, stat::PlayItemStatistics};
+use api_models::{
+    auth::{AccessToken, User},
+    player::{RadioStation, Song},
+    playlist::Album,
+    stat::PlayItemStatistics,
+};
 
 use crate::error::{RepoError, RepoResult};
 use crate::ports::{
-    album_repository::AlbumRepository, loudness_repository::LoudnessRepository, play_statistics_repository::PlayStatisticsRepository,
+    album_repository::AlbumRepository,
+    loudness_repository::{AlbumLoudness, LoudnessRepository, TrackLoudness},
+    play_statistics_repository::PlayStatisticsRepository,
+    radio_station_repository::RadioStationRepository,
     song_repository::SongRepository,
+    user_repository::UserRepository,
 };
 
 #[derive(Default)]
@@ -225,11 +234,17 @@ impl PlayStatisticsRepository for InMemoryPlayStatisticsRepository {
 
 #[derive(Default)]
 pub struct InMemoryLoudnessRepository {
-    entries: Mutex<Vec<(String, Option<i32>)>>,
+    entries: Mutex<Vec<(String, Option<TrackLoudness>)>>,
+    albums: Mutex<Vec<(String, Option<AlbumLoudness>)>>,
+    fingerprints: Mutex<Vec<(String, Vec<u32>)>>,
 }
 
 impl LoudnessRepository for InMemoryLoudnessRepository {
     fn get(&self, file_key: &str) -> Option<i32> {
+        self.get_track(file_key).map(|t| t.loudness)
+    }
+
+    fn get_track(&self, file_key: &str) -> Option<TrackLoudness> {
         self.entries
             .lock()
             .unwrap()
@@ -242,7 +257,7 @@ impl LoudnessRepository for InMemoryLoudnessRepository {
         self.entries.lock().unwrap().iter().any(|(k, _)| k == file_key)
     }
 
-    fn save_loudness(&self, file_key: &str, loudness: i32) -> RepoResult<()> {
+    fn save_loudness(&self, file_key: &str, loudness: TrackLoudness) -> RepoResult<()> {
         let mut g = self.entries.lock().unwrap();
         if let Some(existing) = g.iter_mut().find(|(k, _)| k == file_key) {
             existing.1 = Some(loudness);
@@ -262,6 +277,53 @@ impl LoudnessRepository for InMemoryLoudnessRepository {
         Ok(())
     }
 
+    fn get_album(&self, album_id: &str) -> Option<AlbumLoudness> {
+        self.albums
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(k, _)| k == album_id)
+            .and_then(|(_, v)| *v)
+    }
+
+    fn contains_album(&self, album_id: &str) -> bool {
+        self.albums.lock().unwrap().iter().any(|(k, _)| k == album_id)
+    }
+
+    fn save_album_loudness(&self, album_id: &str, loudness: AlbumLoudness) -> RepoResult<()> {
+        let mut g = self.albums.lock().unwrap();
+        g.retain(|(k, _)| k != album_id);
+        g.push((album_id.to_owned(), Some(loudness)));
+        Ok(())
+    }
+
+    fn save_album_unavailable(&self, album_id: &str) -> RepoResult<()> {
+        let mut g = self.albums.lock().unwrap();
+        g.retain(|(k, _)| k != album_id);
+        g.push((album_id.to_owned(), None));
+        Ok(())
+    }
+
+    fn get_fingerprint(&self, file_key: &str) -> Option<Vec<u32>> {
+        self.fingerprints
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(k, v)| k == file_key && !v.is_empty())
+            .map(|(_, v)| v.clone())
+    }
+
+    fn contains_fingerprint(&self, file_key: &str) -> bool {
+        self.fingerprints.lock().unwrap().iter().any(|(k, _)| k == file_key)
+    }
+
+    fn save_fingerprint(&self, file_key: &str, fingerprint: &[u32]) -> RepoResult<()> {
+        let mut g = self.fingerprints.lock().unwrap();
+        g.retain(|(k, _)| k != file_key);
+        g.push((file_key.to_owned(), fingerprint.to_vec()));
+        Ok(())
+    }
+
     fn count_analysed(&self) -> usize {
         self.entries.lock().unwrap().len()
     }
@@ -270,6 +332,104 @@ impl LoudnessRepository for InMemoryLoudnessRepository {
 
     fn delete_all(&self) {
         self.entries.lock().unwrap().clear();
+        self.albums.lock().unwrap().clear();
+        self.fingerprints.lock().unwrap().clear();
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryRadioStationRepository {
+    stations: Mutex<Vec<RadioStation>>,
+}
+
+impl RadioStationRepository for InMemoryRadioStationRepository {
+    fn find_all(&self) -> Vec<RadioStation> {
+        let mut stations = self.stations.lock().unwrap().clone();
+        stations.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        stations
+    }
+
+    fn find_by_id(&self, id: &str) -> Option<RadioStation> {
+        self.stations.lock().unwrap().iter().find(|s| s.id == id).cloned()
+    }
+
+    fn save(&self, station: &RadioStation) -> RepoResult<()> {
+        if station.id.is_empty() {
+            return Err(RepoError::Invalid("radio station without id".to_owned()));
+        }
+        let mut g = self.stations.lock().unwrap();
+        if let Some(existing) = g.iter_mut().find(|s| s.id == station.id) {
+            *existing = station.clone();
+        } else {
+            g.push(station.clone());
+        }
+        Ok(())
+    }
+
+    fn add(&self, station: &mut RadioStation) -> RepoResult<()> {
+        if station.id.is_empty() {
+            return Err(RepoError::Invalid("radio station without id".to_owned()));
+        }
+        let mut g = self.stations.lock().unwrap();
+        station.position = g.iter().map(|s| s.position + 1).max().unwrap_or(0);
+        g.push(station.clone());
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> RepoResult<()> {
+        self.stations.lock().unwrap().retain(|s| s.id != id);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    users: Mutex<Vec<User>>,
+    tokens: Mutex<Vec<AccessToken>>,
+}
+
+impl UserRepository for InMemoryUserRepository {
+    fn find_all_users(&self) -> Vec<User> {
+        self.users.lock().unwrap().clone()
+    }
+
+    fn find_user(&self, name: &str) -> Option<User> {
+        self.users.lock().unwrap().iter().find(|u| u.name == name).cloned()
+    }
+
+    fn save_user(&self, user: &User) -> RepoResult<()> {
+        if user.name.is_empty() {
+            return Err(RepoError::Invalid("user without name".to_owned()));
+        }
+        let mut g = self.users.lock().unwrap();
+        g.retain(|u| u.name != user.name);
+        g.push(user.clone());
+        Ok(())
+    }
+
+    fn delete_user(&self, name: &str) -> RepoResult<()> {
+        self.users.lock().unwrap().retain(|u| u.name != name);
+        Ok(())
+    }
+
+    fn find_all_tokens(&self) -> Vec<AccessToken> {
+        self.tokens.lock().unwrap().clone()
+    }
+
+    fn find_token(&self, token_hash: &str) -> Option<AccessToken> {
+        self.tokens.lock().unwrap().iter().find(|t| t.token_hash == token_hash).cloned()
+    }
+
+    fn save_token(&self, token: &AccessToken) -> RepoResult<()> {
+        let mut g = self.tokens.lock().unwrap();
+        g.retain(|t| t.token_hash != token.token_hash);
+        g.push(token.clone());
+        Ok(())
+    }
+
+    fn delete_token(&self, token_hash: &str) -> RepoResult<()> {
+        self.tokens.lock().unwrap().retain(|t| t.token_hash != token_hash);
+        Ok(())
     }
 }
 
@@ -332,12 +492,36 @@ mod tests {
     #[test]
     fn loudness_round_trip() {
         let repo = InMemoryLoudnessRepository::default();
-        repo.save_loudness("a", 1234).expect("save loudness");
+        let measured = TrackLoudness {
+            loudness: 1234,
+            sample_peak: -120,
+            true_peak: -80,
+            lra: 650,
+        };
+        repo.save_loudness("a", measured).expect("save loudness");
         assert!(repo.contains("a"));
         assert_eq!(repo.get("a"), Some(1234));
+        assert_eq!(repo.get_track("a"), Some(measured));
         repo.save_unavailable("b").expect("save unavailable");
         assert!(repo.contains("b"));
         assert_eq!(repo.get("b"), None);
         assert_eq!(repo.count_analysed(), 2);
     }
+
+    #[test]
+    fn album_loudness_round_trip() {
+        let repo = InMemoryLoudnessRepository::default();
+        let measured = AlbumLoudness {
+            loudness: -1050,
+            true_peak: -30,
+        };
+        repo.save_album_loudness("artist|album", measured).expect("save album loudness");
+        assert!(repo.contains_album("artist|album"));
+        assert_eq!(repo.get_album("artist|album"), Some(measured));
+        repo.save_album_unavailable("artist|album").expect("save album unavailable");
+        assert!(repo.contains_album("artist|album"));
+        assert_eq!(repo.get_album("artist|album"), None);
+        repo.delete_all();
+        assert!(!repo.contains_album("artist|album"));
+    }
 }
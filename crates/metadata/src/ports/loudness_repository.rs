@@ -2,14 +2,53 @@ use std::sync::Arc;
 
 use crate::error::RepoResult;
 
+/// EBU R128 measurement of one track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrackLoudness {
+    /// Integrated loudness in hundredths of a LUFS.
+    pub loudness: i32,
+    /// Highest sample peak over all channels, in hundredths of a dBFS.
+    pub sample_peak: i32,
+    /// Highest true peak (oversampled), in hundredths of a dBTP.
+    pub true_peak: i32,
+    /// Loudness range in hundredths of an LU.
+    pub lra: i32,
+}
+
+/// Gated loudness of a whole album, measured across all of its tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlbumLoudness {
+    /// Integrated loudness in hundredths of a LUFS.
+    pub loudness: i32,
+    /// Highest true peak of any track, in hundredths of a dBTP.
+    pub true_peak: i32,
+}
+
 pub trait LoudnessRepository: Send + Sync {
     /// Returns the stored loudness in hundredths of a LUFS, or `None` if the
     /// file has not been analysed yet (or analysed but unmeasurable).
     fn get(&self, file_key: &str) -> Option<i32>;
+    /// Full measurement, `None` if not analysed, unmeasurable, or stored
+    /// before peaks and loudness range were recorded.
+    fn get_track(&self, file_key: &str) -> Option<TrackLoudness>;
+    /// Whether the file needs no (re-)analysis: measured with peaks, or
+    /// marked unmeasurable.
     fn contains(&self, file_key: &str) -> bool;
-    fn save_loudness(&self, file_key: &str, loudness: i32) -> RepoResult<()>;
+    fn save_loudness(&self, file_key: &str, loudness: TrackLoudness) -> RepoResult<()>;
     /// Mark the file as analysed but without a usable loudness value.
     fn save_unavailable(&self, file_key: &str) -> RepoResult<()>;
+    /// Album loudness by album id, `None` if not analysed or unmeasurable.
+    fn get_album(&self, album_id: &str) -> Option<AlbumLoudness>;
+    fn contains_album(&self, album_id: &str) -> bool;
+    fn save_album_loudness(&self, album_id: &str, loudness: AlbumLoudness) -> RepoResult<()>;
+    /// Mark the album as analysed but without a usable loudness value.
+    fn save_album_unavailable(&self, album_id: &str) -> RepoResult<()>;
+    /// Acoustic fingerprint taken with the loudness, `None` if the file was
+    /// analysed before fingerprints were recorded or is too short.
+    fn get_fingerprint(&self, file_key: &str) -> Option<Vec<u32>>;
+    /// Whether a fingerprint was recorded, an empty one included.
+    fn contains_fingerprint(&self, file_key: &str) -> bool;
+    fn save_fingerprint(&self, file_key: &str, fingerprint: &[u32]) -> RepoResult<()>;
     fn count_analysed(&self) -> usize;
     fn flush(&self);
     fn delete_all(&self);
@@ -7,7 +7,10 @@
 pub mod album_repository;
 pub mod loudness_repository;
 pub mod play_statistics_repository;
+pub mod radio_station_repository;
 pub mod song_repository;
+pub mod user_repository;
+pub mod work_repository;
 
 #[cfg(test)]
 pub mod fakes;
@@ -15,4 +18,7 @@ pub mod fakes;
 pub use album_repository::{AlbumRepository, ArcAlbumRepository};
 pub use loudness_repository::{ArcLoudnessRepository, LoudnessRepository};
 pub use play_statistics_repository::{ArcPlayStatisticsRepository, PlayStatisticsRepository};
+pub use radio_station_repository::{ArcRadioStationRepository, RadioStationRepository};
 pub use song_repository::{ArcSongRepository, SongRepository};
+pub use user_repository::{ArcUserRepository, UserRepository};
+pub use work_repository::{ArcWorkRepository, WorkRepository};
@@ -0,0 +1,18 @@
+use std::sync::Arc;
+
+use api_models::player::RadioStation;
+
+use crate::error::RepoResult;
+
+pub trait RadioStationRepository: Send + Sync {
+    /// All stations, in list order.
+    fn find_all(&self) -> Vec<RadioStation>;
+    fn find_by_id(&self, id: &str) -> Option<RadioStation>;
+    fn save(&self, station: &RadioStation) -> RepoResult<()>;
+    /// Saves a new station after the last one, setting its position; safe
+    /// against other threads adding stations at the same time.
+    fn add(&self, station: &mut RadioStation) -> RepoResult<()>;
+    fn delete(&self, id: &str) -> RepoResult<()>;
+}
+
+pub type ArcRadioStationRepository = Arc<dyn RadioStationRepository>;
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use api_models::auth::{AccessToken, User};
+
+use crate::error::RepoResult;
+
+pub trait UserRepository: Send + Sync {
+    fn find_all_users(&self) -> Vec<User>;
+    fn find_user(&self, name: &str) -> Option<User>;
+    fn save_user(&self, user: &User) -> RepoResult<()>;
+    fn delete_user(&self, name: &str) -> RepoResult<()>;
+
+    fn find_all_tokens(&self) -> Vec<AccessToken>;
+    /// Looks a token up by the SHA-256 of its value.
+    fn find_token(&self, token_hash: &str) -> Option<AccessToken>;
+    fn save_token(&self, token: &AccessToken) -> RepoResult<()>;
+    fn delete_token(&self, token_hash: &str) -> RepoResult<()>;
+}
+
+pub type ArcUserRepository = Arc<dyn UserRepository>;
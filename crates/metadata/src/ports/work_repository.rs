@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use api_models::{player::Song, playlist::WorkRecording};
+
+use crate::error::RepoResult;
+
+pub trait WorkRepository: Send + Sync {
+    fn delete_all(&self);
+    fn find_all_composers(&self) -> Vec<String>;
+    /// Works of the composer as (work id, title), sorted by title.
+    fn find_works_by_composer(&self, composer: &str) -> Vec<(String, String)>;
+    /// The recording with the given id, or every recording of the work with
+    /// the given id.
+    fn find_recordings(&self, id: &str) -> Vec<WorkRecording>;
+    fn update_from_song(&self, song: &Song) -> RepoResult<()>;
+    fn remove_from_song(&self, song: &Song) -> RepoResult<()>;
+}
+
+pub type ArcWorkRepository = Arc<dyn WorkRepository>;
@@ -7,22 +7,40 @@
 //! no song repeats until the queue is exhausted. All mutation goes through
 //! the command handler — this type is internally synchronized but has no
 //! external listeners; callers publish the resulting `CurrentQueueEvent`.
+//!
+//! With auto-continue on, running off the end of the queue appends a batch
+//! of library songs similar to the last few played (see [`crate::similarity`])
+//! instead of finishing.
+//!
+//! Undo/redo is in memory: the command handler calls [`QueueService::checkpoint`]
+//! before each queue-changing command, and the last [`HISTORY_LIMIT`] states
+//! can be stepped back and forth. Named snapshots (songs, position, playback
+//! mode) persist in the `queue_snapshots` keyspace, apart from playlists.
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::ops::Bound;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::{
-    RwLock,
-    atomic::{AtomicU32, AtomicU64, Ordering},
+    Mutex, RwLock,
+    atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
 };
 
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
 use rand::RngExt;
 
-use api_models::{common::PlaybackMode, player::Song, playlist::PlaylistPage, state::CurrentQueueQuery};
+use api_models::{
+    common::PlaybackMode,
+    player::Song,
+    playlist::{PlaylistPage, QueueSnapshot},
+    state::CurrentQueueQuery,
+};
 
-use crate::ports::{play_statistics_repository::ArcPlayStatisticsRepository, song_repository::ArcSongRepository};
+use crate::album_identity::album_id_for_song;
+use crate::ports::{
+    loudness_repository::ArcLoudnessRepository, play_statistics_repository::ArcPlayStatisticsRepository, song_repository::ArcSongRepository,
+};
+use crate::similarity::SimilarTracks;
 
 pub struct QueueService {
     db: Database,
@@ -35,14 +53,43 @@ pub struct QueueService {
     next_id: AtomicU64,
     song_repository: ArcSongRepository,
     statistics_repository: ArcPlayStatisticsRepository,
+    auto_continue: AtomicBool,
+    similar_tracks: SimilarTracks,
+    history: Mutex<QueueHistory>,
+    snapshot_db: Keyspace,
+}
+
+/// Raw queue entries and position, as captured for undo/redo. Keys are kept
+/// as-is; `next_id` only grows, so restored keys never collide with new ones.
+struct QueueState {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    current: Option<Vec<u8>>,
+    priority_queue: Vec<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct QueueHistory {
+    undo: VecDeque<QueueState>,
+    redo: Vec<QueueState>,
 }
 
 const CURRENT_SONG_KEY: &str = "current_song_key";
 const NEXT_ID_KEY: &str = "_next_queue_id";
+/// How many of the most recent queue songs seed an auto-continue pick.
+const AUTO_CONTINUE_SEEDS: usize = 5;
+/// Songs appended each time auto-continue kicks in.
+const AUTO_CONTINUE_BATCH: usize = 10;
+/// Undo steps kept in memory; each holds a full copy of the queue.
+pub const HISTORY_LIMIT: usize = 20;
 
 impl QueueService {
     #[must_use]
-    pub fn new(db: &Database, song_repository: ArcSongRepository, statistics_repository: ArcPlayStatisticsRepository) -> Arc<Self> {
+    pub fn new(
+        db: &Database,
+        song_repository: ArcSongRepository,
+        statistics_repository: ArcPlayStatisticsRepository,
+        loudness_repository: ArcLoudnessRepository,
+    ) -> Arc<Self> {
         let queue_db = db
             .keyspace("queue", KeyspaceCreateOptions::default)
             .expect("Failed to open queue keyspace");
@@ -52,6 +99,9 @@ impl QueueService {
         let random_history_db = db
             .keyspace("queue_random_history", KeyspaceCreateOptions::default)
             .expect("Failed to open queue_random_history keyspace");
+        let snapshot_db = db
+            .keyspace("queue_snapshots", KeyspaceCreateOptions::default)
+            .expect("Failed to open queue_snapshots keyspace");
 
         let playback_mode = if let Ok(Some(mode_bytes)) = status_db.get("playback_mode") {
             PlaybackMode::from_str(std::str::from_utf8(&mode_bytes).unwrap_or("Sequential")).unwrap_or_default()
@@ -78,8 +128,12 @@ impl QueueService {
             random_history_index: AtomicU32::new(0),
             random_played_keys: RwLock::new(HashSet::new()),
             next_id: AtomicU64::new(next_id),
+            similar_tracks: SimilarTracks::new(song_repository.clone(), statistics_repository.clone(), loudness_repository),
             song_repository,
             statistics_repository,
+            auto_continue: AtomicBool::new(false),
+            history: Mutex::new(QueueHistory::default()),
+            snapshot_db,
         };
         service.reset_random_state();
         Arc::new(service)
@@ -100,21 +154,200 @@ impl QueueService {
     }
 
     pub fn cycle_playback_mode(&self) -> PlaybackMode {
-        let mut mode_lock = self.playback_mode.write().expect("lock poisoned");
         let modes = api_models::common::all_playback_modes();
-        let current_index = modes.iter().position(|&m| m == *mode_lock).unwrap_or(0);
+        let current_index = modes.iter().position(|&m| m == self.get_playback_mode()).unwrap_or(0);
         let next_mode = modes[(current_index + 1) % modes.len()];
-        *mode_lock = next_mode;
-        let mode_str: &'static str = next_mode.into();
+        self.set_playback_mode(next_mode);
+        next_mode
+    }
+
+    pub fn set_playback_mode(&self, mode: PlaybackMode) {
+        *self.playback_mode.write().expect("lock poisoned") = mode;
+        let mode_str: &'static str = mode.into();
         _ = self.status_db.insert("playback_mode", mode_str);
         self.reset_random_state();
-        next_mode
     }
 
     pub fn get_playback_mode(&self) -> PlaybackMode {
         *self.playback_mode.read().expect("lock poisoned")
     }
 
+    pub fn set_auto_continue(&self, enabled: bool) {
+        self.auto_continue.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_auto_continue(&self) -> bool {
+        self.auto_continue.load(Ordering::Relaxed)
+    }
+
+    /// Called when [`Self::move_current_to_next_song`] ran out of songs:
+    /// if auto-continue is on, appends songs similar to the last few in the
+    /// queue and advances to the first of them. Returns `false` when
+    /// auto-continue is off or nothing similar is left in the library.
+    pub fn continue_with_similar_songs(&self) -> bool {
+        if !self.is_auto_continue() {
+            return false;
+        }
+        let Some(current_key) = self.get_current_or_first_song_key() else {
+            return false;
+        };
+        let mut seeds: Vec<Song> = self
+            .queue_db
+            .range(..=current_key.as_slice())
+            .rev()
+            .filter_map(|guard| Song::bytes_to_song(&guard.value().ok()?))
+            .filter(|song| !song.file.starts_with("http"))
+            .take(AUTO_CONTINUE_SEEDS)
+            .collect();
+        seeds.reverse();
+        let in_queue: HashSet<String> = self.get_all_songs().into_iter().map(|s| s.file).collect();
+        let similar = self.similar_tracks.find_similar(&seeds, &in_queue, AUTO_CONTINUE_BATCH);
+        if similar.is_empty() {
+            return false;
+        }
+        log::info!("Auto-continue: appending {} similar songs", similar.len());
+        for song in &similar {
+            self.add_song(song);
+        }
+        self.move_current_to_next_song()
+    }
+
+    /// Records the current queue as an undo step and drops the redo steps.
+    /// Call before every user-initiated queue change.
+    pub fn checkpoint(&self) {
+        let state = self.capture_state();
+        let mut history = self.history.lock().expect("lock poisoned");
+        push_bounded(&mut history.undo, state);
+        history.redo.clear();
+    }
+
+    /// Restores the queue to the last checkpoint. Returns `false` if there is
+    /// nothing to undo.
+    pub fn undo(&self) -> bool {
+        let mut history = self.history.lock().expect("lock poisoned");
+        let Some(previous) = history.undo.pop_back() else {
+            return false;
+        };
+        history.redo.push(self.capture_state());
+        self.apply_state(previous);
+        true
+    }
+
+    /// Re-applies the last undone change. Returns `false` if there is nothing
+    /// to redo.
+    pub fn redo(&self) -> bool {
+        let mut history = self.history.lock().expect("lock poisoned");
+        let Some(next) = history.redo.pop() else {
+            return false;
+        };
+        push_bounded(&mut history.undo, self.capture_state());
+        self.apply_state(next);
+        true
+    }
+
+    fn capture_state(&self) -> QueueState {
+        QueueState {
+            entries: self
+                .queue_db
+                .iter()
+                .filter_map(|guard| {
+                    let (key, value) = guard.into_inner().ok()?;
+                    Some((key.to_vec(), value.to_vec()))
+                })
+                .collect(),
+            current: self.status_db.get(CURRENT_SONG_KEY).ok().flatten().map(|k| k.to_vec()),
+            priority_queue: self.get_priority_queue(),
+        }
+    }
+
+    fn apply_state(&self, state: QueueState) {
+        self.clear();
+        let mut batch = self.db.batch();
+        for (key, value) in state.entries {
+            batch.insert(&self.queue_db, key, value);
+        }
+        if let Err(e) = batch.commit() {
+            log::error!("Failed to restore queue: {e}");
+        }
+        if let Some(current) = state.current {
+            _ = self.status_db.insert(CURRENT_SONG_KEY, current);
+        }
+        self.save_priority_queue(&state.priority_queue);
+    }
+
+    /// Saves the queue, current position and playback mode under `name`,
+    /// overwriting a snapshot with the same name. Returns `false` for an
+    /// empty queue.
+    pub fn save_snapshot(&self, name: &str) -> bool {
+        let entries: Vec<(Vec<u8>, Song)> = self
+            .queue_db
+            .iter()
+            .filter_map(|guard| {
+                let (key, value) = guard.into_inner().ok()?;
+                Some((key.to_vec(), Song::bytes_to_song(&value)?))
+            })
+            .collect();
+        if entries.is_empty() {
+            return false;
+        }
+        let current = self.get_current_or_first_song_key();
+        let snapshot = QueueSnapshot {
+            name: name.to_string(),
+            current_index: entries.iter().position(|(key, _)| Some(key) == current.as_ref()),
+            song_count: entries.len(),
+            songs: entries.into_iter().map(|(_, song)| song).collect(),
+            playback_mode: self.get_playback_mode(),
+            created: chrono::Utc::now(),
+        };
+        self.snapshot_db.insert(name, snapshot.to_json_string_bytes()).is_ok()
+    }
+
+    /// Replaces the queue with a saved snapshot and restores its position and
+    /// playback mode. Returns `false` if there is no snapshot by that name.
+    pub fn restore_snapshot(&self, name: &str) -> bool {
+        let Some(snapshot) = self.get_snapshot(name) else {
+            return false;
+        };
+        self.replace_all(snapshot.songs);
+        if let Some(key) = snapshot
+            .current_index
+            .and_then(|idx| self.queue_db.iter().nth(idx))
+            .and_then(|guard| guard.key().ok())
+        {
+            _ = self.status_db.insert(CURRENT_SONG_KEY, key);
+        }
+        self.set_playback_mode(snapshot.playback_mode);
+        true
+    }
+
+    pub fn has_snapshot(&self, name: &str) -> bool {
+        self.snapshot_db.contains_key(name).unwrap_or(false)
+    }
+
+    pub fn get_snapshot(&self, name: &str) -> Option<QueueSnapshot> {
+        QueueSnapshot::from_bytes(&self.snapshot_db.get(name).ok()??)
+    }
+
+    pub fn delete_snapshot(&self, name: &str) -> bool {
+        if !self.has_snapshot(name) {
+            return false;
+        }
+        self.snapshot_db.remove(name).is_ok()
+    }
+
+    /// Saved snapshots without their songs, ordered by name.
+    pub fn get_snapshots(&self) -> Vec<QueueSnapshot> {
+        self.snapshot_db
+            .iter()
+            .filter_map(|guard| {
+                let mut snapshot = QueueSnapshot::from_bytes(&guard.value().ok()?)?;
+                snapshot.song_count = snapshot.songs.len();
+                snapshot.songs.clear();
+                Some(snapshot)
+            })
+            .collect()
+    }
+
     pub fn get_current_song(&self) -> Option<Song> {
         let current_key = self.get_current_or_first_song_key()?;
         let value = self.queue_db.get(&current_key).ok()??;
@@ -123,6 +356,37 @@ impl QueueService {
         Some(song)
     }
 
+    /// Whether the current song is heard as part of its album: the queue
+    /// plays in order and the song before or after it is from the same album.
+    pub fn is_playing_album_in_sequence(&self) -> bool {
+        if !matches!(self.get_playback_mode(), PlaybackMode::Sequential | PlaybackMode::LoopQueue) {
+            return false;
+        }
+        let Some(current_key) = self.get_current_or_first_song_key() else {
+            return false;
+        };
+        let Some(album_id) = self
+            .queue_db
+            .get(&current_key)
+            .ok()
+            .flatten()
+            .and_then(|value| Song::bytes_to_song(&value))
+            .and_then(|song| album_id_for_song(&song))
+        else {
+            return false;
+        };
+        let previous = self.queue_db.range(..current_key.as_slice()).next_back();
+        let next = self
+            .queue_db
+            .range::<&[u8], _>((Bound::Excluded(current_key.as_slice()), Bound::Unbounded))
+            .next();
+        [previous, next]
+            .into_iter()
+            .flatten()
+            .filter_map(|guard| Song::bytes_to_song(&guard.value().ok()?))
+            .any(|song| album_id_for_song(&song).as_ref() == Some(&album_id))
+    }
+
     fn get_priority_queue(&self) -> Vec<Vec<u8>> {
         self.status_db
             .get("priority_queue")
@@ -575,3 +839,10 @@ impl QueueService {
         self.replace_all(self.song_repository.find_songs_by_dir_prefix(dir));
     }
 }
+
+fn push_bounded(steps: &mut VecDeque<QueueState>, state: QueueState) {
+    if steps.len() == HISTORY_LIMIT {
+        steps.pop_front();
+    }
+    steps.push_back(state);
+}
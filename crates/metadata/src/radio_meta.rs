@@ -2,6 +2,8 @@
 //! `ice-audio-info`…), enriched by provider-specific lookups in
 //! [`radio_providers`].
 
+use api_models::player::Song;
+
 use crate::radio_providers;
 
 #[derive(Clone, Debug)]
@@ -16,6 +18,27 @@ pub struct RadioMeta {
     pub bitrate: Option<u32>,
 }
 
+impl RadioMeta {
+    /// The song shown while the station plays `title`; the station stands in
+    /// for the album.
+    pub fn now_playing(&self, artist: Option<String>, title: Option<String>) -> Song {
+        let album = [&self.description, &self.name]
+            .into_iter()
+            .flatten()
+            .find(|s| !s.is_empty())
+            .map_or_else(|| self.url.clone(), Clone::clone);
+        Song {
+            title,
+            artist,
+            album: Some(album),
+            genre: self.genre.clone(),
+            file: self.url.clone(),
+            image_url: self.image_url.clone(),
+            ..Default::default()
+        }
+    }
+}
+
 pub fn get_external_radio_meta(agent: &ureq::Agent, resp: &ureq::http::Response<ureq::Body>) -> Option<RadioMeta> {
     use ureq::ResponseExt;
 
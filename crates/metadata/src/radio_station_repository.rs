@@ -0,0 +1,71 @@
+//! Fjall-backed [`RadioStationRepository`]: the saved radio stations, keyed
+//! by station id.
+
+use std::sync::Mutex;
+
+use fjall::{Database, Keyspace, KeyspaceCreateOptions};
+
+use api_models::player::RadioStation;
+
+use crate::error::{RepoError, RepoResult};
+pub use crate::ports::radio_station_repository::{ArcRadioStationRepository, RadioStationRepository};
+
+pub struct FjallRadioStationRepository {
+    stations_db: Keyspace,
+    /// Held while a new station's position is picked and the station saved.
+    adding: Mutex<()>,
+}
+
+impl FjallRadioStationRepository {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            stations_db: db
+                .keyspace("radio_stations", KeyspaceCreateOptions::default)
+                .expect("Failed to open radio_stations keyspace"),
+            adding: Mutex::new(()),
+        }
+    }
+
+    /// Standalone constructor for tests — opens its own fjall Database.
+    pub fn new_standalone(db_path: &str) -> Self {
+        let db = Database::builder(db_path).open().expect("Failed to open radio stations db");
+        Self::new(&db)
+    }
+}
+
+impl RadioStationRepository for FjallRadioStationRepository {
+    fn find_all(&self) -> Vec<RadioStation> {
+        let mut stations: Vec<RadioStation> = self
+            .stations_db
+            .iter()
+            .filter_map(|guard| RadioStation::from_bytes(&guard.value().ok()?))
+            .collect();
+        stations.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        stations
+    }
+
+    fn find_by_id(&self, id: &str) -> Option<RadioStation> {
+        RadioStation::from_bytes(&self.stations_db.get(id).ok()??)
+    }
+
+    fn save(&self, station: &RadioStation) -> RepoResult<()> {
+        if station.id.is_empty() {
+            return Err(RepoError::Invalid("radio station without id".to_owned()));
+        }
+        self.stations_db
+            .insert(station.id.as_str(), station.to_json_string_bytes())
+            .map_err(|e| RepoError::Storage(format!("save radio station '{}': {e}", station.id)))
+    }
+
+    fn add(&self, station: &mut RadioStation) -> RepoResult<()> {
+        let _adding = self.adding.lock().expect("radio station lock poisoned");
+        station.position = self.find_all().last().map_or(0, |last| last.position + 1);
+        self.save(station)
+    }
+
+    fn delete(&self, id: &str) -> RepoResult<()> {
+        self.stations_db
+            .remove(id)
+            .map_err(|e| RepoError::Storage(format!("delete radio station '{id}': {e}")))
+    }
+}
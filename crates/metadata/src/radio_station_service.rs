@@ -0,0 +1,300 @@
+//! The radio station library.
+//!
+//! Stations are saved with everything needed to list and play them (stream
+//! URL, logo, genre, codec, bitrate, tags), so they keep working offline and
+//! when radio-browser.info changes or drops a station; custom stations that
+//! radio-browser never had are saved the same way. New stations go after
+//! the last one, the repository picking their position so an import can add
+//! stations while others are edited; removing and moving a station number
+//! the list densely (0, 1, 2…) again. Stations liked before the library existed
+//! (`radio_uuid_<uuid>` play statistics) are imported by looking their uuids
+//! up on radio-browser.info.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, format_err};
+use log::{info, warn};
+use serde_json::Value;
+use uuid::Uuid;
+
+use api_models::player::RadioStation;
+
+use crate::error::{RepoError, RepoResult};
+use crate::ports::radio_station_repository::ArcRadioStationRepository;
+
+const RADIO_BROWSER_URL: &str = "https://de2.api.radio-browser.info/json/";
+/// Uuids looked up per radio-browser request, keeping the URL short.
+const LOOKUP_BATCH: usize = 50;
+
+pub struct RadioStationService {
+    repository: ArcRadioStationRepository,
+}
+
+impl RadioStationService {
+    #[must_use]
+    pub fn new(repository: ArcRadioStationRepository) -> Arc<Self> {
+        Arc::new(Self { repository })
+    }
+
+    pub fn get_stations(&self) -> Vec<RadioStation> {
+        self.repository.find_all()
+    }
+
+    /// Adds `station` at the end of the list, or updates the saved station
+    /// with its id in place.
+    pub fn save_station(&self, mut station: RadioStation) -> RepoResult<RadioStation> {
+        station.name = station.name.trim().to_owned();
+        station.url = station.url.trim().to_owned();
+        if station.name.is_empty() {
+            return Err(RepoError::Invalid("radio station without name".to_owned()));
+        }
+        if !is_stream_url(&station.url) {
+            return Err(RepoError::Invalid(format!("'{}' is not an http(s) stream URL", station.url)));
+        }
+        station.tags = station
+            .tags
+            .iter()
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+            .map(str::to_owned)
+            .collect();
+        for field in [&mut station.logo_url, &mut station.genre, &mut station.codec] {
+            *field = field.take().filter(|value| !value.trim().is_empty());
+        }
+        match self.repository.find_by_id(&station.id) {
+            Some(saved) if !station.id.is_empty() => {
+                station.position = saved.position;
+                self.repository.save(&station)?;
+            }
+            _ => {
+                if station.id.is_empty() {
+                    station.id = Uuid::new_v4().to_string();
+                }
+                self.repository.add(&mut station)?;
+            }
+        }
+        Ok(station)
+    }
+
+    /// `false` when there is no station with the id.
+    pub fn delete_station(&self, id: &str) -> RepoResult<bool> {
+        if self.repository.find_by_id(id).is_none() {
+            return Ok(false);
+        }
+        self.repository.delete(id)?;
+        self.renumber(self.repository.find_all())?;
+        Ok(true)
+    }
+
+    pub fn move_station(&self, id: &str, position: usize) -> RepoResult<()> {
+        let mut stations = self.repository.find_all();
+        let from = stations
+            .iter()
+            .position(|s| s.id == id)
+            .ok_or_else(|| RepoError::Invalid(format!("no radio station '{id}'")))?;
+        let station = stations.remove(from);
+        stations.insert(position.min(stations.len()), station);
+        self.renumber(stations)
+    }
+
+    /// Saves the liked stations — radio-browser uuids, or stream URLs liked
+    /// directly — that are not in the library yet, and returns how many.
+    pub fn import_favorites(&self, favorites: &[String]) -> Result<usize> {
+        let saved = self.repository.find_all();
+        let (urls, uuids): (Vec<&String>, Vec<&String>) = favorites
+            .iter()
+            .filter(|f| !saved.iter().any(|s| s.url == **f || s.radio_browser_uuid.as_ref() == Some(*f)))
+            .partition(|f| is_stream_url(f));
+        let mut stations: Vec<RadioStation> = urls
+            .into_iter()
+            .map(|url| RadioStation {
+                name: url
+                    .split("://")
+                    .nth(1)
+                    .and_then(|rest| rest.split(['/', '?']).next())
+                    .unwrap_or(url)
+                    .to_owned(),
+                url: url.clone(),
+                ..Default::default()
+            })
+            .collect();
+        if !uuids.is_empty() {
+            let found = fetch_radio_browser_stations(&uuids)?;
+            if found.len() < uuids.len() {
+                warn!(
+                    "{} favorite radio stations are no longer listed on radio-browser",
+                    uuids.len() - found.len()
+                );
+            }
+            stations.extend(found);
+        }
+        let imported = stations.len();
+        for station in stations {
+            self.save_station(station)?;
+        }
+        info!("Imported {imported} favorite radio stations");
+        Ok(imported)
+    }
+
+    fn renumber(&self, stations: Vec<RadioStation>) -> RepoResult<()> {
+        for (position, mut station) in stations.into_iter().enumerate() {
+            if station.position != position {
+                station.position = position;
+                self.repository.save(&station)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_stream_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+fn fetch_radio_browser_stations(uuids: &[&String]) -> Result<Vec<RadioStation>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(15)))
+        .build()
+        .into();
+    let mut stations = Vec::new();
+    for batch in uuids.chunks(LOOKUP_BATCH) {
+        let ids: Vec<&str> = batch.iter().map(|uuid| uuid.as_str()).collect();
+        let url = format!("{RADIO_BROWSER_URL}stations/byuuid?uuids={}", ids.join(","));
+        let body = agent
+            .get(&url)
+            .call()
+            .and_then(|resp| resp.into_body().read_to_string())
+            .map_err(|e| format_err!("radio-browser lookup failed: {e}"))?;
+        stations.extend(parse_radio_browser_stations(&body)?);
+    }
+    Ok(stations)
+}
+
+/// Stations of a radio-browser `stations` response; the first tag counts
+/// as the genre.
+fn parse_radio_browser_stations(body: &str) -> Result<Vec<RadioStation>> {
+    let Value::Array(found) = serde_json::from_str(body)? else {
+        return Err(format_err!("unexpected radio-browser response"));
+    };
+    let text = |station: &Value, key: &str| {
+        station
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .unwrap_or_default()
+            .to_owned()
+    };
+    Ok(found
+        .iter()
+        .map(|station| {
+            let tags: Vec<String> = text(station, "tags")
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_owned)
+                .collect();
+            RadioStation {
+                name: text(station, "name"),
+                url: text(station, "url"),
+                logo_url: non_empty(text(station, "favicon")),
+                genre: tags.first().cloned(),
+                codec: non_empty(text(station, "codec")),
+                bitrate_kbps: station
+                    .get("bitrate")
+                    .and_then(Value::as_u64)
+                    .and_then(|kbps| u32::try_from(kbps).ok())
+                    .filter(|kbps| *kbps > 0),
+                tags,
+                radio_browser_uuid: Some(text(station, "stationuuid")),
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ports::fakes::InMemoryRadioStationRepository;
+
+    fn station(name: &str, url: &str) -> RadioStation {
+        RadioStation {
+            name: name.to_owned(),
+            url: url.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stations_keep_their_order() {
+        let service = RadioStationService::new(Arc::new(InMemoryRadioStationRepository::default()));
+        let first = service.save_station(station("First", "http://one.example.com/live")).expect("save");
+        let second = service
+            .save_station(station(" Second ", "https://two.example.com/live"))
+            .expect("save");
+        service
+            .save_station(station("Third", "http://three.example.com/live"))
+            .expect("save");
+        assert_eq!((first.position, second.position, second.name.as_str()), (0, 1, "Second"));
+        assert!(service.save_station(station("Broken", "two.example.com/live")).is_err());
+
+        service.move_station(&second.id, 0).expect("move");
+        let renamed = service
+            .save_station(RadioStation {
+                name: "Renamed".to_owned(),
+                ..first
+            })
+            .expect("update");
+        let names: Vec<String> = service.get_stations().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["Second", "Renamed", "Third"]);
+
+        assert!(service.delete_station(&second.id).expect("delete"));
+        assert!(!service.delete_station(&second.id).expect("delete"));
+        let positions: Vec<(String, usize)> = service.get_stations().into_iter().map(|s| (s.id, s.position)).collect();
+        assert_eq!(positions[0], (renamed.id, 0));
+        assert_eq!(positions[1].1, 1);
+
+        let favorites = [
+            "http://three.example.com/live".to_owned(),
+            "http://radioaparat.com/stream".to_owned(),
+        ];
+        assert_eq!(service.import_favorites(&favorites).expect("import"), 1);
+        assert_eq!(service.get_stations()[2].name, "radioaparat.com");
+    }
+
+    #[test]
+    fn stations_added_at_once_get_their_own_positions() {
+        let service = RadioStationService::new(Arc::new(InMemoryRadioStationRepository::default()));
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let service = &service;
+                scope.spawn(move || service.save_station(station(&format!("Station {i}"), "http://example.com/live")));
+            }
+        });
+        let mut positions: Vec<usize> = service.get_stations().into_iter().map(|s| s.position).collect();
+        positions.dedup();
+        assert_eq!(positions, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn radio_browser_stations_are_read() {
+        let body = r#"[{"stationuuid":"9617a958-0601-11e8-ae97-52543be04c81","name":" Radio Paradise ",
+            "url":"http://stream.radioparadise.com/aac-320","favicon":"","tags":"eclectic, rock,,",
+            "codec":"AAC","bitrate":320}]"#;
+        let stations = parse_radio_browser_stations(body).expect("stations");
+        assert_eq!(stations.len(), 1);
+        assert_eq!(stations[0].name, "Radio Paradise");
+        assert_eq!(stations[0].genre.as_deref(), Some("eclectic"));
+        assert_eq!(stations[0].tags, ["eclectic", "rock"]);
+        assert_eq!(stations[0].bitrate_kbps, Some(320));
+        assert_eq!(
+            stations[0].radio_browser_uuid.as_deref(),
+            Some("9617a958-0601-11e8-ae97-52543be04c81")
+        );
+    }
+}
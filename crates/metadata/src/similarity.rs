@@ -0,0 +1,303 @@
+//! Local track similarity for the auto-continue ("radio") queue mode.
+//!
+//! No network and no audio analysis at pick time: a candidate is scored
+//! against the most recent queue songs over shared artist, album artist,
+//! genre (folded through `genre_utils`, so `(17)` and `rock` match `Rock`),
+//! release decade, measured loudness and tempo (`BPM`/`TBPM` tags). The raw
+//! score is then weighted by the candidate's play statistics — liked songs
+//! rise, frequently skipped and disliked ones sink.
+
+use std::collections::{HashMap, HashSet};
+
+use api_models::{player::Song, stat::PlayItemStatistics};
+
+use crate::genre_utils::{is_junk_genre, normalize_genre_key, normalize_name, resolve_id3v1_genre};
+use crate::ports::{
+    loudness_repository::ArcLoudnessRepository, play_statistics_repository::ArcPlayStatisticsRepository, song_repository::ArcSongRepository,
+};
+
+const ARTIST_WEIGHT: f64 = 3.0;
+const ALBUM_ARTIST_WEIGHT: f64 = 2.0;
+const GENRE_WEIGHT: f64 = 2.0;
+const DECADE_WEIGHT: f64 = 1.0;
+const LOUDNESS_WEIGHT: f64 = 1.0;
+const TEMPO_WEIGHT: f64 = 1.0;
+/// Loudness difference (LU) at which the loudness term reaches zero.
+const LOUDNESS_RANGE_LU: f64 = 6.0;
+/// Tempo difference (BPM) at which the tempo term reaches zero.
+const TEMPO_RANGE_BPM: f64 = 20.0;
+/// At most this many songs by the same artist in one appended batch.
+const MAX_PER_ARTIST: usize = 2;
+
+/// The comparable attributes of one song, extracted once per pick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackFeatures {
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub decade: Option<i32>,
+    /// Integrated loudness in LUFS.
+    pub loudness: Option<f64>,
+    pub tempo: Option<f64>,
+}
+
+impl TrackFeatures {
+    /// `loudness_hundredths` is the value stored by the loudness scan (LUFS × 100).
+    #[must_use]
+    pub fn from_song(song: &Song, loudness_hundredths: Option<i32>) -> Self {
+        Self {
+            artist: song.artist.as_deref().map(normalize_name).filter(|a| !a.is_empty()),
+            album_artist: song.album_artist.as_deref().map(normalize_name).filter(|a| !a.is_empty()),
+            genre: song.genre.as_deref().and_then(genre_key),
+            decade: song.date.as_deref().and_then(parse_year).map(|y| y / 10),
+            loudness: loudness_hundredths.map(|l| f64::from(l) / 100.0),
+            tempo: parse_tempo(song),
+        }
+    }
+}
+
+/// Similarity of two tracks; 0 means nothing in common. Terms only count
+/// when both sides have the attribute, so sparse tags never penalize.
+#[must_use]
+pub fn similarity(a: &TrackFeatures, b: &TrackFeatures) -> f64 {
+    let mut score = 0.0;
+    if a.artist.is_some() && a.artist == b.artist {
+        score += ARTIST_WEIGHT;
+    }
+    // Album artist also matches a plain artist credit on the other side,
+    // so compilations connect to the artists' own albums.
+    let a_album_artist = a.album_artist.as_ref().or(a.artist.as_ref());
+    let b_album_artist = b.album_artist.as_ref().or(b.artist.as_ref());
+    if a_album_artist.is_some() && a_album_artist == b_album_artist {
+        score += ALBUM_ARTIST_WEIGHT;
+    }
+    if a.genre.is_some() && a.genre == b.genre {
+        score += GENRE_WEIGHT;
+    }
+    if let (Some(da), Some(db)) = (a.decade, b.decade) {
+        match (da - db).abs() {
+            0 => score += DECADE_WEIGHT,
+            1 => score += DECADE_WEIGHT / 2.0,
+            _ => {}
+        }
+    }
+    if let (Some(la), Some(lb)) = (a.loudness, b.loudness) {
+        score = LOUDNESS_WEIGHT.mul_add((1.0 - (la - lb).abs() / LOUDNESS_RANGE_LU).max(0.0), score);
+    }
+    if let (Some(ta), Some(tb)) = (a.tempo, b.tempo) {
+        score = TEMPO_WEIGHT.mul_add((1.0 - (ta - tb).abs() / TEMPO_RANGE_BPM).max(0.0), score);
+    }
+    score
+}
+
+/// Multiplier from play history: each like adds 50% (capped at three),
+/// skips divide the weight down gently, dislikes sharply.
+#[must_use]
+pub fn history_weight(stats: Option<&PlayItemStatistics>) -> f64 {
+    let Some(stats) = stats else {
+        return 1.0;
+    };
+    let likes = stats.liked_count.clamp(0, 3);
+    let dislikes = (-stats.liked_count).max(0);
+    let skips = stats.skipped_count.max(0);
+    let boost = 0.5f64.mul_add(f64::from(likes), 1.0);
+    boost / 0.5f64.mul_add(f64::from(skips), 1.0) / f64::from(1 + dislikes).powi(2)
+}
+
+/// Picks library songs similar to a set of seed songs.
+pub struct SimilarTracks {
+    song_repository: ArcSongRepository,
+    statistics_repository: ArcPlayStatisticsRepository,
+    loudness_repository: ArcLoudnessRepository,
+}
+
+impl SimilarTracks {
+    #[must_use]
+    pub const fn new(
+        song_repository: ArcSongRepository,
+        statistics_repository: ArcPlayStatisticsRepository,
+        loudness_repository: ArcLoudnessRepository,
+    ) -> Self {
+        Self {
+            song_repository,
+            statistics_repository,
+            loudness_repository,
+        }
+    }
+
+    /// Returns up to `limit` library songs most similar to `seeds`, best
+    /// first, skipping any file in `exclude`. Later seeds (the most recently
+    /// played) weigh more. Songs with no similarity at all are never picked.
+    #[must_use]
+    pub fn find_similar(&self, seeds: &[Song], exclude: &HashSet<String>, limit: usize) -> Vec<Song> {
+        if seeds.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+        let seed_features: Vec<TrackFeatures> = seeds
+            .iter()
+            .map(|s| TrackFeatures::from_song(s, self.loudness_repository.get(&s.file)))
+            .collect();
+        let stats: HashMap<String, PlayItemStatistics> = self
+            .statistics_repository
+            .get_all()
+            .into_iter()
+            .map(|s| (s.play_item_id.clone(), s))
+            .collect();
+
+        #[allow(clippy::cast_precision_loss)]
+        let seed_weight_total: f64 = (1..=seed_features.len()).map(|w| w as f64).sum();
+        let mut scored: Vec<(f64, Song)> = self
+            .song_repository
+            .find_all()
+            .into_iter()
+            .filter(|song| !exclude.contains(&song.file) && !song.file.starts_with("http"))
+            .filter_map(|song| {
+                let features = TrackFeatures::from_song(&song, self.loudness_repository.get(&song.file));
+                #[allow(clippy::cast_precision_loss)]
+                let raw: f64 = seed_features
+                    .iter()
+                    .enumerate()
+                    .map(|(i, seed)| (i + 1) as f64 * similarity(seed, &features))
+                    .sum::<f64>()
+                    / seed_weight_total;
+                let score = raw * history_weight(stats.get(&song.file));
+                (score > 0.0).then_some((score, song))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.file.cmp(&b.1.file)));
+
+        let mut per_artist: HashMap<String, usize> = HashMap::new();
+        let mut result = Vec::with_capacity(limit);
+        for (_, song) in scored {
+            let artist = song.artist.as_deref().map(normalize_name).unwrap_or_default();
+            let count = per_artist.entry(artist).or_default();
+            if *count >= MAX_PER_ARTIST {
+                continue;
+            }
+            *count += 1;
+            result.push(song);
+            if result.len() == limit {
+                break;
+            }
+        }
+        result
+    }
+}
+
+fn genre_key(raw: &str) -> Option<String> {
+    let genre = resolve_id3v1_genre(raw).unwrap_or(raw);
+    let key = normalize_genre_key(genre);
+    (!key.is_empty() && !is_junk_genre(&key)).then_some(key)
+}
+
+fn parse_year(date: &str) -> Option<i32> {
+    date.get(..4).and_then(|y| y.parse().ok())
+}
+
+fn parse_tempo(song: &Song) -> Option<f64> {
+    song.tags
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("BPM") || k.eq_ignore_ascii_case("TBPM") || k.eq_ignore_ascii_case("TEMPO"))
+        .and_then(|(_, v)| v.trim().parse::<f64>().ok())
+        .filter(|bpm| *bpm > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::ports::fakes::{InMemoryLoudnessRepository, InMemoryPlayStatisticsRepository, InMemorySongRepository};
+    use crate::ports::{LoudnessRepository, PlayStatisticsRepository, SongRepository, loudness_repository::TrackLoudness};
+
+    fn song(file: &str, artist: &str, genre: &str, date: &str) -> Song {
+        Song {
+            file: file.to_owned(),
+            artist: Some(artist.to_owned()),
+            genre: Some(genre.to_owned()),
+            date: Some(date.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_artist_scores_higher_than_same_genre() {
+        let seed = TrackFeatures::from_song(&song("a", "Miles Davis", "Jazz", "1959"), None);
+        let same_artist = TrackFeatures::from_song(&song("b", "miles davis", "Bebop", "1970"), None);
+        let same_genre = TrackFeatures::from_song(&song("c", "Coltrane", "jazz", "1970"), None);
+        assert!(similarity(&seed, &same_artist) > similarity(&seed, &same_genre));
+        assert!(similarity(&seed, &same_genre) > 0.0);
+    }
+
+    #[test]
+    fn id3v1_genre_codes_match_names() {
+        let a = TrackFeatures::from_song(&song("a", "x", "(17)", ""), None);
+        let b = TrackFeatures::from_song(&song("b", "y", "rock", ""), None);
+        assert_eq!(a.genre, b.genre);
+    }
+
+    #[test]
+    fn unrelated_tracks_score_zero() {
+        let a = TrackFeatures::from_song(&song("a", "x", "Rock", "1970"), None);
+        let b = TrackFeatures::from_song(&song("b", "y", "Jazz", "2010"), None);
+        assert!(similarity(&a, &b).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn skips_and_dislikes_lower_weight_likes_raise_it() {
+        let liked = PlayItemStatistics {
+            liked_count: 1,
+            ..Default::default()
+        };
+        let skipped = PlayItemStatistics {
+            skipped_count: 2,
+            ..Default::default()
+        };
+        let disliked = PlayItemStatistics {
+            liked_count: -1,
+            ..Default::default()
+        };
+        assert!(history_weight(Some(&liked)) > history_weight(None));
+        assert!(history_weight(Some(&skipped)) < history_weight(None));
+        assert!(history_weight(Some(&disliked)) < history_weight(Some(&skipped)));
+    }
+
+    #[test]
+    fn find_similar_excludes_queue_and_limits_per_artist() {
+        let songs = Arc::new(InMemorySongRepository::default());
+        let stats = Arc::new(InMemoryPlayStatisticsRepository::default());
+        let loudness = Arc::new(InMemoryLoudnessRepository::default());
+        for s in [
+            song("seed", "A", "Rock", "1975"),
+            song("a1", "A", "Rock", "1976"),
+            song("a2", "A", "Rock", "1977"),
+            song("a3", "A", "Rock", "1978"),
+            song("b1", "B", "Rock", "1974"),
+            song("c1", "C", "Jazz", "2015"),
+        ] {
+            songs.save(&s).unwrap();
+        }
+        loudness
+            .save_loudness(
+                "b1",
+                TrackLoudness {
+                    loudness: -1400,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        stats
+            .save(&PlayItemStatistics {
+                play_item_id: "a3".to_owned(),
+                skipped_count: 10,
+                ..Default::default()
+            })
+            .unwrap();
+        let finder = SimilarTracks::new(songs.clone(), stats, loudness);
+        let seeds = vec![songs.find_by_id("seed").unwrap()];
+        let exclude: HashSet<String> = ["seed".to_owned()].into();
+
+        let picked: Vec<String> = finder.find_similar(&seeds, &exclude, 10).into_iter().map(|s| s.file).collect();
+        assert_eq!(picked, vec!["a1", "a2", "b1"]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Playlist files radio stations publish in place of the stream itself:
+//! `.pls` and `.m3u` wrappers listing stream URLs, and HLS (`.m3u8`), which
+//! [`crate::hls_reader`] plays.
+
+const PLAYLIST_CONTENT_TYPES: [&str; 7] = [
+    "application/vnd.apple.mpegurl",
+    "application/x-mpegurl",
+    "audio/mpegurl",
+    "audio/x-mpegurl",
+    "audio/x-scpls",
+    "audio/scpls",
+    "application/pls+xml",
+];
+const PLAYLIST_EXTENSIONS: [&str; 3] = ["m3u8", "m3u", "pls"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamPlaylist {
+    Hls,
+    /// Stream URLs in playlist order, resolved against the playlist URL.
+    Entries(Vec<String>),
+}
+
+/// Whether a response is a playlist rather than audio: by its content
+/// type, or by the extension of the URL path when the type is generic
+/// (`text/plain`, `application/octet-stream`…).
+pub fn is_playlist(content_type: &str, url: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if PLAYLIST_CONTENT_TYPES.contains(&mime.as_str()) {
+        return true;
+    }
+    if mime.starts_with("audio/") || mime == "application/ogg" {
+        return false;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit_once('.')
+        .is_some_and(|(_, ext)| PLAYLIST_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+pub fn parse(body: &str, base_url: &str) -> StreamPlaylist {
+    if body.contains("#EXT-X-") {
+        return StreamPlaylist::Hls;
+    }
+    let body = body.trim_start_matches('\u{feff}').trim_start();
+    let is_pls = body.get(..10).is_some_and(|header| header.eq_ignore_ascii_case("[playlist]"));
+    let entries = if is_pls {
+        let mut files: Vec<(u32, &str)> = body
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                let key = key.trim().to_ascii_lowercase();
+                let number = key.strip_prefix("file")?.parse().ok()?;
+                Some((number, value.trim()))
+            })
+            .collect();
+        files.sort_by_key(|(number, _)| *number);
+        files.into_iter().map(|(_, url)| resolve_url(base_url, url)).collect()
+    } else {
+        body.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| resolve_url(base_url, line))
+            .collect()
+    };
+    StreamPlaylist::Entries(entries)
+}
+
+/// `reference` as an absolute URL, relative references taken from the
+/// directory of `base`.
+pub fn resolve_url(base: &str, reference: &str) -> String {
+    if reference.contains("://") {
+        return reference.to_owned();
+    }
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return reference.to_owned();
+    };
+    if let Some(network_path) = reference.strip_prefix("//") {
+        return format!("{scheme}://{network_path}");
+    }
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    if reference.starts_with('/') {
+        let authority = path.split('/').next().unwrap_or_default();
+        return format!("{scheme}://{authority}{reference}");
+    }
+    path.rfind('/').map_or_else(
+        || format!("{scheme}://{path}/{reference}"),
+        |slash| format!("{scheme}://{}{reference}", &path[..=slash]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrappers_list_their_streams() {
+        let pls = "[playlist]\nNumberOfEntries=2\nFile2=http://backup.example.com/live\nTitle1=Main\nFile1=http://main.example.com/live\n";
+        assert_eq!(
+            parse(pls, "http://example.com/radio.pls"),
+            StreamPlaylist::Entries(vec![
+                "http://main.example.com/live".to_owned(),
+                "http://backup.example.com/live".to_owned()
+            ])
+        );
+        let m3u = "#EXTM3U\n#EXTINF:-1,Radio\n\nstream.mp3\n/other/stream.aac\n";
+        assert_eq!(
+            parse(m3u, "https://example.com/lists/radio.m3u?token=1"),
+            StreamPlaylist::Entries(vec![
+                "https://example.com/lists/stream.mp3".to_owned(),
+                "https://example.com/other/stream.aac".to_owned()
+            ])
+        );
+        assert_eq!(
+            parse("#EXTM3U\n#EXT-X-TARGETDURATION:6\nseg1.ts\n", "http://example.com/a.m3u8"),
+            StreamPlaylist::Hls
+        );
+    }
+
+    #[test]
+    fn playlists_are_told_from_audio() {
+        assert!(is_playlist("application/vnd.apple.mpegurl", "http://example.com/live"));
+        assert!(is_playlist("audio/x-scpls; charset=utf-8", "http://example.com/live"));
+        assert!(is_playlist("text/plain", "http://example.com/radio.PLS?id=3"));
+        assert!(!is_playlist("audio/mpeg", "http://example.com/radio.m3u"));
+        assert!(!is_playlist("", "http://example.com/live"));
+    }
+}
@@ -0,0 +1,340 @@
+//! Radio stream recording.
+//!
+//! One [`StreamRecorder`] is shared by the player and every HTTP stream it
+//! opens. A stream registers its station and container extension when it
+//! opens, and a [`RecordingTap`] around its body reader (for live streams,
+//! the player's reconnecting buffer) hands the recorder the raw, undecoded
+//! audio bytes (ICY metadata already stripped), which are written to
+//! `<recordings dir>/<station>/` as they are — no transcoding.
+//! With splitting on, every ICY title change closes the file and starts one
+//! named after the new title; `IcyMetadataReader` reports the title before it
+//! returns the bytes that follow it, so each file starts at the title change.
+//! A recording ends when it is stopped, when the stream stops playing, or on
+//! a write error; the recorder broadcasts its own state as `RecordingEvent`s.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Result as IoResult, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Result, format_err};
+use log::{info, warn};
+use tokio::sync::broadcast::Sender;
+
+use api_models::state::StateChangeEvent;
+
+/// File names are cut to this many characters, before the timestamp.
+const MAX_NAME_CHARS: usize = 120;
+
+struct OpenStream {
+    station: String,
+    extension: &'static str,
+    /// The last ICY title, if the station sent one.
+    title: Option<String>,
+}
+
+struct Recording {
+    dir: PathBuf,
+    split_on_title: bool,
+    file: BufWriter<File>,
+    path: PathBuf,
+    /// Bytes written to the current file.
+    written: u64,
+    files: usize,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    stream: Option<OpenStream>,
+    recording: Option<Recording>,
+}
+
+pub struct StreamRecorder {
+    state: Mutex<RecorderState>,
+    changes_tx: Sender<StateChangeEvent>,
+}
+
+impl StreamRecorder {
+    #[must_use]
+    pub fn new(changes_tx: Sender<StateChangeEvent>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(RecorderState::default()),
+            changes_tx,
+        })
+    }
+
+    /// Registers the HTTP stream starting to play; `extension` names its
+    /// container (`mp3`, `aac`, `ogg`…) and becomes the files' extension.
+    pub fn stream_opened(&self, station: &str, extension: &'static str) {
+        self.lock().stream = Some(OpenStream {
+            station: station.to_owned(),
+            extension,
+            title: None,
+        });
+    }
+
+    /// Forgets the stream that stopped playing, ending its recording.
+    pub fn stream_closed(&self) {
+        let recording = {
+            let mut state = self.lock();
+            state.stream = None;
+            state.recording.take()
+        };
+        if let Some(recording) = recording {
+            self.finish(recording);
+        }
+    }
+
+    /// The file the stream is being recorded to.
+    pub fn current_file(&self) -> Option<String> {
+        self.lock()
+            .recording
+            .as_ref()
+            .map(|recording| recording.path.to_string_lossy().into_owned())
+    }
+
+    /// Starts recording the stream playing now into a subdirectory of `dir`
+    /// named after the station.
+    pub fn start(&self, dir: &Path, split_on_title: bool) -> Result<()> {
+        let path = {
+            let mut state = self.lock();
+            if state.recording.is_some() {
+                return Err(format_err!("The stream is already being recorded"));
+            }
+            let stream = state
+                .stream
+                .as_ref()
+                .ok_or_else(|| format_err!("No radio stream that can be recorded is playing"))?;
+            let dir = dir.join(file_name(&stream.station));
+            fs::create_dir_all(&dir).map_err(|e| format_err!("Failed to create {}: {e}", dir.display()))?;
+            let (file, path) = create_file(&dir, stream)?;
+            state.recording = Some(Recording {
+                dir,
+                split_on_title,
+                file,
+                path: path.clone(),
+                written: 0,
+                files: 1,
+            });
+            path
+        };
+        info!("Recording radio stream to {}", path.display());
+        self.send(StateChangeEvent::RecordingEvent(Some(path.to_string_lossy().into_owned())));
+        Ok(())
+    }
+
+    /// `false` when nothing was being recorded.
+    pub fn stop(&self) -> bool {
+        let recording = self.lock().recording.take();
+        recording.map(|recording| self.finish(recording)).is_some()
+    }
+
+    /// Appends raw stream bytes to the recording, if one is running.
+    pub fn write(&self, data: &[u8]) {
+        let mut state = self.lock();
+        let Some(recording) = state.recording.as_mut() else {
+            return;
+        };
+        match recording.file.write_all(data) {
+            Ok(()) => recording.written += data.len() as u64,
+            Err(e) => {
+                let recording = state.recording.take();
+                drop(state);
+                self.fail(recording, &e.to_string());
+            }
+        }
+    }
+
+    /// Removes the last `len` bytes written, as far as they are in the
+    /// current file: the unfinished frame a dropped connection ended with.
+    pub fn take_back(&self, len: u64) {
+        let mut state = self.lock();
+        let Some(recording) = state.recording.as_mut() else {
+            return;
+        };
+        let kept = recording.written.saturating_sub(len);
+        let result = recording.file.flush().and_then(|()| {
+            let file = recording.file.get_mut();
+            file.set_len(kept)?;
+            file.seek(SeekFrom::End(0)).map(|_| ())
+        });
+        match result {
+            Ok(()) => recording.written = kept,
+            Err(e) => {
+                let recording = state.recording.take();
+                drop(state);
+                self.fail(recording, &e.to_string());
+            }
+        }
+    }
+
+    /// Notes the stream's new ICY title, moving on to a new file when the
+    /// recording splits on titles.
+    pub fn title_changed(&self, title: &str) {
+        let mut state = self.lock();
+        let RecorderState {
+            stream: Some(stream),
+            recording,
+        } = &mut *state
+        else {
+            return;
+        };
+        stream.title = Some(title.to_owned());
+        let Some(recording) = recording.as_mut().filter(|recording| recording.split_on_title) else {
+            return;
+        };
+        match create_file(&recording.dir, stream) {
+            Ok((file, path)) => {
+                let previous = std::mem::replace(&mut recording.file, file);
+                close_file(previous, &recording.path, recording.written);
+                recording.path = path;
+                recording.written = 0;
+                recording.files += 1;
+                let path = recording.path.to_string_lossy().into_owned();
+                drop(state);
+                self.send(StateChangeEvent::RecordingEvent(Some(path)));
+            }
+            Err(e) => {
+                let recording = state.recording.take();
+                drop(state);
+                self.fail(recording, &e.to_string());
+            }
+        }
+    }
+
+    fn finish(&self, recording: Recording) {
+        close_file(recording.file, &recording.path, recording.written);
+        info!("Radio recording finished: {} files in {}", recording.files, recording.dir.display());
+        self.send(StateChangeEvent::NotificationSuccess(format!(
+            "Recording saved to {}",
+            recording.dir.display()
+        )));
+        self.send(StateChangeEvent::RecordingEvent(None));
+    }
+
+    fn fail(&self, recording: Option<Recording>, error: &str) {
+        warn!("Radio recording stopped: {error}");
+        if let Some(recording) = recording {
+            close_file(recording.file, &recording.path, recording.written);
+        }
+        self.send(StateChangeEvent::NotificationError(format!("Recording stopped: {error}")));
+        self.send(StateChangeEvent::RecordingEvent(None));
+    }
+
+    fn send(&self, event: StateChangeEvent) {
+        self.changes_tx.send(event).ok();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RecorderState> {
+        self.state.lock().expect("recorder lock poisoned")
+    }
+}
+
+/// Hands every byte read from the inner reader to the [`StreamRecorder`].
+pub struct RecordingTap<R: Read> {
+    inner: R,
+    recorder: Arc<StreamRecorder>,
+}
+
+impl<R: Read> RecordingTap<R> {
+    pub const fn new(inner: R, recorder: Arc<StreamRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl<R: Read> Read for RecordingTap<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        if bytes_read > 0 {
+            self.recorder.write(&buf[..bytes_read]);
+        }
+        Ok(bytes_read)
+    }
+}
+
+/// A new file named after the stream's title (the station before the first
+/// title) and the current time.
+fn create_file(dir: &Path, stream: &OpenStream) -> Result<(BufWriter<File>, PathBuf)> {
+    let name = file_name(stream.title.as_deref().unwrap_or(&stream.station));
+    let time = chrono::Local::now().format("%Y-%m-%d %H-%M-%S");
+    let path = dir.join(format!("{name} {time}.{}", stream.extension));
+    let file = File::create(&path).map_err(|e| format_err!("Failed to create {}: {e}", path.display()))?;
+    Ok((BufWriter::new(file), path))
+}
+
+/// Flushes a finished file; a file nothing was written to is removed.
+fn close_file(mut file: BufWriter<File>, path: &Path, written: u64) {
+    if let Err(e) = file.flush() {
+        warn!("Failed to write {}: {e}", path.display());
+    }
+    drop(file);
+    if written == 0 {
+        fs::remove_file(path).ok();
+    }
+}
+
+/// `name` with the characters file systems reject replaced.
+fn file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').trim();
+    if cleaned.is_empty() {
+        "radio".to_owned()
+    } else {
+        cleaned.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn recording_splits_on_title_changes() {
+        let dir = std::env::temp_dir().join(format!("rsptest_recording_{}", Uuid::new_v4()));
+        let (tx, _rx) = tokio::sync::broadcast::channel(32);
+        let recorder = StreamRecorder::new(tx);
+        assert!(recorder.start(&dir, true).is_err());
+
+        recorder.stream_opened("Radio: Paradise", "mp3");
+        recorder.write(b"not recorded");
+        recorder.start(&dir, true).expect("start");
+        let mut tap = RecordingTap::new(&b"first song"[..], recorder.clone());
+        std::io::copy(&mut tap, &mut std::io::sink()).expect("read");
+        recorder.title_changed("Artist - Title/2");
+        recorder.title_changed("Artist - Title 3");
+        recorder.write(b"third song");
+        recorder.stream_closed();
+        assert!(!recorder.stop());
+
+        let station_dir = dir.join("Radio_ Paradise");
+        let mut files: Vec<(String, Vec<u8>)> = fs::read_dir(&station_dir)
+            .expect("station dir")
+            .map(|entry| {
+                let path = entry.expect("entry").path();
+                (
+                    path.file_name().expect("name").to_string_lossy().into_owned(),
+                    fs::read(&path).expect("file"),
+                )
+            })
+            .collect();
+        files.sort();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].0.starts_with("Artist - Title 3 "));
+        assert_eq!(Path::new(&files[0].0).extension(), Some("mp3".as_ref()));
+        assert_eq!(files[0].1, b"third song");
+        assert!(files[1].0.starts_with("Radio_ Paradise "));
+        assert_eq!(files[1].1, b"first song");
+    }
+}
@@ -0,0 +1,250 @@
+//! Writes tags back into the audio files.
+//!
+//! Each format's native tag (Vorbis comments, `ID3v2`, MP4 `ilst`, APEv2) is
+//! split into lofty's generic [`Tag`], edited, and merged back, so frames and
+//! atoms the editor does not know about survive the round trip. A file
+//! without a tag gets an empty one of its native type. Edits are saved to a
+//! hidden copy next to the file and renamed over it, so a failed write never
+//! leaves a half-written file. Used by the tag editor
+//! (`MetadataCommand::UpdateTags`) and by [`crate::loudness_tag_writer`].
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use lofty::{
+    ape::ApeFile,
+    config::{ParseOptions, WriteOptions},
+    file::AudioFile,
+    flac::FlacFile,
+    mp4::Mp4File,
+    mpeg::MpegFile,
+    ogg::{OpusFile, VorbisFile},
+    tag::{ItemKey, MergeTag, SplitTag, Tag},
+};
+
+use api_models::player::TagFields;
+
+/// Extensions [`edit_tags`] can write.
+const SUPPORTED_EXTENSIONS: [&str; 8] = ["flac", "ogg", "oga", "opus", "mp3", "m4a", "mp4", "ape"];
+
+#[must_use]
+pub fn is_supported(file: &str) -> bool {
+    extension(file).is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// A tag edit saved to a copy of the file. [`PendingEdit::commit`] replaces
+/// the file with it; dropping it discards the edit.
+struct PendingEdit {
+    path: PathBuf,
+    copy: PathBuf,
+    committed: bool,
+}
+
+impl PendingEdit {
+    fn commit(mut self) -> Result<()> {
+        fs::rename(&self.copy, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingEdit {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.copy);
+        }
+    }
+}
+
+/// Applies `fields` to the tags of the file at `path`.
+pub fn write_fields(path: &Path, fields: &TagFields) -> Result<()> {
+    edit_tags(path, |tag| apply_fields(tag, fields))
+}
+
+/// Runs `edit` on the file's tag and saves the file.
+pub(crate) fn edit_tags(path: &Path, edit: impl FnOnce(&mut Tag)) -> Result<()> {
+    prepare(path, edit)?.commit()
+}
+
+fn prepare(path: &Path, edit: impl FnOnce(&mut Tag)) -> Result<PendingEdit> {
+    let Some(name) = path.file_name().filter(|_| is_supported(&path.to_string_lossy())) else {
+        bail!("tags cannot be written to {}", path.display());
+    };
+    let copy = path.with_file_name(format!(".{}.tagedit", name.to_string_lossy()));
+    fs::copy(path, &copy)?;
+    let pending = PendingEdit {
+        path: path.to_path_buf(),
+        copy,
+        committed: false,
+    };
+    save_edited(path, &pending.copy, edit)?;
+    Ok(pending)
+}
+
+/// Reads the tag of `copy`, a copy of `path` (whose extension picks the
+/// format), runs `edit` on it and saves `copy`.
+fn save_edited(path: &Path, copy: &Path, edit: impl FnOnce(&mut Tag)) -> Result<()> {
+    let options = ParseOptions::new().read_properties(false);
+    let write = WriteOptions::default();
+    match extension(&path.to_string_lossy()).as_deref() {
+        Some("flac") => {
+            let mut file = FlacFile::read_from(&mut File::open(copy)?, options)?;
+            let comments = file.vorbis_comments().cloned().unwrap_or_default();
+            file.set_vorbis_comments(edited(comments, edit));
+            file.save_to_path(copy, write)?;
+        }
+        Some("ogg" | "oga") => {
+            let mut file = VorbisFile::read_from(&mut File::open(copy)?, options)?;
+            let comments = std::mem::take(file.vorbis_comments_mut());
+            *file.vorbis_comments_mut() = edited(comments, edit);
+            file.save_to_path(copy, write)?;
+        }
+        Some("opus") => {
+            let mut file = OpusFile::read_from(&mut File::open(copy)?, options)?;
+            let comments = std::mem::take(file.vorbis_comments_mut());
+            *file.vorbis_comments_mut() = edited(comments, edit);
+            file.save_to_path(copy, write)?;
+        }
+        Some("mp3") => {
+            let mut file = MpegFile::read_from(&mut File::open(copy)?, options)?;
+            let id3v2 = file.id3v2().cloned().unwrap_or_default();
+            file.set_id3v2(edited(id3v2, edit));
+            file.save_to_path(copy, write)?;
+        }
+        Some("m4a" | "mp4") => {
+            let mut file = Mp4File::read_from(&mut File::open(copy)?, options)?;
+            let ilst = file.ilst().cloned().unwrap_or_default();
+            file.set_ilst(edited(ilst, edit));
+            file.save_to_path(copy, write)?;
+        }
+        Some("ape") => {
+            let mut file = ApeFile::read_from(&mut File::open(copy)?, options)?;
+            let ape = file.ape().cloned().unwrap_or_default();
+            file.set_ape(edited(ape, edit));
+            file.save_to_path(copy, write)?;
+        }
+        _ => bail!("tags cannot be written to {}", path.display()),
+    }
+    Ok(())
+}
+
+fn edited<T>(native: T, edit: impl FnOnce(&mut Tag)) -> T
+where
+    T: SplitTag,
+    T::Remainder: MergeTag<Merged = T>,
+{
+    let (remainder, mut tag) = native.split_tag();
+    edit(&mut tag);
+    remainder.merge_tag(tag)
+}
+
+fn extension(file: &str) -> Option<String> {
+    Some(Path::new(file).extension()?.to_str()?.to_ascii_lowercase())
+}
+
+fn apply_fields(tag: &mut Tag, fields: &TagFields) {
+    let text = [
+        (&fields.title, ItemKey::TrackTitle),
+        (&fields.artist, ItemKey::TrackArtist),
+        (&fields.album, ItemKey::AlbumTitle),
+        (&fields.album_artist, ItemKey::AlbumArtist),
+        (&fields.date, ItemKey::RecordingDate),
+        (&fields.genre, ItemKey::Genre),
+        (&fields.composer, ItemKey::Composer),
+    ];
+    for (value, key) in text {
+        if let Some(value) = value {
+            set_text(tag, key, value);
+        }
+    }
+    if let Some(track) = &fields.track {
+        set_position(tag, ItemKey::TrackNumber, ItemKey::TrackTotal, track);
+    }
+    if let Some(disc) = &fields.disc {
+        set_position(tag, ItemKey::DiscNumber, ItemKey::DiscTotal, disc);
+    }
+}
+
+/// Sets `key`, or removes it when `value` is blank.
+fn set_text(tag: &mut Tag, key: ItemKey, value: &str) {
+    let value = value.trim();
+    if value.is_empty() {
+        tag.remove_key(&key);
+    } else {
+        tag.insert_text(key, value.to_owned());
+    }
+}
+
+/// `3` sets the number and keeps the total, `3/12` sets both, blank removes both.
+fn set_position(tag: &mut Tag, number_key: ItemKey, total_key: ItemKey, value: &str) {
+    let (number, total) = value.split_once('/').map_or((value, None), |(n, t)| (n, Some(t)));
+    set_text(tag, number_key, number);
+    if number.trim().is_empty() {
+        tag.remove_key(&total_key);
+    } else if let Some(total) = total {
+        set_text(tag, total_key, total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lofty::tag::{Accessor, TagType};
+
+    use super::*;
+
+    #[test]
+    fn fields_set_and_clear_generic_items() {
+        let mut tag = Tag::new(TagType::VorbisComments);
+        tag.set_genre("Rock".to_owned());
+        tag.insert_text(ItemKey::TrackTotal, "10".to_owned());
+        let fields = TagFields {
+            title: Some("Song".to_owned()),
+            genre: Some(String::new()),
+            track: Some("4/12".to_owned()),
+            ..Default::default()
+        };
+        apply_fields(&mut tag, &fields);
+        assert_eq!(tag.title().as_deref(), Some("Song"));
+        assert_eq!(tag.genre(), None);
+        assert_eq!(tag.get_string(&ItemKey::TrackNumber), Some("4"));
+        assert_eq!(tag.get_string(&ItemKey::TrackTotal), Some("12"));
+
+        apply_fields(
+            &mut tag,
+            &TagFields {
+                track: Some(String::new()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(tag.get_string(&ItemKey::TrackNumber), None);
+        assert_eq!(tag.get_string(&ItemKey::TrackTotal), None);
+    }
+
+    #[test]
+    fn failed_edit_leaves_file_untouched() {
+        let dir = std::env::temp_dir().join(format!("rsptest_tag_editor_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.flac");
+        fs::write(&path, b"not a flac file").unwrap();
+
+        let fields = TagFields {
+            title: Some("Song".to_owned()),
+            ..Default::default()
+        };
+        assert!(write_fields(&path, &fields).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"not a flac file");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "the edited copy was left behind");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn supported_formats_by_extension() {
+        assert!(is_supported("a/b/Song.FLAC"));
+        assert!(is_supported("a/b/song.ape"));
+        assert!(!is_supported("a/b/song.dsf"));
+        assert!(!is_supported("a/b/noext"));
+    }
+}
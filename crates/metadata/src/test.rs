@@ -2,8 +2,9 @@
 mod queue {
     use std::sync::Arc;
 
-    use api_models::common::PlaybackMode;
+    use api_models::{common::PlaybackMode, player::Song};
 
+    use crate::loudness_repository::FjallLoudnessRepository;
     use crate::play_statistic_repository::FjallPlayStatisticsRepository;
     use crate::song_repository::FjallSongRepository;
     use crate::{
@@ -360,6 +361,148 @@ mod queue {
         assert_eq!(queue.get_current_song().unwrap().file, "assets/music.mp3");
     }
 
+    #[test]
+    fn should_not_continue_when_auto_continue_is_off() {
+        let queue = create_queue();
+        queue.add_song(&create_song("mp3"));
+        assert!(!queue.move_current_to_next_song());
+        assert!(!queue.continue_with_similar_songs());
+        assert_eq!(queue.get_all_songs().len(), 1);
+    }
+
+    #[test]
+    fn should_append_similar_songs_when_queue_runs_out() {
+        use crate::ports::SongRepository;
+
+        let ctx = Box::leak(Box::new(Context::default()));
+        let db = fjall::Database::builder(&ctx.db_dir).open().expect("Failed to open test db");
+        let song_repo = Arc::new(FjallSongRepository::new(&db));
+        let mut seed = create_song("seed");
+        seed.artist = Some("Artist".to_owned());
+        seed.genre = Some("Rock".to_owned());
+        let mut similar = create_song("similar");
+        similar.artist = Some("artist".to_owned());
+        let mut unrelated = create_song("unrelated");
+        unrelated.artist = Some("Other".to_owned());
+        unrelated.genre = Some("Jazz".to_owned());
+        for song in [&seed, &similar, &unrelated] {
+            song_repo.save(song).expect("save song");
+        }
+        let queue = QueueService::new(
+            &db,
+            song_repo,
+            Arc::new(FjallPlayStatisticsRepository::new(&db)),
+            Arc::new(FjallLoudnessRepository::new(&db)),
+        );
+        queue.set_auto_continue(true);
+        queue.add_song(&seed);
+
+        assert!(!queue.move_current_to_next_song());
+        assert!(queue.continue_with_similar_songs());
+        assert_eq!(queue.get_current_song().unwrap().file, "assets/music.similar");
+        assert_eq!(queue.get_all_songs().len(), 2);
+        // Nothing similar left that is not already queued.
+        assert!(!queue.continue_with_similar_songs());
+    }
+
+    #[test]
+    fn should_undo_and_redo_queue_changes() {
+        let queue = create_queue();
+        queue.add_song(&create_song("mp3"));
+        queue.add_song(&create_song("flac"));
+        assert!(queue.move_current_to_next_song());
+        assert!(!queue.undo());
+
+        queue.checkpoint();
+        queue.clear();
+        assert!(queue.get_all_songs().is_empty());
+
+        assert!(queue.undo());
+        assert_eq!(queue.get_all_songs().len(), 2);
+        assert_eq!(queue.get_current_song().unwrap().file, "assets/music.flac");
+        assert!(!queue.undo());
+
+        assert!(queue.redo());
+        assert!(queue.get_all_songs().is_empty());
+        assert!(!queue.redo());
+    }
+
+    #[test]
+    fn should_drop_redo_after_new_change_and_bound_history() {
+        let queue = create_queue();
+        queue.checkpoint();
+        queue.add_song(&create_song("mp3"));
+        assert!(queue.undo());
+        queue.checkpoint();
+        queue.add_song(&create_song("flac"));
+        assert!(!queue.redo());
+
+        for ext in 0..crate::queue_service::HISTORY_LIMIT + 5 {
+            queue.checkpoint();
+            queue.add_song(&create_song(&ext.to_string()));
+        }
+        let mut undone = 0;
+        while queue.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, crate::queue_service::HISTORY_LIMIT);
+        assert_eq!(queue.get_all_songs().len(), 6);
+    }
+
+    #[test]
+    fn should_save_and_restore_snapshot() {
+        let queue = create_queue();
+        assert!(!queue.save_snapshot("empty"));
+        for ext in ["mp3", "flac", "wav"] {
+            queue.add_song(&create_song(ext));
+        }
+        assert!(queue.move_current_to_next_song());
+        while queue.get_playback_mode() != PlaybackMode::LoopQueue {
+            queue.cycle_playback_mode();
+        }
+        assert!(queue.save_snapshot("evening"));
+        assert!(queue.has_snapshot("evening"));
+        assert!(!queue.has_snapshot("morning"));
+
+        queue.replace_all(vec![create_song("aac")]);
+        queue.set_playback_mode(PlaybackMode::Sequential);
+
+        assert!(queue.restore_snapshot("evening"));
+        assert_eq!(queue.get_all_songs().len(), 3);
+        assert_eq!(queue.get_current_song().unwrap().file, "assets/music.flac");
+        assert_eq!(queue.get_playback_mode(), PlaybackMode::LoopQueue);
+
+        let snapshots = queue.get_snapshots();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].song_count, 3);
+        assert!(snapshots[0].songs.is_empty());
+        assert!(queue.delete_snapshot("evening"));
+        assert!(!queue.delete_snapshot("evening"));
+        assert!(!queue.restore_snapshot("evening"));
+    }
+
+    #[test]
+    fn should_detect_album_played_in_sequence() {
+        let queue = create_queue();
+        let track = |ext: &str, album: &str| Song {
+            album: Some(album.to_owned()),
+            artist: Some("Artist".to_owned()),
+            ..create_song(ext)
+        };
+        queue.add_song(&track("mp3", "A"));
+        queue.add_song(&track("flac", "A"));
+        queue.add_song(&track("wav", "B"));
+        assert!(queue.is_playing_album_in_sequence());
+        assert!(queue.move_current_to_next_song());
+        assert!(queue.is_playing_album_in_sequence());
+        assert!(queue.move_current_to_next_song());
+        assert!(!queue.is_playing_album_in_sequence());
+
+        assert!(queue.move_current_to_previous_song());
+        queue.set_playback_mode(PlaybackMode::Random);
+        assert!(!queue.is_playing_album_in_sequence());
+    }
+
     fn create_queue() -> Arc<QueueService> {
         // Leak the context so its Drop impl doesn't delete the DB directory
         // while the returned service still has it open.
@@ -371,7 +514,8 @@ mod queue {
         let db = fjall::Database::builder(&ctx.db_dir).open().expect("Failed to open test db");
         let song_repo = Arc::new(FjallSongRepository::new(&db));
         let stat_repo = Arc::new(FjallPlayStatisticsRepository::new(&db));
-        QueueService::new(&db, song_repo, stat_repo)
+        let loudness_repo = Arc::new(FjallLoudnessRepository::new(&db));
+        QueueService::new(&db, song_repo, stat_repo, loudness_repo)
     }
 }
 
@@ -478,10 +622,10 @@ mod metadata {
     fn test_like_media_item() {
         let ctx = TestContext::new();
         ctx.metadata_service.scan_music_dir(true, &ctx.sender);
-        ctx.metadata_service.like_media_item("aa/music.m4a");
+        ctx.metadata_service.like_media_item(None, "aa/music.m4a");
         let stat = ctx.stat_repository.find_by_id("aa/music.m4a").unwrap();
         assert_eq!(stat.liked_count, 1);
-        ctx.metadata_service.like_media_item("aa/music.m4a");
+        ctx.metadata_service.like_media_item(None, "aa/music.m4a");
         let stat = ctx.stat_repository.find_by_id("aa/music.m4a").unwrap();
         assert_eq!(stat.liked_count, 2);
     }
@@ -490,8 +634,8 @@ mod metadata {
     fn test_dislike_media_item() {
         let ctx = TestContext::new();
         ctx.metadata_service.scan_music_dir(true, &ctx.sender);
-        ctx.metadata_service.dislike_media_item("aa/music.m4a");
-        ctx.metadata_service.dislike_media_item("aa/music.m4a");
+        ctx.metadata_service.dislike_media_item(None, "aa/music.m4a");
+        ctx.metadata_service.dislike_media_item(None, "aa/music.m4a");
         let stat = ctx.stat_repository.find_by_id("aa/music.m4a").unwrap();
         assert_eq!(stat.liked_count, -2);
     }
@@ -499,8 +643,8 @@ mod metadata {
     #[test]
     fn test_favorite_radio_station() {
         let ctx = TestContext::new();
-        ctx.metadata_service.like_media_item("radio_uuid_http://radioaparat.com");
-        let favs = ctx.metadata_service.get_favorite_radio_stations();
+        ctx.metadata_service.like_media_item(None, "radio_uuid_http://radioaparat.com");
+        let favs = ctx.metadata_service.get_favorite_radio_stations(None);
         assert_eq!(favs.len(), 1);
         assert_eq!(favs.first().unwrap(), "http://radioaparat.com");
     }
@@ -581,13 +725,47 @@ mod metadata {
         let stat = ctx.stat_repository.find_by_id("aa/music.m4a").unwrap();
         assert_eq!(stat.play_count, 2);
     }
+
+    #[test]
+    fn test_increase_skip_count() {
+        let ctx = TestContext::new();
+        ctx.metadata_service.increase_skip_count("aa/music.m4a");
+        let stat = ctx.stat_repository.find_by_id("aa/music.m4a").unwrap();
+        assert_eq!(stat.skipped_count, 1);
+        assert_eq!(stat.play_count, 0);
+    }
+
+    #[test]
+    fn profiles_like_on_their_own() {
+        let ctx = TestContext::new();
+        ctx.metadata_service.scan_music_dir(true, &ctx.sender);
+        ctx.metadata_service.like_media_item(Some("anna"), "aa/music.m4a");
+        assert!(ctx.stat_repository.find_by_id("aa/music.m4a").is_none());
+        assert_eq!(ctx.metadata_service.get_liked_songs(Some("anna"), 10).len(), 1);
+        assert!(ctx.metadata_service.get_liked_songs(Some("bob"), 10).is_empty());
+        assert!(ctx.metadata_service.get_liked_songs(None, 10).is_empty());
+    }
+
+    #[test]
+    fn plays_count_for_the_household_and_the_listening_profile() {
+        let ctx = TestContext::new();
+        ctx.metadata_service.scan_music_dir(true, &ctx.sender);
+        ctx.metadata_service.increase_play_count("aa/music.m4a");
+        ctx.metadata_service.set_listening_profile(Some("anna".to_owned()));
+        ctx.metadata_service.increase_play_count("aa/music.m4a");
+        let stat = ctx.stat_repository.find_by_id("aa/music.m4a").unwrap();
+        assert_eq!(stat.play_count, 2);
+        let anna = ctx.metadata_service.get_song_statistics(Some("anna"), "aa/music.m4a").unwrap();
+        assert_eq!(anna.play_count, 1);
+        assert_eq!(ctx.metadata_service.get_library_stats(Some("anna")).total_plays, 1);
+    }
 }
 
 #[cfg(test)]
 mod playlist {
     use std::{sync::Arc, vec};
 
-    use api_models::player::Song;
+    use api_models::{player::Song, playlist::Playlist};
 
     use crate::playlist_service::PlaylistService;
 
@@ -596,8 +774,8 @@ mod playlist {
     #[test]
     fn should_save_new_playlist() {
         let svc = create_pl_service();
-        svc.save_new_playlist("plista1", &create_songs(10));
-        let plists = svc.get_playlists().items;
+        svc.save_new_playlist(None, "plista1", &create_songs(10));
+        let plists = svc.get_playlists(None).items;
         assert_eq!(plists.len(), 1);
         if let api_models::playlist::PlaylistType::Saved(pl) = &plists[0] {
             assert_eq!(pl.name, "plista1");
@@ -611,16 +789,130 @@ mod playlist {
         let svc = create_pl_service();
         let playlist_name1 = "plist1";
         let playlist_name2 = "plist2";
-        svc.save_new_playlist(playlist_name1, &create_songs(200));
-        svc.save_new_playlist(playlist_name2, &create_songs(100));
-        let pl1_page_2 = svc.get_playlist_page_by_name(playlist_name1, 10, 20);
+        svc.save_new_playlist(None, playlist_name1, &create_songs(200));
+        svc.save_new_playlist(None, playlist_name2, &create_songs(100));
+        let pl1 = svc.find_playlist_by_name(None, playlist_name1).unwrap();
+        let pl2 = svc.find_playlist_by_name(None, playlist_name2).unwrap();
+        let pl1_page_2 = svc.get_playlist_page(&pl1.id, 10, 20);
         assert_eq!(pl1_page_2.total, 200);
         assert_eq!(pl1_page_2.items.len(), 20);
-        let pl2_page_2 = svc.get_playlist_page_by_name(playlist_name2, 10, 10);
+        assert_eq!(pl1_page_2.items[0].file, "assets/music.10");
+        let pl2_page_2 = svc.get_playlist_page(&pl2.id, 10, 10);
         assert_eq!(pl2_page_2.total, 100);
         assert_eq!(pl2_page_2.items.len(), 10);
     }
 
+    #[test]
+    fn should_replace_items_when_saving_under_existing_name() {
+        let svc = create_pl_service();
+        svc.save_new_playlist(None, "plist1", &create_songs(5));
+        svc.save_new_playlist(None, "plist1", &create_songs(3));
+        assert_eq!(svc.get_playlists(None).items.len(), 1);
+        let pl = svc.find_playlist_by_name(None, "plist1").unwrap();
+        assert_eq!(svc.get_all_items(&pl.id).len(), 3);
+    }
+
+    #[test]
+    fn should_rename_and_update_details() {
+        let svc = create_pl_service();
+        let pl = svc.create_playlist(None, "old", &create_songs(2));
+        assert!(svc.rename_playlist(&pl.id, "new"));
+        assert!(svc.update_playlist_details(&pl.id, Some("desc".to_string()), Some("img".to_string())));
+        let renamed = svc.get_playlist(&pl.id).unwrap();
+        assert_eq!(renamed.name, "new");
+        assert_eq!(renamed.description.as_deref(), Some("desc"));
+        assert_eq!(renamed.image.as_deref(), Some("img"));
+        assert_eq!(svc.image_ids(), vec!["img".to_string()]);
+        assert_eq!(svc.get_all_items(&pl.id).len(), 2);
+        assert!(!svc.rename_playlist("missing", "x"));
+    }
+
+    #[test]
+    fn should_delete_playlist_and_items() {
+        let svc = create_pl_service();
+        let keep = svc.create_playlist(None, "keep", &create_songs(3));
+        let gone = svc.create_playlist(None, "gone", &create_songs(4));
+        assert!(svc.delete_playlist(&gone.id));
+        assert!(svc.get_playlist(&gone.id).is_none());
+        assert_eq!(svc.get_playlist_page(&gone.id, 0, 10).total, 0);
+        assert_eq!(svc.get_all_items(&keep.id).len(), 3);
+        assert!(!svc.delete_playlist(&gone.id));
+    }
+
+    #[test]
+    fn should_duplicate_playlist() {
+        let svc = create_pl_service();
+        let pl = svc.create_playlist(None, "orig", &create_songs(3));
+        svc.update_playlist_details(&pl.id, Some("desc".to_string()), None);
+        let copy = svc.duplicate_playlist(None, &pl.id, "copy").unwrap();
+        assert_ne!(copy.id, pl.id);
+        assert_eq!(svc.get_playlist(&copy.id).unwrap().description.as_deref(), Some("desc"));
+        assert_eq!(svc.get_all_items(&copy.id), svc.get_all_items(&pl.id));
+        assert_eq!(svc.get_playlists(None).items.len(), 2);
+    }
+
+    #[test]
+    fn should_list_profile_playlists_for_their_owner_only() {
+        let svc = create_pl_service();
+        svc.create_playlist(None, "shared", &create_songs(1));
+        let own = svc.create_playlist(Some("anna"), "mine", &create_songs(1));
+        assert_eq!(svc.get_playlists(Some("anna")).items.len(), 2);
+        assert_eq!(svc.get_playlists(Some("bob")).items.len(), 1);
+        assert_eq!(svc.get_playlists(None).items.len(), 1);
+        assert!(svc.is_visible_to(&own.id, Some("anna")));
+        assert!(!svc.is_visible_to(&own.id, None));
+        assert!(svc.find_playlist_by_name(Some("bob"), "mine").is_none());
+    }
+
+    #[test]
+    fn should_append_remove_and_move_items() {
+        let svc = create_pl_service();
+        let pl = svc.create_playlist(None, "edit", &create_songs(3));
+        assert!(svc.append_songs(&pl.id, &[create_song("x")]));
+        assert!(svc.remove_item(&pl.id, 1));
+        assert!(svc.move_item(&pl.id, 2, 0));
+        assert!(!svc.remove_item(&pl.id, 3));
+        assert!(!svc.move_item(&pl.id, 0, 3));
+        let files: Vec<String> = svc.get_all_items(&pl.id).into_iter().map(|s| s.file).collect();
+        assert_eq!(files, vec!["assets/music.x", "assets/music.0", "assets/music.2"]);
+        assert!(!svc.append_songs("missing", &[create_song("y")]));
+    }
+
+    #[test]
+    fn should_migrate_legacy_playlists_in_order() {
+        let ctx = Context::default();
+        let db = fjall::Database::builder(&ctx.db_dir).open().expect("Failed to open test db");
+        let items = db.keyspace("playlist", fjall::KeyspaceCreateOptions::default).unwrap();
+        let list = db.keyspace("playlist_list", fjall::KeyspaceCreateOptions::default).unwrap();
+        for (name, count) in [("mix", 12), ("mix1", 2)] {
+            for idx in 0..count {
+                items
+                    .insert(
+                        format!("{name}_{idx}").as_str(),
+                        create_song(&format!("{name}{idx}")).to_json_string_bytes(),
+                    )
+                    .unwrap();
+            }
+            let pl = Playlist {
+                name: name.to_string(),
+                id: name.to_string(),
+                ..Default::default()
+            };
+            list.insert(name, serde_json::to_vec(&pl).unwrap()).unwrap();
+        }
+
+        let svc = PlaylistService::new(&db);
+        let mix = svc.find_playlist_by_name(None, "mix").unwrap();
+        assert_ne!(mix.id, "mix");
+        assert!(svc.get_playlist("mix").is_none());
+        let files: Vec<String> = svc.get_all_items(&mix.id).into_iter().map(|s| s.file).collect();
+        let expected: Vec<String> = (0..12).map(|i| format!("assets/music.mix{i}")).collect();
+        assert_eq!(files, expected);
+        let mix1 = svc.find_playlist_by_name(None, "mix1").unwrap();
+        assert_eq!(svc.get_all_items(&mix1.id).len(), 2);
+        assert_eq!(svc.get_playlists(None).items.len(), 2);
+    }
+
     fn create_songs(number_of_songs: usize) -> Vec<Song> {
         let mut songs = vec![];
         for ext in 0..number_of_songs {
@@ -636,6 +928,58 @@ mod playlist {
     }
 }
 
+#[cfg(test)]
+mod bookmark {
+    use std::sync::Arc;
+
+    use crate::bookmark_service::BookmarkService;
+
+    use super::test_shared::{Context, create_song, create_song_with_title};
+
+    #[test]
+    fn should_save_and_clear_resume_point() {
+        let svc = create_bookmark_service();
+        assert_eq!(svc.get_resume_point("assets/music.mp3"), None);
+        svc.save_resume_point("assets/music.mp3", 754);
+        svc.save_resume_point("assets/music.flac", 12);
+        assert_eq!(svc.get_resume_point("assets/music.mp3"), Some(754));
+        svc.save_resume_point("assets/music.mp3", 800);
+        assert_eq!(svc.get_resume_point("assets/music.mp3"), Some(800));
+        svc.clear_resume_point("assets/music.mp3");
+        assert_eq!(svc.get_resume_point("assets/music.mp3"), None);
+        assert_eq!(svc.get_resume_point("assets/music.flac"), Some(12));
+    }
+
+    #[test]
+    fn should_add_list_and_delete_bookmarks() {
+        let svc = create_bookmark_service();
+        let chapter = create_song_with_title("Chapter 1");
+        let late = svc.add_bookmark(&chapter, "late", 900);
+        let early = svc.add_bookmark(&chapter, "early", 60);
+        let other = svc.add_bookmark(&create_song("zz"), "other", 5);
+
+        let stored = svc.get_bookmark(&late.id).unwrap();
+        assert_eq!(stored.name, "late");
+        assert_eq!(stored.song_file, chapter.file);
+        assert_eq!(stored.song_title, "Chapter 1");
+        assert_eq!(stored.position_secs, 900);
+
+        let ids: Vec<String> = svc.get_bookmarks().into_iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![early.id.clone(), late.id.clone(), other.id]);
+
+        assert!(svc.delete_bookmark(&early.id));
+        assert!(!svc.delete_bookmark(&early.id));
+        assert!(svc.get_bookmark(&early.id).is_none());
+        assert_eq!(svc.get_bookmarks().len(), 2);
+    }
+
+    fn create_bookmark_service() -> Arc<BookmarkService> {
+        let ctx = Context::default();
+        let db = fjall::Database::builder(&ctx.db_dir).open().expect("Failed to open test db");
+        BookmarkService::new(&db)
+    }
+}
+
 pub mod test_shared {
     use std::{path::Path, sync::Arc};
 
@@ -646,7 +990,9 @@ pub mod test_shared {
         album_repository::{AlbumRepository, FjallAlbumRepository},
         metadata_service::MetadataService,
         play_statistic_repository::{FjallPlayStatisticsRepository, PlayStatisticsRepository},
+        playlist_service::PlaylistService,
         song_repository::{FjallSongRepository, SongRepository},
+        work_repository::FjallWorkRepository,
     };
 
     pub fn create_song(ext: &str) -> Song {
@@ -718,6 +1064,9 @@ pub mod test_shared {
             let album_repository: Arc<dyn AlbumRepository> = Arc::new(FjallAlbumRepository::new(&db));
             let song_repository: Arc<dyn SongRepository> = Arc::new(FjallSongRepository::new(&db));
             let stat_repository: Arc<dyn PlayStatisticsRepository> = Arc::new(FjallPlayStatisticsRepository::new(&db));
+            let profile_stat_repository: Arc<dyn PlayStatisticsRepository> = Arc::new(FjallPlayStatisticsRepository::profiles(&db));
+            let work_repository = Arc::new(FjallWorkRepository::new(&db));
+            let playlist_service = PlaylistService::new(&db);
             let sender = tokio::sync::broadcast::channel(20).0;
             let receiver = sender.subscribe();
 
@@ -727,7 +1076,10 @@ pub mod test_shared {
                     &settings,
                     song_repository.clone(),
                     album_repository.clone(),
+                    work_repository,
                     stat_repository.clone(),
+                    profile_stat_repository,
+                    playlist_service,
                 )
                 .expect("Failed to create service"),
                 sender,
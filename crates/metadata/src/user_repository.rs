@@ -0,0 +1,84 @@
+//! Fjall-backed [`UserRepository`]: accounts keyed by user name, session
+//! and API tokens keyed by the SHA-256 of the token.
+
+use fjall::{Database, Keyspace, KeyspaceCreateOptions};
+
+use api_models::auth::{AccessToken, User};
+
+use crate::error::{RepoError, RepoResult};
+pub use crate::ports::user_repository::{ArcUserRepository, UserRepository};
+
+pub struct FjallUserRepository {
+    users_db: Keyspace,
+    tokens_db: Keyspace,
+}
+
+impl FjallUserRepository {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            users_db: db
+                .keyspace("users", KeyspaceCreateOptions::default)
+                .expect("Failed to open users keyspace"),
+            tokens_db: db
+                .keyspace("access_tokens", KeyspaceCreateOptions::default)
+                .expect("Failed to open access_tokens keyspace"),
+        }
+    }
+
+    /// Standalone constructor for tests — opens its own fjall Database.
+    pub fn new_standalone(db_path: &str) -> Self {
+        let db = Database::builder(db_path).open().expect("Failed to open users db");
+        Self::new(&db)
+    }
+}
+
+impl UserRepository for FjallUserRepository {
+    fn find_all_users(&self) -> Vec<User> {
+        self.users_db
+            .iter()
+            .filter_map(|guard| User::from_bytes(&guard.value().ok()?))
+            .collect()
+    }
+
+    fn find_user(&self, name: &str) -> Option<User> {
+        User::from_bytes(&self.users_db.get(name).ok()??)
+    }
+
+    fn save_user(&self, user: &User) -> RepoResult<()> {
+        if user.name.is_empty() {
+            return Err(RepoError::Invalid("user without name".to_owned()));
+        }
+        self.users_db
+            .insert(user.name.as_str(), user.to_json_string_bytes())
+            .map_err(|e| RepoError::Storage(format!("save user '{}': {e}", user.name)))
+    }
+
+    fn delete_user(&self, name: &str) -> RepoResult<()> {
+        self.users_db
+            .remove(name)
+            .map_err(|e| RepoError::Storage(format!("delete user '{name}': {e}")))
+    }
+
+    fn find_all_tokens(&self) -> Vec<AccessToken> {
+        self.tokens_db
+            .iter()
+            .filter_map(|guard| AccessToken::from_bytes(&guard.value().ok()?))
+            .collect()
+    }
+
+    fn find_token(&self, token_hash: &str) -> Option<AccessToken> {
+        AccessToken::from_bytes(&self.tokens_db.get(token_hash).ok()??)
+    }
+
+    fn save_token(&self, token: &AccessToken) -> RepoResult<()> {
+        self.tokens_db
+            .insert(token.token_hash.as_str(), token.to_json_string_bytes())
+            .map_err(|e| RepoError::Storage(format!("save access token '{}': {e}", token.id)))
+    }
+
+    fn delete_token(&self, token_hash: &str) -> RepoResult<()> {
+        self.tokens_db
+            .remove(token_hash)
+            .map_err(|e| RepoError::Storage(format!("delete access token: {e}")))
+    }
+}
@@ -0,0 +1,406 @@
+//! Accounts, sessions and API tokens for access control.
+//!
+//! Passwords are stored as salted PBKDF2-HMAC-SHA256 hashes. Tokens are 32
+//! random bytes handed out hex-encoded and stored only as their SHA-256, so
+//! a copy of the database does not let anyone in. Sessions expire after
+//! [`SESSION_DAYS`] days, API tokens live until they are revoked. Tokens
+//! name their user rather than a role, so role changes apply at once. The
+//! last admin can be neither removed nor demoted.
+
+use std::fmt::Write;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use log::info;
+use ring::digest::{self, SHA256};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use uuid::Uuid;
+
+use api_models::auth::{AccessToken, ApiTokenInfo, CreatedApiToken, Role, SaveUserRequest, TokenKind, User, UserInfo};
+
+use crate::error::{RepoError, RepoResult};
+use crate::ports::user_repository::ArcUserRepository;
+
+pub const SESSION_DAYS: i64 = 30;
+const HASH_SCHEME: &str = "pbkdf2-sha256";
+const PBKDF2_ITERATIONS: NonZeroU32 = NonZeroU32::new(100_000).unwrap();
+const SALT_LEN: usize = 16;
+const TOKEN_LEN: usize = 32;
+const MIN_PASSWORD_LEN: usize = 4;
+const MAX_NAME_LEN: usize = 64;
+
+pub struct UserService {
+    repository: ArcUserRepository,
+    rng: SystemRandom,
+}
+
+impl UserService {
+    #[must_use]
+    pub fn new(repository: ArcUserRepository) -> Arc<Self> {
+        Arc::new(Self {
+            repository,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn has_admin(&self) -> bool {
+        self.repository.find_all_users().iter().any(|u| u.role == Role::Admin)
+    }
+
+    /// All accounts, by name.
+    pub fn get_users(&self) -> Vec<UserInfo> {
+        let mut users: Vec<UserInfo> = self.repository.find_all_users().iter().map(User::info).collect();
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        users
+    }
+
+    /// Adds the account, or updates the one with the name. A new account
+    /// needs a password; a new password ends the user's sessions.
+    pub fn save_user(&self, request: &SaveUserRequest) -> RepoResult<UserInfo> {
+        let name = request.name.trim().to_owned();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN || name.chars().any(char::is_control) {
+            return Err(RepoError::Invalid(format!("'{name}' is not a valid user name")));
+        }
+        if let Some(password) = &request.password {
+            check_password(password)?;
+        }
+        let existing = self.repository.find_user(&name);
+        if existing.as_ref().is_some_and(|u| u.role == Role::Admin) && request.role != Role::Admin && self.admin_count() == 1 {
+            return Err(RepoError::Invalid("the last admin cannot be demoted".to_owned()));
+        }
+        let password_hash = match (&request.password, &existing) {
+            (Some(password), _) => self.hash_password(password)?,
+            (None, Some(user)) => user.password_hash.clone(),
+            (None, None) => return Err(RepoError::Invalid("a new user needs a password".to_owned())),
+        };
+        let user = User {
+            name,
+            role: request.role,
+            password_hash,
+        };
+        self.repository.save_user(&user)?;
+        if existing.is_some() && request.password.is_some() {
+            self.revoke_tokens(&user.name, Some(TokenKind::Session))?;
+        }
+        info!("Saved user {} as {:?}", user.name, user.role);
+        Ok(user.info())
+    }
+
+    /// Removes the account and its tokens; `false` when there is none.
+    pub fn delete_user(&self, name: &str) -> RepoResult<bool> {
+        let Some(user) = self.repository.find_user(name) else {
+            return Ok(false);
+        };
+        if user.role == Role::Admin && self.admin_count() == 1 {
+            return Err(RepoError::Invalid("the last admin cannot be removed".to_owned()));
+        }
+        self.revoke_tokens(name, None)?;
+        self.repository.delete_user(name)?;
+        info!("Deleted user {name}");
+        Ok(true)
+    }
+
+    pub fn change_password(&self, name: &str, current_password: &str, new_password: &str) -> RepoResult<()> {
+        let Some(mut user) = self.repository.find_user(name) else {
+            return Err(RepoError::Invalid(format!("no user '{name}'")));
+        };
+        if !verify_password(&user.password_hash, current_password) {
+            return Err(RepoError::Invalid("the current password is wrong".to_owned()));
+        }
+        check_password(new_password)?;
+        user.password_hash = self.hash_password(new_password)?;
+        self.repository.save_user(&user)?;
+        self.revoke_tokens(name, Some(TokenKind::Session))
+    }
+
+    /// A new session token for the user, `None` when the name or the
+    /// password is wrong.
+    pub fn login(&self, name: &str, password: &str) -> RepoResult<Option<(String, UserInfo)>> {
+        let Some(user) = self.repository.find_user(name.trim()) else {
+            // Take as long as a wrong password would, not telling which names exist.
+            let _ = self.hash_password(password);
+            return Ok(None);
+        };
+        if !verify_password(&user.password_hash, password) {
+            return Ok(None);
+        }
+        let now = Utc::now();
+        for expired in self
+            .repository
+            .find_all_tokens()
+            .into_iter()
+            .filter(|t| t.user == user.name && t.is_expired(now))
+        {
+            self.repository.delete_token(&expired.token_hash)?;
+        }
+        let (token, _) = self.issue_token(
+            &user.name,
+            TokenKind::Session,
+            String::new(),
+            Some(now + Duration::days(SESSION_DAYS)),
+        )?;
+        Ok(Some((token, user.info())))
+    }
+
+    /// The user a session or API token belongs to, while it is valid.
+    pub fn authenticate(&self, token: &str) -> Option<UserInfo> {
+        let hash = token_hash(token);
+        let access = self.repository.find_token(&hash)?;
+        if access.is_expired(Utc::now()) {
+            let _ = self.repository.delete_token(&hash);
+            return None;
+        }
+        self.repository.find_user(&access.user).as_ref().map(User::info)
+    }
+
+    pub fn logout(&self, token: &str) -> RepoResult<()> {
+        self.repository.delete_token(&token_hash(token))
+    }
+
+    pub fn create_api_token(&self, user: &str, name: &str) -> RepoResult<CreatedApiToken> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(RepoError::Invalid("an API token needs a name".to_owned()));
+        }
+        let (token, access) = self.issue_token(user, TokenKind::Api, name.to_owned(), None)?;
+        info!("Created API token '{name}' for {user}");
+        Ok(CreatedApiToken {
+            info: api_token_info(&access),
+            token,
+        })
+    }
+
+    /// API tokens of `user`, or of everyone when `None`; oldest first.
+    pub fn get_api_tokens(&self, user: Option<&str>) -> Vec<ApiTokenInfo> {
+        let mut tokens: Vec<ApiTokenInfo> = self
+            .repository
+            .find_all_tokens()
+            .iter()
+            .filter(|t| t.kind == TokenKind::Api && user.is_none_or(|user| t.user == user))
+            .map(api_token_info)
+            .collect();
+        tokens.sort_by_key(|t| t.created);
+        tokens
+    }
+
+    /// Revokes the API token with the id if it belongs to `user` (anyone's
+    /// when `None`); `false` when there is no such token.
+    pub fn revoke_api_token(&self, id: &str, user: Option<&str>) -> RepoResult<bool> {
+        let token = self
+            .repository
+            .find_all_tokens()
+            .into_iter()
+            .find(|t| t.id == id && t.kind == TokenKind::Api && user.is_none_or(|user| t.user == user));
+        match token {
+            Some(token) => self.repository.delete_token(&token.token_hash).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    fn admin_count(&self) -> usize {
+        self.repository.find_all_users().iter().filter(|u| u.role == Role::Admin).count()
+    }
+
+    /// Revokes the user's tokens of `kind`, or all of them.
+    fn revoke_tokens(&self, user: &str, kind: Option<TokenKind>) -> RepoResult<()> {
+        for token in self
+            .repository
+            .find_all_tokens()
+            .into_iter()
+            .filter(|t| t.user == user && kind.is_none_or(|kind| t.kind == kind))
+        {
+            self.repository.delete_token(&token.token_hash)?;
+        }
+        Ok(())
+    }
+
+    fn issue_token(
+        &self,
+        user: &str,
+        kind: TokenKind,
+        name: String,
+        expires: Option<chrono::DateTime<Utc>>,
+    ) -> RepoResult<(String, AccessToken)> {
+        let token = to_hex(&self.random_bytes::<TOKEN_LEN>()?);
+        let access = AccessToken {
+            id: Uuid::new_v4().to_string(),
+            token_hash: token_hash(&token),
+            user: user.to_owned(),
+            kind,
+            name,
+            created: Utc::now(),
+            expires,
+        };
+        self.repository.save_token(&access)?;
+        Ok((token, access))
+    }
+
+    fn hash_password(&self, password: &str) -> RepoResult<String> {
+        let salt = self.random_bytes::<SALT_LEN>()?;
+        let mut hash = [0u8; digest::SHA256_OUTPUT_LEN];
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, PBKDF2_ITERATIONS, &salt, password.as_bytes(), &mut hash);
+        Ok(format!("{HASH_SCHEME}${PBKDF2_ITERATIONS}${}${}", to_hex(&salt), to_hex(&hash)))
+    }
+
+    fn random_bytes<const N: usize>(&self) -> RepoResult<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.rng
+            .fill(&mut bytes)
+            .map_err(|_| RepoError::Storage("no random numbers from the system".to_owned()))?;
+        Ok(bytes)
+    }
+}
+
+fn check_password(password: &str) -> RepoResult<()> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(RepoError::Invalid(format!(
+            "a password needs at least {MIN_PASSWORD_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+fn verify_password(stored: &str, password: &str) -> bool {
+    let mut parts = stored.split('$');
+    let (Some(HASH_SCHEME), Some(iterations), Some(salt), Some(hash), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let (Some(iterations), Some(salt), Some(hash)) = (iterations.parse().ok(), from_hex(salt), from_hex(hash)) else {
+        return false;
+    };
+    pbkdf2::verify(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, password.as_bytes(), &hash).is_ok()
+}
+
+fn token_hash(token: &str) -> String {
+    to_hex(digest::digest(&SHA256, token.as_bytes()).as_ref())
+}
+
+fn api_token_info(token: &AccessToken) -> ApiTokenInfo {
+    ApiTokenInfo {
+        id: token.id.clone(),
+        name: token.name.clone(),
+        user: token.user.clone(),
+        created: token.created,
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ports::fakes::InMemoryUserRepository;
+    use crate::ports::user_repository::UserRepository;
+
+    fn request(name: &str, role: Role, password: Option<&str>) -> SaveUserRequest {
+        SaveUserRequest {
+            name: name.to_owned(),
+            role,
+            password: password.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn login_issues_sessions_for_the_right_password() {
+        let service = UserService::new(Arc::new(InMemoryUserRepository::default()));
+        assert!(!service.has_admin());
+        service.save_user(&request(" admin ", Role::Admin, Some("secret"))).expect("save");
+        assert!(service.has_admin());
+        assert!(service.login("admin", "wrong").expect("login").is_none());
+        assert!(service.login("nobody", "secret").expect("login").is_none());
+
+        let (token, user) = service.login("admin", "secret").expect("login").expect("session");
+        assert_eq!(user.role, Role::Admin);
+        assert_eq!(service.authenticate(&token).map(|u| u.name), Some("admin".to_owned()));
+        service.logout(&token).expect("logout");
+        assert!(service.authenticate(&token).is_none());
+    }
+
+    #[test]
+    fn password_changes_end_sessions_but_keep_api_tokens() {
+        let service = UserService::new(Arc::new(InMemoryUserRepository::default()));
+        service.save_user(&request("admin", Role::Admin, Some("secret"))).expect("save");
+        service.save_user(&request("kid", Role::Listener, Some("1234"))).expect("save");
+        let (session, _) = service.login("kid", "1234").expect("login").expect("session");
+        let api = service.create_api_token("kid", "script").expect("token");
+
+        assert!(service.change_password("kid", "wrong", "5678").is_err());
+        service.change_password("kid", "1234", "5678").expect("change");
+        assert!(service.authenticate(&session).is_none());
+        assert_eq!(service.authenticate(&api.token).map(|u| u.role), Some(Role::Listener));
+
+        // Roles are looked up per request.
+        service.save_user(&request("kid", Role::Guest, None)).expect("save");
+        assert_eq!(service.authenticate(&api.token).map(|u| u.role), Some(Role::Guest));
+        assert!(service.login("kid", "5678").expect("login").is_some());
+
+        assert_eq!(service.get_api_tokens(Some("admin")), Vec::new());
+        assert!(!service.revoke_api_token(&api.info.id, Some("admin")).expect("revoke"));
+        assert!(service.revoke_api_token(&api.info.id, None).expect("revoke"));
+        assert!(service.authenticate(&api.token).is_none());
+    }
+
+    #[test]
+    fn the_last_admin_stays() {
+        let service = UserService::new(Arc::new(InMemoryUserRepository::default()));
+        assert!(service.save_user(&request("admin", Role::Admin, None)).is_err());
+        assert!(service.save_user(&request("admin", Role::Admin, Some("123"))).is_err());
+        service.save_user(&request("admin", Role::Admin, Some("secret"))).expect("save");
+        assert!(service.save_user(&request("admin", Role::Listener, None)).is_err());
+        assert!(service.delete_user("admin").is_err());
+
+        service.save_user(&request("second", Role::Admin, Some("secret"))).expect("save");
+        let (token, _) = service.login("admin", "secret").expect("login").expect("session");
+        assert!(service.delete_user("admin").expect("delete"));
+        assert!(service.authenticate(&token).is_none());
+        assert!(!service.delete_user("admin").expect("delete"));
+        assert_eq!(service.get_users().len(), 1);
+    }
+
+    #[test]
+    fn expired_sessions_are_refused() {
+        let repository = Arc::new(InMemoryUserRepository::default());
+        let service = UserService::new(repository.clone());
+        service.save_user(&request("admin", Role::Admin, Some("secret"))).expect("save");
+        repository
+            .save_token(&AccessToken {
+                id: "old".to_owned(),
+                token_hash: token_hash("old-token"),
+                user: "admin".to_owned(),
+                kind: TokenKind::Session,
+                name: String::new(),
+                created: Utc::now() - Duration::days(SESSION_DAYS + 1),
+                expires: Some(Utc::now() - Duration::days(1)),
+            })
+            .expect("save");
+        assert!(service.authenticate("old-token").is_none());
+        assert!(repository.find_all_tokens().is_empty());
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(to_hex(&[0, 0xab, 0x10]), "00ab10");
+        assert_eq!(from_hex("00ab10"), Some(vec![0, 0xab, 0x10]));
+        assert_eq!(from_hex("0g"), None);
+        assert_eq!(from_hex("abc"), None);
+    }
+}
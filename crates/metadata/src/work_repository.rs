@@ -0,0 +1,324 @@
+//! Fjall-backed [`WorkRepository`]: classical works by composer.
+//!
+//! Songs with a composer and a work tag are filed as one [`WorkRecording`]
+//! per work and album, keyed `composer|work|album id` (normalized like album
+//! keys, see `genre_utils::normalize_name`), so the id of a work —
+//! `composer|work` — is a key prefix of all its recordings. Work, movement,
+//! conductor and orchestra tags are read from `Song.tags` under their Vorbis,
+//! `ID3v2` (`TXXX`, `MVNM`, `MVIN`, `TPE3`) and MP4 names; [`sort_movements`]
+//! puts the songs of a recording in playing order.
+
+use std::path::Path;
+
+use fjall::{Database, Keyspace, KeyspaceCreateOptions};
+
+use api_models::{player::Song, playlist::WorkRecording};
+
+use crate::album_identity::album_id_for_song;
+use crate::error::{RepoError, RepoResult};
+use crate::genre_utils::normalize_name;
+pub use crate::ports::work_repository::{ArcWorkRepository, WorkRepository};
+
+const WORK_TAGS: [&str; 2] = ["work", "©wrk"];
+const MOVEMENT_NAME_TAGS: [&str; 3] = ["movementname", "mvnm", "©mvn"];
+const MOVEMENT_TAGS: [&str; 4] = ["movement", "movementnumber", "mvin", "©mvi"];
+const CONDUCTOR_TAGS: [&str; 2] = ["conductor", "tpe3"];
+const ORCHESTRA_TAGS: [&str; 2] = ["orchestra", "ensemble"];
+
+pub struct FjallWorkRepository {
+    pub(crate) works_db: Keyspace,
+}
+
+impl FjallWorkRepository {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            works_db: db
+                .keyspace("works", KeyspaceCreateOptions::default)
+                .expect("Failed to open works keyspace"),
+        }
+    }
+
+    /// Standalone constructor for tests — opens its own fjall Database.
+    pub fn new_standalone(db_path: &str) -> Self {
+        let db = Database::builder(db_path).open().expect("Failed to open works db");
+        Self::new(&db)
+    }
+
+    pub fn work_db_key(composer: &str, work: &str) -> String {
+        format!("{}|{}", normalize_name(composer), normalize_name(work))
+    }
+
+    /// Id of the recording `song` belongs to: its work on its album, or in
+    /// its directory for songs without an album. `None` unless the song has
+    /// a composer and a work.
+    pub fn recording_id_for_song(song: &Song) -> Option<String> {
+        let composer = song.composer.as_deref().map(str::trim).filter(|c| !c.is_empty())?;
+        let work = work_of(song)?;
+        let album = album_id_for_song(song).unwrap_or_else(|| {
+            let dir = Path::new(&song.file).parent().map(|p| p.to_string_lossy()).unwrap_or_default();
+            normalize_name(&dir)
+        });
+        Some(format!("{}|{album}", Self::work_db_key(composer, work)))
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> Vec<WorkRecording> {
+        self.works_db
+            .prefix(prefix.as_bytes())
+            .filter_map(|guard| {
+                let (key, value) = guard.into_inner().ok()?;
+                let mut recording = WorkRecording::from_bytes(&value)?;
+                recording.id = String::from_utf8(key.to_vec()).ok()?;
+                Some(recording)
+            })
+            .collect()
+    }
+}
+
+impl WorkRepository for FjallWorkRepository {
+    fn delete_all(&self) {
+        _ = self.works_db.clear();
+    }
+
+    fn find_all_composers(&self) -> Vec<String> {
+        let mut pairs: Vec<(String, String)> = self
+            .find_by_prefix("")
+            .into_iter()
+            .map(|recording| (normalize_name(&recording.composer), recording.composer))
+            .collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        pairs.dedup_by(|a, b| a.0 == b.0);
+        pairs.into_iter().map(|(_, display)| display).collect()
+    }
+
+    fn find_works_by_composer(&self, composer: &str) -> Vec<(String, String)> {
+        let mut works: Vec<(String, String)> = self
+            .find_by_prefix(&format!("{}|", normalize_name(composer)))
+            .into_iter()
+            .map(|recording| (Self::work_db_key(&recording.composer, &recording.work), recording.work))
+            .collect();
+        works.sort_by(|a, b| a.0.cmp(&b.0));
+        works.dedup_by(|a, b| a.0 == b.0);
+        works.sort_by_key(|(_, title)| normalize_name(title));
+        works
+    }
+
+    fn find_recordings(&self, id: &str) -> Vec<WorkRecording> {
+        if let Some(mut recording) = self
+            .works_db
+            .get(id)
+            .ok()
+            .flatten()
+            .and_then(|bytes| WorkRecording::from_bytes(&bytes))
+        {
+            recording.id = id.to_owned();
+            return vec![recording];
+        }
+        self.find_by_prefix(&format!("{id}|"))
+    }
+
+    fn update_from_song(&self, song: &Song) -> RepoResult<()> {
+        let Some(key) = Self::recording_id_for_song(song) else {
+            return Ok(());
+        };
+        let existing = self
+            .works_db
+            .get(key.as_bytes())
+            .map_err(|e| RepoError::Storage(format!("read work recording '{key}': {e}")))?;
+        let mut recording = existing.and_then(|bytes| WorkRecording::from_bytes(&bytes)).unwrap_or_default();
+        if !recording.song_keys.contains(&song.file) {
+            recording.song_keys.push(song.file.clone());
+        }
+        if recording.composer.is_empty() {
+            recording.composer = song.composer.as_deref().unwrap_or_default().trim().to_owned();
+            recording.work = work_of(song).unwrap_or_default().to_owned();
+        }
+        if recording.album.is_none() {
+            recording.album = song.album.as_deref().map(str::trim).filter(|a| !a.is_empty()).map(str::to_owned);
+        }
+        if recording.date.is_none() {
+            recording.date.clone_from(&song.date);
+        }
+        for performer in performers(song) {
+            if !recording.performers.iter().any(|p| normalize_name(p) == normalize_name(&performer)) {
+                recording.performers.push(performer);
+            }
+        }
+        self.works_db
+            .insert(key.as_bytes(), recording.to_json_string_bytes())
+            .map_err(|e| RepoError::Storage(format!("write work recording '{key}': {e}")))
+    }
+
+    fn remove_from_song(&self, song: &Song) -> RepoResult<()> {
+        let Some(key) = Self::recording_id_for_song(song) else {
+            return Ok(());
+        };
+        let existing = self
+            .works_db
+            .get(key.as_bytes())
+            .map_err(|e| RepoError::Storage(format!("read work recording '{key}': {e}")))?;
+        let Some(mut recording) = existing.and_then(|bytes| WorkRecording::from_bytes(&bytes)) else {
+            return Ok(());
+        };
+        recording.song_keys.retain(|k| k != &song.file);
+        if recording.song_keys.is_empty() {
+            self.works_db
+                .remove(key.as_bytes())
+                .map_err(|e| RepoError::Storage(format!("delete empty work recording '{key}': {e}")))
+        } else {
+            self.works_db
+                .insert(key.as_bytes(), recording.to_json_string_bytes())
+                .map_err(|e| RepoError::Storage(format!("write work recording '{key}': {e}")))
+        }
+    }
+}
+
+/// Whether a raw tag key holds one of the tags works are indexed by, so the
+/// extractor keeps it in `Song.tags` even when it maps to a standard tag.
+pub fn is_work_tag(raw_key: &str) -> bool {
+    let name = tag_name(raw_key);
+    [
+        &WORK_TAGS[..],
+        &MOVEMENT_NAME_TAGS,
+        &MOVEMENT_TAGS,
+        &CONDUCTOR_TAGS,
+        &ORCHESTRA_TAGS,
+    ]
+    .iter()
+    .any(|names| names.contains(&name.as_str()))
+}
+
+pub fn work_of(song: &Song) -> Option<&str> {
+    tag_value(song, &WORK_TAGS)
+}
+
+/// `2. Adagio` from the movement number and name, `None` without a name.
+pub fn movement_title(song: &Song) -> Option<String> {
+    let name = tag_value(song, &MOVEMENT_NAME_TAGS)?;
+    Some(match tag_value(song, &MOVEMENT_TAGS).and_then(leading_number) {
+        Some(number) => format!("{number}. {name}"),
+        None => name.to_owned(),
+    })
+}
+
+/// Orders songs by movement number, then disc and track, then file.
+pub fn sort_movements(songs: &mut [Song]) {
+    songs.sort_by_cached_key(|song| {
+        (
+            tag_value(song, &MOVEMENT_TAGS).and_then(leading_number).unwrap_or(u32::MAX),
+            song.disc_number().unwrap_or(u32::MAX),
+            song.track_number().unwrap_or(u32::MAX),
+            song.file.clone(),
+        )
+    });
+}
+
+/// Conductor, then orchestra, then performer; the album artist or artist
+/// when none of them is tagged.
+fn performers(song: &Song) -> Vec<String> {
+    let tagged: Vec<String> = [
+        tag_value(song, &CONDUCTOR_TAGS),
+        tag_value(song, &ORCHESTRA_TAGS),
+        song.performer.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(str::trim)
+    .filter(|p| !p.is_empty())
+    .map(str::to_owned)
+    .collect();
+    if !tagged.is_empty() {
+        return tagged;
+    }
+    let artist = song.album_artist.as_deref().or(song.artist.as_deref()).map(str::trim);
+    artist.filter(|a| !a.is_empty()).map(str::to_owned).into_iter().collect()
+}
+
+/// Raw keys differ by format (`WORK`, `TXXX:WORK`, `----:com.apple.iTunes:WORK`, `©wrk`).
+fn tag_name(raw_key: &str) -> String {
+    raw_key.rsplit(':').next().unwrap_or(raw_key).to_lowercase()
+}
+
+fn tag_value<'a>(song: &'a Song, names: &[&str]) -> Option<&'a str> {
+    song.tags
+        .iter()
+        .find(|(key, value)| names.contains(&tag_name(key).as_str()) && !value.trim().is_empty())
+        .map(|(_, value)| value.trim())
+}
+
+/// `3` from `3`, `03` or `3/4`.
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value.trim().chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use api_models::player::Song;
+
+    use crate::test::test_shared;
+    use crate::work_repository::{FjallWorkRepository, WorkRepository, is_work_tag, movement_title, sort_movements};
+
+    fn movement(file: &str, album: &str, number: &str, name: &str) -> Song {
+        let mut song = Song {
+            file: file.to_owned(),
+            album: Some(album.to_owned()),
+            album_artist: Some("Berliner Philharmoniker".to_owned()),
+            composer: Some("Ludwig van Beethoven".to_owned()),
+            ..Default::default()
+        };
+        song.tags.insert("WORK".to_owned(), "Symphony No. 5".to_owned());
+        song.tags.insert("TXXX:MOVEMENT".to_owned(), number.to_owned());
+        song.tags.insert("MOVEMENTNAME".to_owned(), name.to_owned());
+        song
+    }
+
+    #[test]
+    fn works_are_browsable_by_composer_and_recording() {
+        let ctx = test_shared::Context::default();
+        let repo = FjallWorkRepository::new_standalone(&ctx.db_dir);
+        let mut conducted = movement("b/1.flac", "Fifth", "1", "Allegro con brio");
+        conducted.tags.insert("CONDUCTOR".to_owned(), "Carlos Kleiber".to_owned());
+        for song in [
+            movement("a/2.flac", "Symphonies", "2", "Andante con moto"),
+            movement("a/1.flac", "Symphonies", "1", "Allegro con brio"),
+            conducted,
+            Song {
+                file: "c/1.flac".to_owned(),
+                composer: Some("Ludwig van Beethoven".to_owned()),
+                ..Default::default()
+            },
+        ] {
+            repo.update_from_song(&song).expect("update_from_song failed");
+        }
+
+        assert_eq!(repo.find_all_composers(), ["Ludwig van Beethoven"]);
+        let works = repo.find_works_by_composer("ludwig van beethoven");
+        assert_eq!(works.len(), 1);
+        assert_eq!(works[0].1, "Symphony No. 5");
+        let recordings = repo.find_recordings(&works[0].0);
+        assert_eq!(recordings.len(), 2);
+        let kleiber = recordings.iter().find(|r| r.album.as_deref() == Some("Fifth")).expect("recording");
+        assert_eq!(kleiber.performers, ["Carlos Kleiber"]);
+        assert_eq!(repo.find_recordings(&kleiber.id).len(), 1);
+
+        repo.remove_from_song(&movement("b/1.flac", "Fifth", "1", ""))
+            .expect("remove_from_song failed");
+        assert_eq!(repo.find_recordings(&works[0].0).len(), 1);
+    }
+
+    #[test]
+    fn movements_sort_by_number_then_track() {
+        let mut songs = vec![
+            movement("x/b.flac", "A", "2/3", "Scherzo"),
+            movement("x/c.flac", "A", "10", "Finale"),
+            movement("x/a.flac", "A", "01", "Allegro"),
+        ];
+        sort_movements(&mut songs);
+        let files: Vec<&str> = songs.iter().map(|s| s.file.as_str()).collect();
+        assert_eq!(files, ["x/a.flac", "x/b.flac", "x/c.flac"]);
+        assert_eq!(movement_title(&songs[1]).as_deref(), Some("2. Scherzo"));
+        assert!(is_work_tag("----:com.apple.iTunes:MOVEMENTNAME"));
+        assert!(is_work_tag("©wrk"));
+        assert!(!is_work_tag("TXXX:MOOD"));
+    }
+}
@@ -8,7 +8,8 @@
 //! callback drains it, applying software volume last so volume changes act
 //! within one device buffer. Between push and drain sit the format-typed
 //! writers: rubato FFT resampling when the device can't do the source rate,
-//! channel mapping, EQ (`DspHandle` pending-swap) and VU metering.
+//! channel mapping, EQ (`DspHandle` pending-swap), VU metering and the
+//! `/stream` web radio tap (`OutputTee`).
 //!
 //! Opening negotiates the sample format/rate/channels against the device's
 //! capabilities with retry ladders for drivers that reject configs they
@@ -47,6 +48,7 @@ const LATENCY_FREEZE_AFTER_CALLBACKS: u32 = 64;
 
 use crate::rsp::device_capabilities::{fallback_rate_candidates, find_device_channels, find_device_rate};
 use crate::rsp::dsd::DsdU32;
+use crate::rsp::output_tee::OutputTee;
 use crate::rsp::vumeter::{VUMeter, cubic_gain};
 use dsp::DspHandle;
 use dsp::Equalizer;
@@ -98,6 +100,8 @@ where
     output_channels: usize,
     /// Reused buffer for mono→stereo (or other) channel mapping.
     channel_buf: Vec<T>,
+    output_tee: Option<OutputTee>,
+    output_rate: u32,
 }
 
 impl<T> AudioWriter for PcmWriter<T>
@@ -152,6 +156,9 @@ where
         // in the cpal output callback so volume changes take effect within
         // the cpal buffer latency rather than the ring_buffer_size_ms latency.
         let remaining: &[T] = if needs_channel_map { &self.channel_buf } else { &self.samples };
+        if let Some(tee) = &self.output_tee {
+            tee.send(self.output_rate, self.output_channels, remaining);
+        }
         push_to_ring(&self.producer, remaining, error_count)
     }
 }
@@ -194,6 +201,8 @@ where
     /// quantization on integer formats.
     interleaved_f32: Vec<f32>,
     interleaved_out: Vec<T>,
+    output_tee: Option<OutputTee>,
+    output_rate: u32,
 }
 
 impl<T> AudioWriter for ResamplingPcmWriter<T>
@@ -261,6 +270,10 @@ where
             vu.update_peaks(self.output_channels, &self.interleaved_f32);
         }
 
+        if let Some(tee) = &self.output_tee {
+            tee.send(self.output_rate, self.output_channels, &self.interleaved_f32);
+        }
+
         // Convert to the device sample type only once, at the very end.
         // Software volume gain is applied later in the cpal output callback.
         self.interleaved_out.clear();
@@ -315,6 +328,8 @@ impl AudioOutput {
         dsp_handle: Option<&DspHandle>,
         vu_meter: Option<VUMeter>,
         software_gain: Option<&Arc<AtomicU8>>,
+        fade_gain: Option<&Arc<AtomicU8>>,
+        output_tee: Option<&OutputTee>,
     ) -> Result<AudioOutput> {
         debug!("Spec: {spec:?}");
 
@@ -402,8 +417,11 @@ impl AudioOutput {
             None
         };
 
-        // For DSD streams, VU metering is also skipped.
+        // For DSD streams, VU metering is also skipped, and the sleep timer's
+        // fade: a DSD bitstream cannot be scaled.
         let effective_vu = if is_dsd { None } else { vu_meter };
+        let output_tee = output_tee.filter(|_| !is_dsd);
+        let fade_gain = fade_gain.filter(|_| !is_dsd);
         let spec_clone = spec;
 
         // Determine which ALSA buffer size(s) to try.
@@ -442,6 +460,8 @@ impl AudioOutput {
                 device_channels,
                 explicit_buf,
                 software_gain,
+                fade_gain,
+                output_tee,
             )
         } else {
             let mut r = Err(Error::msg("no buffer size tried"));
@@ -458,6 +478,8 @@ impl AudioOutput {
                     device_channels,
                     *buf,
                     software_gain,
+                    fade_gain,
+                    output_tee,
                 );
                 if r.is_ok() {
                     break;
@@ -502,6 +524,8 @@ impl AudioOutput {
                         device_channels,
                         explicit_buf,
                         software_gain,
+                        fade_gain,
+                        output_tee,
                     );
                     if retry.is_ok() {
                         result = retry;
@@ -521,6 +545,8 @@ impl AudioOutput {
                             device_channels,
                             *buf,
                             software_gain,
+                            fade_gain,
+                            output_tee,
                         );
                         if retry.is_ok() {
                             result = retry;
@@ -547,6 +573,8 @@ impl AudioOutput {
         device_channels: Option<u16>,
         buffer_size: cpal::BufferSize,
         software_gain: Option<&Arc<AtomicU8>>,
+        fade_gain: Option<&Arc<AtomicU8>>,
+        output_tee: Option<&OutputTee>,
     ) -> Result<AudioOutput> {
         let source_channels = spec.channels().count();
         let output_channels = device_channels.map_or(source_channels, |ch| ch as usize);
@@ -573,6 +601,7 @@ impl AudioOutput {
                 let (producer, consumer) = (ring_buf.producer(), ring_buf.consumer());
                 let ec_data = error_count_clone.clone();
                 let gain_level = software_gain.cloned();
+                let fade_level = fade_gain.cloned();
                 let cb_latency = device_latency_micros.clone();
                 let cb_latency_at = latency_measured_at_micros.clone();
                 let mut cb_samples_taken: u32 = 0;
@@ -608,14 +637,14 @@ impl AudioOutput {
                             // Apply software volume gain after draining the ring buffer
                             // so volume changes take effect within the cpal buffer
                             // latency, not the ring_buffer_size_ms latency.
-                            if let Some(ref level) = gain_level {
-                                let vol = level.load(Ordering::Relaxed);
-                                if vol < 100 {
-                                    let g = cubic_gain(vol);
-                                    for s in &mut data[..written] {
-                                        let f: f32 = (*s).into_sample();
-                                        *s = (f * g).into_sample();
-                                    }
+                            // The sleep-timer fade multiplies in on the same curve.
+                            let vol = gain_level.as_ref().map_or(100, |level| level.load(Ordering::Relaxed));
+                            let fade = fade_level.as_ref().map_or(100, |level| level.load(Ordering::Relaxed));
+                            if vol < 100 || fade < 100 {
+                                let g = cubic_gain(vol) * cubic_gain(fade);
+                                for s in &mut data[..written] {
+                                    let f: f32 = (*s).into_sample();
+                                    *s = (f * g).into_sample();
                                 }
                             }
                             // Successful callback — reset transient error counter.
@@ -672,6 +701,8 @@ impl AudioOutput {
                         channel_out,
                         interleaved_f32: Vec::with_capacity(max_out_samples),
                         interleaved_out: Vec::with_capacity(max_out_samples),
+                        output_tee: output_tee.cloned(),
+                        output_rate,
                     })
                 } else {
                     Box::new(PcmWriter {
@@ -680,6 +711,8 @@ impl AudioOutput {
                         source_channels,
                         output_channels,
                         channel_buf: Vec::new(),
+                        output_tee: output_tee.cloned(),
+                        output_rate,
                     })
                 };
                 let ring_fill: Box<dyn Fn() -> usize + Send> = Box::new(move || ring_buf.count());
@@ -812,9 +845,10 @@ impl AudioOutput {
         // blocking; if the writer is mid-update we pick it up next write().
         if let Some(dsp) = &mut self.dsp
             && let Ok(mut slot) = dsp.handle.pending.try_lock()
-            && let Some(new_eq) = slot.take()
+            && let Some(mut new_eq) = slot.take()
         {
             info!("Swapped in new equalizer with filters: {}", new_eq.has_filters());
+            new_eq.continue_from(&mut dsp.equalizer);
             dsp.equalizer = new_eq;
         }
 
@@ -2,40 +2,90 @@
 //!
 //! Local paths are resolved against the configured music directories;
 //! HTTP(S) URLs are fetched with `Icy-Metadata: 1` and wrapped in
-//! [`IcyMetadataReader`] so radio title updates flow out as events; APE and
-//! SACD-ISO keys (`…#SACD_<n>`) get their special readers.
+//! [`IcyMetadataReader`] so radio title updates flow out as events.
+//! Playlist URLs are resolved first: `.pls`/`.m3u` wrappers to their first
+//! playable entry, HLS to an [`HlsReader`]. Live streams (no
+//! `content-length`) are read through a [`ReconnectingStream`] that
+//! pre-buffers and re-requests the URL when the connection drops. Bodies
+//! are read with an [`IdleTimeout`], so a connection that stops sending
+//! fails instead of hanging. Streams are registered with the
+//! [`StreamRecorder`], so a recording can be started at any time; other
+//! bodies pass through a [`RecordingTap`], live streams are recorded by the
+//! [`ReconnectingStream`] once it cut them at frame boundaries. APE and
+//! SACD-ISO keys (`…#SACD_<n>`) get their special readers, which
+//! [`open_local_reader`] picks for playback and transcoding alike.
 
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Result, format_err};
 use symphonia::core::formats::probe::Hint;
-use symphonia::core::io::{MediaSource, ReadOnlySource};
-use tokio::sync::broadcast::Sender;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions, ReadOnlySource};
+use symphonia::core::meta::MetadataOptions;
+use tokio::sync::broadcast::{self, Sender};
+use ureq::ResponseExt;
+use ureq::http::Response;
 
 use api_models::state::StateChangeEvent;
-use log::info;
+use log::{debug, info, warn};
+use metadata::ape_bundle::ApeReader;
+use metadata::build_probe;
+use metadata::hls_reader::HlsReader;
 use metadata::icy_reader::IcyMetadataReader;
 use metadata::radio_meta::{self, RadioMeta};
+use metadata::sacd_bundle::SacdIsoReader;
+use metadata::stream_playlist::{self, StreamPlaylist};
+use metadata::stream_recorder::{RecordingTap, StreamRecorder};
+
+use crate::rsp::stream_buffer::{FrameSync, IdleTimeout, ReconnectingStream, StreamOpener};
+
+/// Playlists pointing at playlists are followed this deep.
+const MAX_PLAYLIST_DEPTH: usize = 3;
+const MAX_PLAYLIST_BYTES: u64 = 1024 * 1024;
+/// Assumed for the pre-buffer size when the station announces no bitrate.
+const DEFAULT_STREAM_KBPS: u32 = 128;
+/// A body read fails when no data arrived for this long.
+const BODY_IDLE_TIMEOUT: Duration = Duration::from_secs(15);
 
 pub fn probe_http_source(
     url: &str,
     hint: &mut Hint,
     changes_tx: &Sender<StateChangeEvent>,
+    recorder: &Arc<StreamRecorder>,
+    prebuffer_secs: u32,
 ) -> Result<(Box<dyn MediaSource>, Option<RadioMeta>)> {
-    use std::time::Duration;
-
+    // No global timeout: it would cut live streams, whose body never ends.
     let agent: ureq::Agent = ureq::Agent::config_builder()
         .timeout_connect(Some(Duration::from_secs(3)))
-        .timeout_global(Some(Duration::from_secs(30)))
+        .timeout_recv_response(Some(Duration::from_secs(10)))
         .build()
         .into();
 
-    let resp = agent
+    probe_http_url(&agent, url, hint, changes_tx, recorder, prebuffer_secs, 0)
+}
+
+fn request(agent: &ureq::Agent, url: &str) -> Result<Response<ureq::Body>> {
+    agent
         .get(url)
         .header("accept", "*/*")
         .header("Icy-Metadata", "1")
         .call()
-        .map_err(|e| format_err!("Failed to get url {url}: {e}"))?;
+        .map_err(|e| format_err!("Failed to get url {url}: {e}"))
+}
+
+fn probe_http_url(
+    agent: &ureq::Agent,
+    url: &str,
+    hint: &mut Hint,
+    changes_tx: &Sender<StateChangeEvent>,
+    recorder: &Arc<StreamRecorder>,
+    prebuffer_secs: u32,
+    depth: usize,
+) -> Result<(Box<dyn MediaSource>, Option<RadioMeta>)> {
+    let resp = request(agent, url)?;
 
     let status = resp.status().as_u16();
     info!(
@@ -46,7 +96,7 @@ pub fn probe_http_source(
         .iter()
         .for_each(|(name, value)| info!("{name} = {:?}", value.to_str().unwrap_or("")));
 
-    let radio_meta = radio_meta::get_external_radio_meta(&agent, &resp);
+    let radio_meta = radio_meta::get_external_radio_meta(agent, &resp);
 
     let ct_str = resp
         .headers()
@@ -71,22 +121,147 @@ pub fn probe_http_source(
         return Err(format_err!("Invalid streaming url {url}"));
     }
 
+    let final_url = resp.get_uri().to_string();
+    if stream_playlist::is_playlist(&ct_str, &final_url) {
+        let body = resp
+            .into_body()
+            .with_config()
+            .limit(MAX_PLAYLIST_BYTES)
+            .read_to_string()
+            .map_err(|e| format_err!("Failed to read playlist {url}: {e}"))?;
+        return match stream_playlist::parse(&body, &final_url) {
+            StreamPlaylist::Hls => {
+                let (reader, extension) = HlsReader::open(agent.clone(), &final_url, &body, changes_tx.clone(), radio_meta.clone())?;
+                if let Some(ext) = extension {
+                    hint.with_extension(ext);
+                    recorder.stream_opened(&station_name(url, radio_meta.as_ref()), ext);
+                }
+                let reader = RecordingTap::new(reader, recorder.clone());
+                Ok((Box::new(ReadOnlySource::new(reader)), radio_meta))
+            }
+            StreamPlaylist::Entries(entries) => {
+                probe_playlist_entries(agent, url, &entries, hint, changes_tx, recorder, prebuffer_secs, depth)
+            }
+        };
+    }
+
+    if let Some(ext) = ext {
+        recorder.stream_opened(&station_name(url, radio_meta.as_ref()), ext);
+    }
+    if resp.headers().contains_key("content-length") {
+        let reader = RecordingTap::new(body_reader(resp, changes_tx, recorder, radio_meta.as_ref()), recorder.clone());
+        return Ok((Box::new(ReadOnlySource::new(reader)), radio_meta));
+    }
+
+    let kbps = resp
+        .headers()
+        .get("icy-br")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.split(',').next()?.trim().parse::<u32>().ok())
+        .or_else(|| radio_meta.as_ref().and_then(|rm| rm.bitrate))
+        .unwrap_or(DEFAULT_STREAM_KBPS);
+    let prebuffer_bytes = usize::try_from(u64::from(prebuffer_secs) * u64::from(kbps) * 1000 / 8).unwrap_or(usize::MAX);
+    // Titles are published by the stream once playback reaches them, not
+    // when they are fetched up to the pre-buffer ahead.
+    let (titles_tx, titles_rx) = broadcast::channel(16);
+    let reader = body_reader(resp, &titles_tx, recorder, radio_meta.as_ref());
+    let reopen: StreamOpener = {
+        let (agent, url, titles_tx, recorder, radio_meta) =
+            (agent.clone(), url.to_owned(), titles_tx, recorder.clone(), radio_meta.clone());
+        Box::new(move || {
+            let resp = request(&agent, &url)?;
+            if resp.status().as_u16() != 200 {
+                return Err(format_err!("Invalid streaming url {url}"));
+            }
+            let reader: Box<dyn Read + Send> = body_reader(resp, &titles_tx, &recorder, radio_meta.as_ref());
+            Ok(reader)
+        })
+    };
+    let stream = ReconnectingStream::new(
+        reader,
+        reopen,
+        FrameSync::for_extension(ext),
+        prebuffer_bytes,
+        recorder.clone(),
+        changes_tx.clone(),
+        titles_rx,
+    );
+
+    Ok((Box::new(ReadOnlySource::new(stream)), radio_meta))
+}
+
+/// The response body, with ICY metadata stripped and its title changes sent
+/// to `changes_tx` when the station interleaves it. Reads fail after
+/// [`BODY_IDLE_TIMEOUT`] without data.
+fn body_reader(
+    resp: Response<ureq::Body>,
+    changes_tx: &Sender<StateChangeEvent>,
+    recorder: &Arc<StreamRecorder>,
+    radio_meta: Option<&RadioMeta>,
+) -> Box<dyn Read + Send + Sync> {
     let metaint_val = resp
         .headers()
         .get("icy-metaint")
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.parse::<usize>().ok());
 
-    let media_source: Box<dyn MediaSource> = if let (Some(metaint_val), Some(rm)) = (metaint_val, radio_meta.clone()) {
+    let reader = IdleTimeout::new(resp.into_body().into_reader(), BODY_IDLE_TIMEOUT);
+    if let (Some(metaint_val), Some(rm)) = (metaint_val, radio_meta) {
         info!("ICY stream detected with metaint={metaint_val}");
-        let reader = resp.into_body().into_reader();
-        let icy_reader = IcyMetadataReader::new(reader, metaint_val, changes_tx.clone(), rm);
-        Box::new(ReadOnlySource::new(Box::new(icy_reader)))
+        Box::new(IcyMetadataReader::new(
+            reader,
+            metaint_val,
+            changes_tx.clone(),
+            rm.clone(),
+            recorder.clone(),
+        ))
     } else {
-        Box::new(ReadOnlySource::new(resp.into_body().into_reader()))
-    };
+        Box::new(reader)
+    }
+}
 
-    Ok((media_source, radio_meta))
+/// Recordings of the stream go to a directory with this name: the station's
+/// ICY name, or the host of its URL.
+fn station_name(url: &str, radio_meta: Option<&RadioMeta>) -> String {
+    radio_meta
+        .and_then(|rm| rm.name.clone())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| {
+            url.split("://")
+                .nth(1)
+                .and_then(|rest| rest.split(['/', '?', ':']).next())
+                .unwrap_or(url)
+                .to_owned()
+        })
+}
+
+/// The first entry of a `.pls`/`.m3u` playlist that opens.
+#[allow(clippy::too_many_arguments)]
+fn probe_playlist_entries(
+    agent: &ureq::Agent,
+    url: &str,
+    entries: &[String],
+    hint: &mut Hint,
+    changes_tx: &Sender<StateChangeEvent>,
+    recorder: &Arc<StreamRecorder>,
+    prebuffer_secs: u32,
+    depth: usize,
+) -> Result<(Box<dyn MediaSource>, Option<RadioMeta>)> {
+    if depth >= MAX_PLAYLIST_DEPTH {
+        return Err(format_err!("Playlist {url} nests too many playlists"));
+    }
+    let mut last_error = format_err!("Playlist {url} lists no stream");
+    for entry in entries {
+        info!("Playlist {url}: trying {entry}");
+        match probe_http_url(agent, entry, hint, changes_tx, recorder, prebuffer_secs, depth + 1) {
+            Ok(source) => return Ok(source),
+            Err(e) => {
+                warn!("{e}");
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
 }
 
 pub fn probe_local_file(path_str: &str, music_dirs: &[String], hint: &mut Hint) -> Result<(Box<dyn MediaSource>, Option<RadioMeta>)> {
@@ -102,6 +277,35 @@ pub fn probe_local_file(path_str: &str, music_dirs: &[String], hint: &mut Hint)
     Err(format_err!("Unable to open file: {path_str}"))
 }
 
+/// Opens a local track with the reader it needs: SACD ISO and APE keys get
+/// their direct readers, everything else is probed by Symphonia.
+pub fn open_local_reader(path_str: &str, music_dirs: &[String]) -> Result<Box<dyn FormatReader>> {
+    if let Some((iso_path, track_idx)) = resolve_sacd_iso_path(path_str, music_dirs) {
+        debug!("SACD ISO: {} track {}", iso_path.display(), track_idx);
+        let file = std::fs::File::open(&iso_path).map_err(|e| format_err!("Failed to open SACD ISO {}: {e}", iso_path.display()))?;
+        return Ok(Box::new(
+            SacdIsoReader::try_new_for_track(file, track_idx).map_err(|e| format_err!("Failed to open SACD track: {e}"))?,
+        ));
+    }
+    if let Some(ape_path) = resolve_ape_path(path_str, music_dirs) {
+        debug!("APE direct file path: {}", ape_path.display());
+        // 1 MB buffer to amortize NFS round trips — default 8 KB causes
+        // dozens of small reads per frame, starving the audio ring buffer on slow links.
+        let file = std::io::BufReader::with_capacity(1024 * 1024, std::fs::File::open(&ape_path)?);
+        return Ok(Box::new(ApeReader::try_new_from_reader(file)?));
+    }
+    let mut hint = Hint::new();
+    let (source, _) = probe_local_file(path_str, music_dirs, &mut hint)?;
+    build_probe()
+        .probe(
+            &hint,
+            MediaSourceStream::new(source, MediaSourceStreamOptions::default()),
+            FormatOptions::default(),
+            MetadataOptions::default(),
+        )
+        .map_err(|_| format_err!("Media source probe failed"))
+}
+
 /// Resolve an APE file path across music directories.
 /// Returns the full path if found, None otherwise.
 pub fn resolve_ape_path(path_str: &str, music_dirs: &[String]) -> Option<PathBuf> {
@@ -0,0 +1,210 @@
+//! DSD to PCM conversion for transcoding. Playback never uses it — DSD goes
+//! to the DAC bit for bit (see `dsd`) — but browsers cannot play DSD at all.
+//!
+//! A windowed-sinc low-pass runs over the 1-bit stream and is decimated to
+//! [`PCM_RATE`]. It works a byte at a time: every byte position of the
+//! filter window has a table with its eight taps summed for all 256 bit
+//! patterns, so a PCM sample costs one lookup per byte instead of one
+//! multiply per bit. DSD's 0 dB (50% modulation) comes out at -6 dBFS,
+//! which leaves the headroom SACDs use.
+
+use std::f64::consts::PI;
+
+use anyhow::{Result, format_err};
+
+/// PCM rate for DSD64 and its multiples; DSD rates of the 48 kHz family
+/// come out at 96 kHz.
+pub const PCM_RATE: u32 = 88_200;
+/// Filter length in output samples.
+const FILTER_SPAN: usize = 32;
+/// Passes the audio band, removes the noise DSD shapes above it.
+const CUTOFF_HZ: f64 = 30_000.0;
+
+pub struct DsdToPcm {
+    channels: usize,
+    /// Bytes of DSD per channel behind each PCM sample.
+    bytes_per_sample: usize,
+    /// `window` tables of 256 entries, oldest byte position first.
+    tables: Vec<f32>,
+    window: usize,
+    history: Vec<ChannelHistory>,
+    out: Vec<f32>,
+    pcm_rate: u32,
+}
+
+/// The last `window` bytes of one channel, stored twice so they can always
+/// be read as one slice.
+struct ChannelHistory {
+    bytes: Vec<u8>,
+    pos: usize,
+    pending: usize,
+}
+
+impl DsdToPcm {
+    /// `dsd_rate` is the 1-bit rate, e.g. 2 822 400 for DSD64.
+    pub fn new(dsd_rate: u32, channels: usize) -> Result<Self> {
+        let bits_per_sample = (dsd_rate / PCM_RATE) as usize / 8 * 8;
+        if bits_per_sample == 0 || channels == 0 {
+            return Err(format_err!("Cannot convert {channels} channel DSD at {dsd_rate}Hz"));
+        }
+        let bytes_per_sample = bits_per_sample / 8;
+        let window = bytes_per_sample * FILTER_SPAN;
+        let taps = lowpass(window * 8, CUTOFF_HZ / f64::from(dsd_rate));
+        let mut tables = vec![0.0; window * 256];
+        for (position, table) in tables.chunks_exact_mut(256).enumerate() {
+            let taps = &taps[position * 8..position * 8 + 8];
+            for (pattern, entry) in table.iter_mut().enumerate() {
+                // The most significant bit is the earliest.
+                let sum: f64 = taps
+                    .iter()
+                    .enumerate()
+                    .map(|(bit, tap)| if pattern & (0x80 >> bit) == 0 { -tap } else { *tap })
+                    .sum();
+                #[allow(clippy::cast_possible_truncation)]
+                {
+                    *entry = sum as f32;
+                }
+            }
+        }
+        let history = (0..channels)
+            .map(|_| ChannelHistory {
+                // Start from DSD silence rather than a full-scale step.
+                bytes: vec![0x69; window * 2],
+                pos: 0,
+                pending: 0,
+            })
+            .collect();
+        #[allow(clippy::cast_possible_truncation)]
+        let pcm_rate = dsd_rate / bits_per_sample as u32;
+        Ok(Self {
+            channels,
+            bytes_per_sample,
+            tables,
+            window,
+            history,
+            out: Vec::new(),
+            pcm_rate,
+        })
+    }
+
+    pub const fn pcm_rate(&self) -> u32 {
+        self.pcm_rate
+    }
+
+    /// Converts interleaved DSD words as the DSD decoder produces them —
+    /// four bytes per word, earliest byte lowest — into interleaved PCM.
+    pub fn process(&mut self, words: &[u32]) -> &[f32] {
+        self.out.clear();
+        let mut frame_out = vec![0.0; self.channels];
+        for frame in words.chunks_exact(self.channels) {
+            for byte in 0..4 {
+                let mut ready = false;
+                for (ch, word) in frame.iter().enumerate() {
+                    let history = &mut self.history[ch];
+                    let b = word.to_le_bytes()[byte];
+                    history.bytes[history.pos] = b;
+                    history.bytes[history.pos + self.window] = b;
+                    history.pos = (history.pos + 1) % self.window;
+                    history.pending += 1;
+                    if history.pending == self.bytes_per_sample {
+                        history.pending = 0;
+                        let recent = &history.bytes[history.pos..history.pos + self.window];
+                        frame_out[ch] = recent
+                            .iter()
+                            .zip(self.tables.chunks_exact(256))
+                            .map(|(&b, table)| table[usize::from(b)])
+                            .sum();
+                        ready = true;
+                    }
+                }
+                if ready {
+                    self.out.extend_from_slice(&frame_out);
+                }
+            }
+        }
+        &self.out
+    }
+}
+
+/// Blackman-windowed sinc with a DC gain of one; `cutoff` is relative to
+/// the sample rate.
+fn lowpass(len: usize, cutoff: f64) -> Vec<f64> {
+    #[allow(clippy::cast_precision_loss)]
+    let taps: Vec<f64> = (0..len)
+        .map(|i| {
+            let n = i as f64;
+            let m = (len - 1) as f64;
+            let x = n - m / 2.0;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            let window = 0.08f64.mul_add((4.0 * PI * n / m).cos(), 0.5f64.mul_add(-(2.0 * PI * n / m).cos(), 0.42));
+            sinc * window
+        })
+        .collect();
+    let sum: f64 = taps.iter().sum();
+    taps.into_iter().map(|t| t / sum).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DSD64: u32 = 2_822_400;
+
+    /// First-order sigma-delta modulation of `signal`, packed the way the
+    /// DSD decoder delivers it.
+    fn modulate(signal: impl Fn(f64) -> f64, bits: usize) -> Vec<u32> {
+        let mut integrator = 0.0;
+        let mut out = 0.0;
+        let mut bytes = Vec::with_capacity(bits / 8);
+        let mut byte = 0u8;
+        for i in 0..bits {
+            #[allow(clippy::cast_precision_loss)]
+            let t = i as f64 / f64::from(DSD64);
+            integrator += signal(t) - out;
+            let bit = integrator >= 0.0;
+            out = if bit { 1.0 } else { -1.0 };
+            byte = (byte << 1) | u8::from(bit);
+            if i % 8 == 7 {
+                bytes.push(byte);
+            }
+        }
+        bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn silence_pattern_converts_to_zero() {
+        let mut converter = DsdToPcm::new(DSD64, 2).expect("converter");
+        assert_eq!(converter.pcm_rate(), PCM_RATE);
+        let pcm = converter.process(&vec![0x6969_6969; 2 * 4096]);
+        assert_eq!(pcm.len(), 2 * 4096);
+        assert!(pcm.iter().all(|s| s.abs() < 1e-4));
+    }
+
+    #[test]
+    fn sine_keeps_its_level() {
+        let mut converter = DsdToPcm::new(DSD64, 1).expect("converter");
+        let words = modulate(|t| 0.5 * (2.0 * PI * 1000.0 * t).sin(), DSD64 as usize / 10);
+        let pcm = converter.process(&words);
+        // Skip the filter's warm-up.
+        let settled = &pcm[FILTER_SPAN * 2..];
+        let peak = settled.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        #[allow(clippy::cast_precision_loss)]
+        let rms = (settled.iter().map(|s| s * s).sum::<f32>() / settled.len() as f32).sqrt();
+        assert!((peak - 0.5).abs() < 0.02, "peak {peak}");
+        assert!((rms - 0.5 / 2f32.sqrt()).abs() < 0.01, "rms {rms}");
+    }
+
+    #[test]
+    fn dsd128_is_decimated_to_the_same_rate() {
+        let mut converter = DsdToPcm::new(DSD64 * 2, 2).expect("converter");
+        assert_eq!(converter.pcm_rate(), PCM_RATE);
+        assert_eq!(converter.process(&vec![0x6969_6969; 2 * 100]).len(), 2 * 50);
+    }
+}
@@ -0,0 +1,439 @@
+//! A small streaming FLAC encoder.
+//!
+//! Fixed predictors (orders 0–4) with partitioned Rice coding and
+//! independent channels — no LPC or stereo decorrelation, which keeps it
+//! cheap enough to run per listener on a Raspberry Pi at the cost of a few
+//! percent of size. STREAMINFO is written up front with the total length
+//! and MD5 left unset, as a live stream has neither.
+
+use anyhow::{Result, format_err};
+
+use super::AudioEncoder;
+
+const BLOCK_SIZE: usize = 4096;
+const MAX_PARTITION_ORDER: u32 = 6;
+const MAX_FIXED_ORDER: usize = 4;
+/// Largest Rice parameter; 31 is the escape code, never used here.
+const MAX_RICE_PARAMETER: u32 = 30;
+
+pub struct FlacEncoder {
+    rate: u32,
+    channels: usize,
+    bits_per_sample: u32,
+    /// Interleaved samples waiting for a full block.
+    pending: Vec<i32>,
+    frame_number: u64,
+    header_written: bool,
+}
+
+impl FlacEncoder {
+    pub fn new(rate: u32, channels: usize, bits_per_sample: u32) -> Result<Self> {
+        if !(1..=8).contains(&channels) {
+            return Err(format_err!("FLAC cannot encode {channels} channels"));
+        }
+        if !matches!(bits_per_sample, 16 | 24) {
+            return Err(format_err!("FLAC encoding supports 16 and 24 bits, not {bits_per_sample}"));
+        }
+        if !(1..=655_350).contains(&rate) {
+            return Err(format_err!("FLAC cannot encode {rate}Hz"));
+        }
+        Ok(Self {
+            rate,
+            channels,
+            bits_per_sample,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels),
+            frame_number: 0,
+            header_written: false,
+        })
+    }
+
+    fn write_header(&mut self, out: &mut Vec<u8>) {
+        if self.header_written {
+            return;
+        }
+        self.header_written = true;
+        let mut w = BitWriter::default();
+        w.put(u64::from(u32::from_be_bytes(*b"fLaC")), 32);
+        // Last metadata block, type STREAMINFO, 34 bytes.
+        w.put(1, 1);
+        w.put(0, 7);
+        w.put(34, 24);
+        w.put(BLOCK_SIZE as u64, 16);
+        w.put(BLOCK_SIZE as u64, 16);
+        // Minimum and maximum frame size: unknown.
+        w.put(0, 24);
+        w.put(0, 24);
+        w.put(u64::from(self.rate), 20);
+        w.put(self.channels as u64 - 1, 3);
+        w.put(u64::from(self.bits_per_sample - 1), 5);
+        // Total samples: unknown.
+        w.put(0, 36);
+        // MD5 of the audio: unset.
+        w.put(0, 32);
+        w.put(0, 32);
+        w.put(0, 32);
+        w.put(0, 32);
+        out.append(&mut w.bytes);
+    }
+
+    fn write_frame(&mut self, samples: &[i32], out: &mut Vec<u8>) {
+        let block = samples.len() / self.channels;
+        let mut w = BitWriter::default();
+        w.put(0b11_1111_1111_1110, 14);
+        w.put(0, 1);
+        // Fixed block size strategy.
+        w.put(0, 1);
+        // Block size from the 16 bits at the end of the header.
+        w.put(0b0111, 4);
+        // Sample rate from STREAMINFO.
+        w.put(0b0000, 4);
+        // Independent channels.
+        w.put(self.channels as u64 - 1, 4);
+        w.put(if self.bits_per_sample == 16 { 0b100 } else { 0b110 }, 3);
+        w.put(0, 1);
+        w.put_utf8(self.frame_number);
+        w.put(block as u64 - 1, 16);
+        let crc = crc8(&w.bytes);
+        w.put(u64::from(crc), 8);
+
+        let mut channel = Vec::with_capacity(block);
+        for c in 0..self.channels {
+            channel.clear();
+            channel.extend(samples.iter().skip(c).step_by(self.channels));
+            write_subframe(&channel, self.bits_per_sample, &mut w);
+        }
+        w.align();
+        let crc = crc16(&w.bytes);
+        w.put(u64::from(crc), 16);
+        out.append(&mut w.bytes);
+        self.frame_number += 1;
+    }
+}
+
+impl AudioEncoder for FlacEncoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write_header(&mut out);
+        // Full scale is 2^(bits-1), as decoders divide by it; +1.0 itself
+        // does not fit and is clamped with everything beyond it.
+        let full_scale = 1i32 << (self.bits_per_sample - 1);
+        #[allow(clippy::cast_precision_loss)]
+        let scale = full_scale as f32;
+        #[allow(clippy::cast_possible_truncation)]
+        self.pending.extend(
+            samples
+                .iter()
+                .map(|s| ((s * scale).round() as i32).clamp(-full_scale, full_scale - 1)),
+        );
+        let frame_len = BLOCK_SIZE * self.channels;
+        let mut start = 0;
+        let pending = std::mem::take(&mut self.pending);
+        while pending.len() - start >= frame_len {
+            self.write_frame(&pending[start..start + frame_len], &mut out);
+            start += frame_len;
+        }
+        self.pending = pending;
+        self.pending.drain(..start);
+        Ok(out)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write_header(&mut out);
+        let pending = std::mem::take(&mut self.pending);
+        if !pending.is_empty() {
+            self.write_frame(&pending, &mut out);
+        }
+        Ok(out)
+    }
+}
+
+fn write_subframe(samples: &[i32], bits: u32, w: &mut BitWriter) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        w.put(0b0000_0000, 8);
+        w.put_signed(i64::from(samples[0]), bits);
+        return;
+    }
+    let order = best_fixed_order(samples);
+    let residual = fixed_residual(samples, order);
+    let (partition_order, parameters, residual_bits) = best_partitioning(&residual, samples.len(), order);
+    let fixed_bits = residual_bits + order as u64 * u64::from(bits);
+    if fixed_bits >= samples.len() as u64 * u64::from(bits) {
+        w.put(0b0000_0010, 8);
+        for &s in samples {
+            w.put_signed(i64::from(s), bits);
+        }
+        return;
+    }
+    w.put(0b0001_0000 | ((order as u64) << 1), 8);
+    for &s in &samples[..order] {
+        w.put_signed(i64::from(s), bits);
+    }
+    // Rice coding with 5-bit parameters.
+    w.put(0b01, 2);
+    w.put(u64::from(partition_order), 4);
+    let partition_len = samples.len() >> partition_order;
+    let mut start = 0;
+    for (i, &parameter) in parameters.iter().enumerate() {
+        let end = (i + 1) * partition_len - order;
+        w.put(u64::from(parameter), 5);
+        for &r in &residual[start..end] {
+            let folded = fold(r);
+            w.put_unary(folded >> parameter);
+            w.put(folded, parameter);
+        }
+        start = end;
+    }
+}
+
+/// The fixed predictor order with the smallest residual.
+fn best_fixed_order(samples: &[i32]) -> usize {
+    (0..=MAX_FIXED_ORDER.min(samples.len() - 1))
+        .min_by_key(|&order| fixed_residual(samples, order).iter().map(|r| r.unsigned_abs()).sum::<u64>())
+        .unwrap_or(0)
+}
+
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i64> {
+    let x = |i: usize| i64::from(samples[i]);
+    (order..samples.len())
+        .map(|i| match order {
+            0 => x(i),
+            1 => x(i) - x(i - 1),
+            2 => x(i) - 2 * x(i - 1) + x(i - 2),
+            3 => x(i) - 3 * x(i - 1) + 3 * x(i - 2) - x(i - 3),
+            _ => x(i) - 4 * x(i - 1) + 6 * x(i - 2) - 4 * x(i - 3) + x(i - 4),
+        })
+        .collect()
+}
+
+/// The partition order, per-partition Rice parameters and residual size
+/// (in bits, headers included) that code `residual` smallest.
+fn best_partitioning(residual: &[i64], block: usize, order: usize) -> (u32, Vec<u32>, u64) {
+    let max_order = (0..=MAX_PARTITION_ORDER)
+        .rev()
+        .find(|&p| block.is_multiple_of(1 << p) && block >> p > order)
+        .unwrap_or(0);
+    // Folded sums and lengths of the finest partitions, merged pairwise
+    // for each coarser order.
+    let partitions = 1usize << max_order;
+    let partition_len = block >> max_order;
+    let mut sums = Vec::with_capacity(partitions);
+    let mut lens = Vec::with_capacity(partitions);
+    let mut start = 0;
+    for i in 0..partitions {
+        let end = (i + 1) * partition_len - order;
+        sums.push(residual[start..end].iter().map(|&r| fold(r)).sum::<u64>());
+        lens.push((end - start) as u64);
+        start = end;
+    }
+    let mut best: Option<(u32, Vec<u32>, u64)> = None;
+    let mut partition_order = max_order;
+    loop {
+        let (parameters, bits): (Vec<u32>, Vec<u64>) = sums.iter().zip(&lens).map(|(&sum, &len)| rice_parameter(sum, len)).unzip();
+        let total = bits.iter().sum::<u64>() + 6 + 5 * parameters.len() as u64;
+        if best.as_ref().is_none_or(|(_, _, b)| total < *b) {
+            best = Some((partition_order, parameters, total));
+        }
+        if partition_order == 0 {
+            break;
+        }
+        partition_order -= 1;
+        sums = sums.chunks(2).map(|pair| pair.iter().sum()).collect();
+        lens = lens.chunks(2).map(|pair| pair.iter().sum()).collect();
+    }
+    best.unwrap_or_else(|| (0, vec![0], 0))
+}
+
+/// The Rice parameter for `len` folded values adding up to `sum`, and the
+/// estimated size of the coded values in bits.
+fn rice_parameter(sum: u64, len: u64) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|k| (k, len * u64::from(k + 1) + (sum >> k)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+/// Maps signed residuals onto unsigned: 0, -1, 1, -2, 2…
+#[allow(clippy::cast_sign_loss)]
+const fn fold(r: i64) -> u64 {
+    ((r << 1) ^ (r >> 63)) as u64
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `n` (at most 32) bits of `value`.
+    fn put(&mut self, value: u64, n: u32) {
+        if n == 0 {
+            return;
+        }
+        self.acc = (self.acc << n) | (value & ((1 << n) - 1));
+        self.bits += n;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            #[allow(clippy::cast_possible_truncation)]
+            self.bytes.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn put_signed(&mut self, value: i64, n: u32) {
+        self.put(value as u64, n);
+    }
+
+    /// `q` zeros and a one.
+    fn put_unary(&mut self, mut q: u64) {
+        while q >= 32 {
+            self.put(0, 32);
+            q -= 32;
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        self.put(1, q as u32 + 1);
+    }
+
+    /// The frame number coding of FLAC frame headers, UTF-8 style.
+    fn put_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.put(value, 8);
+            return;
+        }
+        // Each continuation byte carries 6 bits, the lead byte what is left.
+        let continuation: u32 = match 64 - value.leading_zeros() {
+            ..=11 => 1,
+            12..=16 => 2,
+            17..=21 => 3,
+            22..=26 => 4,
+            27..=31 => 5,
+            _ => 6,
+        };
+        let lead = (0xFF00u64 >> (continuation + 1)) & 0xFF;
+        self.put(lead | (value >> (6 * continuation)), 8);
+        for i in (0..continuation).rev() {
+            self.put(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+        }
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.put(0, 8 - self.bits);
+        }
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |crc, &b| {
+        (0..8).fold(crc ^ b, |c, _| if c & 0x80 == 0 { c << 1 } else { (c << 1) ^ 0x07 })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(
+            crc ^ (u16::from(b) << 8),
+            |c, _| {
+                if c & 0x8000 == 0 { c << 1 } else { (c << 1) ^ 0x8005 }
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rsp::encoder::readback;
+
+    #[test]
+    fn frames_carry_sync_codes_and_checksums() {
+        let mut encoder = FlacEncoder::new(48_000, 2, 16).expect("encoder");
+        let samples: Vec<f32> = (0..BLOCK_SIZE * 2 + 100)
+            .flat_map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let s = (i as f32 / 20.0).sin() * 0.8;
+                [s, -s]
+            })
+            .collect();
+        let mut stream = encoder.encode(&samples).expect("encode");
+        stream.extend(encoder.finish().expect("finish"));
+
+        assert_eq!(&stream[..4], b"fLaC");
+        assert_eq!(stream[4], 0x80);
+        let mut frames = Vec::new();
+        let mut at = 42;
+        while at < stream.len() {
+            assert_eq!(&stream[at..at + 2], &[0xFF, 0xF8], "sync at {at}");
+            frames.push(at);
+            // Frames are found from the next sync code; none of these frames
+            // contains one by chance.
+            at = (at + 2..stream.len() - 1)
+                .find(|&i| stream[i] == 0xFF && stream[i + 1] == 0xF8)
+                .unwrap_or(stream.len());
+        }
+        assert_eq!(frames.len(), 3);
+        frames.push(stream.len());
+        for pair in frames.windows(2) {
+            let frame = &stream[pair[0]..pair[1]];
+            assert_eq!(crc16(frame), 0, "frame at {}", pair[0]);
+        }
+        // Sine waves predict well: far below 16-bit PCM.
+        assert!(stream.len() < samples.len());
+    }
+
+    #[test]
+    fn silence_is_coded_as_constant_subframes() {
+        let mut encoder = FlacEncoder::new(44_100, 2, 24).expect("encoder");
+        let stream = encoder.encode(&vec![0.0; BLOCK_SIZE * 2]).expect("encode");
+        // Header (42) + frame header (8) + two 4-byte constant subframes + CRC.
+        assert_eq!(stream.len(), 42 + 8 + 4 + 4 + 2);
+        assert!(FlacEncoder::new(44_100, 9, 16).is_err());
+    }
+
+    #[test]
+    fn decodes_to_the_samples_encoded() {
+        for (channels, bits) in [(2, 16), (1, 24), (3, 16)] {
+            let full_scale = 1i32 << (bits - 1);
+            // Steps of the target depth, so encoding loses nothing: a tone,
+            // noise that needs verbatim subframes, silence and both extremes.
+            #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let samples: Vec<f32> = (0..BLOCK_SIZE * 2 + 100)
+                .flat_map(|i| {
+                    (0..channels).map(move |c| {
+                        let value = match (i / 1000 + c) % 4 {
+                            0 => ((i as f64 / 20.0).sin() * 0.7 * f64::from(full_scale)).round() as i32,
+                            1 => ((i * 7919) % 65_536) as i32 - 32_768,
+                            2 => 0,
+                            _ => {
+                                if i % 2 == 0 {
+                                    full_scale - 1
+                                } else {
+                                    -full_scale
+                                }
+                            }
+                        };
+                        value as f32 / full_scale as f32
+                    })
+                })
+                .collect();
+            let mut encoder = FlacEncoder::new(44_100, channels, bits).expect("encoder");
+            let mut stream = encoder.encode(&samples).expect("encode");
+            stream.extend(encoder.finish().expect("finish"));
+
+            let decoded = readback::decode(stream, "flac");
+            assert_eq!((decoded.rate, decoded.channels), (44_100, channels));
+            assert_eq!(decoded.samples.len(), samples.len(), "{channels}ch {bits} bit");
+            assert!(decoded.samples == samples, "{channels}ch {bits} bit samples differ");
+        }
+    }
+
+    #[test]
+    fn samples_scale_by_full_scale_and_clamp() {
+        let mut encoder = FlacEncoder::new(44_100, 1, 16).expect("encoder");
+        encoder.encode(&[0.5, -1.0, 1.0, 2.0, -2.0]).expect("encode");
+        assert_eq!(encoder.pending, [16_384, -32_768, 32_767, 32_767, -32_768]);
+    }
+}
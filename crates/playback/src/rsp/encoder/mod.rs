@@ -0,0 +1,98 @@
+//! Audio encoders behind the `/stream` web radio: MP3 (LAME), Ogg/Opus
+//! (libopus) and FLAC (an own fixed-predictor encoder).
+//!
+//! [`StreamEncoder`] turns the `OutputTee`'s chunks into one continuous
+//! stream at [`STREAM_RATE`] in stereo, resampling and remapping channels
+//! whenever a track's format differs, so a listener never sees the format
+//! change mid-stream.
+
+mod flac;
+mod mp3;
+mod opus;
+#[cfg(test)]
+pub mod readback;
+mod resample;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use api_models::settings::AudioEncoding;
+
+use crate::rsp::output_tee::OutputChunk;
+pub use flac::FlacEncoder;
+pub use mp3::Mp3Encoder;
+pub use opus::OpusEncoder;
+pub use resample::Resampler;
+
+/// Rate of the `/stream` output — the one Opus requires.
+pub const STREAM_RATE: u32 = opus::OPUS_RATE;
+const STREAM_CHANNELS: usize = 2;
+/// FLAC stream sample depth.
+const STREAM_FLAC_BITS: u32 = 16;
+
+pub trait AudioEncoder: Send {
+    /// Encodes interleaved samples, returning the bytes completed so far;
+    /// the first call's bytes start with the stream headers.
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>>;
+    /// Encodes what is still buffered and ends the stream.
+    fn finish(&mut self) -> Result<Vec<u8>>;
+}
+
+/// Encodes the player's output for one `/stream` listener.
+pub struct StreamEncoder {
+    encoder: Box<dyn AudioEncoder>,
+    /// Converts the current track's rate; `None` when it is already
+    /// [`STREAM_RATE`].
+    resampler: Option<Resampler>,
+    source_rate: u32,
+    stereo: Vec<f32>,
+}
+
+impl StreamEncoder {
+    pub fn new(encoding: AudioEncoding, bitrate_kbps: u32) -> Result<Self> {
+        let encoder: Box<dyn AudioEncoder> = match encoding {
+            AudioEncoding::Mp3 => Box::new(Mp3Encoder::new(STREAM_RATE, STREAM_CHANNELS, bitrate_kbps)?),
+            AudioEncoding::Opus => Box::new(OpusEncoder::new(STREAM_CHANNELS, bitrate_kbps)?),
+            AudioEncoding::Flac => Box::new(FlacEncoder::new(STREAM_RATE, STREAM_CHANNELS, STREAM_FLAC_BITS)?),
+        };
+        Ok(Self {
+            encoder,
+            resampler: None,
+            source_rate: STREAM_RATE,
+            stereo: Vec::new(),
+        })
+    }
+
+    /// The stream headers, so a listener gets them before any audio plays.
+    pub fn start(&mut self) -> Result<Vec<u8>> {
+        self.encoder.encode(&[])
+    }
+
+    pub fn encode(&mut self, chunk: &OutputChunk) -> Result<Vec<u8>> {
+        if chunk.rate != self.source_rate {
+            self.source_rate = chunk.rate;
+            self.resampler = (chunk.rate != STREAM_RATE).then(|| Resampler::new(chunk.rate, STREAM_RATE, STREAM_CHANNELS));
+        }
+        // Mono is doubled; beyond stereo only the front pair is kept.
+        self.stereo.clear();
+        for frame in chunk.samples.chunks_exact(chunk.channels.max(1)) {
+            self.stereo.push(frame[0]);
+            self.stereo.push(frame[1.min(frame.len() - 1)]);
+        }
+        match &mut self.resampler {
+            Some(resampler) => {
+                let resampled = resampler.process(&self.stereo);
+                self.encoder.encode(&resampled)
+            }
+            None => self.encoder.encode(&self.stereo),
+        }
+    }
+
+    /// Encodes `duration` of silence, which keeps listeners connected while
+    /// nothing plays.
+    pub fn encode_silence(&mut self, duration: Duration) -> Result<Vec<u8>> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let frames = (duration.as_secs_f64() * f64::from(STREAM_RATE)) as usize;
+        self.encoder.encode(&vec![0.0; frames * STREAM_CHANNELS])
+    }
+}
@@ -0,0 +1,121 @@
+//! MP3 encoding with LAME (`mp3lame-encoder`), constant bitrate. LAME
+//! resamples rates MP3 has no mode for by itself.
+
+use anyhow::{Result, format_err};
+use mp3lame_encoder::{Bitrate, Builder, Encoder, FlushNoGap, InterleavedPcm, MonoPcm, Quality};
+
+use super::AudioEncoder;
+
+/// What LAME needs at most to flush its buffers.
+const FLUSH_BUFFER: usize = 7200;
+
+pub struct Mp3Encoder {
+    encoder: Encoder,
+    channels: usize,
+}
+
+impl Mp3Encoder {
+    pub fn new(rate: u32, channels: usize, bitrate_kbps: u32) -> Result<Self> {
+        let mut builder = Builder::new().ok_or_else(|| format_err!("Failed to create MP3 encoder"))?;
+        let lame_channels = match channels {
+            1 => 1,
+            2 => 2,
+            _ => return Err(format_err!("MP3 cannot encode {channels} channels")),
+        };
+        builder
+            .set_num_channels(lame_channels)
+            .map_err(|e| format_err!("MP3 channels: {e}"))?;
+        builder
+            .set_sample_rate(rate)
+            .map_err(|e| format_err!("MP3 sample rate {rate}Hz: {e}"))?;
+        builder
+            .set_brate(bitrate(bitrate_kbps))
+            .map_err(|e| format_err!("MP3 bitrate: {e}"))?;
+        // Fast enough for a listener per core on a Raspberry Pi.
+        builder.set_quality(Quality::Good).map_err(|e| format_err!("MP3 quality: {e}"))?;
+        let encoder = builder.build().map_err(|e| format_err!("Failed to create MP3 encoder: {e}"))?;
+        Ok(Self { encoder, channels })
+    }
+}
+
+impl AudioEncoder for Mp3Encoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>> {
+        let frames = samples.len() / self.channels;
+        let mut out = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(frames));
+        let encoded = if self.channels == 1 {
+            self.encoder.encode_to_vec(MonoPcm(samples), &mut out)
+        } else {
+            self.encoder.encode_to_vec(InterleavedPcm(samples), &mut out)
+        };
+        encoded.map_err(|e| format_err!("MP3 encoding failed: {e}"))?;
+        Ok(out)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(FLUSH_BUFFER);
+        self.encoder
+            .flush_to_vec::<FlushNoGap>(&mut out)
+            .map_err(|e| format_err!("MP3 encoding failed: {e}"))?;
+        Ok(out)
+    }
+}
+
+/// The MP3 bitrate closest to `kbps`.
+fn bitrate(kbps: u32) -> Bitrate {
+    const RATES: [(u32, Bitrate); 11] = [
+        (32, Bitrate::Kbps32),
+        (48, Bitrate::Kbps48),
+        (64, Bitrate::Kbps64),
+        (96, Bitrate::Kbps96),
+        (112, Bitrate::Kbps112),
+        (128, Bitrate::Kbps128),
+        (160, Bitrate::Kbps160),
+        (192, Bitrate::Kbps192),
+        (224, Bitrate::Kbps224),
+        (256, Bitrate::Kbps256),
+        (320, Bitrate::Kbps320),
+    ];
+    RATES
+        .iter()
+        .min_by_key(|(rate, _)| rate.abs_diff(kbps))
+        .map_or(Bitrate::Kbps192, |(_, bitrate)| *bitrate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rsp::encoder::readback;
+
+    /// LAME's encoder delay and the padding of the last frame.
+    const MAX_EXTRA_FRAMES: usize = 3 * 1152;
+
+    #[test]
+    fn decodes_to_the_length_encoded() {
+        for (rate, channels) in [(44_100, 2), (48_000, 1)] {
+            let frames = rate as usize + 500;
+            #[allow(clippy::cast_precision_loss)]
+            let samples: Vec<f32> = (0..frames)
+                .flat_map(|i| std::iter::repeat_n((i as f32 / 10.0).sin() * 0.5, channels))
+                .collect();
+            let mut encoder = Mp3Encoder::new(rate, channels, 192).expect("encoder");
+            let mut stream = encoder.encode(&samples).expect("encode");
+            stream.extend(encoder.finish().expect("finish"));
+
+            let decoded = readback::decode(stream, "mp3");
+            assert_eq!((decoded.rate, decoded.channels), (rate, channels));
+            assert!(
+                (frames..frames + MAX_EXTRA_FRAMES).contains(&decoded.frames()),
+                "{} frames decoded of {frames}",
+                decoded.frames()
+            );
+        }
+    }
+
+    #[test]
+    fn bitrates_round_to_the_nearest_mp3_bitrate() {
+        assert!(matches!(bitrate(100), Bitrate::Kbps96));
+        assert!(matches!(bitrate(1000), Bitrate::Kbps320));
+        assert!(matches!(bitrate(0), Bitrate::Kbps32));
+        assert!(Mp3Encoder::new(44_100, 3, 192).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! Ogg/Opus encoding with libopus (`audiopus`) and the `ogg` page writer.
+//!
+//! Opus only runs at 48 kHz in mono or stereo; callers convert first. Pages
+//! are closed every few packets so a listener gets audio within a fraction
+//! of a second instead of whenever a page fills up.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, format_err};
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+use super::AudioEncoder;
+
+pub const OPUS_RATE: u32 = 48_000;
+/// 20 ms packets.
+const FRAME_SAMPLES: usize = 960;
+const PACKETS_PER_PAGE: u64 = 5;
+/// Large enough for any packet at the bitrates on offer.
+const MAX_PACKET: usize = 4000;
+
+pub struct OpusEncoder {
+    encoder: Encoder,
+    channels: usize,
+    writer: PacketWriter<Vec<u8>>,
+    serial: u32,
+    pre_skip: u64,
+    /// Interleaved samples waiting for a full packet.
+    pending: Vec<f32>,
+    /// Samples per channel encoded so far.
+    encoded: u64,
+    packets: u64,
+    headers_written: bool,
+    packet: Vec<u8>,
+}
+
+impl OpusEncoder {
+    pub fn new(channels: usize, bitrate_kbps: u32) -> Result<Self> {
+        let opus_channels = match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            _ => return Err(format_err!("Opus cannot encode {channels} channels")),
+        };
+        let mut encoder = Encoder::new(SampleRate::Hz48000, opus_channels, Application::Audio)
+            .map_err(|e| format_err!("Failed to create Opus encoder: {e}"))?;
+        let bitrate = i32::try_from(bitrate_kbps.saturating_mul(1000)).unwrap_or(i32::MAX);
+        encoder
+            .set_bitrate(Bitrate::BitsPerSecond(bitrate))
+            .map_err(|e| format_err!("Failed to set Opus bitrate: {e}"))?;
+        let pre_skip = encoder
+            .lookahead()
+            .map_err(|e| format_err!("Failed to query the Opus encoder: {e}"))?;
+        // Any serial number will do; a different one per stream helps
+        // players that cache by it.
+        #[allow(clippy::cast_possible_truncation)]
+        let serial = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u32);
+        Ok(Self {
+            encoder,
+            channels,
+            writer: PacketWriter::new(Vec::new()),
+            serial,
+            pre_skip: u64::from(pre_skip),
+            pending: Vec::with_capacity(FRAME_SAMPLES * channels),
+            encoded: 0,
+            packets: 0,
+            headers_written: false,
+            packet: vec![0; MAX_PACKET],
+        })
+    }
+
+    fn write_headers(&mut self) -> Result<()> {
+        if self.headers_written {
+            return Ok(());
+        }
+        self.headers_written = true;
+        let mut head = b"OpusHead".to_vec();
+        head.push(1);
+        #[allow(clippy::cast_possible_truncation)]
+        head.push(self.channels as u8);
+        head.extend_from_slice(&u16::try_from(self.pre_skip).unwrap_or(u16::MAX).to_le_bytes());
+        head.extend_from_slice(&OPUS_RATE.to_le_bytes());
+        // Output gain, channel mapping family.
+        head.extend_from_slice(&[0, 0, 0]);
+        self.writer
+            .write_packet(head.into_boxed_slice(), self.serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        let vendor = b"rsplayer";
+        let mut tags = b"OpusTags".to_vec();
+        #[allow(clippy::cast_possible_truncation)]
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor);
+        tags.extend_from_slice(&0u32.to_le_bytes());
+        self.writer
+            .write_packet(tags.into_boxed_slice(), self.serial, PacketWriteEndInfo::EndPage, 0)?;
+        Ok(())
+    }
+
+    fn write_packet(&mut self, samples: &[f32], valid_samples: usize, end: bool) -> Result<()> {
+        let len = self
+            .encoder
+            .encode_float(samples, &mut self.packet)
+            .map_err(|e| format_err!("Opus encoding failed: {e}"))?;
+        self.encoded += valid_samples as u64;
+        self.packets += 1;
+        let info = if end {
+            PacketWriteEndInfo::EndStream
+        } else if self.packets.is_multiple_of(PACKETS_PER_PAGE) {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        let granule = self.pre_skip + self.encoded;
+        self.writer.write_packet(self.packet[..len].into(), self.serial, info, granule)?;
+        Ok(())
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(self.writer.inner_mut())
+    }
+}
+
+impl AudioEncoder for OpusEncoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>> {
+        self.write_headers()?;
+        self.pending.extend_from_slice(samples);
+        let frame_len = FRAME_SAMPLES * self.channels;
+        let pending = std::mem::take(&mut self.pending);
+        let mut start = 0;
+        while pending.len() - start >= frame_len {
+            self.write_packet(&pending[start..start + frame_len], FRAME_SAMPLES, false)?;
+            start += frame_len;
+        }
+        self.pending = pending;
+        self.pending.drain(..start);
+        Ok(self.take_output())
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        self.write_headers()?;
+        // libopus holds back `pre_skip` samples, so silence follows until
+        // they are out as well; the last granule position tells players
+        // where the audio really ends.
+        let mut tail = std::mem::take(&mut self.pending);
+        let mut valid_samples = tail.len() / self.channels;
+        #[allow(clippy::cast_possible_truncation)]
+        let packets = (valid_samples + self.pre_skip as usize).div_ceil(FRAME_SAMPLES).max(1);
+        tail.resize(packets * FRAME_SAMPLES * self.channels, 0.0);
+        for (i, packet) in tail.chunks_exact(FRAME_SAMPLES * self.channels).enumerate() {
+            let valid = valid_samples.min(FRAME_SAMPLES);
+            valid_samples -= valid;
+            self.write_packet(packet, valid, i + 1 == packets)?;
+        }
+        Ok(self.take_output())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rsp::encoder::readback;
+
+    fn tone(frames: usize, channels: usize) -> Vec<f32> {
+        #[allow(clippy::cast_precision_loss)]
+        (0..frames)
+            .flat_map(|i| std::iter::repeat_n((i as f32 / 10.0).sin() * 0.5, channels))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_to_the_length_encoded() {
+        // Whole packets, a last packet shorter than the encoder's lookahead
+        // and one longer, and less than a packet in all.
+        for (frames, channels) in [(48_000, 2), (48_100, 2), (48_900, 1), (10, 2)] {
+            let samples = tone(frames, channels);
+            let mut encoder = OpusEncoder::new(channels, 128).expect("encoder");
+            let mut stream = encoder.encode(&samples).expect("encode");
+            stream.extend(encoder.finish().expect("finish"));
+
+            let decoded = readback::decode_opus(stream);
+            assert_eq!(decoded.channels, channels);
+            assert_eq!(decoded.frames(), frames, "{frames} frames");
+            if frames > 1000 {
+                // The tone lasts to the end instead of the lookahead's silence.
+                let tail = &decoded.samples[decoded.samples.len() - 100 * channels..];
+                #[allow(clippy::cast_precision_loss)]
+                let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+                assert!(rms > 0.2, "{frames} frames end at rms {rms}");
+            }
+        }
+    }
+
+    #[test]
+    fn only_mono_and_stereo_are_encoded() {
+        assert!(OpusEncoder::new(1, 64).is_ok());
+        assert!(OpusEncoder::new(3, 64).is_err());
+    }
+}
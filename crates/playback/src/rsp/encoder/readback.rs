@@ -0,0 +1,106 @@
+//! Decoders for what the encoders produce, so tests check the audio a
+//! player would get rather than the bytes.
+
+use audiopus::coder::Decoder;
+use audiopus::{Channels, SampleRate};
+use symphonia::core::codecs::CodecParameters;
+use symphonia::core::codecs::audio::AudioDecoderOptions;
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::formats::probe::Hint;
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+
+use super::opus::OPUS_RATE;
+
+/// Decoded audio: rate, channels and interleaved samples.
+pub struct Decoded {
+    pub rate: u32,
+    pub channels: usize,
+    pub samples: Vec<f32>,
+}
+
+impl Decoded {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn seconds(&self) -> f64 {
+        self.frames() as f64 / f64::from(self.rate)
+    }
+}
+
+/// Decodes a FLAC or MP3 stream with symphonia, as playback would.
+pub fn decode(stream: Vec<u8>, extension: &str) -> Decoded {
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+    let mut reader = metadata::build_probe()
+        .probe(
+            &hint,
+            MediaSourceStream::new(Box::new(std::io::Cursor::new(stream)), MediaSourceStreamOptions::default()),
+            FormatOptions::default(),
+            MetadataOptions::default(),
+        )
+        .expect("probe");
+    let track = reader.tracks().first().expect("track");
+    let Some(CodecParameters::Audio(params)) = track.codec_params.clone() else {
+        panic!("no audio codec parameters");
+    };
+    let mut decoder = metadata::build_codec_registry()
+        .make_audio_decoder(&params, &AudioDecoderOptions::default())
+        .expect("decoder");
+    let mut samples = Vec::new();
+    let mut packet_samples = Vec::new();
+    while let Ok(Some(packet)) = reader.next_packet() {
+        match decoder.decode(&packet) {
+            Ok(decoded) => decoded.copy_to_vec_interleaved(&mut packet_samples),
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => panic!("decode: {e}"),
+        }
+        samples.extend_from_slice(&packet_samples);
+    }
+    Decoded {
+        rate: params.sample_rate.expect("sample rate"),
+        channels: params.channels.as_ref().map_or(0, symphonia::core::audio::Channels::count),
+        samples,
+    }
+}
+
+/// Decodes an Ogg/Opus stream with libopus, dropping the pre-skip and
+/// ending where the last granule position says the audio ends.
+pub fn decode_opus(stream: Vec<u8>) -> Decoded {
+    let mut reader = ogg::reading::PacketReader::new(std::io::Cursor::new(stream));
+    let head = reader.read_packet_expected().expect("OpusHead");
+    assert_eq!(&head.data[..8], b"OpusHead");
+    let channels = usize::from(head.data[9]);
+    let pre_skip = usize::from(u16::from_le_bytes([head.data[10], head.data[11]]));
+    let tags = reader.read_packet_expected().expect("OpusTags");
+    assert_eq!(&tags.data[..8], b"OpusTags");
+
+    let opus_channels = if channels == 1 { Channels::Mono } else { Channels::Stereo };
+    let mut decoder = Decoder::new(SampleRate::Hz48000, opus_channels).expect("decoder");
+    // 120 ms, the longest Opus packet.
+    let mut packet_samples = vec![0.0; 5760 * channels];
+    let mut samples = Vec::new();
+    let mut end = 0;
+    while let Some(packet) = reader.read_packet().expect("ogg page") {
+        let frames = decoder
+            .decode_float(
+                Some(packet.data.as_slice().try_into().expect("packet")),
+                packet_samples.as_mut_slice().try_into().expect("output"),
+                false,
+            )
+            .expect("decode");
+        samples.extend_from_slice(&packet_samples[..frames * channels]);
+        end = usize::try_from(packet.absgp_page()).expect("granule position");
+    }
+    assert!(samples.len() >= end * channels, "granule position beyond the decoded audio");
+    samples.truncate(end * channels);
+    samples.drain(..pre_skip * channels);
+    Decoded {
+        rate: OPUS_RATE,
+        channels,
+        samples,
+    }
+}
@@ -0,0 +1,155 @@
+//! Streaming windowed-sinc sample-rate conversion for the encoders.
+//!
+//! Unlike the rubato resampler in `audio_output`, which works on fixed-size
+//! blocks at the device, this one takes chunks of any length, so it can sit
+//! between the `OutputTee` and an encoder. Quality is tuned for lossy
+//! streaming: 16 zero crossings each side of a Blackman-windowed sinc, the
+//! cutoff at 95% of the lower Nyquist frequency.
+
+use std::f64::consts::PI;
+
+/// Zero crossings of the sinc kept on each side of the kernel centre.
+const ZERO_CROSSINGS: usize = 16;
+/// Kernel table resolution, in entries per zero crossing.
+const PHASES: usize = 256;
+const CUTOFF: f64 = 0.95;
+
+pub struct Resampler {
+    channels: usize,
+    /// Input frames advanced per output frame.
+    step: f64,
+    /// Kernel cutoff relative to the input Nyquist frequency.
+    cutoff: f64,
+    /// Kernel reach on each side, in input frames.
+    reach: usize,
+    table: Vec<f64>,
+    /// Interleaved input not yet consumed, starting `reach` frames before
+    /// the next output position at the latest.
+    history: Vec<f32>,
+    /// Next output position in input frames, relative to `history`.
+    position: f64,
+    weights: Vec<f32>,
+}
+
+impl Resampler {
+    #[must_use]
+    pub fn new(from_rate: u32, to_rate: u32, channels: usize) -> Self {
+        let cutoff = (f64::from(to_rate) / f64::from(from_rate)).min(1.0) * CUTOFF;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+        let reach = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+        #[allow(clippy::cast_precision_loss)]
+        let table = (0..=ZERO_CROSSINGS * PHASES)
+            .map(|i| {
+                let x = i as f64 / PHASES as f64;
+                let sinc = if i == 0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let u = x / ZERO_CROSSINGS as f64;
+                sinc * (0.08f64.mul_add((2.0 * PI * u).cos(), 0.5f64.mul_add((PI * u).cos(), 0.42)))
+            })
+            .collect();
+        #[allow(clippy::cast_precision_loss)]
+        Self {
+            channels,
+            step: f64::from(from_rate) / f64::from(to_rate),
+            cutoff,
+            reach,
+            table,
+            // Zeros before the first frame let output start right away.
+            history: vec![0.0; reach * channels],
+            position: reach as f64,
+            weights: Vec::with_capacity(2 * reach),
+        }
+    }
+
+    /// Resamples interleaved `input`, returning the output frames it
+    /// completes; the last `reach` input frames wait for the next chunk.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        self.history.extend_from_slice(input);
+        let frames = self.history.len() / self.channels;
+        let mut output = Vec::with_capacity((input.len() as f64 / self.step) as usize + self.channels);
+        while self.position.floor() as usize + self.reach < frames {
+            let centre = self.position.floor() as usize;
+            let first = centre + 1 - self.reach;
+            self.weights.clear();
+            for frame in first..=centre + self.reach {
+                let weight = self.kernel((self.position - frame as f64).abs());
+                self.weights.push(weight as f32);
+            }
+            for channel in 0..self.channels {
+                let sum: f32 = self
+                    .weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, w)| w * self.history[(first + i) * self.channels + channel])
+                    .sum();
+                output.push(sum);
+            }
+            self.position += self.step;
+        }
+        let consumed = (self.position.floor() as usize).saturating_sub(self.reach);
+        self.history.drain(..consumed * self.channels);
+        self.position -= consumed as f64;
+        output
+    }
+
+    /// Flushes the frames held back for the kernel's reach.
+    pub fn finish(&mut self) -> Vec<f32> {
+        let padding = vec![0.0; self.reach * self.channels];
+        self.process(&padding)
+    }
+
+    /// The kernel `distance` input frames from its centre.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    fn kernel(&self, distance: f64) -> f64 {
+        let x = distance * self.cutoff * PHASES as f64;
+        let index = x as usize;
+        if index >= ZERO_CROSSINGS * PHASES {
+            return 0.0;
+        }
+        let frac = x - index as f64;
+        let value = (self.table[index + 1] - self.table[index]).mul_add(frac, self.table[index]);
+        value * self.cutoff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resampling_keeps_length_level_and_pitch() {
+        let mut resampler = Resampler::new(44_100, 48_000, 2);
+        let input: Vec<f32> = (0..44_100)
+            .flat_map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let s = (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44_100.0).sin() * 0.5;
+                [s, 0.25]
+            })
+            .collect();
+        let mut output = Vec::new();
+        for chunk in input.chunks(1234) {
+            output.extend(resampler.process(chunk));
+        }
+        output.extend(resampler.finish());
+        let frames = output.len() / 2;
+        assert!((47_990..=48_030).contains(&frames), "{frames} frames");
+
+        let steady = &output[2000..output.len() - 2000];
+        let dc = steady.iter().skip(1).step_by(2);
+        assert!(dc.clone().all(|s| (s - 0.25).abs() < 0.005));
+        let peak = steady.iter().step_by(2).fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "peak {peak}");
+        let crossings = steady
+            .iter()
+            .step_by(2)
+            .zip(steady.iter().step_by(2).skip(1))
+            .filter(|(a, b)| a.is_sign_negative() != b.is_sign_negative())
+            .count();
+        // A 1 kHz tone crosses zero twice per 48 output frames.
+        #[allow(clippy::cast_precision_loss)]
+        let expected = (steady.len() / 2) as f32 / 48.0 * 2.0;
+        #[allow(clippy::cast_precision_loss)]
+        let off = (crossings as f32 - expected).abs();
+        assert!(off < 4.0, "{crossings} crossings, expected {expected}");
+    }
+}
@@ -3,20 +3,31 @@
 //! Data flow for one track:
 //! `player_service` (thread lifecycle, queue advance) → `symphonia`
 //! (`play_file` decode loop) → `audio_output` (`AudioOutput`: ring buffer +
-//! cpal stream, resampling, EQ, VU, software volume) → device.
-//! `audio_source` resolves paths/URLs into probed readers; `dsd` bypasses
+//! cpal stream, resampling, EQ, VU, software volume, sleep-timer fade) →
+//! device.
+//! `audio_source` resolves paths/URLs into probed readers, live radio
+//! through `stream_buffer` (pre-buffer, reconnect); `dsd` bypasses
 //! the PCM chain entirely; `tee`/`sync_sink` are the multiroom taps
-//! (documented in `docs/multiroom_architecture.md`).
+//! (documented in `docs/multiroom_architecture.md`); `output_tee` feeds
+//! the `/stream` web radio, which `encoder` turns into MP3, Ogg/Opus or FLAC.
+//! `transcoder` re-encodes library tracks for browser playback with the
+//! same encoders, converting DSD through `dsd_pcm`.
 
 mod audio_output;
 pub mod audio_host;
 mod audio_source;
 mod device_capabilities;
 mod dsd;
+mod dsd_pcm;
+pub mod encoder;
+pub mod output_tee;
 mod playback_config;
 mod playback_context;
 pub mod player_service;
+pub mod sleep_timer;
+mod stream_buffer;
 mod symphonia;
 pub mod sync_sink;
 pub mod tee;
+pub mod transcoder;
 mod vumeter;
@@ -0,0 +1,63 @@
+//! Output tap for the `/stream` web radio.
+//!
+//! The PCM writers in `audio_output` hand every chunk they push to the
+//! device — after resampling, channel mapping and EQ, before software volume
+//! and the sleep-timer fade, which stay local — to the [`OutputTee`]. Each
+//! HTTP listener subscribes and encodes on its own; with no listener the tap
+//! costs one atomic load per chunk. Sending never blocks the playback
+//! thread: a listener that falls behind loses chunks instead (DSD native
+//! output is not tapped at all).
+
+use std::sync::Arc;
+
+use symphonia::core::audio::conv::IntoSample;
+use tokio::sync::broadcast;
+
+/// Chunks kept for a slow listener before it starts losing them — a few
+/// seconds of audio at typical decoder packet sizes.
+const CHANNEL_CAPACITY: usize = 128;
+
+/// Interleaved samples as they were written to the device.
+#[derive(Clone, Debug)]
+pub struct OutputChunk {
+    pub rate: u32,
+    pub channels: usize,
+    pub samples: Arc<[f32]>,
+}
+
+#[derive(Clone)]
+pub struct OutputTee {
+    tx: broadcast::Sender<OutputChunk>,
+}
+
+impl Default for OutputTee {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputTee {
+    #[must_use]
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<OutputChunk> {
+        self.tx.subscribe()
+    }
+
+    #[must_use]
+    pub fn listener_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    pub(crate) fn send<T: IntoSample<f32> + Copy>(&self, rate: u32, channels: usize, samples: &[T]) {
+        if self.tx.receiver_count() == 0 || samples.is_empty() {
+            return;
+        }
+        let samples = samples.iter().map(|&s| s.into_sample()).collect();
+        self.tx.send(OutputChunk { rate, channels, samples }).ok();
+    }
+}
@@ -1,15 +1,18 @@
 //! Per-playback-thread state bundle: the atomics the command side steers
 //! with (`stop_signal`, `skip_to_time`), plus the optional processing hooks
 //! (software gain, DSP handle, VU meter, multiroom tee) the decode loop and
-//! output writers consult.
+//! output writers consult, the radio stream recorder and the `/stream` web
+//! radio tap.
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU16, Ordering};
 
 use api_models::state::StateChangeEvent;
 use dsp::DspHandle;
+use metadata::stream_recorder::StreamRecorder;
 use tokio::sync::broadcast::Sender;
 
+use crate::rsp::output_tee::OutputTee;
 use crate::rsp::tee::SyncTee;
 use crate::rsp::vumeter::VUMeter;
 
@@ -22,22 +25,33 @@ pub struct PlaybackContext {
     /// another volume control (ALSA mixer, Pipewire, hardware) is active so
     /// audio passes through unattenuated.
     pub software_gain: Option<Arc<AtomicU8>>,
+    /// Sleep-timer fade level (100 = unity), applied like `software_gain`
+    /// whatever volume control is active.
+    pub fade_gain: Arc<AtomicU8>,
     pub changes_tx: Sender<StateChangeEvent>,
     pub dsp_handle: Option<DspHandle>,
     pub vu_meter: Option<VUMeter>,
     /// Multiroom PCM tee — `Some` when multiroom is enabled in settings.
     pub sync_tee: Option<SyncTee>,
+    /// Records the raw bytes of HTTP streams while a recording runs.
+    pub stream_recorder: Arc<StreamRecorder>,
+    /// Web radio tap, fed by the PCM writers while listeners are connected.
+    pub output_tee: OutputTee,
 }
 
 impl PlaybackContext {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         stop_signal: Arc<AtomicBool>,
         skip_to_time: Arc<AtomicU16>,
         software_gain: Option<Arc<AtomicU8>>,
+        fade_gain: Arc<AtomicU8>,
         changes_tx: Sender<StateChangeEvent>,
         dsp_handle: Option<DspHandle>,
         vu_meter_enabled: bool,
         sync_tee: Option<SyncTee>,
+        stream_recorder: Arc<StreamRecorder>,
+        output_tee: OutputTee,
     ) -> Self {
         let vu_meter = if vu_meter_enabled {
             Some(VUMeter::new(software_gain.clone(), changes_tx.clone()))
@@ -49,10 +63,13 @@ impl PlaybackContext {
             stop_signal,
             skip_to_time,
             software_gain,
+            fade_gain,
             changes_tx,
             dsp_handle,
             vu_meter,
             sync_tee,
+            stream_recorder,
+            output_tee,
         }
     }
 
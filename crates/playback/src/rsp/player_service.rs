@@ -3,15 +3,26 @@
 //!
 //! [`PlayerService`] owns the playback thread (spawned per play/resume at
 //! the configured `player_threads_priority` — never `Min`, which starved
-//! audio on single-core devices), advances the queue on `SongFinished`,
+//! audio on single-core devices), advances the queue on `SongFinished`
+//! (falling back to queue auto-continue when it runs out), records skips,
 //! persists pause/progress state in the `player_state` keyspace for resume
 //! across restarts, pauses the loudness scan while playing, and owns the
 //! `DspProcessor` (settings changes rebuild the EQ from here). Control in:
 //! atomics (`stop_signal`, `skip_to_time`); results out:
 //! `StateChangeEvent`s.
+//!
+//! Songs matching the resume settings (long-form audio) also get a per-song
+//! resume point: saved when playback of the song stops early, applied when
+//! the song starts again, cleared when it plays to the end. Named bookmarks
+//! jump straight to a song position. The [`SleepTimer`] fades out and stops
+//! playback through `stop_signal` and a check at every song boundary. The
+//! [`StreamRecorder`] records the playing radio stream on request and ends
+//! the recording when the stream stops playing. The [`OutputTee`] outlives
+//! every playback thread so `/stream` listeners stay connected across songs.
 
 use fjall::{Database, Keyspace, KeyspaceCreateOptions};
 use log::{debug, error, info, trace, warn};
+use std::path::Path;
 use std::sync::{
     Arc, Mutex,
     atomic::{AtomicBool, AtomicU8, AtomicU16, AtomicU32, Ordering},
@@ -19,20 +30,27 @@ use std::sync::{
 use std::thread::JoinHandle;
 use thread_priority::{ThreadBuilder, ThreadPriority};
 use tokio::sync::broadcast::{Sender, error::RecvError};
+use tokio::sync::mpsc;
 
 use api_models::{
-    settings::{DspSettings, RsPlayerSettings, Settings},
-    state::{PlayerInfo, PlayerState, StateChangeEvent},
+    common::SystemCommand,
+    player::{Bookmark, Song},
+    settings::{DspSettings, ResumeSettings, RsPlayerSettings, Settings},
+    state::{PlayerInfo, PlayerState, SleepTimerState, SongProgress, StateChangeEvent},
 };
+use metadata::bookmark_service::BookmarkService;
 use metadata::loudness_service::LoudnessService;
 use metadata::metadata_service::MetadataService;
 use metadata::queue_service::QueueService;
+use metadata::stream_recorder::StreamRecorder;
 
 use dsp::DspProcessor;
 
 use super::symphonia::PlaybackResult;
+use crate::rsp::output_tee::OutputTee;
 use crate::rsp::playback_config::PlaybackConfig;
 use crate::rsp::playback_context::PlaybackContext;
+use crate::rsp::sleep_timer::SleepTimer;
 use crate::rsp::tee::SyncTee;
 
 pub struct PlayerService {
@@ -53,10 +71,17 @@ pub struct PlayerService {
     loudness_service: Arc<LoudnessService>,
     last_player_info: Arc<Mutex<Option<PlayerInfo>>>,
     sync_tee: Option<SyncTee>,
+    bookmark_service: Arc<BookmarkService>,
+    resume_settings: ResumeSettings,
+    sleep_timer: Arc<SleepTimer>,
+    stream_recorder: Arc<StreamRecorder>,
+    output_tee: OutputTee,
 }
 
 const LAST_SONG_PAUSED_KEY: &str = "last_song_paused";
 const LAST_SONG_PROGRESS_KEY: &str = "last_played_song_progress";
+/// Resume points this close to either end of a song are dropped instead.
+const RESUME_MARGIN_SECS: u32 = 10;
 
 impl PlayerService {
     #[must_use]
@@ -70,6 +95,8 @@ impl PlayerService {
         state_changes_tx: Sender<StateChangeEvent>,
         loudness_service: Arc<LoudnessService>,
         sync_tee: Option<SyncTee>,
+        bookmark_service: Arc<BookmarkService>,
+        system_commands_tx: mpsc::Sender<SystemCommand>,
     ) -> Arc<Self> {
         let state_db = db
             .keyspace("player_state", KeyspaceCreateOptions::default)
@@ -82,6 +109,17 @@ impl PlayerService {
         let last_known_time = Arc::new(AtomicU32::new(initial_time));
         let last_known_time_clone = last_known_time.clone();
         let current_volume_clone = current_volume.clone();
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let sleep_timer = SleepTimer::new(
+            settings.sleep_timer_settings.clone(),
+            queue_service.clone(),
+            last_known_time.clone(),
+            stop_signal.clone(),
+            state_changes_tx.clone(),
+            system_commands_tx,
+        );
+        let sleep_timer_clone = sleep_timer.clone();
+        let stream_recorder = StreamRecorder::new(state_changes_tx.clone());
         let software_gain_active = settings.volume_ctrl_settings.ctrl_device == api_models::common::VolumeCrtlType::Software;
         let dsp_processor = Arc::new(Mutex::new({
             let rsp = &settings.rs_player_settings;
@@ -94,7 +132,9 @@ impl PlayerService {
                         filters: vec![],
                     }
                 };
-                Some(DspProcessor::new(effective_dsp))
+                let limiter_ceiling = (rsp.loudness_normalization_enabled && rsp.loudness_normalization_limiter)
+                    .then_some(rsp.loudness_normalization_true_peak_ceiling);
+                Some(DspProcessor::new(effective_dsp, limiter_ceiling))
             } else {
                 None
             }
@@ -143,7 +183,9 @@ impl PlayerService {
                         }
                     }
                     StateChangeEvent::VolumeChangeEvent(vol) => {
-                        current_volume_clone.store(vol.current, Ordering::Relaxed);
+                        if current_volume_clone.swap(vol.current, Ordering::Relaxed) != vol.current {
+                            sleep_timer_clone.on_volume_change();
+                        }
                     }
                     StateChangeEvent::PlayerInfoEvent(info) => {
                         if let Ok(mut guard) = last_player_info_clone.lock() {
@@ -172,7 +214,7 @@ impl PlayerService {
             queue_service,
             metadata_service,
             playback_thread_handle: Arc::new(Mutex::new(None)),
-            stop_signal: Arc::new(AtomicBool::new(false)),
+            stop_signal,
             skip_to_time: Arc::new(AtomicU16::new(0)),
             last_known_time,
             current_volume,
@@ -184,6 +226,11 @@ impl PlayerService {
             loudness_service,
             last_player_info,
             sync_tee,
+            bookmark_service,
+            resume_settings: settings.resume_settings.clone(),
+            sleep_timer,
+            stream_recorder,
+            output_tee: OutputTee::new(),
         };
         let last_played_song_progress = ps.get_last_played_song_time();
         if last_played_song_progress > 0 {
@@ -198,6 +245,12 @@ impl PlayerService {
         self.dsp_processor.lock().ok().and_then(|g| g.as_ref().map(DspProcessor::handle))
     }
 
+    /// The tap `/stream` listeners subscribe to.
+    #[must_use]
+    pub fn output_tee(&self) -> OutputTee {
+        self.output_tee.clone()
+    }
+
     pub fn play_from_current_queue_song(&self) {
         if self.is_playing() {
             return;
@@ -233,8 +286,17 @@ impl PlayerService {
     }
 
     pub fn play_next_song(&self) {
+        // Leaving a song mid-play counts as a skip; auto-continue ranks
+        // often-skipped songs lower.
+        if self.is_playing()
+            && let Some(song) = self.queue_service.get_current_song()
+        {
+            self.metadata_service.increase_skip_count(&song.file);
+        }
         self.stop_current_song();
-        self.queue_service.move_current_to_next_song();
+        if !self.queue_service.move_current_to_next_song() {
+            self.queue_service.continue_with_similar_songs();
+        }
         _ = self.state_db.remove(LAST_SONG_PAUSED_KEY);
         self.play_from_current_queue_song();
     }
@@ -273,6 +335,82 @@ impl PlayerService {
         self.seek_current_song(new_time);
     }
 
+    /// Position of the current song in whole seconds, as last reported by the decoder.
+    pub fn current_position_secs(&self) -> u32 {
+        self.last_known_time.load(Ordering::Relaxed)
+    }
+
+    /// Bookmarks the current song at the current position.
+    pub fn add_bookmark(&self, name: &str) -> Option<Bookmark> {
+        let song = self.queue_service.get_current_song()?;
+        Some(self.bookmark_service.add_bookmark(&song, name, self.current_position_secs()))
+    }
+
+    pub fn get_bookmark(&self, id: &str) -> Option<Bookmark> {
+        self.bookmark_service.get_bookmark(id)
+    }
+
+    /// Plays the bookmarked song from the bookmarked position, appending the
+    /// song to the queue if it is no longer there. Returns whether it was
+    /// appended.
+    pub fn jump_to_bookmark(&self, bookmark: &Bookmark) -> bool {
+        self.stop_current_song();
+        let appended = !self.queue_service.move_current_to(&bookmark.song_file);
+        if appended {
+            self.queue_service.add_song_by_id(&bookmark.song_file);
+            self.queue_service.set_current_to_last();
+        }
+        _ = self.state_db.remove(LAST_SONG_PAUSED_KEY);
+        self.seek_current_song(u16::try_from(bookmark.position_secs).unwrap_or(u16::MAX));
+        self.play_from_current_queue_song();
+        appended
+    }
+
+    pub fn delete_bookmark(&self, id: &str) -> bool {
+        self.bookmark_service.delete_bookmark(id)
+    }
+
+    pub fn get_bookmarks(&self) -> Vec<Bookmark> {
+        self.bookmark_service.get_bookmarks()
+    }
+
+    /// Fades out and stops playback after `minutes`; 0 cancels.
+    pub fn set_sleep_timer(&self, minutes: u32) {
+        self.sleep_timer.set_timer(minutes);
+    }
+
+    pub fn sleep_at_end_of_track(&self) {
+        self.sleep_timer.stop_at_end_of_track();
+    }
+
+    pub fn sleep_at_end_of_album(&self) {
+        self.sleep_timer.stop_at_end_of_album();
+    }
+
+    pub fn cancel_sleep_timer(&self) {
+        self.sleep_timer.cancel();
+    }
+
+    pub fn get_sleep_timer(&self) -> Option<SleepTimerState> {
+        self.sleep_timer.state()
+    }
+
+    /// Records the playing radio stream into a station directory under
+    /// `directory`, optionally one file per stream title.
+    pub fn start_recording(&self, directory: &Path, split_on_title: bool) -> anyhow::Result<()> {
+        self.stream_recorder.start(directory, split_on_title)
+    }
+
+    /// `false` when nothing was being recorded.
+    pub fn stop_recording(&self) -> bool {
+        self.stream_recorder.stop()
+    }
+
+    /// The file the radio stream is being recorded to.
+    pub fn get_recording_file(&self) -> Option<String> {
+        self.stream_recorder.current_file()
+    }
+
     pub fn play_song(&self, song_id: &str) {
         self.stop_current_song();
         self.queue_service.move_current_to(song_id);
@@ -298,7 +436,7 @@ impl PlayerService {
         let queue = self.queue_service.clone();
         let audio_device = self.audio_device.clone();
         let playback_thread_prio = self.rsp_settings.player_threads_priority;
-        let music_dirs = self.metadata_service.effective_directories();
+        let music_dirs = self.metadata_service.library_directories();
         let changes_tx = self.changes_tx.clone();
         let rsp_settings = self.rsp_settings.clone();
         let metadata_service = self.metadata_service.clone();
@@ -310,6 +448,12 @@ impl PlayerService {
         let is_multi_core_platform = core_affinity::get_core_ids().is_some_and(|ids| ids.len() > 1);
         let local_browser_playback = self.local_browser_playback;
         let sync_tee = self.sync_tee.clone();
+        let bookmark_service = self.bookmark_service.clone();
+        let resume_settings = self.resume_settings.clone();
+        let last_known_time = self.last_known_time.clone();
+        let sleep_timer = self.sleep_timer.clone();
+        let stream_recorder = self.stream_recorder.clone();
+        let output_tee = self.output_tee.clone();
         // Use the configured priority on single-core platforms too: with
         // ThreadPriority::Min the audio thread on an RPi Zero was starved by
         // web-UI/library requests sharing the one core, breaking playback.
@@ -332,6 +476,7 @@ impl PlayerService {
                         warn!("Failed to set playback thread to last core {last_core:?}");
                     }
                 }
+                sleep_timer.on_playback_start();
                 let mut retry_count = 0;
                 let result = loop {
                     let Some(song) = queue.get_current_song() else {
@@ -339,6 +484,15 @@ impl PlayerService {
                         break PlaybackResult::QueueFinished;
                     };
 
+                    let resumable = resume_settings.applies_to(&song);
+                    if resumable
+                        && skip_to_time.load(Ordering::Relaxed) == 0
+                        && let Some(position) = bookmark_service.get_resume_point(&song.file)
+                    {
+                        info!("Resuming '{}' at {position}s", song.file);
+                        skip_to_time.store(u16::try_from(position).unwrap_or(u16::MAX), Ordering::Relaxed);
+                    }
+
                     if skip_to_time.load(Ordering::Relaxed) == 0 {
                         metadata_service.increase_play_count(&song.file);
                     }
@@ -354,46 +508,79 @@ impl PlayerService {
                         use api_models::settings::NormalizationSource;
                         let tag_track = song.file_tag_track_gain();
                         let tag_album = song.file_tag_album_gain();
-                        let calculated = || {
-                            track_loudness.map(|lufs_hundredths| {
-                                let lufs = f64::from(lufs_hundredths) / 100.0;
-                                rsp_settings.loudness_normalization_target_lufs - lufs
-                            })
+                        let album_loudness = loudness_service.get_album_loudness(&song);
+                        let gain_for = |lufs_hundredths: i32| {
+                            let lufs = f64::from(lufs_hundredths) / 100.0;
+                            rsp_settings.loudness_normalization_target_lufs - lufs
                         };
+                        let calculated = || track_loudness.map(gain_for);
+                        let calculated_album = || album_loudness.map(|album| gain_for(album.loudness));
                         debug!(
                             "Normalization inputs for '{}': source={:?}, track_loudness={:?} (LUFS*100), \
-                             tag_track_gain={:?} dB, tag_album_gain={:?} dB, target={} LUFS",
+                             album_loudness={:?}, tag_track_gain={:?} dB, tag_album_gain={:?} dB, target={} LUFS",
                             song.file,
                             rsp_settings.loudness_normalization_source,
                             track_loudness,
+                            album_loudness,
                             tag_track,
                             tag_album,
                             rsp_settings.loudness_normalization_target_lufs,
                         );
-                        let gain = match rsp_settings.loudness_normalization_source {
+                        let track_peak = loudness_service.get_track_loudness(&song.file).map(|t| t.true_peak);
+                        let album_peak = album_loudness.map(|album| album.true_peak);
+                        let (gain, true_peak) = match rsp_settings.loudness_normalization_source {
                             NormalizationSource::Calculated => {
                                 let g = calculated();
                                 debug!("Normalization [Calculated]: gain={g:?} dB");
-                                g
+                                (g, track_peak)
                             }
                             NormalizationSource::FileTagsTrack => {
                                 debug!("Normalization [FileTagsTrack]: gain={tag_track:?} dB");
-                                tag_track
+                                (tag_track, track_peak)
                             }
                             NormalizationSource::FileTagsAlbum => {
                                 debug!("Normalization [FileTagsAlbum]: gain={tag_album:?} dB");
-                                tag_album
+                                (tag_album, album_peak)
+                            }
+                            NormalizationSource::CalculatedAlbum => {
+                                let g = calculated_album();
+                                debug!("Normalization [CalculatedAlbum]: gain={g:?} dB");
+                                if g.is_some() { (g, album_peak) } else { (calculated(), track_peak) }
                             }
                             NormalizationSource::Auto => {
-                                let g = tag_track.or_else(calculated);
-                                if tag_track.is_some() {
-                                    debug!("Normalization [Auto]: using file tag track gain={g:?} dB");
+                                let album_gain = if queue.is_playing_album_in_sequence() {
+                                    tag_album.or_else(calculated_album)
+                                } else {
+                                    None
+                                };
+                                if album_gain.is_some() {
+                                    debug!("Normalization [Auto]: album playing in sequence, using album gain={album_gain:?} dB");
+                                    (album_gain, album_peak)
                                 } else {
-                                    debug!("Normalization [Auto]: no track tag, falling back to calculated gain={g:?} dB");
+                                    let g = tag_track.or_else(calculated);
+                                    if tag_track.is_some() {
+                                        debug!("Normalization [Auto]: using file tag track gain={g:?} dB");
+                                    } else {
+                                        debug!("Normalization [Auto]: no track tag, falling back to calculated gain={g:?} dB");
+                                    }
+                                    (g, track_peak)
                                 }
-                                g
                             }
                         };
+                        let gain = if rsp_settings.loudness_normalization_limiter {
+                            gain
+                        } else {
+                            let ceiling = rsp_settings.loudness_normalization_true_peak_ceiling;
+                            gain.map(|g| {
+                                let capped = cap_to_true_peak(g, true_peak, ceiling);
+                                if capped < g {
+                                    debug!(
+                                        "Normalization: gain capped to {capped:.2} dB (true peak {true_peak:?}, ceiling {ceiling} dBTP)"
+                                    );
+                                }
+                                capped
+                            })
+                        };
                         if gain.is_none() {
                             debug!("Normalization: no gain available for '{}', skipping normalization", song.file);
                         }
@@ -415,10 +602,13 @@ impl PlayerService {
                         stop_signal.clone(),
                         skip_to_time.clone(),
                         software_gain.clone(),
+                        sleep_timer.fade_gain(),
                         changes_tx.clone(),
                         dsp_handle.clone(),
                         vu_meter_enabled,
                         sync_tee.clone(),
+                        stream_recorder.clone(),
+                        output_tee.clone(),
                     );
 
                     let play_result = if local_browser_playback {
@@ -431,8 +621,12 @@ impl PlayerService {
                     } else {
                         super::symphonia::play_file(&song.file, &config, &mut context, track_loudness, normalization_gain_hundredths)
                     };
+                    stream_recorder.stream_closed();
                     match play_result {
                         Ok(PlaybackResult::PlaybackStopped) => {
+                            if resumable {
+                                save_resume_point(&bookmark_service, &song, last_known_time.load(Ordering::Relaxed));
+                            }
                             changes_tx.send(StateChangeEvent::PlaybackStateEvent(PlayerState::STOPPED)).ok();
                             break PlaybackResult::PlaybackStopped;
                         }
@@ -466,11 +660,30 @@ impl PlayerService {
                         }
                         res => {
                             info!("Playback finished with result {res:?}");
+                            if resumable {
+                                bookmark_service.clear_resume_point(&song.file);
+                            }
                         }
                     }
 
                     retry_count = 0;
-                    if !queue.move_current_to_next_song() {
+                    let has_next = queue.move_current_to_next_song() || queue.continue_with_similar_songs();
+                    let next = if has_next { queue.get_current_song() } else { None };
+                    if sleep_timer.stops_before(&song, next.as_ref()) {
+                        // Park on the next song at 0:00 so Play starts it from the beginning.
+                        if let Some(next) = next {
+                            changes_tx
+                                .send(StateChangeEvent::SongTimeEvent(SongProgress {
+                                    total_time: next.time.unwrap_or_default(),
+                                    current_time: std::time::Duration::ZERO,
+                                }))
+                                .ok();
+                            changes_tx.send(StateChangeEvent::CurrentSongEvent(next)).ok();
+                        }
+                        changes_tx.send(StateChangeEvent::PlaybackStateEvent(PlayerState::STOPPED)).ok();
+                        break PlaybackResult::PlaybackStopped;
+                    }
+                    if !has_next {
                         break PlaybackResult::QueueFinished;
                     }
                 };
@@ -491,3 +704,64 @@ impl PlayerService {
         }
     }
 }
+
+/// Lowers `gain_db` so audio with the given true peak (hundredths of a dBTP)
+/// stays at or below `ceiling_db`; an unknown peak leaves the gain as is.
+fn cap_to_true_peak(gain_db: f64, true_peak: Option<i32>, ceiling_db: f64) -> f64 {
+    true_peak.map_or(gain_db, |peak| gain_db.min(ceiling_db - f64::from(peak) / 100.0))
+}
+
+fn save_resume_point(bookmark_service: &BookmarkService, song: &Song, position_secs: u32) {
+    let near_end = song
+        .time
+        .is_some_and(|total| u64::from(position_secs + RESUME_MARGIN_SECS) >= total.as_secs());
+    if position_secs < RESUME_MARGIN_SECS || near_end {
+        bookmark_service.clear_resume_point(&song.file);
+    } else {
+        debug!("Saving resume point for '{}' at {position_secs}s", song.file);
+        bookmark_service.save_resume_point(&song.file, position_secs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn chapter(file: &str) -> Song {
+        Song {
+            file: file.to_owned(),
+            time: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resume_points_are_kept_only_away_from_both_ends() {
+        let dir = format!("/tmp/rsptest_resume_{}", random_string::generate(8, "abcdefgh"));
+        let db = Database::builder(&dir).open().expect("Failed to open test db");
+        let bookmarks = BookmarkService::new(&db);
+        let song = chapter("book/ch1.m4b");
+
+        save_resume_point(&bookmarks, &song, 1200);
+        assert_eq!(bookmarks.get_resume_point(&song.file), Some(1200));
+
+        save_resume_point(&bookmarks, &song, RESUME_MARGIN_SECS - 1);
+        assert_eq!(bookmarks.get_resume_point(&song.file), None);
+
+        save_resume_point(&bookmarks, &song, 1200);
+        save_resume_point(&bookmarks, &song, 3600 - RESUME_MARGIN_SECS);
+        assert_eq!(bookmarks.get_resume_point(&song.file), None);
+
+        let unknown_length = Song {
+            time: None,
+            ..chapter("book/ch2.m4b")
+        };
+        save_resume_point(&bookmarks, &unknown_length, 3590);
+        assert_eq!(bookmarks.get_resume_point(&unknown_length.file), Some(3590));
+
+        drop(db);
+        _ = std::fs::remove_dir_all(dir);
+    }
+}
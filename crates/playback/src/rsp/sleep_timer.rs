@@ -0,0 +1,330 @@
+//! Sleep timer: stops playback after a number of minutes, at the end of the
+//! current song, or at the end of the current album — fading out first.
+//!
+//! The fade runs through [`SleepTimer::fade_gain`], a 0–100 level the cpal
+//! callback applies on top of the software volume (same cubic curve), so it
+//! works whatever volume control is configured. A ticker thread broadcasts
+//! the remaining time and lowers the level over the last `fade_seconds`.
+//! The timed mode stops playback from the ticker; the end-of-song modes are
+//! decided by the playback thread at each song boundary via
+//! [`SleepTimer::stops_before`]. The level comes back at the next playback
+//! start; without `restore_volume` the real volume is set to 0 at the stop
+//! instead, so the next playback stays silent and the UI shows why.
+//!
+//! Native DSD and DoP carry a bitstream that cannot be scaled, so they stop
+//! without a fade, as do multiroom followers, which play the leader's
+//! stream and stop when the leader does.
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering},
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::sync::{broadcast::Sender, mpsc};
+
+use api_models::{
+    common::SystemCommand,
+    player::Song,
+    settings::SleepTimerSettings,
+    state::{SleepTimerMode, SleepTimerState, StateChangeEvent},
+};
+use metadata::queue_service::QueueService;
+
+const TICK: Duration = Duration::from_millis(200);
+/// Song positions arrive about once a second; an estimate older than this
+/// means playback is paused and the remaining time stops counting down.
+const POSITION_STALE: Duration = Duration::from_millis(1500);
+/// How far ahead in the queue the end-of-album estimate looks.
+const ALBUM_LOOKAHEAD: usize = 200;
+
+#[derive(Debug, Clone, Copy)]
+struct Armed {
+    mode: SleepTimerMode,
+    /// Only set for [`SleepTimerMode::Timer`].
+    deadline: Option<Instant>,
+}
+
+pub struct SleepTimer {
+    armed: Mutex<Option<Armed>>,
+    /// Bumped on every arm, cancel and stop so a superseded ticker exits.
+    generation: AtomicU64,
+    fade_gain: Arc<AtomicU8>,
+    settings: SleepTimerSettings,
+    queue_service: Arc<QueueService>,
+    last_known_time: Arc<AtomicU32>,
+    stop_signal: Arc<AtomicBool>,
+    changes_tx: Sender<StateChangeEvent>,
+    /// Turns the volume down after a stop without `restore_volume`.
+    volume_tx: mpsc::Sender<SystemCommand>,
+}
+
+impl SleepTimer {
+    #[must_use]
+    pub fn new(
+        settings: SleepTimerSettings,
+        queue_service: Arc<QueueService>,
+        last_known_time: Arc<AtomicU32>,
+        stop_signal: Arc<AtomicBool>,
+        changes_tx: Sender<StateChangeEvent>,
+        volume_tx: mpsc::Sender<SystemCommand>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            armed: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            fade_gain: Arc::new(AtomicU8::new(100)),
+            settings,
+            queue_service,
+            last_known_time,
+            stop_signal,
+            changes_tx,
+            volume_tx,
+        })
+    }
+
+    /// Output level (0–100) the audio callback multiplies in; 100 is unity.
+    #[must_use]
+    pub fn fade_gain(&self) -> Arc<AtomicU8> {
+        self.fade_gain.clone()
+    }
+
+    /// Stops playback after `minutes`; 0 cancels the timer.
+    pub fn set_timer(self: &Arc<Self>, minutes: u32) {
+        if minutes == 0 {
+            self.cancel();
+            return;
+        }
+        let deadline = Instant::now() + Duration::from_secs(u64::from(minutes) * 60);
+        self.arm(SleepTimerMode::Timer, Some(deadline));
+    }
+
+    pub fn stop_at_end_of_track(self: &Arc<Self>) {
+        self.arm(SleepTimerMode::EndOfTrack, None);
+    }
+
+    pub fn stop_at_end_of_album(self: &Arc<Self>) {
+        self.arm(SleepTimerMode::EndOfAlbum, None);
+    }
+
+    pub fn cancel(&self) {
+        self.disarm();
+        self.fade_gain.store(100, Ordering::Relaxed);
+        self.changes_tx.send(StateChangeEvent::SleepTimerEvent(None)).ok();
+    }
+
+    #[must_use]
+    pub fn state(&self) -> Option<SleepTimerState> {
+        let armed = (*self.armed.lock().expect("lock poisoned"))?;
+        let remaining = match armed.mode {
+            SleepTimerMode::Timer => armed.deadline.map(|d| d.saturating_duration_since(Instant::now())),
+            SleepTimerMode::EndOfTrack | SleepTimerMode::EndOfAlbum => self.remaining_in_queue(armed.mode),
+        };
+        Some(SleepTimerState {
+            mode: armed.mode,
+            remaining_secs: remaining.map(whole_secs),
+        })
+    }
+
+    /// Called by the playback thread when `finished` has played to its end
+    /// and `next` is about to start. Returns `true` (and disarms the timer)
+    /// when playback should stop here instead.
+    pub fn stops_before(&self, finished: &Song, next: Option<&Song>) -> bool {
+        let Some(armed) = *self.armed.lock().expect("lock poisoned") else {
+            return false;
+        };
+        if !ends_here(armed.mode, finished, next) {
+            return false;
+        }
+        info!("Sleep timer: stopping after '{}'", finished.file);
+        self.stopped();
+        true
+    }
+
+    /// Brings the level back for a new playback, unless a timer is still
+    /// armed (the fade continues).
+    pub fn on_playback_start(&self) {
+        if self.armed.lock().expect("lock poisoned").is_none() {
+            self.fade_gain.store(100, Ordering::Relaxed);
+        }
+    }
+
+    /// The user changed the volume: drop a leftover fade.
+    pub fn on_volume_change(&self) {
+        if self.armed.lock().expect("lock poisoned").is_none() {
+            self.fade_gain.store(100, Ordering::Relaxed);
+        }
+    }
+
+    fn arm(self: &Arc<Self>, mode: SleepTimerMode, deadline: Option<Instant>) {
+        let generation = {
+            let mut armed = self.armed.lock().expect("lock poisoned");
+            *armed = Some(Armed { mode, deadline });
+            self.generation.fetch_add(1, Ordering::Relaxed) + 1
+        };
+        self.fade_gain.store(100, Ordering::Relaxed);
+        info!("Sleep timer armed: {mode:?}");
+        self.changes_tx.send(StateChangeEvent::SleepTimerEvent(self.state())).ok();
+
+        let timer = self.clone();
+        thread::Builder::new()
+            .name("sleep-timer".into())
+            .spawn(move || timer.run_ticker(generation))
+            .expect("Failed to spawn sleep-timer thread");
+    }
+
+    /// The timer ended playback: disarm it and, unless the volume is to be
+    /// restored, leave the real volume where the fade ended.
+    fn stopped(&self) {
+        self.disarm();
+        self.changes_tx.send(StateChangeEvent::SleepTimerEvent(None)).ok();
+        if !self.settings.restore_volume
+            && let Err(e) = self.volume_tx.try_send(SystemCommand::SetVol(0))
+        {
+            warn!("Sleep timer could not turn the volume down: {e}");
+        }
+    }
+
+    fn disarm(&self) {
+        let mut armed = self.armed.lock().expect("lock poisoned");
+        *armed = None;
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn run_ticker(&self, generation: u64) {
+        let fade = Duration::from_secs(u64::from(self.settings.fade_seconds));
+        let mut last_position = u32::MAX;
+        let mut estimate: Option<Duration> = None;
+        let mut estimated_at = Instant::now();
+        // `arm` already broadcast the initial state.
+        let mut last_sent = self.state().and_then(|s| s.remaining_secs);
+        loop {
+            thread::sleep(TICK);
+            let armed = {
+                let armed = self.armed.lock().expect("lock poisoned");
+                if self.generation.load(Ordering::Relaxed) != generation {
+                    return;
+                }
+                let Some(armed) = *armed else {
+                    return;
+                };
+                armed
+            };
+
+            let remaining = if let Some(deadline) = armed.deadline {
+                Some(deadline.saturating_duration_since(Instant::now()))
+            } else {
+                let position = self.last_known_time.load(Ordering::Relaxed);
+                if position != last_position {
+                    last_position = position;
+                    estimate = self.remaining_in_queue(armed.mode);
+                    estimated_at = Instant::now();
+                }
+                estimate.map(|e| e.saturating_sub(estimated_at.elapsed().min(POSITION_STALE)))
+            };
+
+            if let Some(remaining) = remaining {
+                self.fade_gain.store(fade_level(remaining, fade), Ordering::Relaxed);
+            }
+            let secs = remaining.map(whole_secs);
+            if secs != last_sent {
+                last_sent = secs;
+                self.changes_tx
+                    .send(StateChangeEvent::SleepTimerEvent(Some(SleepTimerState {
+                        mode: armed.mode,
+                        remaining_secs: secs,
+                    })))
+                    .ok();
+            }
+
+            if armed.mode == SleepTimerMode::Timer && remaining.is_some_and(|r| r.is_zero()) {
+                info!("Sleep timer expired, stopping playback");
+                self.stop_signal.store(true, Ordering::Relaxed);
+                self.stopped();
+                return;
+            }
+        }
+    }
+
+    /// Time left in the current song, plus the following queued songs of the
+    /// same album for [`SleepTimerMode::EndOfAlbum`]. `None` when a length is unknown.
+    fn remaining_in_queue(&self, mode: SleepTimerMode) -> Option<Duration> {
+        let upcoming = self.queue_service.get_queue_page_starting_from_current_song(ALBUM_LOOKAHEAD);
+        let (current, rest) = upcoming.split_first()?;
+        let position = Duration::from_secs(u64::from(self.last_known_time.load(Ordering::Relaxed)));
+        let mut remaining = current.time?.saturating_sub(position);
+        if mode == SleepTimerMode::EndOfAlbum {
+            for song in rest.iter().take_while(|s| same_album(current, s)) {
+                remaining += song.time?;
+            }
+        }
+        Some(remaining)
+    }
+}
+
+/// Whether a timer in `mode` stops playback between `finished` and `next`.
+fn ends_here(mode: SleepTimerMode, finished: &Song, next: Option<&Song>) -> bool {
+    match mode {
+        SleepTimerMode::Timer => false,
+        SleepTimerMode::EndOfTrack => true,
+        SleepTimerMode::EndOfAlbum => next.is_none_or(|next| !same_album(finished, next)),
+    }
+}
+
+fn same_album(a: &Song, b: &Song) -> bool {
+    a.album.is_some() && a.album == b.album && a.album_artist == b.album_artist
+}
+
+/// Linear 100 → 0 over the last `fade` of the countdown.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn fade_level(remaining: Duration, fade: Duration) -> u8 {
+    if fade.is_zero() || remaining >= fade {
+        return 100;
+    }
+    (remaining.as_secs_f64() / fade.as_secs_f64() * 100.0).round() as u8
+}
+
+/// Rounds up so the display reads 1 until the very end.
+fn whole_secs(d: Duration) -> u32 {
+    u32::try_from(d.as_secs() + u64::from(d.subsec_nanos() > 0)).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(file: &str, album: Option<&str>) -> Song {
+        Song {
+            file: file.to_owned(),
+            album: album.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fade_level_ramps_down_over_fade_period() {
+        let fade = Duration::from_secs(30);
+        assert_eq!(fade_level(Duration::from_secs(60), fade), 100);
+        assert_eq!(fade_level(Duration::from_secs(15), fade), 50);
+        assert_eq!(fade_level(Duration::ZERO, fade), 0);
+        assert_eq!(fade_level(Duration::ZERO, Duration::ZERO), 100);
+    }
+
+    #[test]
+    fn end_of_album_stops_only_when_album_changes() {
+        let a1 = song("a1", Some("A"));
+        let a2 = song("a2", Some("A"));
+        let b1 = song("b1", Some("B"));
+        assert!(!ends_here(SleepTimerMode::EndOfAlbum, &a1, Some(&a2)));
+        assert!(ends_here(SleepTimerMode::EndOfAlbum, &a2, Some(&b1)));
+        assert!(ends_here(SleepTimerMode::EndOfAlbum, &a2, None));
+        assert!(ends_here(SleepTimerMode::EndOfTrack, &a1, Some(&a2)));
+        assert!(!ends_here(SleepTimerMode::Timer, &a1, None));
+    }
+
+    #[test]
+    fn songs_without_album_never_share_one() {
+        assert!(!same_album(&song("x", None), &song("y", None)));
+    }
+}
@@ -0,0 +1,652 @@
+//! Reconnecting, pre-buffered reader for live radio streams.
+//!
+//! A fetch thread reads the HTTP body ahead of the decoder into a bounded
+//! buffer. The decoder gets its first bytes once `prebuffer_bytes` are
+//! buffered, and waits for the buffer to refill when it ran dry. When the
+//! body fails or ends, the thread re-requests the stream with exponential
+//! backoff while the decoder plays on from the buffer. Each attempt is
+//! reported as a notification. Around the gap the stream is cut at frame
+//! boundaries: the unfinished last frame of the old connection is dropped,
+//! and the new one starts at its first frame header. Bytes are handed to the
+//! [`StreamRecorder`] as they are buffered, so a recording is cut at the same
+//! frame boundaries. Title changes parsed from the stream are queued at the
+//! byte offset they arrived at and published once the decoder reads that
+//! far, so they match what is heard rather than what was fetched.
+//! [`IdleTimeout`] turns a connection that stays silent into a read error,
+//! so it counts as dropped too.
+
+use std::collections::VecDeque;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::{info, warn};
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::sync::broadcast::{Receiver as EventReceiver, Sender};
+
+use api_models::state::StateChangeEvent;
+use metadata::stream_recorder::StreamRecorder;
+
+pub type StreamOpener = Box<dyn FnMut() -> Result<Box<dyn Read + Send>> + Send>;
+
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+/// Delay before the first reconnect attempt; doubled for each further one.
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+/// The decoder gives up when no data arrived for this long, longer than all
+/// reconnect attempts take.
+const STALL_TIMEOUT: Duration = Duration::from_secs(90);
+const MIN_CAPACITY: usize = 256 * 1024;
+const READ_CHUNK: usize = 16 * 1024;
+/// New data searched for a frame header before it is passed on unaligned.
+const MAX_SYNC_SEARCH: usize = 64 * 1024;
+
+/// Frame header pattern of a stream's container, used to cut it cleanly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameSync {
+    Mpeg,
+    Adts,
+    Ogg,
+    Flac,
+    Unknown,
+}
+
+impl FrameSync {
+    pub fn for_extension(extension: Option<&str>) -> Self {
+        match extension {
+            Some("mp3") => Self::Mpeg,
+            Some("aac") => Self::Adts,
+            Some("ogg") => Self::Ogg,
+            Some("flac") => Self::Flac,
+            _ => Self::Unknown,
+        }
+    }
+
+    fn is_frame_start(self, data: &[u8]) -> bool {
+        match (self, data) {
+            (Self::Mpeg, [0xff, b1, b2, ..]) => {
+                b1 & 0xe0 == 0xe0 && (b1 >> 3) & 0x3 != 1 && (b1 >> 1) & 0x3 != 0 && !matches!(b2 >> 4, 0 | 0xf) && (b2 >> 2) & 0x3 != 3
+            }
+            (Self::Adts, [0xff, b1, b2, ..]) => b1 & 0xf6 == 0xf0 && (b2 >> 2) & 0xf < 13,
+            (Self::Ogg, [b'O', b'g', b'g', b'S', ..]) | (Self::Flac, [0xff, 0xf8 | 0xf9, ..]) => true,
+            _ => false,
+        }
+    }
+
+    fn first_frame(self, data: &[u8]) -> Option<usize> {
+        (0..data.len()).find(|i| self.is_frame_start(&data[*i..]))
+    }
+
+    /// Length of the frame `data` starts with, `None` when its header is
+    /// cut off or the format does not tell.
+    fn frame_len(self, data: &[u8]) -> Option<usize> {
+        match (self, data) {
+            (Self::Mpeg, [_, b1, b2, ..]) => mpeg_frame_len(*b1, *b2),
+            (Self::Adts, [_, _, _, b3, b4, b5, ..]) => Some((usize::from(b3 & 0x3) << 11) | (usize::from(*b4) << 3) | usize::from(b5 >> 5)),
+            (Self::Ogg, _) => data.get(26).and_then(|segments| {
+                let lacing = data.get(27..27 + usize::from(*segments))?;
+                Some(27 + lacing.len() + lacing.iter().map(|l| usize::from(*l)).sum::<usize>())
+            }),
+            _ => None,
+        }
+    }
+
+    /// Where the unfinished frame at the end of `data` starts. A frame
+    /// header is only trusted when the frame length it gives leads to the
+    /// next header, so payload bytes that look like a header are not taken
+    /// for one. `None` when `data` ends with a whole frame or no chain of
+    /// frames is found.
+    fn unfinished_frame(self, data: &[u8]) -> Option<usize> {
+        let mut start = 0;
+        while let Some(offset) = self.first_frame(&data[start..]) {
+            let frame = start + offset;
+            let mut end = frame;
+            loop {
+                match self.frame_len(&data[end..]) {
+                    Some(len) if len > 0 && end + len < data.len() => {
+                        if !self.is_frame_start(&data[end + len..]) {
+                            break;
+                        }
+                        end += len;
+                    }
+                    Some(len) if len > 0 && end + len == data.len() => {
+                        if end > frame {
+                            return None;
+                        }
+                        break;
+                    }
+                    _ => {
+                        if end > frame {
+                            return Some(end);
+                        }
+                        break;
+                    }
+                }
+            }
+            start = frame + 1;
+        }
+        None
+    }
+}
+
+/// Length of an MPEG audio frame from the second and third header bytes.
+fn mpeg_frame_len(b1: u8, b2: u8) -> Option<usize> {
+    const V1_LAYER1: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+    const V1_LAYER2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+    const V1_LAYER3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const V2_LAYER1: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+    const V2_LAYER23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    // Version 3 is MPEG-1, 2 is MPEG-2, 0 is MPEG-2.5; layer 3 is Layer I.
+    let (version, layer) = ((b1 >> 3) & 0x3, (b1 >> 1) & 0x3);
+    let bitrates = match (version, layer) {
+        (3, 3) => &V1_LAYER1,
+        (3, 2) => &V1_LAYER2,
+        (3, _) => &V1_LAYER3,
+        (_, 3) => &V2_LAYER1,
+        _ => &V2_LAYER23,
+    };
+    let bits_per_sec = bitrates.get(usize::from(b2 >> 4))? * 1000;
+    let base_rate = [44_100, 48_000, 32_000].get(usize::from((b2 >> 2) & 0x3))?;
+    let sample_rate = match version {
+        3 => *base_rate,
+        2 => base_rate / 2,
+        _ => base_rate / 4,
+    };
+    let padding = u32::from((b2 >> 1) & 0x1);
+    let len = match layer {
+        3 => (12 * bits_per_sec / sample_rate + padding) * 4,
+        1 if version != 3 => 72 * bits_per_sec / sample_rate + padding,
+        _ => 144 * bits_per_sec / sample_rate + padding,
+    };
+    usize::try_from(len).ok()
+}
+
+#[derive(Default)]
+struct State {
+    data: VecDeque<u8>,
+    /// Stream offset of the end of `data`: all bytes buffered so far.
+    pushed: u64,
+    /// Title changes and the stream offset they apply from.
+    titles: VecDeque<(u64, StateChangeEvent)>,
+    /// Enough is buffered to start, or restart after running dry.
+    ready: bool,
+    /// Why the fetch thread gave up, once it did.
+    ended: Option<String>,
+    /// The reader was dropped; the fetch thread stops.
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct ReconnectingStream {
+    shared: Arc<Shared>,
+    changes_tx: Sender<StateChangeEvent>,
+}
+
+impl ReconnectingStream {
+    /// Starts reading `source` ahead; `reopen` requests the stream again
+    /// after it dropped. What is read is also written to `recorder`. Events
+    /// sent to `titles` while a source is read are published on `changes_tx`
+    /// once the decoder reaches the bytes read with them.
+    pub fn new(
+        source: Box<dyn Read + Send>,
+        reopen: StreamOpener,
+        sync: FrameSync,
+        prebuffer_bytes: usize,
+        recorder: Arc<StreamRecorder>,
+        changes_tx: Sender<StateChangeEvent>,
+        titles: EventReceiver<StateChangeEvent>,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+        let fetcher = Fetcher {
+            shared: shared.clone(),
+            reopen,
+            sync,
+            prebuffer_bytes,
+            capacity: MIN_CAPACITY.max(prebuffer_bytes * 2),
+            recorder,
+            changes_tx: changes_tx.clone(),
+            titles,
+            pending_titles: Vec::new(),
+        };
+        thread::Builder::new()
+            .name("radio_fetch".to_string())
+            .spawn(move || fetcher.run(source))
+            .expect("Failed to start radio fetch thread");
+        Self { shared, changes_tx }
+    }
+}
+
+impl Read for ReconnectingStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let deadline = Instant::now() + STALL_TIMEOUT;
+        let mut state = self.shared.lock();
+        loop {
+            if !state.data.is_empty() && (state.ready || state.ended.is_some()) {
+                break;
+            }
+            if let Some(reason) = &state.ended {
+                return Err(IoError::other(reason.clone()));
+            }
+            if state.data.is_empty() {
+                state.ready = false;
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(IoError::new(ErrorKind::TimedOut, "Radio stream stalled"));
+            }
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, timeout)
+                .map_or_else(|e| e.into_inner().0, |r| r.0);
+        }
+        let len = buf.len().min(state.data.len());
+        for (slot, byte) in buf.iter_mut().zip(state.data.drain(..len)) {
+            *slot = byte;
+        }
+        let consumed = state.pushed - state.data.len() as u64;
+        let mut reached = Vec::new();
+        while state.titles.front().is_some_and(|(offset, _)| *offset < consumed) {
+            reached.extend(state.titles.pop_front().map(|(_, event)| event));
+        }
+        self.shared.changed.notify_all();
+        drop(state);
+        for event in reached {
+            let _ = self.changes_tx.send(event);
+        }
+        Ok(len)
+    }
+}
+
+impl Drop for ReconnectingStream {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+struct Fetcher {
+    shared: Arc<Shared>,
+    reopen: StreamOpener,
+    sync: FrameSync,
+    prebuffer_bytes: usize,
+    capacity: usize,
+    recorder: Arc<StreamRecorder>,
+    changes_tx: Sender<StateChangeEvent>,
+    titles: EventReceiver<StateChangeEvent>,
+    /// Title changes read with data not buffered yet.
+    pending_titles: Vec<StateChangeEvent>,
+}
+
+impl Fetcher {
+    fn run(mut self, mut source: Box<dyn Read + Send>) {
+        let mut chunk = vec![0; READ_CHUNK];
+        // After a reconnect: bytes held back until a frame header shows up.
+        let mut unsynced: Option<Vec<u8>> = None;
+        loop {
+            if self.shared.lock().closed {
+                return;
+            }
+            match source.read(&mut chunk) {
+                Ok(len) if len > 0 => {
+                    self.take_titles();
+                    let data = &chunk[..len];
+                    let passed = match unsynced.as_mut() {
+                        None => self.push(data),
+                        Some(held) => {
+                            held.extend_from_slice(data);
+                            let frame = self.sync.first_frame(held);
+                            if frame.is_none() && held.len() < MAX_SYNC_SEARCH {
+                                continue;
+                            }
+                            let held = unsynced.take().unwrap_or_default();
+                            self.push(&held[frame.unwrap_or(0)..])
+                        }
+                    };
+                    if !passed {
+                        return;
+                    }
+                }
+                result => {
+                    if let Err(e) = result {
+                        warn!("Radio stream dropped: {e}");
+                    } else {
+                        info!("Radio stream ended");
+                    }
+                    self.drop_unfinished_frame();
+                    match self.reconnect() {
+                        Some(reopened) => {
+                            source = reopened;
+                            unsynced = (self.sync != FrameSync::Unknown).then(Vec::new);
+                        }
+                        None => return,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves the title changes sent while the source was read to
+    /// `pending_titles`.
+    fn take_titles(&mut self) {
+        loop {
+            match self.titles.try_recv() {
+                Ok(event) => self.pending_titles.push(event),
+                Err(TryRecvError::Lagged(_)) => {}
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Records `data` and appends it once there is room, with the pending
+    /// title changes queued at its start; `false` when the reader is gone.
+    fn push(&mut self, data: &[u8]) -> bool {
+        self.recorder.write(data);
+        let mut state = self.shared.lock();
+        while !state.closed && state.data.len() + data.len() > self.capacity && !state.data.is_empty() {
+            state = self.shared.changed.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        if state.closed {
+            return false;
+        }
+        let offset = state.pushed;
+        state.titles.extend(self.pending_titles.drain(..).map(|event| (offset, event)));
+        state.data.extend(data);
+        state.pushed += data.len() as u64;
+        if state.data.len() >= self.prebuffer_bytes {
+            state.ready = true;
+        }
+        self.shared.changed.notify_all();
+        true
+    }
+
+    fn drop_unfinished_frame(&self) {
+        let mut state = self.shared.lock();
+        let data = state.data.make_contiguous();
+        let Some(frame) = self.sync.unfinished_frame(data) else {
+            return;
+        };
+        let dropped = data.len() - frame;
+        state.data.truncate(frame);
+        state.pushed -= dropped as u64;
+        let end = state.pushed;
+        for (offset, _) in &mut state.titles {
+            *offset = (*offset).min(end);
+        }
+        drop(state);
+        self.recorder.take_back(dropped as u64);
+    }
+
+    /// Requests the stream again with backoff; `None` when every attempt
+    /// failed or the reader is gone.
+    fn reconnect(&mut self) -> Option<Box<dyn Read + Send>> {
+        let mut delay = FIRST_BACKOFF;
+        for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+            self.notify(StateChangeEvent::NotificationError(format!(
+                "Radio stream interrupted, reconnecting ({attempt}/{MAX_RECONNECT_ATTEMPTS})"
+            )));
+            if self.sleep(delay) {
+                return None;
+            }
+            delay *= 2;
+            match (self.reopen)() {
+                Ok(source) => {
+                    self.notify(StateChangeEvent::NotificationSuccess("Radio stream reconnected".to_string()));
+                    return Some(source);
+                }
+                Err(e) => warn!("Radio reconnect attempt {attempt} failed: {e}"),
+            }
+        }
+        let mut state = self.shared.lock();
+        state.ended = Some(format!("Radio stream lost after {MAX_RECONNECT_ATTEMPTS} reconnect attempts"));
+        self.shared.changed.notify_all();
+        None
+    }
+
+    /// Waits `delay`; `true` when the reader was dropped meanwhile.
+    fn sleep(&self, delay: Duration) -> bool {
+        let state = self.shared.lock();
+        let (state, _) = self
+            .shared
+            .changed
+            .wait_timeout_while(state, delay, |s| !s.closed)
+            .unwrap_or_else(PoisonError::into_inner);
+        state.closed
+    }
+
+    fn notify(&self, event: StateChangeEvent) {
+        let _ = self.changes_tx.send(event);
+    }
+}
+
+/// Reads a response body on its own thread, so that a read fails with
+/// `TimedOut` once no data arrived for `idle`.
+///
+/// Without it a read blocks for as long as the server keeps the connection
+/// open without sending. The thread ends with the body, or with its next
+/// read after this reader was dropped.
+pub struct IdleTimeout {
+    chunks: Mutex<Receiver<IoResult<Vec<u8>>>>,
+    pending: Vec<u8>,
+    pos: usize,
+    idle: Duration,
+}
+
+impl IdleTimeout {
+    pub fn new(mut inner: impl Read + Send + 'static, idle: Duration) -> Self {
+        let (chunks_tx, chunks) = mpsc::sync_channel(4);
+        thread::Builder::new()
+            .name("stream_read".to_string())
+            .spawn(move || {
+                let mut chunk = vec![0; READ_CHUNK];
+                loop {
+                    let result = match inner.read(&mut chunk) {
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        result => result.map(|len| chunk[..len].to_vec()),
+                    };
+                    let last = !matches!(&result, Ok(data) if !data.is_empty());
+                    if chunks_tx.send(result).is_err() || last {
+                        return;
+                    }
+                }
+            })
+            .expect("Failed to start stream read thread");
+        Self {
+            chunks: Mutex::new(chunks),
+            pending: Vec::new(),
+            pos: 0,
+            idle,
+        }
+    }
+}
+
+impl Read for IdleTimeout {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.pos == self.pending.len() {
+            let chunks = self.chunks.get_mut().unwrap_or_else(PoisonError::into_inner);
+            match chunks.recv_timeout(self.idle) {
+                Ok(chunk) => {
+                    self.pending = chunk?;
+                    self.pos = 0;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(IoError::new(
+                        ErrorKind::TimedOut,
+                        format!("No stream data for {} s", self.idle.as_secs()),
+                    ));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.pending.len() - self.pos);
+        buf[..len].copy_from_slice(&self.pending[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Reads its bytes, then fails like a dropped connection.
+    struct Dropping(Cursor<Vec<u8>>);
+
+    impl Read for Dropping {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+            match self.0.read(buf)? {
+                0 => Err(IoError::from(ErrorKind::ConnectionReset)),
+                len => Ok(len),
+            }
+        }
+    }
+
+    /// An ADTS frame with `payload` bytes of `fill`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn frame(fill: u8, payload: usize) -> Vec<u8> {
+        let len = 7 + payload;
+        let header = [
+            0xff,
+            0xf1,
+            0x50,
+            0x80 | (len >> 11) as u8,
+            (len >> 3) as u8,
+            ((len & 0x7) << 5) as u8 | 0x1f,
+            0xfc,
+        ];
+        [&header[..], &vec![fill; payload]].concat()
+    }
+
+    #[test]
+    fn reconnects_and_cuts_the_gap_at_frame_boundaries() {
+        let frames: Vec<Vec<u8>> = (1..=4).map(|fill| frame(fill, 10)).collect();
+        let first = Dropping(Cursor::new([&frames[0][..], &frames[1][..8]].concat()));
+        let mut reopened = Some(Dropping(Cursor::new(
+            [&[0x12, 0x34, 0xff, 0x00][..], &frames[2], &frames[3]].concat(),
+        )));
+        let reopen: StreamOpener = Box::new(move || {
+            let source = reopened.take().ok_or_else(|| anyhow::format_err!("offline"))?;
+            Ok(Box::new(source) as Box<dyn Read + Send>)
+        });
+        let (changes_tx, mut changes_rx) = tokio::sync::broadcast::channel(16);
+        let expected = [&frames[0][..], &frames[2], &frames[3]].concat();
+        let dir = std::env::temp_dir().join(format!("rsptest_reconnect_{}", std::process::id()));
+        let recorder = StreamRecorder::new(tokio::sync::broadcast::channel(16).0);
+        recorder.stream_opened("Station", "aac");
+        recorder.start(&dir, false).expect("start recording");
+
+        let mut stream = ReconnectingStream::new(
+            Box::new(first),
+            reopen,
+            FrameSync::Adts,
+            expected.len(),
+            recorder.clone(),
+            changes_tx,
+            tokio::sync::broadcast::channel(16).1,
+        );
+        let mut played = vec![0; expected.len()];
+        stream.read_exact(&mut played).expect("buffered stream");
+        drop(stream);
+        let recording = recorder.current_file().expect("recording");
+        recorder.stop();
+        let recorded = std::fs::read(recording).expect("recorded file");
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(played, expected);
+        assert_eq!(recorded, expected);
+        assert!(matches!(changes_rx.try_recv(), Ok(StateChangeEvent::NotificationError(m)) if m.contains("reconnecting (1/5)")));
+        assert!(matches!(changes_rx.try_recv(), Ok(StateChangeEvent::NotificationSuccess(_))));
+    }
+
+    #[test]
+    fn titles_are_published_when_the_decoder_reaches_them() {
+        /// Sends a title change along with its second chunk, as the ICY
+        /// reader does with the audio after a metadata block.
+        struct Titled {
+            chunks: VecDeque<Vec<u8>>,
+            titles_tx: Sender<StateChangeEvent>,
+        }
+        impl Read for Titled {
+            fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+                let Some(chunk) = self.chunks.pop_front() else {
+                    return Ok(0);
+                };
+                if self.chunks.is_empty() {
+                    let _ = self.titles_tx.send(StateChangeEvent::NotificationSuccess("Next".to_string()));
+                }
+                buf[..chunk.len()].copy_from_slice(&chunk);
+                Ok(chunk.len())
+            }
+        }
+        let (titles_tx, titles_rx) = tokio::sync::broadcast::channel(16);
+        let source = Titled {
+            chunks: VecDeque::from([vec![1; 1000], vec![2; 1000]]),
+            titles_tx,
+        };
+        let reopen: StreamOpener = Box::new(|| Err(anyhow::format_err!("offline")));
+        let (changes_tx, mut changes_rx) = tokio::sync::broadcast::channel(16);
+        let recorder = StreamRecorder::new(tokio::sync::broadcast::channel(16).0);
+        let mut stream = ReconnectingStream::new(Box::new(source), reopen, FrameSync::Unknown, 2000, recorder, changes_tx, titles_rx);
+
+        // Reconnect attempts after the source ended are reported as errors.
+        let mut titles = move || {
+            std::iter::from_fn(|| changes_rx.try_recv().ok())
+                .filter_map(|event| match event {
+                    StateChangeEvent::NotificationSuccess(title) => Some(title),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut played = vec![0; 1000];
+        stream.read_exact(&mut played).expect("first chunk");
+        assert!(titles().is_empty(), "title published before its audio");
+        stream.read_exact(&mut played[..1]).expect("second chunk");
+        assert_eq!(played[0], 2);
+        assert_eq!(titles(), ["Next"]);
+    }
+
+    #[test]
+    fn payload_looking_like_a_header_is_not_taken_for_one() {
+        let whole = frame(1, 10);
+        let mut unfinished = frame(2, 40);
+        // A false header inside the payload whose length reaches exactly to
+        // where the data is cut.
+        let fake = frame(3, 3);
+        unfinished[9..9 + 7].copy_from_slice(&fake[..7]);
+        let data = [&whole[..], &unfinished[..9 + 10]].concat();
+
+        assert_eq!(FrameSync::Adts.unfinished_frame(&data), Some(whole.len()));
+        assert_eq!(FrameSync::Adts.unfinished_frame(&[&whole[..], &frame(2, 5)].concat()), None);
+    }
+
+    #[test]
+    fn silent_body_times_out() {
+        struct Silent;
+        impl Read for Silent {
+            fn read(&mut self, _: &mut [u8]) -> IoResult<usize> {
+                thread::sleep(Duration::from_millis(200));
+                Ok(1)
+            }
+        }
+        let mut reader = IdleTimeout::new(Silent, Duration::from_millis(20));
+        let error = reader.read(&mut [0; 4]).expect_err("idle body");
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+    }
+}
@@ -17,7 +17,6 @@ use anyhow::{Result, format_err};
 use api_models::player::Song;
 use api_models::state::{PlayerInfo, SongProgress, StateChangeEvent};
 use log::{debug, warn};
-use metadata::ape_bundle::ApeReader;
 use metadata::dsd_bundle::{CODEC_TYPE_DSD_LSBF, CODEC_TYPE_DSD_MSBF};
 use metadata::{build_codec_registry, build_probe};
 
@@ -35,7 +34,7 @@ use symphonia::core::meta::MetadataOptions;
 use symphonia::core::units::{Time, TimeBase, Timestamp};
 
 use crate::rsp::audio_output::AudioOutput;
-use crate::rsp::audio_source::{is_http_stream, probe_http_source, probe_local_file, resolve_ape_path, resolve_sacd_iso_path};
+use crate::rsp::audio_source::{is_http_stream, open_local_reader, probe_http_source};
 use crate::rsp::device_capabilities::{DeviceCapabilities, fallback_rate_candidates};
 use crate::rsp::playback_config::PlaybackConfig;
 use crate::rsp::playback_context::PlaybackContext;
@@ -58,48 +57,20 @@ pub fn play_file(
     normalization_gain_db: Option<i32>,
 ) -> Result<PlaybackResult> {
     debug!("Playing file {path_str}");
-    let mut hint = Hint::new();
 
     let is_seekable = !is_http_stream(path_str);
 
-    // For APE files: open the file directly and construct ApeReader without
-    // reading the entire file into memory. Only headers + seek table are read
-    // upfront (~1KB); frame data is read on demand during playback.
-    let ape_path = if is_http_stream(path_str) {
-        None
-    } else {
-        resolve_ape_path(path_str, &config.music_dirs)
-    };
-
-    // For SACD ISO virtual paths (e.g. "album.iso#SACD_0002"): open the ISO directly
-    // and construct a SacdIsoReader for the specified track index.
-    let sacd_path = if is_http_stream(path_str) {
-        None
-    } else {
-        resolve_sacd_iso_path(path_str, &config.music_dirs)
-    };
-
     let mut radio_meta: Option<RadioMeta> = None;
 
-    let mut reader: Box<dyn FormatReader + '_> = if let Some((iso_path, track_idx)) = sacd_path {
-        debug!("SACD ISO: {} track {}", iso_path.display(), track_idx);
-        let file = std::fs::File::open(&iso_path).map_err(|e| format_err!("Failed to open SACD ISO {}: {e}", iso_path.display()))?;
-        Box::new(
-            metadata::sacd_bundle::SacdIsoReader::try_new_for_track(file, track_idx)
-                .map_err(|e| format_err!("Failed to open SACD track: {e}"))?,
-        )
-    } else if let Some(ape_path) = ape_path {
-        debug!("APE direct file path: {}", ape_path.display());
-        // 1 MB buffer to amortize NFS round trips — default 8 KB causes
-        // dozens of small reads per frame, starving the audio ring buffer on slow links.
-        let file = std::io::BufReader::with_capacity(1024 * 1024, std::fs::File::open(&ape_path)?);
-        Box::new(ApeReader::try_new_from_reader(file)?)
-    } else {
-        let (source, rm) = if is_http_stream(path_str) {
-            probe_http_source(path_str, &mut hint, &context.changes_tx)?
-        } else {
-            probe_local_file(path_str, &config.music_dirs, &mut hint)?
-        };
+    let mut reader: Box<dyn FormatReader + '_> = if is_http_stream(path_str) {
+        let mut hint = Hint::new();
+        let (source, rm) = probe_http_source(
+            path_str,
+            &mut hint,
+            &context.changes_tx,
+            &context.stream_recorder,
+            config.settings.radio_prebuffer_secs,
+        )?;
         radio_meta = rm;
 
         if let Some(rm) = &radio_meta {
@@ -124,6 +95,8 @@ pub fn play_file(
                 MetadataOptions::default(),
             )
             .map_err(|_| format_err!("Media source probe failed"))?
+    } else {
+        open_local_reader(path_str, &config.music_dirs)?
     };
 
     let tracks = reader.tracks();
@@ -255,6 +228,8 @@ pub fn play_file(
                         context.dsp_handle.as_ref(),
                         vu_meter.clone(),
                         context.software_gain.as_ref(),
+                        Some(&context.fade_gain),
+                        Some(&context.output_tee),
                     ) else {
                         if caps.rate.is_none() {
                             let fallback_rates = fallback_rate_candidates(&device, spec_rate);
@@ -275,6 +250,8 @@ pub fn play_file(
                                     context.dsp_handle.as_ref(),
                                     vu_meter.clone(),
                                     context.software_gain.as_ref(),
+                                    Some(&context.fade_gain),
+                                    Some(&context.output_tee),
                                 ) {
                                     debug!("Audio opened with fallback rate");
                                     audio_output.replace(audio_out);
@@ -398,7 +375,7 @@ pub fn play_file(
     loop_result
 }
 
-fn first_supported_track(tracks: &[Track]) -> Option<&Track> {
+pub(crate) fn first_supported_track(tracks: &[Track]) -> Option<&Track> {
     tracks.iter().find(|t| {
         matches!(
             &t.codec_params,
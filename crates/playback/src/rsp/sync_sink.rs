@@ -188,6 +188,9 @@ fn run_sink(
         dsp_handle,
         vu_meter,
         software_gain,
+        // No sleep timer fade: the follower stops when the leader does.
+        None,
+        None,
     )
     .context("failed to open audio output for multiroom sink")?;
 
@@ -0,0 +1,356 @@
+//! On-the-fly transcoding of library tracks for browser playback (`/music`
+//! with a target format).
+//!
+//! Tracks are opened like playback opens them — APE and SACD ISO through
+//! their own readers, DSF through the DSD decoder — and re-encoded with the
+//! `/stream` encoders. DSD is converted to PCM by `dsd_pcm` first. FLAC
+//! keeps the source's rate and channels (24 bit for hi-res and DSD); Opus
+//! and MP3 get at most stereo at a rate they support. Output is produced
+//! packet by packet, so the response starts before the track is decoded.
+
+use std::time::Duration;
+
+use anyhow::{Result, format_err};
+use api_models::settings::AudioEncoding;
+use log::{debug, warn};
+use metadata::build_codec_registry;
+use metadata::dsd_bundle::{CODEC_TYPE_DSD_LSBF, CODEC_TYPE_DSD_MSBF};
+use symphonia::core::audio::Channels;
+use symphonia::core::codecs::CodecParameters;
+use symphonia::core::codecs::audio::{AudioDecoder, AudioDecoderOptions};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
+use symphonia::core::units::Time;
+
+use crate::rsp::audio_source::open_local_reader;
+use crate::rsp::dsd_pcm::DsdToPcm;
+use crate::rsp::encoder::{AudioEncoder, FlacEncoder, Mp3Encoder, OpusEncoder, Resampler, STREAM_RATE};
+use crate::rsp::symphonia::first_supported_track;
+
+/// Highest rate MP3 has a mode for.
+const MP3_MAX_RATE: u32 = 48_000;
+
+pub struct Transcoder {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn AudioDecoder>,
+    track_id: u32,
+    dsd: Option<DsdToPcm>,
+    source_channels: usize,
+    channels: usize,
+    resampler: Option<Resampler>,
+    encoder: Box<dyn AudioEncoder>,
+    /// Interleaved samples still to drop after seeking, which lands on the
+    /// packet before the requested time.
+    skip_samples: usize,
+    words: Vec<u32>,
+    samples: Vec<f32>,
+    mapped: Vec<f32>,
+    finished: bool,
+}
+
+impl Transcoder {
+    /// Opens `path_str` (a library key, as queued) and positions it at `start`.
+    pub fn open(path_str: &str, music_dirs: &[String], encoding: AudioEncoding, bitrate_kbps: u32, start: Duration) -> Result<Self> {
+        let mut reader = open_local_reader(path_str, music_dirs)?;
+        let track = first_supported_track(reader.tracks()).ok_or_else(|| format_err!("No audio track in {path_str}"))?;
+        let track_id = track.id;
+        let Some(CodecParameters::Audio(params)) = track.codec_params.clone() else {
+            return Err(format_err!("Invalid track codec params"));
+        };
+        let track_rate = params.sample_rate.ok_or_else(|| format_err!("Unknown sample rate of {path_str}"))?;
+        let source_channels = params.channels.as_ref().map_or(2, Channels::count).max(1);
+        let is_dsd = params.codec == CODEC_TYPE_DSD_LSBF || params.codec == CODEC_TYPE_DSD_MSBF;
+        let dsd = is_dsd.then(|| DsdToPcm::new(track_rate, source_channels)).transpose()?;
+        let pcm_rate = dsd.as_ref().map_or(track_rate, DsdToPcm::pcm_rate);
+        let decoder = build_codec_registry().make_audio_decoder(&params, &AudioDecoderOptions::default())?;
+
+        let (rate, channels) = match encoding {
+            AudioEncoding::Flac if source_channels <= 8 => (pcm_rate, source_channels),
+            AudioEncoding::Flac => (pcm_rate, 2),
+            AudioEncoding::Opus => (STREAM_RATE, source_channels.min(2)),
+            AudioEncoding::Mp3 if pcm_rate <= MP3_MAX_RATE => (pcm_rate, source_channels.min(2)),
+            AudioEncoding::Mp3 if pcm_rate.is_multiple_of(44_100) => (44_100, source_channels.min(2)),
+            AudioEncoding::Mp3 => (MP3_MAX_RATE, source_channels.min(2)),
+        };
+        let encoder: Box<dyn AudioEncoder> = match encoding {
+            AudioEncoding::Flac => {
+                let bits = if is_dsd || params.bits_per_sample.is_some_and(|b| b > 16) {
+                    24
+                } else {
+                    16
+                };
+                Box::new(FlacEncoder::new(rate, channels, bits)?)
+            }
+            AudioEncoding::Opus => Box::new(OpusEncoder::new(channels, bitrate_kbps)?),
+            AudioEncoding::Mp3 => Box::new(Mp3Encoder::new(rate, channels, bitrate_kbps)?),
+        };
+        debug!("Transcoding {path_str} from {pcm_rate}Hz/{source_channels}ch to {encoding:?} {rate}Hz/{channels}ch");
+
+        let mut skip_samples = 0;
+        if !start.is_zero() {
+            let seeked = reader.seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::try_new(start.as_secs().cast_signed(), 0).unwrap_or(Time::ZERO),
+                    track_id: Some(track_id),
+                },
+            )?;
+            // Timestamps count frames at the track's rate (bits for DSD);
+            // the fraction of a second is skipped as well.
+            let skip_ts = seeked.required_ts.get().saturating_sub(seeked.actual_ts.get()).unsigned_abs();
+            let skip_frames = u128::from(skip_ts) * u128::from(pcm_rate) / u128::from(track_rate)
+                + u128::from(start.subsec_millis()) * u128::from(pcm_rate) / 1000;
+            skip_samples = usize::try_from(skip_frames).unwrap_or(0) * source_channels;
+        }
+
+        Ok(Self {
+            reader,
+            decoder,
+            track_id,
+            dsd,
+            source_channels,
+            channels,
+            resampler: (rate != pcm_rate).then(|| Resampler::new(pcm_rate, rate, channels)),
+            encoder,
+            skip_samples,
+            words: Vec::new(),
+            samples: Vec::new(),
+            mapped: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// The next piece of the encoded file, `None` once it is complete. The
+    /// first piece starts with the format headers.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        while !self.finished {
+            let packet = match self.reader.next_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => return self.finish().map(Some),
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => return self.finish().map(Some),
+                Err(Error::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(e)) => {
+                    warn!("decode error: {e}");
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let pcm = if let Some(dsd) = &mut self.dsd {
+                decoded.copy_to_vec_interleaved(&mut self.words);
+                dsd.process(&self.words)
+            } else {
+                decoded.copy_to_vec_interleaved(&mut self.samples);
+                &self.samples
+            };
+            let skip = self.skip_samples.min(pcm.len());
+            self.skip_samples -= skip;
+            let pcm = &pcm[skip..];
+
+            let pcm = if self.channels == self.source_channels {
+                pcm
+            } else {
+                // Beyond what the target takes only the front pair is kept.
+                self.mapped.clear();
+                for frame in pcm.chunks_exact(self.source_channels) {
+                    self.mapped.extend_from_slice(&frame[..self.channels]);
+                }
+                &self.mapped
+            };
+            let encoded = match &mut self.resampler {
+                Some(resampler) => {
+                    let resampled = resampler.process(pcm);
+                    self.encoder.encode(&resampled)?
+                }
+                None => self.encoder.encode(pcm)?,
+            };
+            if !encoded.is_empty() {
+                return Ok(Some(encoded));
+            }
+        }
+        Ok(None)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        self.finished = true;
+        let mut out = match &mut self.resampler {
+            Some(resampler) => {
+                let tail = resampler.finish();
+                self.encoder.encode(&tail)?
+            }
+            None => Vec::new(),
+        };
+        out.extend(self.encoder.finish()?);
+        Ok(out)
+    }
+}
+
+/// Length of a library track as its container states it.
+pub fn duration(path_str: &str, music_dirs: &[String]) -> Option<Duration> {
+    let reader = open_local_reader(path_str, music_dirs).ok()?;
+    let track = first_supported_track(reader.tracks())?;
+    let Some(CodecParameters::Audio(params)) = &track.codec_params else {
+        return None;
+    };
+    let frames = track.num_frames?;
+    let rate = params.sample_rate.filter(|rate| *rate > 0)?;
+    #[allow(clippy::cast_precision_loss)]
+    Some(Duration::from_secs_f64(frames as f64 / f64::from(rate)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::rsp::encoder::readback::{self, Decoded};
+
+    const WAV_RATE: u32 = 44_100;
+    const DSD64: u32 = 2_822_400;
+    /// Bytes per channel in a DSF block.
+    const DSF_BLOCK: usize = 4096;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsptest_transcoder_{}", random_string::generate(8, "abcdefgh")));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        dir
+    }
+
+    /// Stereo 16 bit samples of a tone, the right channel inverted.
+    fn wav_samples(frames: usize) -> Vec<i16> {
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        (0..frames)
+            .flat_map(|i| {
+                let s = ((i as f64 / 30.0).sin() * 20_000.0) as i16;
+                [s, -s]
+            })
+            .collect()
+    }
+
+    fn write_wav(path: &Path, samples: &[i16]) {
+        let data_len = u32::try_from(samples.len() * 2).expect("size");
+        let mut wav = Vec::with_capacity(44 + samples.len() * 2);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, two channels, 16 bit.
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&WAV_RATE.to_le_bytes());
+        wav.extend_from_slice(&(WAV_RATE * 4).to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for s in samples {
+            wav.extend_from_slice(&s.to_le_bytes());
+        }
+        std::fs::write(path, wav).expect("write wav");
+    }
+
+    /// A stereo DSD64 file of `blocks` blocks of the idle pattern (silence).
+    fn write_dsf(path: &Path, blocks: usize) {
+        let data_len = (blocks * DSF_BLOCK * 2) as u64;
+        let mut dsf = Vec::new();
+        dsf.extend_from_slice(b"DSD ");
+        dsf.extend_from_slice(&28u64.to_le_bytes());
+        dsf.extend_from_slice(&(28 + 52 + 12 + data_len).to_le_bytes());
+        // No ID3 metadata.
+        dsf.extend_from_slice(&0u64.to_le_bytes());
+        dsf.extend_from_slice(b"fmt ");
+        dsf.extend_from_slice(&52u64.to_le_bytes());
+        // Version 1, DSD raw, stereo, two channels.
+        for field in [1u32, 0, 2, 2, DSD64, 1] {
+            dsf.extend_from_slice(&field.to_le_bytes());
+        }
+        dsf.extend_from_slice(&((blocks * DSF_BLOCK * 8) as u64).to_le_bytes());
+        dsf.extend_from_slice(&u32::try_from(DSF_BLOCK).expect("block").to_le_bytes());
+        dsf.extend_from_slice(&0u32.to_le_bytes());
+        dsf.extend_from_slice(b"data");
+        dsf.extend_from_slice(&(12 + data_len).to_le_bytes());
+        // The idle pattern 0x69, stored least significant bit first.
+        dsf.resize(dsf.len() + blocks * DSF_BLOCK * 2, 0x96);
+        std::fs::write(path, dsf).expect("write dsf");
+    }
+
+    fn transcode(dir: &Path, file: &str, encoding: AudioEncoding, start: Duration) -> Vec<u8> {
+        let music_dirs = [dir.to_string_lossy().into_owned()];
+        let mut transcoder = Transcoder::open(file, &music_dirs, encoding, 128, start).expect("open");
+        let mut out = Vec::new();
+        while let Some(chunk) = transcoder.next_chunk().expect("transcode") {
+            out.extend(chunk);
+        }
+        out
+    }
+
+    fn assert_seconds(decoded: &Decoded, expected: f64, tolerance: f64) {
+        let seconds = decoded.seconds();
+        assert!((seconds - expected).abs() <= tolerance, "{seconds}s decoded, {expected}s expected");
+    }
+
+    #[test]
+    fn wav_is_transcoded_from_the_start_offset() {
+        let dir = temp_dir();
+        let samples = wav_samples(3 * WAV_RATE as usize);
+        write_wav(&dir.join("tone.wav"), &samples);
+        let music_dirs = [dir.to_string_lossy().into_owned()];
+        assert_eq!(duration("tone.wav", &music_dirs), Some(Duration::from_secs(3)));
+        let start = Duration::from_millis(1250);
+
+        // Lossless: exactly the samples from the offset on.
+        let flac = readback::decode(transcode(&dir, "tone.wav", AudioEncoding::Flac, start), "flac");
+        assert_eq!((flac.rate, flac.channels), (WAV_RATE, 2));
+        let offset = 2 * WAV_RATE as usize * 5 / 4;
+        let expected: Vec<f32> = samples[offset..].iter().map(|&s| f32::from(s) / 32_768.0).collect();
+        assert_eq!(flac.samples.len(), expected.len());
+        assert!(flac.samples == expected, "FLAC samples differ from the source");
+
+        let mp3 = readback::decode(transcode(&dir, "tone.wav", AudioEncoding::Mp3, start), "mp3");
+        assert_eq!((mp3.rate, mp3.channels), (WAV_RATE, 2));
+        // LAME adds its delay and pads the last frame.
+        assert_seconds(&mp3, 1.75 + 0.04, 0.04);
+
+        let opus = readback::decode_opus(transcode(&dir, "tone.wav", AudioEncoding::Opus, start));
+        assert_eq!((opus.rate, opus.channels), (STREAM_RATE, 2));
+        assert_seconds(&opus, 1.75, 0.005);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn dsd_is_converted_and_transcoded_from_the_start_offset() {
+        let dir = temp_dir();
+        let blocks = 172;
+        write_dsf(&dir.join("idle.dsf"), blocks);
+        let bits = blocks * DSF_BLOCK * 8;
+        #[allow(clippy::cast_precision_loss)]
+        let seconds = bits as f64 / f64::from(DSD64);
+        let music_dirs = [dir.to_string_lossy().into_owned()];
+        let length = duration("idle.dsf", &music_dirs).expect("duration");
+        assert!((length.as_secs_f64() - seconds).abs() < 1e-6);
+        // Seeking lands on a block boundary before 1 s; the rest is skipped.
+        let start = Duration::from_millis(1500);
+
+        let flac = readback::decode(transcode(&dir, "idle.dsf", AudioEncoding::Flac, start), "flac");
+        assert_eq!((flac.rate, flac.channels), (crate::rsp::dsd_pcm::PCM_RATE, 2));
+        let pcm_rate = crate::rsp::dsd_pcm::PCM_RATE as usize;
+        assert_eq!(flac.frames(), bits / 32 - pcm_rate * 3 / 2);
+        assert!(flac.samples.iter().all(|s| s.abs() < 1e-3), "idle pattern converts to silence");
+
+        let opus = readback::decode_opus(transcode(&dir, "idle.dsf", AudioEncoding::Opus, start));
+        assert_eq!((opus.rate, opus.channels), (STREAM_RATE, 2));
+        assert_seconds(&opus, seconds - 1.5, 0.005);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,494 @@
+//! Access control for the HTTP API and the WebSocket (see
+//! `api_models::auth` for roles and `metadata::user_service` for accounts).
+//!
+//! [`guard`] runs in front of every route. It finds the caller — the session
+//! cookie set at login, an `Authorization: Bearer` token, or a `token` query
+//! parameter for players that cannot send headers — and refuses requests
+//! its role is too low for: 401 without valid credentials, 403 with too few
+//! rights. Only the web UI, artwork and `/api/auth/*` are open to anonymous
+//! callers, so the login page can load. The caller rides along as a [`Caller`] extension: `/api/ws`
+//! checks each command against the role its token has at that moment, and
+//! `GET /api/settings` leaves out secrets for non-admins. While access
+//! control is off everyone is an admin. [`routes`] serves login, logout,
+//! user management, API tokens and the list of user profiles.
+//!
+//! Failed logins are throttled per address: after a few, each further try
+//! has to wait twice as long as the one before. Browser pages may call the
+//! API and open the WebSocket only from the server's own origin or one of
+//! `AuthSettings::allowed_origins`, see [`Auth::allows_origin`].
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, FromRef, Path, Request, State},
+    http::{header, uri::Authority, HeaderMap, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use log::{error, info, warn};
+
+use api_models::auth::{
+    ApiTokenInfo, AuthStatus, ChangePasswordRequest, CreateApiTokenRequest, CreatedApiToken, LoginRequest, Role, SaveUserRequest, UserInfo,
+};
+use config::Configuration;
+use metadata::error::RepoError;
+use metadata::user_service::{UserService, SESSION_DAYS};
+
+pub const SESSION_COOKIE: &str = "rsplayer_session";
+/// Failed logins from one address before it has to wait.
+const FREE_LOGIN_ATTEMPTS: u32 = 5;
+/// The first wait; it doubles with every further failure.
+const LOGIN_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_LOGIN_BACKOFF: Duration = Duration::from_mins(15);
+/// Failures older than this are forgotten.
+const FORGET_LOGIN_ATTEMPTS: Duration = Duration::from_hours(1);
+
+#[derive(Clone)]
+pub struct Auth {
+    users: Arc<UserService>,
+    config: Arc<Configuration>,
+    login_attempts: Arc<LoginAttempts>,
+}
+
+/// Marks requests served by the HTTPS listener, whose session cookies are
+/// `Secure`.
+#[derive(Debug, Clone, Copy)]
+pub struct ServedOverTls;
+
+/// Who sent a request. `user` is whoever the credentials belong to, also
+/// while access control is off; `role` is `None` for anonymous callers
+/// while it is on.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user: Option<UserInfo>,
+    pub role: Option<Role>,
+    token: Option<String>,
+}
+
+impl Auth {
+    pub fn new(users: Arc<UserService>, config: Arc<Configuration>) -> Self {
+        Self {
+            users,
+            config,
+            login_attempts: Arc::default(),
+        }
+    }
+
+    /// Enabled in the settings, and there is an admin who can log in.
+    pub fn is_active(&self) -> bool {
+        self.config.get_settings().auth_settings.enabled && self.users.has_admin()
+    }
+
+    fn identify(&self, headers: &HeaderMap, query: Option<&str>) -> Caller {
+        let token = request_token(headers, query);
+        let user = token.as_deref().and_then(|t| self.users.authenticate(t));
+        let role = if self.is_active() {
+            user.as_ref().map(|u| u.role)
+        } else {
+            Some(Role::Admin)
+        };
+        Caller { user, role, token }
+    }
+
+    /// The caller's role now; `None` once its token is no longer valid.
+    /// Long-lived connections check this so that logging out, a revoked
+    /// token or a changed role applies to them too.
+    pub fn current_role(&self, caller: &Caller) -> Option<Role> {
+        if !self.is_active() {
+            return Some(Role::Admin);
+        }
+        let token = caller.token.as_deref()?;
+        self.users.authenticate(token).map(|u| u.role)
+    }
+
+    /// The user profile a WebSocket session runs as: the signed-in user
+    /// while access control is on, otherwise the `requested` one if such a
+    /// user exists. `None` is the shared profile.
+    pub fn session_profile(&self, caller: &Caller, requested: Option<&str>) -> Option<String> {
+        if self.is_active() {
+            return caller.user.as_ref().map(|u| u.name.clone());
+        }
+        let requested = requested?;
+        self.users.get_users().into_iter().map(|u| u.name).find(|name| name == requested)
+    }
+
+    /// Whether a browser page from `origin` may call the API: the server's
+    /// own origin, by whatever host name it was reached, or one of the
+    /// configured `allowed_origins`.
+    pub fn allows_origin(&self, origin: &str, uri: &Uri, headers: &HeaderMap) -> bool {
+        let host = uri
+            .authority()
+            .map(Authority::as_str)
+            .or_else(|| headers.get(header::HOST).and_then(|h| h.to_str().ok()));
+        host.is_some_and(|host| same_origin(origin, host))
+            || self
+                .config
+                .get_settings()
+                .auth_settings
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed.trim().trim_end_matches('/').eq_ignore_ascii_case(origin))
+    }
+}
+
+/// Whether `origin` (`scheme://host[:port]`) names the `host` a request was
+/// sent to.
+fn same_origin(origin: &str, host: &str) -> bool {
+    origin
+        .split_once("://")
+        .is_some_and(|(_, origin_host)| origin_host.eq_ignore_ascii_case(host))
+}
+
+/// Login attempts per address, for the backoff after failed ones.
+#[derive(Debug, Default)]
+struct LoginAttempts {
+    by_address: Mutex<HashMap<IpAddr, Attempts>>,
+}
+
+#[derive(Debug)]
+struct Attempts {
+    count: u32,
+    last: Instant,
+}
+
+impl LoginAttempts {
+    /// Counts an attempt from `address`, or tells how long it still has to
+    /// wait. Attempts count before their outcome is known, so guesses sent
+    /// in parallel are held back too; a successful login clears them.
+    fn attempt(&self, address: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut by_address = self.by_address.lock().expect("login attempts lock poisoned");
+        by_address.retain(|_, a| now.duration_since(a.last) < FORGET_LOGIN_ATTEMPTS);
+        let attempts = by_address.entry(address).or_insert(Attempts { count: 0, last: now });
+        let wait = login_backoff(attempts.count).and_then(|backoff| (attempts.last + backoff).checked_duration_since(now));
+        if let Some(wait) = wait.filter(|w| !w.is_zero()) {
+            return Err(wait);
+        }
+        attempts.count += 1;
+        attempts.last = now;
+        Ok(())
+    }
+
+    fn succeeded(&self, address: IpAddr) {
+        self.by_address.lock().expect("login attempts lock poisoned").remove(&address);
+    }
+}
+
+/// The wait after `failures` failed logins in a row.
+fn login_backoff(failures: u32) -> Option<Duration> {
+    let over = failures.checked_sub(FREE_LOGIN_ATTEMPTS)?;
+    Some(LOGIN_BACKOFF.saturating_mul(1 << over.min(16)).min(MAX_LOGIN_BACKOFF))
+}
+
+/// The role a request needs; `None` for what the login page needs itself.
+fn required_role(method: &Method, path: &str) -> Option<Role> {
+    let role = match path {
+        "/api/settings" if method == Method::GET => Role::Guest,
+        "/api/settings" => Role::Admin,
+        _ if path.starts_with("/api/auth/") => return None,
+        _ if path.starts_with("/api/users") || path.starts_with("/api/albums/") => Role::Admin,
+        _ if path.starts_with("/api/download/") || path.starts_with("/music/") || path == "/stream" => Role::Listener,
+        _ if path.starts_with("/api/") => Role::Guest,
+        _ => return None,
+    };
+    Some(role)
+}
+
+pub async fn guard(State(auth): State<Auth>, mut request: Request, next: Next) -> Response {
+    let caller = auth.identify(request.headers(), request.uri().query());
+    if let Some(required) = required_role(request.method(), request.uri().path()) {
+        if caller.role.is_none_or(|role| role < required) {
+            let status = if caller.role.is_none() {
+                StatusCode::UNAUTHORIZED
+            } else {
+                StatusCode::FORBIDDEN
+            };
+            warn!(
+                "Refused {} {} to {}",
+                request.method(),
+                request.uri().path(),
+                caller.user.as_ref().map_or("anonymous", |u| u.name.as_str())
+            );
+            return status.into_response();
+        }
+    }
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+pub fn routes<S>() -> Router<S>
+where
+    Auth: FromRef<S>,
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/api/auth/status", get(status))
+        .route("/api/auth/login", post(login))
+        .route("/api/auth/logout", post(logout))
+        .route("/api/auth/password", post(change_password))
+        .route("/api/users", get(get_users).post(save_user))
+        .route("/api/users/{name}", delete(delete_user))
+        .route("/api/tokens", get(get_tokens).post(create_token))
+        .route("/api/tokens/{id}", delete(revoke_token))
+        .route("/api/profiles", get(get_profiles))
+}
+
+async fn status(State(auth): State<Auth>, Extension(caller): Extension<Caller>) -> Json<AuthStatus> {
+    Json(AuthStatus {
+        enabled: auth.is_active(),
+        user: caller.user,
+        has_admin: auth.users.has_admin(),
+    })
+}
+
+async fn login(
+    State(auth): State<Auth>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    tls: Option<Extension<ServedOverTls>>,
+    headers: HeaderMap,
+    Json(request): Json<LoginRequest>,
+) -> Response {
+    if let Err(wait) = auth.login_attempts.attempt(peer.ip(), Instant::now()) {
+        warn!("Login as {} from {} refused: too many failed logins", request.name, peer.ip());
+        let secs = wait.as_secs() + 1;
+        let message = format!("Too many failed logins, try again in {secs} seconds");
+        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, secs.to_string())], message).into_response();
+    }
+    // Password hashing takes a noticeable while on purpose.
+    let users = auth.users.clone();
+    let name = request.name.clone();
+    let result = tokio::task::spawn_blocking(move || users.login(&request.name, &request.password)).await;
+    match result {
+        Ok(Ok(Some((token, user)))) => {
+            info!("{} logged in", user.name);
+            auth.login_attempts.succeeded(peer.ip());
+            let cookie = session_cookie(&token, SESSION_DAYS * 24 * 60 * 60, is_secure(tls, &headers));
+            ([(header::SET_COOKIE, cookie)], Json(user)).into_response()
+        }
+        Ok(Ok(None)) => {
+            warn!("Failed login as {name}");
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+        Ok(Err(e)) => repo_error(&e),
+        Err(e) => {
+            error!("Login task failed: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn logout(
+    State(auth): State<Auth>,
+    Extension(caller): Extension<Caller>,
+    tls: Option<Extension<ServedOverTls>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(token) = &caller.token {
+        if let Err(e) = auth.users.logout(token) {
+            return repo_error(&e);
+        }
+    }
+    let cookie = session_cookie("", 0, is_secure(tls, &headers));
+    ([(header::SET_COOKIE, cookie)], StatusCode::NO_CONTENT).into_response()
+}
+
+/// The request came over HTTPS: to the HTTPS listener, or through a proxy
+/// that says so.
+fn is_secure(tls: Option<Extension<ServedOverTls>>, headers: &HeaderMap) -> bool {
+    tls.is_some()
+        || headers
+            .get("x-forwarded-proto")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+}
+
+fn session_cookie(token: &str, max_age_secs: i64, secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age_secs}{secure}")
+}
+
+async fn change_password(
+    State(auth): State<Auth>,
+    Extension(caller): Extension<Caller>,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Response {
+    let Some(user) = caller.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let result = tokio::task::spawn_blocking(move || {
+        auth.users
+            .change_password(&user.name, &request.current_password, &request.new_password)
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => repo_error(&e),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn get_users(State(auth): State<Auth>) -> Json<Vec<UserInfo>> {
+    Json(auth.users.get_users())
+}
+
+/// The names of the user profiles a session can pick; none while access
+/// control is on, where the profile is the signed-in user and the names
+/// are for admins to see.
+async fn get_profiles(State(auth): State<Auth>) -> Json<Vec<String>> {
+    if auth.is_active() {
+        return Json(Vec::new());
+    }
+    Json(auth.users.get_users().into_iter().map(|u| u.name).collect())
+}
+
+async fn save_user(State(auth): State<Auth>, Json(request): Json<SaveUserRequest>) -> Response {
+    let result = tokio::task::spawn_blocking(move || auth.users.save_user(&request)).await;
+    match result {
+        Ok(Ok(user)) => Json(user).into_response(),
+        Ok(Err(e)) => repo_error(&e),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn delete_user(State(auth): State<Auth>, Path(name): Path<String>) -> Response {
+    let result = tokio::task::spawn_blocking(move || auth.users.delete_user(&name)).await;
+    match result {
+        Ok(Ok(true)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok(false)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Err(e)) => repo_error(&e),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// The caller's own API tokens; everyone's for admins.
+async fn get_tokens(State(auth): State<Auth>, Extension(caller): Extension<Caller>) -> Json<Vec<ApiTokenInfo>> {
+    Json(auth.users.get_api_tokens(token_owner(&caller)))
+}
+
+async fn create_token(
+    State(auth): State<Auth>,
+    Extension(caller): Extension<Caller>,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> Result<Json<CreatedApiToken>, Response> {
+    // A token acts as its user, so there has to be one.
+    let Some(user) = caller.user else {
+        return Err(StatusCode::UNAUTHORIZED.into_response());
+    };
+    auth.users
+        .create_api_token(&user.name, &request.name)
+        .map(Json)
+        .map_err(|e| repo_error(&e))
+}
+
+async fn revoke_token(State(auth): State<Auth>, Extension(caller): Extension<Caller>, Path(id): Path<String>) -> Response {
+    match auth.users.revoke_api_token(&id, token_owner(&caller)) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => repo_error(&e),
+    }
+}
+
+/// Whose tokens the caller manages: everyone's for admins.
+fn token_owner(caller: &Caller) -> Option<&str> {
+    if caller.role == Some(Role::Admin) {
+        return None;
+    }
+    // Only admins get this far without an account.
+    Some(caller.user.as_ref().map_or("", |u| u.name.as_str()))
+}
+
+fn repo_error(e: &RepoError) -> Response {
+    if let RepoError::Invalid(message) = e {
+        return (StatusCode::BAD_REQUEST, message.clone()).into_response();
+    }
+    error!("Access control storage failed: {e}");
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// The session cookie, a bearer token or the `token` query parameter.
+fn request_token(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let cookie = || {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .find_map(|c| c.trim().strip_prefix(SESSION_COOKIE)?.strip_prefix('='))
+    };
+    let param = || query?.split('&').find_map(|p| p.strip_prefix("token="));
+    bearer
+        .or_else(cookie)
+        .or_else(param)
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_need_the_expected_roles() {
+        assert_eq!(required_role(&Method::GET, "/"), None);
+        assert_eq!(required_role(&Method::GET, "/artwork/thumbs/a.jpg"), None);
+        assert_eq!(required_role(&Method::POST, "/api/auth/login"), None);
+        assert_eq!(required_role(&Method::GET, "/api/ws"), Some(Role::Guest));
+        assert_eq!(required_role(&Method::GET, "/api/settings"), Some(Role::Guest));
+        assert_eq!(required_role(&Method::POST, "/api/settings"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::DELETE, "/api/users/kid"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::POST, "/api/albums/a/artwork"), Some(Role::Admin));
+        assert_eq!(required_role(&Method::GET, "/music/a/b.flac"), Some(Role::Listener));
+        assert_eq!(required_role(&Method::GET, "/stream"), Some(Role::Listener));
+        assert_eq!(required_role(&Method::GET, "/api/download/queue"), Some(Role::Listener));
+        assert_eq!(required_role(&Method::GET, "/api/tokens"), Some(Role::Guest));
+        assert_eq!(required_role(&Method::GET, "/api/profiles"), Some(Role::Guest));
+    }
+
+    #[test]
+    fn tokens_come_from_header_cookie_or_query() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_token(&headers, Some("format=mp3&token=abc")), Some("abc".to_owned()));
+        headers.insert(header::COOKIE, "theme=dark; rsplayer_session=fromcookie".parse().expect("header"));
+        assert_eq!(request_token(&headers, Some("token=abc")), Some("fromcookie".to_owned()));
+        headers.insert(header::AUTHORIZATION, "Bearer frombearer".parse().expect("header"));
+        assert_eq!(request_token(&headers, None), Some("frombearer".to_owned()));
+        assert_eq!(request_token(&HeaderMap::new(), Some("t=1")), None);
+    }
+
+    #[test]
+    fn only_the_own_origin_is_the_same() {
+        assert!(same_origin("http://rsplayer.local:8000", "rsplayer.local:8000"));
+        assert!(same_origin("https://RSPlayer.local", "rsplayer.local"));
+        assert!(!same_origin("http://rsplayer.local:8001", "rsplayer.local:8000"));
+        assert!(!same_origin("http://evil.example", "rsplayer.local"));
+        assert!(!same_origin("null", "rsplayer.local"));
+    }
+
+    #[test]
+    fn failed_logins_back_off() {
+        let attempts = LoginAttempts::default();
+        let address = IpAddr::from([192, 168, 1, 20]);
+        let start = Instant::now();
+        for _ in 0..FREE_LOGIN_ATTEMPTS {
+            assert_eq!(attempts.attempt(address, start), Ok(()));
+        }
+        assert_eq!(attempts.attempt(address, start), Err(LOGIN_BACKOFF));
+        // Another address is not held back.
+        assert_eq!(attempts.attempt(IpAddr::from([192, 168, 1, 21]), start), Ok(()));
+        let later = start + LOGIN_BACKOFF;
+        assert_eq!(attempts.attempt(address, later), Ok(()));
+        assert_eq!(attempts.attempt(address, later), Err(LOGIN_BACKOFF * 2));
+        attempts.succeeded(address);
+        assert_eq!(attempts.attempt(address, later), Ok(()));
+        assert_eq!(login_backoff(100), Some(MAX_LOGIN_BACKOFF));
+    }
+}
@@ -1,12 +1,14 @@
 //! [`CommandContext`] — the service bundle passed to every command handler,
 //! plus the `multiroom_follower_active` flag that locks local transport
-//! while grouped.
+//! while grouped — and [`BackgroundJob`], for commands that run long on
+//! their own thread.
 
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use tokio::sync::broadcast::Sender;
 
+use api_models::auth::Role;
 use api_models::state::StateChangeEvent;
 use config::ArcConfiguration;
 use hardware::audio_device::audio_service::ArcAudioInterfaceSvc;
@@ -15,8 +17,10 @@ use metadata::metadata_service::MetadataService;
 use metadata::playlist_service::PlaylistService;
 use metadata::ports::{
     album_repository::ArcAlbumRepository, loudness_repository::ArcLoudnessRepository, song_repository::ArcSongRepository,
+    work_repository::ArcWorkRepository,
 };
 use metadata::queue_service::QueueService;
+use metadata::radio_station_service::RadioStationService;
 use playback::rsp::player_service::PlayerService;
 
 pub struct CommandContext {
@@ -24,13 +28,21 @@ pub struct CommandContext {
     pub metadata_service: Arc<MetadataService>,
     pub playlist_service: Arc<PlaylistService>,
     pub queue_service: Arc<QueueService>,
+    pub radio_station_service: Arc<RadioStationService>,
     pub album_repository: ArcAlbumRepository,
+    pub work_repository: ArcWorkRepository,
     pub song_repository: ArcSongRepository,
     pub loudness_repository: ArcLoudnessRepository,
     pub config_store: ArcConfiguration,
     /// True while this instance plays as a grouped multiroom follower.
     pub multiroom_follower_active: Arc<AtomicBool>,
     pub state_changes_sender: Sender<StateChangeEvent>,
+    /// The user profile the command being handled runs for; `None` is the
+    /// shared profile.
+    pub profile: Option<String>,
+    /// The role of whoever sent the command; local input (USB, IR) counts
+    /// as an admin.
+    pub role: Role,
 }
 
 impl CommandContext {
@@ -40,7 +52,9 @@ impl CommandContext {
         metadata_service: Arc<MetadataService>,
         playlist_service: Arc<PlaylistService>,
         queue_service: Arc<QueueService>,
+        radio_station_service: Arc<RadioStationService>,
         album_repository: ArcAlbumRepository,
+        work_repository: ArcWorkRepository,
         song_repository: ArcSongRepository,
         loudness_repository: ArcLoudnessRepository,
         config_store: ArcConfiguration,
@@ -52,12 +66,16 @@ impl CommandContext {
             metadata_service,
             playlist_service,
             queue_service,
+            radio_station_service,
             album_repository,
+            work_repository,
             song_repository,
             loudness_repository,
             config_store,
             multiroom_follower_active,
             state_changes_sender,
+            profile: None,
+            role: Role::Guest,
         }
     }
 
@@ -65,6 +83,11 @@ impl CommandContext {
         let _ = self.state_changes_sender.send(event);
     }
 
+    /// Sends `event` only to the sessions of the command's profile.
+    pub fn send_profile_event(&self, event: StateChangeEvent) {
+        self.send_event(StateChangeEvent::ProfileEvent(self.profile.clone(), Box::new(event)));
+    }
+
     pub fn send_notification(&self, message: &str) {
         let _ = self
             .state_changes_sender
@@ -110,3 +133,54 @@ impl SystemCommandContext {
         let _ = self.state_changes_sender.send(event);
     }
 }
+
+/// A command that runs on its own named thread, one run at a time. Kept in a
+/// `static` next to the command's handler.
+pub struct BackgroundJob {
+    thread_name: &'static str,
+    busy_message: &'static str,
+    running: AtomicBool,
+}
+
+impl BackgroundJob {
+    pub const fn new(thread_name: &'static str, busy_message: &'static str) -> Self {
+        Self {
+            thread_name,
+            busy_message,
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs `job` on a new thread and sends the events it returns, or sends
+    /// the busy message when the previous run has not finished yet.
+    pub fn spawn<F>(&'static self, ctx: &CommandContext, job: F)
+    where
+        F: FnOnce() -> Vec<StateChangeEvent> + Send + 'static,
+    {
+        if self.running.swap(true, Ordering::AcqRel) {
+            ctx.send_error(self.busy_message);
+            return;
+        }
+        let sender = ctx.state_changes_sender.clone();
+        std::thread::Builder::new()
+            .name(self.thread_name.to_string())
+            .spawn(move || {
+                let running = Running(&self.running);
+                let events = job();
+                drop(running);
+                for event in events {
+                    let _ = sender.send(event);
+                }
+            })
+            .unwrap_or_else(|e| panic!("Failed to start {} thread: {e}", self.thread_name));
+    }
+}
+
+/// Marks a [`BackgroundJob`] finished when dropped, also if it panicked.
+struct Running(&'static AtomicBool);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
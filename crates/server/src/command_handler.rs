@@ -1,7 +1,11 @@
 //! Command dispatch.
 //!
-//! One task drains the `UserCommand` mpsc channel and routes each command
+//! One task drains the `UserCommand` mpsc channels and routes each command
 //! to its domain handler (`*_commands.rs`), all sharing [`CommandContext`].
+//! Commands from WebSocket sessions arrive as [`ProfileCommand`]s and run
+//! for the session's user profile, the scheduler's for the profile that
+//! saved the schedule; the rest (USB, IR) run for the shared profile. Whoever last controlled playback is the profile listening,
+//! and anyone but the scheduler controlling it cancels a scheduled fade-in.
 //! A second task handles `SystemCommand`s (volume, power) against the
 //! hardware audio service. Sequential by design — one command at a time,
 //! state flows back via broadcast events.
@@ -15,13 +19,16 @@ use metadata::playlist_service::PlaylistService;
 use metadata::ports::album_repository::ArcAlbumRepository;
 use metadata::ports::loudness_repository::ArcLoudnessRepository;
 use metadata::ports::song_repository::ArcSongRepository;
+use metadata::ports::work_repository::ArcWorkRepository;
 use metadata::queue_service::QueueService;
+use metadata::radio_station_service::RadioStationService;
 use playback::rsp::player_service::PlayerService;
 use tokio::sync::broadcast::Sender;
 use tokio::sync::mpsc::{self, Receiver};
 
+use api_models::auth::Role;
 use api_models::common::SystemCommand;
-use api_models::common::UserCommand::{self, Metadata, Multiroom, Player, Playlist, Queue, Storage, System, UpdateDsp};
+use api_models::common::UserCommand::{self, Metadata, Multiroom, Player, Playlist, Queue, Scheduler, Storage, System, UpdateDsp};
 use api_models::state::StateChangeEvent;
 
 use crate::command_context::{CommandContext, SystemCommandContext};
@@ -29,31 +36,49 @@ use crate::metadata_commands::handle_metadata_command;
 use crate::player_commands::handle_player_command;
 use crate::playlist_commands::handle_playlist_command;
 use crate::queue_commands::handle_queue_command;
+use crate::scheduler::FadeIn;
+use crate::scheduler_commands::handle_scheduler_command;
 use crate::storage_commands::handle_storage_command;
 use crate::system_commands::handle_system_command;
 
+/// A command from a WebSocket session, with the user profile it runs for
+/// (`None` is the shared profile) and the role of whoever sent it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileCommand {
+    pub profile: Option<String>,
+    pub role: Role,
+    pub command: UserCommand,
+}
+
 #[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 pub async fn handle_user_commands(
     player_service: Arc<PlayerService>,
     metadata_service: Arc<MetadataService>,
     playlist_service: Arc<PlaylistService>,
     queue_service: Arc<QueueService>,
+    radio_station_service: Arc<RadioStationService>,
     album_repository: ArcAlbumRepository,
+    work_repository: ArcWorkRepository,
     song_repository: ArcSongRepository,
     loudness_repository: ArcLoudnessRepository,
     config_store: ArcConfiguration,
     mut input_commands_rx: Receiver<UserCommand>,
+    mut profile_commands_rx: Receiver<ProfileCommand>,
+    mut scheduler_commands_rx: Receiver<ProfileCommand>,
+    fade_in: Arc<FadeIn>,
     system_commands_tx: mpsc::Sender<SystemCommand>,
     multiroom_commands_tx: mpsc::Sender<api_models::common::MultiroomCommand>,
     multiroom_follower_active: std::sync::Arc<std::sync::atomic::AtomicBool>,
     state_changes_sender: Sender<StateChangeEvent>,
 ) {
-    let ctx = CommandContext::new(
+    let mut ctx = CommandContext::new(
         player_service,
         metadata_service,
         playlist_service,
         queue_service,
+        radio_station_service,
         album_repository,
+        work_repository,
         song_repository,
         loudness_repository,
         config_store,
@@ -63,8 +88,22 @@ pub async fn handle_user_commands(
 
     // recv() returns None only when every sender is gone — the process is
     // shutting down, so exit rather than spin on a closed channel.
-    while let Some(cmd) = input_commands_rx.recv().await {
-        debug!("Received command {cmd:?}");
+    loop {
+        let (profile, role, cmd, scheduled) = tokio::select! {
+            Some(cmd) = input_commands_rx.recv() => (None, Role::Admin, cmd, false),
+            Some(ProfileCommand { profile, role, command }) = profile_commands_rx.recv() => (profile, role, command, false),
+            Some(ProfileCommand { profile, role, command }) = scheduler_commands_rx.recv() => (profile, role, command, true),
+            else => break,
+        };
+        debug!("Received command {cmd:?} for profile {profile:?}");
+        if matches!(cmd, Player(_) | Queue(_)) && cmd.required_role() > Role::Guest {
+            ctx.metadata_service.set_listening_profile(profile.clone());
+            if !scheduled {
+                fade_in.cancel();
+            }
+        }
+        ctx.profile = profile;
+        ctx.role = role;
         match cmd {
             Player(player_cmd) => {
                 handle_player_command(player_cmd, &ctx);
@@ -81,6 +120,9 @@ pub async fn handle_user_commands(
             Storage(storage_cmd) => {
                 handle_storage_command(storage_cmd, &ctx);
             }
+            Scheduler(scheduler_cmd) => {
+                handle_scheduler_command(scheduler_cmd, &ctx);
+            }
             UpdateDsp(dsp_settings) => {
                 ctx.player_service.update_dsp_settings(&dsp_settings);
                 let mut settings = ctx.config_store.get_settings();
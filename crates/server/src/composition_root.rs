@@ -18,6 +18,7 @@ use config::ArcConfiguration;
 use hardware::audio_device::audio_service::{ArcAudioInterfaceSvc, AudioInterfaceService};
 use hardware::usb::{ArcUsbService, UsbService};
 use metadata::album_repository::FjallAlbumRepository;
+use metadata::bookmark_service::BookmarkService;
 use metadata::loudness_repository::FjallLoudnessRepository;
 use metadata::loudness_service::LoudnessService;
 use metadata::metadata_service::MetadataService;
@@ -25,13 +26,19 @@ use metadata::play_statistic_repository::FjallPlayStatisticsRepository;
 use metadata::playlist_service::PlaylistService;
 use metadata::ports::{
     album_repository::ArcAlbumRepository, loudness_repository::ArcLoudnessRepository,
-    play_statistics_repository::ArcPlayStatisticsRepository, song_repository::ArcSongRepository,
+    play_statistics_repository::ArcPlayStatisticsRepository, song_repository::ArcSongRepository, work_repository::ArcWorkRepository,
 };
 use metadata::queue_service::QueueService;
+use metadata::radio_station_repository::FjallRadioStationRepository;
+use metadata::radio_station_service::RadioStationService;
 use metadata::song_repository::FjallSongRepository;
+use metadata::work_repository::FjallWorkRepository;
 use playback::rsp::player_service::PlayerService;
 use playback::rsp::tee::{SyncTee, TeeEvent};
 
+use crate::command_handler::ProfileCommand;
+use crate::scheduler::FadeIn;
+
 pub struct ChannelPair<T> {
     pub tx: mpsc::Sender<T>,
     pub rx: mpsc::Receiver<T>,
@@ -51,11 +58,13 @@ impl<T> ChannelPair<T> {
 pub struct AppContainer {
     pub song_repository: ArcSongRepository,
     pub album_repository: ArcAlbumRepository,
+    pub work_repository: ArcWorkRepository,
     pub loudness_repository: ArcLoudnessRepository,
 
     pub metadata_service: Arc<MetadataService>,
     pub playlist_service: Arc<PlaylistService>,
     pub queue_service: Arc<QueueService>,
+    pub radio_station_service: Arc<RadioStationService>,
     pub player_service: Arc<PlayerService>,
 
     pub audio_service: ArcAudioInterfaceSvc,
@@ -63,11 +72,18 @@ pub struct AppContainer {
 
     pub state_changes_tx: broadcast::Sender<StateChangeEvent>,
     pub user_commands: ChannelPair<UserCommand>,
+    /// Commands from WebSocket sessions, with their user profile.
+    pub profile_commands: ChannelPair<ProfileCommand>,
+    /// Commands from the scheduler, for the profile of their schedule; kept
+    /// apart so they don't cancel its own fade-ins.
+    pub scheduler_commands: ChannelPair<ProfileCommand>,
     pub system_commands: ChannelPair<SystemCommand>,
     pub multiroom_commands: ChannelPair<MultiroomCommand>,
     /// True while this instance plays as a grouped multiroom follower;
     /// local transport commands are rejected while set.
     pub multiroom_follower_active: Arc<AtomicBool>,
+    /// The running scheduled fade-in, cancelled when playback is taken over.
+    pub fade_in: Arc<FadeIn>,
     /// Audio-side multiroom plumbing — `Some` when multiroom is enabled.
     pub multiroom: Option<MultiroomParts>,
 }
@@ -88,24 +104,38 @@ pub enum BuildOutcome {
 pub fn build_app_container(config: &ArcConfiguration, shared_db: &Arc<fjall::Database>) -> BuildOutcome {
     let song_repository: ArcSongRepository = Arc::new(FjallSongRepository::new(shared_db));
     let album_repository: ArcAlbumRepository = Arc::new(FjallAlbumRepository::new(shared_db));
+    let work_repository: ArcWorkRepository = Arc::new(FjallWorkRepository::new(shared_db));
     let play_statistics_repository: ArcPlayStatisticsRepository = Arc::new(FjallPlayStatisticsRepository::new(shared_db));
+    let profile_statistics_repository: ArcPlayStatisticsRepository = Arc::new(FjallPlayStatisticsRepository::profiles(shared_db));
     let loudness_repository: ArcLoudnessRepository = Arc::new(FjallLoudnessRepository::new(shared_db));
 
+    let playlist_service = PlaylistService::new(shared_db);
+    info!("Playlist service successfully created.");
+
     let metadata_service = MetadataService::new(
         shared_db.clone(),
         &config.get_settings().metadata_settings,
         song_repository.clone(),
         album_repository.clone(),
+        work_repository.clone(),
         play_statistics_repository.clone(),
+        profile_statistics_repository,
+        playlist_service.clone(),
     )
     .expect("Failed to start metadata service");
 
     info!("Metadata service successfully created.");
 
-    let playlist_service = PlaylistService::new(shared_db);
-    info!("Playlist service successfully created.");
+    let bookmark_service = BookmarkService::new(shared_db);
+    let radio_station_service = RadioStationService::new(Arc::new(FjallRadioStationRepository::new(shared_db)));
 
-    let queue_service = QueueService::new(shared_db, song_repository.clone(), play_statistics_repository.clone());
+    let queue_service = QueueService::new(
+        shared_db,
+        song_repository.clone(),
+        play_statistics_repository.clone(),
+        loudness_repository.clone(),
+    );
+    queue_service.set_auto_continue(config.get_settings().playback_queue_settings.auto_continue);
     info!("Queue service successfully created.");
 
     let usb_settings = config.get_settings().usb_settings;
@@ -134,15 +164,19 @@ pub fn build_app_container(config: &ArcConfiguration, shared_db: &Arc<fjall::Dat
     info!("Audio interface service successfully created.");
 
     let user_commands = ChannelPair::<UserCommand>::new(5);
+    let profile_commands = ChannelPair::<ProfileCommand>::new(5);
+    let scheduler_commands = ChannelPair::<ProfileCommand>::new(5);
     let system_commands = ChannelPair::<SystemCommand>::new(5);
     let multiroom_commands = ChannelPair::<MultiroomCommand>::new(16);
     let multiroom_follower_active = Arc::new(AtomicBool::new(false));
+    let fade_in = Arc::new(FadeIn::default());
     let (state_changes_tx, _) = broadcast::channel(64);
 
     let loudness_service = LoudnessService::new(
         loudness_repository.clone(),
         song_repository.clone(),
-        config.get_settings().metadata_settings.effective_directories(),
+        album_repository.clone(),
+        config.get_settings().metadata_settings.library_directories(),
     );
     if config.get_settings().rs_player_settings.loudness_normalization_enabled {
         loudness_service.start();
@@ -168,6 +202,8 @@ pub fn build_app_container(config: &ArcConfiguration, shared_db: &Arc<fjall::Dat
         state_changes_tx.clone(),
         loudness_service,
         sync_tee.clone(),
+        bookmark_service,
+        system_commands.tx.clone(),
     );
     info!("Player service successfully created.");
 
@@ -195,18 +231,23 @@ pub fn build_app_container(config: &ArcConfiguration, shared_db: &Arc<fjall::Dat
     BuildOutcome::Ready(Box::new(AppContainer {
         song_repository,
         album_repository,
+        work_repository,
         loudness_repository,
         metadata_service,
         playlist_service,
         queue_service,
+        radio_station_service,
         player_service,
         audio_service,
         usb_service,
         state_changes_tx,
         user_commands,
+        profile_commands,
+        scheduler_commands,
         system_commands,
         multiroom_commands,
         multiroom_follower_active,
+        fade_in,
         multiroom,
     }))
 }
@@ -214,6 +255,7 @@ pub fn build_app_container(config: &ArcConfiguration, shared_db: &Arc<fjall::Dat
 #[cfg(test)]
 mod tests {
     use super::*;
+    use api_models::auth::Role;
     use api_models::common::PlayerCommand;
     use config::Configuration;
     use tempfile::TempDir;
@@ -236,6 +278,20 @@ mod tests {
         let received = container.user_commands.rx.recv().await.expect("rx closed");
         assert_eq!(received, cmd);
 
+        let profile_cmd = ProfileCommand {
+            profile: Some("anna".to_owned()),
+            role: Role::Listener,
+            command: cmd,
+        };
+        container
+            .profile_commands
+            .tx
+            .send(profile_cmd.clone())
+            .await
+            .expect("send profile command");
+        let received_profile = container.profile_commands.rx.recv().await.expect("rx closed");
+        assert_eq!(received_profile, profile_cmd);
+
         let sys_cmd = SystemCommand::PowerOff;
         container
             .system_commands
@@ -0,0 +1,404 @@
+//! Album, playlist and queue downloads: `/api/download/*` streams the
+//! tracks as a ZIP ([`ZipStream`]) while they are read, so large box sets
+//! need no temporary space.
+//!
+//! Entries are named `track - title` for albums (`disc-track - title` when
+//! the album has several discs) and `position - artist - title` for
+//! playlists and the queue. With `format=mp3` or `format=opus` (and an
+//! optional `bitrate`) every track is transcoded the way browser playback
+//! transcodes it; SACD ISO tracks, which have no file of their own, always
+//! are, to FLAC unless a format is given. A track that cannot be opened is
+//! left out; a read error aborts the download so it is not mistaken for a
+//! complete archive.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{format_err, Result};
+use axum::body::{Body, Bytes};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use api_models::player::Song;
+use api_models::settings::{AudioEncoding, Settings};
+use metadata::playlist_service::PlaylistService;
+use metadata::ports::{album_repository::ArcAlbumRepository, song_repository::ArcSongRepository};
+use metadata::queue_service::QueueService;
+use metadata::sacd_bundle::SACD_TRACK_MARKER;
+use playback::rsp::transcoder::Transcoder;
+
+use crate::zip_stream::ZipStream;
+
+/// Archive chunks buffered before reading waits for the client.
+const CHUNK_BUFFER: usize = 8;
+const READ_CHUNK: usize = 256 * 1024;
+/// Longest entry name kept, in bytes, leaving room for the extension.
+const MAX_NAME: usize = 200;
+
+pub enum DownloadSource {
+    Album(String),
+    /// A playlist, if the profile (`None` is the shared one) sees it.
+    Playlist(String, Option<String>),
+    Queue,
+}
+
+#[derive(Clone)]
+pub struct Downloads {
+    song_repository: ArcSongRepository,
+    album_repository: ArcAlbumRepository,
+    playlist_service: Arc<PlaylistService>,
+    queue_service: Arc<QueueService>,
+}
+
+/// One archive entry: which library file goes in under what name.
+#[derive(Debug, PartialEq, Eq)]
+struct EntryPlan {
+    file: String,
+    name: String,
+    transcode: Option<AudioEncoding>,
+    modified: DateTime<Utc>,
+}
+
+impl Downloads {
+    #[must_use]
+    pub fn new(
+        song_repository: ArcSongRepository,
+        album_repository: ArcAlbumRepository,
+        playlist_service: Arc<PlaylistService>,
+        queue_service: Arc<QueueService>,
+    ) -> Self {
+        Self {
+            song_repository,
+            album_repository,
+            playlist_service,
+            queue_service,
+        }
+    }
+
+    /// Streams `source` as a ZIP; 404 when it does not exist or has no
+    /// library tracks.
+    pub async fn respond(&self, source: DownloadSource, query: &HashMap<String, String>, settings: &Settings) -> Response {
+        let downloads = self.clone();
+        let format = query.get("format").and_then(|f| AudioEncoding::parse_loose(f));
+        let listed = tokio::task::spawn_blocking(move || {
+            downloads
+                .list(&source)
+                .map(|(name, songs, album)| (name, plan_entries(&songs, album, format)))
+        })
+        .await;
+        let Ok(Some((name, entries))) = listed else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        if entries.is_empty() {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        let bitrate_kbps = query
+            .get("bitrate")
+            .and_then(|b| b.parse::<u32>().ok())
+            .unwrap_or(settings.transcode_settings.bitrate_kbps)
+            .clamp(32, 320);
+        let music_dirs = settings.metadata_settings.library_directories();
+        info!("Downloading {name} ({} tracks)", entries.len());
+
+        let (body_tx, body_rx) = mpsc::channel::<io::Result<Bytes>>(CHUNK_BUFFER);
+        tokio::task::spawn_blocking(move || write_archive(entries, &music_dirs, bitrate_kbps, &body_tx));
+
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/zip")
+            .header(header::CONTENT_DISPOSITION, content_disposition(&format!("{name}.zip")))
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from_stream(ReceiverStream::new(body_rx)))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+    }
+
+    /// The archive name, the songs in order, and whether they are an album.
+    fn list(&self, source: &DownloadSource) -> Option<(String, Vec<Song>, bool)> {
+        match source {
+            DownloadSource::Album(album_id) => {
+                let album = self.album_repository.find_by_id(album_id)?;
+                let songs = album
+                    .song_keys
+                    .iter()
+                    .filter_map(|key| self.song_repository.find_by_id(key))
+                    .collect();
+                let name = match &album.artist {
+                    Some(artist) if !artist.is_empty() => format!("{artist} - {}", album.title),
+                    _ => album.title,
+                };
+                Some((name, songs, true))
+            }
+            DownloadSource::Playlist(playlist_id, profile) => {
+                if !self.playlist_service.is_visible_to(playlist_id, profile.as_deref()) {
+                    return None;
+                }
+                let playlist = self.playlist_service.get_playlist(playlist_id)?;
+                Some((playlist.name, self.playlist_service.get_all_items(playlist_id), false))
+            }
+            DownloadSource::Queue => Some(("Queue".to_owned(), self.queue_service.get_all_songs(), false)),
+        }
+    }
+}
+
+/// Names the entries for `songs`, leaving out streams, which are not
+/// library files.
+fn plan_entries(songs: &[Song], album: bool, format: Option<AudioEncoding>) -> Vec<EntryPlan> {
+    let songs: Vec<&Song> = songs.iter().filter(|s| !s.file.starts_with("http")).collect();
+    let discs: HashSet<u32> = songs.iter().filter_map(|s| leading_number(s.disc.as_deref())).collect();
+    let width = songs.len().to_string().len().max(2);
+    let mut taken = HashSet::new();
+    songs
+        .iter()
+        .enumerate()
+        .map(|(index, song)| {
+            let position = index + 1;
+            let prefix = if album {
+                let track = leading_number(song.track.as_deref()).map_or(position, |t| t as usize);
+                match leading_number(song.disc.as_deref()) {
+                    Some(disc) if discs.len() > 1 => format!("{disc}-{track:02}"),
+                    _ => format!("{track:02}"),
+                }
+            } else {
+                format!("{position:0width$}")
+            };
+            let title = song.title.clone().filter(|t| !t.trim().is_empty()).unwrap_or_else(|| {
+                let file_name = song.get_file_name_without_path();
+                file_name.rsplit_once('.').map_or(file_name.as_str(), |(stem, _)| stem).to_owned()
+            });
+            let stem = match &song.artist {
+                Some(artist) if !album && !artist.is_empty() => format!("{prefix} - {artist} - {title}"),
+                _ => format!("{prefix} - {title}"),
+            };
+            let transcode = format.or_else(|| song.file.contains(SACD_TRACK_MARKER).then_some(AudioEncoding::Flac));
+            let extension = match transcode {
+                Some(encoding) => encoding.extension().to_owned(),
+                None => song
+                    .file
+                    .rsplit_once('.')
+                    .map(|(_, ext)| ext.to_ascii_lowercase())
+                    .unwrap_or_default(),
+            };
+            let stem = sanitize(&stem);
+            let mut name = format!("{stem}.{extension}");
+            let mut copy = 1;
+            while !taken.insert(name.clone()) {
+                copy += 1;
+                name = format!("{stem} ({copy}).{extension}");
+            }
+            EntryPlan {
+                file: song.file.clone(),
+                name,
+                transcode,
+                modified: song.file_date,
+            }
+        })
+        .collect()
+}
+
+/// Sends the archive through `body_tx`, until done or the client leaves.
+fn write_archive(entries: Vec<EntryPlan>, music_dirs: &[String], bitrate_kbps: u32, body_tx: &mpsc::Sender<io::Result<Bytes>>) {
+    let send = |bytes: Vec<u8>| body_tx.blocking_send(Ok(Bytes::from(bytes))).is_ok();
+    let mut zip = ZipStream::default();
+    for entry in entries {
+        let mut source = match EntrySource::open(&entry, music_dirs, bitrate_kbps) {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Leaving {} out of the download: {e}", entry.file);
+                continue;
+            }
+        };
+        if !send(zip.begin(&entry.name, entry.modified, source.is_large())) {
+            return;
+        }
+        let ended = loop {
+            match source.next_chunk() {
+                Ok(Some(chunk)) => {
+                    zip.update(&chunk);
+                    if !send(chunk) {
+                        return;
+                    }
+                }
+                Ok(None) => break zip.end(),
+                Err(e) => break Err(e),
+            }
+        };
+        match ended {
+            Ok(descriptor) => {
+                if !send(descriptor) {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!("Download aborted at {}: {e}", entry.file);
+                let _ = body_tx.blocking_send(Err(io::Error::other(e.to_string())));
+                return;
+            }
+        }
+    }
+    send(zip.finish());
+}
+
+enum EntrySource {
+    File { file: File, size: u64 },
+    Transcoded(Box<Transcoder>),
+}
+
+impl EntrySource {
+    fn open(entry: &EntryPlan, music_dirs: &[String], bitrate_kbps: u32) -> Result<Self> {
+        if let Some(encoding) = entry.transcode {
+            let transcoder = Transcoder::open(&entry.file, music_dirs, encoding, bitrate_kbps, std::time::Duration::ZERO)?;
+            return Ok(Self::Transcoded(Box::new(transcoder)));
+        }
+        let path = music_dirs
+            .iter()
+            .map(|dir| PathBuf::from(dir).join(&entry.file))
+            .find(|p| p.is_file())
+            .ok_or_else(|| format_err!("not found in the music directories"))?;
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self::File { file, size })
+    }
+
+    /// Whether the entry needs ZIP64 sizes. Transcoded tracks never get near
+    /// 4 GiB.
+    const fn is_large(&self) -> bool {
+        match self {
+            Self::File { size, .. } => *size >= u32::MAX as u64,
+            Self::Transcoded(_) => false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::File { file, .. } => {
+                let mut chunk = vec![0; READ_CHUNK];
+                let read = file.read(&mut chunk)?;
+                chunk.truncate(read);
+                Ok((read > 0).then_some(chunk))
+            }
+            Self::Transcoded(transcoder) => transcoder.next_chunk(),
+        }
+    }
+}
+
+/// The leading number of a track or disc tag such as `3/12`.
+fn leading_number(tag: Option<&str>) -> Option<u32> {
+    let tag = tag?.trim();
+    let digits = tag.find(|c: char| !c.is_ascii_digit()).unwrap_or(tag.len());
+    tag[..digits].parse().ok()
+}
+
+/// A file name safe on every system the archive may be unpacked on.
+fn sanitize(name: &str) -> String {
+    let mut clean: String = name
+        .chars()
+        .map(|c| if c.is_control() || r#"/\:*?"<>|"#.contains(c) { '_' } else { c })
+        .collect();
+    if clean.len() > MAX_NAME {
+        let mut end = MAX_NAME;
+        while !clean.is_char_boundary(end) {
+            end -= 1;
+        }
+        clean.truncate(end);
+    }
+    clean.trim_end_matches(['.', ' ']).trim_start().to_owned()
+}
+
+/// `attachment` with an ASCII fallback name and the UTF-8 one.
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            ' ' => c,
+            _ if c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+        utf8_percent_encode(file_name, NON_ALPHANUMERIC)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(file: &str, title: Option<&str>, track: Option<&str>, disc: Option<&str>) -> Song {
+        Song {
+            file: file.to_owned(),
+            title: title.map(ToOwned::to_owned),
+            track: track.map(ToOwned::to_owned),
+            disc: disc.map(ToOwned::to_owned),
+            artist: Some("Artist".to_owned()),
+            ..Song::default()
+        }
+    }
+
+    fn names(entries: &[EntryPlan]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn album_entries_use_disc_and_track_numbers() {
+        let songs = [
+            song("a/1-01.flac", Some("Intro"), Some("1/9"), Some("1")),
+            song("a/2-03.FLAC", Some("What? / Why"), Some("3"), Some("2/2")),
+            song("a/untagged.ape", None, None, Some("2")),
+        ];
+        let entries = plan_entries(&songs, true, None);
+        assert_eq!(
+            names(&entries),
+            ["1-01 - Intro.flac", "2-03 - What_ _ Why.flac", "2-03 - untagged.ape"]
+        );
+        assert!(entries.iter().all(|e| e.transcode.is_none()));
+
+        let single_disc = plan_entries(&songs[..1], true, None);
+        assert_eq!(names(&single_disc), ["01 - Intro.flac"]);
+    }
+
+    #[test]
+    fn list_entries_use_positions_and_skip_streams() {
+        let songs = [
+            song("a/x.flac", Some("Same"), Some("7"), None),
+            song("http://radio.example/stream", Some("Radio"), None, None),
+            song("b/y.flac", Some("Same"), Some("7"), None),
+            song("c/disc.iso#SACD_0002", Some("Sacd"), None, None),
+        ];
+        let originals = plan_entries(&songs, false, None);
+        assert_eq!(
+            names(&originals),
+            ["01 - Artist - Same.flac", "02 - Artist - Same.flac", "03 - Artist - Sacd.flac"]
+        );
+        assert_eq!(originals[2].transcode, Some(AudioEncoding::Flac));
+
+        let mp3 = plan_entries(&songs[..1], false, Some(AudioEncoding::Mp3));
+        assert_eq!(names(&mp3), ["01 - Artist - Same.mp3"]);
+        assert_eq!(mp3[0].transcode, Some(AudioEncoding::Mp3));
+    }
+
+    #[test]
+    fn duplicate_names_get_numbered() {
+        let songs = [
+            song("a/1.flac", Some("Take"), Some("1"), None),
+            song("a/2.flac", Some("Take"), Some("1"), None),
+        ];
+        assert_eq!(names(&plan_entries(&songs, true, None)), ["01 - Take.flac", "01 - Take (2).flac"]);
+    }
+
+    #[test]
+    fn download_names_survive_headers() {
+        assert_eq!(
+            content_disposition("Björk - \"Debut\".zip"),
+            "attachment; filename=\"Bj_rk - _Debut_.zip\"; filename*=UTF-8''Bj%C3%B6rk%20%2D%20%22Debut%22%2Ezip"
+        );
+    }
+}
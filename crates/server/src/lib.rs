@@ -3,16 +3,20 @@
 //! [`run_backend`] opens the shared fjall database, builds every service via
 //! `composition_root`, then races the long-lived futures in one `select!`:
 //! HTTP(S) servers + WebSocket fan-out, the user/system command handlers,
-//! the multiroom sync service, and shutdown signals (SIGTERM/ctrl-c, or the
-//! desktop app's oneshot). Whichever finishes first takes the process down;
-//! the database is persisted on the signal paths. If the audio device can't
-//! be opened at startup the server comes up in *degraded* mode: settings UI
-//! only, so the user can fix the device selection remotely.
+//! the multiroom sync service, the playback scheduler, and shutdown signals
+//! (SIGTERM/ctrl-c, or the desktop app's oneshot). Whichever finishes first
+//! takes the process down; the database is persisted on the signal paths.
+//! If the audio device can't be opened at startup the server comes up in
+//! *degraded* mode: settings UI only, so the user can fix the device
+//! selection remotely. Access control applies in both modes, so it is
+//! built here rather than in the container.
 
 extern crate log;
+pub mod auth;
 pub mod command_context;
 pub mod command_handler;
 pub mod composition_root;
+pub mod download;
 pub mod metadata_commands;
 #[cfg_attr(target_os = "linux", path = "mount_service_linux.rs")]
 #[cfg_attr(not(target_os = "linux"), path = "mount_service_stub.rs")]
@@ -20,9 +24,14 @@ pub mod mount_service;
 pub mod player_commands;
 pub mod playlist_commands;
 pub mod queue_commands;
+pub mod scheduler;
+pub mod scheduler_commands;
 pub mod server;
 pub mod storage_commands;
+pub mod stream_output;
 pub mod system_commands;
+pub mod transcode;
+pub mod zip_stream;
 
 use fjall::PersistMode;
 use hardware::usb;
@@ -32,10 +41,14 @@ use tokio::sync::mpsc;
 use tokio::sync::oneshot::{Receiver, Sender};
 use tokio::{select, spawn};
 
+use crate::auth::Auth;
 use crate::composition_root::{build_app_container, AppContainer, BuildOutcome};
+use crate::download::Downloads;
 use crate::mount_service::MountService;
 use api_models::common::UserCommand;
 use config::{ArcConfiguration, Configuration};
+use metadata::user_repository::FjallUserRepository;
+use metadata::user_service::UserService;
 
 use env_logger::Env;
 
@@ -80,15 +93,17 @@ pub async fn run_backend(
 
     MountService::mount_all(&config.get_settings().network_storage_settings);
 
+    let auth = Auth::new(UserService::new(Arc::new(FjallUserRepository::new(&shared_db))), config.clone());
+
     let container = match build_app_container(&config, &shared_db) {
         BuildOutcome::Ready(c) => c,
         BuildOutcome::Degraded(e) => {
-            start_degraded(&e, &config).await;
+            start_degraded(&e, &config, auth).await;
             return;
         }
     };
 
-    run(container, shutdown_rx, command_sender_out, restart_tx, &config, &shared_db).await;
+    run(container, shutdown_rx, command_sender_out, restart_tx, &config, &shared_db, auth).await;
 
     info!("RSPlayer shutdown completed.");
 }
@@ -101,27 +116,35 @@ async fn run(
     restart_tx: Option<mpsc::Sender<()>>,
     config: &ArcConfiguration,
     shared_db: &Arc<fjall::Database>,
+    auth: Auth,
 ) {
     let AppContainer {
         album_repository,
+        work_repository,
         song_repository,
         loudness_repository,
         metadata_service,
         playlist_service,
         queue_service,
+        radio_station_service,
         player_service,
         audio_service,
         usb_service,
         state_changes_tx,
         user_commands,
+        profile_commands,
+        scheduler_commands,
         system_commands,
         multiroom_commands,
         multiroom_follower_active,
+        fade_in,
         multiroom,
         ..
     } = *container;
 
     let (player_commands_tx, player_commands_rx) = user_commands.split();
+    let (profile_commands_tx, profile_commands_rx) = profile_commands.split();
+    let (scheduler_commands_tx, scheduler_commands_rx) = scheduler_commands.split();
     let (system_commands_tx, system_commands_rx) = system_commands.split();
     let (multiroom_commands_tx, multiroom_commands_rx) = multiroom_commands.split();
 
@@ -129,8 +152,21 @@ async fn run(
         let _ = out.send(player_commands_tx.clone());
     }
 
-    let (http_server_future, https_server_future, websocket_future) =
-        server::start(state_changes_tx.subscribe(), player_commands_tx.clone(), config);
+    let downloads = Downloads::new(
+        song_repository.clone(),
+        album_repository.clone(),
+        playlist_service.clone(),
+        queue_service.clone(),
+    );
+    let (http_server_future, https_server_future, websocket_future) = server::start(
+        state_changes_tx.subscribe(),
+        profile_commands_tx,
+        metadata_service.clone(),
+        config,
+        player_service.output_tee(),
+        downloads,
+        auth,
+    );
     info!("HTTP servers started.");
 
     if let Some(service) = usb_service.clone() {
@@ -145,6 +181,14 @@ async fn run(
     if config.get_settings().auto_resume_playback {
         player_service.play_from_current_queue_song();
     }
+    {
+        let metadata_service = metadata_service.clone();
+        let state_changes_tx = state_changes_tx.clone();
+        std::thread::Builder::new()
+            .name("library_migrations".to_string())
+            .spawn(move || metadata_service.run_library_migrations(&state_changes_tx))
+            .expect("Failed to start library migration thread");
+    }
     #[cfg(feature = "lirc")]
     {
         let player_commands_tx_clone = player_commands_tx.clone();
@@ -206,11 +250,16 @@ async fn run(
                     metadata_service,
                     playlist_service,
                     queue_service,
+                    radio_station_service,
                     album_repository,
+                    work_repository,
                     song_repository,
                     loudness_repository,
                     config.clone(),
                     player_commands_rx,
+                    profile_commands_rx,
+                    scheduler_commands_rx,
+                    fade_in.clone(),
                     system_commands_tx,
                     multiroom_commands_tx,
                     multiroom_follower_active,
@@ -219,6 +268,16 @@ async fn run(
                 error!("Exit from command handler thread.");
             }
 
+        _ = spawn(scheduler::run_scheduler(
+                config.clone(),
+                scheduler_commands_tx,
+                fade_in,
+                multiroom_follower_active.clone(),
+                state_changes_tx.clone()))
+            => {
+                error!("Exit from scheduler thread.");
+            }
+
         _ = spawn(sync_service_future) => {
             error!("Exit from multiroom sync service.");
         }
@@ -284,9 +343,9 @@ fn terminate_signal() -> std::pin::Pin<Box<dyn std::future::Future<Output = ()>
 }
 
 #[allow(clippy::redundant_pub_crate)]
-async fn start_degraded(error: &anyhow::Error, config: &Arc<Configuration>) {
+async fn start_degraded(error: &anyhow::Error, config: &Arc<Configuration>, auth: Auth) {
     warn!("Starting server in degraded mode.");
-    let http_server_future = server::start_degraded(config, error);
+    let http_server_future = server::start_degraded(config, error, auth);
     select! {
         () = http_server_future => {}
 
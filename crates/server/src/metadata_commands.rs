@@ -1,12 +1,24 @@
-//! Metadata commands: library browse/search queries, like/dislike, stats,
-//! and `RescanMetadata`, which runs the scanner on its own named thread so
-//! the command loop stays responsive.
+//! Metadata commands: library browse/search queries (artists and albums,
+//! albums grouped by disc, composers and works), like/dislike, stats, tag
+//! edits, and `RescanMetadata`, which runs the scanner on its own named
+//! thread so the command loop stays responsive. `FindDuplicates` runs on its
+//! own thread too, as does `ImportFavoriteRadioStations`, which looks the
+//! liked stations up on radio-browser.info. Radio station edits answer with
+//! the whole station list.
 
 use api_models::common::MetadataCommand::{self, QueryLocalFiles, RescanMetadata};
 use api_models::common::MetadataLibraryItem;
+use api_models::playlist::{Album, AlbumTrack};
 use api_models::state::StateChangeEvent;
+use log::error;
+use metadata::duplicate_finder::DuplicateFinder;
+use metadata::work_repository::movement_title;
 
-use crate::command_context::CommandContext;
+use crate::command_context::{BackgroundJob, CommandContext};
+use crate::queue_commands::get_songs_from_work;
+
+static FINDING_DUPLICATES: BackgroundJob = BackgroundJob::new("duplicates", "Duplicates are already being searched");
+static IMPORTING_RADIO_STATIONS: BackgroundJob = BackgroundJob::new("radio_import", "Favorite radio stations are already being imported");
 
 pub fn handle_metadata_command(cmd: MetadataCommand, ctx: &CommandContext) {
     match cmd {
@@ -66,43 +78,184 @@ pub fn handle_metadata_command(cmd: MetadataCommand, ctx: &CommandContext) {
             ctx.send_event(StateChangeEvent::MetadataLocalItems(items));
         }
         MetadataCommand::QuerySongsByAlbum(album) => {
-            let items: Vec<MetadataLibraryItem> = ctx
+            let items = ctx
                 .album_repository
                 .find_by_id(&album)
+                .map(|alb| album_items(&alb, ctx))
+                .unwrap_or_default();
+            ctx.send_event(StateChangeEvent::MetadataLocalItems(items));
+        }
+        MetadataCommand::QueryComposers => {
+            let items: Vec<MetadataLibraryItem> = ctx
+                .work_repository
+                .find_all_composers()
+                .into_iter()
+                .map(|name| MetadataLibraryItem::Composer { name })
+                .collect();
+            ctx.send_event(StateChangeEvent::MetadataLocalItems(items));
+        }
+        MetadataCommand::QueryWorksByComposer(composer) => {
+            let items: Vec<MetadataLibraryItem> = ctx
+                .work_repository
+                .find_works_by_composer(&composer)
+                .into_iter()
+                .map(|(id, name)| MetadataLibraryItem::Work { name, id })
+                .collect();
+            ctx.send_event(StateChangeEvent::MetadataLocalItems(items));
+        }
+        MetadataCommand::QueryRecordingsByWork(work_id) => {
+            let items: Vec<MetadataLibraryItem> = ctx
+                .work_repository
+                .find_recordings(&work_id)
                 .iter()
-                .flat_map(|alb| alb.song_keys.iter().filter_map(|sk| ctx.song_repository.find_by_id(sk)))
-                .map(MetadataLibraryItem::SongItem)
+                .map(|rec| MetadataLibraryItem::Recording {
+                    name: rec.label(),
+                    id: rec.id.clone(),
+                })
                 .collect();
             ctx.send_event(StateChangeEvent::MetadataLocalItems(items));
         }
+        MetadataCommand::QuerySongsByRecording(recording_id) => {
+            let items: Vec<MetadataLibraryItem> = get_songs_from_work(ctx, &recording_id)
+                .into_iter()
+                .map(|mut song| {
+                    song.title = movement_title(&song).or(song.title);
+                    MetadataLibraryItem::SongItem(song)
+                })
+                .collect();
+            ctx.send_event(StateChangeEvent::MetadataLocalItems(items));
+        }
+        MetadataCommand::UpdateTags(song_key, fields) => match ctx.metadata_service.update_song_tags(&song_key, &fields) {
+            Ok(song) => ctx.send_notification(&format!("Tags of '{}' updated", song.get_title())),
+            Err(e) => {
+                error!("Tag update failed: {e}");
+                ctx.send_error(&format!("Tag update failed: {e}"));
+            }
+        },
+        MetadataCommand::UpdateAlbumTags(album_id, fields) => match ctx.metadata_service.update_album_tags(&album_id, &fields) {
+            Ok(songs) => ctx.send_notification(&format!("Tags of {} songs updated", songs.len())),
+            Err(e) => {
+                error!("Album tag update failed: {e}");
+                ctx.send_error(&format!("Album tag update failed: {e}"));
+            }
+        },
+        MetadataCommand::SetAlbumImage(album_id, image_id) => match ctx.metadata_service.set_album_image(&album_id, &image_id) {
+            Ok(()) => ctx.send_notification("Album artwork updated"),
+            Err(e) => {
+                error!("Setting album artwork failed: {e}");
+                ctx.send_error(&format!("Setting album artwork failed: {e}"));
+            }
+        },
         MetadataCommand::LikeMediaItem(id) => {
-            ctx.metadata_service.like_media_item(&id);
+            ctx.metadata_service.like_media_item(ctx.profile.as_deref(), &id);
             ctx.send_notification(&format!("Song {id} liked"));
             resend_current_song_if_affected(ctx, &id);
         }
         MetadataCommand::DislikeMediaItem(id) => {
-            ctx.metadata_service.dislike_media_item(&id);
+            ctx.metadata_service.dislike_media_item(ctx.profile.as_deref(), &id);
             ctx.send_notification(&format!("Song {id} disliked"));
             resend_current_song_if_affected(ctx, &id);
         }
         MetadataCommand::QueryFavoriteRadioStations => {
-            let favorites = ctx.metadata_service.get_favorite_radio_stations();
-            ctx.send_event(StateChangeEvent::FavoriteRadioStations(favorites));
+            let favorites = ctx.metadata_service.get_favorite_radio_stations(ctx.profile.as_deref());
+            ctx.send_profile_event(StateChangeEvent::FavoriteRadioStations(favorites));
         }
         MetadataCommand::QueryLibraryStats => {
-            let mut stats = ctx.metadata_service.get_library_stats();
+            let mut stats = ctx.metadata_service.get_library_stats(ctx.profile.as_deref());
             stats.songs_loudness_analysed = ctx.loudness_repository.count_analysed();
-            ctx.send_event(StateChangeEvent::LibraryStatsEvent(stats));
+            ctx.send_profile_event(StateChangeEvent::LibraryStatsEvent(stats));
+        }
+        MetadataCommand::FindDuplicates(use_fingerprints) => {
+            let music_dirs = ctx.config_store.get_settings().metadata_settings.library_directories();
+            let finder = DuplicateFinder::new(ctx.song_repository.clone(), ctx.loudness_repository.clone());
+            FINDING_DUPLICATES.spawn(ctx, move || {
+                let report = finder.run(&music_dirs, use_fingerprints);
+                let message = format!("Found {} songs with duplicates", report.group_count);
+                vec![
+                    StateChangeEvent::DuplicateReportEvent(report),
+                    StateChangeEvent::NotificationSuccess(message),
+                ]
+            });
+        }
+        MetadataCommand::QueryRadioStations => send_radio_stations(ctx),
+        MetadataCommand::SaveRadioStation(station) => match ctx.radio_station_service.save_station(station) {
+            Ok(saved) => {
+                ctx.send_notification(&format!("Radio station {} saved", saved.name));
+                send_radio_stations(ctx);
+            }
+            Err(e) => {
+                error!("Saving radio station failed: {e}");
+                ctx.send_error(&format!("Saving radio station failed: {e}"));
+            }
+        },
+        MetadataCommand::DeleteRadioStation(id) => match ctx.radio_station_service.delete_station(&id) {
+            Ok(true) => {
+                ctx.send_notification("Radio station removed");
+                send_radio_stations(ctx);
+            }
+            Ok(false) => ctx.send_error("Radio station not found"),
+            Err(e) => {
+                error!("Removing radio station failed: {e}");
+                ctx.send_error(&format!("Removing radio station failed: {e}"));
+            }
+        },
+        MetadataCommand::MoveRadioStation(id, position) => {
+            if let Err(e) = ctx.radio_station_service.move_station(&id, position) {
+                error!("Moving radio station failed: {e}");
+                ctx.send_error(&format!("Moving radio station failed: {e}"));
+            }
+            send_radio_stations(ctx);
+        }
+        MetadataCommand::ImportFavoriteRadioStations => {
+            let favorites = ctx.metadata_service.get_favorite_radio_stations(ctx.profile.as_deref());
+            let service = ctx.radio_station_service.clone();
+            IMPORTING_RADIO_STATIONS.spawn(ctx, move || {
+                let notification = match service.import_favorites(&favorites) {
+                    Ok(imported) => StateChangeEvent::NotificationSuccess(format!("Imported {imported} favorite radio stations")),
+                    Err(e) => {
+                        error!("Importing favorite radio stations failed: {e}");
+                        StateChangeEvent::NotificationError(format!("Importing favorite radio stations failed: {e}"))
+                    }
+                };
+                vec![notification, StateChangeEvent::RadioStationsEvent(service.get_stations())]
+            });
         }
     }
 }
 
-/// Re-broadcast the current song with fresh statistics when it was the
-/// (dis)liked item, so clients update the like indicator immediately.
+fn send_radio_stations(ctx: &CommandContext) {
+    ctx.send_event(StateChangeEvent::RadioStationsEvent(ctx.radio_station_service.get_stations()));
+}
+
+/// Re-send the current song with the profile's fresh statistics when it was
+/// the (dis)liked item, so its clients update the like indicator immediately.
 fn resend_current_song_if_affected(ctx: &CommandContext, media_item_id: &str) {
-    if let Some(song) = ctx.queue_service.get_current_song() {
+    if let Some(mut song) = ctx.queue_service.get_current_song() {
         if song.file == media_item_id {
-            ctx.send_event(StateChangeEvent::CurrentSongEvent(song));
+            song.statistics = ctx.metadata_service.get_song_statistics(ctx.profile.as_deref(), media_item_id);
+            ctx.send_profile_event(StateChangeEvent::CurrentSongEvent(song));
+        }
+    }
+}
+
+/// Songs of an album in playing order, each disc of a multi-disc album (or
+/// a disc with a subtitle) under a `Disc` header.
+fn album_items(album: &Album, ctx: &CommandContext) -> Vec<MetadataLibraryItem> {
+    let with_headers = album.disc_count() > 1 || !album.disc_subtitles.is_empty();
+    let mut items = Vec::with_capacity(album.song_keys.len());
+    let mut current_disc = None;
+    for key in &album.song_keys {
+        let Some(song) = ctx.song_repository.find_by_id(key) else {
+            continue;
+        };
+        let disc = album.tracks.iter().find(|t| &t.key == key).map(AlbumTrack::disc_number);
+        if with_headers && disc.is_some() && disc != current_disc {
+            current_disc = disc;
+            items.push(MetadataLibraryItem::Disc {
+                name: album.disc_label(disc.unwrap_or(1)),
+            });
         }
+        items.push(MetadataLibraryItem::SongItem(song));
     }
+    items
 }
@@ -1,18 +1,33 @@
-//! Transport commands (play/pause/seek/next…) → `PlayerService`. Rejected
-//! with a notification while this instance is a grouped multiroom follower.
+//! Transport commands (play/pause/seek/next…), bookmarks, the sleep
+//! timer and radio recording → `PlayerService`. Transport is rejected with a notification while this
+//! instance is a grouped multiroom follower.
 
-use api_models::state::StateChangeEvent;
+use std::path::Path;
+
+use api_models::state::{CurrentQueueQuery, StateChangeEvent};
 
 use crate::command_context::CommandContext;
 
 pub fn handle_player_command(cmd: api_models::common::PlayerCommand, ctx: &CommandContext) {
     use api_models::common::PlayerCommand::{
-        CyclePlaybackMode, Next, Pause, Play, PlayItem, Prev, QueryCurrentPlayerInfo, Seek, SeekBackward, SeekForward, Stop, TogglePlay,
+        AddBookmark, CancelSleepTimer, CyclePlaybackMode, DeleteBookmark, JumpToBookmark, Next, Pause, Play, PlayItem, Prev,
+        QueryBookmarks, QueryCurrentPlayerInfo, QuerySleepTimer, Seek, SeekBackward, SeekForward, SetSleepTimer, SleepAtEndOfAlbum,
+        SleepAtEndOfTrack, StartRecording, Stop, StopRecording, TogglePlay,
     };
 
     // A grouped multiroom follower plays what the leader streams; local
     // transport commands would fight over the audio device.
-    let is_transport = !matches!(cmd, QueryCurrentPlayerInfo | CyclePlaybackMode);
+    let is_transport = !matches!(
+        cmd,
+        QueryCurrentPlayerInfo
+            | CyclePlaybackMode
+            | AddBookmark(_)
+            | DeleteBookmark(_)
+            | QueryBookmarks
+            | QuerySleepTimer
+            | StartRecording
+            | StopRecording
+    );
     if is_transport && ctx.multiroom_follower_active.load(std::sync::atomic::Ordering::SeqCst) {
         ctx.send_error("Playback is controlled by the multiroom group leader. Leave the group to control it locally.");
         return;
@@ -53,11 +68,79 @@ pub fn handle_player_command(cmd: api_models::common::PlayerCommand, ctx: &Comma
         QueryCurrentPlayerInfo => {
             let mode = ctx.queue_service.get_playback_mode();
             ctx.send_event(StateChangeEvent::PlaybackModeChangedEvent(mode));
+            ctx.send_event(StateChangeEvent::AutoContinueChangedEvent(ctx.queue_service.is_auto_continue()));
             let settings = ctx.config_store.get_settings();
             ctx.send_event(StateChangeEvent::VuMeterEnabledEvent(settings.rs_player_settings.vu_meter_enabled));
             if let Some(info) = ctx.player_service.get_current_player_info() {
                 ctx.send_event(StateChangeEvent::PlayerInfoEvent(info));
             }
+            ctx.send_event(StateChangeEvent::SleepTimerEvent(ctx.player_service.get_sleep_timer()));
+            ctx.send_event(StateChangeEvent::RecordingEvent(ctx.player_service.get_recording_file()));
+        }
+        AddBookmark(name) => {
+            if let Some(bookmark) = ctx.player_service.add_bookmark(&name) {
+                ctx.send_notification(&format!("Bookmark {} added", bookmark.name));
+                ctx.send_event(StateChangeEvent::BookmarksEvent(ctx.player_service.get_bookmarks()));
+            } else {
+                ctx.send_error("Nothing is playing");
+            }
+        }
+        JumpToBookmark(id) => {
+            let Some(bookmark) = ctx.player_service.get_bookmark(&id) else {
+                ctx.send_error("Bookmark not found");
+                return;
+            };
+            if ctx.song_repository.find_by_id(&bookmark.song_file).is_none() {
+                ctx.send_error("Bookmark's song is no longer in the library");
+                return;
+            }
+            ctx.queue_service.checkpoint();
+            if ctx.player_service.jump_to_bookmark(&bookmark) {
+                let queue = ctx.queue_service.query_current_queue(CurrentQueueQuery::CurrentSongPage);
+                ctx.send_event(StateChangeEvent::CurrentQueueEvent(queue));
+            }
+        }
+        DeleteBookmark(id) => {
+            if ctx.player_service.delete_bookmark(&id) {
+                ctx.send_event(StateChangeEvent::BookmarksEvent(ctx.player_service.get_bookmarks()));
+            }
+        }
+        QueryBookmarks => {
+            ctx.send_event(StateChangeEvent::BookmarksEvent(ctx.player_service.get_bookmarks()));
+        }
+        // The timer broadcasts its own state changes.
+        SetSleepTimer(minutes) => {
+            ctx.player_service.set_sleep_timer(minutes);
+        }
+        SleepAtEndOfTrack => {
+            ctx.player_service.sleep_at_end_of_track();
+        }
+        SleepAtEndOfAlbum => {
+            ctx.player_service.sleep_at_end_of_album();
+        }
+        CancelSleepTimer => {
+            ctx.player_service.cancel_sleep_timer();
+        }
+        QuerySleepTimer => {
+            ctx.send_event(StateChangeEvent::SleepTimerEvent(ctx.player_service.get_sleep_timer()));
+        }
+        // The recorder broadcasts its own state changes.
+        StartRecording => {
+            let settings = ctx.config_store.get_settings().metadata_settings;
+            let directory = settings.recordings_directory.trim();
+            if directory.is_empty() {
+                ctx.send_error("Set a recordings directory in the music library settings first");
+            } else if let Err(e) = ctx
+                .player_service
+                .start_recording(Path::new(directory), settings.split_recordings_on_title)
+            {
+                ctx.send_error(&e.to_string());
+            }
+        }
+        StopRecording => {
+            if !ctx.player_service.stop_recording() {
+                ctx.send_error("Nothing is being recorded");
+            }
         }
     }
 }
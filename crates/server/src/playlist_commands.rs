@@ -1,33 +1,113 @@
-//! Playlist commands: saved-playlist CRUD, the dynamic playlists
-//! (most played, liked) and the album carousels behind the home page.
+//! Playlist commands: saved-playlist CRUD and item editing, the dynamic
+//! playlists (most played, liked) and the album carousels behind the home
+//! page. Header changes (rename, delete, duplicate, details) answer with a
+//! fresh `PlaylistsEvent`; item removal and moves with the edited
+//! playlist's first page. Saved playlists, most played and liked are the
+//! command profile's own, and go only to that profile's sessions.
 
 use api_models::common::PlaylistCommand::{
-    QueryAlbumItems, QueryAlbumsByDecade, QueryAlbumsByGenre, QueryPlaylist, QueryPlaylistItems, SaveQueueAsPlaylist,
+    AddAlbumToPlaylist, AddDirectoryToPlaylist, AddSongToPlaylist, DeletePlaylist, DuplicatePlaylist, MovePlaylistItem, QueryAlbumItems,
+    QueryAlbumsByDecade, QueryAlbumsByGenre, QueryPlaylist, QueryPlaylistItems, RemovePlaylistItem, RenamePlaylist, SaveQueueAsPlaylist,
+    UpdatePlaylistDetails,
 };
+use api_models::player::Song;
 use api_models::playlist::PlaylistType;
 use api_models::state::StateChangeEvent;
 
 use crate::command_context::CommandContext;
+use crate::queue_commands::get_songs_from_album;
+
+const PAGE_SIZE: usize = 20;
+
+fn send_playlists(ctx: &CommandContext) {
+    let profile = ctx.profile.as_deref();
+    let mut pls = ctx.playlist_service.get_playlists(profile);
+    ctx.album_repository.find_all_sort_by_added_desc(30).into_iter().for_each(|alb| {
+        pls.items.push(PlaylistType::RecentlyAdded(alb));
+    });
+    ctx.album_repository.find_all_sort_by_released_desc(30).into_iter().for_each(|alb| {
+        pls.items.push(PlaylistType::LatestRelease(alb));
+    });
+    if let Some(first_most_played) = ctx.metadata_service.get_most_played_songs(profile, 1).first() {
+        let pl = api_models::playlist::Playlist {
+            id: "most_played".to_string(),
+            name: "Most Played".to_string(),
+            description: Some("Your most played tracks".to_string()),
+            image: first_most_played.image_id.clone(),
+            owner_name: None,
+        };
+        pls.items.push(PlaylistType::MostPlayed(pl));
+    }
+
+    if let Some(first_liked) = ctx.metadata_service.get_liked_songs(profile, 1).first() {
+        let pl = api_models::playlist::Playlist {
+            id: "liked".to_string(),
+            name: "Liked".to_string(),
+            description: Some("Songs you liked".to_string()),
+            image: first_liked.image_id.clone(),
+            owner_name: None,
+        };
+        pls.items.push(PlaylistType::Liked(pl));
+    }
+
+    ctx.album_repository.find_all_by_genre(20).into_iter().for_each(|(genre, albums)| {
+        pls.items.push(PlaylistType::GenreHeader(genre, albums.len()));
+    });
+
+    ctx.album_repository
+        .find_all_by_decade(20)
+        .into_iter()
+        .for_each(|(decade, albums)| {
+            pls.items.push(PlaylistType::DecadeHeader(decade, albums.len()));
+        });
+
+    ctx.send_profile_event(StateChangeEvent::PlaylistsEvent(pls));
+}
+
+fn send_first_page(ctx: &CommandContext, playlist_id: &str) {
+    let songs = ctx.playlist_service.get_playlist_page(playlist_id, 0, PAGE_SIZE).items;
+    ctx.send_profile_event(StateChangeEvent::PlaylistItemsEvent(songs, 0));
+}
+
+/// Whether the command's profile may see and change the playlist.
+fn is_visible(ctx: &CommandContext, playlist_id: &str) -> bool {
+    ctx.playlist_service.is_visible_to(playlist_id, ctx.profile.as_deref())
+}
+
+fn append_to_playlist(ctx: &CommandContext, playlist_id: &str, songs: &[Song]) {
+    if songs.is_empty() {
+        ctx.send_error("Nothing to add");
+    } else if is_visible(ctx, playlist_id) && ctx.playlist_service.append_songs(playlist_id, songs) {
+        ctx.send_notification(&format!("{} songs added to playlist", songs.len()));
+    } else {
+        ctx.send_error("Playlist not found");
+    }
+}
 
 #[allow(clippy::too_many_lines)]
 pub fn handle_playlist_command(cmd: api_models::common::PlaylistCommand, ctx: &CommandContext) {
     match cmd {
         SaveQueueAsPlaylist(playlist_name) => {
             ctx.playlist_service
-                .save_new_playlist(&playlist_name, &ctx.queue_service.get_all_songs());
+                .save_new_playlist(ctx.profile.as_deref(), &playlist_name, &ctx.queue_service.get_all_songs());
             ctx.send_notification(&format!("Playlist {playlist_name} saved."));
         }
         QueryPlaylistItems(playlist_id, page_no) => {
+            let profile = ctx.profile.as_deref();
             let songs = if playlist_id == "most_played" {
-                let all = ctx.metadata_service.get_most_played_songs(100);
+                let all = ctx.metadata_service.get_most_played_songs(profile, 100);
                 all.into_iter().skip(page_no * 20).take(20).collect()
             } else if playlist_id == "liked" {
-                let all = ctx.metadata_service.get_liked_songs(100);
+                let all = ctx.metadata_service.get_liked_songs(profile, 100);
                 all.into_iter().skip(page_no * 20).take(20).collect()
+            } else if is_visible(ctx, &playlist_id) {
+                ctx.playlist_service
+                    .get_playlist_page(&playlist_id, page_no * PAGE_SIZE, PAGE_SIZE)
+                    .items
             } else {
-                ctx.playlist_service.get_playlist_page_by_name(&playlist_id, page_no * 20, 20).items
+                Vec::new()
             };
-            ctx.send_event(StateChangeEvent::PlaylistItemsEvent(songs, page_no));
+            ctx.send_profile_event(StateChangeEvent::PlaylistItemsEvent(songs, page_no));
         }
         QueryAlbumItems(album_title, page_no) => {
             let songs = ctx.album_repository.find_by_id(&album_title).map(|alb| alb.song_keys);
@@ -39,52 +119,10 @@ pub fn handle_playlist_command(cmd: api_models::common::PlaylistCommand, ctx: &C
                     .take(20)
                     .filter_map(|song_key| ctx.song_repository.find_by_id(song_key))
                     .collect::<Vec<_>>();
-                ctx.send_event(StateChangeEvent::PlaylistItemsEvent(songs, page_no));
-            }
-        }
-        QueryPlaylist => {
-            let mut pls = ctx.playlist_service.get_playlists();
-            ctx.album_repository.find_all_sort_by_added_desc(30).into_iter().for_each(|alb| {
-                pls.items.push(PlaylistType::RecentlyAdded(alb));
-            });
-            ctx.album_repository.find_all_sort_by_released_desc(30).into_iter().for_each(|alb| {
-                pls.items.push(PlaylistType::LatestRelease(alb));
-            });
-            if let Some(first_most_played) = ctx.metadata_service.get_most_played_songs(1).first() {
-                let pl = api_models::playlist::Playlist {
-                    id: "most_played".to_string(),
-                    name: "Most Played".to_string(),
-                    description: Some("Your most played tracks".to_string()),
-                    image: first_most_played.image_id.clone(),
-                    owner_name: None,
-                };
-                pls.items.push(PlaylistType::MostPlayed(pl));
+                ctx.send_profile_event(StateChangeEvent::PlaylistItemsEvent(songs, page_no));
             }
-
-            if let Some(first_liked) = ctx.metadata_service.get_liked_songs(1).first() {
-                let pl = api_models::playlist::Playlist {
-                    id: "liked".to_string(),
-                    name: "Liked".to_string(),
-                    description: Some("Songs you liked".to_string()),
-                    image: first_liked.image_id.clone(),
-                    owner_name: None,
-                };
-                pls.items.push(PlaylistType::Liked(pl));
-            }
-
-            ctx.album_repository.find_all_by_genre(20).into_iter().for_each(|(genre, albums)| {
-                pls.items.push(PlaylistType::GenreHeader(genre, albums.len()));
-            });
-
-            ctx.album_repository
-                .find_all_by_decade(20)
-                .into_iter()
-                .for_each(|(decade, albums)| {
-                    pls.items.push(PlaylistType::DecadeHeader(decade, albums.len()));
-                });
-
-            ctx.send_event(StateChangeEvent::PlaylistsEvent(pls));
         }
+        QueryPlaylist => send_playlists(ctx),
         QueryAlbumsByGenre(genre) => {
             let albums: Vec<api_models::playlist::Album> = ctx
                 .album_repository
@@ -105,5 +143,62 @@ pub fn handle_playlist_command(cmd: api_models::common::PlaylistCommand, ctx: &C
                 .unwrap_or_default();
             ctx.send_event(StateChangeEvent::DecadeAlbumsEvent(decade, albums));
         }
+        RenamePlaylist(playlist_id, name) => {
+            if is_visible(ctx, &playlist_id) && ctx.playlist_service.rename_playlist(&playlist_id, &name) {
+                send_playlists(ctx);
+            } else {
+                ctx.send_error("Playlist not found");
+            }
+        }
+        DeletePlaylist(playlist_id) => {
+            if is_visible(ctx, &playlist_id) && ctx.playlist_service.delete_playlist(&playlist_id) {
+                ctx.send_notification("Playlist deleted");
+                send_playlists(ctx);
+            } else {
+                ctx.send_error("Playlist not found");
+            }
+        }
+        DuplicatePlaylist(playlist_id, name) => {
+            let copy = is_visible(ctx, &playlist_id)
+                .then(|| ctx.playlist_service.duplicate_playlist(ctx.profile.as_deref(), &playlist_id, &name))
+                .flatten();
+            if copy.is_some() {
+                ctx.send_notification(&format!("Playlist {name} created"));
+                send_playlists(ctx);
+            } else {
+                ctx.send_error("Playlist not found");
+            }
+        }
+        UpdatePlaylistDetails(playlist_id, description, image) => {
+            if is_visible(ctx, &playlist_id) && ctx.playlist_service.update_playlist_details(&playlist_id, description, image) {
+                send_playlists(ctx);
+            } else {
+                ctx.send_error("Playlist not found");
+            }
+        }
+        AddSongToPlaylist(playlist_id, song_id) => {
+            let songs: Vec<Song> = ctx.song_repository.find_by_id(&song_id).into_iter().collect();
+            append_to_playlist(ctx, &playlist_id, &songs);
+        }
+        AddAlbumToPlaylist(playlist_id, album_id) => {
+            append_to_playlist(ctx, &playlist_id, &get_songs_from_album(ctx, &album_id));
+        }
+        AddDirectoryToPlaylist(playlist_id, dir) => {
+            append_to_playlist(ctx, &playlist_id, &ctx.song_repository.find_songs_by_dir_prefix(&dir));
+        }
+        RemovePlaylistItem(playlist_id, position) => {
+            if is_visible(ctx, &playlist_id) && ctx.playlist_service.remove_item(&playlist_id, position) {
+                send_first_page(ctx, &playlist_id);
+            } else {
+                ctx.send_error("Playlist item not found");
+            }
+        }
+        MovePlaylistItem(playlist_id, from, to) => {
+            if is_visible(ctx, &playlist_id) && ctx.playlist_service.move_item(&playlist_id, from, to) {
+                send_first_page(ctx, &playlist_id);
+            } else {
+                ctx.send_error("Playlist item not found");
+            }
+        }
     }
 }
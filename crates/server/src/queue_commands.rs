@@ -1,6 +1,10 @@
-//! Queue commands: load/add songs, albums, artists, directories, genres
-//! and decades into the playback queue, reorder/remove items, and answer
-//! queue queries with `CurrentQueueEvent` pages.
+//! Queue commands: load/add songs, albums, artists, directories, genres,
+//! decades and classical works into the playback queue, reorder/remove
+//! items, toggle auto-continue, and answer queue queries with
+//! `CurrentQueueEvent` pages.
+//! Every queue-changing command first records an undo checkpoint; undo,
+//! redo and snapshot restores restart playback only if the current song
+//! changed while playing.
 
 use api_models::common::QueueCommand::{
     self, AddLocalLibDirectory, AddSongToQueue, ClearQueue, LoadAlbumInQueue, LoadArtistInQueue, LoadPlaylistInQueue, LoadSongToQueue,
@@ -8,16 +12,35 @@ use api_models::common::QueueCommand::{
 };
 use api_models::player::Song;
 use api_models::state::StateChangeEvent;
+use metadata::work_repository::sort_movements;
 
 use crate::command_context::CommandContext;
 
-fn get_songs_from_album(ctx: &CommandContext, album_id: &str) -> Vec<Song> {
+pub(crate) fn get_songs_from_album(ctx: &CommandContext, album_id: &str) -> Vec<Song> {
     ctx.album_repository
         .find_by_id(album_id)
         .map(|alb| alb.song_keys.iter().filter_map(|sk| ctx.song_repository.find_by_id(sk)).collect())
         .unwrap_or_default()
 }
 
+/// Movements of a recording in order; for a work, those of each of its
+/// recordings one after another.
+pub(crate) fn get_songs_from_work(ctx: &CommandContext, id: &str) -> Vec<Song> {
+    ctx.work_repository
+        .find_recordings(id)
+        .iter()
+        .flat_map(|recording| {
+            let mut songs: Vec<Song> = recording
+                .song_keys
+                .iter()
+                .filter_map(|sk| ctx.song_repository.find_by_id(sk))
+                .collect();
+            sort_movements(&mut songs);
+            songs
+        })
+        .collect()
+}
+
 fn get_songs_from_artist(ctx: &CommandContext, artist: &str) -> Vec<Song> {
     ctx.album_repository
         .find_by_artist(artist)
@@ -55,6 +78,56 @@ fn get_songs_from_decade(ctx: &CommandContext, decade: &str) -> Vec<Song> {
         .collect()
 }
 
+/// Commands that change the queue and so get an undo checkpoint.
+/// `RestoreSnapshot` records its own, once the snapshot is known to exist.
+const fn changes_queue(cmd: &QueueCommand) -> bool {
+    matches!(
+        cmd,
+        LoadPlaylistInQueue(_)
+            | LoadAlbumInQueue(_)
+            | LoadArtistInQueue(_)
+            | LoadSongToQueue(_)
+            | QueueCommand::LoadLocalLibDirectory(_)
+            | AddSongToQueue(_)
+            | QueueCommand::AddArtistToQueue(_)
+            | AddLocalLibDirectory(_)
+            | QueueCommand::AddPlaylistToQueue(_)
+            | QueueCommand::AddAlbumToQueue(_)
+            | ClearQueue
+            | RemoveItem(_)
+            | QueueCommand::AddSongAfterCurrent(_)
+            | QueueCommand::AddSongAndPlay(_)
+            | QueueCommand::AddDirectoryAfterCurrent(_)
+            | QueueCommand::AddDirectoryAndPlay(_)
+            | QueueCommand::AddArtistAfterCurrent(_)
+            | QueueCommand::AddArtistAndPlay(_)
+            | QueueCommand::AddAlbumAfterCurrent(_)
+            | QueueCommand::AddAlbumAndPlay(_)
+            | QueueCommand::LoadGenreInQueue(_)
+            | QueueCommand::AddGenreToQueue(_)
+            | QueueCommand::LoadDecadeInQueue(_)
+            | QueueCommand::AddDecadeToQueue(_)
+            | QueueCommand::LoadWorkInQueue(_)
+            | QueueCommand::AddWorkToQueue(_)
+            | QueueCommand::MoveItem(..)
+            | QueueCommand::MoveItemAfterCurrent(_)
+    )
+}
+
+/// Runs a queue restore and, if it moved the current song while playing,
+/// restarts playback at the new current song.
+fn restore_queue(ctx: &CommandContext, restore: impl FnOnce() -> bool) -> bool {
+    let playing = ctx.player_service.get_current_player_info().is_some();
+    let before = ctx.queue_service.get_current_song().map(|s| s.file);
+    if !restore() {
+        return false;
+    }
+    if playing && ctx.queue_service.get_current_song().map(|s| s.file) != before {
+        ctx.player_service.play_from_beginning();
+    }
+    true
+}
+
 fn load_songs_and_play(ctx: &CommandContext, songs: Vec<Song>, notification: &str) {
     let count = songs.len();
     ctx.player_service.stop_current_song();
@@ -73,6 +146,16 @@ fn add_songs_to_queue(ctx: &CommandContext, songs: &[Song], notification: &str)
 
 #[allow(clippy::too_many_lines)]
 pub fn handle_queue_command(cmd: QueueCommand, ctx: &CommandContext) {
+    // Another profile's playlists are not there for this one.
+    if let LoadPlaylistInQueue(pl_id) | QueueCommand::AddPlaylistToQueue(pl_id) = &cmd {
+        if !ctx.playlist_service.is_visible_to(pl_id, ctx.profile.as_deref()) {
+            ctx.send_error("Playlist not found");
+            return;
+        }
+    }
+    if changes_queue(&cmd) {
+        ctx.queue_service.checkpoint();
+    }
     match cmd {
         AddSongToQueue(song_id) => {
             ctx.queue_service.add_song_by_id(&song_id);
@@ -152,6 +235,14 @@ pub fn handle_queue_command(cmd: QueueCommand, ctx: &CommandContext) {
             let songs = get_songs_from_decade(ctx, &decade);
             add_songs_to_queue(ctx, &songs, &format!("songs from '{decade}' added to queue"));
         }
+        QueueCommand::LoadWorkInQueue(id) => {
+            let songs = get_songs_from_work(ctx, &id);
+            load_songs_and_play(ctx, songs, "movements loaded into queue");
+        }
+        QueueCommand::AddWorkToQueue(id) => {
+            let songs = get_songs_from_work(ctx, &id);
+            add_songs_to_queue(ctx, &songs, "movements added to queue");
+        }
         QueueCommand::MoveItem(from, to) => {
             ctx.queue_service.move_item(&from, &to);
         }
@@ -159,15 +250,67 @@ pub fn handle_queue_command(cmd: QueueCommand, ctx: &CommandContext) {
             ctx.queue_service.move_item_after_current(&from);
             ctx.send_notification("Song moved after current");
         }
+        QueueCommand::SetAutoContinue(enabled) => {
+            ctx.queue_service.set_auto_continue(enabled);
+            let mut settings = ctx.config_store.get_settings();
+            settings.playback_queue_settings.auto_continue = enabled;
+            ctx.config_store.save_settings(&settings);
+            ctx.send_event(StateChangeEvent::AutoContinueChangedEvent(enabled));
+        }
+        QueueCommand::Undo => {
+            if restore_queue(ctx, || ctx.queue_service.undo()) {
+                ctx.send_notification("Queue change undone");
+            } else {
+                ctx.send_error("Nothing to undo");
+            }
+        }
+        QueueCommand::Redo => {
+            if restore_queue(ctx, || ctx.queue_service.redo()) {
+                ctx.send_notification("Queue change redone");
+            } else {
+                ctx.send_error("Nothing to redo");
+            }
+        }
+        QueueCommand::SaveSnapshot(name) => {
+            if ctx.queue_service.save_snapshot(&name) {
+                ctx.send_notification(&format!("Queue snapshot {name} saved"));
+                ctx.send_event(StateChangeEvent::QueueSnapshotsEvent(ctx.queue_service.get_snapshots()));
+            } else {
+                ctx.send_error("Queue is empty");
+            }
+        }
+        QueueCommand::RestoreSnapshot(name) => {
+            if !ctx.queue_service.has_snapshot(&name) {
+                ctx.send_error(&format!("No queue snapshot named {name}"));
+                return;
+            }
+            ctx.queue_service.checkpoint();
+            if restore_queue(ctx, || ctx.queue_service.restore_snapshot(&name)) {
+                ctx.send_event(StateChangeEvent::PlaybackModeChangedEvent(ctx.queue_service.get_playback_mode()));
+                ctx.send_notification(&format!("Queue snapshot {name} restored"));
+            } else {
+                ctx.send_error(&format!("No queue snapshot named {name}"));
+            }
+        }
+        QueueCommand::DeleteSnapshot(name) => {
+            if ctx.queue_service.delete_snapshot(&name) {
+                ctx.send_event(StateChangeEvent::QueueSnapshotsEvent(ctx.queue_service.get_snapshots()));
+            } else {
+                ctx.send_error(&format!("No queue snapshot named {name}"));
+            }
+        }
+        QueueCommand::QuerySnapshots => {
+            ctx.send_event(StateChangeEvent::QueueSnapshotsEvent(ctx.queue_service.get_snapshots()));
+        }
         LoadPlaylistInQueue(pl_id) => {
             ctx.player_service.stop_current_song();
-            let pl_songs = ctx.playlist_service.get_playlist_page_by_name(&pl_id, 0, 20000).items;
+            let pl_songs = ctx.playlist_service.get_all_items(&pl_id);
             ctx.queue_service.replace_all(pl_songs);
             ctx.player_service.play_from_beginning();
             ctx.send_notification("Playlist loaded into queue");
         }
         QueueCommand::AddPlaylistToQueue(pl_id) => {
-            let pl_songs = ctx.playlist_service.get_playlist_page_by_name(&pl_id, 0, 20000).items;
+            let pl_songs = ctx.playlist_service.get_all_items(&pl_id);
             add_songs_to_queue(ctx, &pl_songs, "songs added to queue");
             ctx.send_notification("Playlist added to queue");
         }
@@ -0,0 +1,166 @@
+//! Scheduled playback: alarms and timed radio/playlist starts.
+//!
+//! [`run_scheduler`] wakes at the start of every minute, reads
+//! `Settings::schedules` and fires each enabled schedule due in that minute
+//! by sending the same `UserCommand`s the UI would — a volume, then
+//! `AddSongAndPlay` or `LoadPlaylistInQueue` — for the profile that saved
+//! the schedule, so the handlers stay unaware of it. A grouped multiroom
+//! follower never fires its own schedules; the leader's drive the group. A
+//! fade-in starts at volume zero and raises it once a second, and gives up
+//! as soon as the volume is changed by anyone else, another schedule fires
+//! or someone else takes over playback (see [`FadeIn`]).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{Local, Timelike};
+use log::{info, warn};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+
+use api_models::auth::Role;
+use api_models::common::{QueueCommand, SystemRequest, UserCommand};
+use api_models::settings::{Schedule, ScheduledAction};
+use api_models::state::{NextAlarm, StateChangeEvent};
+use config::ArcConfiguration;
+
+use crate::command_handler::ProfileCommand;
+
+/// How long after the minute boundary the schedules are checked, so a
+/// slightly early wake-up still lands in the right minute.
+const CHECK_DELAY: Duration = Duration::from_millis(100);
+
+/// The cancellation token of the running fade-in. Shared with the command
+/// handler, which cancels it when a command not sent by the scheduler
+/// controls playback.
+#[derive(Debug, Default)]
+pub struct FadeIn {
+    running: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl FadeIn {
+    /// Cancels the running fade-in, if any.
+    pub fn cancel(&self) {
+        if let Some(cancelled) = self.running.lock().expect("fade-in lock poisoned").take() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Cancels the running fade-in and hands out the token of a new one.
+    fn start(&self) -> Arc<AtomicBool> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let previous = self.running.lock().expect("fade-in lock poisoned").replace(cancelled.clone());
+        if let Some(previous) = previous {
+            previous.store(true, Ordering::Relaxed);
+        }
+        cancelled
+    }
+}
+
+pub async fn run_scheduler(
+    config: ArcConfiguration,
+    commands_tx: mpsc::Sender<ProfileCommand>,
+    fade_in: Arc<FadeIn>,
+    follower_active: Arc<AtomicBool>,
+    state_changes_tx: broadcast::Sender<StateChangeEvent>,
+) {
+    let mut checked_minute = None;
+    let mut next_alarm = None;
+    loop {
+        let now = Local::now().naive_local();
+        let minute = now.with_second(0).and_then(|t| t.with_nanosecond(0));
+        if checked_minute != minute {
+            checked_minute = minute;
+            let settings = config.get_settings();
+            for schedule in settings.schedules.iter().filter(|s| s.enabled && s.is_due(now)) {
+                if follower_active.load(Ordering::Relaxed) {
+                    info!("Schedule '{}' skipped: playing as a multiroom follower", schedule.name);
+                    continue;
+                }
+                info!("Schedule '{}' fired", schedule.name);
+                let _ = state_changes_tx.send(StateChangeEvent::NotificationSuccess(format!("{} started", schedule.name)));
+                let volume = schedule.volume.or(settings.volume_ctrl_settings.saved_volume);
+                let cancelled = fade_in.start();
+                tokio::spawn(fire(schedule.clone(), volume, config.clone(), commands_tx.clone(), cancelled));
+            }
+            let next = NextAlarm::of(&settings.schedules, now);
+            if next != next_alarm {
+                next_alarm.clone_from(&next);
+                let _ = state_changes_tx.send(StateChangeEvent::SchedulesEvent(settings.schedules, next));
+            }
+        }
+        let elapsed = Duration::from_millis(u64::from(now.second()) * 1000 + u64::from(now.nanosecond() / 1_000_000));
+        sleep(Duration::from_mins(1).saturating_sub(elapsed) + CHECK_DELAY).await;
+    }
+}
+
+/// The `SchedulesEvent` for `schedules` as of now.
+pub fn schedules_event(schedules: Vec<Schedule>) -> StateChangeEvent {
+    let next = NextAlarm::of(&schedules, Local::now().naive_local());
+    StateChangeEvent::SchedulesEvent(schedules, next)
+}
+
+/// Starts the schedule's playback at `volume`, fading in when it asks to
+/// until `cancelled` is set.
+async fn fire(
+    schedule: Schedule,
+    volume: Option<u8>,
+    config: ArcConfiguration,
+    commands_tx: mpsc::Sender<ProfileCommand>,
+    cancelled: Arc<AtomicBool>,
+) {
+    let profile = schedule.profile.as_deref();
+    let fade = volume.filter(|v| *v > 0 && schedule.fade_in_secs > 0);
+    let start_volume = if fade.is_some() { Some(0) } else { volume };
+    if let Some(start_volume) = start_volume {
+        if !send(&commands_tx, profile, UserCommand::System(SystemRequest::SetVol(start_volume))).await {
+            return;
+        }
+    }
+    let play = match schedule.action {
+        ScheduledAction::Radio(url) => QueueCommand::AddSongAndPlay(url),
+        ScheduledAction::Playlist(id) => QueueCommand::LoadPlaylistInQueue(id),
+    };
+    if !send(&commands_tx, profile, UserCommand::Queue(play)).await {
+        return;
+    }
+    let Some(target) = fade else {
+        return;
+    };
+    let mut current = 0;
+    for second in 1..=schedule.fade_in_secs {
+        sleep(Duration::from_secs(1)).await;
+        if cancelled.load(Ordering::Relaxed) {
+            info!("Fade-in of '{}' stopped: playback was taken over", schedule.name);
+            return;
+        }
+        if config.get_settings().volume_ctrl_settings.saved_volume != Some(current) {
+            info!("Fade-in of '{}' stopped: the volume was changed", schedule.name);
+            return;
+        }
+        let step = u8::try_from(u32::from(target) * second / schedule.fade_in_secs).unwrap_or(target);
+        if step != current {
+            current = step;
+            if !send(&commands_tx, profile, UserCommand::System(SystemRequest::SetVol(current))).await {
+                return;
+            }
+        }
+    }
+}
+
+async fn send(commands_tx: &mpsc::Sender<ProfileCommand>, profile: Option<&str>, command: UserCommand) -> bool {
+    // A schedule plays with a listener's rights, for the profile that saved it.
+    let cmd = ProfileCommand {
+        profile: profile.map(str::to_owned),
+        role: Role::Listener,
+        command,
+    };
+    match commands_tx.send(cmd).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Scheduled command not sent: {e}");
+            false
+        }
+    }
+}
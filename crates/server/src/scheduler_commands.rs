@@ -0,0 +1,100 @@
+//! Scheduler commands: create, update, enable/disable and delete the
+//! schedules kept in `Settings::schedules`. The `scheduler` task reads them
+//! every minute, so changes apply from the next minute on. A schedule
+//! plays for the profile that saved it, so its playlist has to be one that
+//! profile sees. Only that profile changes or deletes it, or an admin, whose
+//! edits leave it with its profile.
+
+use api_models::auth::Role;
+use api_models::common::SchedulerCommand;
+use api_models::settings::{Schedule, ScheduledAction};
+use api_models::validator::Validate;
+use uuid::Uuid;
+
+use crate::command_context::CommandContext;
+use crate::scheduler::schedules_event;
+
+pub fn handle_scheduler_command(cmd: SchedulerCommand, ctx: &CommandContext) {
+    match cmd {
+        SchedulerCommand::QuerySchedules => {}
+        SchedulerCommand::SaveSchedule(mut schedule) => {
+            schedule.name = schedule.name.trim().to_string();
+            let mut settings = ctx.config_store.get_settings();
+            let existing = settings.schedules.iter().position(|s| s.id == schedule.id);
+            match existing.map(|index| &settings.schedules[index]) {
+                Some(saved) if !may_change(saved, ctx) => {
+                    ctx.send_error(NOT_YOURS);
+                    return;
+                }
+                Some(saved) => schedule.profile.clone_from(&saved.profile),
+                None => schedule.profile.clone_from(&ctx.profile),
+            }
+            if let Err(e) = check_schedule(&schedule, ctx) {
+                ctx.send_error(&e);
+                return;
+            }
+            if schedule.id.is_empty() {
+                schedule.id = Uuid::new_v4().to_string();
+            }
+            let name = schedule.name.clone();
+            match existing {
+                Some(index) => settings.schedules[index] = schedule,
+                None => settings.schedules.push(schedule),
+            }
+            ctx.config_store.save_settings(&settings);
+            ctx.send_notification(&format!("Schedule {name} saved"));
+        }
+        SchedulerCommand::DeleteSchedule(id) => {
+            let mut settings = ctx.config_store.get_settings();
+            let Some(index) = settings.schedules.iter().position(|s| s.id == id) else {
+                ctx.send_error("Schedule not found");
+                return;
+            };
+            if !may_change(&settings.schedules[index], ctx) {
+                ctx.send_error(NOT_YOURS);
+                return;
+            }
+            settings.schedules.remove(index);
+            ctx.config_store.save_settings(&settings);
+        }
+        SchedulerCommand::SetScheduleEnabled(id, enabled) => {
+            let mut settings = ctx.config_store.get_settings();
+            let Some(schedule) = settings.schedules.iter_mut().find(|s| s.id == id) else {
+                ctx.send_error("Schedule not found");
+                return;
+            };
+            if !may_change(schedule, ctx) {
+                ctx.send_error(NOT_YOURS);
+                return;
+            }
+            schedule.enabled = enabled;
+            ctx.config_store.save_settings(&settings);
+        }
+    }
+    ctx.send_event(schedules_event(ctx.config_store.get_settings().schedules));
+}
+
+const NOT_YOURS: &str = "This schedule belongs to another profile";
+
+/// Whether the command may change `schedule`: one of its profile's own, or
+/// any when an admin sent it.
+fn may_change(schedule: &Schedule, ctx: &CommandContext) -> bool {
+    ctx.role == Role::Admin || schedule.profile == ctx.profile
+}
+
+fn check_schedule(schedule: &Schedule, ctx: &CommandContext) -> Result<(), String> {
+    schedule.validate().map_err(|e| format!("Invalid schedule: {e}"))?;
+    let target = match &schedule.action {
+        ScheduledAction::Radio(url) => url,
+        ScheduledAction::Playlist(id) => id,
+    };
+    if target.trim().is_empty() {
+        return Err("Choose a station or playlist to play".to_string());
+    }
+    if let ScheduledAction::Playlist(id) = &schedule.action {
+        if !ctx.playlist_service.is_visible_to(id, schedule.profile.as_deref()) {
+            return Err("Playlist not found".to_string());
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,17 @@
 //! HTTP/HTTPS server and WebSocket endpoint (axum).
 //!
 //! Serves the embedded web UI (rust-embed; from disk in debug builds), the
-//! REST-ish `/api/*` routes (settings, artwork, local-browser audio
-//! streaming with range support) and `/api/ws`, where commands come in as
+//! REST-ish `/api/*` routes (settings, album artwork upload, local-browser
+//! audio streaming with range support or transcoded, see [`transcode`]),
+//! the `/stream` web radio output, ZIP downloads of albums, playlists and
+//! the queue (see [`download`]) and `/api/ws`, where commands come in as
 //! JSON `UserCommand`s and every `StateChangeEvent` is fanned out to all
-//! connected clients through one broadcast channel. Audio-card enumeration
-//! is cached at startup because probing drivers (ASIO especially) can
-//! disrupt a live stream.
+//! connected clients through one broadcast channel. A WebSocket session
+//! belongs to a user profile; it only gets the `ProfileEvent`s of that
+//! profile, and the current song with that profile's likes and plays.
+//! Every route passes the access control in [`auth`]. Audio-card
+//! enumeration is cached at startup because probing drivers (ASIO
+//! especially) can disrupt a live stream.
 
 use std::collections::HashMap;
 use std::env;
@@ -18,15 +23,16 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path as AxumPath, Query, Request, State,
+        ConnectInfo, DefaultBodyLimit, FromRef, Path as AxumPath, Query, Request, State,
     },
     http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware,
     response::{IntoResponse, Response},
-    routing::get,
-    Json, Router,
+    routing::{get, post},
+    Extension, Json, Router,
 };
 use axum_server::tls_rustls::RustlsConfig;
 use futures::{SinkExt, StreamExt};
@@ -37,22 +43,33 @@ use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::{broadcast, mpsc};
 use tower_http::{
     compression::CompressionLayer,
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, CorsLayer},
     services::ServeDir,
     set_header::SetResponseHeaderLayer,
 };
 
-use api_models::common::UserCommand;
+use api_models::auth::Role;
+use api_models::common::{MetadataCommand, UserCommand};
+use api_models::player::Song;
 use api_models::serde_json;
 use api_models::settings::Settings;
 use api_models::state::StateChangeEvent;
 use config::Configuration;
+use metadata::artwork::{ArtworkStore, ARTWORK_DIR, THUMBS_DIR};
+use metadata::metadata_service::MetadataService;
+use playback::rsp::output_tee::OutputTee;
+
+use crate::auth::{self, Auth, Caller, ServedOverTls};
+use crate::command_handler::ProfileCommand;
+use crate::download::{DownloadSource, Downloads};
+use crate::stream_output::StreamOutput;
+use crate::transcode;
 
 static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
 static ACTIVE_USERS: AtomicUsize = AtomicUsize::new(0);
 
 type Config = Arc<Configuration>;
-type UserCommandSender = mpsc::Sender<UserCommand>;
+type UserCommandSender = mpsc::Sender<ProfileCommand>;
 
 #[derive(RustEmbed)]
 #[folder = "../../dist/web-ui/public"]
@@ -62,28 +79,90 @@ struct StaticContentDir;
 struct AppState {
     config: Config,
     user_commands_tx: UserCommandSender,
-    ws_broadcast: broadcast::Sender<Arc<String>>,
+    ws_broadcast: broadcast::Sender<Arc<WsMessage>>,
+    /// `None` in degraded mode, where the library is not open.
+    metadata_service: Option<Arc<MetadataService>>,
     /// Cached list of available output devices. Enumerating output devices is
     /// expensive and, on the Windows ASIO host, probing the drivers can disrupt
     /// the live output stream (see `get_cpal_audio_cards`). So we enumerate once
     /// (eagerly at startup, before playback runs) and reuse the result until an
     /// explicit rescan is requested via `GET /api/settings?rescan=true`.
     audio_cards_cache: Arc<Mutex<Option<Vec<api_models::common::AudioCard>>>>,
+    /// `None` in degraded mode, where nothing plays.
+    stream_output: Option<StreamOutput>,
+    /// `None` in degraded mode, where the library is not open.
+    downloads: Option<Downloads>,
+    auth: Auth,
+}
+
+/// Which WebSocket sessions an event is for.
+#[derive(Debug)]
+enum Audience {
+    Everyone,
+    /// The sessions of one user profile; `None` is the shared profile.
+    Profile(Option<String>),
+}
+
+/// A serialized state change on its way to the WebSocket sessions.
+#[derive(Debug)]
+struct WsMessage {
+    audience: Audience,
+    json: String,
+    /// The new current song, which sessions of a profile get with that
+    /// profile's statistics.
+    song: Option<Song>,
+}
+
+impl WsMessage {
+    fn reaches(&self, profile: Option<&str>) -> bool {
+        match &self.audience {
+            Audience::Everyone => true,
+            Audience::Profile(audience) => audience.as_deref() == profile,
+        }
+    }
+
+    /// The JSON a session of `profile` gets: the current song with the
+    /// profile's own statistics.
+    fn json_for(&self, profile: Option<&str>, metadata_service: Option<&MetadataService>) -> String {
+        if let (Some(song), Some(_), Some(metadata_service)) = (&self.song, profile, metadata_service) {
+            let mut song = song.clone();
+            song.statistics = metadata_service.get_song_statistics(profile, &song.file);
+            if let Ok(json) = serde_json::to_string(&StateChangeEvent::CurrentSongEvent(song)) {
+                return json;
+            }
+        }
+        self.json.clone()
+    }
+}
+
+impl FromRef<AppState> for Auth {
+    fn from_ref(state: &AppState) -> Self {
+        state.auth.clone()
+    }
 }
 
 pub fn start(
     mut state_changes_rx: broadcast::Receiver<StateChangeEvent>,
     user_commands_tx: UserCommandSender,
+    metadata_service: Arc<MetadataService>,
     config: &Config,
+    output_tee: OutputTee,
+    downloads: Downloads,
+    auth: Auth,
 ) -> (impl Future<Output = ()>, Option<impl Future<Output = ()>>, impl Future<Output = ()>) {
-    let (ws_broadcast, _) = broadcast::channel::<Arc<String>>(32);
+    let (ws_broadcast, _) = broadcast::channel::<Arc<WsMessage>>(32);
+    let stream_output = StreamOutput::new(output_tee);
     let state = AppState {
         config: config.clone(),
         user_commands_tx,
         ws_broadcast: ws_broadcast.clone(),
+        metadata_service: Some(metadata_service),
         // Enumerate now, while nothing is playing yet — this is the one moment
         // an ASIO driver probe cannot interrupt a live stream.
         audio_cards_cache: Arc::new(Mutex::new(Some(enumerate_audio_cards()))),
+        stream_output: Some(stream_output.clone()),
+        downloads: Some(downloads),
+        auth,
     };
 
     let app = build_router(state);
@@ -102,11 +181,23 @@ pub fn start(
                     }
                     Ok(ev) => {
                         trace!("Received state changed event {ev:?}");
-                        let Ok(json_msg) = serde_json::to_string(&ev) else {
+                        let (audience, ev) = match ev {
+                            StateChangeEvent::ProfileEvent(profile, ev) => (Audience::Profile(profile), *ev),
+                            ev => (Audience::Everyone, ev),
+                        };
+                        let song = match (&audience, &ev) {
+                            (Audience::Everyone, StateChangeEvent::CurrentSongEvent(song)) => {
+                                stream_output.song_changed(song);
+                                Some(song.clone())
+                            }
+                            _ => None,
+                        };
+                        let Ok(json) = serde_json::to_string(&ev) else {
                             error!("Failed to serialize state change event: {ev:?}");
                             continue;
                         };
-                        if !json_msg.is_empty() && ws_broadcast.send(Arc::new(json_msg)).is_err() {
+                        let message = WsMessage { audience, json, song };
+                        if !message.json.is_empty() && ws_broadcast.send(Arc::new(message)).is_err() {
                             trace!("No active ws clients, not sending state change");
                         }
                     }
@@ -121,7 +212,10 @@ pub fn start(
     let http_handle = async move {
         let addr = SocketAddr::new(bind_addr, http_port);
         info!("HTTP is listening on port {http_port}");
-        if let Err(e) = axum_server::bind(addr).serve(http_app.into_make_service()).await {
+        if let Err(e) = axum_server::bind(addr)
+            .serve(http_app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+        {
             error!("HTTP server exited with error: {e}");
         }
     };
@@ -137,7 +231,13 @@ pub fn start(
             };
             let addr = SocketAddr::new(bind_addr, https_port);
             info!("HTTPS listening on port {https_port}");
-            if let Err(e) = axum_server::bind_rustls(addr, tls_config).serve(app.into_make_service()).await {
+            if let Err(e) = axum_server::bind_rustls(addr, tls_config)
+                .serve(
+                    app.layer(Extension(ServedOverTls))
+                        .into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+            {
                 error!("HTTPS server exited with error: {e}");
             }
         })
@@ -149,10 +249,8 @@ pub fn start(
     (http_handle, https_handle, ws_handle)
 }
 
-pub fn start_degraded(config: &Config, error: &anyhow::Error) -> impl Future<Output = ()> {
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
-        .allow_origin(Any);
+pub fn start_degraded(config: &Config, error: &anyhow::Error, auth: Auth) -> impl Future<Output = ()> {
+    let cors = cors(auth.clone());
 
     let error_msg = error.to_string();
     let degraded_state = config.clone();
@@ -166,14 +264,20 @@ pub fn start_degraded(config: &Config, error: &anyhow::Error) -> impl Future<Out
                 move || async move { msg }
             }),
         )
+        .merge(auth::routes())
         .fallback(spa_or_static_fallback)
+        .layer(middleware::from_fn_with_state(auth.clone(), auth::guard))
         .with_state(AppState {
             config: degraded_state,
             user_commands_tx: mpsc::channel(1).0,
             ws_broadcast: broadcast::channel(1).0,
+            metadata_service: None,
             // Degraded mode may itself stem from an audio failure — enumerate
             // lazily on first request rather than risk a probe at startup.
             audio_cards_cache: Arc::new(Mutex::new(None)),
+            stream_output: None,
+            downloads: None,
+            auth,
         })
         .layer(cors);
 
@@ -181,25 +285,52 @@ pub fn start_degraded(config: &Config, error: &anyhow::Error) -> impl Future<Out
 
     async move {
         let addr = SocketAddr::new(bind_addr, http_port);
-        if let Err(e) = axum_server::bind(addr).serve(app.into_make_service()).await {
+        if let Err(e) = axum_server::bind(addr)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+        {
             error!("Degraded HTTP server exited with error: {e}");
         }
     }
 }
 
-fn build_router(state: AppState) -> Router {
-    let cors = CorsLayer::new()
+/// Cross-origin access for the server's own origin and the configured ones,
+/// see [`Auth::allows_origin`].
+fn cors(auth: Auth) -> CorsLayer {
+    CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::DELETE])
-        .allow_origin(Any);
+        .allow_origin(AllowOrigin::predicate(move |origin, request| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| auth.allows_origin(origin, &request.uri, &request.headers))
+        }))
+}
+
+fn build_router(state: AppState) -> Router {
+    let cors = cors(state.auth.clone());
 
     let cache_3d = SetResponseHeaderLayer::if_not_present(header::CACHE_CONTROL, HeaderValue::from_static("max-age=259200"));
 
-    let artwork = ServeDir::new("artwork");
+    let artwork = ServeDir::new(ARTWORK_DIR);
+    // Artwork stored before thumbnails existed has none until the next scan.
+    let thumbs = ServeDir::new(Path::new(ARTWORK_DIR).join(THUMBS_DIR)).fallback(ServeDir::new(ARTWORK_DIR));
+    let guard = middleware::from_fn_with_state(state.auth.clone(), auth::guard);
 
     Router::new()
         .route("/api/ws", get(ws_handler))
         .route("/api/settings", get(get_settings).post(save_settings))
-        .route("/music/{*path}", get(serve_music))
+        .route(
+            "/api/albums/{album_id}/artwork",
+            post(upload_album_artwork).layer(DefaultBodyLimit::max(ARTWORK_UPLOAD_LIMIT)),
+        )
+        .merge(auth::routes())
+        .nest_service(
+            "/artwork/thumbs",
+            tower::ServiceBuilder::new()
+                .layer(cache_3d.clone())
+                .layer(CompressionLayer::new())
+                .service(thumbs),
+        )
         .nest_service(
             "/artwork",
             tower::ServiceBuilder::new()
@@ -209,15 +340,57 @@ fn build_router(state: AppState) -> Router {
         )
         .fallback(spa_or_static_fallback)
         .layer(CompressionLayer::new())
+        // Added after the compression layer: audio does not compress, and
+        // endless, transcoded or zipped streams must reach the client as
+        // they are produced.
+        .route("/music/{*path}", get(serve_music))
+        .route("/stream", get(serve_stream))
+        .route("/api/download/album/{album_id}", get(download_album))
+        .route("/api/download/playlist/{playlist_id}", get(download_playlist))
+        .route("/api/download/queue", get(download_queue))
+        .layer(guard)
         .layer(cors)
         .with_state(state)
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(move |socket| user_connected(socket, state.ws_broadcast.subscribe(), state.user_commands_tx))
+/// The session's profile comes from the `profile` query parameter, see
+/// [`Auth::session_profile`]. Browsers send the page's origin with the
+/// upgrade, which has to be one the API allows: a foreign page must not act
+/// with the visitor's session cookie.
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<HashMap<String, String>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin = origin.to_str().unwrap_or_default();
+        if !state.auth.allows_origin(origin, &uri, &headers) {
+            warn!("Refused WebSocket from foreign origin {origin}");
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+    let profile = state.auth.session_profile(&caller, query.get("profile").map(String::as_str));
+    ws.on_upgrade(move |socket| {
+        user_connected(
+            socket,
+            state.ws_broadcast.subscribe(),
+            state.user_commands_tx,
+            state.metadata_service,
+            state.auth,
+            caller,
+            profile,
+        )
+    })
 }
 
-async fn get_settings(State(state): State<AppState>, Query(query): Query<HashMap<String, String>>) -> Json<Settings> {
+async fn get_settings(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Settings> {
     let mut settings = state.config.get_settings_mut();
     settings.version = env!("CARGO_PKG_VERSION").to_string();
     settings.demo_mode = env::var("DEMO_MODE").is_ok();
@@ -272,7 +445,13 @@ async fn get_settings(State(state): State<AppState>, Query(query): Query<HashMap
         settings.network_mounts_available = false;
     }
 
-    Json(settings.clone())
+    let mut settings = settings.clone();
+    if caller.role < Some(Role::Admin) {
+        for mount in &mut settings.network_storage_settings.mounts {
+            mount.password = None;
+        }
+    }
+    Json(settings)
 }
 
 /// Return the cached audio-device list, enumerating (once) if the cache is
@@ -398,9 +577,82 @@ async fn save_settings(
     StatusCode::CREATED
 }
 
+/// Largest accepted artwork upload.
+const ARTWORK_UPLOAD_LIMIT: usize = 20 * 1024 * 1024;
+
+/// Stores the picture in the request body and makes it the album's artwork.
+async fn upload_album_artwork(State(state): State<AppState>, AxumPath(album_id): AxumPath<String>, body: Bytes) -> StatusCode {
+    if body.is_empty() {
+        return StatusCode::BAD_REQUEST;
+    }
+    let saved = tokio::task::spawn_blocking(move || ArtworkStore::new(ARTWORK_DIR).save(&body)).await;
+    let image_id = match saved.map_err(anyhow::Error::from).and_then(|result| result) {
+        Ok(id) => id,
+        Err(e) => {
+            error!("Failed to store uploaded artwork: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    let cmd = ProfileCommand {
+        profile: None,
+        role: Role::Admin,
+        command: UserCommand::Metadata(MetadataCommand::SetAlbumImage(album_id, image_id)),
+    };
+    if state.user_commands_tx.send(cmd).await.is_err() {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    StatusCode::ACCEPTED
+}
+
+/// The web radio output, while it is enabled in the settings.
+async fn serve_stream(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let settings = state.config.get_settings().stream_output_settings;
+    match state.stream_output.filter(|_| settings.enabled) {
+        Some(output) => output.listen(&settings, &headers),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn download_album(
+    State(state): State<AppState>,
+    AxumPath(album_id): AxumPath<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    download(&state, DownloadSource::Album(album_id), &query).await
+}
+
+/// As the session's profile, see [`Auth::session_profile`].
+async fn download_playlist(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    AxumPath(playlist_id): AxumPath<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let profile = state.auth.session_profile(&caller, query.get("profile").map(String::as_str));
+    download(&state, DownloadSource::Playlist(playlist_id, profile), &query).await
+}
+
+async fn download_queue(State(state): State<AppState>, Query(query): Query<HashMap<String, String>>) -> Response {
+    download(&state, DownloadSource::Queue, &query).await
+}
+
+async fn download(state: &AppState, source: DownloadSource, query: &HashMap<String, String>) -> Response {
+    let Some(downloads) = &state.downloads else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let settings = state.config.get_settings();
+    downloads.respond(source, query, &settings).await
+}
+
 const STREAM_CHUNK: u64 = 300 * 1024; // 300 KB per chunk
 
-async fn serve_music(State(state): State<AppState>, AxumPath(path): AxumPath<String>, headers: HeaderMap) -> Response {
+async fn serve_music(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    AxumPath(path): AxumPath<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
     if path.contains("..") {
         return StatusCode::FORBIDDEN.into_response();
     }
@@ -409,12 +661,24 @@ async fn serve_music(State(state): State<AppState>, AxumPath(path): AxumPath<Str
         .and_then(|v| v.to_str().ok())
         .map(std::borrow::ToOwned::to_owned);
 
-    let music_dirs = state.config.get_settings().metadata_settings.effective_directories();
+    let settings = state.config.get_settings();
+    let music_dirs = settings.metadata_settings.library_directories();
     let file_path = music_dirs
         .iter()
         .map(|dir| PathBuf::from(dir).join(&path))
         .find(|p| p.exists() && p.is_file());
 
+    let remote = transcode::is_remote(peer.ip(), &headers);
+    let mut source_kbps = None;
+    if remote && settings.transcode_settings.remote_max_bitrate_kbps > 0 {
+        if let Some(file_path) = &file_path {
+            source_kbps = transcode::source_kbps(path.clone(), file_path.clone(), music_dirs.clone()).await;
+        }
+    }
+    if let Some(request) = transcode::plan(&path, &query, &headers, &settings.transcode_settings, remote, source_kbps) {
+        return transcode::respond(path, music_dirs, request).await;
+    }
+
     let Some(file_path) = file_path else {
         return StatusCode::NOT_FOUND.into_response();
     };
@@ -566,10 +830,18 @@ async fn spa_or_static_fallback(uri: Uri, _req: Request) -> Response {
     }
 }
 
-async fn user_connected(ws: WebSocket, mut ws_rx: broadcast::Receiver<Arc<String>>, user_commands_tx: UserCommandSender) {
+async fn user_connected(
+    ws: WebSocket,
+    mut ws_rx: broadcast::Receiver<Arc<WsMessage>>,
+    user_commands_tx: UserCommandSender,
+    metadata_service: Option<Arc<MetadataService>>,
+    auth: Auth,
+    caller: Caller,
+    profile: Option<String>,
+) {
     let user_id = NEXT_USER_ID.fetch_add(1, Ordering::Relaxed);
 
-    debug!("new websocket client: {user_id}");
+    debug!("new websocket client: {user_id}, profile {profile:?}");
     let current_users = ACTIVE_USERS.fetch_add(1, Ordering::SeqCst) + 1;
     info!("Number of active websockets is: {current_users}");
 
@@ -595,6 +867,25 @@ async fn user_connected(ws: WebSocket, mut ws_rx: broadcast::Receiver<Arc<String
                         info!("Got command from user {user_id}: {cmd:?}");
                         match serde_json::from_str::<UserCommand>(cmd) {
                             Ok(pc) => {
+                                let Some(role) = auth.current_role(&caller) else {
+                                    debug!("Session of user {user_id} ended, closing its websocket.");
+                                    break;
+                                };
+                                if role < pc.required_role() {
+                                    warn!("Refused command from user {user_id} with role {role:?}: {cmd:?}");
+                                    let refusal = StateChangeEvent::NotificationError("Not allowed for your account".to_owned());
+                                    if let Ok(json_msg) = serde_json::to_string(&refusal) {
+                                        if to_user_ws.send(Message::text(json_msg)).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                    continue;
+                                }
+                                let pc = ProfileCommand {
+                                    profile: profile.clone(),
+                                    role,
+                                    command: pc,
+                                };
                                 if user_commands_tx.send(pc).await.is_err() {
                                     error!("failed to send user message");
                                     break;
@@ -608,12 +899,18 @@ async fn user_connected(ws: WebSocket, mut ws_rx: broadcast::Receiver<Arc<String
             },
             result = ws_rx.recv() => {
                 match result {
-                    Ok(json_msg) => {
-                        if to_user_ws
-                            .send(Message::text(json_msg.as_ref().clone()))
-                            .await
-                            .is_err()
-                        {
+                    Ok(message) => {
+                        if !message.reaches(profile.as_deref()) {
+                            continue;
+                        }
+                        // Logging out or a revoked token ends the session for
+                        // listening sockets too, not only at their next command.
+                        if auth.current_role(&caller).is_none() {
+                            debug!("Session of user {user_id} ended, closing its websocket.");
+                            break;
+                        }
+                        let json_msg = message.json_for(profile.as_deref(), metadata_service.as_deref());
+                        if to_user_ws.send(Message::text(json_msg)).await.is_err() {
                             debug!("Failed to send message to user {user_id}, client disconnected.");
                             break;
                         }
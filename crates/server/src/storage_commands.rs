@@ -1,11 +1,16 @@
 //! Storage commands: SMB/NFS mount/unmount via `MountService`, persisting
-//! successful mounts into settings and reporting mount/music-dir status.
+//! successful mounts into settings and reporting mount/music-dir status, plus
+//! writing measured loudness back into the files as `REPLAYGAIN_*` tags.
 
 use api_models::common::StorageCommand;
+use api_models::settings::Settings;
 use api_models::state::StateChangeEvent;
 use log::error;
+use metadata::loudness_tag_writer::LoudnessTagWriter;
 
-use crate::command_context::CommandContext;
+use crate::command_context::{BackgroundJob, CommandContext};
+
+static WRITING_LOUDNESS_TAGS: BackgroundJob = BackgroundJob::new("loudness-tags", "ReplayGain tags are already being written");
 
 #[allow(clippy::too_many_lines)]
 pub fn handle_storage_command(cmd: StorageCommand, ctx: &CommandContext) {
@@ -120,5 +125,47 @@ pub fn handle_storage_command(cmd: StorageCommand, ctx: &CommandContext) {
                 ctx.send_error(&format!("External mount not found: {mount_point}"));
             }
         }
+        StorageCommand::WriteLoudnessTags(dry_run) => {
+            let music_dirs = music_dir_writability(&ctx.config_store.get_settings());
+            let writer = LoudnessTagWriter::new(ctx.song_repository.clone(), ctx.loudness_repository.clone());
+            WRITING_LOUDNESS_TAGS.spawn(ctx, move || {
+                let report = writer.run(&music_dirs, dry_run);
+                let message = if dry_run {
+                    format!("ReplayGain dry run: {} files would be tagged", report.changed)
+                } else {
+                    format!("ReplayGain tags written to {} files", report.changed)
+                };
+                vec![
+                    StateChangeEvent::LoudnessTagReportEvent(report),
+                    StateChangeEvent::NotificationSuccess(message),
+                ]
+            });
+        }
     }
 }
+
+/// Music directories in configuration order, each with whether files under it
+/// may be written. Network mounts report their own status; anything that
+/// cannot be checked counts as read-only.
+fn music_dir_writability(settings: &Settings) -> Vec<(String, bool)> {
+    let dir_statuses = crate::mount_service::MountService::query_music_dir_status(&settings.metadata_settings);
+    let mount_statuses = crate::mount_service::MountService::query_mount_status(&settings.network_storage_settings);
+    settings
+        .metadata_settings
+        .effective_directories()
+        .into_iter()
+        .map(|dir| {
+            let writable = dir_statuses
+                .iter()
+                .find(|s| s.path == dir)
+                .map(|s| s.readable && s.writable)
+                .or_else(|| {
+                    mount_statuses
+                        .iter()
+                        .find(|m| m.mount_point == dir)
+                        .map(|m| m.is_mounted && m.readable && m.writable)
+                });
+            (dir, writable.unwrap_or(false))
+        })
+        .collect()
+}
@@ -0,0 +1,295 @@
+//! Web radio output: `/stream` serves what the player plays as an endless
+//! MP3, Ogg/Opus or FLAC stream, Icecast-style, so internet-radio apps and
+//! other devices can tune in.
+//!
+//! Every listener subscribes to the player's `OutputTee` (post-DSP PCM) and
+//! gets its own `StreamEncoder`, so listeners join at any time and a slow
+//! one only loses chunks of its own. Resampling and encoding run on a
+//! blocking thread per listener, fed by a task reading the tee, so at most
+//! `max_listeners` are served and others get 503. While nothing plays,
+//! silence is encoded to keep connections open. Clients asking for
+//! `Icy-MetaData: 1` get the now-playing title, taken from
+//! `CurrentSongEvent`s, every [`ICY_METAINT`] bytes.
+
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::{debug, info, warn};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc, watch};
+use tokio::time::timeout;
+use tokio_stream::wrappers::ReceiverStream;
+
+use api_models::player::Song;
+use api_models::settings::StreamOutputSettings;
+use playback::rsp::encoder::StreamEncoder;
+use playback::rsp::output_tee::{OutputChunk, OutputTee};
+
+/// Audio bytes between two ICY metadata blocks.
+pub const ICY_METAINT: usize = 16_000;
+/// How long the output may stay quiet before silence is sent instead.
+const SILENCE_AFTER: Duration = Duration::from_millis(500);
+/// Encoded chunks buffered per listener before encoding waits for it.
+const LISTENER_BUFFER: usize = 16;
+/// ICY metadata is at most 255 blocks of 16 bytes.
+const MAX_ICY_METADATA: usize = 255 * 16;
+
+#[derive(Clone)]
+pub struct StreamOutput {
+    tee: OutputTee,
+    title: Arc<watch::Sender<String>>,
+    /// Listeners connected now.
+    listeners: Arc<AtomicUsize>,
+}
+
+impl StreamOutput {
+    #[must_use]
+    pub fn new(tee: OutputTee) -> Self {
+        Self {
+            tee,
+            title: Arc::new(watch::channel(String::new()).0),
+            listeners: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Makes `song` the title sent to ICY listeners.
+    pub fn song_changed(&self, song: &Song) {
+        let title = match &song.artist {
+            Some(artist) if !artist.is_empty() => format!("{artist} - {}", song.get_title()),
+            _ => song.get_title(),
+        };
+        self.title.send_if_modified(|current| {
+            let changed = *current != title;
+            *current = title;
+            changed
+        });
+    }
+
+    /// A new listener's endless stream response, or 503 when
+    /// `max_listeners` are connected already.
+    pub fn listen(&self, settings: &StreamOutputSettings, request_headers: &HeaderMap) -> Response {
+        let Some(slot) = ListenerSlot::take(&self.listeners, settings.max_listeners) else {
+            info!("Stream listener refused, {} listening already", settings.max_listeners);
+            return (StatusCode::SERVICE_UNAVAILABLE, "Too many listeners").into_response();
+        };
+        let mut encoder = match StreamEncoder::new(settings.encoding, settings.bitrate_kbps) {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                warn!("Stream output unavailable: {e}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        let icy = request_headers
+            .get("icy-metadata")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.trim() == "1");
+        let mut metadata = icy.then(|| IcyMetadata::new(self.title.subscribe()));
+        let mut chunks = self.tee.subscribe();
+        let (body_tx, body_rx) = mpsc::channel::<Result<Bytes, Infallible>>(LISTENER_BUFFER);
+        info!("Stream listener connected ({} listening)", slot.count());
+
+        // `None` asks for silence.
+        let (input_tx, mut input_rx) = mpsc::channel::<Option<OutputChunk>>(LISTENER_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let input = match timeout(SILENCE_AFTER, chunks.recv()).await {
+                    Ok(Ok(chunk)) => Some(chunk),
+                    Ok(Err(RecvError::Lagged(skipped))) => {
+                        debug!("Stream listener fell behind, {skipped} chunks skipped");
+                        continue;
+                    }
+                    Ok(Err(RecvError::Closed)) => break,
+                    Err(_) => None,
+                };
+                if input_tx.send(input).await.is_err() {
+                    break;
+                }
+            }
+        });
+        tokio::task::spawn_blocking(move || {
+            let mut encoded = encoder.start();
+            loop {
+                let bytes = match encoded {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        warn!("Stream encoding failed: {e}");
+                        break;
+                    }
+                };
+                if !bytes.is_empty() {
+                    let bytes = match &mut metadata {
+                        Some(metadata) => metadata.interleave(&bytes),
+                        None => bytes,
+                    };
+                    if body_tx.blocking_send(Ok(Bytes::from(bytes))).is_err() {
+                        break;
+                    }
+                }
+                encoded = match input_rx.blocking_recv() {
+                    Some(Some(chunk)) => encoder.encode(&chunk),
+                    Some(None) => encoder.encode_silence(SILENCE_AFTER),
+                    None => break,
+                };
+            }
+            drop(slot);
+            info!("Stream listener disconnected");
+        });
+
+        let mut response = Response::builder()
+            .header(header::CONTENT_TYPE, settings.encoding.content_type())
+            .header(header::CACHE_CONTROL, "no-cache, no-store")
+            .header("icy-name", "rsplayer");
+        if settings.encoding.is_lossy() {
+            response = response.header("icy-br", settings.bitrate_kbps.to_string());
+        }
+        if icy {
+            response = response.header("icy-metaint", ICY_METAINT.to_string());
+        }
+        response
+            .body(Body::from_stream(ReceiverStream::new(body_rx)))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+    }
+}
+
+/// A listener's place among `max_listeners`, given back on drop.
+struct ListenerSlot(Arc<AtomicUsize>);
+
+impl ListenerSlot {
+    fn take(listeners: &Arc<AtomicUsize>, max_listeners: u32) -> Option<Self> {
+        let max = usize::try_from(max_listeners).unwrap_or(usize::MAX);
+        listeners
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < max).then_some(n + 1))
+            .ok()?;
+        Some(Self(listeners.clone()))
+    }
+
+    fn count(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for ListenerSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Inserts a metadata block after every [`ICY_METAINT`] audio bytes: the
+/// title when it changed since the last block, an empty block otherwise.
+struct IcyMetadata {
+    title: watch::Receiver<String>,
+    until_block: usize,
+    first: bool,
+}
+
+impl IcyMetadata {
+    const fn new(title: watch::Receiver<String>) -> Self {
+        Self {
+            title,
+            until_block: ICY_METAINT,
+            first: true,
+        }
+    }
+
+    fn interleave(&mut self, mut audio: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(audio.len() + audio.len() / ICY_METAINT + 1);
+        while !audio.is_empty() {
+            let n = self.until_block.min(audio.len());
+            out.extend_from_slice(&audio[..n]);
+            audio = &audio[n..];
+            self.until_block -= n;
+            if self.until_block == 0 {
+                self.write_block(&mut out);
+                self.until_block = ICY_METAINT;
+            }
+        }
+        out
+    }
+
+    fn write_block(&mut self, out: &mut Vec<u8>) {
+        if !self.first && !self.title.has_changed().unwrap_or(false) {
+            out.push(0);
+            return;
+        }
+        self.first = false;
+        let mut text = stream_title(&self.title.borrow_and_update()).into_bytes();
+        let blocks = text.len().div_ceil(16);
+        text.resize(blocks * 16, 0);
+        #[allow(clippy::cast_possible_truncation)]
+        out.push(blocks as u8);
+        out.extend(text);
+    }
+}
+
+/// The `StreamTitle` metadata for `title`, with its quotes escaped and cut
+/// at a character boundary so the whole fits in one metadata block.
+fn stream_title(title: &str) -> String {
+    const START: &str = "StreamTitle='";
+    const END: &str = "';";
+    let mut text = String::from(START);
+    for c in title.chars() {
+        let escaped = if c == '\'' { 2 } else { c.len_utf8() };
+        if text.len() + escaped + END.len() > MAX_ICY_METADATA {
+            break;
+        }
+        if c == '\'' {
+            text.push('\\');
+        }
+        text.push(c);
+    }
+    text.push_str(END);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icy_metadata_follows_every_metaint_bytes() {
+        let (title_tx, title_rx) = watch::channel("Artist - Title".to_string());
+        let mut metadata = IcyMetadata::new(title_rx);
+        let out = metadata.interleave(&vec![1; ICY_METAINT * 2 + 10]);
+        let title = b"StreamTitle='Artist - Title';";
+        let blocks = title.len().div_ceil(16);
+        assert_eq!(usize::from(out[ICY_METAINT]), blocks);
+        assert_eq!(&out[ICY_METAINT + 1..=ICY_METAINT + title.len()], title);
+        // Unchanged title: an empty block.
+        let second = 2 * ICY_METAINT + 1 + blocks * 16;
+        assert_eq!(out[second], 0);
+        assert_eq!(out.len(), second + 1 + 10);
+
+        title_tx.send("Next".to_string()).expect("send");
+        let out = metadata.interleave(&vec![1; ICY_METAINT]);
+        assert_eq!(out[ICY_METAINT - 10], 2);
+        assert_eq!(&out[ICY_METAINT - 9..ICY_METAINT + 10], b"StreamTitle='Next';");
+    }
+
+    #[test]
+    fn listeners_beyond_the_limit_are_refused() {
+        let listeners = Arc::new(AtomicUsize::new(0));
+        let first = ListenerSlot::take(&listeners, 2).expect("first");
+        let second = ListenerSlot::take(&listeners, 2).expect("second");
+        assert_eq!(second.count(), 2);
+        assert!(ListenerSlot::take(&listeners, 2).is_none());
+        drop(first);
+        assert!(ListenerSlot::take(&listeners, 2).is_some());
+        assert_eq!(second.count(), 1);
+    }
+
+    #[test]
+    fn stream_titles_are_escaped_and_cut_to_one_block() {
+        assert_eq!(stream_title("Don't Stop"), "StreamTitle='Don\\'t Stop';");
+        let long = stream_title(&"é".repeat(MAX_ICY_METADATA));
+        assert!(long.len() <= MAX_ICY_METADATA);
+        assert!(long.ends_with("é';"));
+        let quotes = stream_title(&"'".repeat(MAX_ICY_METADATA));
+        assert!(quotes.len() <= MAX_ICY_METADATA);
+        assert!(quotes.ends_with("\\'';"));
+    }
+}
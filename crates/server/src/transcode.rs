@@ -0,0 +1,276 @@
+//! Transcoding for `/music` browser playback: decides whether a request gets
+//! the file as it is or re-encoded by the playback crate's `Transcoder`, and
+//! streams the result as it is encoded.
+//!
+//! A track is transcoded when the request names a `format` (`flac`, `opus`,
+//! `mp3`) or a start time `t` in seconds — byte ranges cannot seek in a file
+//! that does not exist yet — or when no browser plays the original (APE,
+//! DSD, SACD ISO tracks, WMA). Without a `format` the first encoding the
+//! `Accept` header lists is used, else the configured one. Clients outside
+//! the local network get at most `remote_max_bitrate_kbps`: lossless and
+//! heavier files reach them as MP3 or Opus at that bitrate.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::warn;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use api_models::settings::{AudioEncoding, TranscodeSettings};
+use metadata::sacd_bundle::SACD_TRACK_MARKER;
+use playback::rsp::transcoder::{self, Transcoder};
+
+/// Encoded chunks buffered before encoding waits for the client.
+const CHUNK_BUFFER: usize = 8;
+/// Extensions no browser plays.
+const UNPLAYABLE: [&str; 4] = ["ape", "dsf", "dff", "wma"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscodeRequest {
+    pub encoding: AudioEncoding,
+    pub bitrate_kbps: u32,
+    pub start: Duration,
+}
+
+/// What a request for `path` gets; `None` serves the original file.
+/// `source_kbps` is the file's average bitrate, needed only when the
+/// remote limit applies.
+pub fn plan(
+    path: &str,
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+    settings: &TranscodeSettings,
+    remote: bool,
+    source_kbps: Option<u32>,
+) -> Option<TranscodeRequest> {
+    let requested = query.get("format").and_then(|f| AudioEncoding::parse_loose(f));
+    let start = query
+        .get("t")
+        .and_then(|t| t.parse::<f64>().ok())
+        .filter(|t| t.is_finite() && *t > 0.0)
+        .map(Duration::from_secs_f64);
+    let accepted = accepted_encodings(headers);
+    let limit = Some(settings.remote_max_bitrate_kbps).filter(|kbps| remote && *kbps > 0);
+    let lossy = || accepted.iter().copied().find(|e| e.is_lossy()).unwrap_or(AudioEncoding::Mp3);
+
+    let mut encoding = if requested.is_some() || start.is_some() || needs_transcoding(path) {
+        requested.or_else(|| accepted.first().copied()).unwrap_or(settings.encoding)
+    } else if limit.is_some_and(|limit| source_kbps.is_some_and(|kbps| kbps > limit)) {
+        lossy()
+    } else {
+        return None;
+    };
+    let mut bitrate_kbps = query
+        .get("bitrate")
+        .and_then(|b| b.parse::<u32>().ok())
+        .unwrap_or(settings.bitrate_kbps)
+        .clamp(32, 320);
+    if let Some(limit) = limit {
+        if !encoding.is_lossy() {
+            encoding = lossy();
+        }
+        bitrate_kbps = bitrate_kbps.min(limit);
+    }
+    Some(TranscodeRequest {
+        encoding,
+        bitrate_kbps,
+        start: start.unwrap_or_default(),
+    })
+}
+
+/// Streams `path` transcoded; 404 when it cannot be opened.
+pub async fn respond(path: String, music_dirs: Vec<String>, request: TranscodeRequest) -> Response {
+    let opened = tokio::task::spawn_blocking(move || {
+        Transcoder::open(&path, &music_dirs, request.encoding, request.bitrate_kbps, request.start)
+            .map_err(|e| warn!("Cannot transcode {path}: {e}"))
+    })
+    .await;
+    let Ok(Ok(mut transcoder)) = opened else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (body_tx, body_rx) = mpsc::channel::<io::Result<Bytes>>(CHUNK_BUFFER);
+    tokio::task::spawn_blocking(move || loop {
+        let chunk = match transcoder.next_chunk() {
+            Ok(Some(bytes)) => Ok(Bytes::from(bytes)),
+            Ok(None) => break,
+            // An error ends the response abruptly, so the client does not
+            // take the truncated file for a complete one.
+            Err(e) => Err(io::Error::other(e.to_string())),
+        };
+        let failed = chunk.is_err();
+        if body_tx.blocking_send(chunk).is_err() || failed {
+            break;
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, request.encoding.content_type())
+        .header(header::CACHE_CONTROL, "no-cache")
+        // Seeking is a new request with `t`.
+        .header(header::ACCEPT_RANGES, "none")
+        .body(Body::from_stream(ReceiverStream::new(body_rx)))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// Average bitrate of a library file, from its size and stated length.
+pub async fn source_kbps(path: String, file: PathBuf, music_dirs: Vec<String>) -> Option<u32> {
+    let size = tokio::fs::metadata(&file).await.ok()?.len();
+    let duration = tokio::task::spawn_blocking(move || transcoder::duration(&path, &music_dirs))
+        .await
+        .ok()??;
+    // Bits per millisecond are kilobits per second.
+    u32::try_from(u128::from(size) * 8 / duration.as_millis().max(1)).ok()
+}
+
+/// Whether a client at `peer` is outside the local network. Behind a local
+/// reverse proxy the client is the first `X-Forwarded-For` address.
+pub fn is_remote(peer: IpAddr, headers: &HeaderMap) -> bool {
+    let client = if is_local(peer) {
+        headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|ip| ip.trim().parse().ok())
+            .unwrap_or(peer)
+    } else {
+        peer
+    };
+    !is_local(client)
+}
+
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local(IpAddr::V4(ip)),
+            None => ip.is_loopback() || ip.is_unique_local() || ip.is_unicast_link_local(),
+        },
+    }
+}
+
+fn needs_transcoding(path: &str) -> bool {
+    path.contains(SACD_TRACK_MARKER)
+        || path
+            .rsplit_once('.')
+            .is_some_and(|(_, ext)| UNPLAYABLE.iter().any(|u| ext.eq_ignore_ascii_case(u)))
+}
+
+/// The encodings `Accept` names explicitly, most preferred first.
+fn accepted_encodings(headers: &HeaderMap) -> Vec<AudioEncoding> {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return Vec::new();
+    };
+    let mut weighted: Vec<(AudioEncoding, f32)> = accept
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let encoding = AudioEncoding::parse_loose(parts.next()?)?;
+            let quality = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then_some((encoding, quality))
+        })
+        .collect();
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    weighted.into_iter().map(|(encoding, _)| encoding).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| ((*k).to_owned(), (*v).to_owned())).collect()
+    }
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, value.parse().expect("header"));
+        headers
+    }
+
+    #[test]
+    fn originals_are_served_unless_unplayable_or_asked_for() {
+        let settings = TranscodeSettings::default();
+        let none = HashMap::new();
+        assert_eq!(plan("a/b.flac", &none, &HeaderMap::new(), &settings, false, None), None);
+        let ape = plan("a/b.APE", &none, &HeaderMap::new(), &settings, false, None).expect("transcoded");
+        assert_eq!(ape.encoding, settings.encoding);
+        assert!(plan("a/disc.iso#SACD_0002", &none, &HeaderMap::new(), &settings, false, None).is_some());
+
+        let seek = plan(
+            "a/b.flac",
+            &query(&[("t", "61.5")]),
+            &accept("audio/ogg, */*"),
+            &settings,
+            false,
+            None,
+        )
+        .expect("transcoded");
+        assert_eq!(seek.encoding, AudioEncoding::Opus);
+        assert_eq!(seek.start, Duration::from_millis(61_500));
+
+        let mp3 = plan(
+            "a/b.dsf",
+            &query(&[("format", "mp3")]),
+            &accept("audio/flac"),
+            &settings,
+            false,
+            None,
+        )
+        .expect("transcoded");
+        assert_eq!(mp3.encoding, AudioEncoding::Mp3);
+    }
+
+    #[test]
+    fn remote_clients_get_lossy_within_the_limit() {
+        let settings = TranscodeSettings {
+            remote_max_bitrate_kbps: 128,
+            ..TranscodeSettings::default()
+        };
+        let none = HashMap::new();
+        assert_eq!(
+            plan("a/b.flac", &none, &HeaderMap::new(), &settings, true, Some(900)).map(|r| r.encoding),
+            Some(AudioEncoding::Mp3)
+        );
+        assert_eq!(plan("a/b.mp3", &none, &HeaderMap::new(), &settings, true, Some(128)), None);
+        assert_eq!(plan("a/b.flac", &none, &HeaderMap::new(), &settings, false, Some(900)), None);
+
+        let flac = plan(
+            "a/b.flac",
+            &query(&[("format", "flac")]),
+            &accept("audio/ogg;q=0.8"),
+            &settings,
+            true,
+            None,
+        )
+        .expect("transcoded");
+        assert_eq!(flac.encoding, AudioEncoding::Opus);
+        assert_eq!(flac.bitrate_kbps, 128);
+    }
+
+    #[test]
+    fn accept_order_follows_quality() {
+        let headers = accept("audio/mpeg;q=0.5, audio/flac, audio/ogg;q=0");
+        assert_eq!(accepted_encodings(&headers), vec![AudioEncoding::Flac, AudioEncoding::Mp3]);
+    }
+
+    #[test]
+    fn forwarded_clients_count_only_behind_a_local_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.7, 10.0.0.1".parse().expect("header"));
+        assert!(is_remote("127.0.0.1".parse().expect("ip"), &headers));
+        assert!(!is_remote("192.168.1.20".parse().expect("ip"), &HeaderMap::new()));
+        assert!(is_remote("198.51.100.1".parse().expect("ip"), &HeaderMap::new()));
+        assert!(!is_remote("::ffff:10.1.2.3".parse().expect("ip"), &HeaderMap::new()));
+    }
+}
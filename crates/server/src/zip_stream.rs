@@ -0,0 +1,328 @@
+//! Streaming ZIP writer for downloads: the archive is produced front to
+//! back while its entries are read, so nothing is staged in memory or on
+//! disk and the response can start with the first track.
+//!
+//! Entries are stored uncompressed — audio does not compress — and each is
+//! followed by a data descriptor, because its CRC and size are known only
+//! once it is written. ZIP64 records are added where sizes, offsets or the
+//! entry count outgrow the classic 32-bit fields, so box sets larger than
+//! 4 GiB stay readable.
+
+use anyhow::{format_err, Result};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP64_END: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const END: u32 = 0x0605_4b50;
+/// Sizes follow in a data descriptor; the name is UTF-8.
+const FLAGS: u16 = 0x0008 | 0x0800;
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Unix as the host system, so unzip takes names as they are and applies
+/// [`FILE_MODE`].
+const MADE_ON_UNIX: u16 = 3 << 8;
+/// A regular file, `rw-r--r--`.
+const FILE_MODE: u32 = 0o100_644;
+const ZIP64_EXTRA: u16 = 0x0001;
+/// Marks a 32-bit field whose value is in the ZIP64 extra field.
+const OVERFLOW: u32 = u32::MAX;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[derive(Default)]
+pub struct ZipStream {
+    offset: u64,
+    entries: Vec<Entry>,
+    current: Option<Entry>,
+}
+
+struct Entry {
+    name: String,
+    time: u16,
+    date: u16,
+    offset: u64,
+    crc: u32,
+    size: u64,
+    /// Written with ZIP64 sizes in the local header and data descriptor.
+    zip64: bool,
+}
+
+impl ZipStream {
+    /// Starts an entry and returns its local header. `large` entries may
+    /// exceed 4 GiB; others fail in [`Self::end`] if they do.
+    pub fn begin(&mut self, name: &str, modified: DateTime<Utc>, large: bool) -> Vec<u8> {
+        let (time, date) = dos_date_time(modified);
+        let entry = Entry {
+            name: name.to_owned(),
+            time,
+            date,
+            offset: self.offset,
+            crc: !0,
+            size: 0,
+            zip64: large,
+        };
+        let mut out = Vec::with_capacity(30 + name.len() + 20);
+        put32(&mut out, LOCAL_HEADER);
+        put16(&mut out, if large { VERSION_ZIP64 } else { VERSION });
+        put16(&mut out, FLAGS);
+        put16(&mut out, 0); // stored
+        put16(&mut out, time);
+        put16(&mut out, date);
+        put32(&mut out, 0); // CRC and sizes are in the data descriptor
+        put32(&mut out, if large { OVERFLOW } else { 0 });
+        put32(&mut out, if large { OVERFLOW } else { 0 });
+        put16(&mut out, len16(name.len()));
+        put16(&mut out, if large { 20 } else { 0 });
+        out.extend_from_slice(name.as_bytes());
+        if large {
+            put16(&mut out, ZIP64_EXTRA);
+            put16(&mut out, 16);
+            put64(&mut out, 0);
+            put64(&mut out, 0);
+        }
+        self.offset += out.len() as u64;
+        self.current = Some(entry);
+        out
+    }
+
+    /// Accounts for the next `bytes` of the current entry, which the caller
+    /// sends as they are.
+    pub fn update(&mut self, bytes: &[u8]) {
+        if let Some(entry) = &mut self.current {
+            entry.crc = bytes
+                .iter()
+                .fold(entry.crc, |crc, &b| CRC_TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8));
+            entry.size += bytes.len() as u64;
+            self.offset += bytes.len() as u64;
+        }
+    }
+
+    /// Ends the current entry and returns its data descriptor.
+    pub fn end(&mut self) -> Result<Vec<u8>> {
+        let mut entry = self.current.take().ok_or_else(|| format_err!("No ZIP entry started"))?;
+        if !entry.zip64 && entry.size >= u64::from(OVERFLOW) {
+            return Err(format_err!("{} outgrew 4 GiB", entry.name));
+        }
+        entry.crc = !entry.crc;
+        let mut out = Vec::with_capacity(24);
+        put32(&mut out, DATA_DESCRIPTOR);
+        put32(&mut out, entry.crc);
+        if entry.zip64 {
+            put64(&mut out, entry.size);
+            put64(&mut out, entry.size);
+        } else {
+            put32(&mut out, low32(entry.size));
+            put32(&mut out, low32(entry.size));
+        }
+        self.offset += out.len() as u64;
+        self.entries.push(entry);
+        Ok(out)
+    }
+
+    /// The central directory and end records that complete the archive.
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for entry in &self.entries {
+            let mut extra = Vec::new();
+            if entry.size >= u64::from(OVERFLOW) {
+                put64(&mut extra, entry.size);
+                put64(&mut extra, entry.size);
+            }
+            if entry.offset >= u64::from(OVERFLOW) {
+                put64(&mut extra, entry.offset);
+            }
+            let zip64 = entry.zip64 || !extra.is_empty();
+            let version = if zip64 { VERSION_ZIP64 } else { VERSION };
+            put32(&mut out, CENTRAL_HEADER);
+            put16(&mut out, MADE_ON_UNIX | version);
+            put16(&mut out, version);
+            put16(&mut out, FLAGS);
+            put16(&mut out, 0);
+            put16(&mut out, entry.time);
+            put16(&mut out, entry.date);
+            put32(&mut out, entry.crc);
+            put32(&mut out, capped32(entry.size));
+            put32(&mut out, capped32(entry.size));
+            put16(&mut out, len16(entry.name.len()));
+            put16(&mut out, if extra.is_empty() { 0 } else { len16(extra.len() + 4) });
+            put16(&mut out, 0); // comment
+            put16(&mut out, 0); // disk
+            put16(&mut out, 0); // internal attributes
+            put32(&mut out, FILE_MODE << 16);
+            put32(&mut out, capped32(entry.offset));
+            out.extend_from_slice(entry.name.as_bytes());
+            if !extra.is_empty() {
+                put16(&mut out, ZIP64_EXTRA);
+                put16(&mut out, len16(extra.len()));
+                out.extend_from_slice(&extra);
+            }
+        }
+
+        let directory_offset = self.offset;
+        let directory_size = out.len() as u64;
+        let count = self.entries.len() as u64;
+        if count >= u64::from(u16::MAX) || directory_offset >= u64::from(OVERFLOW) || directory_size >= u64::from(OVERFLOW) {
+            let zip64_end_offset = directory_offset + directory_size;
+            put32(&mut out, ZIP64_END);
+            put64(&mut out, 44); // size of the rest of this record
+            put16(&mut out, VERSION_ZIP64);
+            put16(&mut out, VERSION_ZIP64);
+            put32(&mut out, 0);
+            put32(&mut out, 0);
+            put64(&mut out, count);
+            put64(&mut out, count);
+            put64(&mut out, directory_size);
+            put64(&mut out, directory_offset);
+            put32(&mut out, ZIP64_LOCATOR);
+            put32(&mut out, 0);
+            put64(&mut out, zip64_end_offset);
+            put32(&mut out, 1);
+        }
+        let count16 = u16::try_from(count).unwrap_or(u16::MAX);
+        put32(&mut out, END);
+        put16(&mut out, 0);
+        put16(&mut out, 0);
+        put16(&mut out, count16);
+        put16(&mut out, count16);
+        put32(&mut out, capped32(directory_size));
+        put32(&mut out, capped32(directory_offset));
+        put16(&mut out, 0); // comment
+        out
+    }
+}
+
+fn put16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn capped32(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(OVERFLOW)
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const fn low32(value: u64) -> u32 {
+    value as u32
+}
+
+/// Entry names are file names, far below the 64 KiB limit.
+fn len16(len: usize) -> u16 {
+    u16::try_from(len).unwrap_or(u16::MAX)
+}
+
+/// MS-DOS time and date, which ZIP keeps in two-second steps from 1980.
+fn dos_date_time(time: DateTime<Utc>) -> (u16, u16) {
+    if time.year() < 1980 {
+        return (0, 0x21); // 1980-01-01
+    }
+    let year = u16::try_from(time.year() - 1980).unwrap_or(127).min(127);
+    #[allow(clippy::cast_possible_truncation)]
+    let (month, day, hour, minute, second) = (
+        time.month() as u16,
+        time.day() as u16,
+        time.hour() as u16,
+        time.minute() as u16,
+        time.second() as u16,
+    );
+    ((hour << 11) | (minute << 5) | (second / 2), (year << 9) | (month << 5) | day)
+}
+
+/// CRC-32 (IEEE, reflected) lookup table.
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0u32;
+    while i < 256 {
+        let mut crc = i;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 { crc >> 1 } else { (crc >> 1) ^ 0xEDB8_8320 };
+            bit += 1;
+        }
+        table[i as usize] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().expect("four bytes"))
+    }
+
+    fn archive(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut zip = ZipStream::default();
+        let mut out = Vec::new();
+        for (name, data, large) in entries {
+            out.extend(zip.begin(name, DateTime::UNIX_EPOCH, *large));
+            for chunk in data.chunks(3) {
+                zip.update(chunk);
+                out.extend_from_slice(chunk);
+            }
+            out.extend(zip.end().expect("entry"));
+        }
+        out.extend(zip.finish());
+        out
+    }
+
+    #[test]
+    fn central_directory_points_at_the_entries() {
+        let fox = b"The quick brown fox jumps over the lazy dog";
+        let zip = archive(&[("01 - Intro.flac", fox, false), ("02 - Ünïcode.flac", b"", false)]);
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(&zip, end), END);
+        assert_eq!(u16_at(&zip, end + 10), 2);
+        let directory = u32_at(&zip, end + 16) as usize;
+        assert_eq!(directory + u32_at(&zip, end + 12) as usize, end);
+
+        assert_eq!(u32_at(&zip, directory), CENTRAL_HEADER);
+        assert_eq!(u32_at(&zip, directory + 16), 0x414f_a339);
+        assert_eq!(u32_at(&zip, directory + 24), 43);
+        assert_eq!(u32_at(&zip, u32_at(&zip, directory + 42) as usize), LOCAL_HEADER);
+        let name_len = u16_at(&zip, directory + 28) as usize;
+        assert_eq!(&zip[directory + 46..directory + 46 + name_len], b"01 - Intro.flac");
+
+        let second = directory + 46 + name_len;
+        assert_eq!(u32_at(&zip, second), CENTRAL_HEADER);
+        assert_eq!(u32_at(&zip, second + 16), 0);
+        let second_local = u32_at(&zip, second + 42) as usize;
+        assert_eq!(u32_at(&zip, second_local), LOCAL_HEADER);
+    }
+
+    #[test]
+    fn large_entries_carry_zip64_sizes() {
+        let zip = archive(&[("box.flac", b"abc", true)]);
+        assert_eq!(u16_at(&zip, 4), VERSION_ZIP64);
+        assert_eq!(u32_at(&zip, 22), OVERFLOW);
+        // Local header with its ZIP64 extra, three bytes, then the
+        // descriptor with 64-bit sizes.
+        let descriptor = 30 + "box.flac".len() + 20 + 3;
+        assert_eq!(u32_at(&zip, descriptor), DATA_DESCRIPTOR);
+        assert_eq!(u32_at(&zip, descriptor + 8), 3);
+        assert_eq!(u32_at(&zip, descriptor + 12), 0);
+        assert_eq!(u32_at(&zip, descriptor + 16), 3);
+    }
+
+    #[test]
+    fn dos_time_counts_from_1980() {
+        let time = DateTime::parse_from_rfc3339("2024-06-15T13:45:31Z").expect("time").to_utc();
+        assert_eq!(dos_date_time(time), ((13 << 11) | (45 << 5) | 15, (44 << 9) | (6 << 5) | 15));
+        assert_eq!(dos_date_time(DateTime::UNIX_EPOCH), (0, 0x21));
+    }
+}
@@ -60,6 +60,8 @@ pub enum HostToFw {
         right: u8,
     },
     PlaybackMode(PlaybackMode),
+    /// Seconds until the sleep timer stops playback; `None` clears the display.
+    SleepTimer(Option<u32>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -1,6 +1,7 @@
+use crate::page::account::selected_profile;
 use crate::state::AppState;
 use api_models::{
-    common::{MultiroomCommand, PlayerCommand, QueueCommand, SystemRequest, UserCommand},
+    common::{MultiroomCommand, PlayerCommand, QueueCommand, SchedulerCommand, SystemRequest, UserCommand},
     state::StateChangeEvent,
 };
 use dioxus::prelude::*;
@@ -26,7 +27,10 @@ fn connect(app_state: AppState, ws_holder: Signal<Signal<Option<WebSocket>>>) {
     let host = window.location().host().unwrap_or_else(|_| "localhost".to_string());
     let protocol = window.location().protocol().unwrap_or_default();
     let ws_scheme = if protocol == "https:" { "wss" } else { "ws" };
-    let url = format!("{ws_scheme}://{host}/api/ws");
+    let url = match selected_profile() {
+        Some(profile) => format!("{ws_scheme}://{host}/api/ws?profile={}", js_sys::encode_uri_component(&profile)),
+        None => format!("{ws_scheme}://{host}/api/ws"),
+    };
 
     let ws = match WebSocket::new(&url) {
         Ok(ws) => ws,
@@ -79,6 +83,9 @@ fn connect(app_state: AppState, ws_holder: Signal<Signal<Option<WebSocket>>>) {
             if let Ok(json) = serde_json::to_string(&UserCommand::Multiroom(MultiroomCommand::QueryState)) {
                 send(&json);
             }
+            if let Ok(json) = serde_json::to_string(&UserCommand::Scheduler(SchedulerCommand::QuerySchedules)) {
+                send(&json);
+            }
         });
         ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
         onopen.forget();
@@ -91,6 +98,9 @@ fn connect(app_state: AppState, ws_holder: Signal<Signal<Option<WebSocket>>>) {
             let mut s = state.clone();
             *s.connected.write() = false;
             log!("WebSocket closed — reconnecting in 3s");
+            // The server also closes it when the session is gone; ask again
+            // so the login screen shows instead of reconnecting forever.
+            crate::page::account::refresh_auth_status(s.auth_status);
             let s2 = state.clone();
             let holder = ws_holder;
             wasm_bindgen_futures::spawn_local(async move {
@@ -20,6 +20,8 @@ pub mod vumeter;
 
 use api_models::{
     common::{dur_to_string, MetadataCommand, PlayerCommand, PlaylistCommand, QueueCommand, SystemRequest, UserCommand},
+    player::Song,
+    playlist::PlaylistType,
     settings::{InstallMethod, Settings},
     state::{CurrentQueueQuery, PlayerState, StateChangeEvent},
 };
@@ -33,10 +35,11 @@ use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::spawn_local;
 use web_sys::WebSocket;
 
+use page::account::selected_profile;
 use page::{
-    home::HomePage, library_artists::LibraryArtistsPage, library_files::LibraryFilesPage, library_playlists::LibraryPlaylistsPage,
-    library_radio::LibraryRadioPage, library_stats::LibraryStatsPage, not_found::NotFoundPage, player::BrowserAudioPlayback,
-    player::PlayerPage, queue::QueuePage, settings::SettingsPage,
+    account::LoginScreen, home::HomePage, library_artists::LibraryArtistsPage, library_files::LibraryFilesPage,
+    library_playlists::LibraryPlaylistsPage, library_radio::LibraryRadioPage, library_stats::LibraryStatsPage, not_found::NotFoundPage,
+    player::BrowserAudioPlayback, player::PlayerPage, queue::QueuePage, settings::SettingsPage,
 };
 
 fn main() {
@@ -112,6 +115,9 @@ fn show_panic_overlay(message: &str) {
 #[derive(Clone, Copy)]
 pub struct UiState {
     pub lyrics_open: Signal<bool>,
+    pub bookmarks_open: Signal<bool>,
+    pub sleep_timer_open: Signal<bool>,
+    pub alarms_open: Signal<bool>,
     pub shortcuts_open: Signal<bool>,
     pub welcome_open: Signal<bool>,
     pub playlist_modal_open: Signal<bool>,
@@ -123,6 +129,7 @@ pub struct UiState {
     pub queue_save_playlist_open: Signal<bool>,
     pub queue_save_playlist_input: Signal<String>,
     pub queue_clear_confirm_open: Signal<bool>,
+    pub queue_snapshots_open: Signal<bool>,
 }
 
 // ─── Navigation ─────────────────────────────────────────────────────────────
@@ -187,6 +194,9 @@ fn App() -> Element {
 
     let ui_state = UiState {
         lyrics_open: use_signal(|| false),
+        bookmarks_open: use_signal(|| false),
+        sleep_timer_open: use_signal(|| false),
+        alarms_open: use_signal(|| false),
         shortcuts_open: use_signal(|| false),
         welcome_open: use_signal(|| false),
         playlist_modal_open: use_signal(|| false),
@@ -198,6 +208,7 @@ fn App() -> Element {
         queue_save_playlist_open: use_signal(|| false),
         queue_save_playlist_input: use_signal(String::new),
         queue_clear_confirm_open: use_signal(|| false),
+        queue_snapshots_open: use_signal(|| false),
     };
     use_context_provider(|| ui_state);
 
@@ -244,6 +255,12 @@ fn App() -> Element {
         });
     }
 
+    // Find out who is logged in, if access control is on.
+    {
+        let auth_status = use_context::<AppState>().auth_status;
+        use_hook(move || page::account::refresh_auth_status(auth_status));
+    }
+
     // Fetch Last.fm album art when the song has no local image.
     // Uses use_context (same pattern as child components) to get a stable signal reference.
     let app_state_ctx = use_context::<AppState>();
@@ -282,9 +299,13 @@ fn App() -> Element {
     };
 
     let connected = app_state_ctx.connected;
+    let needs_login = app_state_ctx.auth_status.read().as_ref().is_some_and(|s| s.role().is_none());
 
     rsx! {
         document::Stylesheet { href: asset!("/public/tw.css") }
+        if needs_login {
+            LoginScreen {}
+        }
         // Show a full-screen "Connecting" overlay while the WebSocket is not connected.
         // On desktop builds the backend starts asynchronously — this gives the user
         // visible feedback instead of a dead-looking UI.
@@ -315,6 +336,9 @@ fn App() -> Element {
         if (ui_state.queue_clear_confirm_open)() {
             QueueClearConfirmModal {}
         }
+        if (ui_state.queue_snapshots_open)() {
+            QueueSnapshotsModal {}
+        }
         div { class: "app-shell", style: "{bg_style}",
             if show_bg && app_state_ctx.album_image.read().is_some() {
                 div { class: "app-bg" }
@@ -421,10 +445,14 @@ fn setup_keyboard_shortcuts(path: Signal<String>, ws: Signal<Option<WebSocket>>,
                     }
                     ui.shortcuts_open.set(false);
                     ui.lyrics_open.set(false);
+                    ui.bookmarks_open.set(false);
+                    ui.sleep_timer_open.set(false);
+                    ui.alarms_open.set(false);
                     ui.playlist_modal_open.set(false);
                     ui.queue_add_url_open.set(false);
                     ui.queue_save_playlist_open.set(false);
                     ui.queue_clear_confirm_open.set(false);
+                    ui.queue_snapshots_open.set(false);
                 }
                 // ── Player shortcuts (only on / page) ──
                 " " if on_player => {
@@ -711,11 +739,25 @@ fn PlaylistModal() -> Element {
     let state = use_context::<AppState>();
     let ws = use_context::<Signal<Option<WebSocket>>>();
     let playlist_items = state.playlist_items;
+    let playlists = state.playlists;
+    let mut editing = use_signal(|| false);
+    let mut name_input = use_signal(String::new);
+    let mut description_input = use_signal(String::new);
+    let mut image_input: Signal<Option<String>> = use_signal(|| None);
+
+    let playlist_id = ui.playlist_modal_id.read().clone().unwrap_or_default();
+    // Albums and the dynamic playlists are read-only.
+    let editable = !*ui.playlist_modal_is_album.read() && playlist_id != "most_played" && playlist_id != "liked";
+    let item_count = playlist_items.read().len();
+    // Songs of an album come in disc order; a header starts each disc of a multi-disc album.
+    let discs: Vec<Option<u32>> = playlist_items.read().iter().map(Song::disc_number).collect();
+    let multi_disc = *ui.playlist_modal_is_album.read() && discs.iter().any(|disc| disc.is_some_and(|d| d > 1));
 
     let close = move |_| {
         ui.playlist_modal_open.set(false);
         ui.playlist_modal_id.set(None);
         ui.playlist_modal_name.set(String::new());
+        editing.set(false);
     };
 
     rsx! {
@@ -724,22 +766,145 @@ fn PlaylistModal() -> Element {
             div { class: "modal-box max-w-md",
                 div { class: "flex items-center gap-2 px-4 py-3 border-b border-base-300 shrink-0",
                     h3 { class: "font-bold text-base flex-1 truncate", "{ui.playlist_modal_name}" }
+                    if editable {
+                        button {
+                            class: "btn btn-xs btn-circle btn-ghost",
+                            title: "Edit playlist",
+                            onclick: {
+                                let id = playlist_id.clone();
+                                move |_| {
+                                    name_input.set(ui.playlist_modal_name.read().clone());
+                                    let saved = playlists.read().as_ref().and_then(|pls| {
+                                        pls.items.iter().find_map(|item| match item {
+                                            PlaylistType::Saved(pl) if pl.id == id => Some(pl.clone()),
+                                            _ => None,
+                                        })
+                                    });
+                                    description_input.set(saved.as_ref().and_then(|pl| pl.description.clone()).unwrap_or_default());
+                                    image_input.set(saved.and_then(|pl| pl.image));
+                                    let on = !editing();
+                                    editing.set(on);
+                                }
+                            },
+                            i { class: "material-icons text-sm", "edit" }
+                        }
+                    }
                     button {
                         class: "btn btn-xs btn-circle btn-ghost",
                         onclick: close,
                         "✕"
                     }
                 }
+                if editable && editing() {
+                    div { class: "flex flex-wrap items-center gap-2 px-3 py-2 border-b border-base-300 shrink-0",
+                        input {
+                            class: "input input-bordered input-sm flex-1 min-w-0",
+                            placeholder: "Playlist name",
+                            value: "{name_input}",
+                            oninput: move |e| name_input.set(e.value()),
+                        }
+                        button {
+                            class: "btn btn-sm",
+                            title: "Rename",
+                            onclick: {
+                                let id = playlist_id.clone();
+                                move |_| {
+                                    let name = name_input.read().trim().to_string();
+                                    if !name.is_empty() {
+                                        ws_send(&ws, &UserCommand::Playlist(PlaylistCommand::RenamePlaylist(id.clone(), name.clone())));
+                                        ui.playlist_modal_name.set(name);
+                                        editing.set(false);
+                                    }
+                                }
+                            },
+                            i { class: "material-icons text-sm", "check" }
+                        }
+                        button {
+                            class: "btn btn-sm",
+                            title: "Duplicate",
+                            onclick: {
+                                let id = playlist_id.clone();
+                                move |_| {
+                                    let name = format!("{} (copy)", ui.playlist_modal_name.read());
+                                    ws_send(&ws, &UserCommand::Playlist(PlaylistCommand::DuplicatePlaylist(id.clone(), name)));
+                                    editing.set(false);
+                                }
+                            },
+                            i { class: "material-icons text-sm", "content_copy" }
+                        }
+                        button {
+                            class: "btn btn-sm btn-error",
+                            title: "Delete playlist",
+                            onclick: {
+                                let id = playlist_id.clone();
+                                move |_| {
+                                    ws_send(&ws, &UserCommand::Playlist(PlaylistCommand::DeletePlaylist(id.clone())));
+                                    ui.playlist_modal_open.set(false);
+                                    ui.playlist_modal_id.set(None);
+                                    editing.set(false);
+                                }
+                            },
+                            i { class: "material-icons text-sm", "delete" }
+                        }
+                    }
+                    div { class: "flex items-center gap-2 px-3 py-2 border-b border-base-300 shrink-0",
+                        if let Some(image) = image_input() {
+                            img { class: "w-8 h-8 rounded object-cover", src: "/artwork/thumbs/{image}" }
+                            button {
+                                class: "btn btn-xs btn-ghost",
+                                title: "Remove image",
+                                onclick: move |_| image_input.set(None),
+                                i { class: "material-icons text-sm", "hide_image" }
+                            }
+                        }
+                        input {
+                            class: "input input-bordered input-sm flex-1 min-w-0",
+                            placeholder: "Description",
+                            value: "{description_input}",
+                            oninput: move |e| description_input.set(e.value()),
+                        }
+                        button {
+                            class: "btn btn-sm",
+                            title: "Save description and image",
+                            onclick: {
+                                let id = playlist_id.clone();
+                                move |_| {
+                                    let description = description_input.read().trim().to_string();
+                                    ws_send(
+                                        &ws,
+                                        &UserCommand::Playlist(PlaylistCommand::UpdatePlaylistDetails(
+                                            id.clone(),
+                                            (!description.is_empty()).then_some(description),
+                                            image_input(),
+                                        )),
+                                    );
+                                    editing.set(false);
+                                }
+                            },
+                            i { class: "material-icons text-sm", "save" }
+                        }
+                    }
+                    if image_input().is_none() {
+                        p { class: "px-3 pt-1 text-xs text-base-content/50",
+                            "Pick a song's artwork as the playlist image with its image button."
+                        }
+                    }
+                }
                 div { class: "overflow-y-auto flex-1",
                     {
                         playlist_items
                             .read()
                             .iter()
-                            .map(|song| {
+                            .enumerate()
+                            .map(|(idx, song)| {
                                 let song = song.clone();
+                                let pl_id = playlist_id.clone();
+                                let pl_id2 = playlist_id.clone();
+                                let pl_id3 = playlist_id.clone();
                                 let file = song.file.clone();
                                 let file2 = song.file.clone();
                                 let file3 = song.file.clone();
+                                let song_image = song.image_id.clone();
                                 let title = song.get_title();
                                 let artist = song.artist.clone().unwrap_or_default();
                                 let dur = song
@@ -747,7 +912,12 @@ fn PlaylistModal() -> Element {
                                     .as_ref()
                                     .map(|t| format!(" • {}", dur_to_string(t)))
                                     .unwrap_or_default();
+                                let disc_header = (multi_disc && (idx == 0 || discs[idx - 1] != discs[idx]))
+                                    .then(|| format!("Disc {}", discs[idx].unwrap_or(1)));
                                 rsx! {
+                                    if let Some(header) = disc_header {
+                                        p { class: "px-3 pt-3 pb-1 text-xs font-semibold text-base-content/60", "{header}" }
+                                    }
                                     div { class: "flex items-center gap-2 py-1.5 px-3 hover:bg-base-200 group",
                                         div { class: "flex-1 min-w-0",
                                             p { class: "text-sm font-medium truncate", "{title}" }
@@ -781,6 +951,55 @@ fn PlaylistModal() -> Element {
                                                 ),
                                                 i { class: "material-icons text-sm", "play_arrow" }
                                             }
+                                            if editable && editing() {
+                                                if let Some(image) = song_image {
+                                                    button {
+                                                        class: "btn btn-ghost btn-xs",
+                                                        title: "Use as playlist image",
+                                                        onclick: move |_| image_input.set(Some(image.clone())),
+                                                        i { class: "material-icons text-sm", "image" }
+                                                    }
+                                                }
+                                                button {
+                                                    class: "btn btn-ghost btn-xs",
+                                                    title: "Move up",
+                                                    disabled: idx == 0,
+                                                    onclick: move |_| ws_send(
+                                                        &ws,
+                                                        &UserCommand::Playlist(PlaylistCommand::MovePlaylistItem(
+                                                            pl_id.clone(),
+                                                            idx,
+                                                            idx.saturating_sub(1),
+                                                        )),
+                                                    ),
+                                                    i { class: "material-icons text-sm", "arrow_upward" }
+                                                }
+                                                button {
+                                                    class: "btn btn-ghost btn-xs",
+                                                    title: "Move down",
+                                                    disabled: idx + 1 >= item_count,
+                                                    onclick: move |_| ws_send(
+                                                        &ws,
+                                                        &UserCommand::Playlist(PlaylistCommand::MovePlaylistItem(
+                                                            pl_id2.clone(),
+                                                            idx,
+                                                            idx + 1,
+                                                        )),
+                                                    ),
+                                                    i { class: "material-icons text-sm", "arrow_downward" }
+                                                }
+                                                button {
+                                                    class: "btn btn-ghost btn-xs",
+                                                    title: "Remove from playlist",
+                                                    onclick: move |_| ws_send(
+                                                        &ws,
+                                                        &UserCommand::Playlist(
+                                                        PlaylistCommand::RemovePlaylistItem(pl_id3.clone(), idx),
+                                                    ),
+                                                    ),
+                                                    i { class: "material-icons text-sm", "remove_circle_outline" }
+                                                }
+                                            }
                                         }
                                     }
                                 }
@@ -814,6 +1033,10 @@ fn PlaylistModal() -> Element {
                                 i { class: "material-icons text-sm", "queue" }
                                 "Add"
                             }
+                            DownloadMenu {
+                                url: format!("/api/download/album/{}", String::from(js_sys::encode_uri_component(id))),
+                                upward: true,
+                            }
                         } else {
                             button {
                                 class: "btn btn-primary btn-sm flex-1",
@@ -839,6 +1062,10 @@ fn PlaylistModal() -> Element {
                                 i { class: "material-icons text-sm", "queue" }
                                 "Add"
                             }
+                            DownloadMenu {
+                                url: playlist_download_url(id),
+                                upward: true,
+                            }
                         }
                     }
                 }
@@ -959,6 +1186,110 @@ fn QueueSavePlaylistModal() -> Element {
     }
 }
 
+#[component]
+fn QueueSnapshotsModal() -> Element {
+    let mut ui = use_context::<UiState>();
+    let state = use_context::<AppState>();
+    let ws = use_context::<Signal<Option<WebSocket>>>();
+    let mut name_input = use_signal(String::new);
+    let snapshots = state.queue_snapshots;
+
+    use_effect(move || {
+        ws_send(&ws, &UserCommand::Queue(QueueCommand::QuerySnapshots));
+    });
+
+    let close = move |_| {
+        ui.queue_snapshots_open.set(false);
+    };
+
+    rsx! {
+        div { class: "modal modal-open modal-viewport",
+            div { class: "modal-backdrop", onclick: close }
+            div { class: "modal-box",
+                button {
+                    class: "btn btn-sm btn-circle btn-ghost absolute right-2 top-2",
+                    onclick: close,
+                    "✕"
+                }
+                h3 { class: "font-bold text-lg mb-4", "Queue snapshots" }
+                div { class: "flex gap-2",
+                    input {
+                        class: "input input-bordered input-sm flex-1",
+                        placeholder: "Snapshot name",
+                        value: "{name_input}",
+                        oninput: move |e| name_input.set(e.value()),
+                    }
+                    button {
+                        class: "btn btn-primary btn-sm",
+                        onclick: move |_| {
+                            let name = name_input.read().trim().to_string();
+                            if !name.is_empty() {
+                                ws_send(&ws, &UserCommand::Queue(QueueCommand::SaveSnapshot(name)));
+                                name_input.set(String::new());
+                            }
+                        },
+                        i { class: "material-icons text-sm", "save" }
+                        "Save current"
+                    }
+                }
+                div { class: "flex flex-col mt-3",
+                    if snapshots.read().is_empty() {
+                        p { class: "text-sm text-base-content/50 py-2", "No snapshots saved yet." }
+                    }
+                    {
+                        snapshots
+                            .read()
+                            .iter()
+                            .map(|snapshot| {
+                                let name = snapshot.name.clone();
+                                let name2 = snapshot.name.clone();
+                                let created = snapshot.created.format("%Y-%m-%d %H:%M").to_string();
+                                let count = snapshot.song_count;
+                                rsx! {
+                                    div { class: "flex items-center gap-2 py-1.5 hover:bg-base-200",
+                                        div { class: "flex-1 min-w-0",
+                                            p { class: "text-sm font-medium truncate", "{snapshot.name}" }
+                                            p { class: "text-xs text-base-content/50", "{count} songs • {created}" }
+                                        }
+                                        button {
+                                            class: "btn btn-ghost btn-xs",
+                                            title: "Restore",
+                                            onclick: move |_| {
+                                                ws_send(&ws, &UserCommand::Queue(QueueCommand::RestoreSnapshot(name.clone())));
+                                                ws_send(
+                                                    &ws,
+                                                    &UserCommand::Queue(
+                                                        QueueCommand::QueryCurrentQueue(
+                                                            CurrentQueueQuery::WithSearchTerm(String::new(), 0),
+                                                        ),
+                                                    ),
+                                                );
+                                                ui.queue_snapshots_open.set(false);
+                                            },
+                                            i { class: "material-icons text-sm", "restore" }
+                                        }
+                                        button {
+                                            class: "btn btn-ghost btn-xs text-error",
+                                            title: "Delete",
+                                            onclick: move |_| ws_send(
+                                                &ws,
+                                                &UserCommand::Queue(QueueCommand::DeleteSnapshot(name2.clone())),
+                                            ),
+                                            i { class: "material-icons text-sm", "delete" }
+                                        }
+                                    }
+                                }
+                            })
+                    }
+                }
+                div { class: "modal-action",
+                    button { class: "btn", onclick: close, "Close" }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 fn QueueClearConfirmModal() -> Element {
     let mut ui = use_context::<UiState>();
@@ -998,6 +1329,46 @@ fn QueueClearConfirmModal() -> Element {
     }
 }
 
+// ─── ZIP download menu ──────────────────────────────────────────────────────
+
+/// A playlist's download, as this browser's profile: private playlists are
+/// found only for their owner.
+fn playlist_download_url(id: &str) -> String {
+    let url = format!("/api/download/playlist/{}", String::from(js_sys::encode_uri_component(id)));
+    match selected_profile() {
+        Some(profile) => format!("{url}?profile={}", String::from(js_sys::encode_uri_component(&profile))),
+        None => url,
+    }
+}
+
+/// Downloads `url` (an `/api/download/...` route) as a ZIP of the original
+/// files or transcoded to MP3 or Opus. `upward` opens the menu above the
+/// button, for buttons at the bottom of a modal.
+#[component]
+pub fn DownloadMenu(url: String, #[props(optional)] class: Option<String>, #[props(default)] upward: bool) -> Element {
+    let cls = class.unwrap_or_else(|| "btn btn-sm".to_string());
+    let sep = if url.contains('?') { '&' } else { '?' };
+    rsx! {
+        div { class: if upward { "dropdown dropdown-top dropdown-end" } else { "dropdown dropdown-end" },
+            div {
+                tabindex: "0",
+                role: "button",
+                class: "{cls}",
+                title: "Download as ZIP",
+                i { class: "material-icons text-sm", "download" }
+                span { class: "hidden sm:inline text-xs", "Download" }
+            }
+            ul {
+                tabindex: "0",
+                class: "dropdown-content menu bg-base-200 rounded-box z-10 w-44 p-2 shadow",
+                li { a { href: "{url}", download: "", "Original files" } }
+                li { a { href: "{url}{sep}format=mp3", download: "", "MP3" } }
+                li { a { href: "{url}{sep}format=opus", download: "", "Opus" } }
+            }
+        }
+    }
+}
+
 // ─── SPA link component ─────────────────────────────────────────────────────
 
 #[component]
@@ -1258,12 +1629,7 @@ fn UpdateAvailableBanner() -> Element {
         return rsx! {};
     }
     let latest_dismiss = latest.clone();
-    let method = state
-        .global_settings
-        .read()
-        .as_ref()
-        .map(|s| s.install_method)
-        .unwrap_or_default();
+    let method = state.global_settings.read().as_ref().map(|s| s.install_method).unwrap_or_default();
     let command = update::update_command(method);
 
     rsx! {
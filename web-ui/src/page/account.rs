@@ -0,0 +1,513 @@
+//! Login, the signed-in user's account, for admins the user list, and the
+//! profile picker for when nobody has to log in.
+
+use api_models::auth::{
+    ApiTokenInfo, AuthStatus, ChangePasswordRequest, CreateApiTokenRequest, CreatedApiToken, LoginRequest, Role, SaveUserRequest, UserInfo,
+};
+use dioxus::prelude::*;
+use gloo_net::http::{Request, Response};
+use strum::IntoEnumIterator;
+use wasm_bindgen_futures::spawn_local;
+
+use crate::state::AppState;
+
+/// Fetch who this browser is logged in as into [`AppState::auth_status`].
+pub fn refresh_auth_status(mut auth_status: Signal<Option<AuthStatus>>) {
+    spawn_local(async move {
+        if let Ok(resp) = Request::get("/api/auth/status").send().await {
+            if let Ok(status) = resp.json::<AuthStatus>().await {
+                auth_status.set(Some(status));
+            }
+        }
+    });
+}
+
+/// The role the UI offers controls for; admin until the status is known, as
+/// the server has the final say anyway.
+pub fn current_role(auth_status: Signal<Option<AuthStatus>>) -> Role {
+    auth_status
+        .read()
+        .as_ref()
+        .map_or(Some(Role::Admin), AuthStatus::role)
+        .unwrap_or_default()
+}
+
+fn reload() {
+    if let Some(window) = web_sys::window() {
+        let _ = window.location().reload();
+    }
+}
+
+/// The server's message for a rejected request, or a generic one.
+async fn error_message(resp: Response) -> String {
+    match resp.status() {
+        400 | 429 => resp.text().await.unwrap_or_default(),
+        401 => "Wrong password".to_string(),
+        403 => "Not allowed for your account".to_string(),
+        status => format!("Request failed ({status})"),
+    }
+}
+
+// ─── Login ───────────────────────────────────────────────────────────────────
+
+/// Full-screen login form, shown instead of the app while nobody is logged in.
+#[component]
+pub fn LoginScreen() -> Element {
+    let mut name = use_signal(String::new);
+    let mut password = use_signal(String::new);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+    let mut busy = use_signal(|| false);
+
+    let mut submit = move || {
+        if busy() || name.read().is_empty() {
+            return;
+        }
+        busy.set(true);
+        let request = LoginRequest {
+            name: name.read().trim().to_string(),
+            password: password.read().clone(),
+        };
+        spawn(async move {
+            match Request::post("/api/auth/login")
+                .json(&request)
+                .expect("serialize login")
+                .send()
+                .await
+            {
+                // Everything opened before the login was refused; start over.
+                Ok(resp) if resp.ok() => reload(),
+                Ok(resp) if resp.status() == 401 => error.set(Some("Wrong name or password".to_string())),
+                Ok(resp) => error.set(Some(error_message(resp).await)),
+                Err(_) => error.set(Some("Server not reachable".to_string())),
+            }
+            busy.set(false);
+        });
+    };
+
+    rsx! {
+        div { class: "fixed inset-0 z-[110] flex items-center justify-center bg-base-300 px-4",
+            div { class: "card bg-base-100 shadow-xl w-full max-w-sm",
+                div { class: "card-body gap-3",
+                    h1 { class: "text-3xl font-bold text-center mb-2", "RSPlayer" }
+                    input {
+                        class: "input input-bordered w-full",
+                        placeholder: "User name",
+                        autofocus: true,
+                        value: "{name}",
+                        oninput: move |e| name.set(e.value()),
+                    }
+                    input {
+                        r#type: "password",
+                        class: "input input-bordered w-full",
+                        placeholder: "Password",
+                        value: "{password}",
+                        oninput: move |e| password.set(e.value()),
+                        onkeydown: move |e| {
+                            if e.key() == Key::Enter {
+                                submit();
+                            }
+                        },
+                    }
+                    if let Some(msg) = error() {
+                        p { class: "text-sm text-error", "{msg}" }
+                    }
+                    button {
+                        class: "btn btn-primary w-full",
+                        disabled: busy(),
+                        onclick: move |_| submit(),
+                        if busy() {
+                            span { class: "loading loading-spinner loading-sm" }
+                        }
+                        "Log in"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ─── Own account ─────────────────────────────────────────────────────────────
+
+/// Password, API tokens and logout for whoever is logged in.
+#[component]
+pub fn AccountSection(user: UserInfo) -> Element {
+    let mut current_password = use_signal(String::new);
+    let mut new_password = use_signal(String::new);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+
+    let change_password = move |_| {
+        let request = ChangePasswordRequest {
+            current_password: current_password.read().clone(),
+            new_password: new_password.read().clone(),
+        };
+        spawn(async move {
+            match Request::post("/api/auth/password")
+                .json(&request)
+                .expect("serialize password")
+                .send()
+                .await
+            {
+                // That ended every session of the user, this one too.
+                Ok(resp) if resp.ok() => reload(),
+                Ok(resp) => error.set(Some(error_message(resp).await)),
+                Err(_) => error.set(Some("Server not reachable".to_string())),
+            }
+        });
+    };
+
+    let role: &'static str = user.role.into();
+    rsx! {
+        div { class: "flex items-center justify-between mb-3",
+            span { class: "text-sm",
+                "Logged in as "
+                span { class: "font-semibold", "{user.name}" }
+                span { class: "badge badge-sm badge-ghost ml-2", "{role}" }
+            }
+            button {
+                class: "btn btn-sm btn-outline",
+                onclick: move |_| {
+                    spawn(async move {
+                        let _ = Request::post("/api/auth/logout").send().await;
+                        reload();
+                    });
+                },
+                i { class: "material-icons text-sm mr-1", "logout" }
+                "Log out"
+            }
+        }
+        p { class: "text-xs text-base-content/60 mb-3",
+            "The queue is shared: its plays count for whoever last controlled playback, not for whoever queued the songs."
+        }
+        div { class: "flex flex-wrap gap-2 mb-1",
+            input {
+                r#type: "password",
+                class: "input input-sm input-bordered flex-1 min-w-32",
+                placeholder: "Current password",
+                value: "{current_password}",
+                oninput: move |e| current_password.set(e.value()),
+            }
+            input {
+                r#type: "password",
+                class: "input input-sm input-bordered flex-1 min-w-32",
+                placeholder: "New password",
+                value: "{new_password}",
+                oninput: move |e| new_password.set(e.value()),
+            }
+            button {
+                class: "btn btn-sm",
+                disabled: new_password.read().is_empty(),
+                onclick: change_password,
+                "Change password"
+            }
+        }
+        if let Some(msg) = error() {
+            p { class: "text-xs text-error", "{msg}" }
+        }
+        div { class: "divider my-2" }
+        ApiTokens { show_owner: user.role == Role::Admin }
+    }
+}
+
+/// API tokens for scripts and apps; admins see and revoke everyone's.
+#[component]
+fn ApiTokens(show_owner: bool) -> Element {
+    let mut tokens: Signal<Vec<ApiTokenInfo>> = use_signal(Vec::new);
+    let mut token_name = use_signal(String::new);
+    // Shown once: the server keeps only its hash.
+    let mut created: Signal<Option<String>> = use_signal(|| None);
+
+    let mut load = move || {
+        spawn(async move {
+            if let Ok(resp) = Request::get("/api/tokens").send().await {
+                if let Ok(list) = resp.json::<Vec<ApiTokenInfo>>().await {
+                    tokens.set(list);
+                }
+            }
+        });
+    };
+    use_effect(move || load());
+
+    let create = move |_| {
+        let request = CreateApiTokenRequest {
+            name: token_name.read().trim().to_string(),
+        };
+        spawn(async move {
+            if let Ok(resp) = Request::post("/api/tokens").json(&request).expect("serialize token").send().await {
+                if let Ok(token) = resp.json::<CreatedApiToken>().await {
+                    created.set(Some(token.token));
+                    token_name.set(String::new());
+                    load();
+                }
+            }
+        });
+    };
+
+    rsx! {
+        p { class: "text-sm font-medium mb-1", "API tokens" }
+        p { class: "text-xs text-base-content/60 mb-2",
+            "Send as "
+            code { "Authorization: Bearer <token>" }
+            " or append "
+            code { "?token=<token>" }
+            " to a URL."
+        }
+        for token in tokens() {
+            div { key: "{token.id}", class: "flex items-center gap-2 py-1",
+                span { class: "text-sm flex-1 truncate", "{token.name}" }
+                if show_owner {
+                    span { class: "badge badge-sm badge-ghost", "{token.user}" }
+                }
+                span { class: "text-xs text-base-content/50", {token.created.format("%Y-%m-%d").to_string()} }
+                button {
+                    class: "btn btn-ghost btn-xs text-error",
+                    title: "Revoke",
+                    onclick: {
+                        let id = token.id.clone();
+                        move |_| {
+                            let id = id.clone();
+                            spawn(async move {
+                                let url = format!("/api/tokens/{}", js_sys::encode_uri_component(&id));
+                                let _ = Request::delete(&url).send().await;
+                                load();
+                            });
+                        }
+                    },
+                    i { class: "material-icons text-sm", "delete" }
+                }
+            }
+        }
+        div { class: "flex gap-2 mt-2",
+            input {
+                class: "input input-sm input-bordered flex-1",
+                placeholder: "Token name, e.g. Home Assistant",
+                value: "{token_name}",
+                oninput: move |e| token_name.set(e.value()),
+            }
+            button {
+                class: "btn btn-sm",
+                disabled: token_name.read().trim().is_empty(),
+                onclick: create,
+                "Create token"
+            }
+        }
+        if let Some(token) = created() {
+            div { class: "alert alert-info mt-2 flex-col items-start gap-1",
+                span { class: "text-xs", "Copy the token now, it is not shown again:" }
+                code { class: "text-xs break-all select-all", "{token}" }
+            }
+        }
+    }
+}
+
+// ─── Users (admin) ───────────────────────────────────────────────────────────
+
+/// Add, change and remove accounts.
+#[component]
+pub fn UsersSection() -> Element {
+    let auth_status = use_context::<AppState>().auth_status;
+    let mut users: Signal<Vec<UserInfo>> = use_signal(Vec::new);
+    let mut name = use_signal(String::new);
+    let mut role = use_signal(|| Role::Listener);
+    let mut password = use_signal(String::new);
+    let mut error: Signal<Option<String>> = use_signal(|| None);
+
+    let mut load = move || {
+        spawn(async move {
+            if let Ok(resp) = Request::get("/api/users").send().await {
+                if let Ok(list) = resp.json::<Vec<UserInfo>>().await {
+                    users.set(list);
+                }
+            }
+        });
+    };
+    use_effect(move || load());
+
+    // The first admin account switches access control on, if enabled.
+    let mut save = move |request: SaveUserRequest| {
+        spawn(async move {
+            match Request::post("/api/users").json(&request).expect("serialize user").send().await {
+                Ok(resp) if resp.ok() => {
+                    error.set(None);
+                    load();
+                    refresh_auth_status(auth_status);
+                }
+                Ok(resp) => error.set(Some(error_message(resp).await)),
+                Err(_) => error.set(Some("Server not reachable".to_string())),
+            }
+        });
+    };
+
+    rsx! {
+        for user in users() {
+            div { key: "{user.name}", class: "flex items-center gap-2 py-1",
+                span { class: "text-sm flex-1 truncate", "{user.name}" }
+                select {
+                    class: "select select-xs select-bordered",
+                    onchange: {
+                        let name = user.name.clone();
+                        move |e: FormEvent| {
+                            if let Ok(role) = e.value().parse::<Role>() {
+                                save(SaveUserRequest {
+                                    name: name.clone(),
+                                    role,
+                                    password: None,
+                                });
+                            }
+                        }
+                    },
+                    for r in Role::iter() {
+                        option {
+                            value: <&'static str>::from(r),
+                            selected: r == user.role,
+                            {<&'static str>::from(r)}
+                        }
+                    }
+                }
+                button {
+                    class: "btn btn-ghost btn-xs text-error",
+                    title: "Delete user",
+                    onclick: {
+                        let name = user.name.clone();
+                        move |_| {
+                            let name = name.clone();
+                            spawn(async move {
+                                let url = format!("/api/users/{}", js_sys::encode_uri_component(&name));
+                                match Request::delete(&url).send().await {
+                                    Ok(resp) if resp.ok() => {
+                                        load();
+                                        refresh_auth_status(auth_status);
+                                    }
+                                    Ok(resp) => error.set(Some(error_message(resp).await)),
+                                    Err(_) => error.set(Some("Server not reachable".to_string())),
+                                }
+                            });
+                        }
+                    },
+                    i { class: "material-icons text-sm", "delete" }
+                }
+            }
+        }
+        p { class: "text-xs text-base-content/60 mt-2 mb-1",
+            "Saving an existing name changes its role and, if given, its password."
+        }
+        div { class: "flex flex-wrap gap-2",
+            input {
+                class: "input input-sm input-bordered flex-1 min-w-24",
+                placeholder: "Name",
+                value: "{name}",
+                oninput: move |e| name.set(e.value()),
+            }
+            input {
+                r#type: "password",
+                class: "input input-sm input-bordered flex-1 min-w-24",
+                placeholder: "Password",
+                value: "{password}",
+                oninput: move |e| password.set(e.value()),
+            }
+            select {
+                class: "select select-sm select-bordered",
+                onchange: move |e| {
+                    if let Ok(r) = e.value().parse::<Role>() {
+                        role.set(r);
+                    }
+                },
+                for r in Role::iter() {
+                    option {
+                        value: <&'static str>::from(r),
+                        selected: r == role(),
+                        {<&'static str>::from(r)}
+                    }
+                }
+            }
+            button {
+                class: "btn btn-sm",
+                disabled: name.read().trim().is_empty(),
+                onclick: move |_| {
+                    let pw = password.read().clone();
+                    save(SaveUserRequest {
+                        name: name.read().trim().to_string(),
+                        role: role(),
+                        password: (!pw.is_empty()).then_some(pw),
+                    });
+                    name.set(String::new());
+                    password.set(String::new());
+                },
+                "Save user"
+            }
+        }
+        if let Some(msg) = error() {
+            p { class: "text-xs text-error mt-1", "{msg}" }
+        }
+    }
+}
+
+// ─── Profile ─────────────────────────────────────────────────────────────────
+
+/// Where this browser remembers its profile.
+const PROFILE_KEY: &str = "rsplayer_profile";
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// The profile this browser listens as while login is not required; the
+/// WebSocket carries it. `None` is the shared profile.
+pub fn selected_profile() -> Option<String> {
+    local_storage()?.get_item(PROFILE_KEY).ok()?
+}
+
+/// Picks whose likes, statistics and playlists this browser shows.
+#[component]
+pub fn ProfileSection() -> Element {
+    let mut profiles: Signal<Vec<String>> = use_signal(Vec::new);
+    let selected = selected_profile();
+
+    use_effect(move || {
+        spawn(async move {
+            if let Ok(resp) = Request::get("/api/profiles").send().await {
+                if let Ok(list) = resp.json::<Vec<String>>().await {
+                    profiles.set(list);
+                }
+            }
+        });
+    });
+
+    // A new session with the new profile.
+    let choose = move |e: FormEvent| {
+        if let Some(storage) = local_storage() {
+            let name = e.value();
+            let _ = if name.is_empty() {
+                storage.remove_item(PROFILE_KEY)
+            } else {
+                storage.set_item(PROFILE_KEY, &name)
+            };
+        }
+        reload();
+    };
+
+    rsx! {
+        if profiles.read().is_empty() {
+            p { class: "text-xs text-base-content/60",
+                "Add users under Access control to keep separate likes, statistics and playlists."
+            }
+        } else {
+            div { class: "flex items-center justify-between py-1.5",
+                span { class: "text-sm", "Listening as" }
+                select { class: "select select-sm select-bordered", onchange: choose,
+                    option { value: "", selected: selected.is_none(), "Everyone" }
+                    for name in profiles() {
+                        option {
+                            key: "{name}",
+                            value: "{name}",
+                            selected: selected.as_deref() == Some(name.as_str()),
+                            "{name}"
+                        }
+                    }
+                }
+            }
+            p { class: "text-xs text-base-content/60",
+                "Likes, play statistics and saved playlists are kept per profile. The queue is shared; its plays count for whoever controlled it last."
+            }
+        }
+    }
+}
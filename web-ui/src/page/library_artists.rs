@@ -1,5 +1,7 @@
 use api_models::common::{MetadataCommand, MetadataLibraryItem, QueueCommand, UserCommand};
+use api_models::player::TagFields;
 use dioxus::prelude::*;
+use gloo_net::http::Request;
 use indextree::{Arena, NodeId};
 use web_sys::WebSocket;
 
@@ -55,6 +57,9 @@ pub fn LibraryArtistsPage() -> Element {
     let mut tree: Signal<Tree> = use_signal(Tree::new);
     let mut loading = use_signal(|| true);
     let mut search = use_signal(String::new);
+    let mut editing: Signal<Option<MetadataLibraryItem>> = use_signal(|| None);
+    // Composer → work → recording browsing instead of artists.
+    let mut composers = use_signal(|| false);
 
     let route_search = use_hook(|| {
         web_sys::window()
@@ -95,7 +100,9 @@ pub fn LibraryArtistsPage() -> Element {
         *loading.write() = true;
         let term = search();
         *tree.write() = Tree::new();
-        if term.is_empty() {
+        if composers() {
+            ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryComposers));
+        } else if term.is_empty() {
             ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryArtists));
         } else {
             ws_send(&ws, &UserCommand::Metadata(MetadataCommand::SearchArtists(term)));
@@ -108,7 +115,7 @@ pub fn LibraryArtistsPage() -> Element {
                 input {
                     class: "input input-sm input-bordered flex-1",
                     r#type: "text",
-                    placeholder: "Search artists…",
+                    placeholder: if composers() { "Search composers…" } else { "Search artists…" },
                     value: "{search}",
                     oninput: move |e| search.set(e.value()),
                     onkeydown: move |e| { if e.key() == Key::Enter { do_search(); } },
@@ -120,12 +127,19 @@ pub fn LibraryArtistsPage() -> Element {
                     class: "btn btn-sm btn-ghost",
                     onclick: move |_| {
                         search.set(String::new());
-                        *tree.write() = Tree::new();
-                        *loading.write() = true;
-                        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryArtists));
+                        do_search();
                     },
                     i { class: "material-icons text-base", "backspace" }
                 }
+                button {
+                    class: if composers() { "btn btn-sm btn-active" } else { "btn btn-sm btn-ghost" },
+                    title: "Browse by composer and work",
+                    onclick: move |_| {
+                        composers.set(!composers());
+                        do_search();
+                    },
+                    i { class: "material-icons text-base", "history_edu" }
+                }
             }
 
             if loading() {
@@ -141,15 +155,18 @@ pub fn LibraryArtistsPage() -> Element {
                 {
                     let top_nodes: Vec<(NodeId, MetadataLibraryItem)> = {
                         let t = tree.read();
+                        // Composers are filtered here, the server searches artists.
+                        let term = search().to_lowercase();
                         t.root.children(&t.arena)
                             .map(|id| (id, t.arena.get(id).unwrap().get().clone()))
+                            .filter(|(_, item)| !composers() || item.get_title().to_lowercase().contains(&term))
                             .collect()
                     };
                     if !search().is_empty() && top_nodes.is_empty() {
                         rsx! {
                             div { class: "flex flex-col items-center justify-center gap-2 p-8 text-base-content/60",
                                 i { class: "material-icons text-4xl", "search_off" }
-                                span { "No artists match your search." }
+                                span { if composers() { "No composers match your search." } else { "No artists match your search." } }
                                 span { class: "text-sm", "Try a different search term." }
                             }
                         }
@@ -165,6 +182,7 @@ pub fn LibraryArtistsPage() -> Element {
                                                 node_id,
                                                 ws,
                                                 tree,
+                                                editing,
                                             }
                                         }
                                     })
@@ -174,18 +192,43 @@ pub fn LibraryArtistsPage() -> Element {
                     }
                 }
             }
+            if let Some(item) = editing() {
+                TagEditorModal {
+                    key: "{item.get_id()}",
+                    item,
+                    on_close: move |saved: bool| {
+                        editing.set(None);
+                        if saved {
+                            do_search();
+                        }
+                    },
+                }
+            }
         }
     }
 }
 
 #[component]
-fn ArtistNode(item: MetadataLibraryItem, node_id: NodeId, ws: Signal<Option<WebSocket>>, tree: Signal<Tree>) -> Element {
+fn ArtistNode(
+    item: MetadataLibraryItem,
+    node_id: NodeId,
+    ws: Signal<Option<WebSocket>>,
+    tree: Signal<Tree>,
+    editing: Signal<Option<MetadataLibraryItem>>,
+) -> Element {
     let label = item.get_title();
+    if matches!(item, MetadataLibraryItem::Disc { .. }) {
+        return rsx! {
+            div { class: "library-node__disc pl-3 pr-2 pt-2 pb-1 text-xs font-semibold text-base-content/60", "{label}" }
+        };
+    }
     let is_song = matches!(item, MetadataLibraryItem::SongItem(_));
     let has_children = node_id.children(&tree.read().arena).count() > 0;
     let icon = match &item {
         MetadataLibraryItem::Artist { .. } => "person",
-        MetadataLibraryItem::Album { .. } => "album",
+        MetadataLibraryItem::Album { .. } | MetadataLibraryItem::Recording { .. } => "album",
+        MetadataLibraryItem::Composer { .. } => "history_edu",
+        MetadataLibraryItem::Work { .. } => "library_music",
         MetadataLibraryItem::SongItem(_) => "music_note",
         _ => "folder",
     };
@@ -209,15 +252,7 @@ fn ArtistNode(item: MetadataLibraryItem, node_id: NodeId, ws: Signal<Option<WebS
                                     tw.collapse_siblings(node_id);
                                     tw.current = node_id;
                                 }
-                                match &item {
-                                    MetadataLibraryItem::Artist { name } => {
-                                        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryAlbumsByArtist(name.clone())));
-                                    }
-                                    MetadataLibraryItem::Album { .. } => {
-                                        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QuerySongsByAlbum(item.get_id())));
-                                    }
-                                    _ => {}
-                                }
+                                query_children(&item, &ws);
                             }
                         }
                     }
@@ -240,15 +275,7 @@ fn ArtistNode(item: MetadataLibraryItem, node_id: NodeId, ws: Signal<Option<WebS
                                         tw.collapse_siblings(node_id);
                                         tw.current = node_id;
                                     }
-                                    match &item {
-                                        MetadataLibraryItem::Artist { name } => {
-                                            ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryAlbumsByArtist(name.clone())));
-                                        }
-                                        MetadataLibraryItem::Album { .. } => {
-                                            ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QuerySongsByAlbum(item.get_id())));
-                                        }
-                                        _ => {}
-                                    }
+                                    query_children(&item, &ws);
                                 }
                             }
                         },
@@ -263,7 +290,7 @@ fn ArtistNode(item: MetadataLibraryItem, node_id: NodeId, ws: Signal<Option<WebS
                 }
                 span { class: "flex-1 text-sm truncate", "{label}" }
                 div { class: "ml-auto flex items-center gap-1",
-                    {queue_actions(item.clone(), ws)}
+                    {queue_actions(item.clone(), ws, editing)}
                 }
             }
             if has_children {
@@ -283,6 +310,7 @@ fn ArtistNode(item: MetadataLibraryItem, node_id: NodeId, ws: Signal<Option<WebS
                                     node_id: child_id,
                                     ws,
                                     tree,
+                                    editing,
                                 }
                             }
                         })
@@ -293,12 +321,38 @@ fn ArtistNode(item: MetadataLibraryItem, node_id: NodeId, ws: Signal<Option<WebS
     }
 }
 
-fn queue_actions(item: MetadataLibraryItem, ws: Signal<Option<WebSocket>>) -> Element {
+fn query_children(item: &MetadataLibraryItem, ws: &Signal<Option<WebSocket>>) {
+    let cmd = match item {
+        MetadataLibraryItem::Artist { name } => MetadataCommand::QueryAlbumsByArtist(name.clone()),
+        MetadataLibraryItem::Album { .. } => MetadataCommand::QuerySongsByAlbum(item.get_id()),
+        MetadataLibraryItem::Composer { name } => MetadataCommand::QueryWorksByComposer(name.clone()),
+        MetadataLibraryItem::Work { id, .. } => MetadataCommand::QueryRecordingsByWork(id.clone()),
+        MetadataLibraryItem::Recording { id, .. } => MetadataCommand::QuerySongsByRecording(id.clone()),
+        _ => return,
+    };
+    ws_send(ws, &UserCommand::Metadata(cmd));
+}
+
+fn queue_actions(item: MetadataLibraryItem, ws: Signal<Option<WebSocket>>, mut editing: Signal<Option<MetadataLibraryItem>>) -> Element {
     let is_song = matches!(item, MetadataLibraryItem::SongItem(_));
+    let is_album = matches!(item, MetadataLibraryItem::Album { .. });
+    let is_composer = matches!(item, MetadataLibraryItem::Composer { .. });
     let i2 = item.clone();
+    let i3 = item.clone();
 
-    if is_song {
-        rsx! {
+    rsx! {
+        if is_song || is_album {
+            button {
+                class: "btn btn-ghost btn-xs",
+                title: "Edit tags",
+                onclick: move |e| {
+                    e.stop_propagation();
+                    editing.set(Some(i3.clone()));
+                },
+                i { class: "material-icons text-sm", "edit" }
+            }
+        }
+        if is_song {
             button {
                 class: "btn btn-ghost btn-xs",
                 title: "Add to queue",
@@ -311,9 +365,7 @@ fn queue_actions(item: MetadataLibraryItem, ws: Signal<Option<WebSocket>>) -> El
                 onclick: move |_| send_queue_cmd(&i2, &ws, "after"),
                 i { class: "material-icons text-sm", "playlist_play" }
             }
-        }
-    } else {
-        rsx! {
+        } else if !is_composer {
             button {
                 class: "btn btn-ghost btn-xs",
                 title: "Load to queue",
@@ -344,7 +396,168 @@ fn send_queue_cmd(item: &MetadataLibraryItem, ws: &Signal<Option<WebSocket>>, ac
         (MetadataLibraryItem::Artist { name }, "after") => QueueCommand::AddArtistAfterCurrent(name.clone()),
         (MetadataLibraryItem::Artist { name }, "load") => QueueCommand::LoadArtistInQueue(name.clone()),
         (MetadataLibraryItem::Artist { name }, "play") => QueueCommand::AddArtistAndPlay(name.clone()),
+        (MetadataLibraryItem::Work { .. } | MetadataLibraryItem::Recording { .. }, "add") => QueueCommand::AddWorkToQueue(item.get_id()),
+        (MetadataLibraryItem::Work { .. } | MetadataLibraryItem::Recording { .. }, "load") => QueueCommand::LoadWorkInQueue(item.get_id()),
         _ => return,
     };
     ws_send(ws, &UserCommand::Queue(cmd));
 }
+
+const TAG_FIELD_LABELS: [&str; 9] = [
+    "Title",
+    "Artist",
+    "Album",
+    "Album artist",
+    "Track",
+    "Disc",
+    "Date",
+    "Genre",
+    "Composer",
+];
+/// Title and track differ per song, so album edits leave them out.
+const PER_SONG_FIELDS: [usize; 2] = [0, 4];
+
+fn tag_values(fields: &TagFields) -> [Option<String>; 9] {
+    [
+        fields.title.clone(),
+        fields.artist.clone(),
+        fields.album.clone(),
+        fields.album_artist.clone(),
+        fields.track.clone(),
+        fields.disc.clone(),
+        fields.date.clone(),
+        fields.genre.clone(),
+        fields.composer.clone(),
+    ]
+}
+
+fn tag_fields([title, artist, album, album_artist, track, disc, date, genre, composer]: [Option<String>; 9]) -> TagFields {
+    TagFields {
+        title,
+        artist,
+        album,
+        album_artist,
+        track,
+        disc,
+        date,
+        genre,
+        composer,
+    }
+}
+
+/// Edits the tags of one song, or the shared tags of a whole album. Only
+/// changed fields are sent; for an album a blank field is left as it is.
+/// Albums also get a cover image upload, which applies immediately.
+#[component]
+fn TagEditorModal(item: MetadataLibraryItem, on_close: EventHandler<bool>) -> Element {
+    let ws = use_context::<Signal<Option<WebSocket>>>();
+    let is_album = matches!(item, MetadataLibraryItem::Album { .. });
+    let original = match &item {
+        MetadataLibraryItem::SongItem(song) => tag_values(&TagFields::from_song(song)),
+        MetadataLibraryItem::Album { name, .. } => tag_values(&TagFields {
+            album: Some(name.clone()),
+            ..Default::default()
+        }),
+        _ => tag_values(&TagFields::default()),
+    };
+    let mut values = use_signal(|| original.clone());
+    let mut upload_status: Signal<Option<String>> = use_signal(|| None);
+    let heading = if is_album { "Edit album tags" } else { "Edit tags" };
+    let title = item.get_title();
+    let album_id = item.get_id();
+
+    let upload = move |e: Event<FormData>| {
+        let album_id = album_id.clone();
+        spawn(async move {
+            let Some(file) = e.files().into_iter().next() else {
+                return;
+            };
+            upload_status.set(Some("Uploading…".to_owned()));
+            let result = match file.read_bytes().await {
+                Ok(bytes) => upload_album_artwork(&album_id, &bytes).await,
+                Err(e) => Err(e.to_string()),
+            };
+            upload_status.set(Some(
+                result.map_or_else(|e| format!("Upload failed: {e}"), |()| "Cover image uploaded".to_owned()),
+            ));
+        });
+    };
+
+    let save = move |_| {
+        let mut changed = values.read().clone();
+        for (value, before) in changed.iter_mut().zip(&original) {
+            let keep = value.as_deref().is_none_or(|v| is_album && v.trim().is_empty());
+            if keep || value == before {
+                *value = None;
+            }
+        }
+        let fields = tag_fields(changed);
+        if fields.is_empty() {
+            on_close.call(false);
+            return;
+        }
+        let cmd = match &item {
+            MetadataLibraryItem::SongItem(song) => MetadataCommand::UpdateTags(song.file.clone(), fields),
+            _ => MetadataCommand::UpdateAlbumTags(item.get_id(), fields),
+        };
+        ws_send(&ws, &UserCommand::Metadata(cmd));
+        on_close.call(true);
+    };
+
+    rsx! {
+        div { class: "modal modal-open",
+            div { class: "modal-backdrop", onclick: move |_| on_close.call(false) }
+            div { class: "modal-box max-w-md",
+                h3 { class: "font-bold text-lg", "{heading}" }
+                p { class: "text-sm text-base-content/60 truncate mb-3", "{title}" }
+                div { class: "space-y-2",
+                    for (index, name) in TAG_FIELD_LABELS.iter().enumerate().filter(|(i, _)| !is_album || !PER_SONG_FIELDS.contains(i)) {
+                        label { class: "flex items-center gap-2",
+                            span { class: "text-sm w-28 shrink-0", "{name}" }
+                            input {
+                                class: "input input-sm input-bordered flex-1",
+                                r#type: "text",
+                                placeholder: if is_album { "unchanged" } else { "" },
+                                value: "{values.read()[index].clone().unwrap_or_default()}",
+                                oninput: move |e| values.write()[index] = Some(e.value()),
+                            }
+                        }
+                    }
+                }
+                if is_album {
+                    label { class: "flex items-center gap-2 mt-3",
+                        span { class: "text-sm w-28 shrink-0", "Cover image" }
+                        input {
+                            class: "file-input file-input-sm file-input-bordered flex-1",
+                            r#type: "file",
+                            accept: "image/*",
+                            onchange: upload,
+                        }
+                    }
+                    if let Some(status) = upload_status() {
+                        p { class: "text-xs text-base-content/60 mt-1", "{status}" }
+                    }
+                }
+                div { class: "modal-action",
+                    button { class: "btn btn-sm", onclick: move |_| on_close.call(false), "Cancel" }
+                    button { class: "btn btn-sm btn-primary", onclick: save, "Save" }
+                }
+            }
+        }
+    }
+}
+
+async fn upload_album_artwork(album_id: &str, image: &[u8]) -> Result<(), String> {
+    let url = format!("/api/albums/{}/artwork", js_sys::encode_uri_component(album_id));
+    let response = Request::post(&url)
+        .body(js_sys::Uint8Array::from(image))
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.ok() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status()))
+    }
+}
@@ -285,7 +285,7 @@ fn RawAlbumCarousel(
                 let title = album.title.clone(); let title2 = title.clone();
                 let artist = album.artist.clone().unwrap_or_default();
                 let year = album.released.as_ref().map(|d| format!("{}", d.format("%Y"))).unwrap_or_default();
-                let img_src = album.image_id.as_ref().map_or_else(|| "/no_album.svg".to_string(), |i| format!("/artwork/{i}"));
+                let img_src = album.image_id.as_ref().map_or_else(|| "/no_album.svg".to_string(), |i| format!("/artwork/thumbs/{i}"));
                 rsx! {
                     div { key: "{id}", class: "carousel-item",
                         div { class: "card w-32 bg-base-200 cursor-pointer hover:bg-base-300 transition shadow-sm group",
@@ -328,7 +328,7 @@ fn PlaylistCarousel(
                     _ => return None,
                 };
                 let id2 = id.clone(); let id3 = id.clone(); let name2 = name.clone();
-                let img_src = image_id.map_or_else(|| "/no_album.svg".to_string(), |i| format!("/artwork/{i}"));
+                let img_src = image_id.map_or_else(|| "/no_album.svg".to_string(), |i| format!("/artwork/thumbs/{i}"));
                 Some(rsx! {
                     div { key: "{id}", class: "carousel-item",
                         div { class: "card w-32 bg-base-200 cursor-pointer hover:bg-base-300 transition shadow-sm group",
@@ -1,4 +1,5 @@
 use api_models::common::{MetadataCommand, QueueCommand, UserCommand};
+use api_models::player::RadioStation;
 use dioxus::prelude::*;
 use gloo_net::http::Request;
 use serde::{Deserialize, Serialize};
@@ -40,9 +41,32 @@ struct Station {
     bitrate: usize,
 }
 
+impl Station {
+    fn to_radio_station(&self) -> RadioStation {
+        let tags: Vec<String> = self
+            .tags
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_owned)
+            .collect();
+        RadioStation {
+            name: self.name.clone(),
+            url: self.url.clone(),
+            logo_url: Some(self.favicon.clone()),
+            genre: tags.first().cloned(),
+            codec: Some(self.codec.clone()),
+            bitrate_kbps: u32::try_from(self.bitrate).ok().filter(|kbps| *kbps > 0),
+            tags,
+            radio_browser_uuid: Some(self.stationuuid.clone()),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum FilterType {
-    Favorites,
+    Saved,
     Country,
     Language,
     Tag,
@@ -61,34 +85,16 @@ pub fn LibraryRadioPage() -> Element {
     let state = use_context::<AppState>();
     let ws = use_context::<Signal<Option<WebSocket>>>();
 
-    let mut filter = use_signal(|| FilterType::Favorites);
-    let mut loading = use_signal(|| *filter.read() == FilterType::Favorites);
+    let mut filter = use_signal(|| FilterType::Saved);
+    let mut loading = use_signal(|| false);
     let mut browse_items: Signal<Vec<BrowseItem>> = use_signal(Vec::new);
     let mut stations: Signal<Vec<Station>> = use_signal(Vec::new);
     let mut showing_stations = use_signal(|| false);
     let mut search = use_signal(String::new);
 
-    // Query favorites on mount
+    // Saved stations, also marking browsed stations that are saved already
     use_effect(move || {
-        if *filter.read() == FilterType::Favorites {
-            ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryFavoriteRadioStations));
-        }
-    });
-
-    // When favorite UUIDs arrive, fetch station details
-    use_effect(move || {
-        let uuids = state.favorite_radio_stations.read().clone();
-        if !uuids.is_empty() && *filter.read() == FilterType::Favorites {
-            spawn(async move {
-                let fetched = fetch_stations_by_uuid(uuids).await;
-                *stations.write() = fetched;
-                *loading.write() = false;
-                *showing_stations.write() = true;
-            });
-        } else if uuids.is_empty() && *filter.read() == FilterType::Favorites {
-            *loading.write() = false;
-            *showing_stations.write() = true;
-        }
+        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryRadioStations));
     });
 
     let mut change_filter = move |new_filter: FilterType| {
@@ -98,8 +104,8 @@ pub fn LibraryRadioPage() -> Element {
         *browse_items.write() = Vec::new();
         *stations.write() = Vec::new();
         match new_filter {
-            FilterType::Favorites => {
-                ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryFavoriteRadioStations));
+            FilterType::Saved => {
+                *loading.write() = false;
             }
             FilterType::Country => {
                 spawn(async move {
@@ -145,7 +151,7 @@ pub fn LibraryRadioPage() -> Element {
             // ── Filter tabs ────────────────────────────────────────────────
             div { class: "flex gap-2 px-3 py-2 border-b border-base-300 overflow-x-auto",
                 for (label , ft) in [
-                    ("Favorites", FilterType::Favorites),
+                    ("My stations", FilterType::Saved),
                     ("Search", FilterType::Search),
                     ("Countries", FilterType::Country),
                     ("Languages", FilterType::Language),
@@ -225,7 +231,9 @@ pub fn LibraryRadioPage() -> Element {
             }
 
             // ── Content ────────────────────────────────────────────────────
-            if loading() && *filter.read() != FilterType::Favorites {
+            if *filter.read() == FilterType::Saved {
+                SavedStations {}
+            } else if loading() {
                 div { class: "flex flex-col gap-1 p-3",
                     {(0..8).map(|_| rsx! {
                         div { class: "flex items-center gap-2 py-1.5 px-2",
@@ -237,29 +245,12 @@ pub fn LibraryRadioPage() -> Element {
                         }
                     })}
                 }
-            } else if *filter.read() == FilterType::Favorites && loading() {
-                div { class: "flex flex-col gap-1 p-3",
-                    {(0..4).map(|_| rsx! {
-                        div { class: "flex items-center gap-2 py-1.5 px-2",
-                            div { class: "skeleton w-8 h-8 rounded-full" }
-                            div { class: "flex-1 flex flex-col gap-1",
-                                div { class: "skeleton h-4 w-3/4 rounded" }
-                                div { class: "skeleton h-3 w-1/2 rounded" }
-                            }
-                        }
-                    })}
-                }
             } else if showing_stations() {
                 // Station list
                 if stations.read().is_empty() {
                     div { class: "flex flex-col items-center py-16 gap-3 text-base-content/40",
                         i { class: "material-icons text-5xl", "radio" }
-                        if *filter.read() == FilterType::Favorites {
-                            p { class: "text-center", "No favourite stations yet." }
-                            p { class: "text-sm text-center",
-                                "Browse by Country, Language, or Tags to discover stations and add them here."
-                            }
-                        } else if *filter.read() == FilterType::Search {
+                        if *filter.read() == FilterType::Search {
                             p { "No stations found. Try a different search term." }
                         } else {
                             p { "No stations found." }
@@ -268,14 +259,18 @@ pub fn LibraryRadioPage() -> Element {
                 } else {
                     div { class: "overflow-y-auto",
                         {
-                            let is_favorites = *filter.read() == FilterType::Favorites;
+                            let saved = state.radio_stations.read().clone();
                             stations
                                 .read()
                                 .iter()
                                 .map(move |st| {
                                     let url = st.url.clone();
                                     let url2 = st.url.clone();
-                                    let uuid = st.stationuuid.clone();
+                                    let saved_id = saved
+                                        .iter()
+                                        .find(|s| s.radio_browser_uuid.as_deref() == Some(st.stationuuid.as_str()))
+                                        .map(|s| s.id.clone());
+                                    let station = st.to_radio_station();
                                     let key = st.stationuuid.clone();
                                     rsx! {
                                         div {
@@ -313,34 +308,23 @@ pub fn LibraryRadioPage() -> Element {
                                                     ),
                                                     i { class: "material-icons text-sm", "play_arrow" }
                                                 }
-                                                if is_favorites {
+                                                if let Some(id) = saved_id {
                                                     button {
                                                         class: "btn btn-ghost btn-xs text-error",
-                                                        title: "Remove from favorites",
-                                                        onclick: move |_| {
-                                                            ws_send(
-                                                                // Re-query to refresh list
-                                                                &ws,
-                                                                &UserCommand::Metadata(
-                                                                    MetadataCommand::DislikeMediaItem(format!("radio_uuid_{}", uuid.clone())),
-                                                                ),
-                                                            );
-                                                            ws_send(
-                                                                &ws,
-                                                                &UserCommand::Metadata(MetadataCommand::QueryFavoriteRadioStations),
-                                                            );
-                                                        },
+                                                        title: "Remove from my stations",
+                                                        onclick: move |_| ws_send(
+                                                            &ws,
+                                                            &UserCommand::Metadata(MetadataCommand::DeleteRadioStation(id.clone())),
+                                                        ),
                                                         i { class: "material-icons text-sm", "favorite" }
                                                     }
                                                 } else {
                                                     button {
                                                         class: "btn btn-ghost btn-xs",
-                                                        title: "Add to favorites",
+                                                        title: "Add to my stations",
                                                         onclick: move |_| ws_send(
                                                             &ws,
-                                                            &UserCommand::Metadata(
-                                                                MetadataCommand::LikeMediaItem(format!("radio_uuid_{uuid}")),
-                                                            ),
+                                                            &UserCommand::Metadata(MetadataCommand::SaveRadioStation(station.clone())),
                                                         ),
                                                         i { class: "material-icons text-sm", "favorite_border" }
                                                     }
@@ -412,12 +396,226 @@ pub fn LibraryRadioPage() -> Element {
     }
 }
 
-async fn fetch_stations_by_uuid(uuids: Vec<String>) -> Vec<Station> {
-    let url = format!("{RADIO_BROWSER_URL}stations/byuuid?uuids={}", uuids.join(","));
-    let Ok(resp) = Request::get(&url).send().await else {
-        return vec![];
+/// The station library kept on the server: custom stations and the ones
+/// saved while browsing, in the user's order.
+#[component]
+fn SavedStations() -> Element {
+    let state = use_context::<AppState>();
+    let ws = use_context::<Signal<Option<WebSocket>>>();
+    let mut editing: Signal<Option<RadioStation>> = use_signal(|| None);
+
+    // Liked stations not saved yet, offered for import
+    use_effect(move || {
+        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryFavoriteRadioStations));
+    });
+
+    let saved = state.radio_stations.read().clone();
+    let last = saved.len().saturating_sub(1);
+    let liked = state
+        .favorite_radio_stations
+        .read()
+        .iter()
+        .filter(|f| !saved.iter().any(|s| s.radio_browser_uuid.as_ref() == Some(*f) || s.url == **f))
+        .count();
+
+    rsx! {
+        div { class: "flex items-center gap-2 px-3 py-2 border-b border-base-300",
+            button {
+                class: "btn btn-sm btn-ghost",
+                title: "Add a station by its stream URL",
+                onclick: move |_| editing.set(Some(RadioStation::default())),
+                i { class: "material-icons text-base", "add" }
+                "Add station"
+            }
+            if liked > 0 {
+                button {
+                    class: "btn btn-sm btn-ghost",
+                    title: "Save the stations liked in the radio browser",
+                    onclick: move |_| ws_send(&ws, &UserCommand::Metadata(MetadataCommand::ImportFavoriteRadioStations)),
+                    i { class: "material-icons text-base", "download" }
+                    "Import {liked} liked"
+                }
+            }
+        }
+        if saved.is_empty() {
+            div { class: "flex flex-col items-center py-16 gap-3 text-base-content/40",
+                i { class: "material-icons text-5xl", "radio" }
+                p { class: "text-center", "No saved stations yet." }
+                p { class: "text-sm text-center",
+                    "Browse by Country, Language, or Tags to discover stations, or add one by its stream URL."
+                }
+            }
+        } else {
+            div { class: "overflow-y-auto",
+                for (index , st) in saved.into_iter().enumerate() {
+                    div {
+                        key: "{st.id}",
+                        class: "flex items-center gap-3 px-3 py-2 hover:bg-base-200 group",
+                        if let Some(logo) = st.logo_url.clone() {
+                            img { class: "w-8 h-8 rounded-full object-cover", src: "{logo}" }
+                        } else {
+                            span { class: "w-8 h-8 flex items-center justify-center rounded-full bg-base-300",
+                                i { class: "material-icons text-sm", "radio" }
+                            }
+                        }
+                        div { class: "flex-1 min-w-0",
+                            p { class: "text-sm font-medium truncate", "{st.name}" }
+                            p { class: "text-xs text-base-content/50 truncate", "{station_details(&st)}" }
+                        }
+                        div { class: "flex sm:hidden sm:group-hover:flex items-center gap-1",
+                            button {
+                                class: "btn btn-ghost btn-xs",
+                                title: "Add to queue",
+                                onclick: {
+                                    let url = st.url.clone();
+                                    move |_| ws_send(&ws, &UserCommand::Queue(QueueCommand::AddSongToQueue(url.clone())))
+                                },
+                                i { class: "material-icons text-sm", "playlist_add" }
+                            }
+                            button {
+                                class: "btn btn-ghost btn-xs",
+                                title: "Play now",
+                                onclick: {
+                                    let url = st.url.clone();
+                                    move |_| ws_send(&ws, &UserCommand::Queue(QueueCommand::AddSongAndPlay(url.clone())))
+                                },
+                                i { class: "material-icons text-sm", "play_arrow" }
+                            }
+                            button {
+                                class: "btn btn-ghost btn-xs",
+                                title: "Move up",
+                                disabled: index == 0,
+                                onclick: {
+                                    let id = st.id.clone();
+                                    move |_| ws_send(
+                                        &ws,
+                                        &UserCommand::Metadata(MetadataCommand::MoveRadioStation(id.clone(), index.saturating_sub(1))),
+                                    )
+                                },
+                                i { class: "material-icons text-sm", "arrow_upward" }
+                            }
+                            button {
+                                class: "btn btn-ghost btn-xs",
+                                title: "Move down",
+                                disabled: index == last,
+                                onclick: {
+                                    let id = st.id.clone();
+                                    move |_| ws_send(&ws, &UserCommand::Metadata(MetadataCommand::MoveRadioStation(id.clone(), index + 1)))
+                                },
+                                i { class: "material-icons text-sm", "arrow_downward" }
+                            }
+                            button {
+                                class: "btn btn-ghost btn-xs",
+                                title: "Edit",
+                                onclick: {
+                                    let st = st.clone();
+                                    move |_| editing.set(Some(st.clone()))
+                                },
+                                i { class: "material-icons text-sm", "edit" }
+                            }
+                            button {
+                                class: "btn btn-ghost btn-xs text-error",
+                                title: "Remove from my stations",
+                                onclick: {
+                                    let id = st.id.clone();
+                                    move |_| ws_send(&ws, &UserCommand::Metadata(MetadataCommand::DeleteRadioStation(id.clone())))
+                                },
+                                i { class: "material-icons text-sm", "delete" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(station) = editing() {
+            StationEditorModal {
+                key: "{station.id}",
+                station,
+                on_close: move |()| editing.set(None),
+            }
+        }
+    }
+}
+
+/// Codec, bitrate, genre and tags of a saved station, as far as known.
+fn station_details(station: &RadioStation) -> String {
+    let format = [
+        station.codec.clone(),
+        station.bitrate_kbps.map(|kbps| format!("{kbps}kbps")),
+        station.genre.clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" ");
+    let tags = station.tags.join(", ");
+    match (format.is_empty(), tags.is_empty()) {
+        (true, true) => station.url.clone(),
+        (false, true) => format,
+        (true, false) => tags,
+        (false, false) => format!("{format} • {tags}"),
+    }
+}
+
+const STATION_FIELD_LABELS: [&str; 5] = ["Name", "Stream URL", "Logo URL", "Genre", "Tags"];
+
+/// Adds a custom station, or edits a saved one.
+#[component]
+fn StationEditorModal(station: RadioStation, on_close: EventHandler<()>) -> Element {
+    let ws = use_context::<Signal<Option<WebSocket>>>();
+    let heading = if station.id.is_empty() { "Add station" } else { "Edit station" };
+    let mut values = use_signal(|| {
+        [
+            station.name.clone(),
+            station.url.clone(),
+            station.logo_url.clone().unwrap_or_default(),
+            station.genre.clone().unwrap_or_default(),
+            station.tags.join(", "),
+        ]
+    });
+    let incomplete = values.read()[0].trim().is_empty() || values.read()[1].trim().is_empty();
+
+    let save = move |_| {
+        let [name, url, logo_url, genre, tags] = values.read().clone();
+        let optional = |value: String| (!value.trim().is_empty()).then_some(value);
+        let station = RadioStation {
+            name,
+            url,
+            logo_url: optional(logo_url),
+            genre: optional(genre),
+            tags: tags.split(',').map(|t| t.trim().to_owned()).filter(|t| !t.is_empty()).collect(),
+            ..station.clone()
+        };
+        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::SaveRadioStation(station)));
+        on_close.call(());
     };
-    resp.json::<Vec<Station>>().await.unwrap_or_default()
+
+    rsx! {
+        div { class: "modal modal-open",
+            div { class: "modal-backdrop", onclick: move |_| on_close.call(()) }
+            div { class: "modal-box max-w-md",
+                h3 { class: "font-bold text-lg mb-3", "{heading}" }
+                div { class: "space-y-2",
+                    for (index , name) in STATION_FIELD_LABELS.iter().enumerate() {
+                        label { class: "flex items-center gap-2",
+                            span { class: "text-sm w-28 shrink-0", "{name}" }
+                            input {
+                                class: "input input-sm input-bordered flex-1",
+                                r#type: if index == 1 || index == 2 { "url" } else { "text" },
+                                placeholder: if index == 1 { "https://…" } else if index == 4 { "comma separated" } else { "" },
+                                value: "{values.read()[index]}",
+                                oninput: move |e| values.write()[index] = e.value(),
+                            }
+                        }
+                    }
+                }
+                div { class: "modal-action",
+                    button { class: "btn btn-sm", onclick: move |_| on_close.call(()), "Cancel" }
+                    button { class: "btn btn-sm btn-primary", disabled: incomplete, onclick: save, "Save" }
+                }
+            }
+        }
+    }
 }
 
 async fn fetch_stations(by: &str, value: &str) -> Vec<Station> {
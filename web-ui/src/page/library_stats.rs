@@ -1,4 +1,5 @@
-use api_models::common::{MetadataCommand, UserCommand};
+use api_models::common::{dur_to_string, MetadataCommand, UserCommand};
+use api_models::state::DuplicateFile;
 use dioxus::prelude::*;
 use web_sys::WebSocket;
 
@@ -83,6 +84,8 @@ pub fn LibraryStatsPage() -> Element {
                         })}
                     }
                 }
+
+                DuplicatesSection {}
             }
         }
     } else {
@@ -107,3 +110,82 @@ fn StatCard(label: String, value: String) -> Element {
         }
     }
 }
+
+/// Runs a duplicate search and lists its groups, best copy first.
+#[component]
+fn DuplicatesSection() -> Element {
+    let state = use_context::<AppState>();
+    let ws = use_context::<Signal<Option<WebSocket>>>();
+    let mut use_fingerprints = use_signal(|| false);
+    let report = state.duplicate_report.read().clone();
+
+    rsx! {
+        div { class: "mb-6",
+            h2 { class: "text-lg font-semibold mb-3", "Duplicates" }
+            div { class: "flex items-center gap-3 mb-3",
+                label { class: "flex items-center gap-2 text-sm cursor-pointer",
+                    input {
+                        r#type: "checkbox",
+                        class: "toggle toggle-sm toggle-primary",
+                        checked: use_fingerprints(),
+                        onclick: move |_| use_fingerprints.toggle(),
+                    }
+                    "Compare fingerprints"
+                }
+                button {
+                    class: "btn btn-sm",
+                    onclick: move |_| {
+                        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::FindDuplicates(use_fingerprints())));
+                    },
+                    "Find duplicates"
+                }
+            }
+            if let Some(report) = report {
+                p { class: "text-sm text-base-content/60 mb-2",
+                    "{report.group_count} songs with copies, {report.redundant_files} redundant files among {report.songs_checked} songs"
+                    if report.use_fingerprints {
+                        " ({report.fingerprinted} fingerprinted)"
+                    }
+                }
+                if report.groups.len() < report.group_count {
+                    p { class: "text-xs text-base-content/50 mb-2", "Showing the first {report.groups.len()} songs." }
+                }
+                div { class: "space-y-2",
+                    for group in report.groups.iter() {
+                        div { class: "bg-base-200 rounded-lg p-3",
+                            div { class: "flex items-center gap-2 text-sm font-medium",
+                                span { class: "truncate", "{group.artist} — {group.title}" }
+                                if group.by_fingerprint {
+                                    span { class: "badge badge-sm badge-secondary", "fingerprint" }
+                                }
+                            }
+                            for file in group.files.iter() {
+                                div { class: "mt-1 text-xs",
+                                    p { class: "text-base-content/70", "{file_summary(file)}" }
+                                    p { class: "font-mono truncate text-base-content/50", title: "{file.file}", "{file.file}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn file_summary(file: &DuplicateFile) -> String {
+    let mut parts = vec![file.format.clone()];
+    if let Some(kbps) = file.bitrate_kbps {
+        parts.push(format!("{kbps} kbps"));
+    }
+    if let Some(rate) = file.sample_rate {
+        parts.push(format!("{:.1} kHz", f64::from(rate) / 1000.0));
+    }
+    if let Some(bits) = file.bits_per_sample {
+        parts.push(format!("{bits} bit"));
+    }
+    if let Some(time) = file.time {
+        parts.push(dur_to_string(&time));
+    }
+    parts.join(" · ")
+}
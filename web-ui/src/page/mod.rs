@@ -1,3 +1,4 @@
+pub mod account;
 pub mod home;
 pub mod library_artists;
 pub mod library_files;
@@ -1,9 +1,14 @@
 use std::time::Duration;
 
 use api_models::{
-    common::{MetadataCommand, MultiroomCommand, PlaybackMode, PlayerCommand, SystemRequest, UserCommand, Volume},
-    player::Song,
-    state::{MultiroomRole, PlayerInfo, PlayerState, SongProgress},
+    common::{
+        MetadataCommand, MultiroomCommand, PlaybackMode, PlayerCommand, PlaylistCommand, SchedulerCommand, SystemRequest, UserCommand,
+        Volume,
+    },
+    player::{Bookmark, RadioStation, Song},
+    playlist::{Playlist, PlaylistType, Playlists},
+    settings::{Schedule, ScheduledAction, Weekday},
+    state::{MultiroomRole, NextAlarm, PlayerInfo, PlayerState, SleepTimerMode, SleepTimerState, SongProgress},
 };
 use dioxus::prelude::*;
 use gloo_net::http::Request;
@@ -169,6 +174,12 @@ pub fn PlayerPage() -> Element {
                             let open = *ui.lyrics_open.peek();
                             ui.lyrics_open.set(!open);
                         },
+                        on_bookmarks: move |()| ui.bookmarks_open.set(true),
+                        sleep_timer: *state.sleep_timer.read(),
+                        on_sleep_timer: move |()| ui.sleep_timer_open.set(true),
+                        next_alarm: state.next_alarm.read().clone(),
+                        on_alarms: move |()| ui.alarms_open.set(true),
+                        recording_file: state.recording_file.read().clone(),
                     }
                     MultiroomPanel {
                         ws,
@@ -186,6 +197,32 @@ pub fn PlayerPage() -> Element {
                     progress: progress.read().clone(),
                 }
             }
+            if (ui.bookmarks_open)() {
+                BookmarksModal {
+                    ws,
+                    on_close: move |()| ui.bookmarks_open.set(false),
+                    bookmarks: state.bookmarks.read().clone(),
+                    current_song: current_song.read().clone(),
+                    current_time: progress.read().current_time.as_secs(),
+                }
+            }
+            if (ui.sleep_timer_open)() {
+                SleepTimerModal {
+                    ws,
+                    on_close: move |()| ui.sleep_timer_open.set(false),
+                    sleep_timer: *state.sleep_timer.read(),
+                }
+            }
+            if (ui.alarms_open)() {
+                AlarmsModal {
+                    ws,
+                    on_close: move |()| ui.alarms_open.set(false),
+                    schedules: state.schedules.read().clone(),
+                    next_alarm: state.next_alarm.read().clone(),
+                    stations: state.radio_stations.read().clone(),
+                    playlists: state.playlists.read().clone(),
+                }
+            }
         }
     }
 }
@@ -334,6 +371,12 @@ fn Controls(
     volume: Volume,
     current_song: Option<Song>,
     on_lyrics: EventHandler,
+    on_bookmarks: EventHandler,
+    sleep_timer: Option<SleepTimerState>,
+    on_sleep_timer: EventHandler,
+    next_alarm: Option<NextAlarm>,
+    on_alarms: EventHandler,
+    recording_file: Option<String>,
     vu_meter_enabled: bool,
     visualizer_type: Signal<VisualizerType>,
 ) -> Element {
@@ -349,6 +392,13 @@ fn Controls(
         .and_then(|s| s.statistics.as_ref())
         .is_some_and(|st| st.liked_count > 0);
     let song_id = current_song.as_ref().map(|s| s.file.clone());
+    let is_stream = song_id
+        .as_ref()
+        .is_some_and(|file| file.starts_with("http://") || file.starts_with("https://"));
+    let recording = recording_file.is_some();
+    let record_title = recording_file.map_or_else(|| "Record stream".to_string(), |file| format!("Stop recording to {file}"));
+    let alarm_time = next_alarm.as_ref().map(|alarm| alarm.at.format("%H:%M").to_string());
+    let alarm_title = next_alarm.map_or_else(|| "Alarms".to_string(), |alarm| format!("Next alarm: {}", alarm.name));
     let is_muted = volume.current == 0;
 
     rsx! {
@@ -462,6 +512,52 @@ fn Controls(
                     onclick: move |_| on_lyrics.call(()),
                     i { class: "material-icons", "lyrics" }
                 }
+                button {
+                    class: "btn btn-ghost btn-sm",
+                    title: "Bookmarks",
+                    onclick: move |_| on_bookmarks.call(()),
+                    i { class: "material-icons", "bookmarks" }
+                }
+                button {
+                    class: if sleep_timer.is_some() { "btn btn-ghost btn-sm text-primary" } else { "btn btn-ghost btn-sm" },
+                    title: "Sleep timer",
+                    onclick: move |_| on_sleep_timer.call(()),
+                    i { class: "material-icons", "bedtime" }
+                    if let Some(secs) = sleep_timer.and_then(|t| t.remaining_secs) {
+                        span { class: "text-xs", "{format_time(u64::from(secs))}" }
+                    }
+                }
+                button {
+                    class: if alarm_time.is_some() { "btn btn-ghost btn-sm text-primary" } else { "btn btn-ghost btn-sm" },
+                    title: "{alarm_title}",
+                    onclick: move |_| on_alarms.call(()),
+                    i { class: "material-icons", "alarm" }
+                    if let Some(time) = alarm_time {
+                        span { class: "text-xs", "{time}" }
+                    }
+                }
+                if recording || (is_stream && playing) {
+                    button {
+                        class: if recording { "btn btn-ghost btn-sm text-error" } else { "btn btn-ghost btn-sm" },
+                        title: "{record_title}",
+                        onclick: {
+                            let ws = ws;
+                            move |_| ws_send(
+                                &ws,
+                                &UserCommand::Player(
+                                    if recording { PlayerCommand::StopRecording } else { PlayerCommand::StartRecording },
+                                ),
+                            )
+                        },
+                        i { class: "material-icons",
+                            if recording {
+                                "stop_circle"
+                            } else {
+                                "fiber_manual_record"
+                            }
+                        }
+                    }
+                }
                 button {
                     class: "btn btn-ghost btn-sm",
                     title: if is_muted { "Unmute" } else { "Mute" },
@@ -597,6 +693,7 @@ pub fn BrowserAudioPlayback() -> Element {
                 if let Some(el) = doc.get_element_by_id(audio_id) {
                     let audio: web_sys::HtmlAudioElement = el.unchecked_into();
 
+                    let mut transcode_from = None;
                     let src = cur_song
                         .as_ref()
                         .map(|s| {
@@ -604,12 +701,22 @@ pub fn BrowserAudioPlayback() -> Element {
                             if file.starts_with("http://") || file.starts_with("https://") {
                                 file.clone()
                             } else {
-                                let encoded = String::from(js_sys::encode_uri_component(file));
-                                let encoded = encoded.replace("%2F", "/");
-                                format!("/music/{encoded}")
+                                if needs_transcoding(&audio, file) {
+                                    transcode_from = Some(0.0);
+                                }
+                                music_src(&audio, file, transcode_from)
                             }
                         })
                         .unwrap_or_default();
+                    let mut transcode_start = state_a.audio_transcode_start;
+                    transcode_start.set(transcode_from);
+                    if transcode_from.is_some() {
+                        // A stream being encoded has no duration yet.
+                        if let Some(time) = cur_song.as_ref().and_then(|s| s.time) {
+                            let mut progress = state_a.progress;
+                            progress.write().total_time = time;
+                        }
+                    }
 
                     if !src.is_empty() {
                         src_changing.set(true);
@@ -631,12 +738,13 @@ pub fn BrowserAudioPlayback() -> Element {
                             if *s1.audio_seeking.read() {
                                 return;
                             }
-                            let current = a1.current_time();
+                            let transcode_start = *s1.audio_transcode_start.read();
+                            let current = a1.current_time() + transcode_start.unwrap_or(0.0);
                             let duration = a1.duration();
                             if current.is_finite() {
                                 s1.progress.write().current_time = Duration::from_secs_f64(current);
                             }
-                            if duration.is_finite() && duration > 0.0 {
+                            if transcode_start.is_none() && duration.is_finite() && duration > 0.0 {
                                 s1.progress.write().total_time = Duration::from_secs_f64(duration);
                             }
                         });
@@ -685,6 +793,33 @@ pub fn BrowserAudioPlayback() -> Element {
                             .ok();
                         on_seeked.forget();
 
+                        // The browser could not play the original after all
+                        // (e.g. ALAC in an .m4a): fall back to a transcode.
+                        let mut s6 = state_a.clone();
+                        let a6 = audio.clone();
+                        let on_error = Closure::<dyn FnMut(web_sys::Event)>::new(move |_| {
+                            if s6.audio_transcode_start.read().is_some() {
+                                return;
+                            }
+                            let Some(song) = s6.current_song.read().clone() else {
+                                return;
+                            };
+                            if song.file.starts_with("http://") || song.file.starts_with("https://") {
+                                return;
+                            }
+                            let start = a6.current_time();
+                            s6.audio_transcode_start.set(Some(start));
+                            if let Some(time) = song.time {
+                                s6.progress.write().total_time = time;
+                            }
+                            a6.set_src(&music_src(&a6, &song.file, Some(start)));
+                            let _ = a6.play();
+                        });
+                        audio
+                            .add_event_listener_with_callback("error", on_error.as_ref().unchecked_ref())
+                            .ok();
+                        on_error.forget();
+
                         *listeners_attached.write() = true;
                     }
                 }
@@ -692,6 +827,31 @@ pub fn BrowserAudioPlayback() -> Element {
         }
     });
 
+    // Seeking in a transcoded stream reloads it from the new position.
+    let state_t = state.clone();
+    use_effect(move || {
+        let Some(position) = *state_t.audio_transcode_seek.read() else {
+            return;
+        };
+        let file = state_t.current_song.peek().as_ref().map(|s| s.file.clone());
+        if let (Some(file), Some(window)) = (file, web_sys::window()) {
+            if let Some(el) = window.document().and_then(|doc| doc.get_element_by_id(audio_id)) {
+                let audio: web_sys::HtmlAudioElement = el.unchecked_into();
+                let mut transcode_start = state_t.audio_transcode_start;
+                transcode_start.set(Some(position));
+                src_changing.set(true);
+                audio.set_src(&music_src(&audio, &file, Some(position)));
+                let _ = audio.play();
+                wasm_bindgen_futures::spawn_local(async move {
+                    gloo_timers::future::TimeoutFuture::new(200).await;
+                    src_changing.set(false);
+                });
+            }
+        }
+        let mut seek = state_t.audio_transcode_seek;
+        seek.set(None);
+    });
+
     // Sync play/pause from backend state changes.
     let state_p = state.clone();
     use_effect(move || {
@@ -756,6 +916,49 @@ pub fn BrowserAudioPlayback() -> Element {
     rsx! {}
 }
 
+/// Formats `/music` transcodes to, best first, with the type the browser is
+/// asked about.
+const TRANSCODE_FORMATS: [(&str, &str); 3] = [("flac", "audio/flac"), ("opus", "audio/ogg; codecs=opus"), ("mp3", "audio/mpeg")];
+/// Extensions no browser plays.
+const UNPLAYABLE: [&str; 4] = ["ape", "dsf", "dff", "wma"];
+
+/// Whether the browser needs library `file` transcoded. Files it only might
+/// not play are tried as they are; a playback error switches them over.
+fn needs_transcoding(audio: &web_sys::HtmlAudioElement, file: &str) -> bool {
+    if file.contains("#SACD_") {
+        return true;
+    }
+    let ext = file.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    if UNPLAYABLE.contains(&ext.as_str()) {
+        return true;
+    }
+    let mime = match ext.as_str() {
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "m4a" | "mp4" | "aac" => "audio/mp4",
+        "aif" | "aiff" => "audio/aiff",
+        "webm" => "audio/webm",
+        _ => return false,
+    };
+    audio.can_play_type(mime).is_empty()
+}
+
+/// `/music` URL of library `file`; `transcode_from` asks for a transcode to
+/// the best format the browser plays, starting there (seconds).
+fn music_src(audio: &web_sys::HtmlAudioElement, file: &str, transcode_from: Option<f64>) -> String {
+    let encoded = String::from(js_sys::encode_uri_component(file)).replace("%2F", "/");
+    let Some(start) = transcode_from else {
+        return format!("/music/{encoded}");
+    };
+    let format = TRANSCODE_FORMATS
+        .iter()
+        .find(|(_, mime)| !audio.can_play_type(mime).is_empty())
+        .map_or("mp3", |(format, _)| *format);
+    format!("/music/{encoded}?format={format}&t={start:.1}")
+}
+
 // ─── Seek Bar ────────────────────────────────────────────────────────────────
 
 #[component]
@@ -781,10 +984,15 @@ fn SeekBar(ws: Signal<Option<WebSocket>>, progress: SongProgress) -> Element {
                     let ws = ws;
                     let mut progress_sig = state.progress;
                     let mut seeking_sig = state.audio_seeking;
+                    let transcode_start_sig = state.audio_transcode_start;
+                    let mut transcode_seek_sig = state.audio_transcode_seek;
                     move |e: Event<FormData>| {
                         if let Ok(v) = e.value().parse::<u16>() {
                             ws_send(&ws, &UserCommand::Player(PlayerCommand::Seek(v)));
-                            if browser {
+                            if browser && transcode_start_sig.read().is_some() {
+                                transcode_seek_sig.set(Some(f64::from(v)));
+                                progress_sig.write().current_time = std::time::Duration::from_secs(v.into());
+                            } else if browser {
                                 *seeking_sig.write() = true;
                                 if let Some(window) = web_sys::window() {
                                     if let Some(doc) = window.document() {
@@ -918,6 +1126,464 @@ fn LyricsModal(
     }
 }
 
+// ─── Bookmarks Modal ─────────────────────────────────────────────────────────
+
+#[component]
+fn BookmarksModal(
+    ws: Signal<Option<WebSocket>>,
+    on_close: EventHandler,
+    bookmarks: Vec<Bookmark>,
+    current_song: Option<Song>,
+    current_time: u64,
+) -> Element {
+    let mut name_input = use_signal(String::new);
+
+    use_effect(move || {
+        ws_send(&ws, &UserCommand::Player(PlayerCommand::QueryBookmarks));
+    });
+
+    let mut add_bookmark = move || {
+        let name = name_input.peek().trim().to_string();
+        let name = if name.is_empty() { format_time(current_time) } else { name };
+        ws_send(&ws, &UserCommand::Player(PlayerCommand::AddBookmark(name)));
+        name_input.set(String::new());
+    };
+
+    rsx! {
+        div { class: "modal modal-open",
+            div { class: "modal-backdrop", onclick: move |_| on_close.call(()) }
+            div { class: "modal-box max-w-lg max-h-[80vh] overflow-y-auto",
+                button {
+                    class: "btn btn-sm btn-circle btn-ghost absolute right-2 top-2",
+                    onclick: move |_| on_close.call(()),
+                    "✕"
+                }
+                h3 { class: "font-bold text-lg mb-4", "Bookmarks" }
+                if current_song.is_some() {
+                    div { class: "flex gap-2 mb-4",
+                        input {
+                            class: "input input-bordered input-sm flex-1",
+                            placeholder: "Bookmark at {format_time(current_time)}",
+                            value: "{name_input}",
+                            oninput: move |e| name_input.set(e.value()),
+                            onkeydown: move |e| {
+                                if e.key() == Key::Enter {
+                                    add_bookmark();
+                                }
+                            },
+                        }
+                        button {
+                            class: "btn btn-primary btn-sm",
+                            onclick: move |_| add_bookmark(),
+                            "Add"
+                        }
+                    }
+                }
+                if bookmarks.is_empty() {
+                    div { class: "text-center py-8 text-base-content/50", "No bookmarks yet." }
+                } else {
+                    ul { class: "menu bg-base-200 rounded-box",
+                        for bm in bookmarks.iter() {
+                            li { key: "{bm.id}",
+                                div { class: "flex items-center gap-2",
+                                    a {
+                                        class: "flex-1 min-w-0",
+                                        onclick: {
+                                            let id = bm.id.clone();
+                                            move |_| {
+                                                ws_send(&ws, &UserCommand::Player(PlayerCommand::JumpToBookmark(id.clone())));
+                                                on_close.call(());
+                                            }
+                                        },
+                                        div { class: "truncate font-medium", "{bm.name}" }
+                                        div { class: "truncate text-xs opacity-60",
+                                            "{bm.song_title} · {format_time(u64::from(bm.position_secs))}"
+                                        }
+                                    }
+                                    button {
+                                        class: "btn btn-ghost btn-xs",
+                                        title: "Delete",
+                                        onclick: {
+                                            let id = bm.id.clone();
+                                            move |_| ws_send(&ws, &UserCommand::Player(PlayerCommand::DeleteBookmark(id.clone())))
+                                        },
+                                        i { class: "material-icons text-sm", "delete" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ─── Sleep Timer Modal ───────────────────────────────────────────────────────
+
+const SLEEP_TIMER_MINUTES: [u32; 6] = [15, 30, 45, 60, 90, 120];
+
+#[component]
+fn SleepTimerModal(ws: Signal<Option<WebSocket>>, on_close: EventHandler, sleep_timer: Option<SleepTimerState>) -> Element {
+    let send = move |cmd: PlayerCommand| {
+        ws_send(&ws, &UserCommand::Player(cmd));
+        on_close.call(());
+    };
+    let status = sleep_timer.map(|timer| {
+        let target = match timer.mode {
+            SleepTimerMode::Timer => "Stopping",
+            SleepTimerMode::EndOfTrack => "Stopping after this song",
+            SleepTimerMode::EndOfAlbum => "Stopping after this album",
+        };
+        timer.remaining_secs.map_or_else(
+            || target.to_string(),
+            |secs| format!("{target} in {}", format_time(u64::from(secs))),
+        )
+    });
+
+    rsx! {
+        div { class: "modal modal-open",
+            div { class: "modal-backdrop", onclick: move |_| on_close.call(()) }
+            div { class: "modal-box max-w-sm",
+                button {
+                    class: "btn btn-sm btn-circle btn-ghost absolute right-2 top-2",
+                    onclick: move |_| on_close.call(()),
+                    "✕"
+                }
+                h3 { class: "font-bold text-lg mb-4", "Sleep timer" }
+                if let Some(status) = status {
+                    div { class: "flex items-center justify-between mb-4",
+                        span { class: "text-sm", "{status}" }
+                        button {
+                            class: "btn btn-outline btn-sm",
+                            onclick: move |_| send(PlayerCommand::CancelSleepTimer),
+                            "Cancel"
+                        }
+                    }
+                }
+                div { class: "grid grid-cols-3 gap-2",
+                    for minutes in SLEEP_TIMER_MINUTES {
+                        button {
+                            key: "{minutes}",
+                            class: "btn btn-sm",
+                            onclick: move |_| send(PlayerCommand::SetSleepTimer(minutes)),
+                            "{minutes} min"
+                        }
+                    }
+                }
+                div { class: "flex flex-col gap-2 mt-2",
+                    button {
+                        class: "btn btn-sm",
+                        onclick: move |_| send(PlayerCommand::SleepAtEndOfTrack),
+                        "End of this song"
+                    }
+                    button {
+                        class: "btn btn-sm",
+                        onclick: move |_| send(PlayerCommand::SleepAtEndOfAlbum),
+                        "End of this album"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ─── Alarms Modal ────────────────────────────────────────────────────────────
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// A new schedule: weekdays at 07:00, fading in over a minute.
+fn new_schedule(stations: &[RadioStation]) -> Schedule {
+    Schedule {
+        id: String::new(),
+        name: "Wake up".to_string(),
+        enabled: true,
+        days: WEEKDAYS[..5].to_vec(),
+        hour: 7,
+        minute: 0,
+        action: ScheduledAction::Radio(stations.first().map(|s| s.url.clone()).unwrap_or_default()),
+        volume: None,
+        fade_in_secs: 60,
+        profile: None,
+    }
+}
+
+fn schedule_days(schedule: &Schedule) -> String {
+    match schedule.days.len() {
+        0 | 7 => "Every day".to_string(),
+        _ => WEEKDAYS
+            .iter()
+            .filter(|day| schedule.days.contains(day))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Applies `change` to the schedule being edited.
+fn edit_schedule(mut editing: Signal<Option<Schedule>>, change: impl FnOnce(&mut Schedule)) {
+    if let Some(schedule) = editing.write().as_mut() {
+        change(schedule);
+    }
+}
+
+#[component]
+fn AlarmsModal(
+    ws: Signal<Option<WebSocket>>,
+    on_close: EventHandler,
+    schedules: Vec<Schedule>,
+    next_alarm: Option<NextAlarm>,
+    stations: Vec<RadioStation>,
+    playlists: Option<Playlists>,
+) -> Element {
+    let mut editing = use_signal(|| None::<Schedule>);
+
+    use_effect(move || {
+        ws_send(&ws, &UserCommand::Scheduler(SchedulerCommand::QuerySchedules));
+        ws_send(&ws, &UserCommand::Metadata(MetadataCommand::QueryRadioStations));
+        ws_send(&ws, &UserCommand::Playlist(PlaylistCommand::QueryPlaylist));
+    });
+
+    let saved_playlists: Vec<Playlist> = playlists
+        .map(|p| {
+            p.items
+                .into_iter()
+                .filter_map(|item| match item {
+                    PlaylistType::Saved(pl) => Some(pl),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let target_name = |action: &ScheduledAction| match action {
+        ScheduledAction::Radio(url) => stations
+            .iter()
+            .find(|s| &s.url == url)
+            .map_or_else(|| url.clone(), |s| s.name.clone()),
+        ScheduledAction::Playlist(id) => saved_playlists
+            .iter()
+            .find(|pl| &pl.id == id)
+            .map_or_else(|| id.clone(), |pl| pl.name.clone()),
+    };
+    let next_text = next_alarm.map(|alarm| format!("Next: {} on {}", alarm.name, alarm.at.format("%a %H:%M")));
+
+    rsx! {
+        div { class: "modal modal-open",
+            div { class: "modal-backdrop", onclick: move |_| on_close.call(()) }
+            div { class: "modal-box max-w-lg max-h-[80vh] overflow-y-auto",
+                button {
+                    class: "btn btn-sm btn-circle btn-ghost absolute right-2 top-2",
+                    onclick: move |_| on_close.call(()),
+                    "✕"
+                }
+                h3 { class: "font-bold text-lg mb-4", "Alarms" }
+                if let Some(schedule) = editing.read().clone() {
+                    div { class: "flex flex-col gap-3",
+                        input {
+                            class: "input input-bordered input-sm",
+                            placeholder: "Name",
+                            value: "{schedule.name}",
+                            oninput: move |e| edit_schedule(editing, |s| s.name = e.value()),
+                        }
+                        input {
+                            r#type: "time",
+                            class: "input input-bordered input-sm",
+                            value: "{schedule.hour:02}:{schedule.minute:02}",
+                            onchange: move |e: Event<FormData>| {
+                                let value = e.value();
+                                if let Some((hour, minute)) = value.split_once(':') {
+                                    if let (Ok(hour), Ok(minute)) = (hour.parse::<u8>(), minute.parse::<u8>()) {
+                                        edit_schedule(editing, |s| {
+                                            s.hour = hour;
+                                            s.minute = minute;
+                                        });
+                                    }
+                                }
+                            },
+                        }
+                        div { class: "flex flex-wrap gap-1",
+                            for day in WEEKDAYS {
+                                button {
+                                    key: "{day}",
+                                    class: if schedule.fires_on(day) { "btn btn-xs btn-primary" } else { "btn btn-xs btn-ghost" },
+                                    onclick: move |_| edit_schedule(editing, |s| {
+                                        if s.days.is_empty() {
+                                            s.days = WEEKDAYS.to_vec();
+                                        }
+                                        if s.days.contains(&day) {
+                                            s.days.retain(|d| *d != day);
+                                        } else {
+                                            s.days.push(day);
+                                        }
+                                    }),
+                                    "{day}"
+                                }
+                            }
+                        }
+                        div { class: "flex gap-2",
+                            select {
+                                class: "select select-bordered select-sm",
+                                onchange: {
+                                    let first_station = stations.first().map(|s| s.url.clone()).unwrap_or_default();
+                                    let first_playlist = saved_playlists.first().map(|pl| pl.id.clone()).unwrap_or_default();
+                                    move |e: Event<FormData>| {
+                                        let action = if e.value() == "playlist" {
+                                            ScheduledAction::Playlist(first_playlist.clone())
+                                        } else {
+                                            ScheduledAction::Radio(first_station.clone())
+                                        };
+                                        edit_schedule(editing, |s| s.action = action);
+                                    }
+                                },
+                                option { value: "radio", selected: matches!(schedule.action, ScheduledAction::Radio(_)), "Radio" }
+                                option { value: "playlist", selected: matches!(schedule.action, ScheduledAction::Playlist(_)), "Playlist" }
+                            }
+                            if let ScheduledAction::Radio(url) = &schedule.action {
+                                select {
+                                    class: "select select-bordered select-sm flex-1 min-w-0",
+                                    onchange: move |e: Event<FormData>| {
+                                        edit_schedule(editing, |s| s.action = ScheduledAction::Radio(e.value()));
+                                    },
+                                    for station in stations.iter() {
+                                        option {
+                                            key: "{station.id}",
+                                            value: "{station.url}",
+                                            selected: &station.url == url,
+                                            "{station.name}"
+                                        }
+                                    }
+                                }
+                            }
+                            if let ScheduledAction::Playlist(id) = &schedule.action {
+                                select {
+                                    class: "select select-bordered select-sm flex-1 min-w-0",
+                                    onchange: move |e: Event<FormData>| {
+                                        edit_schedule(editing, |s| s.action = ScheduledAction::Playlist(e.value()));
+                                    },
+                                    for pl in saved_playlists.iter() {
+                                        option { key: "{pl.id}", value: "{pl.id}", selected: &pl.id == id, "{pl.name}" }
+                                    }
+                                }
+                            }
+                        }
+                        label { class: "flex items-center gap-2 text-sm",
+                            span { class: "w-24", "Volume" }
+                            input {
+                                r#type: "number",
+                                class: "input input-bordered input-sm w-24",
+                                min: 0,
+                                max: 255,
+                                placeholder: "Current",
+                                value: schedule.volume.map(|v| v.to_string()).unwrap_or_default(),
+                                oninput: move |e| {
+                                    let volume = e.value().parse().ok();
+                                    edit_schedule(editing, |s| s.volume = volume);
+                                },
+                            }
+                        }
+                        label { class: "flex items-center gap-2 text-sm",
+                            span { class: "w-24", "Fade in (s)" }
+                            input {
+                                r#type: "number",
+                                class: "input input-bordered input-sm w-24",
+                                min: 0,
+                                max: 3600,
+                                value: "{schedule.fade_in_secs}",
+                                oninput: move |e| {
+                                    let secs = e.value().parse().unwrap_or(0);
+                                    edit_schedule(editing, |s| s.fade_in_secs = secs);
+                                },
+                            }
+                        }
+                        div { class: "flex justify-end gap-2",
+                            button { class: "btn btn-ghost btn-sm", onclick: move |_| editing.set(None), "Cancel" }
+                            button {
+                                class: "btn btn-primary btn-sm",
+                                disabled: schedule.name.trim().is_empty(),
+                                onclick: move |_| {
+                                    let schedule = editing.peek().clone();
+                                    if let Some(schedule) = schedule {
+                                        ws_send(&ws, &UserCommand::Scheduler(SchedulerCommand::SaveSchedule(schedule)));
+                                        editing.set(None);
+                                    }
+                                },
+                                "Save"
+                            }
+                        }
+                    }
+                } else {
+                    if let Some(next_text) = next_text {
+                        div { class: "text-sm opacity-70 mb-3", "{next_text}" }
+                    }
+                    if schedules.is_empty() {
+                        div { class: "text-center py-8 text-base-content/50", "No alarms yet." }
+                    } else {
+                        ul { class: "menu bg-base-200 rounded-box mb-4",
+                            for schedule in schedules.iter() {
+                                li { key: "{schedule.id}",
+                                    div { class: "flex items-center gap-2",
+                                        a {
+                                            class: "flex-1 min-w-0",
+                                            onclick: {
+                                                let schedule = schedule.clone();
+                                                move |_| editing.set(Some(schedule.clone()))
+                                            },
+                                            div { class: "truncate font-medium",
+                                                "{schedule.hour:02}:{schedule.minute:02} · {schedule.name}"
+                                            }
+                                            div { class: "truncate text-xs opacity-60",
+                                                "{schedule_days(schedule)} · {target_name(&schedule.action)}"
+                                            }
+                                        }
+                                        input {
+                                            r#type: "checkbox",
+                                            class: "toggle toggle-sm toggle-primary",
+                                            checked: schedule.enabled,
+                                            onchange: {
+                                                let id = schedule.id.clone();
+                                                let enabled = !schedule.enabled;
+                                                move |_| ws_send(
+                                                    &ws,
+                                                    &UserCommand::Scheduler(SchedulerCommand::SetScheduleEnabled(id.clone(), enabled)),
+                                                )
+                                            },
+                                        }
+                                        button {
+                                            class: "btn btn-ghost btn-xs",
+                                            title: "Delete",
+                                            onclick: {
+                                                let id = schedule.id.clone();
+                                                move |_| ws_send(&ws, &UserCommand::Scheduler(SchedulerCommand::DeleteSchedule(id.clone())))
+                                            },
+                                            i { class: "material-icons text-sm", "delete" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    button {
+                        class: "btn btn-primary btn-sm w-full",
+                        onclick: {
+                            let schedule = new_schedule(&stations);
+                            move |_| editing.set(Some(schedule.clone()))
+                        },
+                        "New alarm"
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub fn format_time(seconds: u64) -> String {
     format!("{}:{:02}", seconds / 60, seconds % 60)
 }
@@ -6,7 +6,7 @@ use api_models::{
 use dioxus::prelude::*;
 use web_sys::WebSocket;
 
-use crate::{hooks::ws_send, state::AppState, UiState};
+use crate::{hooks::ws_send, state::AppState, DownloadMenu, UiState};
 
 #[component]
 pub fn QueuePage() -> Element {
@@ -16,6 +16,7 @@ pub fn QueuePage() -> Element {
     let current_song_id = state.current_song.read().as_ref().map(|s| s.file.clone());
 
     let queue = state.current_queue;
+    let auto_continue = state.auto_continue;
     let mut loading = use_signal(|| true);
     let mut search = use_signal(String::new);
     let mut dragged_file: Signal<Option<String>> = use_signal(|| None);
@@ -114,7 +115,46 @@ pub fn QueuePage() -> Element {
                         i { class: "material-icons text-base", "save" }
                         span { class: "hidden sm:inline text-xs", "Save" }
                     }
+                    button {
+                        class: if auto_continue() { "btn btn-sm btn-ghost text-primary" } else { "btn btn-sm btn-ghost" },
+                        title: "Auto-continue: append similar songs when the queue runs out",
+                        onclick: move |_| {
+                            ws_send(&ws, &UserCommand::Queue(QueueCommand::SetAutoContinue(!auto_continue())));
+                        },
+                        i { class: "material-icons text-base", "all_inclusive" }
+                        span { class: "hidden sm:inline text-xs", "Auto" }
+                    }
+                    button {
+                        class: "btn btn-sm btn-ghost",
+                        title: "Queue snapshots",
+                        onclick: move |_| ui.queue_snapshots_open.set(true),
+                        i { class: "material-icons text-base", "bookmarks" }
+                        span { class: "hidden sm:inline text-xs", "Snapshots" }
+                    }
+                    DownloadMenu { url: "/api/download/queue".to_string(), class: "btn btn-sm btn-ghost".to_string() }
                     div { class: "flex-1" }
+                    button {
+                        class: "btn btn-sm btn-ghost",
+                        title: "Undo last queue change",
+                        onclick: move |_| {
+                            ws_send(&ws, &UserCommand::Queue(QueueCommand::Undo));
+                            ws_send(&ws, &UserCommand::Queue(QueueCommand::QueryCurrentQueue(
+                                CurrentQueueQuery::WithSearchTerm(search(), 0)
+                            )));
+                        },
+                        i { class: "material-icons text-base", "undo" }
+                    }
+                    button {
+                        class: "btn btn-sm btn-ghost",
+                        title: "Redo",
+                        onclick: move |_| {
+                            ws_send(&ws, &UserCommand::Queue(QueueCommand::Redo));
+                            ws_send(&ws, &UserCommand::Queue(QueueCommand::QueryCurrentQueue(
+                                CurrentQueueQuery::WithSearchTerm(search(), 0)
+                            )));
+                        },
+                        i { class: "material-icons text-base", "redo" }
+                    }
                     button {
                         class: "btn btn-sm btn-ghost text-error",
                         title: "Clear queue",
@@ -1,6 +1,7 @@
 use api_models::{
-    common::{MetadataCommand, StorageCommand, SystemRequest, UserCommand, VolumeCrtlType},
-    settings::{DspFilter, FilterConfig, NetworkMountConfig, NetworkMountType, NormalizationSource, Settings},
+    auth::Role,
+    common::{MetadataCommand, QueueCommand, StorageCommand, SystemRequest, UserCommand, VolumeCrtlType},
+    settings::{AudioEncoding, DspFilter, FilterConfig, NetworkMountConfig, NetworkMountType, NormalizationSource, Settings},
 };
 use dioxus::prelude::*;
 use gloo_net::http::Request;
@@ -10,6 +11,7 @@ use wasm_bindgen::JsCast;
 use web_sys::WebSocket;
 
 use crate::dsp::get_dsp_presets;
+use crate::page::account::{current_role, refresh_auth_status, AccountSection, ProfileSection, UsersSection};
 use crate::{hooks::ws_send, state::AppState, ws_system};
 
 const API_SETTINGS_PATH: &str = "/api/settings";
@@ -25,6 +27,7 @@ enum ConfirmAction {
     RemoveMusicDirectory(usize),
     RemoveNetworkMount(String),
     ClearDspFilters,
+    WriteLoudnessTags,
 }
 
 #[derive(Debug, Clone, PartialEq, EnumIter)]
@@ -128,7 +131,9 @@ pub fn SettingsPage() -> Element {
     let mut pending_restart = use_signal(|| false);
 
     // Fetch settings on mount
+    let auth_status = state.auth_status;
     use_effect(move || {
+        let is_admin = current_role(auth_status) == Role::Admin;
         spawn(async move {
             if let Ok(resp) = Request::get(API_SETTINGS_PATH).send().await {
                 if let Ok(s) = resp.json::<Settings>().await {
@@ -136,9 +141,11 @@ pub fn SettingsPage() -> Element {
                 }
             }
             *loading.write() = false;
-            // Query mount/dir status
-            ws_send(&ws, &UserCommand::Storage(StorageCommand::QueryMountStatus));
-            ws_send(&ws, &UserCommand::Storage(StorageCommand::QueryMusicDirStatus));
+            // Query mount/dir status; storage is for admins only.
+            if is_admin {
+                ws_send(&ws, &UserCommand::Storage(StorageCommand::QueryMountStatus));
+                ws_send(&ws, &UserCommand::Storage(StorageCommand::QueryMusicDirStatus));
+            }
         });
     });
 
@@ -225,6 +232,9 @@ pub fn SettingsPage() -> Element {
                 settings.write().rs_player_settings.dsp_settings.filters.clear();
                 *dsp_dirty.write() = true;
             }
+            Some(ConfirmAction::WriteLoudnessTags) => {
+                ws_send(&ws, &UserCommand::Storage(StorageCommand::WriteLoudnessTags(false)));
+            }
             None => {}
         }
         *confirm.write() = None;
@@ -238,6 +248,31 @@ pub fn SettingsPage() -> Element {
         };
     }
 
+    let user = auth_status.read().as_ref().and_then(|s| s.user.clone());
+    // Everything but the look and the own account needs an admin.
+    if current_role(auth_status) < Role::Admin {
+        return rsx! {
+            div { class: "max-w-2xl mx-auto px-3 py-4 pb-20 space-y-3",
+                SettingsSection {
+                    title: "Appearance",
+                    icon: "palette",
+                    content: rsx! {
+                        AppearanceSection {}
+                    },
+                }
+                if let Some(user) = user {
+                    SettingsSection {
+                        title: "Account",
+                        icon: "person",
+                        content: rsx! {
+                            AccountSection { user }
+                        },
+                    }
+                }
+            }
+        };
+    }
+
     rsx! {
         div { class: "max-w-2xl mx-auto px-3 py-4 pb-20 space-y-3",
 
@@ -266,6 +301,17 @@ pub fn SettingsPage() -> Element {
                 },
             }
 
+            // ── Profile (with login, the account is the profile) ─────────────
+            if !auth_status.read().as_ref().is_some_and(|s| s.enabled) {
+                SettingsSection {
+                    title: "Profile",
+                    icon: "face",
+                    content: rsx! {
+                        ProfileSection {}
+                    },
+                }
+            }
+
             // ── Playback section ─────────────────────────────────────────────
             SettingsSection {
                 title: "Playback",
@@ -378,6 +424,63 @@ pub fn SettingsPage() -> Element {
                         }
                     }
 
+                    // Transcoding for browser playback — read per request, no restart needed.
+                    if settings.read().local_browser_playback {
+                        div { class: "form-control mb-3",
+                            label { class: "label",
+                                span { class: "label-text font-medium", "Transcode to" }
+                            }
+                            select {
+                                class: "select select-bordered select-sm w-full",
+                                onchange: move |e: Event<FormData>| {
+                                    if let Ok(v) = e.value().parse::<AudioEncoding>() {
+                                        settings.write().transcode_settings.encoding = v;
+                                        auto_save();
+                                    }
+                                },
+                                {
+                                    let current = settings.read().transcode_settings.encoding;
+                                    AudioEncoding::iter().map(move |enc| {
+                                        let label: &'static str = enc.into();
+                                        rsx! {
+                                            option { value: "{label}", selected: current == enc, "{label}" }
+                                        }
+                                    })
+                                }
+                            }
+                        }
+                        if settings.read().transcode_settings.encoding.is_lossy() {
+                            NumberInput {
+                                label: "Transcode bitrate (kbps)",
+                                value: settings.read().transcode_settings.bitrate_kbps.to_string(),
+                                min: "32",
+                                max: "320",
+                                onchange: move |v: String| {
+                                    if let Ok(n) = v.parse::<u32>() {
+                                        settings.write().transcode_settings.bitrate_kbps = n.clamp(32, 320);
+                                        auto_save();
+                                    }
+                                },
+                            }
+                        }
+                        NumberInput {
+                            label: "Bitrate limit outside the local network (kbps, 0 = none)",
+                            value: settings.read().transcode_settings.remote_max_bitrate_kbps.to_string(),
+                            min: "0",
+                            max: "320",
+                            onchange: move |v: String| {
+                                if let Ok(n) = v.parse::<u32>() {
+                                    settings.write().transcode_settings.remote_max_bitrate_kbps = n.min(320);
+                                    auto_save();
+                                }
+                            },
+                        }
+                        p { class: "text-xs opacity-60 mb-2",
+                            "Tracks the browser cannot play (APE, DSD, SACD ISO, WMA, ...) are transcoded on the fly. "
+                            "The web player asks for the best format the browser plays; other clients get this one."
+                        }
+                    }
+
                     // Auto-resume
                     ToggleRow {
                         label: "Auto-resume playback on startup",
@@ -388,6 +491,92 @@ pub fn SettingsPage() -> Element {
                             auto_save();
                         },
                     }
+                    // Auto-continue — applied live via the queue command, which also persists it.
+                    ToggleRow {
+                        label: "Auto-continue with similar songs when the queue ends",
+                        checked: settings.read().playback_queue_settings.auto_continue,
+                        onchange: move |_| {
+                            let v = !settings.read().playback_queue_settings.auto_continue;
+                            settings.write().playback_queue_settings.auto_continue = v;
+                            ws_send(&ws, &UserCommand::Queue(QueueCommand::SetAutoContinue(v)));
+                        },
+                    }
+                    // Resume points — the player reads these once at startup.
+                    ToggleRow {
+                        label: "Remember position in long-form audio",
+                        checked: settings.read().resume_settings.enabled,
+                        onchange: move |_| {
+                            let v = !settings.read().resume_settings.enabled;
+                            settings.write().resume_settings.enabled = v;
+                            auto_save_restart();
+                        },
+                    }
+                    if settings.read().resume_settings.enabled {
+                        NumberInput {
+                            label: "Minimum duration (minutes)",
+                            value: settings.read().resume_settings.min_duration_minutes.to_string(),
+                            min: "1",
+                            max: "1440",
+                            onchange: move |v: String| {
+                                if let Ok(n) = v.parse::<u32>() {
+                                    settings.write().resume_settings.min_duration_minutes = n;
+                                    auto_save_restart();
+                                }
+                            },
+                        }
+                        div { class: "form-control mb-2",
+                            label { class: "label py-0.5",
+                                span { class: "label-text text-sm", "Also for genres (comma separated)" }
+                            }
+                            input {
+                                class: "input input-sm input-bordered w-full",
+                                r#type: "text",
+                                placeholder: "Audiobook, Podcast",
+                                value: settings.read().resume_settings.genres.join(", "),
+                                onchange: move |e: Event<FormData>| {
+                                    settings.write().resume_settings.genres = split_list(&e.value());
+                                    auto_save_restart();
+                                },
+                            }
+                        }
+                        div { class: "form-control mb-2",
+                            label { class: "label py-0.5",
+                                span { class: "label-text text-sm", "Also for directories (comma separated)" }
+                            }
+                            input {
+                                class: "input input-sm input-bordered w-full",
+                                r#type: "text",
+                                placeholder: "/music/audiobooks",
+                                value: settings.read().resume_settings.directories.join(", "),
+                                onchange: move |e: Event<FormData>| {
+                                    settings.write().resume_settings.directories = split_list(&e.value());
+                                    auto_save_restart();
+                                },
+                            }
+                        }
+                    }
+                    // Sleep timer
+                    NumberInput {
+                        label: "Sleep timer fade-out (seconds)",
+                        value: settings.read().sleep_timer_settings.fade_seconds.to_string(),
+                        min: "0",
+                        max: "600",
+                        onchange: move |v: String| {
+                            if let Ok(n) = v.parse::<u32>() {
+                                settings.write().sleep_timer_settings.fade_seconds = n;
+                                auto_save_restart();
+                            }
+                        },
+                    }
+                    ToggleRow {
+                        label: "Restore volume after the sleep timer stops playback",
+                        checked: settings.read().sleep_timer_settings.restore_volume,
+                        onchange: move |_| {
+                            let v = !settings.read().sleep_timer_settings.restore_volume;
+                            settings.write().sleep_timer_settings.restore_volume = v;
+                            auto_save_restart();
+                        },
+                    }
 
                     // RSPlayer advanced (hidden when browser local playback is selected)
                     if !settings.read().local_browser_playback {
@@ -416,6 +605,18 @@ pub fn SettingsPage() -> Element {
                                 }
                             },
                         }
+                        NumberInput {
+                            label: "Radio pre-buffer (s)",
+                            value: settings.read().rs_player_settings.radio_prebuffer_secs.to_string(),
+                            min: "0",
+                            max: "30",
+                            onchange: move |v: String| {
+                                if let Ok(n) = v.parse::<u32>() {
+                                    settings.write().rs_player_settings.radio_prebuffer_secs = n;
+                                    auto_save_restart();
+                                }
+                            },
+                        }
                         NumberInput {
                             label: "Thread priority (1-99)",
                             value: settings.read().rs_player_settings.player_threads_priority.to_string(),
@@ -632,6 +833,7 @@ pub fn SettingsPage() -> Element {
                                             "FileTagsTrack" => NormalizationSource::FileTagsTrack,
                                             "FileTagsAlbum" => NormalizationSource::FileTagsAlbum,
                                             "Calculated" => NormalizationSource::Calculated,
+                                            "CalculatedAlbum" => NormalizationSource::CalculatedAlbum,
                                             _ => NormalizationSource::Auto,
                                         };
                                         settings.write().rs_player_settings.loudness_normalization_source = src;
@@ -648,6 +850,7 @@ pub fn SettingsPage() -> Element {
                                             ("FileTagsTrack", NormalizationSource::FileTagsTrack),
                                             ("FileTagsAlbum", NormalizationSource::FileTagsAlbum),
                                             ("Calculated", NormalizationSource::Calculated),
+                                            ("CalculatedAlbum", NormalizationSource::CalculatedAlbum),
                                         ]
                                             .into_iter()
                                             .map(move |(label, src)| {
@@ -658,6 +861,74 @@ pub fn SettingsPage() -> Element {
                                     }
                                 }
                             }
+                            NumberInput {
+                                label: "True peak ceiling (dBTP)",
+                                value: settings.read().rs_player_settings.loudness_normalization_true_peak_ceiling.to_string(),
+                                min: "-6",
+                                max: "0",
+                                onchange: move |v: String| {
+                                    if let Ok(n) = v.parse::<f64>() {
+                                        settings.write().rs_player_settings.loudness_normalization_true_peak_ceiling = n;
+                                        auto_save_restart();
+                                    }
+                                },
+                            }
+                            ToggleRow {
+                                label: "Limit peaks instead of lowering the gain (lookahead limiter)",
+                                checked: settings.read().rs_player_settings.loudness_normalization_limiter,
+                                onchange: move |_| {
+                                    let v = !settings.read().rs_player_settings.loudness_normalization_limiter;
+                                    settings.write().rs_player_settings.loudness_normalization_limiter = v;
+                                    auto_save_restart();
+                                },
+                            }
+                            div { class: "pt-3 border-t border-base-300",
+                                p { class: "text-sm font-medium", "ReplayGain tags" }
+                                p { class: "text-xs text-base-content/60",
+                                    "Write the measured loudness into FLAC, MP3 and M4A files (reference -18 LUFS)."
+                                }
+                                div { class: "flex gap-2 mt-2",
+                                    button {
+                                        class: "btn btn-sm",
+                                        onclick: move |_| {
+                                            ws_send(&ws, &UserCommand::Storage(StorageCommand::WriteLoudnessTags(true)));
+                                        },
+                                        "Dry run"
+                                    }
+                                    button {
+                                        class: "btn btn-sm btn-warning",
+                                        onclick: move |_| *confirm.write() = Some(ConfirmAction::WriteLoudnessTags),
+                                        "Write tags"
+                                    }
+                                }
+                                if let Some(report) = state.loudness_tag_report.read().clone() {
+                                    div { class: "mt-2 text-xs space-y-1",
+                                        p { class: "font-medium",
+                                            if report.dry_run {
+                                                "Dry run: {report.changed} files would be tagged"
+                                            } else {
+                                                "{report.changed} files tagged"
+                                            }
+                                        }
+                                        p { class: "text-base-content/60",
+                                            "{report.unchanged} up to date, {report.not_analysed} not analysed, {report.unsupported} unsupported, {report.read_only} read-only, {report.failed.len()} failed"
+                                        }
+                                        div { class: "max-h-48 overflow-y-auto font-mono",
+                                            for change in report.changes.iter() {
+                                                p { class: "truncate",
+                                                    "{change.file}: {change.track_gain} / {change.track_peak}"
+                                                    if let (Some(gain), Some(peak)) = (&change.album_gain, &change.album_peak) {
+                                                        ", album {gain} / {peak}"
+                                                    }
+                                                }
+                                            }
+                                            for (file, reason) in report.failed.iter() {
+                                                p { class: "truncate text-error", "{file}: {reason}" }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
                 },
@@ -729,6 +1000,90 @@ pub fn SettingsPage() -> Element {
                 },
             }
 
+            // ── Web radio section ─────────────────────────────────────────────
+            // Read per listener, so changes apply to the next connection without a restart.
+            SettingsSection {
+                title: "Web Radio",
+                icon: "radio",
+                content: rsx! {
+                    ToggleRow {
+                        label: "Stream the playing output over HTTP",
+                        checked: settings.read().stream_output_settings.enabled,
+                        onchange: move |_| {
+                            let v = !settings.read().stream_output_settings.enabled;
+                            settings.write().stream_output_settings.enabled = v;
+                            auto_save();
+                        },
+                    }
+                    if settings.read().stream_output_settings.enabled {
+                        div { class: "mt-3 space-y-3",
+                            div { class: "form-control",
+                                label { class: "label",
+                                    span { class: "label-text font-medium", "Encoding" }
+                                }
+                                select {
+                                    class: "select select-bordered select-sm w-full",
+                                    onchange: move |e: Event<FormData>| {
+                                        if let Ok(v) = e.value().parse::<AudioEncoding>() {
+                                            settings.write().stream_output_settings.encoding = v;
+                                            auto_save();
+                                        }
+                                    },
+                                    {
+                                        let current = settings.read().stream_output_settings.encoding;
+                                        AudioEncoding::iter().map(move |enc| {
+                                            let label: &'static str = enc.into();
+                                            rsx! {
+                                                option { value: "{label}", selected: current == enc, "{label}" }
+                                            }
+                                        })
+                                    }
+                                }
+                            }
+                            if settings.read().stream_output_settings.encoding.is_lossy() {
+                                NumberInput {
+                                    label: "Bitrate (kbps)",
+                                    value: settings.read().stream_output_settings.bitrate_kbps.to_string(),
+                                    min: "32",
+                                    max: "320",
+                                    onchange: move |v: String| {
+                                        if let Ok(n) = v.parse::<u32>() {
+                                            settings.write().stream_output_settings.bitrate_kbps = n.clamp(32, 320);
+                                            auto_save();
+                                        }
+                                    },
+                                }
+                            }
+                            NumberInput {
+                                label: "Max listeners",
+                                value: settings.read().stream_output_settings.max_listeners.to_string(),
+                                min: "1",
+                                max: "64",
+                                onchange: move |v: String| {
+                                    if let Ok(n) = v.parse::<u32>() {
+                                        settings.write().stream_output_settings.max_listeners = n.clamp(1, 64);
+                                        auto_save();
+                                    }
+                                },
+                            }
+                            {
+                                let origin = web_sys::window()
+                                    .and_then(|w| w.location().origin().ok())
+                                    .unwrap_or_default();
+                                rsx! {
+                                    p { class: "text-xs opacity-60",
+                                        "Open "
+                                        span { class: "font-mono select-all", "{origin}/stream" }
+                                        " in any internet radio app or media player. Listeners hear the output after the equalizer, "
+                                        "before volume control; the format takes effect when a listener reconnects."
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+
             // ── DSP section ───────────────────────────────────────────────────
             SettingsSection {
                 title: "DSP Equalizer",
@@ -942,6 +1297,66 @@ pub fn SettingsPage() -> Element {
             }
             } // desktop_mode gate
 
+            // ── Access control ────────────────────────────────────────────────
+            SettingsSection {
+                title: "Access control",
+                icon: "lock",
+                content: rsx! {
+                    ToggleRow {
+                        label: "Require login",
+                        checked: settings.read().auth_settings.enabled,
+                        onchange: move |_| {
+                            let v = !settings.read().auth_settings.enabled;
+                            settings.write().auth_settings.enabled = v;
+                            let s = settings.read().clone();
+                            spawn(async move {
+                                let _ = Request::post(API_SETTINGS_PATH).json(&s).expect("serialize settings").send().await;
+                                // Turning it on logs this browser out unless it has a session.
+                                refresh_auth_status(auth_status);
+                            });
+                        },
+                    }
+                    if settings.read().auth_settings.enabled
+                        && !auth_status.read().as_ref().is_some_and(|s| s.has_admin)
+                    {
+                        p { class: "text-xs text-warning mb-2",
+                            "Login is not required until an Admin user exists."
+                        }
+                    }
+                    p { class: "text-xs text-base-content/60 mb-2",
+                        "Guests browse and see what plays, listeners play music, admins change settings and manage users."
+                    }
+                    div { class: "form-control mb-3",
+                        label { class: "label",
+                            span { class: "label-text font-medium", "Allowed origins" }
+                        }
+                        input {
+                            class: "input input-sm input-bordered w-full",
+                            r#type: "text",
+                            placeholder: "https://music.example.com",
+                            value: settings.read().auth_settings.allowed_origins.join(", "),
+                            onchange: move |e: Event<FormData>| {
+                                settings.write().auth_settings.allowed_origins = split_list(&e.value());
+                                auto_save();
+                            },
+                        }
+                        p { class: "text-xs text-base-content/60 mt-1",
+                            "Other web pages allowed to use this player, comma separated. Its own address always is."
+                        }
+                    }
+                    UsersSection {}
+                },
+            }
+            if let Some(user) = user {
+                SettingsSection {
+                    title: "Account",
+                    icon: "person",
+                    content: rsx! {
+                        AccountSection { user }
+                    },
+                }
+            }
+
             // ── Restart pending banner ────────────────────────────────────────
             if pending_restart() {
                 div { class: "alert alert-warning shadow-sm",
@@ -1051,6 +1466,9 @@ pub fn SettingsPage() -> Element {
                             Some(ConfirmAction::RemoveMusicDirectory(_)) => "Remove this music directory?",
                             Some(ConfirmAction::RemoveNetworkMount(_)) => "Remove this network mount?",
                             Some(ConfirmAction::ClearDspFilters) => "Remove all DSP filters?",
+                            Some(ConfirmAction::WriteLoudnessTags) => {
+                                "Write ReplayGain tags into the music files? Files in read-only directories are left untouched."
+                            }
                             None => "",
                         }
                     }
@@ -1477,6 +1895,60 @@ fn MusicLibraryContent(
                 auto_save();
             },
         }
+        div { class: "form-control mb-2",
+            label { class: "label py-0.5",
+                span { class: "label-text text-sm", "Folder artwork file names, by priority (comma separated)" }
+            }
+            input {
+                class: "input input-sm input-bordered w-full",
+                r#type: "text",
+                placeholder: "cover, folder, front",
+                value: settings.read().metadata_settings.artwork_file_names.join(", "),
+                onchange: move |e: Event<FormData>| {
+                    settings.write().metadata_settings.artwork_file_names = split_list(&e.value());
+                    auto_save();
+                },
+            }
+        }
+        div { class: "form-control mb-2",
+            label { class: "label py-0.5",
+                span { class: "label-text text-sm", "Artist and genre separators (comma separated, applied on next scan)" }
+            }
+            input {
+                class: "input input-sm input-bordered w-full",
+                r#type: "text",
+                placeholder: "; , feat., /",
+                value: settings.read().metadata_settings.credit_separators.join(", "),
+                onchange: move |e: Event<FormData>| {
+                    settings.write().metadata_settings.credit_separators = split_list(&e.value());
+                    auto_save();
+                },
+            }
+        }
+        div { class: "form-control mb-2",
+            label { class: "label py-0.5",
+                span { class: "label-text text-sm", "Radio recordings directory (scanned with the music directories)" }
+            }
+            input {
+                class: "input input-sm input-bordered w-full",
+                r#type: "text",
+                placeholder: "/music/recordings",
+                value: settings.read().metadata_settings.recordings_directory.clone(),
+                onchange: move |e: Event<FormData>| {
+                    settings.write().metadata_settings.recordings_directory = e.value().trim().to_string();
+                    auto_save();
+                },
+            }
+        }
+        ToggleRow {
+            label: "Start a new recording file when the station's title changes",
+            checked: settings.read().metadata_settings.split_recordings_on_title,
+            onchange: move |_| {
+                let v = !settings.read().metadata_settings.split_recordings_on_title;
+                settings.write().metadata_settings.split_recordings_on_title = v;
+                auto_save();
+            },
+        }
 
         // Scan status message
         {
@@ -1549,6 +2021,15 @@ fn ToggleRow(label: &'static str, checked: bool, onchange: EventHandler<MouseEve
     }
 }
 
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
 #[component]
 fn NumberInput(label: &'static str, value: String, min: &'static str, max: &'static str, onchange: EventHandler<String>) -> Element {
     rsx! {
@@ -1,13 +1,14 @@
 use crate::vumeter::VisualizerType;
 use api_models::{
+    auth::AuthStatus,
     common::{MetadataLibraryItem, PlaybackMode, Volume},
-    player::Song,
-    playlist::{Album, PlaylistPage, Playlists},
-    settings::Settings,
+    player::{Bookmark, RadioStation, Song},
+    playlist::{Album, PlaylistPage, Playlists, QueueSnapshot},
+    settings::{Schedule, Settings},
     stat::LibraryStats,
     state::{
-        ExternalMount, MountStatus, MultiroomGroupState, MultiroomPeer, MusicDirStatus, PlayerInfo, PlayerState, SongProgress,
-        StateChangeEvent,
+        DuplicateReport, ExternalMount, LoudnessTagReport, MountStatus, MultiroomGroupState, MultiroomPeer, MusicDirStatus, NextAlarm,
+        PlayerInfo, PlayerState, SleepTimerState, SongProgress, StateChangeEvent,
     },
 };
 use dioxus::prelude::*;
@@ -21,11 +22,23 @@ pub struct AppState {
     pub current_song: Signal<Option<Song>>,
     pub progress: Signal<SongProgress>,
     pub playback_mode: Signal<PlaybackMode>,
+    /// Append similar songs when the queue runs out.
+    pub auto_continue: Signal<bool>,
+    /// Saved queue snapshots (names and counts only).
+    pub queue_snapshots: Signal<Vec<QueueSnapshot>>,
+    pub bookmarks: Signal<Vec<Bookmark>>,
+    pub sleep_timer: Signal<Option<SleepTimerState>>,
+    /// The file the playing radio stream is recorded to.
+    pub recording_file: Signal<Option<String>>,
+    /// Alarms and timed playback starts.
+    pub schedules: Signal<Vec<Schedule>>,
+    pub next_alarm: Signal<Option<NextAlarm>>,
     pub player_state: Signal<PlayerState>,
     pub current_queue: Signal<Option<PlaylistPage>>,
     /// Raw items returned by the last Metadata query (files/artists tree).
     pub metadata_local_items: Signal<Vec<MetadataLibraryItem>>,
     pub favorite_radio_stations: Signal<Vec<String>>,
+    pub radio_stations: Signal<Vec<RadioStation>>,
     pub playlists: Signal<Option<Playlists>>,
     pub library_stats: Signal<Option<LibraryStats>>,
     pub playlist_items: Signal<Vec<Song>>,
@@ -38,6 +51,10 @@ pub struct AppState {
     pub mount_statuses: Signal<Vec<MountStatus>>,
     pub music_dir_statuses: Signal<Vec<MusicDirStatus>>,
     pub external_mounts: Signal<Vec<ExternalMount>>,
+    /// Result of the last ReplayGain tag run (or dry run).
+    pub loudness_tag_report: Signal<Option<LoudnessTagReport>>,
+    /// Result of the last duplicate search.
+    pub duplicate_report: Signal<Option<DuplicateReport>>,
     // VU meter
     pub vu_left: Signal<u8>,
     pub vu_right: Signal<u8>,
@@ -56,6 +73,12 @@ pub struct AppState {
     /// Guard: true while the browser <audio> element is seeking to a new position.
     /// Suppresses stale timeupdate events that would overwrite the seek target.
     pub audio_seeking: Signal<bool>,
+    /// Where the transcoded stream the browser <audio> element plays starts,
+    /// in seconds; `None` while it plays the original file. Transcodes cannot
+    /// seek by byte range, so their times are relative to this.
+    pub audio_transcode_start: Signal<Option<f64>>,
+    /// A seek in a transcoded stream, which reloads it from that position.
+    pub audio_transcode_seek: Signal<Option<f64>>,
     /// Other rsplayer instances discovered on the LAN (multiroom).
     pub multiroom_peers: Signal<Vec<MultiroomPeer>>,
     /// This instance's multiroom role and group membership.
@@ -64,6 +87,8 @@ pub struct AppState {
     pub update_available: Signal<Option<String>>,
     /// Whether the user dismissed the update notification banner for the available version.
     pub update_banner_dismissed: Signal<bool>,
+    /// Who this browser is logged in as; `None` until the server answered.
+    pub auth_status: Signal<Option<AuthStatus>>,
 }
 
 impl AppState {
@@ -74,10 +99,18 @@ impl AppState {
             current_song: Signal::new(None),
             progress: Signal::new(SongProgress::default()),
             playback_mode: Signal::new(PlaybackMode::default()),
+            auto_continue: Signal::new(false),
+            queue_snapshots: Signal::new(Vec::new()),
+            bookmarks: Signal::new(Vec::new()),
+            sleep_timer: Signal::new(None),
+            recording_file: Signal::new(None),
+            schedules: Signal::new(Vec::new()),
+            next_alarm: Signal::new(None),
             player_state: Signal::new(PlayerState::STOPPED),
             current_queue: Signal::new(None),
             metadata_local_items: Signal::new(Vec::new()),
             favorite_radio_stations: Signal::new(Vec::new()),
+            radio_stations: Signal::new(Vec::new()),
             playlists: Signal::new(None),
             library_stats: Signal::new(None),
             playlist_items: Signal::new(Vec::new()),
@@ -90,6 +123,8 @@ impl AppState {
             mount_statuses: Signal::new(Vec::new()),
             music_dir_statuses: Signal::new(Vec::new()),
             external_mounts: Signal::new(Vec::new()),
+            loudness_tag_report: Signal::new(None),
+            duplicate_report: Signal::new(None),
             vu_left: Signal::new(0),
             vu_right: Signal::new(0),
             vu_meter_enabled: Signal::new(false),
@@ -99,11 +134,14 @@ impl AppState {
             lazy_genre_albums: Signal::new(HashMap::new()),
             lazy_decade_albums: Signal::new(HashMap::new()),
             audio_seeking: Signal::new(false),
+            audio_transcode_start: Signal::new(None),
+            audio_transcode_seek: Signal::new(None),
             show_bg_image: Signal::new(true),
             multiroom_peers: Signal::new(Vec::new()),
             multiroom_group: Signal::new(MultiroomGroupState::default()),
             update_available: Signal::new(None),
             update_banner_dismissed: Signal::new(false),
+            auth_status: Signal::new(None),
         }
     }
 
@@ -133,6 +171,29 @@ impl AppState {
             StateChangeEvent::PlaybackModeChangedEvent(mode) => {
                 *self.playback_mode.write() = mode;
             }
+            StateChangeEvent::AutoContinueChangedEvent(enabled) => {
+                *self.auto_continue.write() = enabled;
+            }
+            StateChangeEvent::QueueSnapshotsEvent(snapshots) => {
+                *self.queue_snapshots.write() = snapshots;
+            }
+            StateChangeEvent::BookmarksEvent(bookmarks) => {
+                *self.bookmarks.write() = bookmarks;
+            }
+            StateChangeEvent::SleepTimerEvent(timer) => {
+                *self.sleep_timer.write() = timer;
+            }
+            StateChangeEvent::RecordingEvent(file) => {
+                *self.recording_file.write() = file;
+            }
+            StateChangeEvent::SchedulesEvent(schedules, next) => {
+                // Keep the copy POSTed back with UI preferences current.
+                if let Some(settings) = self.global_settings.write().as_mut() {
+                    settings.schedules.clone_from(&schedules);
+                }
+                *self.schedules.write() = schedules;
+                *self.next_alarm.write() = next;
+            }
             StateChangeEvent::PlaybackStateEvent(ps) => {
                 let stopped = !matches!(ps, PlayerState::PLAYING);
                 *self.player_state.write() = ps;
@@ -156,6 +217,9 @@ impl AppState {
             StateChangeEvent::FavoriteRadioStations(stations) => {
                 *self.favorite_radio_stations.write() = stations;
             }
+            StateChangeEvent::RadioStationsEvent(stations) => {
+                *self.radio_stations.write() = stations;
+            }
             StateChangeEvent::PlaylistsEvent(playlists) => {
                 *self.playlists.write() = Some(playlists);
             }
@@ -174,6 +238,12 @@ impl AppState {
             StateChangeEvent::ExternalMountsEvent(mounts) => {
                 *self.external_mounts.write() = mounts;
             }
+            StateChangeEvent::LoudnessTagReportEvent(report) => {
+                *self.loudness_tag_report.write() = Some(report);
+            }
+            StateChangeEvent::DuplicateReportEvent(report) => {
+                *self.duplicate_report.write() = Some(report);
+            }
             StateChangeEvent::GenreAlbumsEvent(genre, albums) => {
                 self.lazy_genre_albums.write().insert(genre, albums);
             }